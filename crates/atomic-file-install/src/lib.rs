@@ -2,7 +2,10 @@
 //! can be either noclobber (fail if destination already exists) or
 //! replacing it atomically if it exists.
 
-use std::{fs, io, path::Path};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use reflink_copy::reflink_or_copy;
 use tempfile::{NamedTempFile, TempPath};
@@ -14,6 +17,102 @@ use std::os::unix::fs::symlink as symlink_file_inner;
 #[cfg(windows)]
 use std::os::windows::fs::symlink_file as symlink_file_inner;
 
+/// How files are installed to filesystems on which atomic rename or
+/// permission bits behave differently, e.g. exFAT, NTFS over SMB or NFS.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FsCompat {
+    /// Fall back to copying the file in place if an atomic strategy fails
+    /// because the filesystem does not support it, and ignore errors of
+    /// setting permissions for the same reason.
+    #[default]
+    Auto,
+    /// Always copy the file in place without attempting the atomic
+    /// strategies, and ignore errors of setting permissions.
+    Always,
+    /// Fail if the atomic strategies or setting permissions fail.
+    Never,
+}
+
+impl FsCompat {
+    /// Return true if `err` should be worked around instead of returned.
+    pub fn tolerates(self, err: &io::Error) -> bool {
+        match self {
+            FsCompat::Auto => is_unsupported_by_fs(err),
+            FsCompat::Always => true,
+            FsCompat::Never => false,
+        }
+    }
+}
+
+/// Return true if `err` means the filesystem does not support the
+/// operation, e.g. renaming across filesystems, hard links or permission bits.
+fn is_unsupported_by_fs(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    let codes = [
+        libc::EXDEV,
+        libc::EPERM,
+        libc::ENOTSUP,
+        libc::EOPNOTSUPP,
+        libc::ENOSYS,
+    ];
+    #[cfg(windows)]
+    let codes = [
+        windows::Win32::Foundation::ERROR_NOT_SAME_DEVICE.0 as i32,
+        windows::Win32::Foundation::ERROR_NOT_SUPPORTED.0 as i32,
+        windows::Win32::Foundation::ERROR_INVALID_FUNCTION.0 as i32,
+    ];
+    #[cfg(not(any(unix, windows)))]
+    let codes: [i32; 0] = [];
+
+    err.kind() == io::ErrorKind::Unsupported
+        || matches!(err.raw_os_error(), Some(code) if codes.contains(&code))
+}
+
+/// Set permissions of `file`, the error is ignored if `fs_compat`
+/// tolerates it, since some filesystems do not support permission bits.
+fn set_permissions(
+    file: &fs::File,
+    path: &Path,
+    permissions: fs::Permissions,
+    fs_compat: FsCompat,
+) -> io::Result<()> {
+    debug!(
+        "Setting permissions of '{}' to '{permissions:#?}'",
+        path.display()
+    );
+    match file.set_permissions(permissions) {
+        Err(err) if fs_compat.tolerates(&err) => {
+            warn!(
+                "Failed to set permissions of '{}': {err}, ignoring it",
+                path.display()
+            );
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// Copy `src` to `dst` in place, which is not atomic but works on
+/// filesystems not supporting renaming over or linking files.
+fn copy_in_place(src: &Path, dst: &Path, noclobber: bool, fs_compat: FsCompat) -> io::Result<()> {
+    debug!(
+        "Copying from '{}' to '{}' in place",
+        src.display(),
+        dst.display()
+    );
+
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::File::options()
+        .write(true)
+        .create(!noclobber)
+        .create_new(noclobber)
+        .truncate(true)
+        .open(dst)?;
+    io::copy(&mut reader, &mut writer)?;
+
+    set_permissions(&writer, dst, reader.metadata()?.permissions(), fs_compat)
+}
+
 fn parent(p: &Path) -> io::Result<&Path> {
     p.parent().ok_or_else(|| {
         io::Error::new(
@@ -23,7 +122,49 @@ fn parent(p: &Path) -> io::Result<&Path> {
     })
 }
 
-fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
+/// Maximum number of symlinks followed by [`resolve_symlink`], same as the
+/// limit of Linux.
+const MAX_SYMLINK_DEPTH: u8 = 40;
+
+/// Return the path `path` ultimately points to, following the symlink at
+/// `path` and the symlink it points to and so on.
+///
+/// Relative symlinks are resolved against the directory of the link, the
+/// symlinks in the parent directories are left as is.
+/// The target of a dangling symlink is returned as is.
+pub fn resolve_symlink(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+
+    // The path reached after following the last symlink allowed is checked
+    // too.
+    for depth in 0..=MAX_SYMLINK_DEPTH {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if depth == MAX_SYMLINK_DEPTH {
+                    break;
+                }
+
+                let target = fs::read_link(&path)?;
+                debug!(
+                    "Following symlink '{}' to '{}'",
+                    path.display(),
+                    target.display()
+                );
+                path = parent(&path)?.join(target);
+            }
+            Ok(_) => return Ok(path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(path),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!("Too many levels of symlinks at `{}`", path.display()),
+    ))
+}
+
+fn copy_to_tempfile(src: &Path, dst: &Path, fs_compat: FsCompat) -> io::Result<NamedTempFile> {
     let parent = parent(dst)?;
     debug!("Creating named tempfile at '{}'", parent.display());
     let tempfile = NamedTempFile::new_in(parent)?;
@@ -43,11 +184,7 @@ fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
     debug!("Retrieving permissions of '{}'", src.display());
     let permissions = src.metadata()?.permissions();
 
-    debug!(
-        "Setting permissions of '{}' to '{permissions:#?}'",
-        tempfile.path().display()
-    );
-    tempfile.as_file().set_permissions(permissions)?;
+    set_permissions(tempfile.as_file(), tempfile.path(), permissions, fs_compat)?;
 
     Ok(tempfile)
 }
@@ -55,29 +192,50 @@ fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
 /// Install a file, this fails if the `dst` already exists.
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
-pub fn atomic_install_noclobber(src: &Path, dst: &Path) -> io::Result<()> {
+pub fn atomic_install_noclobber(src: &Path, dst: &Path, fs_compat: FsCompat) -> io::Result<()> {
+    if fs_compat == FsCompat::Always {
+        return copy_in_place(src, dst, true, fs_compat);
+    }
+
     debug!(
         "Attempting to rename from '{}' to '{}'.",
         src.display(),
         dst.display()
     );
 
-    let tempfile = copy_to_tempfile(src, dst)?;
+    let tempfile = copy_to_tempfile(src, dst, fs_compat)?;
 
     debug!(
         "Persisting '{}' to '{}', fail if dst already exists",
         tempfile.path().display(),
         dst.display()
     );
-    tempfile.persist_noclobber(dst)?;
-
-    Ok(())
+    match tempfile.persist_noclobber(dst) {
+        Ok(_) => Ok(()),
+        // Persisting without clobbering uses hard links on unix, which are
+        // not supported by some filesystems.
+        Err(err) if fs_compat.tolerates(&err.error) => {
+            warn!(
+                "Failed to persist '{}' to '{}': {}, fallback to copying in place",
+                err.file.path().display(),
+                dst.display(),
+                err.error
+            );
+            drop(err.file);
+            copy_in_place(src, dst, true, fs_compat)
+        }
+        Err(err) => Err(err.into()),
+    }
 }
 
 /// Atomically install a file, this atomically replace `dst` if it exists.
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
-pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
+pub fn atomic_install(src: &Path, dst: &Path, fs_compat: FsCompat) -> io::Result<()> {
+    if fs_compat == FsCompat::Always {
+        return copy_in_place(src, dst, false, fs_compat);
+    }
+
     debug!(
         "Attempting to atomically rename from '{}' to '{}'",
         src.display(),
@@ -104,7 +262,15 @@ pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
         // Fallback to creating NamedTempFile on the parent dir of
         // dst.
 
-        persist(copy_to_tempfile(src, dst)?.into_temp_path(), dst)?;
+        match copy_to_tempfile(src, dst, fs_compat)
+            .and_then(|tempfile| persist(tempfile.into_temp_path(), dst))
+        {
+            Err(err) if fs_compat.tolerates(&err) => {
+                warn!("Failed to install using tempfile plus rename: {err}, fallback to copying in place");
+                copy_in_place(src, dst, false, fs_compat)?;
+            }
+            res => res?,
+        }
     } else {
         debug!("Attempting at atomically succeeded.");
     }
@@ -112,18 +278,41 @@ pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
+/// Atomically install a file at the path `dst` points to if `dst` is a
+/// symlink, e.g. one managed by a dotfile manager, so that the symlink is
+/// kept and the real file next to its target is replaced.
+///
+/// Otherwise, this is the same as [`atomic_install`].
+///
+/// This is a blocking function, must be called in `block_in_place` mode.
+pub fn atomic_install_through_symlink(
+    src: &Path,
+    dst: &Path,
+    fs_compat: FsCompat,
+) -> io::Result<()> {
+    atomic_install(src, &resolve_symlink(dst)?, fs_compat)
+}
+
 /// Create a symlink at `link` to `dest`, this fails if the `link`
 /// already exists.
 ///
+/// On Windows, `dest` is copied to `link` with `fs_compat` if creating the
+/// symlink fails.
+///
 /// This is a blocking function, must be called in `block_in_place` mode.
-pub fn atomic_symlink_file_noclobber(dest: &Path, link: &Path) -> io::Result<()> {
+#[cfg_attr(not(windows), allow(unused_variables))]
+pub fn atomic_symlink_file_noclobber(
+    dest: &Path,
+    link: &Path,
+    fs_compat: FsCompat,
+) -> io::Result<()> {
     match symlink_file_inner(dest, link) {
         Ok(_) => Ok(()),
 
         #[cfg(windows)]
         // Symlinks on Windows are disabled in some editions, so creating one is unreliable.
         // Fallback to copy if it fails.
-        Err(_) => atomic_install_noclobber(dest, link),
+        Err(_) => atomic_install_noclobber(dest, link, fs_compat),
 
         #[cfg(not(windows))]
         Err(err) => Err(err),
@@ -133,8 +322,12 @@ pub fn atomic_symlink_file_noclobber(dest: &Path, link: &Path) -> io::Result<()>
 /// Atomically create a symlink at `link` to `dest`, this atomically replace
 /// `link` if it already exists.
 ///
+/// On Windows, `dest` is copied to `link` with `fs_compat` if creating the
+/// symlink fails.
+///
 /// This is a blocking function, must be called in `block_in_place` mode.
-pub fn atomic_symlink_file(dest: &Path, link: &Path) -> io::Result<()> {
+#[cfg_attr(not(windows), allow(unused_variables))]
+pub fn atomic_symlink_file(dest: &Path, link: &Path, fs_compat: FsCompat) -> io::Result<()> {
     let parent = parent(link)?;
 
     debug!("Creating tempPath at '{}'", parent.display());
@@ -155,7 +348,7 @@ pub fn atomic_symlink_file(dest: &Path, link: &Path) -> io::Result<()> {
         #[cfg(windows)]
         // Symlinks on Windows are disabled in some editions, so creating one is unreliable.
         // Fallback to copy if it fails.
-        Err(_) => atomic_install(dest, link),
+        Err(_) => atomic_install(dest, link, fs_compat),
 
         #[cfg(not(windows))]
         Err(err) => Err(err),
@@ -183,6 +376,109 @@ fn persist(temp_path: TempPath, to: &Path) -> io::Result<()> {
     }
 }
 
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    fn install(src_dir: &Path, dst: &Path, content: &str) {
+        let src = src_dir.join("src");
+        fs::write(&src, content).unwrap();
+        atomic_install_through_symlink(&src, dst, FsCompat::Never).unwrap();
+    }
+
+    #[test]
+    fn test_relative_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        fs::write(dir.join("dotfiles/tool"), "old").unwrap();
+        symlink("../dotfiles/tool", dir.join("bin/tool")).unwrap();
+
+        assert_eq!(
+            resolve_symlink(&dir.join("bin/tool")).unwrap(),
+            dir.join("bin/../dotfiles/tool")
+        );
+
+        install(dir, &dir.join("bin/tool"), "new");
+        assert_eq!(
+            fs::read_to_string(dir.join("dotfiles/tool")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_link(dir.join("bin/tool")).unwrap(),
+            Path::new("../dotfiles/tool")
+        );
+    }
+
+    #[test]
+    fn test_symlink_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join("c"), "old").unwrap();
+        symlink("c", dir.join("b")).unwrap();
+        symlink(dir.join("b"), dir.join("a")).unwrap();
+
+        assert_eq!(resolve_symlink(&dir.join("a")).unwrap(), dir.join("c"));
+
+        install(dir, &dir.join("a"), "new");
+        assert_eq!(fs::read_to_string(dir.join("c")).unwrap(), "new");
+        assert!(fs::symlink_metadata(dir.join("a"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::symlink_metadata(dir.join("b"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn test_dangling_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        symlink("missing", dir.join("tool")).unwrap();
+
+        assert_eq!(
+            resolve_symlink(&dir.join("tool")).unwrap(),
+            dir.join("missing")
+        );
+
+        install(dir, &dir.join("tool"), "new");
+        assert_eq!(fs::read_to_string(dir.join("missing")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dir.join("tool")).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_symlink_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        // A chain of `MAX_SYMLINK_DEPTH` links is followed, but not one more.
+        fs::write(dir.join("0"), "").unwrap();
+        for i in 1..=MAX_SYMLINK_DEPTH + 1 {
+            symlink((i - 1).to_string(), dir.join(i.to_string())).unwrap();
+        }
+        assert_eq!(
+            resolve_symlink(&dir.join(MAX_SYMLINK_DEPTH.to_string())).unwrap(),
+            dir.join("0")
+        );
+        resolve_symlink(&dir.join((MAX_SYMLINK_DEPTH + 1).to_string())).unwrap_err();
+
+        symlink("loop-b", dir.join("loop-a")).unwrap();
+        symlink("loop-a", dir.join("loop-b")).unwrap();
+
+        let err = resolve_symlink(&dir.join("loop-a")).unwrap_err();
+        assert!(err.to_string().contains("Too many levels of symlinks"));
+        let src = dir.join("src");
+        fs::write(&src, "new").unwrap();
+        atomic_install_through_symlink(&src, &dir.join("loop-a"), FsCompat::Never).unwrap_err();
+        assert_eq!(fs::read_to_string(&src).unwrap(), "new");
+    }
+}
+
 #[cfg(windows)]
 mod win {
     use std::{os::windows::ffi::OsStrExt, path::Path};
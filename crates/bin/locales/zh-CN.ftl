@@ -0,0 +1,6 @@
+confirm-prompt = 是否继续？[yes]/no
+confirm-timeout-continue = { $secs } 秒内未收到回答，继续安装
+confirm-timeout-abort = { $secs } 秒内未收到回答，中止安装
+installation-cancelled = 安装已取消
+fatal-error = 致命错误
+done = 完成，用时 { $spent }
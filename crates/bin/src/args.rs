@@ -1,26 +1,38 @@
 use std::{
+    collections::BTreeMap,
     env,
     ffi::OsString,
     fmt, mem,
     num::{NonZeroU16, NonZeroU64, ParseIntError},
     path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 
 use binstalk::{
     helpers::remote,
     manifests::cargo_toml_binstall::PkgFmt,
-    ops::resolve::{CrateName, VersionReqExt},
+    ops::{
+        self,
+        resolve::{CrateName, VersionReqExt},
+    },
     registry::Registry,
 };
-use binstalk_manifests::cargo_toml_binstall::{PkgOverride, Strategy};
-use clap::{builder::PossibleValue, error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use binstalk_manifests::{
+    cargo_toml_binstall::{BinDir, PkgOverride, Strategy},
+    tools_manifest::ToolsManifest,
+};
+use clap::{
+    builder::PossibleValue, error::ErrorKind, CommandFactory, FromArgMatches, Parser, ValueEnum,
+};
 use compact_str::CompactString;
 use log::LevelFilter;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use strum::EnumCount;
 use zeroize::Zeroizing;
 
+use crate::theme::{ColorChoice, ThemeName};
+
 #[derive(Debug, Parser)]
 #[clap(
     version,
@@ -45,23 +57,39 @@ pub struct Args {
     /// Each value is either a crate name alone, or a crate name followed by @ and the version to
     /// install. The version syntax is as with the --version option.
     ///
+    /// A value may also contain --version and --targets options applying to this package only,
+    /// e.g. `'ripgrep@14 --targets x86_64-unknown-linux-musl'`.
+    ///
     /// When multiple names are provided, the --version option and override option
     /// `--manifest-path` and `--git` are unavailable due to ambiguity.
     ///
     /// If duplicate names are provided, the last one (and their version requirement)
     /// is kept.
+    ///
+    /// Crates named after a subcommand, e.g. `which`, are installed by passing them
+    /// after `--`, e.g. `cargo binstall -- which`.
     #[clap(
         help_heading = "Package selection",
         value_name = "crate[@version]",
-        required_unless_present_any = ["version", "self_install", "help"],
+        value_parser = parse_crate_name,
+        required_unless_present_any = ["version", "self_install", "help", "watch", "schedule", "tools_manifest", "which", "key_add", "key_list", "key_remove"],
     )]
     pub(crate) crate_names: Vec<CrateName>,
 
+    /// Options given along with the packages, by crate name.
+    #[clap(skip)]
+    pub(crate) crate_overrides: BTreeMap<CompactString, CrateOverrides>,
+
     /// Package version to install.
     ///
     /// Takes either an exact semver version or a semver version requirement expression, which will
     /// be resolved to the highest matching version available.
     ///
+    /// The requirement follows the syntax of Cargo.toml dependencies, e.g. `>=1.2, <1.5`, `1.*`
+    /// or `~1.2`, and build metadata is ignored. As in `cargo install`, a full version such as
+    /// `1.2.3` is exact, while other bare versions are caret requirements, e.g. `1.2` matches
+    /// `1.9.0`, unless --exact is used.
+    ///
     /// Cannot be used when multiple packages are installed at once, use the attached version
     /// syntax in that case.
     #[clap(
@@ -72,6 +100,32 @@ pub struct Args {
     )]
     pub(crate) version_req: Option<VersionReq>,
 
+    /// Treat every bare version in version requirements as exact.
+    ///
+    /// With this flag, `1.2` is treated as `=1.2` and matches `1.2.*` only, instead of `^1.2`.
+    #[clap(help_heading = "Package selection", long)]
+    pub(crate) exact: bool,
+
+    /// Install the tools listed in a tools manifest instead.
+    ///
+    /// Each tool is a `[tools.<crate>]` table, taking an optional `version` requirement and an
+    /// optional `requires` list of the tools it depends on, e.g.
+    /// `requires = ["wasm-tools"]`.
+    ///
+    /// Tools are installed after the tools they require. With --continue-on-failure, tools
+    /// requiring a tool which failed to install are not installed either.
+    #[clap(
+        help_heading = "Package selection",
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["crate_names", "version_req"],
+    )]
+    pub(crate) tools_manifest: Option<PathBuf>,
+
+    /// Tools loaded from --tools-manifest.
+    #[clap(skip)]
+    pub(crate) tools: Option<ToolsManifest>,
+
     /// Override binary target set.
     ///
     /// Binstall is able to look for binaries for several targets, installing the first one it finds
@@ -93,6 +147,14 @@ pub struct Args {
     )]
     pub(crate) targets: Option<Vec<String>>,
 
+    /// Detect the targets of the current platform again.
+    ///
+    /// The targets detected are cached in `$CARGO_HOME/binstall/detected-targets`, and detected
+    /// again automatically once the OS, kernel or libc changes. This forces detecting them again
+    /// and updates the cache, e.g. after installing a different libc.
+    #[clap(help_heading = "Package selection", long)]
+    pub(crate) redetect_target: bool,
+
     /// Override Cargo.toml package manifest path.
     ///
     /// This skips searching crates.io for a manifest and uses the specified path directly, useful
@@ -140,6 +202,10 @@ pub struct Args {
     ///
     /// - tzstd: Download format is TAR + Zstd
     ///
+    /// - tbr: Download format is TAR + Brotli
+    ///
+    /// - tlz: Download format is TAR + Lzip
+    ///
     /// - zip: Download format is Zip
     ///
     /// - bin: Download format is raw / binary
@@ -152,6 +218,15 @@ pub struct Args {
     #[clap(help_heading = "Overrides", long, value_name = "TEMPLATE")]
     pub(crate) pkg_url: Option<String>,
 
+    /// Number of parts the package is split into
+    ///
+    /// The parts are downloaded from `{pkg-url}.001`, `{pkg-url}.002`, ...
+    /// and concatenated before extraction.
+    ///
+    /// Overrides the Cargo.toml package manifest pkg-parts.
+    #[clap(help_heading = "Overrides", long, value_name = "N")]
+    pub(crate) pkg_parts: Option<u16>,
+
     /// Override the rate limit duration.
     ///
     /// By default, cargo-binstall allows one request per 10 ms.
@@ -180,7 +255,12 @@ pub struct Args {
     /// `disabled-strategies` in `package.metadata` in the cargo manifest
     /// of the installed packages.
     ///
-    /// Default value is "crate-meta-data,quick-install,compile".
+    /// Default value is "crate-meta-data,azure-devops,quick-install,compile",
+    /// in which case the strategy which last fetched a crate is tried first
+    /// for it, as recorded locally in `$CARGO_HOME/binstall/strategy-stats.json`.
+    ///
+    /// The strategies "homebrew", for bottles of Homebrew formulae, and "wapm",
+    /// for packages of the Wasmer registry, are only used if specified here.
     #[clap(
         help_heading = "Overrides",
         long,
@@ -205,6 +285,60 @@ pub struct Args {
     )]
     pub(crate) disable_strategies: Vec<StrategyWrapped>,
 
+    /// Toolchain used to build crates from source when falling back to `cargo install`,
+    /// by running `cargo +<TOOLCHAIN> install`.
+    ///
+    /// If not specified, the toolchain pinned by the `rust-toolchain.toml` (or
+    /// `rust-toolchain`) found in the current directory or its ancestors is used.
+    ///
+    /// Requires the `cargo` in `PATH` to be the rustup proxy.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        env = "BINSTALL_COMPILE_TOOLCHAIN",
+        value_name = "TOOLCHAIN"
+    )]
+    pub(crate) compile_toolchain: Option<CompactString>,
+
+    /// Cross compile with the tool specified when falling back to `cargo install`
+    /// for a target other than the host, so that crates without pre-built binaries
+    /// for `--targets` can still be installed.
+    ///
+    /// `cargo zigbuild install` or `cross install` is run instead of `cargo install`,
+    /// which requires `cargo-zigbuild` or `cross` to be installed.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        env = "BINSTALL_CROSS_COMPILE_WITH",
+        value_name = "TOOL"
+    )]
+    pub(crate) cross_compile_with: Option<CrossCompiler>,
+
+    /// Refuse to fall back to `cargo install` for crates with more direct
+    /// dependencies than this.
+    ///
+    /// Useful with `--no-confirm`, since the estimated cost of building the
+    /// crate is otherwise only shown in the confirmation prompt.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        env = "BINSTALL_MAX_COMPILE_DEPS",
+        value_name = "COUNT"
+    )]
+    pub(crate) max_compile_deps: Option<usize>,
+
+    /// Refuse to fall back to `cargo install` for crates that are heavy to build,
+    /// i.e. have more than 30 direct dependencies.
+    ///
+    /// This is a shorthand for `--max-compile-deps 30`.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        conflicts_with("max_compile_deps"),
+        env = "BINSTALL_NO_HEAVY_COMPILE"
+    )]
+    pub(crate) no_heavy_compile: bool,
+
     /// If `--github-token` or environment variable `GITHUB_TOKEN`/`GH_TOKEN`
     /// is not specified, then cargo-binstall will try to extract github token from
     /// `$HOME/.git-credentials` or `$HOME/.config/gh/hosts.yml` by default.
@@ -217,6 +351,19 @@ pub struct Args {
     )]
     pub(crate) no_discover_github_token: bool,
 
+    /// Unless this option is specified, cargo-binstall will authenticate to
+    /// Google Cloud Storage and Azure Blob Storage, e.g. for `gs://` and `az://`
+    /// pkg-url, with the token from `gcloud auth application-default
+    /// print-access-token` and `az account get-access-token`.
+    ///
+    /// The token is only retrieved on the first request to the storage.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        env = "BINSTALL_NO_DISCOVER_CLOUD_CREDENTIALS"
+    )]
+    pub(crate) no_discover_cloud_credentials: bool,
+
     /// Maximum time each resolution (one for each possible target and each strategy), in seconds.
     #[clap(
         help_heading = "Overrides",
@@ -227,16 +374,76 @@ pub struct Args {
     )]
     pub(crate) maximum_resolution_timeout: NonZeroU16,
 
+    /// Maximum time to resolve, download and extract each crate, in seconds.
+    ///
+    /// A crate taking longer is cancelled and reported as failed, with its
+    /// partially downloaded files removed, so that one unresponsive host
+    /// does not stall the installation of the other crates.
+    #[clap(
+        help_heading = "Overrides",
+        long,
+        env = "BINSTALL_TIMEOUT",
+        value_name = "TIMEOUT"
+    )]
+    pub(crate) timeout: Option<NonZeroU64>,
+
     /// This flag is now enabled by default thus a no-op.
     ///
     /// By default, Binstall will install a binary as-is in the install path.
     #[clap(help_heading = "Options", long, default_value_t = true)]
     pub(crate) no_symlinks: bool,
 
+    /// If a binary in the install path is a symlink, e.g. one managed by a
+    /// dotfile manager, replace the file it points to instead of the symlink.
+    ///
+    /// The symlink is kept and the new binary is installed next to its target.
+    #[clap(help_heading = "Options", long)]
+    pub(crate) install_through_symlinks: bool,
+
+    /// Compatibility with install paths on filesystems such as exFAT, NTFS
+    /// over SMB or NFS, on which atomic rename or permission bits behave
+    /// differently.
+    ///
+    /// With `auto`, binaries are copied in place if the filesystem does not
+    /// support installing them atomically, and failures to set permissions
+    /// are ignored if it does not support permission bits.
+    /// `always` copies them in place directly and `never` fails instead.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "BINSTALL_FS_COMPAT",
+        default_value = "auto",
+        value_name = "MODE"
+    )]
+    pub(crate) fs_compat: FsCompat,
+
     /// Dry run, fetch and show changes without installing binaries.
     #[clap(help_heading = "Options", long)]
     pub(crate) dry_run: bool,
 
+    /// Download, verify and extract the binary of the crate to PATH instead
+    /// of installing it, or write it to stdout if PATH is `-`.
+    ///
+    /// PATH is a directory if the crate has several binaries. The installed
+    /// crates are neither looked up nor updated, the crate is never compiled
+    /// from source and there is no confirmation prompt.
+    ///
+    /// Logs are written to stderr with `--extract-to -`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "dry_run",
+            "tools_manifest",
+            "watch",
+            "check_self",
+            "diff",
+            "bench_resolve",
+        ]
+    )]
+    pub(crate) extract_to: Option<PathBuf>,
+
     /// Disable interactive mode / confirmation prompts.
     #[clap(
         help_heading = "Options",
@@ -246,6 +453,36 @@ pub struct Args {
     )]
     pub(crate) no_confirm: bool,
 
+    /// Answer the confirmation prompt automatically if it is not answered in time.
+    ///
+    /// Takes the form `SECS[=yes|no]`, e.g. `--confirm-timeout 30=yes` to proceed with the
+    /// installation after 30 seconds without an answer.
+    /// The answer defaults to `no`, which aborts the installation.
+    ///
+    /// This is useful to avoid hanging forever in unattended sessions that accidentally run
+    /// without `--no-confirm`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_name = "SECS[=yes|no]",
+        env = "BINSTALL_CONFIRM_TIMEOUT"
+    )]
+    pub(crate) confirm_timeout: Option<ConfirmTimeout>,
+
+    /// Send a desktop notification when the installation finishes, if it has run
+    /// for at least SECS seconds.
+    ///
+    /// Handy for long installations kicked off in the background, e.g. when
+    /// falling back to `cargo-install`.
+    #[cfg(feature = "notify")]
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_name = "SECS",
+        env = "BINSTALL_NOTIFY_AFTER"
+    )]
+    pub(crate) notify_after: Option<u64>,
+
     /// Do not cleanup temporary files.
     #[clap(help_heading = "Options", long)]
     pub(crate) no_cleanup: bool,
@@ -254,6 +491,23 @@ pub struct Args {
     #[clap(help_heading = "Options", long)]
     pub(crate) continue_on_failure: bool,
 
+    /// Do not print anything for crates already installed at the requested
+    /// version, only for the crates installed or upgraded.
+    ///
+    /// Keeps the logs of syncing many tools, e.g. in CI, readable.
+    #[clap(help_heading = "Options", long, env = "BINSTALL_QUIET_UNCHANGED")]
+    pub(crate) quiet_unchanged: bool,
+
+    /// Install binaries even if they are built for an architecture other than the target.
+    ///
+    /// By default, binstall inspects the header of each binary extracted and rejects
+    /// the pre-built package if it does not match the target, e.g. an x86_64 binary
+    /// shipped for aarch64, falling back to the next strategy instead.
+    ///
+    /// With this flag, binstall only prints a warning.
+    #[clap(help_heading = "Options", long, env = "BINSTALL_ALLOW_TARGET_MISMATCH")]
+    pub(crate) allow_target_mismatch: bool,
+
     /// By default, binstall keeps track of the installed packages with metadata files
     /// stored in the installation root directory.
     ///
@@ -277,6 +531,14 @@ pub struct Args {
     #[clap(help_heading = "Options", long, env = "BINSTALL_DISABLE_TELEMETRY")]
     pub(crate) disable_telemetry: bool,
 
+    /// Reduce the memory used, at the expense of speed, e.g. on devices
+    /// with little RAM.
+    ///
+    /// Packages are downloaded one crate at a time, the files of tarballs
+    /// are written one at a time, and less of the download is buffered.
+    #[clap(help_heading = "Options", long, env = "BINSTALL_LOW_MEMORY")]
+    pub(crate) low_memory: bool,
+
     /// Install binaries in a custom location.
     ///
     /// By default, binaries are installed to the global location `$CARGO_HOME/bin`, and global
@@ -340,6 +602,15 @@ pub struct Args {
     #[clap(help_heading = "Options", long)]
     pub(crate) force: bool,
 
+    /// Allow installing a version older than the one already installed.
+    ///
+    /// By default, binstall refuses to downgrade a crate, e.g. when a newer
+    /// version has been installed by `cargo install`.
+    ///
+    /// `--force` also allows downgrading.
+    #[clap(help_heading = "Options", long)]
+    pub(crate) allow_downgrade: bool,
+
     /// Require a minimum TLS version from remote endpoints.
     ///
     /// The default is not to require any minimum TLS version, and use the negotiated highest
@@ -347,6 +618,21 @@ pub struct Args {
     #[clap(help_heading = "Options", long, value_enum, value_name = "VERSION")]
     pub(crate) min_tls_version: Option<TLSVersion>,
 
+    /// Root certificates to trust for https connections.
+    ///
+    /// `webpki` only trusts the Mozilla root certificates bundled in binstall,
+    /// `native` only trusts the ones of the system, e.g. corporate root certificates.
+    ///
+    /// The default is to trust both.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_enum,
+        env = "BINSTALL_TLS_ROOTS",
+        value_name = "ROOTS"
+    )]
+    pub(crate) tls_roots: Option<TLSRoots>,
+
     /// Specify the root certificates to use for https connnections,
     /// in addition to default system-wide ones.
     #[clap(
@@ -357,6 +643,27 @@ pub struct Args {
     )]
     pub(crate) root_certificates: Vec<PathBuf>,
 
+    /// When to use colors in the output.
+    ///
+    /// `auto` uses colors only if stdout supports it and the `NO_COLOR` environment
+    /// variable is not set.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_name = "WHEN",
+        default_value_t,
+        value_enum,
+        env = "CARGO_TERM_COLOR"
+    )]
+    pub(crate) color: ColorChoice,
+
+    /// Theme applied to prompts, logs and errors.
+    ///
+    /// Defaults to the `theme` in the `[ui]` section of
+    /// `$CARGO_HOME/binstall/config.toml`, or `default`.
+    #[clap(help_heading = "Options", long, value_enum, env = "BINSTALL_THEME")]
+    pub(crate) theme: Option<ThemeName>,
+
     /// Print logs in json format to be parsable.
     #[clap(help_heading = "Options", long)]
     pub json_output: bool,
@@ -378,6 +685,75 @@ pub struct Args {
     )]
     pub(crate) github_token: Option<GithubToken>,
 
+    /// Hosts of GitHub Enterprise Servers to use the API of, for crates whose
+    /// repository is hosted on them.
+    ///
+    /// The token for each of them is taken from `--github-enterprise-token`,
+    /// or discovered from `gh auth token --hostname HOST` and
+    /// `git credential fill` unless `--no-discover-github-token` is specified.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_delimiter(','),
+        env = "BINSTALL_GITHUB_ENTERPRISE_HOST",
+        value_name = "HOST"
+    )]
+    pub(crate) github_enterprise_host: Vec<CompactString>,
+
+    /// Provide the token used for all hosts specified in
+    /// `--github-enterprise-host`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "GH_ENTERPRISE_TOKEN",
+        value_name = "TOKEN"
+    )]
+    pub(crate) github_enterprise_token: Option<GithubToken>,
+
+    /// Provide the personal access token for Azure DevOps, used by strategy
+    /// azure-devops to access private projects.
+    ///
+    /// Fallback to environment variable `AZURE_DEVOPS_EXT_PAT`, which is also
+    /// used by the `az devops` cli.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "AZURE_DEVOPS_EXT_PAT",
+        value_name = "TOKEN"
+    )]
+    pub(crate) azure_devops_token: Option<AzureDevOpsToken>,
+
+    /// Hosts of JFrog Artifactory instances to authenticate to with
+    /// `--artifactory-user` and `--artifactory-api-key`, for crates whose
+    /// pkg-url points to a private generic repository.
+    #[clap(
+        help_heading = "Options",
+        long,
+        value_delimiter(','),
+        env = "BINSTALL_ARTIFACTORY_HOST",
+        value_name = "HOST"
+    )]
+    pub(crate) artifactory_host: Vec<CompactString>,
+
+    /// User of the `--artifactory-api-key`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "ARTIFACTORY_USER",
+        value_name = "USER"
+    )]
+    pub(crate) artifactory_user: Option<CompactString>,
+
+    /// Provide the API key for the hosts specified in `--artifactory-host`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        requires_all(["artifactory_host", "artifactory_user"]),
+        env = "ARTIFACTORY_API_KEY",
+        value_name = "KEY"
+    )]
+    pub(crate) artifactory_api_key: Option<GithubToken>,
+
     /// Only install packages that are signed
     ///
     /// The default is to verify signatures if they are available, but to allow
@@ -395,6 +771,75 @@ pub struct Args {
     #[clap(help_heading = "Options", long, conflicts_with = "only_signed")]
     pub(crate) skip_signatures: bool,
 
+    /// DNS-over-HTTPS resolver supporting the JSON API, used to look up the
+    /// signing keys published in the DNS TXT record of the hosts of
+    /// repositories.
+    ///
+    /// Defaults to https://cloudflare-dns.com/dns-query.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "BINSTALL_DOH_RESOLVER",
+        value_name = "URL"
+    )]
+    pub(crate) doh_resolver: Option<remote::Url>,
+
+    /// Only install packages found in the Rekor transparency log of Sigstore
+    ///
+    /// The entry of the package must be signed by one of the keys passed to
+    /// --transparency-log-key, and both the entry and its inclusion proof
+    /// must be signed by the pinned key of Rekor.
+    ///
+    /// The index of the entry is recorded in the binstall manifest.
+    #[clap(help_heading = "Options", long, requires = "transparency_log_key")]
+    pub(crate) verify_transparency_log: bool,
+
+    /// Path to a PEM-encoded ECDSA P-256 public key trusted to sign the
+    /// entries of packages in the Rekor transparency log, e.g. the
+    /// `cosign.pub` of `cosign sign-blob --key`
+    ///
+    /// Can be passed multiple times.
+    #[clap(help_heading = "Options", long, value_name = "PATH")]
+    pub(crate) transparency_log_key: Vec<PathBuf>,
+
+    /// Only install the crate versions approved by the channel at this URL
+    ///
+    /// The channel is a toml file listing the approved version of each crate
+    /// and optionally the sha256 digests of its artifacts for each target,
+    /// see SIGNING.md for its format.
+    ///
+    /// Crates not listed in the channel cannot be installed.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "BINSTALL_CHANNEL",
+        value_name = "URL"
+    )]
+    pub(crate) channel: Option<remote::Url>,
+
+    /// Minisign public key to verify the signature of the channel with
+    ///
+    /// The signature is downloaded from `<channel URL>.minisig`.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "BINSTALL_CHANNEL_PUBKEY",
+        value_name = "KEY",
+        requires = "channel"
+    )]
+    pub(crate) channel_pubkey: Option<String>,
+
+    /// Use this time, in seconds since the unix epoch, for the time of
+    /// installation recorded in the manifests and the modification time of
+    /// the installed binaries, so that the installation is reproducible.
+    #[clap(
+        help_heading = "Options",
+        long,
+        env = "SOURCE_DATE_EPOCH",
+        value_name = "TIMESTAMP"
+    )]
+    pub(crate) freeze_time: Option<u64>,
+
     /// Print version information
     #[clap(help_heading = "Meta", short = 'V')]
     pub version: bool,
@@ -421,6 +866,12 @@ pub struct Args {
     #[clap(help_heading = "Meta", long, value_name = "LEVEL")]
     pub log_level: Option<LevelFilter>,
 
+    /// Explain why each strategy was or wasn't attempted for each target, e.g. because
+    /// it is disabled by package metadata, the crate does not specify the repository,
+    /// or GitHub API is rate limited.
+    #[clap(help_heading = "Meta", long)]
+    pub(crate) explain: bool,
+
     /// Implies `--log-level debug` and it can also be used with `--version`
     /// to print out verbose information,
     #[clap(help_heading = "Meta", short, long)]
@@ -434,6 +885,156 @@ pub struct Args {
 
     #[clap(long, hide(true))]
     pub(crate) self_install: bool,
+
+    /// Benchmark resolution of the crates instead of installing them,
+    /// also accepted as `cargo binstall bench-resolve <crate>...`.
+    #[clap(long, hide(true))]
+    pub(crate) bench_resolve: bool,
+
+    /// Number of times each crate is resolved in `--bench-resolve` mode.
+    #[clap(long, hide(true), default_value_t = NonZeroU16::new(5).unwrap())]
+    pub(crate) bench_iterations: NonZeroU16,
+
+    /// Replay the responses recorded to this directory in `--bench-resolve`
+    /// mode instead of sending requests, so that the results do not depend
+    /// on the network.
+    #[clap(long, hide(true), value_name = "DIR", requires = "bench_resolve")]
+    pub(crate) bench_fixtures: Option<PathBuf>,
+
+    /// Send the requests and record their responses to `--bench-fixtures`.
+    #[clap(long, hide(true), requires = "bench_fixtures")]
+    pub(crate) bench_record: bool,
+
+    /// Periodically update the crates installed by cargo-binstall, or the
+    /// crates specified, also accepted as `cargo binstall watch`.
+    ///
+    /// The other options are applied to every update and pins of the
+    /// installed crates are honoured.
+    #[clap(long, hide(true), conflicts_with = "schedule")]
+    pub(crate) watch: bool,
+
+    /// Generate a systemd timer on linux, or a launchd agent on macOS, which
+    /// runs `cargo binstall watch --once` with the other options every
+    /// `--interval`, also accepted as `cargo binstall schedule`.
+    #[clap(long, hide(true))]
+    pub(crate) schedule: bool,
+
+    /// Interval between updates in `--watch` and `--schedule` mode, e.g.
+    /// `30m`, `24h` or `7d`.
+    #[clap(long, hide(true), default_value = "24h", value_name = "DURATION")]
+    pub(crate) interval: Interval,
+
+    /// Print whether a version of the crate newer than this one is
+    /// published as a line of JSON, also accepted as
+    /// `cargo binstall check-self <crate> <current-version>`.
+    ///
+    /// Meant for tools installed by cargo-binstall to show update hints.
+    #[clap(long, hide(true), value_name = "CURRENT_VERSION")]
+    pub(crate) check_self: Option<Version>,
+
+    /// Update the installed crates only once in `--watch` mode.
+    #[clap(long, hide(true), requires = "watch")]
+    pub(crate) once: bool,
+
+    /// Print what installing the tools of --tools-manifest and removing the
+    /// other crates installed by cargo-binstall would change, without
+    /// touching anything, also accepted as `cargo binstall diff <tools.toml>`.
+    #[clap(long, hide(true), requires = "tools_manifest")]
+    pub(crate) diff: bool,
+
+    /// Print the changes in `--diff` mode as a JSON array.
+    #[clap(long, hide(true), requires = "diff")]
+    pub(crate) json: bool,
+
+    /// Print the crate, version, source, digests and time of installation of
+    /// a binary in PATH, or that it is not installed by cargo-binstall, also
+    /// accepted as `cargo binstall which <binary>`.
+    #[clap(long, hide(true), value_name = "BINARY")]
+    pub(crate) which: Option<OsString>,
+
+    /// Add the signing keys at SOURCE to the local trust store, also
+    /// accepted as `cargo binstall key add <source>`.
+    ///
+    /// SOURCE is a file, an https url or `github:<user>` for the ssh keys
+    /// of a GitHub user. Minisign keys are pinned for a host and ssh keys
+    /// are added to the allowed signers.
+    #[clap(long, hide(true), value_name = "SOURCE")]
+    pub(crate) key_add: Option<String>,
+
+    /// Host to pin the minisign key for, or principal of the ssh keys added
+    /// with `--key-add`, defaulting to the host of the url or the GitHub user.
+    #[clap(long, hide(true), requires = "key_add")]
+    pub(crate) key_name: Option<String>,
+
+    /// List the keys of the local trust store, also accepted as
+    /// `cargo binstall key list`.
+    #[clap(long, hide(true))]
+    pub(crate) key_list: bool,
+
+    /// Remove the keys equal to KEY, or pinned for host KEY or of principal
+    /// KEY, from the local trust store, also accepted as
+    /// `cargo binstall key remove <key>`.
+    #[clap(long, hide(true), value_name = "KEY")]
+    pub(crate) key_remove: Option<String>,
+}
+
+/// Options given along with a package, overriding the global ones for it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CrateOverrides {
+    pub(crate) targets: Option<Vec<String>>,
+}
+
+/// Parse `crate[@version] [--version <VERSION>] [--targets <TRIPLES>]`.
+fn parse_crate_arg(arg: &str, exact: bool) -> Result<(CrateName, CrateOverrides), String> {
+    let mut tokens = arg.split_whitespace();
+    let crate_name = tokens.next().ok_or("missing crate name")?;
+    let mut crate_name = if exact {
+        CrateName::parse_exact(crate_name)
+    } else {
+        CrateName::from_str(crate_name)
+    }
+    .map_err(|err| err.to_string())?;
+    let mut overrides = CrateOverrides::default();
+
+    while let Some(token) = tokens.next() {
+        let (option, value) = match token.split_once('=') {
+            Some((option, value)) => (option, value),
+            None => (
+                token,
+                tokens
+                    .next()
+                    .ok_or_else(|| format!("missing value of {token}"))?,
+            ),
+        };
+
+        match option {
+            "--version" if crate_name.version_req.is_some() => {
+                return Err("cannot use --version along with crate@version".into())
+            }
+            "--version" => {
+                let version_req = if exact {
+                    VersionReq::parse_exact_from_cli(value)
+                } else {
+                    VersionReq::parse_from_cli(value)
+                };
+                crate_name.version_req = Some(version_req.map_err(|err| err.to_string())?);
+            }
+            "--targets" | "--target" => {
+                overrides.targets = Some(value.split(',').map(String::from).collect())
+            }
+            _ => {
+                return Err(format!(
+                    "unexpected option {option}, only --version and --targets can be given along with a package"
+                ))
+            }
+        }
+    }
+
+    Ok((crate_name, overrides))
+}
+
+fn parse_crate_name(arg: &str) -> Result<CrateName, String> {
+    parse_crate_arg(arg, false).map(|(crate_name, _)| crate_name)
 }
 
 #[derive(Debug, Clone)]
@@ -445,6 +1046,16 @@ impl From<&str> for GithubToken {
     }
 }
 
+/// Personal access token for Azure DevOps.
+#[derive(Debug, Clone)]
+pub(crate) struct AzureDevOpsToken(pub(crate) Zeroizing<Box<str>>);
+
+impl From<&str> for AzureDevOpsToken {
+    fn from(s: &str) -> Self {
+        Self(Zeroizing::new(s.into()))
+    }
+}
+
 #[derive(Debug, Copy, Clone, ValueEnum)]
 pub(crate) enum TLSVersion {
     #[clap(name = "1.2")]
@@ -462,6 +1073,53 @@ impl From<TLSVersion> for remote::TLSVersion {
     }
 }
 
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub(crate) enum TLSRoots {
+    Webpki,
+    Native,
+}
+
+impl From<TLSRoots> for remote::TLSRoots {
+    fn from(roots: TLSRoots) -> Self {
+        match roots {
+            TLSRoots::Webpki => remote::TLSRoots::Webpki,
+            TLSRoots::Native => remote::TLSRoots::Native,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub(crate) enum CrossCompiler {
+    Zigbuild,
+    Cross,
+}
+
+impl From<CrossCompiler> for ops::CrossCompiler {
+    fn from(cross_compiler: CrossCompiler) -> Self {
+        match cross_compiler {
+            CrossCompiler::Zigbuild => ops::CrossCompiler::Zigbuild,
+            CrossCompiler::Cross => ops::CrossCompiler::Cross,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub(crate) enum FsCompat {
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<FsCompat> for atomic_file_install::FsCompat {
+    fn from(fs_compat: FsCompat) -> Self {
+        match fs_compat {
+            FsCompat::Auto => atomic_file_install::FsCompat::Auto,
+            FsCompat::Always => atomic_file_install::FsCompat::Always,
+            FsCompat::Never => atomic_file_install::FsCompat::Never,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct RateLimit {
     pub(crate) duration: NonZeroU16,
@@ -501,16 +1159,71 @@ impl Default for RateLimit {
     }
 }
 
+/// Duration with a unit of `s`, `m`, `h` or `d`, in seconds if omitted.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Interval(pub(crate) Duration);
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+        let value: u64 = value.parse().map_err(|err| format!("{err}"))?;
+
+        let secs = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return Err(format!("expected s, m, h or d, found {unit}")),
+        };
+
+        Ok(Self(Duration::from_secs(value.saturating_mul(secs))))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ConfirmTimeout {
+    pub(crate) timeout: NonZeroU16,
+    /// Whether to proceed with the installation on timeout.
+    pub(crate) proceed: bool,
+}
+
+impl FromStr for ConfirmTimeout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timeout, answer) = s.split_once('=').unwrap_or((s, "no"));
+
+        Ok(Self {
+            timeout: timeout.parse().map_err(|err| format!("{err}"))?,
+            proceed: match answer {
+                "yes" => true,
+                "no" => false,
+                _ => return Err(format!("expected yes or no, found {answer}")),
+            },
+        })
+    }
+}
+
 /// Strategy for installing the package
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub(crate) struct StrategyWrapped(pub(crate) Strategy);
 
 impl StrategyWrapped {
-    const VARIANTS: &'static [Self; 3] = &[
+    /// All strategies, starting with the [`Self::DEFAULT_COUNT`] default ones
+    /// in the order they are tried.
+    const VARIANTS: &'static [Self; 6] = &[
         Self(Strategy::CrateMetaData),
+        Self(Strategy::AzureDevOps),
         Self(Strategy::QuickInstall),
         Self(Strategy::Compile),
+        Self(Strategy::Homebrew),
+        Self(Strategy::Wapm),
     ];
+
+    /// Number of strategies used by default.
+    const DEFAULT_COUNT: usize = 4;
 }
 
 impl ValueEnum for StrategyWrapped {
@@ -522,7 +1235,64 @@ impl ValueEnum for StrategyWrapped {
     }
 }
 
-pub fn parse() -> (Args, PkgOverride) {
+/// Turn hidden subcommands `bench-resolve`, `watch`, `schedule` and `which`
+/// into `--bench-resolve`, `--watch`, `--schedule` and `--which`,
+/// `check-self <crate> <current-version>` into
+/// `<crate> --check-self <current-version>`, `diff <tools.toml>` into
+/// `--diff --tools-manifest <tools.toml>`, and `key add|list|remove` into
+/// `--key-add`, `--key-list` and `--key-remove`.
+///
+/// Only the first argument after the program is a subcommand, so crates
+/// named after one are installed by passing them after `--`.
+fn expand_subcommands(args: impl Iterator<Item = OsString>) -> Result<Vec<OsString>, clap::Error> {
+    let mut args = args.peekable();
+    let mut leading_args: Vec<_> = args.next().into_iter().collect();
+    if let Some(subcommand) = args.next_if(|arg| {
+        arg == "bench-resolve"
+            || arg == "watch"
+            || arg == "schedule"
+            || arg == "check-self"
+            || arg == "diff"
+            || arg == "which"
+            || arg == "key"
+    }) {
+        if subcommand == "key" {
+            let action = args.next();
+            match action.as_ref().and_then(|action| action.to_str()) {
+                Some(action @ ("add" | "list" | "remove")) => {
+                    leading_args.push(OsString::from(format!("--key-{action}")))
+                }
+                _ => {
+                    return Err(Args::command().error(
+                        ErrorKind::InvalidSubcommand,
+                        format_args!(
+                            "expected `key add|list|remove`, found {}",
+                            match &action {
+                                Some(action) => format!("`key {}`", action.to_string_lossy()),
+                                None => "`key`".to_owned(),
+                            }
+                        ),
+                    ))
+                }
+            }
+        } else if subcommand == "check-self" {
+            leading_args.extend(args.next());
+            leading_args.push(OsString::from("--check-self"));
+        } else if subcommand == "diff" {
+            leading_args.push(OsString::from("--diff"));
+            leading_args.push(OsString::from("--tools-manifest"));
+        } else {
+            let mut flag = OsString::from("--");
+            flag.push(subcommand);
+            leading_args.push(flag);
+        }
+    }
+    leading_args.extend(args);
+    Ok(leading_args)
+}
+
+/// Return the arguments of the program with hidden subcommands expanded.
+fn args_os() -> Vec<OsString> {
     // Filter extraneous arg when invoked by cargo
     // `cargo run -- --help` gives ["target/debug/cargo-binstall", "--help"]
     // `cargo binstall --help` gives ["/home/ryan/.cargo/bin/cargo-binstall", "binstall", "--help"]
@@ -542,13 +1312,108 @@ pub fn parse() -> (Args, PkgOverride) {
         args.into_iter()
     };
 
+    expand_subcommands(args).unwrap_or_else(|err| err.exit())
+}
+
+/// Return the arguments `cargo binstall watch --once` is run with by the
+/// job generated in `--schedule` mode, i.e. the arguments of the program
+/// without itself, `--schedule` and `--interval`.
+pub(crate) fn scheduled_args() -> Vec<OsString> {
+    watch_args(args_os())
+}
+
+fn watch_args(args: Vec<OsString>) -> Vec<OsString> {
+    let mut args = args.into_iter().skip(1);
+    let mut watch_args = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            watch_args.push(arg);
+            watch_args.extend(args);
+            break;
+        } else if arg == "--interval" {
+            args.next();
+        } else if arg != "--schedule"
+            && !arg
+                .to_str()
+                .is_some_and(|arg| arg.starts_with("--interval="))
+        {
+            watch_args.push(arg);
+        }
+    }
+
+    watch_args
+}
+
+pub fn parse() -> (Args, PkgOverride) {
+    let args = args_os();
+
     // Load options
-    let mut opts = Args::parse_from(args);
+    let matches = Args::command().get_matches_from(args);
+    let mut opts = Args::from_arg_matches(&matches)
+        .unwrap_or_else(|err| err.format(&mut Args::command()).exit());
+
+    // The value parsers cannot see --exact, and the options given along with
+    // the packages are not kept by them, so parse the packages again
+    let raw_values = |id| {
+        matches
+            .get_raw(id)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.to_str())
+    };
+    let crate_args = raw_values("crate_names")
+        .map(|arg| parse_crate_arg(arg, opts.exact))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|err| {
+            Args::command()
+                .error(ErrorKind::ValueValidation, err)
+                .exit()
+        });
+    opts.crate_names = crate_args
+        .into_iter()
+        .map(|(crate_name, overrides)| {
+            opts.crate_overrides
+                .insert(crate_name.name.clone(), overrides);
+            crate_name
+        })
+        .collect();
+
+    if opts.exact {
+        if let Some(version) = raw_values("version_req").next() {
+            let version_req = VersionReq::parse_exact_from_cli(version).unwrap_or_else(|err| {
+                Args::command()
+                    .error(ErrorKind::ValueValidation, err)
+                    .exit()
+            });
+            opts.version_req = Some(version_req);
+        }
+    }
 
     if opts.self_install {
         return (opts, Default::default());
     }
 
+    if opts.check_self.is_some() {
+        // Only the result, or else the error, is printed to stdout and the
+        // installed crates are not looked up.
+        opts.log_level.get_or_insert(LevelFilter::Error);
+        opts.no_track = true;
+    }
+
+    if opts.extract_to.is_some() {
+        // Nothing is installed, so neither the installed crates nor a
+        // confirmation are needed.
+        opts.no_track = true;
+        opts.force = true;
+        opts.no_confirm = true;
+    }
+
+    if opts.json {
+        // Keep the JSON array parsable, only warnings and errors are logged.
+        opts.log_level.get_or_insert(LevelFilter::Warn);
+    }
+
     if opts.log_level.is_none() {
         if let Some(log) = env::var("BINSTALL_LOG_LEVEL")
             .ok()
@@ -565,6 +1430,34 @@ pub fn parse() -> (Args, PkgOverride) {
     // Ensure no conflict
     let mut command = Args::command();
 
+    if let Some(path) = &opts.tools_manifest {
+        let tools = ToolsManifest::load(path).unwrap_or_else(|err| {
+            command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format_args!("Failed to load tools manifest {}: {err}", path.display()),
+                )
+                .exit()
+        });
+        let order = tools.install_order().unwrap_or_else(|err| {
+            command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format_args!("Invalid tools manifest {}: {err}", path.display()),
+                )
+                .exit()
+        });
+
+        opts.crate_names = order
+            .into_iter()
+            .map(|name| CrateName {
+                name: name.clone(),
+                version_req: tools.tools[name].version.clone(),
+            })
+            .collect();
+        opts.tools = Some(tools);
+    }
+
     if opts.crate_names.len() > 1 {
         let option = if opts.version_req.is_some() {
             "version"
@@ -597,6 +1490,24 @@ You cannot use --{option} and specify multiple packages at the same time. Do one
         }
     }
 
+    if opts.check_self.is_some() && opts.crate_names.len() != 1 {
+        command
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                "check-self takes exactly one crate",
+            )
+            .exit();
+    }
+
+    if opts.extract_to.is_some() && opts.crate_names.len() != 1 {
+        command
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                "--extract-to takes exactly one crate",
+            )
+            .exit();
+    }
+
     // Check strategies for duplicates
     let mut new_dup_strategy_err = || {
         command.error(
@@ -627,11 +1538,7 @@ You cannot use --{option} and specify multiple packages at the same time. Do one
 
     // Default strategies if empty
     if opts.strategies.is_empty() {
-        opts.strategies = vec![
-            StrategyWrapped(Strategy::CrateMetaData),
-            StrategyWrapped(Strategy::QuickInstall),
-            StrategyWrapped(Strategy::Compile),
-        ];
+        opts.strategies = StrategyWrapped::VARIANTS[..StrategyWrapped::DEFAULT_COUNT].to_vec();
     }
 
     // Filter out all disabled strategies
@@ -654,6 +1561,21 @@ You cannot use --{option} and specify multiple packages at the same time. Do one
         }
     }
 
+    if opts.extract_to.is_some() {
+        // Only pre-built binaries can be extracted
+        opts.strategies
+            .retain(|strategy| strategy.0 != Strategy::Compile);
+
+        if opts.strategies.is_empty() {
+            command
+                .error(
+                    ErrorKind::TooFewValues,
+                    "--extract-to needs a strategy other than compile",
+                )
+                .exit()
+        }
+    }
+
     // Ensure that Strategy::Compile is specified as the last strategy
     if opts.strategies[..(opts.strategies.len() - 1)].contains(&StrategyWrapped(Strategy::Compile))
     {
@@ -678,7 +1600,8 @@ You cannot use --{option} and specify multiple packages at the same time. Do one
     let cli_overrides = PkgOverride {
         pkg_url: opts.pkg_url.take(),
         pkg_fmt: opts.pkg_fmt.take(),
-        bin_dir: opts.bin_dir.take(),
+        pkg_parts: opts.pkg_parts.take(),
+        bin_dir: opts.bin_dir.take().map(BinDir::from),
         disabled_strategies: Some(
             mem::take(&mut opts.disable_strategies)
                 .into_iter()
@@ -688,6 +1611,9 @@ You cannot use --{option} and specify multiple packages at the same time. Do one
         ),
         ignore_disabled_strategies,
         signing: None,
+        checksum_file: None,
+        extract_nested: None,
+        preserve_symlinks: None,
     };
 
     (opts, cli_overrides)
@@ -720,5 +1646,100 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_expand_subcommands() {
+        let expand = |args: &[&str]| {
+            let args = expand_subcommands(args.iter().map(OsString::from)).unwrap();
+            Args::try_parse_from(args).unwrap()
+        };
+
+        let args = expand(&["cargo-binstall", "which", "rg"]);
+        assert_eq!(args.which.as_deref(), Some("rg".as_ref()));
+        assert!(args.crate_names.is_empty());
+
+        let args = expand(&["cargo-binstall", "--", "which"]);
+        assert!(args.which.is_none());
+        assert_eq!(args.crate_names[0].name, "which");
+
+        let args = expand(&["cargo-binstall", "-y", "--", "key", "diff"]);
+        assert!(!args.key_list);
+        assert_eq!(args.crate_names.len(), 2);
+
+        let args = expand(&["cargo-binstall", "key", "list"]);
+        assert!(args.key_list);
+
+        for args in [
+            &["cargo-binstall", "key"][..],
+            &["cargo-binstall", "key", "diff"],
+        ] {
+            let err = expand_subcommands(args.iter().map(OsString::from)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidSubcommand);
+            assert!(err.to_string().contains("expected `key add|list|remove`"));
+        }
+    }
+
+    #[test]
+    fn test_watch_args() {
+        let watch_args = |args: &[&str]| {
+            watch_args(expand_subcommands(args.iter().map(OsString::from)).unwrap())
+        };
+
+        assert_eq!(
+            watch_args(&[
+                "cargo-binstall",
+                "schedule",
+                "--interval",
+                "7d",
+                "--root",
+                "/opt",
+                "--strategies=crate-meta-data",
+                "--only-signed",
+            ]),
+            [
+                "--root",
+                "/opt",
+                "--strategies=crate-meta-data",
+                "--only-signed"
+            ]
+        );
+        assert_eq!(
+            watch_args(&[
+                "cargo-binstall",
+                "--interval=1h",
+                "--schedule",
+                "--",
+                "--interval"
+            ]),
+            ["--", "--interval"]
+        );
+    }
+
+    #[test]
+    fn test_parse_crate_arg() {
+        let (crate_name, overrides) =
+            parse_crate_arg("ripgrep@14 --targets x86_64-unknown-linux-musl", false).unwrap();
+        assert_eq!(crate_name.to_string(), "ripgrep@^14");
+        assert_eq!(overrides.targets.unwrap(), ["x86_64-unknown-linux-musl"]);
+
+        let (crate_name, overrides) =
+            parse_crate_arg("just --version=1.25 --target=a,b", true).unwrap();
+        assert_eq!(crate_name.to_string(), "just@=1.25");
+        assert_eq!(overrides.targets.unwrap(), ["a", "b"]);
+
+        let (crate_name, overrides) = parse_crate_arg("just", false).unwrap();
+        assert_eq!(crate_name.to_string(), "just");
+        assert!(overrides.targets.is_none());
+
+        for arg in [
+            "",
+            "just@1 --version 1.25",
+            "just --targets",
+            "just --force",
+            "just x86_64-unknown-linux-musl",
+        ] {
+            assert!(parse_crate_arg(arg, false).is_err(), "{arg}");
+        }
+    }
+
     const _: () = assert!(Strategy::VARIANTS.len() == StrategyWrapped::VARIANTS.len());
 }
@@ -0,0 +1,87 @@
+//! Hidden `cargo binstall bench-resolve <crate>...` mode, used to track
+//! performance regression of the resolver.
+//!
+//! With `--bench-fixtures`, the responses recorded by `--bench-record` are
+//! replayed instead of sending requests, so that only the resolver is
+//! measured.
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroU16,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use binstalk::{
+    errors::BinstallError,
+    manifests::cargo_toml_binstall::Strategy,
+    ops::{
+        resolve::{self, CrateName, Resolution},
+        Options,
+    },
+};
+use tracing::info;
+
+/// Resolve each crate `iterations` times and print p50/p95 latencies,
+/// grouped by the strategy the crate is resolved to.
+pub(crate) async fn bench_resolve(
+    opts: Arc<Options>,
+    crate_names: Vec<CrateName>,
+    iterations: NonZeroU16,
+) -> Result<(), BinstallError> {
+    let mut samples: BTreeMap<&'static str, Vec<Duration>> = BTreeMap::new();
+
+    for crate_name in crate_names {
+        for i in 0..iterations.get() {
+            info!("Benchmarking resolution of {crate_name}, iteration {i}");
+
+            let start = Instant::now();
+            let resolution = resolve::resolve(opts.clone(), crate_name.clone(), None).await?;
+            let spent = start.elapsed();
+
+            let strategy = match resolution {
+                Resolution::Fetch(fetch) => fetch.fetcher.strategy(),
+                Resolution::InstallFromSource(_) => Strategy::Compile,
+                Resolution::AlreadyUpToDate => continue,
+            };
+
+            samples.entry(strategy.to_str()).or_default().push(spent);
+        }
+    }
+
+    println!("strategy\tsamples\tp50\tp95");
+    for (strategy, mut durations) in samples {
+        durations.sort_unstable();
+
+        println!(
+            "{strategy}\t{}\t{:?}\t{:?}",
+            durations.len(),
+            percentile(&durations, 50),
+            percentile(&durations, 95),
+        );
+    }
+
+    Ok(())
+}
+
+/// Nearest-rank percentile.
+///
+///  * `sorted` - must be sorted and non-empty.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let durations: Vec<_> = (1..=20).map(Duration::from_secs).collect();
+
+        assert_eq!(percentile(&durations, 50), Duration::from_secs(10));
+        assert_eq!(percentile(&durations, 95), Duration::from_secs(19));
+        assert_eq!(percentile(&durations[..1], 95), Duration::from_secs(1));
+    }
+}
@@ -7,9 +7,9 @@ use binstalk::errors::BinstallError;
 use binstalk::helpers::tasks::AutoAbortJoinHandle;
 use miette::Result;
 use tokio::runtime::Runtime;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::signal::cancel_on_user_sig_term;
+use crate::{i18n::Message, signal::cancel_on_user_sig_term};
 
 pub enum MainExit {
     Success(Option<Duration>),
@@ -22,13 +22,21 @@ impl Termination for MainExit {
         match self {
             Self::Success(spent) => {
                 if let Some(spent) = spent {
-                    info!("Done in {spent:?}");
+                    info!("{}", Message::Done { spent });
                 }
                 ExitCode::SUCCESS
             }
-            Self::Error(err) => err.report(),
+            Self::Error(err) => {
+                let code = err.exit_code();
+                if let BinstallError::UserAbort = err {
+                    warn!("{}", Message::InstallationCancelled);
+                } else {
+                    error!("{}:\n{:?}", Message::FatalError, miette::Report::new(err));
+                }
+                code
+            }
             Self::Report(err) => {
-                error!("Fatal error:\n{err:?}");
+                error!("{}:\n{err:?}", Message::FatalError);
                 ExitCode::from(16)
             }
         }
@@ -0,0 +1,60 @@
+//! Load the channel specified with `--channel`, which restricts the crates
+//! that can be installed to the versions approved in it.
+
+use binstalk::{
+    errors::BinstallError,
+    fetchers::SignatureVerifier,
+    helpers::remote::{Client, Url},
+};
+use binstalk_manifests::{
+    cargo_toml_binstall::{PkgSigning, SigningAlgorithm},
+    channel::{self, Channel},
+};
+use miette::{miette, Result, WrapErr};
+use tracing::{info, warn};
+
+/// Fetch the channel at `url` and verify its minisign signature at
+/// `<url>.minisig` against `pubkey`.
+pub(crate) async fn load(client: &Client, url: Url, pubkey: Option<&str>) -> Result<Channel> {
+    let contents = client
+        .get(url.clone())
+        .send(true)
+        .await
+        .map_err(BinstallError::from)?
+        .bytes()
+        .await
+        .map_err(BinstallError::from)
+        .wrap_err_with(|| format!("Failed to fetch channel {url}"))?;
+
+    if let Some(pubkey) = pubkey {
+        let signature_url = Url::parse(&format!("{url}.minisig")).map_err(BinstallError::from)?;
+        let signature = client
+            .get(signature_url)
+            .send(true)
+            .await
+            .map_err(BinstallError::from)?
+            .bytes()
+            .await
+            .map_err(BinstallError::from)
+            .wrap_err_with(|| format!("Failed to fetch signature of channel {url}"))?;
+
+        let config = PkgSigning {
+            algorithm: SigningAlgorithm::Minisign,
+            pubkey: Some(pubkey.to_owned().into()),
+            file: None,
+        };
+        let verifier = SignatureVerifier::new(&config, &signature).map_err(BinstallError::from)?;
+        let mut data_verifier = verifier.data_verifier().map_err(BinstallError::from)?;
+        data_verifier.update(&contents);
+        if !data_verifier.validate() {
+            return Err(miette!("Signature of channel {url} is invalid"));
+        }
+        if let Some(info) = verifier.info() {
+            info!("Verified signature of channel {url}: {info}");
+        }
+    } else {
+        warn!("Channel {url} is not verified, pass --channel-pubkey to verify its signature");
+    }
+
+    channel::parse(&contents).wrap_err_with(|| format!("Failed to parse channel {url}"))
+}
@@ -0,0 +1,34 @@
+//! Discover the ambient credentials of cloud providers, for downloading
+//! `gs://` and `az://` artifacts of private buckets.
+
+use std::io;
+
+use tokio::process::Command;
+use zeroize::Zeroizing;
+
+use crate::gh_token::CommandExt;
+
+/// Access token of the Google Cloud application default credentials.
+pub(super) async fn get_gcloud_token() -> io::Result<Zeroizing<Box<str>>> {
+    Command::new("gcloud")
+        .args(["auth", "application-default", "print-access-token"])
+        .stdout_with_optional_input(None)
+        .await
+}
+
+/// Access token for Azure Storage of the user logged in with the Azure cli.
+pub(super) async fn get_azure_storage_token() -> io::Result<Zeroizing<Box<str>>> {
+    Command::new("az")
+        .args([
+            "account",
+            "get-access-token",
+            "--resource",
+            "https://storage.azure.com/",
+            "--query",
+            "accessToken",
+            "--output",
+            "tsv",
+        ])
+        .stdout_with_optional_input(None)
+        .await
+}
@@ -0,0 +1,118 @@
+//! Credential helpers supply the credential of a host on demand, e.g. from a
+//! secrets manager, configured by `credential-helper` of the `[auth]` table
+//! of the binstall config.
+//!
+//! Similar to the credential helpers of git, the helper is run with an extra
+//! `get` argument, and is given the host on stdin:
+//!
+//! ```text
+//! protocol=https
+//! host=artifacts.example.com
+//! ```
+//!
+//! It prints the credential on stdout as `key=value` lines, either
+//! `token=...` or `username=...` and `password=...`, and exits with a
+//! non-zero status if it has no credential for the host.
+//!
+//! The helper is killed if it does not exit within [`TIMEOUT`], e.g. since
+//! it waits for input.
+
+use std::{io, mem, time::Duration};
+
+use compact_str::CompactString;
+use tokio::{process::Command, time::timeout};
+use zeroize::Zeroizing;
+
+use crate::gh_token::CommandExt;
+
+/// Time given to the credential helper to print the credential.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `helper` to retrieve the credential of `host`, which is
+/// `username:password` if the helper prints a username.
+pub(super) async fn get(helper: &[CompactString], host: &str) -> io::Result<Zeroizing<Box<str>>> {
+    let (program, args) = helper
+        .split_first()
+        .ok_or_else(|| io::Error::other("Credential helper is empty"))?;
+
+    let output = timeout(
+        TIMEOUT,
+        Command::new(&**program)
+            .args(args.iter().map(|arg| &**arg))
+            .arg("get")
+            .kill_on_drop(true)
+            .untrimmed_stdout_with_optional_input(Some(
+                format!("protocol=https\nhost={host}\n\n").as_bytes(),
+            )),
+    )
+    .await
+    .map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Credential helper `{program}` did not exit within {TIMEOUT:?}"),
+        )
+    })??;
+
+    parse_output(&output).ok_or_else(|| {
+        io::Error::other(format!(
+            "Credential not found in the output of credential helper `{program}`"
+        ))
+    })
+}
+
+fn parse_output(output: &str) -> Option<Zeroizing<Box<str>>> {
+    let (mut token, mut username, mut password) = (None, None, None);
+
+    // Unlike the keys, the values are not trimmed since whitespace may be
+    // part of the credential.
+    for line in output.lines() {
+        match line.trim_end_matches(['\r', '\n']).split_once('=') {
+            Some(("token", value)) => token = Some(value),
+            Some(("username", value)) => username = Some(value),
+            Some(("password", value)) => password = Some(value),
+            _ => (),
+        }
+    }
+
+    Some(match (token, username, password) {
+        (Some(token), ..) => Zeroizing::new(token.into()),
+        (None, Some(username), Some(password)) => {
+            let mut credential =
+                Zeroizing::new(String::with_capacity(username.len() + 1 + password.len()));
+            credential.push_str(username);
+            credential.push(':');
+            credential.push_str(password);
+            Zeroizing::new(mem::take(&mut *credential).into_boxed_str())
+        }
+        (None, None, Some(password)) => Zeroizing::new(password.into()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        let parse = |output| parse_output(output).map(|credential| credential.to_string());
+
+        assert_eq!(parse("token=abc\n").as_deref(), Some("abc"));
+        assert_eq!(
+            parse("username=user\npassword=p=w\n").as_deref(),
+            Some("user:p=w")
+        );
+        assert_eq!(parse("password=pw").as_deref(), Some("pw"));
+        assert_eq!(
+            parse("username=user\ntoken=abc\npassword=pw").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            parse("username=user\r\npassword= p w \r\n").as_deref(),
+            Some("user: p w ")
+        );
+        assert_eq!(parse("token=\tabc \n").as_deref(), Some("\tabc "));
+        assert_eq!(parse("username=user\n"), None);
+        assert_eq!(parse(""), None);
+    }
+}
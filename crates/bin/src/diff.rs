@@ -0,0 +1,205 @@
+//! `cargo binstall diff <tools.toml>`, showing how the installed crates
+//! differ from a tools manifest without installing or removing anything.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use binstalk::{
+    errors::BinstallError,
+    helpers::tasks::AutoAbortJoinHandle,
+    ops::{self, resolve::CrateName, Options},
+};
+use binstalk_manifests::crate_info::{InstalledBy, InstalledCrate};
+use compact_str::CompactString;
+use semver::Version;
+use serde::Serialize;
+use tracing::info;
+
+/// Serializes to a JSON object, e.g.
+/// `{"action":"upgrade","name":"foo","from":"1.0.0","to":"1.1.0"}`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+enum Change {
+    Install {
+        name: CompactString,
+        to: Version,
+    },
+    Upgrade {
+        name: CompactString,
+        from: Version,
+        to: Version,
+    },
+    /// The version requirement in the tools manifest excludes the
+    /// installed version.
+    Downgrade {
+        name: CompactString,
+        from: Version,
+        to: Version,
+    },
+    /// Installed by cargo-binstall, but not in the tools manifest.
+    Remove {
+        name: CompactString,
+        from: Version,
+    },
+}
+
+impl Change {
+    fn action(&self) -> &'static str {
+        match self {
+            Change::Install { .. } => "install",
+            Change::Upgrade { .. } => "upgrade",
+            Change::Downgrade { .. } => "downgrade",
+            Change::Remove { .. } => "remove",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Change::Install { name, .. }
+            | Change::Upgrade { name, .. }
+            | Change::Downgrade { name, .. }
+            | Change::Remove { name, .. } => name,
+        }
+    }
+
+    fn versions(&self) -> (Option<&Version>, Option<&Version>) {
+        match self {
+            Change::Install { to, .. } => (None, Some(to)),
+            Change::Upgrade { from, to, .. } | Change::Downgrade { from, to, .. } => {
+                (Some(from), Some(to))
+            }
+            Change::Remove { from, .. } => (Some(from), None),
+        }
+    }
+}
+
+/// Formats as `<action> <name> [<from>] [-> <to>]`, e.g.
+/// `upgrade foo 1.0.0 -> 1.1.0`.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.action(), self.name())?;
+        match self.versions() {
+            (Some(from), Some(to)) => write!(f, " {from} -> {to}"),
+            (Some(version), None) | (None, Some(version)) => write!(f, " {version}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Print what installing the tools `crate_names` and removing the other
+/// crates installed by cargo-binstall would change, as a JSON array if
+/// `json`.
+pub(crate) async fn diff(
+    opts: Arc<Options>,
+    crate_names: Vec<CrateName>,
+    mut installed_crates: BTreeMap<CompactString, InstalledCrate>,
+    json: bool,
+) -> Result<(), BinstallError> {
+    let tasks: Vec<_> = crate_names
+        .into_iter()
+        .map(|crate_name| {
+            let opts = opts.clone();
+            AutoAbortJoinHandle::spawn(async move {
+                let latest_version = ops::check_update::latest_version(&opts, &crate_name).await?;
+                Ok::<_, BinstallError>((crate_name.name, latest_version))
+            })
+        })
+        .collect();
+
+    let mut changes = Vec::new();
+
+    for task in tasks {
+        let (name, to) = task.flattened_join().await?;
+
+        changes.extend(match installed_crates.remove(&name) {
+            None => Some(Change::Install { name, to }),
+            Some(installed) if installed.version < to => Some(Change::Upgrade {
+                name,
+                from: installed.version,
+                to,
+            }),
+            Some(installed) if installed.version > to => Some(Change::Downgrade {
+                name,
+                from: installed.version,
+                to,
+            }),
+            Some(_) => None,
+        });
+    }
+
+    // Crates installed by `cargo install` are left alone.
+    changes.extend(
+        installed_crates
+            .into_iter()
+            .filter(|(_, installed)| installed.installed_by == InstalledBy::CargoBinstall)
+            .map(|(name, installed)| Change::Remove {
+                name,
+                from: installed.version,
+            }),
+    );
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&changes).expect("the changes are always serializable")
+        );
+    } else if changes.is_empty() {
+        info!("The installed crates match the tools manifest");
+    } else {
+        for change in changes {
+            println!("{change}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let v = |s| Version::parse(s).unwrap();
+
+        for (change, text, json) in [
+            (
+                Change::Install {
+                    name: "foo".into(),
+                    to: v("1.1.0"),
+                },
+                "install foo 1.1.0",
+                r#"{"action":"install","name":"foo","to":"1.1.0"}"#,
+            ),
+            (
+                Change::Upgrade {
+                    name: "foo".into(),
+                    from: v("1.0.0"),
+                    to: v("1.1.0"),
+                },
+                "upgrade foo 1.0.0 -> 1.1.0",
+                r#"{"action":"upgrade","name":"foo","from":"1.0.0","to":"1.1.0"}"#,
+            ),
+            (
+                Change::Remove {
+                    name: "foo".into(),
+                    from: v("1.0.0"),
+                },
+                "remove foo 1.0.0",
+                r#"{"action":"remove","name":"foo","from":"1.0.0"}"#,
+            ),
+        ] {
+            assert_eq!(change.to_string(), text);
+            assert_eq!(serde_json::to_string(&change).unwrap(), json);
+        }
+
+        let changes = [Change::Downgrade {
+            name: "foo".into(),
+            from: v("2.0.0"),
+            to: v("1.0.0"),
+        }];
+        assert_eq!(
+            serde_json::to_string(&changes).unwrap(),
+            r#"[{"action":"downgrade","name":"foo","from":"2.0.0","to":"1.0.0"}]"#
+        );
+    }
+}
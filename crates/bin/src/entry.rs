@@ -1,44 +1,62 @@
 use std::{
+    collections::BTreeMap,
     env, fs,
     path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
-use atomic_file_install::atomic_install;
+use atomic_file_install::{atomic_install, FsCompat};
 use binstalk::{
     errors::{BinstallError, CrateContextError},
-    fetchers::{Fetcher, GhCrateMeta, QuickInstall, SignaturePolicy},
-    get_desired_targets,
+    fetchers::{
+        rekor, AllowedSigners, AzureDevOps, Fetcher, GhCrateMeta, Homebrew, PinnedKeys,
+        QuickInstall, SignaturePolicy, Wapm, AZURE_BLOB_AUTH_HEADERS, AZURE_BLOB_DOMAIN,
+        AZURE_DEVOPS_DOMAINS, GCS_DOMAIN,
+    },
+    get_desired_targets, get_desired_targets_cached,
     helpers::{
         jobserver_client::LazyJobserverClient,
         lazy_gh_api_client::LazyGhApiClient,
-        remote::{Certificate, Client},
+        remote::{
+            header::{HeaderMap, HeaderName, HeaderValue},
+            Certificate, Client, CrossHostAuth, Fixtures, RedirectPolicy,
+        },
         tasks::AutoAbortJoinHandle,
     },
     ops::{
         self,
-        resolve::{CrateName, Resolution, ResolutionFetch, VersionReqExt},
+        resolve::{CrateName, Resolution, ResolutionFetch, ResolutionSource, VersionReqExt},
         CargoTomlFetchOverride, Options, Resolver,
     },
     TARGET,
 };
 use binstalk_manifests::{
+    binstall_config::{BinstallConfig, CrossHostRedirectAuth},
     cargo_config::Config,
     cargo_toml_binstall::{PkgOverride, Strategy},
-    crate_info::{CrateInfo, CrateSource},
+    crate_info::{CrateInfo, CrateSource, InstalledCrate},
     crates_manifests::Manifests,
+    rust_toolchain,
+    strategy_stats::{self, StrategyStats},
+    tools_manifest::ToolsManifest,
 };
 use compact_str::CompactString;
 use file_format::FileFormat;
 use home::cargo_home;
 use log::LevelFilter;
-use miette::{miette, Report, Result, WrapErr};
-use semver::Version;
-use tokio::task::block_in_place;
+use miette::{miette, IntoDiagnostic, Report, Result, WrapErr};
+use semver::{Version, VersionReq};
+use tokio::{sync::Semaphore, task::block_in_place};
 use tracing::{debug, error, info, warn};
 
-use crate::{args::Args, gh_token, git_credentials, install_path, ui::confirm};
+use crate::{
+    args::Args,
+    bench, channel, cloud_token, credential_helper, diff, extract, gh_token, git_credentials,
+    hooks::{Event, HookCrate, Hooks},
+    install_path, redact, signal,
+    ui::confirm,
+};
 
 pub fn install_crates(
     args: Args,
@@ -54,6 +72,9 @@ pub fn install_crates(
         .filter_map(|strategy| match strategy.0 {
             Strategy::CrateMetaData => Some(GhCrateMeta::new as Resolver),
             Strategy::QuickInstall => Some(QuickInstall::new as Resolver),
+            Strategy::AzureDevOps => Some(AzureDevOps::new as Resolver),
+            Strategy::Homebrew => Some(Homebrew::new as Resolver),
+            Strategy::Wapm => Some(Wapm::new as Resolver),
             Strategy::Compile => {
                 cargo_install_fallback = true;
                 None
@@ -61,49 +82,274 @@ pub fn install_crates(
         })
         .collect();
 
+    // Honour the toolchain pinned by the current project like rustup would,
+    // since the default toolchain could be too old to build the crates.
+    let compile_toolchain = args.compile_toolchain.or_else(|| {
+        if !cargo_install_fallback {
+            return None;
+        }
+
+        let cwd = env::current_dir().ok()?;
+        rust_toolchain::find_toolchain(&cwd).unwrap_or_else(|err| {
+            warn!("Failed to load rust-toolchain file: {err}");
+            None
+        })
+    });
+
     // Load .cargo/config.toml
     let cargo_home = cargo_home().map_err(BinstallError::from)?;
     let mut config = Config::load_from_path(cargo_home.join("config.toml"))?;
 
+    // Load binstall/config.toml
+    let binstall_config =
+        BinstallConfig::load_from_path(cargo_home.join("binstall").join("config.toml"))?;
+    let compile_config = binstall_config.compile.unwrap_or_default();
+    let hooks = Hooks::new(binstall_config.hooks);
+
+    // Load binstall/pinned-keys
+    let pinned_keys = PinnedKeys::load(cargo_home.join("binstall").join("pinned-keys"))
+        .map_err(BinstallError::from)?;
+
+    // Load binstall/allowed_signers
+    let allowed_signers = AllowedSigners::load(cargo_home.join("binstall").join("allowed_signers"))
+        .map_err(BinstallError::from)?;
+
+    // Load the keys trusted to sign entries of the transparency log
+    let transparency_log_signers = args
+        .verify_transparency_log
+        .then(|| {
+            args.transparency_log_key
+                .iter()
+                .map(|path| {
+                    fs::read_to_string(path)
+                        .into_diagnostic()
+                        .and_then(|pem| rekor::SignerKey::from_pem(&pem).into_diagnostic())
+                        .wrap_err_with(|| format!("Failed to load key {}", path.display()))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+
+    let detected_targets_cache = cargo_home.join("binstall").join("detected-targets");
+
+    // Load binstall/strategy-stats.json, unless the strategies are specified
+    // explicitly, in which case they are run in the order specified.
+    let crate_strategy_order = if cli_overrides.ignore_disabled_strategies || args.bench_resolve {
+        BTreeMap::new()
+    } else {
+        strategy_stats::default_path(&cargo_home)
+            .and_then(StrategyStats::load_from_path)
+            .map(|stats| stats.strategy_order())
+            .unwrap_or_else(|err| {
+                warn!("Failed to load the strategy stats: {err}");
+                BTreeMap::new()
+            })
+    };
+
     // Compute paths
     let cargo_root = args.root;
     let (install_path, mut manifests, temp_dir) = compute_paths_and_load_manifests(
         cargo_root.clone(),
         args.install_path,
         args.no_track,
-        cargo_home,
+        cargo_home.clone(),
         &mut config,
+        args.fs_compat.into(),
     )?;
 
-    // Remove installed crates
-    let mut crate_names =
-        filter_out_installed_crates(args.crate_names, args.force, manifests.as_mut())?.peekable();
+    // Stage the binaries only if the installation is tracked, so that it
+    // is resumed under the lock of the manifests.
+    let staging_dir = manifests
+        .is_some()
+        .then(|| install_path.join(ops::staging::STAGING_DIR));
+
+    if let (Some(manifests), Some(freeze_time)) = (manifests.as_mut(), args.freeze_time) {
+        manifests.freeze_time(freeze_time);
+    }
+
+    // The crate to extract is resolved whether it is installed or not
+    let mut extract_crate = None;
+
+    // Update the crates installed by cargo-binstall in watch mode
+    let crate_names = if args.extract_to.is_some() {
+        extract_crate = args.crate_names.into_iter().next();
+        Vec::new()
+    } else if args.watch && args.crate_names.is_empty() {
+        let manifests = manifests.as_ref().ok_or_else(|| {
+            miette!("Cannot update installed crates without tracking them in the manifests")
+        })?;
+        installed_crate_names(manifests)
+    } else {
+        args.crate_names
+    };
+
+    // Installed crates to compare the tools manifest against in diff mode
+    let diff_installed_crates = if args.diff {
+        Some(
+            manifests
+                .as_mut()
+                .map(Manifests::load_installed_crates)
+                .transpose()?
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
 
-    if crate_names.peek().is_none() {
+    // Remove installed crates
+    let mut crate_names = filter_out_installed_crates(
+        crate_names,
+        args.force || args.bench_resolve || args.diff,
+        args.quiet_unchanged,
+        manifests.as_mut(),
+    )?
+    .peekable();
+
+    if crate_names.peek().is_none() && !args.diff && extract_crate.is_none() {
         debug!("Nothing to do");
         return Ok(None);
     }
 
     // Launch target detection
-    let desired_targets = get_desired_targets(args.targets);
+    let desired_targets =
+        get_desired_targets_cached(args.targets, detected_targets_cache, args.redetect_target);
+    let crate_desired_targets = args
+        .crate_overrides
+        .into_iter()
+        .filter_map(|(name, overrides)| {
+            let targets = overrides.targets?;
+            Some((name, get_desired_targets(Some(targets))))
+        })
+        .collect();
 
     // Initialize reqwest client
     let rate_limit = args.rate_limit;
 
     let mut http = config.http.take();
-
-    let client = Client::new(
-        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
-        args.min_tls_version.map(|v| v.into()),
+    let binstall_http = binstall_config.http.unwrap_or_default();
+
+    let client = Client::from_builder(
+        Client::default_builder(
+            binstall_http.user_agent.as_deref().unwrap_or(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            )),
+            args.min_tls_version.map(|v| v.into()),
+            args.tls_roots.map(|roots| roots.into()),
+            &mut read_root_certs(
+                args.root_certificates,
+                http.as_mut().and_then(|http| http.cainfo.take()),
+            ),
+        )
+        .default_headers(parse_extra_headers(&binstall_http.headers)),
         rate_limit.duration,
         rate_limit.request_count,
-        read_root_certs(
-            args.root_certificates,
-            http.as_mut().and_then(|http| http.cainfo.take()),
-        ),
     )
     .map_err(BinstallError::from)?;
 
+    let redirect_policy = RedirectPolicy::default();
+    client.set_redirect_policy(RedirectPolicy {
+        max_redirects: binstall_http
+            .max_redirects
+            .unwrap_or(redirect_policy.max_redirects),
+        cross_host_auth: match binstall_http.cross_host_redirect_auth {
+            Some(CrossHostRedirectAuth::Strip) => CrossHostAuth::Strip,
+            Some(CrossHostRedirectAuth::Deny) => CrossHostAuth::Deny,
+            None => redirect_policy.cross_host_auth,
+        },
+    });
+
+    if let Some(dir) = args.bench_fixtures {
+        let dir = dir.into();
+        client.set_fixtures(if args.bench_record {
+            Fixtures::Record(dir)
+        } else {
+            Fixtures::Replay(dir)
+        });
+    }
+
+    if let Some(token) = args.azure_devops_token {
+        redact::add_secret(&token.0);
+        for domain in AZURE_DEVOPS_DOMAINS {
+            client.add_basic_auth_for_domain(domain, "", &token.0);
+        }
+    }
+
+    if let (Some(user), Some(api_key)) = (&args.artifactory_user, args.artifactory_api_key) {
+        redact::add_secret(&api_key.0);
+        for host in &args.artifactory_host {
+            client.add_basic_auth_for_domain(host, user, &api_key.0);
+        }
+    }
+
+    for (host, auth) in binstall_config.auth {
+        match (auth.credential_env, auth.credential_helper) {
+            (Some(credential_env), None) => match env::var(&*credential_env) {
+                Ok(credential) => {
+                    redact::add_secret(&credential);
+                    client.add_auth_for_domain(&host, auth.scheme, &credential)
+                }
+                Err(err) => warn!(
+                    "Failed to read credential of {host} from environment variable {credential_env}: {err}"
+                ),
+            },
+            (None, Some(helper)) => {
+                let helper = Arc::new(helper);
+                let domain = host.clone();
+                client.add_lazy_auth_for_domain(&domain, auth.scheme, move || {
+                    let (helper, host) = (helper.clone(), host.clone());
+                    async move {
+                        match credential_helper::get(&helper, &host).await {
+                            Ok(credential) => {
+                                redact::add_secret(&credential);
+                                Some(credential)
+                            }
+                            Err(err) => {
+                                warn!("Failed to retrieve credential of {host} from credential helper: {err}");
+                                None
+                            }
+                        }
+                    }
+                });
+            }
+            _ => warn!(
+                "Ignoring auth of {host}, exactly one of credential-env and credential-helper is required"
+            ),
+        }
+    }
+
+    if !args.no_discover_cloud_credentials {
+        client.add_lazy_bearer_auth_for_domain(GCS_DOMAIN, &[], || async {
+            match cloud_token::get_gcloud_token().await {
+                Ok(token) => {
+                    redact::add_secret(&token);
+                    Some(token)
+                }
+                Err(err) => {
+                    debug!(?err, "Failed to retrieve Google Cloud access token");
+                    None
+                }
+            }
+        });
+        client.add_lazy_bearer_auth_for_domain(
+            AZURE_BLOB_DOMAIN,
+            AZURE_BLOB_AUTH_HEADERS,
+            || async {
+                match cloud_token::get_azure_storage_token().await {
+                    Ok(token) => {
+                        redact::add_secret(&token);
+                        Some(token)
+                    }
+                    Err(err) => {
+                        debug!(?err, "Failed to retrieve Azure Storage access token");
+                        None
+                    }
+                }
+            },
+        );
+    }
+
     let gh_api_client = args
         .github_token
         .map(|token| token.0)
@@ -114,14 +360,20 @@ pub fn install_crates(
                 git_credentials::try_from_home()
             }
         })
-        .map(|token| LazyGhApiClient::new(client.clone(), Some(token)))
+        .map(|token| {
+            redact::add_secret(&token);
+            LazyGhApiClient::new(client.clone(), Some(token))
+        })
         .unwrap_or_else(|| {
             if args.no_discover_github_token {
                 LazyGhApiClient::new(client.clone(), None)
             } else {
                 LazyGhApiClient::with_get_gh_token_future(client.clone(), async {
-                    match gh_token::get().await {
-                        Ok(token) => Some(token),
+                    match gh_token::get("github.com").await {
+                        Ok(token) => {
+                            redact::add_secret(&token);
+                            Some(token)
+                        }
                         Err(err) => {
                             debug!(?err, "Failed to retrieve token from `gh auth token`");
                             debug!("Failed to read git credential file");
@@ -132,12 +384,62 @@ pub fn install_crates(
             }
         });
 
+    let gh_api_client = if args.github_enterprise_host.is_empty() {
+        gh_api_client
+    } else {
+        let hosts = args.github_enterprise_host;
+        let enterprise_token = args.github_enterprise_token.map(|token| token.0);
+        if let Some(token) = &enterprise_token {
+            redact::add_secret(token);
+        }
+        let no_discover_github_token = args.no_discover_github_token;
+
+        gh_api_client.with_enterprise_hosts_future(async move {
+            let mut enterprise_hosts = Vec::with_capacity(hosts.len());
+            for host in hosts {
+                let auth_token = if enterprise_token.is_some() {
+                    enterprise_token.clone()
+                } else if no_discover_github_token {
+                    None
+                } else {
+                    match gh_token::get(&host).await {
+                        Ok(token) => {
+                            redact::add_secret(&token);
+                            Some(token)
+                        }
+                        Err(err) => {
+                            debug!(?err, "Failed to retrieve token for {host}");
+                            None
+                        }
+                    }
+                };
+                enterprise_hosts.push((host, auth_token));
+            }
+            enterprise_hosts
+        })
+    };
+
+    let channel = args
+        .channel
+        .map(|url| {
+            tokio::runtime::Handle::current().block_on(channel::load(
+                &client,
+                url,
+                args.channel_pubkey.as_deref(),
+            ))
+        })
+        .transpose()?;
+
     // Create binstall_opts
     let binstall_opts = Arc::new(Options {
         no_symlinks: args.no_symlinks,
+        install_through_symlinks: args.install_through_symlinks,
+        fs_compat: args.fs_compat.into(),
         dry_run: args.dry_run,
+        show_provenance: !args.dry_run && !args.no_confirm,
         force: args.force,
         quiet: args.log_level == Some(LevelFilter::Off),
+        quiet_unchanged: args.quiet_unchanged,
         locked: args.locked,
         no_track: args.no_track,
 
@@ -155,10 +457,26 @@ pub fn install_crates(
         cli_overrides,
 
         desired_targets,
+        crate_desired_targets,
         resolvers,
+        crate_strategy_order,
         cargo_install_fallback,
-
+        compile_toolchain,
+        cross_compiler: args.cross_compile_with.map(Into::into),
+        compile_rustc_wrapper: compile_config.rustc_wrapper,
+        compile_target_dir: compile_config.target_dir,
+        max_compile_deps: args.max_compile_deps.or(args
+            .no_heavy_compile
+            .then_some(ops::resolve::HEAVY_COMPILE_DEPS)),
+        allow_target_mismatch: args.allow_target_mismatch,
+        allow_downgrade: args.allow_downgrade,
+        explain: args.explain,
+
+        frozen_time: args
+            .freeze_time
+            .map(|freeze_time| SystemTime::UNIX_EPOCH + Duration::from_secs(freeze_time)),
         temp_dir: temp_dir.path().to_owned(),
+        staging_dir,
         install_path,
         cargo_root,
 
@@ -205,35 +523,125 @@ pub fn install_crates(
         } else {
             SignaturePolicy::IfPresent
         },
-        disable_telemetry: args.disable_telemetry,
+        pinned_keys: Arc::new(pinned_keys),
+        doh_resolver: args.doh_resolver,
+        allowed_signers: Arc::new(allowed_signers),
+        transparency_log_signers,
+        channel,
+        disable_telemetry: args.disable_telemetry || args.bench_resolve,
+        low_memory: args.low_memory,
+        low_memory_download: Semaphore::new(1),
 
         maximum_resolution_timeout: Duration::from_secs(
             args.maximum_resolution_timeout.get().into(),
         ),
+        crate_timeout: args
+            .timeout
+            .map(|timeout| Duration::from_secs(timeout.get())),
     });
 
     // Destruct args before any async function to reduce size of the future
     let dry_run = args.dry_run;
     let no_confirm = args.no_confirm;
+    let confirm_timeout = args.confirm_timeout;
     let no_cleanup = args.no_cleanup;
+    let mut failures = Failures::new(args.tools);
+
+    if let Some(current_version) = args.check_self {
+        let (crate_name, _) = crate_names.next().unwrap();
+
+        return Ok(Some(AutoAbortJoinHandle::spawn(async move {
+            let update_check =
+                ops::check_update::check_update(&binstall_opts, crate_name, current_version)
+                    .await?;
+            println!(
+                "{}",
+                serde_json::to_string(&update_check)
+                    .expect("the result of an update check is always serializable")
+            );
+            drop(temp_dir);
+            Ok(())
+        })));
+    }
 
-    // Resolve crates
-    let tasks: Vec<_> = crate_names
-        .map(|(crate_name, current_version)| {
-            AutoAbortJoinHandle::spawn(ops::resolve::resolve(
-                binstall_opts.clone(),
+    if let Some(installed_crates) = diff_installed_crates {
+        let crate_names = crate_names.map(|(crate_name, _)| crate_name).collect();
+        let json = args.json;
+
+        return Ok(Some(AutoAbortJoinHandle::spawn(async move {
+            let res = diff::diff(binstall_opts, crate_names, installed_crates, json).await;
+            drop(temp_dir);
+            Ok(res?)
+        })));
+    }
+
+    if args.bench_resolve {
+        let crate_names = crate_names.map(|(crate_name, _)| crate_name).collect();
+        let iterations = args.bench_iterations;
+
+        return Ok(Some(AutoAbortJoinHandle::spawn(async move {
+            let res = bench::bench_resolve(binstall_opts, crate_names, iterations).await;
+            // Keep temp_dir alive until the benchmark is done
+            drop(temp_dir);
+            Ok(res?)
+        })));
+    }
+
+    if let (Some(dest), Some(crate_name)) = (args.extract_to, extract_crate) {
+        let name = crate_name.name.clone();
+
+        return Ok(Some(AutoAbortJoinHandle::spawn(async move {
+            let fs_compat = binstall_opts.fs_compat;
+            let Resolution::Fetch(fetch) =
+                ops::resolve::resolve(binstall_opts, crate_name, None).await?
+            else {
+                return Err(miette!("No pre-built binary of {name} is found"));
+            };
+            let res = block_in_place(|| extract::extract_to(*fetch, &dest, fs_compat));
+            drop(temp_dir);
+            res
+        })));
+    }
+
+    let crate_names: Vec<_> = crate_names.collect();
+
+    // Run the pre-resolve hook
+    let requested: Vec<_> = crate_names
+        .iter()
+        .map(|(crate_name, installed)| {
+            HookCrate::requested(
                 crate_name,
-                current_version,
-            ))
+                installed.as_ref().map(|installed| &installed.version),
+            )
         })
         .collect();
+    tokio::runtime::Handle::current().block_on(hooks.run(
+        Event::PreResolve,
+        dry_run,
+        &requested,
+    ))?;
+
+    // Resolve crates
+    let tasks: Vec<_> = if crate_names.len() >= ops::resolve::LARGE_BATCH {
+        ops::resolve::resolve_batch(binstall_opts.clone(), crate_names)
+    } else {
+        crate_names
+            .into_iter()
+            .map(|(crate_name, installed)| {
+                AutoAbortJoinHandle::spawn(ops::resolve::resolve(
+                    binstall_opts.clone(),
+                    crate_name,
+                    installed,
+                ))
+            })
+            .collect()
+    };
 
     Ok(Some(if args.continue_on_failure {
         AutoAbortJoinHandle::spawn(async move {
             // Collect results
             let mut resolution_fetchs = Vec::new();
             let mut resolution_sources = Vec::new();
-            let mut errors = Vec::new();
 
             for task in tasks {
                 match task.flattened_join().await {
@@ -246,13 +654,16 @@ pub fn install_crates(
                         source.print();
                         resolution_sources.push(source)
                     }
-                    Err(BinstallError::CrateContext(err)) => errors.push(err),
+                    Err(BinstallError::CrateContext(err)) => failures.push(err),
                     Err(e) => panic!("Expected BinstallError::CrateContext(_), got {}", e),
                 }
             }
 
+            resolution_fetchs.retain(|fetch| failures.should_install(&fetch.name));
+            resolution_sources.retain(|source| failures.should_install(&source.name));
+
             if resolution_fetchs.is_empty() && resolution_sources.is_empty() {
-                return if let Some(err) = BinstallError::crate_errors(errors) {
+                return if let Some(err) = BinstallError::crate_errors(failures.errors) {
                     Err(err.into())
                 } else {
                     debug!("Nothing to do");
@@ -262,8 +673,8 @@ pub fn install_crates(
 
             // Confirm
             if !dry_run && !no_confirm {
-                if let Err(abort_err) = confirm().await {
-                    return if let Some(err) = BinstallError::crate_errors(errors) {
+                if let Err(abort_err) = confirm(confirm_timeout).await {
+                    return if let Some(err) = BinstallError::crate_errors(failures.errors) {
                         Err(Report::new(abort_err).wrap_err(err))
                     } else {
                         Err(abort_err.into())
@@ -271,30 +682,91 @@ pub fn install_crates(
                 }
             }
 
-            let manifest_update_res = do_install_fetches_continue_on_failure(
-                resolution_fetchs,
-                manifests,
-                &binstall_opts,
-                dry_run,
-                temp_dir,
-                no_cleanup,
-                &mut errors,
-            );
+            failures.sort(&mut resolution_fetchs, |fetch| &fetch.name);
+            failures.sort(&mut resolution_sources, |source| &source.name);
+
+            if let Err(hook_err) = hooks
+                .run(
+                    Event::PreInstall,
+                    dry_run,
+                    &resolved_hook_crates(&resolution_fetchs, &resolution_sources),
+                )
+                .await
+            {
+                return if let Some(err) = BinstallError::crate_errors(failures.errors) {
+                    Err(hook_err.wrap_err(err))
+                } else {
+                    Err(hook_err)
+                };
+            }
+
+            let strategies = fetched_strategies(&resolution_fetchs);
+
+            let mut installed = Vec::new();
+            let (manifest_update_res, resolution_sources) = if failures.has_tools() {
+                let res = install_tools(
+                    resolution_fetchs,
+                    resolution_sources,
+                    manifests,
+                    &binstall_opts,
+                    dry_run,
+                    temp_dir,
+                    no_cleanup,
+                    true,
+                    &mut failures,
+                    &mut installed,
+                )
+                .await;
+                (res, Vec::new())
+            } else {
+                let res = do_install_fetches_continue_on_failure(
+                    resolution_fetchs,
+                    manifests,
+                    &binstall_opts,
+                    dry_run,
+                    temp_dir,
+                    no_cleanup,
+                    &mut failures,
+                    &mut installed,
+                );
+                (res, resolution_sources)
+            };
+            if !dry_run {
+                record_strategies(&cargo_home, strategies, &installed);
+            }
+            if signal::is_cancelled() {
+                if !dry_run {
+                    hooks.run(Event::PostInstall, dry_run, &installed).await?;
+                }
+                return manifest_update_res.and(Err(BinstallError::UserAbort.into()));
+            }
 
             let tasks: Vec<_> = resolution_sources
                 .into_iter()
-                .map(|source| AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())))
+                .map(|source| {
+                    (
+                        HookCrate::source(&source),
+                        AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())),
+                    )
+                })
                 .collect();
 
-            for task in tasks {
+            for (hook_crate, task) in tasks {
                 match task.flattened_join().await {
-                    Ok(_) => (),
-                    Err(BinstallError::CrateContext(err)) => errors.push(err),
+                    Ok(_) => installed.push(hook_crate),
+                    Err(BinstallError::CrateContext(err)) => failures.push(err),
                     Err(e) => panic!("Expected BinstallError::CrateContext(_), got {}", e),
                 }
             }
 
-            match (BinstallError::crate_errors(errors), manifest_update_res) {
+            if !dry_run {
+                hooks.run(Event::PostInstall, dry_run, &installed).await?;
+            }
+
+            match (
+                BinstallError::crate_errors(failures.errors),
+                manifest_update_res,
+            ) {
                 (None, Ok(())) => Ok(()),
                 (None, Err(err)) => Err(err),
                 (Some(err), Ok(())) => Err(err.into()),
@@ -330,32 +802,139 @@ pub fn install_crates(
 
             // Confirm
             if !dry_run && !no_confirm {
-                confirm().await?;
+                confirm(confirm_timeout).await?;
             }
 
-            do_install_fetches(
-                resolution_fetchs,
-                manifests,
-                &binstall_opts,
-                dry_run,
-                temp_dir,
-                no_cleanup,
-            )?;
+            failures.sort(&mut resolution_fetchs, |fetch| &fetch.name);
+            failures.sort(&mut resolution_sources, |source| &source.name);
+
+            hooks
+                .run(
+                    Event::PreInstall,
+                    dry_run,
+                    &resolved_hook_crates(&resolution_fetchs, &resolution_sources),
+                )
+                .await?;
+
+            let strategies = fetched_strategies(&resolution_fetchs);
+
+            let mut installed = Vec::new();
+            let res = async {
+                if failures.has_tools() {
+                    install_tools(
+                        resolution_fetchs,
+                        resolution_sources,
+                        manifests,
+                        &binstall_opts,
+                        dry_run,
+                        temp_dir,
+                        no_cleanup,
+                        false,
+                        &mut failures,
+                        &mut installed,
+                    )
+                    .await?;
+                    if signal::is_cancelled() {
+                        return Err(BinstallError::UserAbort.into());
+                    }
 
-            let tasks: Vec<_> = resolution_sources
-                .into_iter()
-                .map(|source| AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())))
-                .collect();
+                    return Ok(());
+                }
 
-            for task in tasks {
-                task.await??;
+                do_install_fetches(
+                    resolution_fetchs,
+                    manifests,
+                    &binstall_opts,
+                    dry_run,
+                    temp_dir,
+                    no_cleanup,
+                    &mut installed,
+                )?;
+                if signal::is_cancelled() {
+                    return Err(BinstallError::UserAbort.into());
+                }
+
+                let tasks: Vec<_> = resolution_sources
+                    .into_iter()
+                    .map(|source| {
+                        (
+                            HookCrate::source(&source),
+                            AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())),
+                        )
+                    })
+                    .collect();
+
+                for (hook_crate, task) in tasks {
+                    task.await??;
+                    installed.push(hook_crate);
+                }
+
+                Ok::<_, Report>(())
             }
+            .await;
 
-            Ok(())
+            // Report the crates installed before any failure.
+            if !dry_run {
+                record_strategies(&cargo_home, strategies, &installed);
+                hooks.run(Event::PostInstall, dry_run, &installed).await?;
+            }
+
+            res
         })
     }))
 }
 
+/// Return the strategies which fetched the crates, to record them once the
+/// crates are installed.
+fn fetched_strategies(
+    resolution_fetchs: &[Box<ResolutionFetch>],
+) -> Vec<(CompactString, Strategy)> {
+    resolution_fetchs
+        .iter()
+        .map(|fetch| (fetch.name.clone(), fetch.fetcher.strategy()))
+        .collect()
+}
+
+/// Record the strategies which fetched the crates `installed` in the
+/// strategy stats, to try them first next time.
+fn record_strategies(
+    cargo_home: &Path,
+    strategies: Vec<(CompactString, Strategy)>,
+    installed: &[HookCrate],
+) {
+    let strategies: Vec<_> = strategies
+        .into_iter()
+        .filter(|(name, _)| installed.iter().any(|hook_crate| hook_crate.name() == name))
+        .collect();
+    if strategies.is_empty() {
+        return;
+    }
+
+    let now = SystemTime::now();
+    let res = block_in_place(|| {
+        let mut stats = StrategyStats::load_from_path(strategy_stats::default_path(cargo_home)?)?;
+        for (name, strategy) in strategies {
+            stats.record_success(&name, strategy, now);
+        }
+        stats.overwrite()
+    });
+
+    if let Err(err) = res {
+        warn!("Failed to record the strategies in the strategy stats: {err}");
+    }
+}
+
+fn resolved_hook_crates(
+    resolution_fetchs: &[Box<ResolutionFetch>],
+    resolution_sources: &[ResolutionSource],
+) -> Vec<HookCrate> {
+    resolution_fetchs
+        .iter()
+        .map(|fetch| HookCrate::fetch(fetch))
+        .chain(resolution_sources.iter().map(HookCrate::source))
+        .collect()
+}
+
 fn do_read_root_cert(path: &Path) -> Result<Option<Certificate>, BinstallError> {
     use std::io::{Read, Seek};
 
@@ -384,6 +963,29 @@ fn do_read_root_cert(path: &Path) -> Result<Option<Certificate>, BinstallError>
     open_cert(&buffer).map_err(From::from).map(Some)
 }
 
+/// Invalid headers are ignored with a warning.
+fn parse_extra_headers(headers: &BTreeMap<CompactString, CompactString>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(mut value)) => {
+                    // Headers may carry credentials of internal mirrors.
+                    value.set_sensitive(true);
+                    Some((name, value))
+                }
+                _ => {
+                    warn!("Ignoring invalid http header {name} in binstall config");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
 fn read_root_certs(
     root_certificate_paths: Vec<PathBuf>,
     config_cainfo: Option<PathBuf>,
@@ -410,6 +1012,7 @@ fn compute_paths_and_load_manifests(
     no_track: bool,
     cargo_home: PathBuf,
     config: &mut Config,
+    fs_compat: FsCompat,
 ) -> Result<(PathBuf, Option<Manifests>, tempfile::TempDir)> {
     // Compute cargo_roots
     let cargo_roots =
@@ -432,7 +1035,20 @@ fn compute_paths_and_load_manifests(
 
     // Load manifests
     let manifests = if !no_manifests {
-        Some(Manifests::open_exclusive(&cargo_roots)?)
+        let manifests = Manifests::open_exclusive(&cargo_roots)?;
+
+        // Finish the installations interrupted after staging their binaries
+        let staging_dir = install_path.join(ops::staging::STAGING_DIR);
+        let resumed = ops::staging::resume(&staging_dir, fs_compat)?;
+        if resumed.is_empty() {
+            Some(manifests)
+        } else {
+            let names: Vec<_> = resumed.iter().map(|info| info.name.clone()).collect();
+            manifests.update(resumed)?;
+            unstage(&staging_dir, &names);
+
+            Some(Manifests::open_exclusive(&cargo_roots)?)
+        }
     } else {
         None
     };
@@ -451,12 +1067,43 @@ fn compute_paths_and_load_manifests(
     Ok((install_path, manifests, temp_dir))
 }
 
-/// Return vec of (crate_name, current_version)
+/// Remove the staged binaries of crates `names` once they are recorded in
+/// the manifests.
+fn unstage(staging_dir: &Path, names: &[CompactString]) {
+    for name in names {
+        ops::staging::unstage(staging_dir, name).unwrap_or_else(|err| {
+            warn!("Failed to clean up the staged binaries of {name}: {err}");
+        });
+    }
+}
+
+/// Return the crates installed by cargo-binstall, restricted to the version
+/// requirements they are pinned to.
+fn installed_crate_names(manifests: &Manifests) -> Vec<CrateName> {
+    manifests
+        .binstall_crates()
+        .into_iter()
+        .map(|data| {
+            let name = data.crate_info.name.clone();
+            let version_req = data.pin.as_ref().and_then(|pin| {
+                VersionReq::parse(&pin.version_req)
+                    .map_err(|err| {
+                        warn!("Ignoring invalid pin {} of {name}: {err}", pin.version_req)
+                    })
+                    .ok()
+            });
+
+            CrateName { name, version_req }
+        })
+        .collect()
+}
+
 fn filter_out_installed_crates(
     crate_names: Vec<CrateName>,
     force: bool,
+    quiet_unchanged: bool,
     manifests: Option<&mut Manifests>,
-) -> Result<impl Iterator<Item = (CrateName, Option<semver::Version>)> + '_> {
+) -> Result<impl Iterator<Item = (CrateName, Option<InstalledCrate>)> + '_> {
     let mut installed_crates = manifests
         .map(Manifests::load_installed_crates)
         .transpose()?;
@@ -465,7 +1112,7 @@ fn filter_out_installed_crates(
     .filter_map(move |crate_name| {
         let name = &crate_name.name;
 
-        let curr_version = installed_crates
+        let installed = installed_crates
             .as_mut()
             // Since crate_name is deduped, every entry of installed_crates
             // can be visited at most once.
@@ -475,20 +1122,25 @@ fn filter_out_installed_crates(
 
         match (
             force,
-            curr_version,
+            installed,
             &crate_name.version_req,
         ) {
-            (false, Some(curr_version), Some(version_req))
-                if version_req.is_latest_compatible(&curr_version) =>
+            (false, Some(installed), Some(version_req))
+                if version_req.is_latest_compatible(&installed.version) =>
             {
                 debug!("Bailing out early because we can assume wanted is already installed from metafile");
-                info!("{name} v{curr_version} is already installed, use --force to override");
+                if quiet_unchanged {
+                    debug!("{name} v{} is already installed", installed.version);
+                } else {
+                    info!("{name} v{} is already installed, use --force to override", installed.version);
+                }
                 None
             }
 
-            // The version req is "*" thus a remote upgraded version could exist
-            (false, Some(curr_version), None) => {
-                Some((crate_name, Some(curr_version)))
+            // Either a remote upgraded version could exist or the version req
+            // could resolve to an older version, which is checked when resolving.
+            (false, Some(installed), _) => {
+                Some((crate_name, Some(installed)))
             }
 
             _ => Some((crate_name, None)),
@@ -496,6 +1148,77 @@ fn filter_out_installed_crates(
     }))
 }
 
+/// Errors of the crates which failed to install, and the tools of
+/// `--tools-manifest` which are not installed as a result.
+#[allow(clippy::vec_box)]
+struct Failures {
+    errors: Vec<Box<CrateContextError>>,
+    tools: Option<ToolsManifest>,
+    /// Names of the tools, in installation order.
+    install_order: Vec<CompactString>,
+    /// Maps the tools not to install to the required tool which failed.
+    blocked: BTreeMap<CompactString, CompactString>,
+}
+
+impl Failures {
+    fn new(tools: Option<ToolsManifest>) -> Self {
+        let install_order = tools
+            .as_ref()
+            .and_then(|tools| tools.install_order().ok())
+            .map(|order| order.into_iter().cloned().collect())
+            .unwrap_or_default();
+
+        Self {
+            errors: Vec::new(),
+            tools,
+            install_order,
+            blocked: BTreeMap::new(),
+        }
+    }
+
+    fn has_tools(&self) -> bool {
+        self.tools.is_some()
+    }
+
+    fn push(&mut self, err: Box<CrateContextError>) {
+        if let Some(tools) = &self.tools {
+            for dependent in tools.dependents(err.crate_name()) {
+                self.blocked
+                    .entry(dependent.into())
+                    .or_insert_with(|| err.crate_name().into());
+            }
+        }
+        self.errors.push(err);
+    }
+
+    /// Return false and record the error if `name` requires a tool which
+    /// failed to install.
+    fn should_install(&mut self, name: &str) -> bool {
+        let Some(failed) = self.blocked.get(name) else {
+            return true;
+        };
+
+        warn!("Skipping {name}, as required tool {failed} failed to install");
+        let err = BinstallError::RequiredToolFailed(failed.clone()).crate_context(name);
+        let BinstallError::CrateContext(err) = err else {
+            unreachable!()
+        };
+        self.errors.push(err);
+
+        false
+    }
+
+    /// Sort `items` in installation order of the tools.
+    fn sort<T>(&self, items: &mut [T], name: impl Fn(&T) -> &CompactString) {
+        if self.tools.is_some() {
+            items.sort_by_key(|item| {
+                let name = name(item);
+                self.install_order.iter().position(|tool| tool == name)
+            });
+        }
+    }
+}
+
 #[allow(clippy::vec_box)]
 fn do_install_fetches(
     resolution_fetchs: Vec<Box<ResolutionFetch>>,
@@ -505,6 +1228,7 @@ fn do_install_fetches(
     dry_run: bool,
     temp_dir: tempfile::TempDir,
     no_cleanup: bool,
+    installed: &mut Vec<HookCrate>,
 ) -> Result<()> {
     if resolution_fetchs.is_empty() {
         return Ok(());
@@ -516,29 +1240,33 @@ fn do_install_fetches(
     }
 
     block_in_place(|| {
-        let metadata_vec = resolution_fetchs
-            .into_iter()
-            .map(|fetch| fetch.install(binstall_opts))
-            .collect::<Result<Vec<_>, BinstallError>>()?;
+        let _defer_cancellation = signal::defer_cancellation();
+
+        let count = resolution_fetchs.len();
+        let mut metadata_vec = Vec::with_capacity(count);
+        for (i, fetch) in resolution_fetchs.into_iter().enumerate() {
+            // Only stop between crates, so that the crates installed are
+            // recorded in the manifests.
+            if signal::is_cancelled() {
+                warn!("Not installing the remaining {} crates", count - i);
+                break;
+            }
+            metadata_vec.push(fetch.install(binstall_opts)?);
+        }
+
+        installed.extend(metadata_vec.iter().map(HookCrate::installed));
 
         if let Some(manifests) = manifests {
-            manifests.update(metadata_vec)?;
+            update_manifests(manifests, metadata_vec, binstall_opts)?;
         }
 
-        if no_cleanup {
-            // Consume temp_dir without removing it from fs.
-            let _ = temp_dir.keep();
-        } else {
-            temp_dir.close().unwrap_or_else(|err| {
-                warn!("Failed to clean up some resources: {err}");
-            });
-        }
+        clean_up_temp_dir(temp_dir, no_cleanup);
 
         Ok(())
     })
 }
 
-#[allow(clippy::vec_box)]
+#[allow(clippy::vec_box, clippy::too_many_arguments)]
 fn do_install_fetches_continue_on_failure(
     resolution_fetchs: Vec<Box<ResolutionFetch>>,
     // Take manifests by value to drop the `FileLock`.
@@ -547,7 +1275,8 @@ fn do_install_fetches_continue_on_failure(
     dry_run: bool,
     temp_dir: tempfile::TempDir,
     no_cleanup: bool,
-    errors: &mut Vec<Box<CrateContextError>>,
+    failures: &mut Failures,
+    installed: &mut Vec<HookCrate>,
 ) -> Result<()> {
     if resolution_fetchs.is_empty() {
         return Ok(());
@@ -559,35 +1288,181 @@ fn do_install_fetches_continue_on_failure(
     }
 
     block_in_place(|| {
-        let metadata_vec = resolution_fetchs
-            .into_iter()
-            .filter_map(|fetch| match fetch.install(binstall_opts) {
-                Ok(crate_info) => Some(crate_info),
-                Err(BinstallError::CrateContext(err)) => {
-                    errors.push(err);
-                    None
-                }
+        let _defer_cancellation = signal::defer_cancellation();
+
+        let count = resolution_fetchs.len();
+        let mut metadata_vec = Vec::with_capacity(count);
+        for (i, fetch) in resolution_fetchs.into_iter().enumerate() {
+            // Only stop between crates, so that the crates installed are
+            // recorded in the manifests.
+            if signal::is_cancelled() {
+                warn!("Not installing the remaining {} crates", count - i);
+                break;
+            }
+            if !failures.should_install(&fetch.name) {
+                continue;
+            }
+            match fetch.install(binstall_opts) {
+                Ok(crate_info) => metadata_vec.push(crate_info),
+                Err(BinstallError::CrateContext(err)) => failures.push(err),
                 Err(e) => panic!("Expected BinstallError::CrateContext(_), got {}", e),
-            })
-            .collect::<Vec<_>>();
+            }
+        }
+
+        installed.extend(metadata_vec.iter().map(HookCrate::installed));
 
         if let Some(manifests) = manifests {
-            manifests.update(metadata_vec)?;
+            update_manifests(manifests, metadata_vec, binstall_opts)?;
         }
 
-        if no_cleanup {
-            // Consume temp_dir without removing it from fs.
-            let _ = temp_dir.keep();
-        } else {
-            temp_dir.close().unwrap_or_else(|err| {
-                warn!("Failed to clean up some resources: {err}");
-            });
-        }
+        clean_up_temp_dir(temp_dir, no_cleanup);
 
         Ok(())
     })
 }
 
+/// Install the tools of `--tools-manifest` one at a time, each one after the
+/// tools it requires and not at all if any of them fails, whether it is
+/// fetched or built from source.
+#[allow(clippy::vec_box, clippy::too_many_arguments)]
+async fn install_tools(
+    resolution_fetchs: Vec<Box<ResolutionFetch>>,
+    resolution_sources: Vec<ResolutionSource>,
+    // Take manifests by value to drop the `FileLock`.
+    mut manifests: Option<Manifests>,
+    binstall_opts: &Arc<Options>,
+    dry_run: bool,
+    temp_dir: tempfile::TempDir,
+    no_cleanup: bool,
+    continue_on_failure: bool,
+    failures: &mut Failures,
+    installed: &mut Vec<HookCrate>,
+) -> Result<()> {
+    let mut resolutions: Vec<_> = resolution_fetchs
+        .into_iter()
+        .map(Resolution::Fetch)
+        .chain(
+            resolution_sources
+                .into_iter()
+                .map(Resolution::InstallFromSource),
+        )
+        .collect();
+    failures.sort(&mut resolutions, |resolution| match resolution {
+        Resolution::Fetch(fetch) => &fetch.name,
+        Resolution::InstallFromSource(source) => &source.name,
+        Resolution::AlreadyUpToDate => unreachable!(),
+    });
+
+    if dry_run {
+        info!("Dry-run: Not proceeding to install fetched binaries");
+    }
+
+    let cargo_roots = manifests
+        .as_ref()
+        .map(|manifests| manifests.cargo_roots().to_owned());
+    let mut metadata_vec = Vec::new();
+    let mut res = Ok(());
+
+    let count = resolutions.len();
+    for (i, resolution) in resolutions.into_iter().enumerate() {
+        // Only stop between crates, so that the crates installed are
+        // recorded in the manifests.
+        if signal::is_cancelled() {
+            warn!("Not installing the remaining {} crates", count - i);
+            break;
+        }
+
+        let install_res = match resolution {
+            Resolution::Fetch(fetch) => {
+                if dry_run || !failures.should_install(&fetch.name) {
+                    continue;
+                }
+                block_in_place(|| {
+                    let _defer_cancellation = signal::defer_cancellation();
+                    fetch.install(binstall_opts)
+                })
+                .map(|crate_info| {
+                    installed.push(HookCrate::installed(&crate_info));
+                    metadata_vec.push(crate_info);
+                })
+            }
+            Resolution::InstallFromSource(source) => {
+                if !failures.should_install(&source.name) {
+                    continue;
+                }
+                // `cargo install` takes the lock of the manifests, so record
+                // the tools fetched so far and release it first.
+                if let Some(manifests) = manifests.take() {
+                    let metadata_vec = std::mem::take(&mut metadata_vec);
+                    if let Err(err) =
+                        block_in_place(|| update_manifests(manifests, metadata_vec, binstall_opts))
+                    {
+                        res = Err(err);
+                        break;
+                    }
+                }
+                let hook_crate = HookCrate::source(&source);
+                source
+                    .install(binstall_opts.clone())
+                    .await
+                    .map(|()| installed.push(hook_crate))
+            }
+            Resolution::AlreadyUpToDate => continue,
+        };
+
+        match install_res {
+            Ok(()) => (),
+            Err(BinstallError::CrateContext(err)) if continue_on_failure => failures.push(err),
+            Err(err) => {
+                res = Err(err.into());
+                break;
+            }
+        }
+    }
+
+    let update_res = block_in_place(|| {
+        if metadata_vec.is_empty() {
+            return Ok(());
+        }
+        let manifests = match (manifests, cargo_roots) {
+            (Some(manifests), _) => manifests,
+            (None, Some(cargo_roots)) => Manifests::open_exclusive(&cargo_roots)?,
+            (None, None) => return Ok(()),
+        };
+        update_manifests(manifests, metadata_vec, binstall_opts)
+    });
+
+    clean_up_temp_dir(temp_dir, no_cleanup);
+
+    res.and(update_res)
+}
+
+/// Record the crates installed in the manifests.
+fn update_manifests(
+    manifests: Manifests,
+    metadata_vec: Vec<CrateInfo>,
+    binstall_opts: &Options,
+) -> Result<()> {
+    let names: Vec<_> = metadata_vec.iter().map(|info| info.name.clone()).collect();
+    manifests.update(metadata_vec)?;
+    if let Some(staging_dir) = &binstall_opts.staging_dir {
+        unstage(staging_dir, &names);
+    }
+
+    Ok(())
+}
+
+fn clean_up_temp_dir(temp_dir: tempfile::TempDir, no_cleanup: bool) {
+    if no_cleanup {
+        // Consume temp_dir without removing it from fs.
+        let _ = temp_dir.keep();
+    } else {
+        temp_dir.close().unwrap_or_else(|err| {
+            warn!("Failed to clean up some resources: {err}");
+        });
+    }
+}
+
 pub fn self_install(args: Args) -> Result<()> {
     // Load .cargo/config.toml
     let cargo_home = cargo_home().map_err(BinstallError::from)?;
@@ -601,6 +1476,7 @@ pub fn self_install(args: Args) -> Result<()> {
         args.no_track,
         cargo_home,
         &mut config,
+        args.fs_compat.into(),
     )?;
 
     let mut dest = install_path.join("cargo-binstall");
@@ -608,8 +1484,12 @@ pub fn self_install(args: Args) -> Result<()> {
         assert!(dest.set_extension("exe"));
     }
 
-    atomic_install(&env::current_exe().map_err(BinstallError::from)?, &dest)
-        .map_err(BinstallError::from)?;
+    atomic_install(
+        &env::current_exe().map_err(BinstallError::from)?,
+        &dest,
+        args.fs_compat.into(),
+    )
+    .map_err(BinstallError::from)?;
 
     if let Some(manifests) = manifests {
         manifests.update(vec![CrateInfo {
@@ -623,8 +1503,56 @@ pub fn self_install(args: Args) -> Result<()> {
             source: CrateSource::cratesio_registry(),
             target: CompactString::const_new(TARGET),
             bins: vec![CompactString::const_new("cargo-binstall")],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
         }])?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_filter_out_installed_crates() {
+        let dir = TempDir::new().unwrap();
+
+        Manifests::open_exclusive(dir.path())
+            .unwrap()
+            .update(vec![CrateInfo {
+                name: "foo".into(),
+                version_req: "*".into(),
+                current_version: Version::new(2, 0, 0),
+                source: CrateSource::cratesio_registry(),
+                target: TARGET.into(),
+                bins: vec!["foo".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
+            }])
+            .unwrap();
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+
+        let mut filter = |force| {
+            let crate_names = vec![CrateName {
+                name: "foo".into(),
+                version_req: Some(VersionReq::parse("=1.0.0").unwrap()),
+            }];
+            filter_out_installed_crates(crate_names, force, false, Some(&mut manifests))
+                .unwrap()
+                .map(|(_, installed)| installed.map(|installed| installed.version))
+                .collect::<Vec<_>>()
+        };
+
+        // The installed version is passed on, so that resolving `foo@1.0.0`
+        // refuses to downgrade it.
+        assert_eq!(filter(false), [Some(Version::new(2, 0, 0))]);
+        // Unless --force is given.
+        assert_eq!(filter(true), [None]);
+    }
+}
@@ -0,0 +1,67 @@
+//! `cargo binstall --extract-to <path>`, which extracts the binaries of a
+//! crate to a path or to stdout instead of installing them.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use atomic_file_install::FsCompat;
+use binstalk::{errors::BinstallError, ops::resolve::ResolutionFetch};
+use miette::{miette, Result};
+use tracing::info;
+
+/// Extract the binaries of `fetch`, already downloaded and verified, to
+/// `dest`, or write its binary to stdout if `dest` is `-`.
+///
+/// `dest` is the binary itself if the crate has only one binary and `dest`
+/// is not a directory, or else the directory to extract the binaries to.
+pub(crate) fn extract_to(fetch: ResolutionFetch, dest: &Path, fs_compat: FsCompat) -> Result<()> {
+    let name = &fetch.name;
+    let mut bin_files = fetch.bin_files;
+
+    if dest == Path::new("-") {
+        let [bin_file] = &bin_files[..] else {
+            return Err(miette!(
+                "{name} has {} binaries, which cannot all be written to stdout",
+                bin_files.len()
+            ));
+        };
+
+        let mut stdout = io::stdout().lock();
+        io::copy(
+            &mut fs::File::open(&bin_file.source).map_err(BinstallError::from)?,
+            &mut stdout,
+        )
+        .and_then(|_| stdout.flush())
+        .map_err(BinstallError::from)?;
+
+        return Ok(());
+    }
+
+    let is_dir = bin_files.len() > 1 || dest.is_dir();
+    if is_dir {
+        fs::create_dir_all(dest).map_err(BinstallError::from)?;
+    }
+
+    for bin_file in &mut bin_files {
+        if is_dir {
+            let file_name = bin_file.dest.file_name().unwrap_or_default().to_owned();
+            bin_file.dest = dest.join(file_name);
+        } else {
+            bin_file.dest = dest.to_owned();
+        }
+
+        bin_file
+            .install_bin(fs_compat)
+            .map_err(BinstallError::from)?;
+        info!(
+            "Extracted {} to {}",
+            bin_file.base_name,
+            bin_file.dest.display()
+        );
+    }
+
+    Ok(())
+}
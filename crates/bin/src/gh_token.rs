@@ -7,9 +7,11 @@ use std::{
 use tokio::{io::AsyncWriteExt, process::Command};
 use zeroize::{Zeroize, Zeroizing};
 
-pub(super) async fn get() -> io::Result<Zeroizing<Box<str>>> {
+/// Retrieve the token for `host`, e.g. `github.com` or the host of a
+/// GitHub Enterprise Server.
+pub(super) async fn get(host: &str) -> io::Result<Zeroizing<Box<str>>> {
     let output = Command::new("gh")
-        .args(["auth", "token"])
+        .args(["auth", "token", "--hostname", host])
         .stdout_with_optional_input(None)
         .await?;
 
@@ -19,7 +21,7 @@ pub(super) async fn get() -> io::Result<Zeroizing<Box<str>>> {
 
     Command::new("git")
         .args(["credential", "fill"])
-        .stdout_with_optional_input(Some("host=github.com\nprotocol=https".as_bytes()))
+        .stdout_with_optional_input(Some(format!("host={host}\nprotocol=https").as_bytes()))
         .await?
         .lines()
         .find_map(|line| {
@@ -30,18 +32,39 @@ pub(super) async fn get() -> io::Result<Zeroizing<Box<str>>> {
         .ok_or_else(|| io::Error::other("Password not found in `git credential fill` output"))
 }
 
-trait CommandExt {
+pub(super) trait CommandExt {
     // Helper function to execute a command, optionally with input
     async fn stdout_with_optional_input(
         &mut self,
         input: Option<&[u8]>,
     ) -> io::Result<Zeroizing<Box<str>>>;
+
+    /// Same as [`CommandExt::stdout_with_optional_input`], but the output
+    /// is not trimmed.
+    async fn untrimmed_stdout_with_optional_input(
+        &mut self,
+        input: Option<&[u8]>,
+    ) -> io::Result<Zeroizing<Box<str>>>;
 }
 
 impl CommandExt for Command {
     async fn stdout_with_optional_input(
         &mut self,
         input: Option<&[u8]>,
+    ) -> io::Result<Zeroizing<Box<str>>> {
+        let output = self.untrimmed_stdout_with_optional_input(input).await?;
+        let trimmed = output.trim();
+
+        Ok(if trimmed.len() == output.len() {
+            output
+        } else {
+            Zeroizing::new(trimmed.into())
+        })
+    }
+
+    async fn untrimmed_stdout_with_optional_input(
+        &mut self,
+        input: Option<&[u8]>,
     ) -> io::Result<Zeroizing<Box<str>>> {
         self.stdout(Stdio::piped())
             .stderr(Stdio::null())
@@ -71,13 +94,7 @@ impl CommandExt for Command {
                 io::Error::new(io::ErrorKind::InvalidData, msg)
             })?;
 
-            let trimmed = s.trim();
-
-            Ok(if trimmed.len() == s.len() {
-                Zeroizing::new(s.into_boxed_str())
-            } else {
-                Zeroizing::new(trimmed.into())
-            })
+            Ok(Zeroizing::new(s.into_boxed_str()))
         } else {
             zeroize_and_drop(stdout);
 
@@ -0,0 +1,266 @@
+//! Hooks run on install events, configured by the `[hooks]` table of the
+//! binstall config, e.g. to integrate binstall with an inventory system:
+//!
+//! ```toml
+//! [hooks]
+//! pre-install = ["inventory", "check"]
+//! post-install = ["inventory", "record"]
+//! ```
+//!
+//! Each hook is given a JSON description of the operation on stdin:
+//!
+//! ```json
+//! {"event":"post-install","dry_run":false,"crates":[{"name":"ripgrep","version":"14.1.0","target":"x86_64-unknown-linux-musl","method":"prebuilt"}]}
+//! ```
+//!
+//! - `pre-resolve` lists the crates requested, with their `version_req` and
+//!   `installed_version` if any.
+//! - `pre-install` lists the crates resolved, with their `version`, `target`
+//!   and `method`, which is either `prebuilt` or `source`.
+//! - `post-install` lists the crates installed, in the same way. It is not
+//!   run on dry-run.
+//!
+//! For scripts not parsing JSON, the event is also passed as `BINSTALL_HOOK`,
+//! the crates as `BINSTALL_CRATES`, e.g. `ripgrep@14.1.0 bat@0.24.0`, and
+//! `BINSTALL_DRY_RUN` is set to `1` on dry-run.
+//!
+//! The output of the hooks is printed on stderr. `pre-resolve` and
+//! `pre-install` abort the installation if they exit with a non-zero status,
+//! whereas `post-install` only warns about it.
+
+use std::{fmt, io, process::Stdio};
+
+use binstalk::ops::resolve::{CrateName, ResolutionFetch, ResolutionSource};
+use binstalk_manifests::{binstall_config, crate_info::CrateInfo};
+use compact_str::CompactString;
+use miette::{miette, Result};
+use semver::Version;
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, warn};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Event {
+    PreResolve,
+    PreInstall,
+    PostInstall,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Event::PreResolve => "pre-resolve",
+            Event::PreInstall => "pre-install",
+            Event::PostInstall => "post-install",
+        })
+    }
+}
+
+/// A crate described to the hooks, omitting the fields which are `None`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub(crate) struct HookCrate {
+    name: CompactString,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_req: Option<CompactString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    installed_version: Option<CompactString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<CompactString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<CompactString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<&'static str>,
+}
+
+impl HookCrate {
+    pub(crate) fn requested(crate_name: &CrateName, installed: Option<&Version>) -> Self {
+        Self {
+            name: crate_name.name.clone(),
+            version_req: crate_name
+                .version_req
+                .as_ref()
+                .map(|req| req.to_string().into()),
+            installed_version: installed.map(|version| version.to_string().into()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn fetch(fetch: &ResolutionFetch) -> Self {
+        Self {
+            name: fetch.name.clone(),
+            version: Some(fetch.new_version.to_string().into()),
+            target: Some(fetch.fetcher.target().into()),
+            method: Some("prebuilt"),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn source(source: &ResolutionSource) -> Self {
+        Self {
+            name: source.name.clone(),
+            version: Some(source.version.clone()),
+            method: Some("source"),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn installed(crate_info: &CrateInfo) -> Self {
+        Self {
+            name: crate_info.name.clone(),
+            version: Some(crate_info.current_version.to_string().into()),
+            target: Some(crate_info.target.clone()),
+            method: Some("prebuilt"),
+            ..Default::default()
+        }
+    }
+}
+
+/// The JSON description of the operation given to the hooks on stdin.
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: Event,
+    dry_run: bool,
+    crates: &'a [HookCrate],
+}
+
+fn payload(event: Event, dry_run: bool, crates: &[HookCrate]) -> String {
+    serde_json::to_string(&Payload {
+        event,
+        dry_run,
+        crates,
+    })
+    .expect("the payload of hooks is always serializable")
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Hooks(binstall_config::Hooks);
+
+impl Hooks {
+    pub(crate) fn new(config: Option<binstall_config::Hooks>) -> Self {
+        Self(config.unwrap_or_default())
+    }
+
+    fn hook(&self, event: Event) -> Option<&[CompactString]> {
+        match event {
+            Event::PreResolve => self.0.pre_resolve.as_deref(),
+            Event::PreInstall => self.0.pre_install.as_deref(),
+            Event::PostInstall => self.0.post_install.as_deref(),
+        }
+    }
+
+    /// Run the hook of `event` if it is configured, returning an error if
+    /// it fails, except for `post-install` which is only warned about.
+    pub(crate) async fn run(
+        &self,
+        event: Event,
+        dry_run: bool,
+        crates: &[HookCrate],
+    ) -> Result<()> {
+        let Some(hook) = self.hook(event) else {
+            return Ok(());
+        };
+
+        debug!("Running {event} hook {hook:?}");
+
+        match run_hook(hook, event, dry_run, crates).await {
+            Ok(()) => Ok(()),
+            Err(err) if event == Event::PostInstall => {
+                warn!("The {event} hook failed: {err}");
+                Ok(())
+            }
+            Err(err) => Err(miette!("The {event} hook failed: {err}")),
+        }
+    }
+}
+
+async fn run_hook(
+    hook: &[CompactString],
+    event: Event,
+    dry_run: bool,
+    crates: &[HookCrate],
+) -> io::Result<()> {
+    let (program, args) = hook
+        .split_first()
+        .ok_or_else(|| io::Error::other("Hook is empty"))?;
+
+    let crates_env = crates
+        .iter()
+        .map(|krate| match &krate.version {
+            Some(version) => format!("{}@{version}", krate.name),
+            None => krate.name.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut command = Command::new(&**program);
+    command
+        .args(args.iter().map(|arg| &**arg))
+        .env("BINSTALL_HOOK", event.to_string())
+        .env("BINSTALL_CRATES", crates_env)
+        .stdin(Stdio::piped())
+        // Keep stdout for the output of binstall, e.g. `--json`.
+        .stdout(io::stderr())
+        .stderr(Stdio::inherit());
+    if dry_run {
+        command.env("BINSTALL_DRY_RUN", "1");
+    }
+
+    let mut child = command.spawn()?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    match stdin
+        .write_all(payload(event, dry_run, crates).as_bytes())
+        .await
+    {
+        // The hook does not have to read the payload.
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err),
+        _ => (),
+    }
+    drop(stdin);
+
+    let status = child.wait().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "`{program}` exited with {status}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let crates = [
+            HookCrate {
+                name: "ripgrep".into(),
+                version: Some("14.1.0".into()),
+                target: Some("x86_64-unknown-linux-musl".into()),
+                method: Some("prebuilt"),
+                ..Default::default()
+            },
+            HookCrate {
+                name: "bat".into(),
+                version_req: Some(r#"^0.24 "x""#.into()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            payload(Event::PreInstall, true, &crates),
+            r#"{"event":"pre-install","dry_run":true,"crates":[{"name":"ripgrep","version":"14.1.0","target":"x86_64-unknown-linux-musl","method":"prebuilt"},{"name":"bat","version_req":"^0.24 \"x\""}]}"#
+        );
+        assert_eq!(
+            payload(Event::PostInstall, false, &[]),
+            r#"{"event":"post-install","dry_run":false,"crates":[]}"#
+        );
+    }
+}
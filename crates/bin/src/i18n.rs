@@ -0,0 +1,149 @@
+//! User-facing messages of the `bin` crate.
+//!
+//! The English text of each [`Message`] is built into the binary and is
+//! always available. With the `i18n` feature enabled, messages are
+//! translated via [fluent] according to `LC_ALL`, `LC_MESSAGES` or `LANG`,
+//! falling back to English if the locale or the message is not translated.
+//!
+//! To add a locale, add `locales/<lang>.ftl` containing the id of each
+//! message (see [`Message::id`]) and register it in `LOCALES`.
+//!
+//! [fluent]: https://projectfluent.org/
+
+use std::{fmt, time::Duration};
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Message {
+    ConfirmPrompt,
+    ConfirmTimeoutContinue { secs: u16 },
+    ConfirmTimeoutAbort { secs: u16 },
+    InstallationCancelled,
+    FatalError,
+    Done { spent: Duration },
+}
+
+impl Message {
+    /// Id of the message in the fluent resources.
+    #[cfg_attr(not(feature = "i18n"), allow(dead_code))]
+    fn id(&self) -> &'static str {
+        match self {
+            Self::ConfirmPrompt => "confirm-prompt",
+            Self::ConfirmTimeoutContinue { .. } => "confirm-timeout-continue",
+            Self::ConfirmTimeoutAbort { .. } => "confirm-timeout-abort",
+            Self::InstallationCancelled => "installation-cancelled",
+            Self::FatalError => "fatal-error",
+            Self::Done { .. } => "done",
+        }
+    }
+
+    fn fmt_english(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfirmPrompt => f.write_str("Do you wish to continue? [yes]/no"),
+            Self::ConfirmTimeoutContinue { secs } => {
+                write!(f, "No answer within {secs}s, continuing")
+            }
+            Self::ConfirmTimeoutAbort { secs } => write!(f, "No answer within {secs}s, aborting"),
+            Self::InstallationCancelled => f.write_str("Installation cancelled"),
+            Self::FatalError => f.write_str("Fatal error"),
+            Self::Done { spent } => write!(f, "Done in {spent:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "i18n")]
+        if let Some(translated) = fluent::translate(self) {
+            return f.write_str(&translated);
+        }
+
+        self.fmt_english(f)
+    }
+}
+
+#[cfg(feature = "i18n")]
+mod fluent {
+    use std::env;
+
+    use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+    use once_cell::sync::Lazy;
+    use tracing::debug;
+    use unic_langid::LanguageIdentifier;
+
+    use super::Message;
+
+    /// Translations available, English is built into [`Message`].
+    static LOCALES: &[(&str, &str)] = &[("zh-CN", include_str!("../locales/zh-CN.ftl"))];
+
+    static BUNDLE: Lazy<Option<FluentBundle<FluentResource>>> = Lazy::new(load_bundle);
+
+    /// Return the language of the user from the POSIX locale env, e.g.
+    /// `zh-CN` for `zh_CN.UTF-8`.
+    fn user_langid() -> Option<LanguageIdentifier> {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(env::var_os)
+            .find(|locale| !locale.is_empty())?;
+        let locale = locale.to_str()?;
+
+        // Strip the codeset and the modifier
+        let locale = locale.split(['.', '@']).next()?;
+
+        locale.replace('_', "-").parse().ok()
+    }
+
+    fn load_bundle() -> Option<FluentBundle<FluentResource>> {
+        let langid = user_langid()?;
+
+        // Prefer an exact match, then one with the same language, e.g.
+        // zh-CN for zh-SG.
+        let (locale, source) = LOCALES
+            .iter()
+            .find(|(locale, _)| locale.parse::<LanguageIdentifier>().ok().as_ref() == Some(&langid))
+            .or_else(|| {
+                LOCALES.iter().find(|(locale, _)| {
+                    locale
+                        .parse::<LanguageIdentifier>()
+                        .is_ok_and(|locale| locale.language == langid.language)
+                })
+            })?;
+
+        let resource = FluentResource::try_new(source.to_string())
+            .map_err(|(_, errs)| debug!("Failed to parse locale {locale}: {errs:?}"))
+            .ok()?;
+
+        let mut bundle = FluentBundle::new_concurrent(vec![locale.parse().ok()?]);
+        // Unicode isolation marks are not rendered properly in most terminals
+        bundle.set_use_isolating(false);
+        bundle
+            .add_resource(resource)
+            .map_err(|errs| debug!("Failed to load locale {locale}: {errs:?}"))
+            .ok()?;
+
+        Some(bundle)
+    }
+
+    pub(super) fn translate(message: &Message) -> Option<String> {
+        let bundle = BUNDLE.as_ref()?;
+        let pattern = bundle.get_message(message.id())?.value()?;
+
+        let mut args = FluentArgs::new();
+        match message {
+            Message::ConfirmTimeoutContinue { secs } | Message::ConfirmTimeoutAbort { secs } => {
+                args.set("secs", *secs)
+            }
+            Message::Done { spent } => args.set("spent", format!("{spent:?}")),
+            _ => (),
+        }
+
+        let mut errs = Vec::new();
+        let translated = bundle.format_pattern(pattern, Some(&args), &mut errs);
+
+        if errs.is_empty() {
+            Some(translated.into_owned())
+        } else {
+            debug!("Failed to translate {}: {errs:?}", message.id());
+            None
+        }
+    }
+}
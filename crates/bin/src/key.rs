@@ -0,0 +1,270 @@
+//! `cargo binstall key add|list|remove`, which manage the local trust store
+//! of signing keys: the minisign keys pinned for hosts in
+//! `binstall/pinned-keys` and the ssh keys in `binstall/allowed_signers`.
+
+use std::{fs, io, path::PathBuf};
+
+use binstalk::{
+    errors::BinstallError,
+    fetchers::{parse_minisign_pubkey, AllowedSigners, PinnedKeys},
+    helpers::remote::{Client, Url},
+};
+use home::cargo_home;
+use miette::{miette, Result, WrapErr};
+use tokio::runtime::Runtime;
+use tracing::info;
+
+use crate::args::RateLimit;
+
+/// Prefixes of the key types of ssh public keys.
+const SSH_KEY_PREFIXES: &[&str] = &["ssh-", "ecdsa-", "sk-"];
+
+struct TrustStore {
+    pinned_keys: PinnedKeys,
+    allowed_signers: PathBuf,
+}
+
+impl TrustStore {
+    fn open() -> Result<Self> {
+        let dir = cargo_home().map_err(BinstallError::from)?.join("binstall");
+        fs::create_dir_all(&dir).map_err(BinstallError::from)?;
+
+        Ok(Self {
+            pinned_keys: PinnedKeys::load(dir.join("pinned-keys")).map_err(BinstallError::from)?,
+            allowed_signers: dir.join("allowed_signers"),
+        })
+    }
+
+    fn read_allowed_signers(&self) -> io::Result<String> {
+        match fs::read_to_string(&self.allowed_signers) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            res => res,
+        }
+    }
+}
+
+enum Keys {
+    Minisign(String),
+    Ssh(Vec<String>),
+}
+
+/// Return the ssh keys in `contents`, e.g. a `.pub` file or the keys of a
+/// GitHub user, or else its minisign key.
+fn parse_keys(contents: &str) -> Option<Keys> {
+    let ssh_keys: Vec<_> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            SSH_KEY_PREFIXES
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        })
+        .map(ToString::to_string)
+        .collect();
+
+    if !ssh_keys.is_empty() {
+        Some(Keys::Ssh(ssh_keys))
+    } else {
+        parse_minisign_pubkey(contents).map(|key| Keys::Minisign(key.to_string()))
+    }
+}
+
+/// Return the contents of `source` and the name of the keys in it, if it
+/// can be derived from `source`.
+async fn fetch_source(client: &Client, source: &str) -> Result<(String, Option<String>)> {
+    let (url, name) = if let Some(user) = source.strip_prefix("github:") {
+        let url =
+            Url::parse(&format!("https://github.com/{user}.keys")).map_err(BinstallError::from)?;
+        (url, user.to_string())
+    } else if source.starts_with("https://") {
+        let url = Url::parse(source).map_err(BinstallError::from)?;
+        let host = url.host_str().unwrap_or_default().to_string();
+        (url, host)
+    } else {
+        let contents = fs::read_to_string(source)
+            .map_err(BinstallError::from)
+            .wrap_err_with(|| format!("Failed to read keys from {source}"))?;
+        return Ok((contents, None));
+    };
+
+    let contents = client
+        .get(url.clone())
+        .send(true)
+        .await
+        .map_err(BinstallError::from)?
+        .bytes()
+        .await
+        .map_err(BinstallError::from)
+        .wrap_err_with(|| format!("Failed to fetch keys from {url}"))?;
+
+    Ok((String::from_utf8_lossy(&contents).into_owned(), Some(name)))
+}
+
+/// Add the keys at `source` to the trust store, under `name` or the name
+/// derived from `source`.
+pub(crate) fn add(source: &str, name: Option<String>, rate_limit: RateLimit) -> Result<()> {
+    let client = Client::new(
+        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+        None,
+        rate_limit.duration,
+        rate_limit.request_count,
+        [],
+    )
+    .map_err(BinstallError::from)?;
+
+    let (contents, derived_name) = Runtime::new()
+        .map_err(BinstallError::from)?
+        .block_on(fetch_source(&client, source))?;
+    let keys = parse_keys(&contents)
+        .ok_or_else(|| miette!("No minisign or ssh public key is found in {source}"))?;
+    let name = name
+        .or(derived_name)
+        .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+        .ok_or_else(|| miette!("Pass --key-name to name the keys of {source}"))?;
+
+    let store = TrustStore::open()?;
+
+    match keys {
+        Keys::Minisign(key) => {
+            store
+                .pinned_keys
+                .insert(&name, &key)
+                .map_err(BinstallError::from)?;
+            info!("Pinned minisign key {key} for {name}");
+        }
+        Keys::Ssh(keys) => {
+            let mut allowed_signers = store.read_allowed_signers().map_err(BinstallError::from)?;
+            if !allowed_signers.is_empty() && !allowed_signers.ends_with('\n') {
+                allowed_signers.push('\n');
+            }
+
+            for key in keys {
+                let line = format!("{name} {key}");
+                if AllowedSigners::parse(&line).is_empty() {
+                    return Err(miette!("Invalid ssh public key in {source}: {key}"));
+                }
+                allowed_signers.push_str(&line);
+                allowed_signers.push('\n');
+                info!("Allowed ssh key {key} of {name}");
+            }
+
+            fs::write(&store.allowed_signers, allowed_signers).map_err(BinstallError::from)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the keys of the trust store, one per line.
+pub(crate) fn list() -> Result<()> {
+    let store = TrustStore::open()?;
+
+    for (host, key) in store.pinned_keys.keys() {
+        println!("minisign {host} {key}");
+    }
+
+    let allowed_signers = store.read_allowed_signers().map_err(BinstallError::from)?;
+    for line in allowed_signers.lines().map(str::trim) {
+        if !line.is_empty() && !line.starts_with('#') {
+            println!("ssh {line}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Return true if the allowed signer `line` is of principal `key`, or its
+/// key is `key`, with or without its key type.
+///
+/// The options and comment of the signer are never matched.
+fn is_allowed_signer_of(line: &str, key: &str) -> bool {
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let Some((principals, rest)) = tokens.split_first() else {
+        return false;
+    };
+
+    // The key type follows the options, if any, and precedes the key.
+    let signer_key = rest.windows(2).find(|pair| {
+        ["ssh-", "ecdsa-", "sk-"]
+            .iter()
+            .any(|prefix| pair[0].starts_with(prefix))
+    });
+
+    principals.split(',').any(|principal| principal == key)
+        || signer_key
+            .is_some_and(|pair| key == pair[1] || key.split_once(' ') == Some((pair[0], pair[1])))
+}
+
+/// Remove the keys equal to `key`, or pinned for host `key` or of principal
+/// `key`, from the trust store.
+pub(crate) fn remove(key: &str) -> Result<()> {
+    let store = TrustStore::open()?;
+
+    let unpinned = store.pinned_keys.remove(key).map_err(BinstallError::from)?;
+
+    let allowed_signers = store.read_allowed_signers().map_err(BinstallError::from)?;
+    let mut kept = String::with_capacity(allowed_signers.len());
+    let mut disallowed = 0;
+    for line in allowed_signers.lines() {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('#') && is_allowed_signer_of(trimmed, key) {
+            disallowed += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    if disallowed > 0 {
+        fs::write(&store.allowed_signers, kept).map_err(BinstallError::from)?;
+    }
+
+    let removed = unpinned + disallowed;
+
+    if removed == 0 {
+        return Err(miette!("No key matches {key}"));
+    }
+    info!("Removed {removed} key(s) matching {key}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINISIGN_KEY: &str = "RWSGOq2NVecA2UPNdBUZykf1CCb147pkmdtYxgb3Ti+JO/wCYvhbAb/U";
+    const SSH_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBCK4Lha1xNLjEkaLTtTm6i6AZQZ5cpYxk9Fi6F5Cc6R";
+
+    #[test]
+    fn test_parse_keys() {
+        assert!(matches!(
+            parse_keys(&format!("untrusted comment: minisign public key\n{MINISIGN_KEY}\n")),
+            Some(Keys::Minisign(key)) if key == MINISIGN_KEY
+        ));
+        assert!(matches!(
+            parse_keys(&format!("{SSH_KEY}\n{SSH_KEY} alice@example.com\n")),
+            Some(Keys::Ssh(keys)) if keys.len() == 2
+        ));
+        assert!(parse_keys("not a key").is_none());
+    }
+
+    #[test]
+    fn test_is_allowed_signer_of() {
+        let line = format!("alice,bob {SSH_KEY} comment");
+        assert!(is_allowed_signer_of(&line, "alice"));
+        assert!(is_allowed_signer_of(&line, "bob"));
+        assert!(is_allowed_signer_of(&line, SSH_KEY));
+        assert!(is_allowed_signer_of(
+            &line,
+            SSH_KEY.split_once(' ').unwrap().1
+        ));
+        assert!(!is_allowed_signer_of(&line, "carol"));
+        assert!(!is_allowed_signer_of(&line, "comment"));
+        assert!(!is_allowed_signer_of(&line, "ssh-ed25519"));
+
+        let line = format!("alice namespaces=\"file\" {SSH_KEY}");
+        assert!(is_allowed_signer_of(&line, SSH_KEY));
+        assert!(!is_allowed_signer_of(&line, "namespaces=\"file\""));
+    }
+}
@@ -1,14 +1,29 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
 mod args;
+mod bench;
 mod bin_util;
+mod channel;
+mod cloud_token;
+mod credential_helper;
+mod diff;
 mod entry;
+mod extract;
 mod gh_token;
 mod git_credentials;
+mod hooks;
+mod i18n;
 mod install_path;
+mod key;
 mod logging;
 mod main_impl;
+#[cfg(feature = "notify")]
+mod notify;
+mod redact;
 mod signal;
+mod theme;
 mod ui;
+mod watch;
+mod which;
 
 pub use main_impl::do_main;
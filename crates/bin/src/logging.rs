@@ -1,12 +1,13 @@
 use std::{
     cmp::min,
+    fmt,
     io::{self, Write},
     iter::repeat,
+    str,
 };
 
 use log::{LevelFilter, Log, STATIC_MAX_LEVEL};
 use once_cell::sync::Lazy;
-use supports_color::{on as supports_color_on_stream, Stream::Stdout};
 use tracing::{
     callsite::Callsite,
     dispatcher, field,
@@ -17,10 +18,17 @@ use tracing_core::{identify_callsite, metadata::Kind, subscriber::Subscriber};
 use tracing_log::AsTrace;
 use tracing_subscriber::{
     filter::targets::Targets,
-    fmt::{fmt, MakeWriter},
+    fmt::{
+        fmt,
+        format::{FormatEvent, FormatFields, Writer},
+        FmtContext, MakeWriter,
+    },
     layer::SubscriberExt,
+    registry::LookupSpan,
 };
 
+use crate::{redact::redact, theme::Theme};
+
 // Shamelessly taken from tracing-log
 
 struct Fields {
@@ -137,42 +145,53 @@ impl Log for Logger {
     fn flush(&self) {}
 }
 
-struct ErrorFreeWriter;
+struct ErrorFreeWriter {
+    /// Write to stderr instead of stdout, which is then left for the output.
+    to_stderr: bool,
+}
 
 fn report_err(err: io::Error) {
-    writeln!(io::stderr(), "Failed to write to stdout: {err}").ok();
+    writeln!(io::stderr(), "Failed to write logs: {err}").ok();
 }
 
 impl io::Write for &ErrorFreeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::stdout().write(buf).or_else(|err| {
-            report_err(err);
-            // Behave as if writing to /dev/null so that logging system
-            // would keep working.
-            Ok(buf.len())
-        })
+        // Write the redacted buffer as a whole, so that the caller would not
+        // be confused by its length.
+        self.write_all(buf).map(|()| buf.len())
     }
 
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        io::stdout().write_all(buf).or_else(|err| {
-            report_err(err);
-            // Behave as if writing to /dev/null so that logging system
-            // would keep working.
-            Ok(())
-        })
-    }
+        // tracing-subscriber writes each event in one go, so credentials
+        // are never split across writes.
+        let redacted = str::from_utf8(buf).map(redact);
+        let buf = match &redacted {
+            Ok(redacted) => redacted.as_bytes(),
+            Err(_) => buf,
+        };
+
+        let res = if self.to_stderr {
+            io::stderr().write_all(buf)
+        } else {
+            io::stdout().write_all(buf)
+        };
 
-    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        io::stdout().write_vectored(bufs).or_else(|err| {
+        res.or_else(|err| {
             report_err(err);
             // Behave as if writing to /dev/null so that logging system
             // would keep working.
-            Ok(bufs.iter().map(|io_slice| io_slice.len()).sum())
+            Ok(())
         })
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        io::stdout().flush().or_else(|err| {
+        let res = if self.to_stderr {
+            io::stderr().flush()
+        } else {
+            io::stdout().flush()
+        };
+
+        res.or_else(|err| {
             report_err(err);
             // Behave as if writing to /dev/null so that logging system
             // would keep working.
@@ -189,7 +208,28 @@ impl<'a> MakeWriter<'a> for ErrorFreeWriter {
     }
 }
 
-pub fn logging(log_level: LevelFilter, json_output: bool) {
+/// Format events as their level styled by [`Theme`] followed by their
+/// fields.
+struct ThemedFormat(Theme);
+
+impl<S, N> FormatEvent<S, N> for ThemedFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        write!(writer, "{} ", self.0.level(*event.metadata().level()))?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+pub fn logging(log_level: LevelFilter, json_output: bool, to_stderr: bool) {
     // Calculate log_level
     let log_level = min(log_level, STATIC_MAX_LEVEL);
 
@@ -211,28 +251,25 @@ pub fn logging(log_level: LevelFilter, json_output: bool) {
 
     // Build fmt subscriber
     let log_level = log_level.as_trace();
-    let subscriber_builder = fmt().with_max_level(log_level).with_writer(ErrorFreeWriter);
+    let subscriber_builder = fmt()
+        .with_max_level(log_level)
+        .with_writer(ErrorFreeWriter { to_stderr });
 
     let subscriber: Box<dyn Subscriber + Send + Sync> = if json_output {
         Box::new(subscriber_builder.json().finish())
     } else {
-        // Disable time, target, file, line_num, thread name/ids to make the
-        // output more readable
-        let subscriber_builder = subscriber_builder
-            .without_time()
-            .with_target(false)
-            .with_file(false)
-            .with_line_number(false)
-            .with_thread_names(false)
-            .with_thread_ids(false);
-
+        // Only print the level and the message to make the output more
+        // readable.
+        //
         // subscriber_builder defaults to write to io::stdout(),
-        // so tests whether it supports color.
-        let stdout_supports_color = supports_color_on_stream(Stdout)
-            .map(|color_level| color_level.has_basic)
-            .unwrap_or_default();
-
-        Box::new(subscriber_builder.with_ansi(stdout_supports_color).finish())
+        // which is what the theme tests for color support.
+        let theme = Theme::get();
+        Box::new(
+            subscriber_builder
+                .with_ansi(theme.ansi())
+                .event_format(ThemedFormat(theme))
+                .finish(),
+        )
     };
 
     // Builder layer for filtering
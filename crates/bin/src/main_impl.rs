@@ -1,4 +1,4 @@
-use std::{process::Termination, time::Instant};
+use std::{path::Path, process::Termination, time::Instant};
 
 use binstalk::{helpers::jobserver_client::LazyJobserverClient, TARGET};
 use log::LevelFilter;
@@ -7,13 +7,26 @@ use tracing::debug;
 use crate::{
     args,
     bin_util::{run_tokio_main, MainExit},
-    entry,
+    entry, key,
     logging::logging,
+    theme::{Theme, ThemeName},
+    watch, which,
 };
 
 pub fn do_main() -> impl Termination {
     let (args, cli_overrides) = args::parse();
 
+    // Fail before anything is downloaded if TLS cannot be restricted.
+    #[cfg(feature = "fips")]
+    if let Err(err) = binstalk::helpers::fips::install_tls_provider() {
+        return MainExit::Report(miette::Report::msg(err));
+    }
+
+    Theme::init(
+        args.color,
+        args.theme.unwrap_or_else(ThemeName::from_config),
+    );
+
     if args.version {
         let cargo_binstall_version = env!("CARGO_PKG_VERSION");
         if args.verbose {
@@ -45,12 +58,64 @@ rustc-llvm-version: {rustc_llvm_version}"#
         MainExit::Success(None)
     } else if args.self_install {
         MainExit::new(entry::self_install(args), None)
+    } else if let Some(binary) = &args.which {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(which::which(binary, args.root, args.install_path), None)
+    } else if let Some(source) = &args.key_add {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(key::add(source, args.key_name, args.rate_limit), None)
+    } else if args.key_list {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(key::list(), None)
+    } else if let Some(key) = &args.key_remove {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(key::remove(key), None)
+    } else if args.schedule {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(watch::schedule(args.interval.0), None)
+    } else if args.watch {
+        logging(
+            args.log_level.unwrap_or(LevelFilter::Info),
+            args.json_output,
+            false,
+        );
+
+        MainExit::new(watch::watch(args.interval.0, args.once), None)
     } else {
         logging(
             args.log_level.unwrap_or(LevelFilter::Info),
             args.json_output,
+            args.extract_to.as_deref() == Some(Path::new("-")),
         );
 
+        #[cfg(feature = "notify")]
+        let notify_after = args.notify_after;
+
         let start = Instant::now();
 
         let jobserver_client = LazyJobserverClient::new();
@@ -61,6 +126,15 @@ rustc-llvm-version: {rustc_llvm_version}"#
         let done = start.elapsed();
         debug!("run time: {done:?}");
 
+        #[cfg(feature = "notify")]
+        if let Some(notify_after) = notify_after {
+            crate::notify::notify_if_slow(
+                std::time::Duration::from_secs(notify_after),
+                done,
+                result.is_ok(),
+            );
+        }
+
         MainExit::new(result, Some(done))
     }
 }
@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+use notify_rust::Notification;
+use tracing::warn;
+
+/// Send a desktop notification once the installation finishes,
+/// if it has run for at least `threshold`.
+pub(crate) fn notify_if_slow(threshold: Duration, done: Duration, success: bool) {
+    if done < threshold {
+        return;
+    }
+
+    let body = if success {
+        format!("Installation finished in {done:.1?}")
+    } else {
+        format!("Installation failed after {done:.1?}")
+    };
+
+    if let Err(err) = Notification::new()
+        .summary("cargo-binstall")
+        .body(&body)
+        .show()
+    {
+        warn!("Failed to send desktop notification: {err}");
+    }
+}
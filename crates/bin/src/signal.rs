@@ -1,12 +1,50 @@
-use std::io;
+use std::{
+    io,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
 
 use binstalk::{errors::BinstallError, helpers::tasks::AutoAbortJoinHandle};
 use tokio::signal;
+use tracing::warn;
+
+/// Set once a cancellation signal is received.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Number of live [`DeferCancellation`] guards.
+static DEFERRED: AtomicUsize = AtomicUsize::new(0);
+
+/// Return true if the user requested cancellation via signal.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Guard returned by [`defer_cancellation`].
+#[must_use]
+pub struct DeferCancellation(());
+
+impl Drop for DeferCancellation {
+    fn drop(&mut self) {
+        DEFERRED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Keep [`cancel_on_user_sig_term`] from aborting the task while the guard
+/// is alive, e.g. while binaries are installed and the manifests updated.
+///
+/// The task must check [`is_cancelled`] at points it is safe to stop at,
+/// and return [`BinstallError::UserAbort`] if it is.
+pub fn defer_cancellation() -> DeferCancellation {
+    DEFERRED.fetch_add(1, Ordering::Relaxed);
+    DeferCancellation(())
+}
 
 /// This function will poll the handle while listening for ctrl_c,
 /// `SIGINT`, `SIGHUP`, `SIGTERM` and `SIGQUIT`.
 ///
-/// When signal is received, [`BinstallError::UserAbort`] will be returned.
+/// When signal is received, [`BinstallError::UserAbort`] will be returned
+/// and the task aborted, cancelling the in-flight downloads, unless the
+/// cancellation is deferred by [`defer_cancellation`], in which case the
+/// task is left to stop by itself.
 ///
 /// It would also ignore `SIGUSER1` and `SIGUSER2` on unix.
 ///
@@ -17,15 +55,26 @@ pub async fn cancel_on_user_sig_term<T>(
 ) -> Result<T, BinstallError> {
     ignore_signals()?;
 
+    tokio::pin!(handle);
+
     tokio::select! {
         biased;
 
         res = wait_on_cancellation_signal() => {
-            res.map_err(BinstallError::Io)
-                .and(Err(BinstallError::UserAbort))
+            res.map_err(BinstallError::Io)?;
+
+            CANCELLED.store(true, Ordering::Relaxed);
+            if DEFERRED.load(Ordering::Relaxed) == 0 {
+                return Err(BinstallError::UserAbort);
+            }
+            warn!("Cancelling once the crate being installed is recorded");
         }
-        res = handle => res,
+        res = &mut handle => return res,
     }
+
+    // Once a signal is received, waiting for the next one returns
+    // immediately, so only wait for the task to stop by itself.
+    handle.await
 }
 
 fn ignore_signals() -> io::Result<()> {
@@ -35,6 +84,8 @@ fn ignore_signals() -> io::Result<()> {
     Ok(())
 }
 
+/// Wait for the next cancellation signal.
+///
 /// If call to it returns `Ok(())`, then all calls to this function after
 /// that also returns `Ok(())`.
 async fn wait_on_cancellation_signal() -> Result<(), io::Error> {
@@ -0,0 +1,184 @@
+use std::{env, fmt};
+
+use binstalk_manifests::binstall_config::{self, BinstallConfig};
+use clap::ValueEnum;
+use once_cell::sync::OnceCell;
+use supports_color::{on as supports_color_on_stream, Stream::Stdout};
+use tracing::Level;
+
+/// When to use colors in the output.
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub(crate) enum ColorChoice {
+    /// Use colors if stdout supports it and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Set of styles applied to the output.
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub(crate) enum ThemeName {
+    #[default]
+    Default,
+    /// Bold, bright colors for better readability on low contrast terminals.
+    HighContrast,
+}
+
+impl ThemeName {
+    /// Return the theme configured in `$CARGO_HOME/binstall/config.toml`,
+    /// or the default one.
+    ///
+    /// Errors are ignored here since the config is loaded again, and its
+    /// errors reported, once the output is set up.
+    pub(crate) fn from_config() -> Self {
+        BinstallConfig::load()
+            .ok()
+            .and_then(|config| config.ui?.theme)
+            .map(Self::from)
+            .unwrap_or_default()
+    }
+}
+
+impl From<binstall_config::Theme> for ThemeName {
+    fn from(theme: binstall_config::Theme) -> Self {
+        match theme {
+            binstall_config::Theme::Default => Self::Default,
+            binstall_config::Theme::HighContrast => Self::HighContrast,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Theme {
+    ansi: bool,
+    name: ThemeName,
+}
+
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+impl Theme {
+    /// Initialize the global theme, must be called before any output is
+    /// produced.
+    pub(crate) fn init(color: ColorChoice, name: ThemeName) {
+        let ansi = match color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                // https://no-color.org/
+                let no_color = env::var_os("NO_COLOR").is_some_and(|val| !val.is_empty());
+
+                !no_color
+                    && supports_color_on_stream(Stdout)
+                        .map(|color_level| color_level.has_basic)
+                        .unwrap_or_default()
+            }
+        };
+
+        THEME.set(Self { ansi, name }).ok();
+
+        #[cfg(feature = "fancy-no-backtrace")]
+        miette::set_hook(Box::new(move |_| {
+            let opts = miette::MietteHandlerOpts::new().color(ansi);
+            let opts = match name {
+                ThemeName::HighContrast if ansi => {
+                    opts.graphical_theme(high_contrast_graphical_theme())
+                }
+                _ => opts,
+            };
+            Box::new(opts.build())
+        }))
+        .ok();
+    }
+
+    /// Return the global theme, or a theme without colors if
+    /// [`Theme::init`] has not been called.
+    pub(crate) fn get() -> Self {
+        THEME.get().copied().unwrap_or(Self {
+            ansi: false,
+            name: ThemeName::Default,
+        })
+    }
+
+    /// Whether ANSI escape codes should be used.
+    pub(crate) fn ansi(&self) -> bool {
+        self.ansi
+    }
+
+    /// Style for interactive prompts.
+    pub(crate) fn prompt<T: fmt::Display>(&self, content: T) -> Styled<T> {
+        self.styled(
+            content,
+            match self.name {
+                ThemeName::Default => "1",
+                ThemeName::HighContrast => "1;97",
+            },
+        )
+    }
+
+    /// Style for errors.
+    pub(crate) fn error<T: fmt::Display>(&self, content: T) -> Styled<T> {
+        self.styled(
+            content,
+            match self.name {
+                ThemeName::Default => "31",
+                ThemeName::HighContrast => "1;91",
+            },
+        )
+    }
+
+    /// Style for the level of logs, padded to the same width.
+    pub(crate) fn level(&self, level: Level) -> Styled<&'static str> {
+        let (content, sgr) = match (level, self.name) {
+            (Level::TRACE, ThemeName::Default) => ("TRACE", "35"),
+            (Level::TRACE, ThemeName::HighContrast) => ("TRACE", "1;95"),
+            (Level::DEBUG, ThemeName::Default) => ("DEBUG", "34"),
+            (Level::DEBUG, ThemeName::HighContrast) => ("DEBUG", "1;94"),
+            (Level::INFO, ThemeName::Default) => (" INFO", "32"),
+            (Level::INFO, ThemeName::HighContrast) => (" INFO", "1;92"),
+            (Level::WARN, ThemeName::Default) => (" WARN", "33"),
+            (Level::WARN, ThemeName::HighContrast) => (" WARN", "1;93"),
+            (Level::ERROR, ThemeName::Default) => ("ERROR", "31"),
+            (Level::ERROR, ThemeName::HighContrast) => ("ERROR", "1;91"),
+        };
+        self.styled(content, sgr)
+    }
+
+    fn styled<T>(&self, content: T, sgr: &'static str) -> Styled<T> {
+        Styled {
+            content,
+            sgr: self.ansi.then_some(sgr),
+        }
+    }
+}
+
+/// Bold, bright variant of the ANSI styles of miette.
+#[cfg(feature = "fancy-no-backtrace")]
+fn high_contrast_graphical_theme() -> miette::GraphicalTheme {
+    let mut styles = miette::ThemeStyles::ansi();
+    styles.error = styles.error.bright_red().bold();
+    styles.warning = styles.warning.bright_yellow().bold();
+    styles.advice = styles.advice.bright_cyan().bold();
+    styles.help = styles.help.bright_cyan().bold();
+    styles.link = styles.link.bright_cyan();
+
+    miette::GraphicalTheme {
+        styles,
+        ..miette::GraphicalTheme::unicode()
+    }
+}
+
+/// Content to display with an optional ANSI style.
+pub(crate) struct Styled<T> {
+    content: T,
+    sgr: Option<&'static str>,
+}
+
+impl<T: fmt::Display> fmt::Display for Styled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sgr {
+            Some(sgr) => write!(f, "\x1b[{sgr}m{}\x1b[0m", self.content),
+            None => self.content.fmt(f),
+        }
+    }
+}
@@ -1,16 +1,23 @@
 use std::{
     io::{self, BufRead, StdinLock, Write},
     thread,
+    time::Duration,
 };
 
 use binstalk::errors::BinstallError;
-use tokio::sync::oneshot;
+use tokio::{sync::oneshot, time::timeout};
+
+use crate::{args::ConfirmTimeout, i18n::Message, theme::Theme};
 
 fn ask_for_confirm(stdin: &mut StdinLock, input: &mut String) -> io::Result<()> {
     {
         let mut stdout = io::stdout().lock();
 
-        write!(&mut stdout, "Do you wish to continue? [yes]/no\n? ")?;
+        write!(
+            &mut stdout,
+            "{}\n? ",
+            Theme::get().prompt(Message::ConfirmPrompt)
+        )?;
         stdout.flush()?;
     }
 
@@ -19,7 +26,7 @@ fn ask_for_confirm(stdin: &mut StdinLock, input: &mut String) -> io::Result<()>
     Ok(())
 }
 
-pub async fn confirm() -> Result<(), BinstallError> {
+pub async fn confirm(confirm_timeout: Option<ConfirmTimeout>) -> Result<(), BinstallError> {
     let (tx, rx) = oneshot::channel();
 
     thread::spawn(move || {
@@ -48,7 +55,33 @@ pub async fn confirm() -> Result<(), BinstallError> {
         tx.send(res).ok();
     });
 
-    if rx.await.unwrap() {
+    let res = if let Some(ConfirmTimeout {
+        timeout: secs,
+        proceed,
+    }) = confirm_timeout
+    {
+        match timeout(Duration::from_secs(secs.get().into()), rx).await {
+            Ok(res) => res.unwrap(),
+            Err(_) => {
+                // The thread reading stdin is left blocked, which is fine
+                // since nothing else reads stdin after the confirmation.
+                let secs = secs.get();
+                if proceed {
+                    println!("\n{}", Message::ConfirmTimeoutContinue { secs });
+                } else {
+                    println!(
+                        "\n{}",
+                        Theme::get().error(Message::ConfirmTimeoutAbort { secs })
+                    );
+                }
+                proceed
+            }
+        }
+    } else {
+        rx.await.unwrap()
+    };
+
+    if res {
         Ok(())
     } else {
         Err(BinstallError::UserAbort)
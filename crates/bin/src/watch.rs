@@ -0,0 +1,232 @@
+//! `cargo binstall watch` mode, which periodically updates the installed
+//! crates, and `cargo binstall schedule`, which generates a systemd timer or
+//! a launchd agent doing so instead of a long-running process.
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use binstalk::{
+    errors::BinstallError,
+    helpers::{jobserver_client::LazyJobserverClient, tasks::AutoAbortJoinHandle},
+};
+use miette::{miette, Result};
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{args, bin_util::run_tokio_main, entry, signal};
+
+/// Name of the generated systemd units.
+const SYSTEMD_UNIT: &str = "cargo-binstall-watch";
+
+/// Label of the generated launchd agent.
+const LAUNCHD_LABEL: &str = "dev.cargo-bins.cargo-binstall.watch";
+
+/// Update the installed crates every `interval`, or only once if `once`.
+///
+/// The arguments are parsed again for every update, so that the same
+/// options are used every time.
+pub(crate) fn watch(interval: Duration, once: bool) -> Result<()> {
+    let jobserver_client = LazyJobserverClient::new();
+
+    loop {
+        let (mut args, cli_overrides) = args::parse();
+        args.no_confirm = true;
+
+        info!("Checking for updates of installed crates");
+        let start = Instant::now();
+        let jobserver_client = jobserver_client.clone();
+
+        match run_tokio_main(|| entry::install_crates(args, cli_overrides, jobserver_client)) {
+            Ok(()) => info!("Updated installed crates in {:?}", start.elapsed()),
+            Err(err) if once || signal::is_cancelled() => return Err(err),
+            Err(err) => error!("Failed to update installed crates: {err:?}"),
+        }
+
+        if once {
+            return Ok(());
+        }
+
+        info!("Checking for updates again in {interval:?}");
+        // Sleep in the runtime, so that it is interrupted by signals.
+        run_tokio_main(|| {
+            Ok(Some(AutoAbortJoinHandle::spawn(async move {
+                sleep(interval).await;
+                Ok(())
+            })))
+        })?;
+    }
+}
+
+/// Generate a systemd timer on linux, or a launchd agent on macOS, which
+/// runs `cargo binstall watch --once` every `interval`.
+///
+/// The job is run with the other arguments of the program, in the current
+/// directory and with the current `CARGO_HOME`, so that it updates the
+/// same crates the same way.
+pub(crate) fn schedule(interval: Duration) -> Result<()> {
+    let exe = env::current_exe().map_err(BinstallError::from)?;
+    let exe = exe.display().to_string();
+    let cwd = env::current_dir().map_err(BinstallError::from)?;
+    let cwd = cwd.display().to_string();
+    let cargo_home = env::var_os("CARGO_HOME")
+        .map(|cargo_home| to_str(&cargo_home).map(ToOwned::to_owned))
+        .transpose()?;
+    let args = args::scheduled_args()
+        .iter()
+        .map(|arg| to_str(arg).map(ToOwned::to_owned))
+        .collect::<Result<Vec<_>>>()?;
+    let secs = interval.as_secs().max(1);
+
+    if cfg!(target_os = "linux") {
+        let dir = dirs::config_dir()
+            .ok_or_else(|| miette!("Cannot find the config directory"))?
+            .join("systemd")
+            .join("user");
+
+        write_file(
+            &dir.join(format!("{SYSTEMD_UNIT}.service")),
+            &format!(
+                "[Unit]
+Description=Update crates installed by cargo-binstall
+
+[Service]
+Type=oneshot
+WorkingDirectory={}
+{}ExecStart={} binstall watch --once{}
+",
+                cwd.replace('%', "%%"),
+                cargo_home
+                    .map(|cargo_home| format!(
+                        "Environment={}\n",
+                        systemd_quote(&format!("CARGO_HOME={cargo_home}"))
+                    ))
+                    .unwrap_or_default(),
+                systemd_quote(&exe.replace('$', "$$")),
+                args.iter()
+                    .map(|arg| format!(" {}", systemd_quote(&arg.replace('$', "$$"))))
+                    .collect::<String>(),
+            ),
+        )?;
+        write_file(
+            &dir.join(format!("{SYSTEMD_UNIT}.timer")),
+            &format!(
+                "[Unit]
+Description=Periodically update crates installed by cargo-binstall
+
+[Timer]
+OnBootSec=15min
+OnUnitActiveSec={secs}s
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+"
+            ),
+        )?;
+
+        info!("Enable it with `systemctl --user enable --now {SYSTEMD_UNIT}.timer`");
+        info!("Results are logged to the journal, see `journalctl --user -u {SYSTEMD_UNIT}`");
+    } else if cfg!(target_os = "macos") {
+        let home = dirs::home_dir().ok_or_else(|| miette!("Cannot find the home directory"))?;
+        let path = home
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{LAUNCHD_LABEL}.plist"));
+        let log = home
+            .join("Library")
+            .join("Logs")
+            .join("cargo-binstall-watch.log");
+
+        write_file(
+            &path,
+            &format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>binstall</string>
+        <string>watch</string>
+        <string>--once</string>
+{}    </array>
+    <key>WorkingDirectory</key>
+    <string>{}</string>
+{}    <key>StartInterval</key>
+    <integer>{secs}</integer>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+                xml_escape(&exe),
+                args.iter()
+                    .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+                    .collect::<String>(),
+                xml_escape(&cwd),
+                cargo_home
+                    .map(|cargo_home| format!(
+                        "    <key>EnvironmentVariables</key>
+    <dict>
+        <key>CARGO_HOME</key>
+        <string>{}</string>
+    </dict>
+",
+                        xml_escape(&cargo_home)
+                    ))
+                    .unwrap_or_default(),
+                log = xml_escape(&log.display().to_string()),
+            ),
+        )?;
+
+        info!("Enable it with `launchctl load -w {}`", path.display());
+    } else {
+        return Err(miette!(
+            "Scheduling updates is only supported on linux and macOS, \
+            run `cargo binstall watch` as a service instead"
+        ));
+    }
+
+    Ok(())
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(BinstallError::from)?;
+    }
+    fs::write(path, contents).map_err(BinstallError::from)?;
+    info!("Written {}", path.display());
+
+    Ok(())
+}
+
+fn to_str(arg: &OsStr) -> Result<&str> {
+    arg.to_str()
+        .ok_or_else(|| miette!("Cannot schedule updates with non-UTF-8 argument {arg:?}"))
+}
+
+/// Quote `arg` for `ExecStart=` or `Environment=` of a systemd unit.
+fn systemd_quote(arg: &str) -> String {
+    format!(
+        "\"{}\"",
+        arg.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
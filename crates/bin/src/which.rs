@@ -0,0 +1,169 @@
+//! `cargo binstall which <binary>`, which maps a binary in `PATH` back to
+//! the crate installing it.
+
+use std::{
+    env::{self, consts::EXE_SUFFIX},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use binstalk::errors::BinstallError;
+use binstalk_manifests::{
+    binstall_crates_v2::{file_sha256, Data},
+    cargo_config::Config,
+    crate_info::{CrateSource, SourceType},
+    crates_manifests::Manifests,
+};
+use home::cargo_home;
+use miette::{miette, Result};
+
+use crate::{install_path, redact::redact};
+
+/// Print the crate, version, source, digests and time of installation of
+/// `binary`, or that it is not installed by cargo-binstall.
+pub(crate) fn which(
+    binary: &OsStr,
+    roots: Option<PathBuf>,
+    install_path: Option<PathBuf>,
+) -> Result<()> {
+    let path = find_in_path(binary)
+        .ok_or_else(|| miette!("{} is not found in PATH", Path::new(binary).display()))?;
+    let canonical_path = fs::canonicalize(&path).map_err(BinstallError::from)?;
+
+    let cargo_home = cargo_home().map_err(BinstallError::from)?;
+    let mut config = Config::load_from_path(cargo_home.join("config.toml"))?;
+    let cargo_roots = install_path::get_cargo_roots_path(roots, cargo_home, &mut config)
+        .ok_or_else(|| miette!("No cargo roots path found or specified"))?;
+    let (install_path, _) = install_path::get_install_path(install_path, Some(&cargo_roots));
+
+    let binstall_crates = Manifests::load_binstall_crates(&cargo_roots)?;
+
+    let owner = binstall_crates.iter().find_map(|data| {
+        data.files
+            .iter()
+            .find(|file| {
+                file.path
+                    .clone()
+                    .or_else(|| {
+                        install_path
+                            .as_ref()
+                            .map(|dir| dir.join(format!("{}{EXE_SUFFIX}", file.name)))
+                    })
+                    .and_then(|path| fs::canonicalize(path).ok())
+                    .is_some_and(|path| path == canonical_path)
+            })
+            .map(|file| (data, file.sha256.as_deref()))
+    });
+
+    let sha256 = file_sha256(&canonical_path).map_err(BinstallError::from)?;
+
+    println!("path: {}", path.display());
+    println!("sha256: {sha256}");
+
+    match owner {
+        Some((data, recorded_sha256)) => {
+            if recorded_sha256.is_some_and(|recorded_sha256| recorded_sha256 != sha256) {
+                println!("modified: the file does not match the installed one");
+            }
+            print_provenance(data);
+        }
+        None => println!("unmanaged: not installed by cargo-binstall"),
+    }
+
+    Ok(())
+}
+
+fn print_provenance(data: &Data) {
+    let crate_info = &data.crate_info;
+
+    println!("crate: {}", crate_info.name);
+    println!("version: {}", crate_info.current_version);
+    println!("source: {}", redact(&format_source(&crate_info.source)));
+    if let Some(preferred_target) = &crate_info.preferred_target {
+        println!(
+            "target: {} (fallback for {preferred_target})",
+            crate_info.target
+        );
+    }
+    if let Some(artifact) = &crate_info.artifact {
+        println!("artifact: {}", redact(artifact.url.as_str()));
+        println!("artifact-sha256: {}", artifact.sha256);
+        if let Some(release_digest) = &artifact.release_digest {
+            println!("release-digest: {release_digest}");
+        }
+    }
+    if let Some(provenance) = &crate_info.provenance {
+        println!("published-by: {provenance}");
+    }
+    if let Some(installed_at) = data.installed_at {
+        println!("installed-at: {}", format_timestamp(installed_at));
+    }
+}
+
+/// Find `binary` in `PATH`, unless it is a path already.
+fn find_in_path(binary: &OsStr) -> Option<PathBuf> {
+    let binary = Path::new(binary);
+    if binary.components().count() > 1 {
+        return binary.is_file().then(|| binary.to_owned());
+    }
+
+    let mut file_name = OsString::from(binary);
+    if Path::new(&file_name).extension().is_none() {
+        file_name.push(EXE_SUFFIX);
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Formats the source like cargo does, e.g. `registry+https://...`.
+fn format_source(source: &CrateSource) -> String {
+    let source_type = match source.source_type {
+        SourceType::Git => "git",
+        SourceType::Path => "path",
+        SourceType::Registry => "registry",
+        SourceType::Sparse => "sparse",
+    };
+
+    format!("{source_type}+{}", *source.url)
+}
+
+/// Formats seconds since the unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+fn format_timestamp(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+
+    // Convert days since the epoch to a date of the proleptic gregorian
+    // calendar, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1704067199), "2023-12-31T23:59:59Z");
+        assert_eq!(format_timestamp(1709251200), "2024-03-01T00:00:00Z");
+    }
+}
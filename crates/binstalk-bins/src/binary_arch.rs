@@ -0,0 +1,157 @@
+//! Sniff the architecture of an executable from its header.
+
+/// Number of bytes to read from the start of the file, enough to cover the
+/// PE header of all common executables.
+pub(crate) const HEADER_LEN: usize = 1024;
+
+/// Return the arch family of the executable (using the same names as the
+/// first component of target triples), or `None` if it is unknown, e.g. a
+/// script or a universal Mach-O binary.
+pub(crate) fn detect_arch(header: &[u8]) -> Option<&'static str> {
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => detect_elf_arch(header),
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..] => detect_macho_arch(header, false),
+        [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..] => detect_macho_arch(header, true),
+        [b'M', b'Z', ..] => detect_pe_arch(header),
+        _ => None,
+    }
+}
+
+/// Return the arch family of `target`, or `None` if it is not one known
+/// by [`detect_arch`].
+pub(crate) fn target_arch(target: &str) -> Option<&'static str> {
+    let arch = target.split('-').next()?;
+
+    Some(match arch {
+        "x86_64" | "x86_64h" => "x86_64",
+        "i386" | "i586" | "i686" | "x86" => "x86",
+        "aarch64" | "arm64" | "arm64e" => "aarch64",
+        "loongarch64" => "loongarch64",
+        "s390x" => "s390x",
+        arch if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        arch if arch.starts_with("riscv64") => "riscv64",
+        arch if arch.starts_with("riscv32") => "riscv32",
+        arch if arch.starts_with("powerpc64") => "powerpc64",
+        arch if arch.starts_with("powerpc") => "powerpc",
+        arch if arch.starts_with("mips64") => "mips64",
+        arch if arch.starts_with("mips") => "mips",
+        _ => return None,
+    })
+}
+
+fn read_u16(header: &[u8], offset: usize, is_le: bool) -> Option<u16> {
+    let bytes = header.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if is_le {
+        u16::from_le_bytes(bytes)
+    } else {
+        u16::from_be_bytes(bytes)
+    })
+}
+
+fn read_u32(header: &[u8], offset: usize, is_le: bool) -> Option<u32> {
+    let bytes = header.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if is_le {
+        u32::from_le_bytes(bytes)
+    } else {
+        u32::from_be_bytes(bytes)
+    })
+}
+
+fn detect_elf_arch(header: &[u8]) -> Option<&'static str> {
+    // EI_CLASS: 1 for 32-bit, 2 for 64-bit
+    let is_64 = *header.get(4)? == 2;
+    // EI_DATA: 1 for little endian, 2 for big endian
+    let is_le = *header.get(5)? == 1;
+
+    Some(match read_u16(header, 18, is_le)? {
+        3 => "x86",
+        62 => "x86_64",
+        40 => "arm",
+        183 => "aarch64",
+        243 if is_64 => "riscv64",
+        243 => "riscv32",
+        20 => "powerpc",
+        21 => "powerpc64",
+        22 => "s390x",
+        8 if is_64 => "mips64",
+        8 => "mips",
+        258 => "loongarch64",
+        _ => return None,
+    })
+}
+
+fn detect_macho_arch(header: &[u8], is_le: bool) -> Option<&'static str> {
+    Some(match read_u32(header, 4, is_le)? {
+        7 => "x86",
+        0x0100_0007 => "x86_64",
+        12 => "arm",
+        0x0100_000c => "aarch64",
+        18 => "powerpc",
+        0x0100_0012 => "powerpc64",
+        _ => return None,
+    })
+}
+
+fn detect_pe_arch(header: &[u8]) -> Option<&'static str> {
+    let pe_offset = usize::try_from(read_u32(header, 0x3c, true)?).ok()?;
+
+    if header.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+
+    Some(match read_u16(header, pe_offset + 4, true)? {
+        0x014c => "x86",
+        0x8664 => "x86_64",
+        0x01c0 | 0x01c4 => "arm",
+        0xaa64 => "aarch64",
+        0x5064 => "riscv64",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect_elf_arch() {
+        let mut header = [0_u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = 2;
+        header[5] = 1;
+        header[18..20].copy_from_slice(&183_u16.to_le_bytes());
+
+        assert_eq!(detect_arch(&header), Some("aarch64"));
+        assert_eq!(
+            detect_arch(&header),
+            target_arch("aarch64-unknown-linux-musl")
+        );
+    }
+
+    #[test]
+    fn test_detect_pe_arch() {
+        let mut header = [0_u8; 256];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3c..0x40].copy_from_slice(&0x80_u32.to_le_bytes());
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        header[0x84..0x86].copy_from_slice(&0x8664_u16.to_le_bytes());
+
+        assert_eq!(detect_arch(&header), Some("x86_64"));
+        assert_eq!(detect_arch(&header), target_arch("x86_64-pc-windows-msvc"));
+    }
+
+    #[test]
+    fn test_detect_macho_arch() {
+        let mut header = [0_u8; 32];
+        header[..4].copy_from_slice(&[0xcf, 0xfa, 0xed, 0xfe]);
+        header[4..8].copy_from_slice(&0x0100_0007_u32.to_le_bytes());
+
+        assert_eq!(detect_arch(&header), Some("x86_64"));
+        assert_ne!(detect_arch(&header), target_arch("aarch64-apple-darwin"));
+    }
+
+    #[test]
+    fn test_detect_script() {
+        assert_eq!(detect_arch(b"#!/bin/sh\n"), None);
+    }
+}
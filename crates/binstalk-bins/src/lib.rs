@@ -1,20 +1,25 @@
 use std::{
     borrow::Cow,
-    fmt, io,
+    fmt, fs,
+    io::{self, Read},
     path::{self, Component, Path, PathBuf},
 };
 
+pub use atomic_file_install::FsCompat;
 use atomic_file_install::{
-    atomic_install, atomic_install_noclobber, atomic_symlink_file, atomic_symlink_file_noclobber,
+    atomic_install, atomic_install_noclobber, atomic_install_through_symlink, atomic_symlink_file,
+    atomic_symlink_file_noclobber,
 };
 use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgMeta};
-use compact_str::{format_compact, CompactString};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use leon::Template;
 use miette::Diagnostic;
 use normalize_path::NormalizePath;
 use thiserror::Error as ThisError;
 use tracing::debug;
 
+mod binary_arch;
+
 #[derive(Debug, ThisError, Diagnostic)]
 pub enum Error {
     /// bin-dir configuration provided generates source path outside
@@ -32,6 +37,18 @@ pub enum Error {
     #[error("bin file {} not found", .0.display())]
     BinFileNotFound(Box<Path>),
 
+    /// Bin file is built for a different architecture than the target.
+    #[error(
+        "bin file {} is built for {found}, but the target {target} requires {expected}",
+        path.display()
+    )]
+    TargetMismatch {
+        path: Box<Path>,
+        target: CompactString,
+        expected: &'static str,
+        found: &'static str,
+    },
+
     #[error(transparent)]
     Io(#[from] io::Error),
 
@@ -93,6 +110,47 @@ pub fn infer_bin_dir_template(
         .unwrap_or(default_bin_dir_template)
 }
 
+/// Pick the bin-dir template to use out of `templates`, which are ordered in
+/// preference.
+///
+/// The template locating the most bins in the extracted archive is selected,
+/// with ties broken by order, so the first template is used if none of them
+/// matches.
+///
+///  * `templates` - must not be empty
+pub fn select_bin_dir_template<'t, 's>(
+    data: &Data<'_>,
+    bin_names: &[&str],
+    templates: &'t [Template<'s>],
+    has_file: &mut dyn FnMut(&Path) -> bool,
+) -> &'t Template<'s> {
+    let mut selected = (&templates[0], 0);
+
+    for template in templates {
+        let found = bin_names
+            .iter()
+            .filter(|bin_name| {
+                BinFile::new(data, bin_name, template, true)
+                    .and_then(|bin_file| bin_file.check_source_exists(has_file))
+                    .is_ok()
+            })
+            .count();
+
+        if found == bin_names.len() {
+            return template;
+        } else if found > selected.1 {
+            selected = (template, found);
+        }
+    }
+
+    debug!(
+        "No bin-dir template locates all the bins, selected {:?}",
+        selected.0
+    );
+
+    selected.0
+}
+
 pub struct BinFile {
     pub base_name: CompactString,
     pub source: PathBuf,
@@ -126,7 +184,17 @@ impl BinFile {
             target_related_info: data.target_related_info,
         };
 
-        let (source, archive_source_path) = if data.meta.pkg_fmt == Some(PkgFmt::Bin) {
+        let (source, archive_source_path) = if matches!(
+            data.meta.pkg_fmt,
+            Some(
+                PkgFmt::Bin
+                    | PkgFmt::Installer
+                    | PkgFmt::Gz
+                    | PkgFmt::Xz
+                    | PkgFmt::Zst
+                    | PkgFmt::AppImage
+            )
+        ) {
             (
                 data.bin_path.to_path_buf(),
                 data.bin_path.file_name().unwrap().into(),
@@ -205,6 +273,64 @@ impl BinFile {
         }))
     }
 
+    /// Override the path of the bin inside the archive, e.g. when it is
+    /// auto-detected instead of generated from the bin-dir template.
+    ///
+    ///  * `bin_path` - the same as [`Data::bin_path`].
+    ///  * `archive_source_path` - must be a normalized, relative path.
+    pub fn set_archive_source_path(&mut self, bin_path: &Path, archive_source_path: PathBuf) {
+        self.source = bin_path.join(&archive_source_path);
+        self.archive_source_path = archive_source_path;
+    }
+
+    /// Try to locate the source for windows targets, where upstream archives
+    /// sometimes use different casing than the bin name, or ship the bin as a
+    /// `.cmd`/`.bat` script instead of `.exe`.
+    ///
+    /// `find_file` should look up the path given case-insensitively and
+    /// return the actual path in the archive.
+    ///
+    /// If found, updates the source and, if the extension differs, the
+    /// destination, then returns true.
+    pub fn locate_source_windows(
+        &mut self,
+        bin_path: &Path,
+        find_file: &mut dyn FnMut(&Path) -> Option<PathBuf>,
+    ) -> bool {
+        if self.archive_source_path.file_name().is_none() {
+            return false;
+        }
+
+        for ext in ["exe", "cmd", "bat"] {
+            let Some(path) = find_file(&self.archive_source_path.with_extension(ext)) else {
+                continue;
+            };
+
+            debug!(
+                "Found bin '{}' at '{}' in the archive",
+                self.base_name,
+                path.display()
+            );
+
+            if ext != "exe" {
+                let base_name = Path::new(self.base_name.as_str()).with_extension(ext);
+                self.base_name = base_name.to_string_lossy().to_compact_string();
+
+                // PathBuf::set_extension returns false if Path::file_name
+                // is None, but we know that the file name must be Some.
+                assert!(self.dest.set_extension(ext));
+                if let Some(link) = &mut self.link {
+                    assert!(link.set_extension(ext));
+                }
+            }
+
+            self.set_archive_source_path(bin_path, path);
+            return true;
+        }
+
+        false
+    }
+
     /// Return `Ok` if the source exists, otherwise `Err`.
     pub fn check_source_exists(
         &self,
@@ -217,49 +343,109 @@ impl BinFile {
         }
     }
 
-    fn pre_install_bin(&self) -> Result<(), Error> {
+    /// Sniff the architecture of the source from its ELF, Mach-O or PE
+    /// header, and return `Err` if it does not match `target`.
+    ///
+    /// Returns `Ok` if either architecture cannot be determined, e.g.
+    /// for scripts or universal Mach-O binaries.
+    ///
+    /// This function uses blocking I/O.
+    pub fn check_target_arch(&self, target: &str) -> Result<(), Error> {
+        let Some(expected) = binary_arch::target_arch(target) else {
+            return Ok(());
+        };
+
+        let mut header = Vec::with_capacity(binary_arch::HEADER_LEN);
+        fs::File::open(&self.source)?
+            .take(binary_arch::HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+
+        match binary_arch::detect_arch(&header) {
+            Some(found) if found != expected => Err(Error::TargetMismatch {
+                path: (&*self.source).into(),
+                target: target.into(),
+                expected,
+                found,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the path of the file to install, which is the target of the
+    /// source if it is a symlink preserved from the package, since it would
+    /// be dangling once moved otherwise.
+    fn pre_install_bin(&self, fs_compat: FsCompat) -> Result<Cow<'_, Path>, Error> {
         if !self.source.try_exists()? {
             return Err(Error::BinFileNotFound((&*self.source).into()));
         }
 
+        let source = if self.source.is_symlink() {
+            Cow::Owned(fs::canonicalize(&self.source)?)
+        } else {
+            Cow::Borrowed(&*self.source)
+        };
+
         #[cfg(unix)]
-        std::fs::set_permissions(
-            &self.source,
-            std::os::unix::fs::PermissionsExt::from_mode(0o755),
-        )?;
+        if let Err(err) =
+            std::fs::set_permissions(&source, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+        {
+            if !fs_compat.tolerates(&err) {
+                return Err(err.into());
+            }
+            tracing::warn!(
+                "Failed to make '{}' executable: {err}, ignoring it",
+                self.source.display()
+            );
+        }
 
-        Ok(())
+        Ok(source)
     }
 
-    pub fn install_bin(&self) -> Result<(), Error> {
-        self.pre_install_bin()?;
+    pub fn install_bin(&self, fs_compat: FsCompat) -> Result<(), Error> {
+        let source = self.pre_install_bin(fs_compat)?;
 
         debug!(
             "Atomically install file from '{}' to '{}'",
-            self.source.display(),
+            source.display(),
+            self.dest.display()
+        );
+
+        atomic_install(&source, &self.dest, fs_compat)?;
+
+        Ok(())
+    }
+
+    /// Same as [`BinFile::install_bin`], except that if the destination is
+    /// a symlink, the file it points to is replaced instead of the symlink.
+    pub fn install_bin_through_symlink(&self, fs_compat: FsCompat) -> Result<(), Error> {
+        let source = self.pre_install_bin(fs_compat)?;
+
+        debug!(
+            "Atomically install file from '{}' to '{}' or the target of it",
+            source.display(),
             self.dest.display()
         );
 
-        atomic_install(&self.source, &self.dest)?;
+        atomic_install_through_symlink(&source, &self.dest, fs_compat)?;
 
         Ok(())
     }
 
-    pub fn install_bin_noclobber(&self) -> Result<(), Error> {
-        self.pre_install_bin()?;
+    pub fn install_bin_noclobber(&self, fs_compat: FsCompat) -> Result<(), Error> {
+        let source = self.pre_install_bin(fs_compat)?;
 
         debug!(
             "Installing file from '{}' to '{}' only if dst not exists",
-            self.source.display(),
+            source.display(),
             self.dest.display()
         );
 
-        atomic_install_noclobber(&self.source, &self.dest)?;
+        atomic_install_noclobber(&source, &self.dest, fs_compat)?;
 
         Ok(())
     }
 
-    pub fn install_link(&self) -> Result<(), Error> {
+    pub fn install_link(&self, fs_compat: FsCompat) -> Result<(), Error> {
         if let Some(link) = &self.link {
             let dest = self.link_dest();
             debug!(
@@ -267,13 +453,13 @@ impl BinFile {
                 link.display(),
                 dest.display()
             );
-            atomic_symlink_file(dest, link)?;
+            atomic_symlink_file(dest, link, fs_compat)?;
         }
 
         Ok(())
     }
 
-    pub fn install_link_noclobber(&self) -> Result<(), Error> {
+    pub fn install_link_noclobber(&self, fs_compat: FsCompat) -> Result<(), Error> {
         if let Some(link) = &self.link {
             let dest = self.link_dest();
             debug!(
@@ -281,7 +467,7 @@ impl BinFile {
                 link.display(),
                 dest.display()
             );
-            atomic_symlink_file_noclobber(dest, link)?;
+            atomic_symlink_file_noclobber(dest, link, fs_compat)?;
         }
 
         Ok(())
@@ -1,13 +1,23 @@
-use std::{fmt, io, path::Path};
+use std::{
+    ffi::OsStr,
+    fmt, io,
+    path::{Path, PathBuf},
+};
 
 use binstalk_types::cargo_toml_binstall::PkgFmtDecomposed;
 use bytes::Bytes;
-use futures_util::{stream::FusedStream, Stream, StreamExt};
+use futures_util::{
+    future,
+    stream::{self, FusedStream, Peekable},
+    Stream, StreamExt, TryStreamExt,
+};
 use thiserror::Error as ThisError;
+use tokio_util::io::ReaderStream;
 use tracing::{debug, error, instrument};
 
 pub use binstalk_types::cargo_toml_binstall::{PkgFmt, TarBasedFmt};
 pub use rc_zip_sync::rc_zip::error::Error as ZipError;
+pub use sevenz_rust::Error as SevenZipError;
 
 use crate::remote::{Client, Error as RemoteError, Response, Url};
 
@@ -20,9 +30,41 @@ pub use async_tar_visitor::{TarEntriesVisitor, TarEntry, TarEntryType};
 
 mod extracter;
 
+mod lzip;
+
+mod resumable_stream;
+use resumable_stream::ResumableStream;
+
 mod extracted_files;
 pub use extracted_files::{ExtractedFiles, ExtractedFilesEntry};
 
+mod extraction_sink;
+pub use extraction_sink::{ArchiveListing, ExtractionSink, FsSink, InMemoryVfs};
+
+mod extraction_limits;
+mod parallel_writer;
+pub use extraction_limits::ExtractionLimits;
+
+mod metadata_policy;
+pub use metadata_policy::{MetadataPolicy, ModePolicy};
+
+mod sevenz_extraction;
+
+mod deb_extraction;
+
+mod rpm_extraction;
+
+mod cpio_extraction;
+
+mod appimage_extraction;
+
+#[cfg(feature = "dmg")]
+mod dmg_extraction;
+
+mod msi_extraction;
+
+mod installer_extraction;
+
 mod zip_extraction;
 
 #[derive(Debug, ThisError)]
@@ -31,9 +73,54 @@ pub enum DownloadError {
     #[error("Failed to extract zipfile: {0}")]
     Unzip(#[from] ZipError),
 
+    #[error("Failed to extract zipfile: {0}")]
+    InvalidZip(&'static str),
+
+    #[error("Failed to extract 7z archive: {0}")]
+    SevenZip(#[from] SevenZipError),
+
+    #[error("Failed to extract deb package: {0}")]
+    InvalidDeb(&'static str),
+
+    #[error("Failed to extract rpm package: {0}")]
+    InvalidRpm(&'static str),
+
+    #[error("Failed to extract cpio archive: {0}")]
+    InvalidCpio(&'static str),
+
+    #[error("Failed to extract AppImage: {0}")]
+    InvalidAppImage(&'static str),
+
+    #[error("Failed to extract dmg image: {0}")]
+    InvalidDmg(&'static str),
+
+    #[error("Failed to extract msi package: {0}")]
+    InvalidMsi(&'static str),
+
+    #[error("Failed to extract installer: {0}")]
+    InvalidInstaller(&'static str),
+
     #[error("Failed to download from remote: {0}")]
     Remote(#[from] RemoteError),
 
+    /// The connection is closed before the whole body is received and
+    /// the download cannot be resumed.
+    #[error("Download from {url} is truncated: received {received} of {expected} bytes")]
+    Truncated {
+        url: Box<Url>,
+        expected: u64,
+        received: u64,
+    },
+
+    /// The package expands beyond its [`ExtractionLimits`] when extracted,
+    /// e.g. a decompression bomb.
+    #[error("Extracted package exceeds the limit of {limit} {what}")]
+    LimitExceeded { what: &'static str, limit: u64 },
+
+    /// The format of a download could not be detected from its content.
+    #[error("Failed to detect the format of the download from its content")]
+    UnknownFormat,
+
     /// A generic I/O error.
     ///
     /// - Code: `binstall::io`
@@ -80,22 +167,72 @@ impl DataVerifier for () {
 
 #[derive(Debug)]
 enum DownloadContent {
-    ToIssue { client: Client, url: Url },
-    Response(Response),
+    ToIssue {
+        client: Client,
+        url: Url,
+    },
+    Response(Box<Response>),
+    /// Parts of a file split for hosts limiting the size of files.
+    Parts {
+        client: Client,
+        urls: Vec<Url>,
+    },
 }
 
 impl DownloadContent {
-    async fn into_response(self) -> Result<Response, DownloadError> {
-        Ok(match self {
-            DownloadContent::ToIssue { client, url } => client.get(url).send(true).await?,
-            DownloadContent::Response(response) => response,
-        })
+    fn url(&self) -> &Url {
+        match self {
+            DownloadContent::ToIssue { url, .. } => url,
+            DownloadContent::Response(response) => response.url(),
+            DownloadContent::Parts { urls, .. } => &urls[0],
+        }
+    }
+
+    /// Send the request of the first part, the request of each of the other
+    /// parts is only sent once the previous ones are read.
+    async fn into_responses(
+        self,
+    ) -> Result<
+        impl Stream<Item = Result<Response, DownloadError>> + Send + Sync + Unpin,
+        DownloadError,
+    > {
+        let (first, client, rest) = match self {
+            DownloadContent::ToIssue { client, url } => {
+                (client.get(url).send(true).await?, client, Vec::new())
+            }
+            DownloadContent::Response(response) => {
+                let client = response.client().clone();
+                (*response, client, Vec::new())
+            }
+            DownloadContent::Parts { client, urls } => {
+                let mut urls = urls.into_iter();
+                let first = client.get(urls.next().unwrap()).send(true).await?;
+                (first, client, urls.collect())
+            }
+        };
+
+        let rest = stream::iter(rest).then(move |url| {
+            // The request is spawned since the future of it is not `Sync`.
+            let handle = tokio::spawn(client.get(url).send(true));
+            async move {
+                match handle.await {
+                    Ok(res) => Ok(res?),
+                    Err(err) => Err(io::Error::from(err).into()),
+                }
+            }
+        });
+
+        Ok(stream::once(future::ready(Ok(first))).chain(Box::pin(rest)))
     }
 }
 
 pub struct Download<'a> {
     content: DownloadContent,
     data_verifier: Option<&'a mut dyn DataVerifier>,
+    extract_nested_archive: bool,
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
 }
 
 impl fmt::Debug for Download<'_> {
@@ -109,13 +246,21 @@ impl Download<'static> {
         Self {
             content: DownloadContent::ToIssue { client, url },
             data_verifier: None,
+            extract_nested_archive: false,
+            preserve_symlinks: false,
+            limits: ExtractionLimits::default(),
+            metadata_policy: MetadataPolicy::default(),
         }
     }
 
     pub fn from_response(response: Response) -> Self {
         Self {
-            content: DownloadContent::Response(response),
+            content: DownloadContent::Response(Box::new(response)),
             data_verifier: None,
+            extract_nested_archive: false,
+            preserve_symlinks: false,
+            limits: ExtractionLimits::default(),
+            metadata_policy: MetadataPolicy::default(),
         }
     }
 }
@@ -129,6 +274,33 @@ impl<'a> Download<'a> {
         Self {
             content: DownloadContent::ToIssue { client, url },
             data_verifier: Some(data_verifier),
+            extract_nested_archive: false,
+            preserve_symlinks: false,
+            limits: ExtractionLimits::default(),
+            metadata_policy: MetadataPolicy::default(),
+        }
+    }
+
+    /// Download a file split into parts at `urls`, which are concatenated
+    /// in order.
+    ///
+    /// # Panics
+    ///
+    /// If `urls` is empty.
+    pub fn new_parts_with_data_verifier(
+        client: Client,
+        urls: Vec<Url>,
+        data_verifier: &'a mut dyn DataVerifier,
+    ) -> Self {
+        assert!(!urls.is_empty(), "a file must have at least one part");
+
+        Self {
+            content: DownloadContent::Parts { client, urls },
+            data_verifier: Some(data_verifier),
+            extract_nested_archive: false,
+            preserve_symlinks: false,
+            limits: ExtractionLimits::default(),
+            metadata_policy: MetadataPolicy::default(),
         }
     }
 
@@ -137,8 +309,12 @@ impl<'a> Download<'a> {
         data_verifier: &'a mut dyn DataVerifier,
     ) -> Self {
         Self {
-            content: DownloadContent::Response(response),
+            content: DownloadContent::Response(Box::new(response)),
             data_verifier: Some(data_verifier),
+            extract_nested_archive: false,
+            preserve_symlinks: false,
+            limits: ExtractionLimits::default(),
+            metadata_policy: MetadataPolicy::default(),
         }
     }
 
@@ -146,6 +322,65 @@ impl<'a> Download<'a> {
         Download {
             content: self.content,
             data_verifier: Some(data_verifier),
+            extract_nested_archive: self.extract_nested_archive,
+            preserve_symlinks: self.preserve_symlinks,
+            limits: self.limits,
+            metadata_policy: self.metadata_policy,
+        }
+    }
+
+    /// If the package only contains an archive or a compressed file, e.g. a
+    /// zip containing a tgz or an xz, extract it as well in
+    /// [`Download::and_extract`], recursively.
+    pub fn extract_nested_archive(self) -> Self {
+        Self {
+            extract_nested_archive: true,
+            ..self
+        }
+    }
+
+    /// Recreate the symlinks of tar-based packages in
+    /// [`Download::and_extract`], e.g. `tool -> tool-1.2.3`, instead of
+    /// skipping them. Symlinks pointing outside of the extraction directory
+    /// are always skipped.
+    pub fn preserve_symlinks(self) -> Self {
+        Self {
+            preserve_symlinks: true,
+            ..self
+        }
+    }
+
+    /// Limit the size and the number of entries of the package extracted
+    /// by [`Download::and_extract`], along with the memory used to
+    /// decompress it, which defaults to [`ExtractionLimits::default`].
+    pub fn with_limits(self, limits: ExtractionLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Limit the memory used by the xz, zstd and lzip decoders to
+    /// `max_decoder_memory` bytes, failing with
+    /// [`DownloadError::LimitExceeded`] on packages needing more instead of
+    /// running out of memory.
+    pub fn with_max_decoder_memory(mut self, max_decoder_memory: u64) -> Self {
+        self.limits.max_decoder_memory = max_decoder_memory;
+        self
+    }
+
+    /// Reduce the memory used by [`Download::and_extract`] if `low_memory`
+    /// is true, see [`ExtractionLimits::low_memory`].
+    pub fn with_low_memory(mut self, low_memory: bool) -> Self {
+        self.limits.low_memory = low_memory;
+        self
+    }
+
+    /// Choose which of the mode, modification time and extended attributes
+    /// of the entries of tar-based packages are restored by
+    /// [`Download::and_extract`], which defaults to
+    /// [`MetadataPolicy::default`].
+    pub fn with_metadata_policy(self, metadata_policy: MetadataPolicy) -> Self {
+        Self {
+            metadata_policy,
+            ..self
         }
     }
 
@@ -156,11 +391,10 @@ impl<'a> Download<'a> {
         DownloadError,
     > {
         let mut data_verifier = self.data_verifier;
-        Ok(self
-            .content
-            .into_response()
-            .await?
-            .bytes_stream()
+        let responses = self.content.into_responses().await?;
+        Ok(responses
+            .map_ok(ResumableStream::new)
+            .try_flatten()
             .map(move |res| {
                 let bytes = res?;
 
@@ -176,6 +410,152 @@ impl<'a> Download<'a> {
     }
 }
 
+#[cfg(not(feature = "dmg"))]
+const DMG_NOT_ENABLED: DownloadError =
+    DownloadError::InvalidDmg("support for dmg images is not enabled in this build");
+
+/// Extract `stream` as `fmt` to `sink`, writing single files, e.g.
+/// [`PkgFmt::Bin`], to `file_name`.
+async fn extract_stream<S, Sink>(
+    stream: &mut Peekable<S>,
+    fmt: PkgFmt,
+    sink: Sink,
+    file_name: &Path,
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    match fmt.decompose() {
+        PkgFmtDecomposed::Tar(fmt) => {
+            extract_tar_based_stream(
+                stream,
+                sink,
+                fmt,
+                preserve_symlinks,
+                limits,
+                metadata_policy,
+            )
+            .await
+        }
+        PkgFmtDecomposed::Bin => extract_bin(stream, sink, file_name, limits).await,
+        PkgFmtDecomposed::Installer => extract_installer(stream, sink, file_name, limits).await,
+        PkgFmtDecomposed::Gz => extract_gz(stream, sink, file_name, limits).await,
+        PkgFmtDecomposed::Xz => extract_xz(stream, sink, file_name, limits).await,
+        PkgFmtDecomposed::Zst => extract_zst(stream, sink, file_name, limits).await,
+        PkgFmtDecomposed::Zip => extract_zip(stream, sink, limits).await,
+        PkgFmtDecomposed::SevenZip => extract_7z(stream, sink, limits).await,
+        PkgFmtDecomposed::Deb => extract_deb(stream, sink, limits).await,
+        PkgFmtDecomposed::Rpm => extract_rpm(stream, sink, limits).await,
+        PkgFmtDecomposed::Cpio => extract_cpio(stream, sink, limits).await,
+        PkgFmtDecomposed::AppImage => extract_appimage(stream, sink, limits).await,
+        #[cfg(feature = "dmg")]
+        PkgFmtDecomposed::Dmg => extract_dmg(stream, sink, limits).await,
+        #[cfg(not(feature = "dmg"))]
+        PkgFmtDecomposed::Dmg => Err(DMG_NOT_ENABLED),
+        PkgFmtDecomposed::Msi => extract_msi(stream, sink, limits).await,
+    }
+}
+
+/// Return true if `fmt` is extracted to a single file instead of a
+/// directory.
+fn is_single_file(fmt: PkgFmt) -> bool {
+    matches!(
+        fmt.decompose(),
+        PkgFmtDecomposed::Bin
+            | PkgFmtDecomposed::Installer
+            | PkgFmtDecomposed::Gz
+            | PkgFmtDecomposed::Xz
+            | PkgFmtDecomposed::Zst
+    )
+}
+
+/// Return the name of the single file extracted from `url` as `fmt`: its
+/// last segment, without the `.gz`, `.xz` or `.zst` suffix.
+fn file_name_of(url: &Url, fmt: PkgFmt) -> PathBuf {
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("bin");
+    let suffix = match fmt.decompose() {
+        PkgFmtDecomposed::Gz => ".gz",
+        PkgFmtDecomposed::Xz => ".xz",
+        PkgFmtDecomposed::Zst => ".zst",
+        _ => "",
+    };
+
+    file_name.strip_suffix(suffix).unwrap_or(file_name).into()
+}
+
+/// Maximum number of archives nested in each other extracted by
+/// [`extract_nested_archive`], to not loop on e.g. a zip quine.
+const MAX_NESTED_ARCHIVE_DEPTH: usize = 4;
+
+/// If `extracted_files` only contains an archive or a compressed file, e.g. a
+/// tgz or an xz inside a zip, extract it to `path` and return the files
+/// extracted from it instead, repeatedly up to [`MAX_NESTED_ARCHIVE_DEPTH`]
+/// times.
+///
+/// `limits` apply to each archive, since the nested archive is removed once
+/// extracted.
+async fn extract_nested_archive(
+    mut extracted_files: ExtractedFiles,
+    path: &Path,
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
+) -> Result<ExtractedFiles, DownloadError> {
+    for _ in 0..MAX_NESTED_ARCHIVE_DEPTH {
+        let nested = {
+            let mut files = extracted_files.files();
+            match (files.next(), files.next()) {
+                (Some(nested), None) => nested
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .and_then(PkgFmt::guess_pkg_format)
+                    .filter(|fmt| !matches!(fmt, PkgFmt::Bin | PkgFmt::AppImage))
+                    .map(|fmt| (nested.to_owned(), fmt)),
+                _ => None,
+            }
+        };
+        let Some((nested, fmt)) = nested else {
+            break;
+        };
+        let nested_path = path.join(&nested);
+
+        debug!(
+            "Extracting nested {fmt} archive '{}'",
+            nested_path.display()
+        );
+
+        let mut stream = ReaderStream::new(tokio::fs::File::open(&nested_path).await?)
+            .map(|res| res.map_err(DownloadError::from))
+            .peekable();
+        let fmt = sniff_fmt(&mut stream, fmt).await;
+
+        // Compressed files are decompressed next to them, without their
+        // suffix.
+        (extracted_files, _) = extract_stream(
+            &mut stream,
+            fmt,
+            FsSink::new(path),
+            &nested.with_extension(""),
+            preserve_symlinks,
+            limits,
+            metadata_policy,
+        )
+        .await?;
+
+        tokio::fs::remove_file(&nested_path).await?;
+    }
+
+    Ok(extracted_files)
+}
+
 /// Make sure `stream` is an alias instead of taking the value to avoid
 /// exploding size of the future generated.
 ///
@@ -207,11 +587,17 @@ impl Download<'_> {
         visitor: &mut dyn TarEntriesVisitor,
     ) -> Result<(), DownloadError> {
         let has_data_verifier = self.data_verifier.is_some();
-        let mut stream = self.get_stream().await?;
+        let low_memory = self.limits.low_memory;
+        let mut stream = self.get_stream().await?.peekable();
+
+        let fmt = match sniff_fmt(&mut stream, fmt.into()).await.decompose() {
+            PkgFmtDecomposed::Tar(sniffed) => sniffed,
+            _ => fmt,
+        };
 
         debug!("Downloading and extracting then in-memory processing");
 
-        let res = extract_tar_based_stream_and_visit(&mut stream, fmt, visitor).await;
+        let res = extract_tar_based_stream_and_visit(&mut stream, fmt, low_memory, visitor).await;
 
         if has_data_verifier {
             consume_stream(&mut stream).await;
@@ -226,7 +612,14 @@ impl Download<'_> {
 
     /// Download a file from the provided URL and extract it to the provided path.
     ///
-    /// NOTE that this will only extract directory and regular files.
+    /// NOTE that this will only extract directory and regular files, unless
+    /// [`Download::preserve_symlinks`] is set.
+    ///
+    /// See [`Download::extract_nested_archive`] for extracting archives
+    /// inside the package.
+    ///
+    /// Fails with [`DownloadError::LimitExceeded`] if the package expands
+    /// beyond the limits set by [`Download::with_limits`].
     #[instrument(
         skip(self, path),
         fields(path = format_args!("{}", path.as_ref().display()))
@@ -236,45 +629,186 @@ impl Download<'_> {
         fmt: PkgFmt,
         path: impl AsRef<Path>,
     ) -> Result<ExtractedFiles, DownloadError> {
+        self.extract_to(Some(fmt), path.as_ref())
+            .await
+            .map(|(_, extracted_files)| extracted_files)
+    }
+
+    /// Download a file from the provided URL and extract it to the provided
+    /// path like [`Download::and_extract`], detecting its format from its
+    /// first bytes, e.g. for urls without a recognisable extension.
+    ///
+    /// Compressed tarballs are told apart from compressed binaries, and
+    /// executables are extracted as [`PkgFmt::Bin`].
+    ///
+    /// Return the format detected along with the files extracted, or
+    /// [`DownloadError::UnknownFormat`].
+    #[instrument(
+        skip(self, path),
+        fields(path = format_args!("{}", path.as_ref().display()))
+    )]
+    pub async fn and_extract_sniffed(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<(PkgFmt, ExtractedFiles), DownloadError> {
+        self.extract_to(None, path.as_ref()).await
+    }
+
+    /// Extract to `path` as `fmt`, or the format sniffed if it is `None`.
+    async fn extract_to(
+        self,
+        fmt: Option<PkgFmt>,
+        path: &Path,
+    ) -> Result<(PkgFmt, ExtractedFiles), DownloadError> {
         async fn inner(
             this: Download<'_>,
-            fmt: PkgFmt,
+            fmt: Option<PkgFmt>,
             path: &Path,
-        ) -> Result<ExtractedFiles, DownloadError> {
+        ) -> Result<(PkgFmt, ExtractedFiles), DownloadError> {
             let has_data_verifier = this.data_verifier.is_some();
-            let mut stream = this.get_stream().await?;
+            let extract_nested = this.extract_nested_archive;
+            let preserve_symlinks = this.preserve_symlinks;
+            let limits = this.limits;
+            let metadata_policy = this.metadata_policy;
+            let url = this.content.url().clone();
+            let mut stream = this.get_stream().await?.peekable();
+
+            let fmt = match fmt {
+                Some(fmt) => sniff_fmt(&mut stream, fmt).await,
+                None => {
+                    let fmt = sniff_unknown_fmt(&mut stream)
+                        .await
+                        .ok_or(DownloadError::UnknownFormat)?;
+                    debug!("Detected {fmt} from the content of the download");
+                    fmt
+                }
+            };
 
             debug!("Downloading and extracting to: '{}'", path.display());
 
-            let res = match fmt.decompose() {
-                PkgFmtDecomposed::Tar(fmt) => {
-                    extract_tar_based_stream(&mut stream, path, fmt).await
-                }
-                PkgFmtDecomposed::Bin => extract_bin(&mut stream, path).await,
-                PkgFmtDecomposed::Zip => extract_zip(&mut stream, path).await,
+            // Single files are written to path itself, other formats are
+            // extracted inside of it.
+            let (dir, file_name) = match path.file_name() {
+                Some(file_name) if is_single_file(fmt) => (
+                    path.parent().unwrap_or(Path::new("")),
+                    PathBuf::from(file_name),
+                ),
+                _ => (path, file_name_of(&url, fmt)),
             };
+            tokio::fs::create_dir_all(dir).await?;
+
+            let res = extract_stream(
+                &mut stream,
+                fmt,
+                FsSink::new(dir),
+                &file_name,
+                preserve_symlinks,
+                limits,
+                metadata_policy,
+            )
+            .await
+            .map(|(extracted_files, _)| extracted_files);
 
             if has_data_verifier {
                 consume_stream(&mut stream).await;
             }
 
+            let res = match res {
+                Ok(extracted_files) if extract_nested => {
+                    extract_nested_archive(
+                        extracted_files,
+                        path,
+                        preserve_symlinks,
+                        limits,
+                        metadata_policy,
+                    )
+                    .await
+                }
+                res => res,
+            };
+
             if res.is_ok() {
                 debug!("Download OK, extracted to: '{}'", path.display());
             }
 
-            res
+            res.map(|extracted_files| (fmt, extracted_files))
+        }
+
+        inner(self, fmt, path).await
+    }
+
+    /// Download a file from the provided URL and extract it into `sink`
+    /// like [`Download::and_extract`], e.g. an [`InMemoryVfs`] instead of
+    /// the filesystem.
+    ///
+    /// For [`PkgFmt::Bin`], [`PkgFmt::Installer`], [`PkgFmt::Gz`],
+    /// [`PkgFmt::Xz`] and [`PkgFmt::Zst`], the file is named after the last
+    /// segment of the url, without the `.gz`, `.xz` or `.zst` suffix.
+    ///
+    /// NOTE that this will only extract directory and regular files, unless
+    /// [`Download::preserve_symlinks`] is set and `sink` supports symlinks.
+    /// Nested archives are not extracted.
+    #[instrument(skip(self, sink))]
+    pub async fn and_extract_to_sink<Sink: ExtractionSink>(
+        self,
+        fmt: PkgFmt,
+        sink: Sink,
+    ) -> Result<(ExtractedFiles, Sink), DownloadError> {
+        let has_data_verifier = self.data_verifier.is_some();
+        let preserve_symlinks = self.preserve_symlinks;
+        let limits = self.limits;
+        let metadata_policy = self.metadata_policy;
+        let url = self.content.url().clone();
+        let mut stream = self.get_stream().await?.peekable();
+        let fmt = sniff_fmt(&mut stream, fmt).await;
+
+        debug!("Downloading and extracting to extraction sink");
+
+        let res = extract_stream(
+            &mut stream,
+            fmt,
+            sink,
+            &file_name_of(&url, fmt),
+            preserve_symlinks,
+            limits,
+            metadata_policy,
+        )
+        .await;
+
+        if has_data_verifier {
+            consume_stream(&mut stream).await;
+        }
+
+        if res.is_ok() {
+            debug!("Download OK, extracted to extraction sink");
         }
 
-        inner(self, fmt, path.as_ref()).await
+        res
+    }
+
+    /// Download a file from the provided URL and list the files in it with
+    /// their sizes, without extracting them.
+    ///
+    /// Tar-based archives and compressed files are listed while streaming,
+    /// while other archives, e.g. zip, are buffered in an anonymous temporary
+    /// file first since they cannot be read sequentially.
+    ///
+    /// The data verifier, if any, is fed the whole file as with
+    /// [`Download::and_extract`].
+    pub async fn list(self, fmt: PkgFmt) -> Result<ArchiveListing, DownloadError> {
+        self.and_extract_to_sink(fmt, ArchiveListing::new())
+            .await
+            .map(|(_, listing)| listing)
     }
 
     #[instrument(skip(self))]
     pub async fn into_bytes(self) -> Result<Bytes, DownloadError> {
-        let bytes = self.content.into_response().await?.bytes().await?;
-        if let Some(verifier) = self.data_verifier {
-            verifier.update(&bytes);
+        let mut bytes = Vec::new();
+        let mut stream = self.get_stream().await?;
+        while let Some(chunk) = stream.next().await {
+            bytes.extend_from_slice(&chunk?);
         }
-        Ok(bytes)
+        Ok(bytes.into())
     }
 }
 
@@ -282,13 +816,950 @@ impl Download<'_> {
 mod test {
     use super::*;
 
+    use futures_util::stream;
     use std::{
         collections::{HashMap, HashSet},
         ffi::OsStr,
+        fs,
         num::NonZeroU16,
     };
     use tempfile::tempdir;
 
+    #[tokio::test]
+    async fn test_extract_tar_to_in_memory_vfs() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let content = b"#!/bin/sh\necho hello\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "dir/hello", &content[..])
+            .unwrap();
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("dir/hello")));
+        assert!(vfs.has_dir(Path::new("dir")));
+        assert_eq!(
+            vfs.get_file(Path::new("dir/hello")).unwrap().as_ref(),
+            content
+        );
+        assert_eq!(vfs.total_size(), content.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_list_tar() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for (path, content) in [("dir/hello", &b"hello"[..]), ("README.md", b"# readme\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content).unwrap();
+        }
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let (_, listing) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            ArchiveListing::new(),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(listing.get_size(Path::new("dir/hello")), Some(5));
+        assert!(listing.has_file(Path::new("README.md")));
+        assert!(listing.has_dir(Path::new("dir")));
+        assert_eq!(listing.total_size(), 14);
+        assert_eq!(
+            listing.files().map(|(path, _)| path).collect::<Vec<_>>(),
+            [Path::new("README.md"), Path::new("dir/hello")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_tar_symlinks() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let content = b"#!/bin/sh\necho hello\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool-1.2.3", &content[..])
+            .unwrap();
+
+        for (link, target) in [
+            ("tool", "tool-1.2.3"),
+            ("bin/tool", "../tool-1.2.3"),
+            ("escape", "../tool-1.2.3"),
+            ("absolute", "/bin/sh"),
+            ("bin/sneaky", "../bin/../../tool-1.2.3"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, link, target).unwrap();
+        }
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive.clone())]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!extracted_files.has_file(Path::new("tool")));
+        assert!(!dir.path().join("tool").exists());
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            true,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        for link in ["tool", "bin/tool"] {
+            assert!(extracted_files.has_file(Path::new(link)));
+            assert_eq!(fs::read(dir.path().join(link)).unwrap(), content);
+        }
+        for link in ["escape", "absolute", "bin/sneaky"] {
+            assert!(!extracted_files.has_file(Path::new(link)));
+            assert!(fs::symlink_metadata(dir.path().join(link)).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_tar_many_files() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut append = |path: &str, content: &[u8], mode| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(mode);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content).unwrap();
+        };
+
+        for i in 0..100 {
+            append(
+                &format!("share/{}/file-{i}", i % 7),
+                i.to_string().as_bytes(),
+                0o644,
+            );
+        }
+        append("bin/tool", b"old", 0o644);
+        append("bin/tool", b"new", 0o755);
+        // Written by the decoder itself
+        let large = vec![1; parallel_writer::MAX_PARALLEL_FILE_SIZE as usize + 1];
+        append("bin/large", &large, 0o644);
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        for i in 0..100 {
+            let path = format!("share/{}/file-{i}", i % 7);
+            assert!(extracted_files.has_file(Path::new(&path)));
+            assert_eq!(
+                fs::read(dir.path().join(path)).unwrap(),
+                i.to_string().as_bytes()
+            );
+        }
+        assert_eq!(fs::read(dir.path().join("bin/tool")).unwrap(), b"new");
+        assert_eq!(fs::read(dir.path().join("bin/large")).unwrap(), large);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(dir.path().join("bin/tool"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_tar_metadata_policy() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, mode) in [("bin/tool", 0o4750), ("share/doc", 0o600)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(mode);
+            header.set_mtime(1000);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, &b"data"[..])
+                .unwrap();
+        }
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        for (policy, tool_mode, doc_mode) in [
+            (MetadataPolicy::default(), 0o750, 0o600),
+            (
+                MetadataPolicy {
+                    mode: ModePolicy::PreserveAll,
+                    preserve_mtime: false,
+                    preserve_xattrs: false,
+                },
+                0o4750,
+                0o600,
+            ),
+            (
+                MetadataPolicy {
+                    mode: ModePolicy::Normalize,
+                    preserve_mtime: false,
+                    // Extracted by tar instead of in parallel
+                    preserve_xattrs: true,
+                },
+                0o755,
+                0o644,
+            ),
+        ] {
+            let dir = tempdir().unwrap();
+            extract_tar_based_stream(
+                stream::iter([Ok(archive.clone())]),
+                FsSink::new(dir.path()),
+                TarBasedFmt::Tar,
+                false,
+                ExtractionLimits::default(),
+                policy,
+            )
+            .await
+            .unwrap();
+
+            for (path, mode) in [("bin/tool", tool_mode), ("share/doc", doc_mode)] {
+                let metadata = fs::metadata(dir.path().join(path)).unwrap();
+                assert_eq!(metadata.permissions().mode() & 0o7777, mode, "{policy:?}");
+                assert_eq!(metadata.mtime() == 1000, policy.preserve_mtime);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_tar_hardlinks() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let content = b"\x7fELF multi-call";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bin/busybox", &content[..])
+            .unwrap();
+
+        for (link, target) in [
+            ("bin/ls", "bin/busybox"),
+            ("sbin/init", "bin/busybox"),
+            ("escape", "../busybox"),
+            ("missing", "bin/missing"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Link);
+            header.set_size(0);
+            builder.append_link(&mut header, link, target).unwrap();
+        }
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        for link in ["bin/ls", "sbin/init"] {
+            assert!(extracted_files.has_file(Path::new(link)));
+            assert_eq!(
+                extracted_files.hardlink_target(Path::new(link)),
+                Some(Path::new("bin/busybox"))
+            );
+            assert_eq!(fs::read(dir.path().join(link)).unwrap(), content);
+        }
+        assert_eq!(extracted_files.hardlinks().count(), 2);
+        assert_eq!(
+            extracted_files.hardlink_target(Path::new("bin/busybox")),
+            None
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let ino = fs::metadata(dir.path().join("bin/busybox")).unwrap().ino();
+            assert_eq!(fs::metadata(dir.path().join("bin/ls")).unwrap().ino(), ino);
+        }
+
+        for link in ["escape", "missing"] {
+            assert!(!extracted_files.has_file(Path::new(link)));
+            assert!(fs::symlink_metadata(dir.path().join(link)).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_tar_hardlink_to_symlink() {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        // Inside of the package from `a/b`, but not from the top.
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "a/b/link", "../../outside")
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "escape", "a/b/link")
+            .unwrap();
+
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            true,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("a/b/link")));
+        assert!(!extracted_files.has_file(Path::new("escape")));
+        assert!(fs::symlink_metadata(dir.path().join("escape")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extraction_limits() {
+        use std::io::Read;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for name in ["a", "b", "c"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, &b"data"[..])
+                .unwrap();
+        }
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        for (limits, what, limit) in [
+            (
+                ExtractionLimits {
+                    max_size: 11,
+                    ..ExtractionLimits::default()
+                },
+                "bytes",
+                11,
+            ),
+            (
+                ExtractionLimits {
+                    max_entries: 2,
+                    ..ExtractionLimits::default()
+                },
+                "entries",
+                2,
+            ),
+        ] {
+            let dir = tempdir().unwrap();
+            let err = extract_tar_based_stream(
+                stream::iter([Ok(archive.clone())]),
+                FsSink::new(dir.path()),
+                TarBasedFmt::Tar,
+                false,
+                limits,
+                MetadataPolicy::default(),
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                matches!(err, DownloadError::LimitExceeded { what: w, limit: l } if w == what && l == limit),
+                "{err:?}"
+            );
+            assert!(!dir.path().join("c").exists());
+        }
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tar,
+            false,
+            ExtractionLimits {
+                max_size: 12,
+                max_entries: 3,
+                ..ExtractionLimits::default()
+            },
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert!(extracted_files.has_file(Path::new("c")));
+
+        // A small stream expanding to a lot of zeroes.
+        let compressed = Bytes::from(zstd::encode_all(&[0; 1024 * 1024][..], 19).unwrap());
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bomb");
+        let err = extract_zst(
+            stream::iter([Ok(compressed)]),
+            FsSink::new(dir.path()),
+            Path::new("bomb"),
+            ExtractionLimits {
+                max_size: 1024,
+                ..ExtractionLimits::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DownloadError::LimitExceeded { what: "bytes", .. }
+        ));
+        assert!(fs::metadata(&path).unwrap().len() <= 1025);
+
+        // Both decoders need about 1 MiB to decompress 1 MiB.
+        let limits = ExtractionLimits {
+            max_decoder_memory: 64 * 1024,
+            ..ExtractionLimits::default()
+        };
+        let data = vec![0; 1024 * 1024];
+        let mut xz = Vec::new();
+        xz2::read::XzEncoder::new(&data[..], 6)
+            .read_to_end(&mut xz)
+            .unwrap();
+        let zst = zstd::encode_all(&data[..], 19).unwrap();
+
+        let dir = tempdir().unwrap();
+        let results = [
+            extract_xz(
+                stream::iter([Ok(Bytes::from(xz))]),
+                FsSink::new(dir.path()),
+                Path::new("xz"),
+                limits,
+            )
+            .await
+            .map(|(extracted_files, _)| extracted_files),
+            extract_zst(
+                stream::iter([Ok(Bytes::from(zst))]),
+                FsSink::new(dir.path()),
+                Path::new("zst"),
+                limits,
+            )
+            .await
+            .map(|(extracted_files, _)| extracted_files),
+        ];
+        for res in results {
+            let err = res.unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    DownloadError::LimitExceeded {
+                        what: "bytes of decoder memory",
+                        limit: 65536
+                    }
+                ),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_tbr() {
+        use async_compression::tokio::bufread::BrotliEncoder;
+        use tokio::io::AsyncReadExt;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"#!/bin/sh\necho hello\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bin/hello", &content[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let mut compressed = Vec::new();
+        BrotliEncoder::new(archive.as_slice())
+            .read_to_end(&mut compressed)
+            .await
+            .unwrap();
+        let compressed = Bytes::from(compressed);
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(compressed.clone())]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tbr,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("bin/hello")));
+        assert_eq!(fs::read(dir.path().join("bin/hello")).unwrap(), content);
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(compressed)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tbr,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("bin/hello")));
+        assert_eq!(
+            vfs.get_file(Path::new("bin/hello")).unwrap().as_ref(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_tlz() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"#!/bin/sh\necho hello\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bin/hello", &content[..])
+            .unwrap();
+        let compressed = Bytes::from(lzip::test::compress(&builder.into_inner().unwrap()));
+
+        assert_eq!(PkgFmt::sniff(&compressed), Some(PkgFmt::Tlz));
+
+        let dir = tempdir().unwrap();
+        let (extracted_files, _) = extract_tar_based_stream(
+            stream::iter([Ok(compressed.clone())]),
+            FsSink::new(dir.path()),
+            TarBasedFmt::Tlz,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("bin/hello")));
+        assert_eq!(fs::read(dir.path().join("bin/hello")).unwrap(), content);
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(compressed)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tlz,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("bin/hello")));
+        assert_eq!(
+            vfs.get_file(Path::new("bin/hello")).unwrap().as_ref(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_zst() {
+        let content = b"\x7fELF hello";
+        // Two concatenated frames
+        let mut compressed = zstd::encode_all(&content[..6], 0).unwrap();
+        compressed.extend(zstd::encode_all(&content[6..], 0).unwrap());
+        let compressed = Bytes::from(compressed);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("hello");
+        let (extracted_files, _) = extract_zst(
+            stream::iter([Ok(compressed.clone())]),
+            FsSink::new(dir.path()),
+            Path::new("hello"),
+            ExtractionLimits::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(fs::read(&path).unwrap(), content);
+
+        let (extracted_files, vfs) = extract_zst(
+            stream::iter([Ok(compressed)]),
+            InMemoryVfs::new(),
+            Path::new("hello"),
+            ExtractionLimits::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(vfs.get_file(Path::new("hello")).unwrap().as_ref(), content);
+    }
+
+    #[tokio::test]
+    async fn test_sniff_unknown_fmt() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        async fn sniff(data: Vec<u8>) -> Option<PkgFmt> {
+            sniff_unknown_fmt(&mut stream::iter([Ok(Bytes::from(data))]).peekable()).await
+        }
+
+        fn gzip(data: &[u8]) -> Vec<u8> {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+
+        let mut elf = b"\x7fELF".to_vec();
+        elf.resize(4096, 1);
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(elf.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, "hello", &elf[..]).unwrap();
+        let tarball = builder.into_inner().unwrap();
+
+        assert_eq!(sniff(elf.clone()).await, Some(PkgFmt::Bin));
+        assert_eq!(sniff(b"MZ\x90\x00".to_vec()).await, Some(PkgFmt::Bin));
+        assert_eq!(sniff(gzip(&elf)).await, Some(PkgFmt::Gz));
+        assert_eq!(sniff(gzip(&tarball)).await, Some(PkgFmt::Tgz));
+        assert_eq!(
+            sniff(zstd::encode_all(&elf[..], 0).unwrap()).await,
+            Some(PkgFmt::Zst)
+        );
+        assert_eq!(
+            sniff(zstd::encode_all(&tarball[..], 0).unwrap()).await,
+            Some(PkgFmt::Tzstd)
+        );
+        assert_eq!(sniff(b"#!/bin/sh".to_vec()).await, None);
+        assert_eq!(sniff(b"BZh is not bzip2".repeat(100)).await, None);
+
+        // Only the start of the bzip2 block is downloaded yet.
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        encoder.write_all(&tarball).unwrap();
+        let tbz2 = encoder.finish().unwrap();
+        assert_eq!(sniff(tbz2[..100].to_vec()).await, Some(PkgFmt::Tbz2));
+    }
+
+    #[tokio::test]
+    async fn test_extract_tgz_of_magic_bytes_name() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        // The tar archive starts with the name of its first entry, which
+        // must not be taken for bzip2 compressed data.
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "BZh91AY&SY", &content[..])
+            .unwrap();
+        let tarball = builder.into_inner().unwrap();
+        assert_eq!(PkgFmt::sniff(&tarball), Some(PkgFmt::Tar));
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&tarball).unwrap();
+        let archive = Bytes::from(encoder.finish().unwrap());
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tgz,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("BZh91AY&SY")));
+        assert_eq!(
+            vfs.get_file(Path::new("BZh91AY&SY")).unwrap().as_ref(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_nested_archive() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool/hello", &content[..])
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("tool.tar"), builder.into_inner().unwrap()).unwrap();
+
+        let mut extracted_files = ExtractedFiles::new();
+        extracted_files.add_file(Path::new("tool.tar"));
+
+        let extracted_files = extract_nested_archive(
+            extracted_files,
+            dir.path(),
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("tool/hello")));
+        assert!(!extracted_files.has_file(Path::new("tool.tar")));
+        assert!(!dir.path().join("tool.tar").exists());
+        assert_eq!(fs::read(dir.path().join("tool/hello")).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_extract_recursively_nested_archive() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let content = b"hello";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(compressed.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool/hello.gz", &compressed[..])
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("tool.tar"), builder.into_inner().unwrap()).unwrap();
+
+        let mut extracted_files = ExtractedFiles::new();
+        extracted_files.add_file(Path::new("tool.tar"));
+
+        let extracted_files = extract_nested_archive(
+            extracted_files,
+            dir.path(),
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("tool/hello")));
+        assert!(!extracted_files.has_file(Path::new("tool/hello.gz")));
+        assert!(!dir.path().join("tool.tar").exists());
+        assert!(!dir.path().join("tool/hello.gz").exists());
+        assert_eq!(fs::read(dir.path().join("tool/hello")).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_extract_double_compressed_tgz() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        fn gzip(data: &[u8]) -> Vec<u8> {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "hello", &content[..])
+            .unwrap();
+
+        let archive = Bytes::from(gzip(&gzip(&builder.into_inner().unwrap())));
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tgz,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(vfs.get_file(Path::new("hello")).unwrap().as_ref(), content);
+    }
+
+    #[tokio::test]
+    async fn test_extract_tlz4() {
+        use lz4_flex::frame::FrameEncoder;
+        use std::io::Write;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "hello", &content[..])
+            .unwrap();
+
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        let archive = Bytes::from(encoder.finish().unwrap());
+
+        assert_eq!(PkgFmt::sniff(&archive), Some(PkgFmt::Tlz4));
+
+        let (extracted_files, vfs) = extract_tar_based_stream(
+            stream::iter([Ok(archive)]),
+            InMemoryVfs::new(),
+            TarBasedFmt::Tlz4,
+            false,
+            ExtractionLimits::default(),
+            MetadataPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(vfs.get_file(Path::new("hello")).unwrap().as_ref(), content);
+    }
+
+    #[tokio::test]
+    async fn test_visit_tlz4() {
+        use lz4_flex::frame::FrameEncoder;
+        use std::{io::Write, path::PathBuf};
+
+        struct Visitor(Vec<(PathBuf, u64)>);
+
+        #[async_trait::async_trait]
+        impl TarEntriesVisitor for Visitor {
+            async fn visit(&mut self, entry: &mut dyn TarEntry) -> Result<(), DownloadError> {
+                self.0.push((entry.path()?.into_owned(), entry.size()?));
+                Ok(())
+            }
+        }
+
+        // Larger than a decompressed chunk, so that it is streamed.
+        let content = vec![b'a'; 200 * 1024];
+        let mut builder = tar::Builder::new(Vec::new());
+        for path in ["hello", "world"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, path, &*content).unwrap();
+        }
+
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        let archive = Bytes::from(encoder.finish().unwrap());
+
+        let mut visitor = Visitor(Vec::new());
+        extract_tar_based_stream_and_visit(
+            stream::iter(
+                archive
+                    .chunks(1000)
+                    .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                    .collect::<Vec<_>>(),
+            ),
+            TarBasedFmt::Tlz4,
+            true,
+            &mut visitor,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            visitor.0,
+            [
+                (PathBuf::from("hello"), content.len() as u64),
+                (PathBuf::from("world"), content.len() as u64)
+            ]
+        );
+
+        // Corrupted data is reported.
+        let mut visitor = Visitor(Vec::new());
+        assert!(extract_tar_based_stream_and_visit(
+            stream::iter([Ok(archive.slice(..archive.len() / 2))]),
+            TarBasedFmt::Tlz4,
+            false,
+            &mut visitor,
+        )
+        .await
+        .is_err());
+    }
+
     #[tokio::test]
     async fn test_and_extract() {
         let client = crate::remote::Client::new(
@@ -0,0 +1,883 @@
+//! Extraction of AppImages, which are an ELF runtime followed by a squashfs
+//! filesystem containing the application.
+
+use std::{
+    cmp::min,
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::bufread::ZlibDecoder;
+use tracing::debug;
+use xz2::bufread::XzDecoder;
+
+use super::{extraction_limits::LimitTracker, DownloadError, ExtractedFiles, ExtractionSink};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF_HEADER_LEN: usize = 64;
+
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+const SUPERBLOCK_LEN: usize = 96;
+
+/// Maximum size of a decompressed metadata block.
+const METADATA_BLOCK_LEN: usize = 8192;
+/// Set in the header of a metadata block if it is stored uncompressed.
+const METADATA_UNCOMPRESSED: u16 = 1 << 15;
+/// Set in the size of a data block if it is stored uncompressed.
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+/// Maximum block size allowed by squashfs.
+const MAX_BLOCK_SIZE: u32 = 1 << 20;
+
+const NO_FRAGMENT: u32 = u32::MAX;
+/// Size of an entry of the fragment table.
+const FRAGMENT_ENTRY_LEN: usize = 16;
+
+const BASIC_DIR: u16 = 1;
+const BASIC_FILE: u16 = 2;
+const EXTENDED_DIR: u16 = 8;
+const EXTENDED_FILE: u16 = 9;
+
+fn invalid(msg: &'static str) -> DownloadError {
+    DownloadError::InvalidAppImage(msg)
+}
+
+/// Little-endian reader of the fields of squashfs structures.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], DownloadError> {
+        if self.0.len() < len {
+            return Err(invalid("squashfs structure is truncated"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, DownloadError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, DownloadError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DownloadError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+/// Return the size of the ELF runtime, i.e. the offset of the squashfs
+/// filesystem, which starts right after the section headers.
+fn runtime_len(header: &[u8; ELF_HEADER_LEN]) -> Result<u64, DownloadError> {
+    if !header.starts_with(ELF_MAGIC) {
+        return Err(invalid("not an ELF executable"));
+    }
+
+    let u16_at = |offset: usize| {
+        let bytes = [header[offset], header[offset + 1]];
+        match header[5] {
+            1 => Ok(u16::from_le_bytes(bytes)),
+            2 => Ok(u16::from_be_bytes(bytes)),
+            _ => Err(invalid("invalid ELF endianness")),
+        }
+    };
+    let uint_at = |offset: usize, len: usize| {
+        let bytes = &header[offset..offset + len];
+        let fold = |acc: u64, byte: &u8| acc << 8 | u64::from(*byte);
+        match header[5] {
+            1 => Ok(bytes.iter().rev().fold(0, fold)),
+            2 => Ok(bytes.iter().fold(0, fold)),
+            _ => Err(invalid("invalid ELF endianness")),
+        }
+    };
+
+    let (section_headers, entry_len, entries) = match header[4] {
+        1 => (uint_at(0x20, 4)?, u16_at(0x2e)?, u16_at(0x30)?),
+        2 => (uint_at(0x28, 8)?, u16_at(0x3a)?, u16_at(0x3c)?),
+        _ => return Err(invalid("invalid ELF class")),
+    };
+
+    section_headers
+        .checked_add(u64::from(entry_len) * u64::from(entries))
+        .ok_or_else(|| invalid("invalid ELF section headers"))
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Compressor {
+    Gzip,
+    Xz,
+    Lz4,
+    Zstd,
+}
+
+impl Compressor {
+    fn decompress(self, data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        let limit = max_len as u64;
+
+        match self {
+            Compressor::Gzip => {
+                ZlibDecoder::new(data)
+                    .take(limit)
+                    .read_to_end(&mut decompressed)?;
+            }
+            Compressor::Xz => {
+                XzDecoder::new(data)
+                    .take(limit)
+                    .read_to_end(&mut decompressed)?;
+            }
+            Compressor::Zstd => {
+                zstd::stream::Decoder::with_buffer(data)?
+                    .take(limit)
+                    .read_to_end(&mut decompressed)?;
+            }
+            Compressor::Lz4 => {
+                decompressed.resize(max_len, 0);
+                let len = lz4_flex::block::decompress_into(data, &mut decompressed)
+                    .map_err(io::Error::other)?;
+                decompressed.truncate(len);
+            }
+        }
+
+        Ok(decompressed)
+    }
+}
+
+/// A decompressed metadata table.
+#[derive(Default)]
+struct Metadata {
+    data: Vec<u8>,
+    /// Offset of each metadata block relative to the start of the table,
+    /// mapped to its offset in `data`.
+    blocks: BTreeMap<u64, usize>,
+}
+
+impl Metadata {
+    fn at(&self, block: u64, offset: usize) -> Result<&[u8], DownloadError> {
+        self.blocks
+            .get(&block)
+            .and_then(|start| self.data.get(start + offset..))
+            .ok_or_else(|| invalid("invalid metadata reference"))
+    }
+
+    /// Return the data referenced by `reference`, which contains the offset
+    /// of the metadata block in the upper 48 bits and the offset in the
+    /// decompressed block in the lower 16 bits.
+    fn at_ref(&self, reference: u64) -> Result<&[u8], DownloadError> {
+        self.at(reference >> 16, (reference & 0xffff) as usize)
+    }
+}
+
+enum Inode {
+    Dir {
+        block: u32,
+        offset: u16,
+        /// Size of the listing, along with 3 bytes for `.` and `..`.
+        len: u32,
+    },
+    File {
+        blocks_start: u64,
+        len: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        block_sizes: Vec<u32>,
+    },
+    Other,
+}
+
+struct SquashFs<R> {
+    reader: R,
+    /// Offset of the squashfs filesystem in `reader`.
+    offset: u64,
+    compressor: Compressor,
+    block_size: u32,
+    root_inode: u64,
+    inodes: Metadata,
+    dirs: Metadata,
+    /// Start and size of the fragment blocks.
+    fragments: Vec<(u64, u32)>,
+}
+
+impl<R: Read + Seek> SquashFs<R> {
+    fn new(mut reader: R) -> Result<Self, DownloadError> {
+        let mut elf_header = [0; ELF_HEADER_LEN];
+        reader.rewind()?;
+        reader.read_exact(&mut elf_header)?;
+        let offset = runtime_len(&elf_header)?;
+
+        let mut superblock = [0; SUPERBLOCK_LEN];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut superblock)?;
+
+        let mut fields = Fields(&superblock);
+        if fields.bytes(4)? != SQUASHFS_MAGIC {
+            return Err(invalid("squashfs not found after the runtime"));
+        }
+        let _inode_count = fields.u32()?;
+        let _mtime = fields.u32()?;
+        let block_size = fields.u32()?;
+        let fragment_count = fields.u32()?;
+        let compressor = match fields.u16()? {
+            1 => Compressor::Gzip,
+            4 => Compressor::Xz,
+            5 => Compressor::Lz4,
+            6 => Compressor::Zstd,
+            _ => return Err(invalid("unsupported squashfs compression")),
+        };
+        let _block_log = fields.u16()?;
+        let _flags = fields.u16()?;
+        let _id_count = fields.u16()?;
+        if fields.u16()? != 4 {
+            return Err(invalid("unsupported squashfs version"));
+        }
+        let _version_minor = fields.u16()?;
+        let root_inode = fields.u64()?;
+        let _bytes_used = fields.u64()?;
+        let id_table = fields.u64()?;
+        let xattr_table = fields.u64()?;
+        let inode_table = fields.u64()?;
+        let dir_table = fields.u64()?;
+        let fragment_table = fields.u64()?;
+        let export_table = fields.u64()?;
+
+        if !(4096..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(invalid("invalid squashfs block size"));
+        }
+
+        // The directory table is followed by the other tables, which are
+        // `u64::MAX` if absent.
+        let dir_table_end = [fragment_table, export_table, id_table, xattr_table]
+            .into_iter()
+            .filter(|start| *start > dir_table && *start != u64::MAX)
+            .min()
+            .ok_or_else(|| invalid("invalid squashfs table"))?;
+
+        let mut fs = Self {
+            reader,
+            offset,
+            compressor,
+            block_size,
+            root_inode,
+            inodes: Metadata::default(),
+            dirs: Metadata::default(),
+            fragments: Vec::new(),
+        };
+
+        fs.inodes = fs.read_table(inode_table, dir_table)?;
+        fs.dirs = fs.read_table(dir_table, dir_table_end)?;
+        fs.fragments = fs.read_fragments(fragment_table, fragment_count)?;
+
+        Ok(fs)
+    }
+
+    fn read_at(&mut self, pos: u64, len: usize) -> Result<Vec<u8>, DownloadError> {
+        let mut data = vec![0; len];
+        self.reader.seek(SeekFrom::Start(self.offset + pos))?;
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read the metadata block at `pos`, return it along with its size.
+    fn read_metadata_block(&mut self, pos: u64) -> Result<(Vec<u8>, u64), DownloadError> {
+        let header = self.read_at(pos, 2)?;
+        let header = u16::from_le_bytes([header[0], header[1]]);
+
+        let len = usize::from(header & !METADATA_UNCOMPRESSED);
+        if len > METADATA_BLOCK_LEN {
+            return Err(invalid("invalid metadata block"));
+        }
+        let data = self.read_at(pos + 2, len)?;
+
+        let data = if header & METADATA_UNCOMPRESSED != 0 {
+            data
+        } else {
+            self.compressor.decompress(&data, METADATA_BLOCK_LEN)?
+        };
+
+        Ok((data, 2 + len as u64))
+    }
+
+    fn read_table(&mut self, start: u64, end: u64) -> Result<Metadata, DownloadError> {
+        let mut table = Metadata::default();
+        let mut pos = start;
+
+        while pos < end {
+            let (data, len) = self.read_metadata_block(pos)?;
+            table.blocks.insert(pos - start, table.data.len());
+            table.data.extend(data);
+            pos += len;
+        }
+
+        Ok(table)
+    }
+
+    fn read_fragments(&mut self, start: u64, count: u32) -> Result<Vec<(u64, u32)>, DownloadError> {
+        let count = count as usize;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        // The table is a list of the positions of the metadata blocks
+        // containing the entries.
+        let blocks = (count * FRAGMENT_ENTRY_LEN).div_ceil(METADATA_BLOCK_LEN);
+        let index = self.read_at(start, blocks * 8)?;
+
+        let mut entries = Vec::new();
+        for pos in index.chunks(8) {
+            let pos = u64::from_le_bytes(pos.try_into().unwrap());
+            entries.extend(self.read_metadata_block(pos)?.0);
+        }
+
+        let mut fields = Fields(&entries);
+        (0..count)
+            .map(|_| {
+                let start = fields.u64()?;
+                let size = fields.u32()?;
+                let _unused = fields.u32()?;
+                Ok((start, size))
+            })
+            .collect()
+    }
+
+    fn inode(&self, reference: u64) -> Result<Inode, DownloadError> {
+        let mut fields = Fields(self.inodes.at_ref(reference)?);
+        let kind = fields.u16()?;
+        // Permissions, uid, gid, mtime and inode number.
+        fields.bytes(14)?;
+
+        Ok(match kind {
+            BASIC_DIR => {
+                let block = fields.u32()?;
+                let _link_count = fields.u32()?;
+                let len = fields.u16()?.into();
+                let offset = fields.u16()?;
+                Inode::Dir { block, offset, len }
+            }
+            EXTENDED_DIR => {
+                let _link_count = fields.u32()?;
+                let len = fields.u32()?;
+                let block = fields.u32()?;
+                let _parent = fields.u32()?;
+                let _index_count = fields.u16()?;
+                let offset = fields.u16()?;
+                Inode::Dir { block, offset, len }
+            }
+            BASIC_FILE | EXTENDED_FILE => {
+                let (blocks_start, len, fragment, fragment_offset) = if kind == BASIC_FILE {
+                    let blocks_start = fields.u32()?.into();
+                    let fragment = fields.u32()?;
+                    let fragment_offset = fields.u32()?;
+                    let len = fields.u32()?.into();
+                    (blocks_start, len, fragment, fragment_offset)
+                } else {
+                    let blocks_start = fields.u64()?;
+                    let len = fields.u64()?;
+                    let _sparse = fields.u64()?;
+                    let _link_count = fields.u32()?;
+                    let fragment = fields.u32()?;
+                    let fragment_offset = fields.u32()?;
+                    let _xattr = fields.u32()?;
+                    (blocks_start, len, fragment, fragment_offset)
+                };
+
+                // The tail of the file is stored in a fragment if present.
+                let block_size = u64::from(self.block_size);
+                let blocks = if fragment == NO_FRAGMENT {
+                    len.div_ceil(block_size)
+                } else {
+                    len / block_size
+                };
+                let block_sizes = (0..blocks)
+                    .map(|_| fields.u32())
+                    .collect::<Result<_, _>>()?;
+
+                Inode::File {
+                    blocks_start,
+                    len,
+                    fragment,
+                    fragment_offset,
+                    block_sizes,
+                }
+            }
+            _ => Inode::Other,
+        })
+    }
+
+    /// Return the name and the inode reference of the entries of the
+    /// directory.
+    fn dir_entries(
+        &self,
+        block: u32,
+        offset: u16,
+        len: u32,
+    ) -> Result<Vec<(String, u64)>, DownloadError> {
+        let listing = self.dirs.at(block.into(), offset.into())?;
+        let len = (len as usize).saturating_sub(3);
+        let mut fields = Fields(
+            listing
+                .get(..len)
+                .ok_or_else(|| invalid("directory listing is truncated"))?,
+        );
+
+        let mut entries = Vec::new();
+        while !fields.0.is_empty() {
+            let count = fields.u32()? + 1;
+            let inode_block = u64::from(fields.u32()?);
+            let _inode_number = fields.u32()?;
+
+            for _ in 0..count {
+                let inode_offset = u64::from(fields.u16()?);
+                let _inode_number_offset = fields.u16()?;
+                let _kind = fields.u16()?;
+                let name_len = usize::from(fields.u16()?) + 1;
+                let name = String::from_utf8(fields.bytes(name_len)?.to_vec())
+                    .map_err(|_| invalid("invalid file name"))?;
+
+                entries.push((name, inode_block << 16 | inode_offset));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn read_file(
+        &mut self,
+        blocks_start: u64,
+        len: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        block_sizes: &[u32],
+    ) -> Result<Vec<u8>, DownloadError> {
+        let block_size = self.block_size as usize;
+        let mut content = Vec::new();
+        let mut pos = blocks_start;
+
+        for &size in block_sizes {
+            let block_len = min(block_size as u64, len - content.len() as u64) as usize;
+
+            if size == 0 {
+                // Sparse block
+                content.resize(content.len() + block_len, 0);
+                continue;
+            }
+
+            let stored_len = size & !DATA_UNCOMPRESSED;
+            if stored_len > MAX_BLOCK_SIZE {
+                return Err(invalid("invalid data block"));
+            }
+            let data = self.read_at(pos, stored_len as usize)?;
+            pos += u64::from(stored_len);
+
+            let data = if size & DATA_UNCOMPRESSED != 0 {
+                data
+            } else {
+                self.compressor.decompress(&data, block_size)?
+            };
+            content.extend(
+                data.get(..block_len)
+                    .ok_or_else(|| invalid("data block is truncated"))?,
+            );
+        }
+
+        if fragment != NO_FRAGMENT {
+            let (start, size) = *self
+                .fragments
+                .get(fragment as usize)
+                .ok_or_else(|| invalid("invalid fragment"))?;
+
+            let stored_len = size & !DATA_UNCOMPRESSED;
+            if stored_len > MAX_BLOCK_SIZE {
+                return Err(invalid("invalid fragment"));
+            }
+            let data = self.read_at(start, stored_len as usize)?;
+            let data = if size & DATA_UNCOMPRESSED != 0 {
+                data
+            } else {
+                self.compressor.decompress(&data, block_size)?
+            };
+
+            let tail_len = (len - content.len() as u64) as usize;
+            let start = fragment_offset as usize;
+            content.extend(
+                data.get(start..start + tail_len)
+                    .ok_or_else(|| invalid("fragment is truncated"))?,
+            );
+        }
+
+        Ok(content)
+    }
+
+    /// Call `f` for every directory and regular file, with the content of
+    /// the latter, parents before their children.
+    ///
+    /// Symlinks and special files are skipped.
+    fn for_each_entry(
+        &mut self,
+        mut f: impl FnMut(&Path, Option<&mut dyn Read>) -> io::Result<()>,
+    ) -> Result<(), DownloadError> {
+        let mut visited = HashSet::new();
+        let mut dirs = vec![(PathBuf::new(), self.root_inode)];
+
+        while let Some((dir, reference)) = dirs.pop() {
+            if !visited.insert(reference) {
+                return Err(invalid("squashfs directories contain a cycle"));
+            }
+
+            let Inode::Dir { block, offset, len } = self.inode(reference)? else {
+                return Err(invalid("expected a directory inode"));
+            };
+
+            for (name, reference) in self.dir_entries(block, offset, len)? {
+                let mut components = Path::new(&name).components();
+                let path = match (components.next(), components.next()) {
+                    (Some(Component::Normal(name)), None) => dir.join(name),
+                    _ => return Err(invalid("invalid file name")),
+                };
+
+                match self.inode(reference)? {
+                    Inode::Dir { .. } => {
+                        f(&path, None)?;
+                        dirs.push((path, reference));
+                    }
+                    Inode::File {
+                        blocks_start,
+                        len,
+                        fragment,
+                        fragment_offset,
+                        block_sizes,
+                    } => {
+                        let content = self.read_file(
+                            blocks_start,
+                            len,
+                            fragment,
+                            fragment_offset,
+                            &block_sizes,
+                        )?;
+                        f(&path, Some(&mut &*content))?;
+                    }
+                    Inode::Other => debug!("Skipping `{}` in AppImage", path.display()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) fn do_extract_appimage(
+    f: File,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    SquashFs::new(f)?.for_each_entry(|path, content| {
+        limits.add_entry()?;
+        match content {
+            None => {
+                sink.create_dir_all(path)?;
+                extracted_files.add_dir(path);
+            }
+            Some(content) => {
+                sink.write_file(path, &mut limits.reader(content))?;
+                extracted_files.add_file(path);
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::ExtractionLimits, *};
+
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::super::{FsSink, InMemoryVfs};
+
+    fn inode_header(kind: u16) -> Vec<u8> {
+        let mut header = kind.to_le_bytes().to_vec();
+        header.resize(16, 0);
+        header
+    }
+
+    /// Build an AppImage of a 64-bit runtime with no code and a squashfs
+    /// containing `usr/bin/hello`, `usr/share/README` and an `AppRun`
+    /// symlink.
+    fn appimage() -> Vec<u8> {
+        const BLOCK_SIZE: u32 = 4096;
+
+        let mut data = Vec::new();
+        let mut inodes = Vec::new();
+        let mut dirs = Vec::new();
+
+        // hello, spanning a compressed block and a fragment.
+        let hello: Vec<u8> = (0..BLOCK_SIZE + 6).map(|i| i as u8).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&hello[..BLOCK_SIZE as usize]).unwrap();
+        let block = encoder.finish().unwrap();
+        let hello_inode = inodes.len() as u64;
+        inodes.extend(inode_header(BASIC_FILE));
+        inodes.extend((SUPERBLOCK_LEN as u32).to_le_bytes());
+        inodes.extend(0u32.to_le_bytes());
+        inodes.extend(0u32.to_le_bytes());
+        inodes.extend((hello.len() as u32).to_le_bytes());
+        inodes.extend((block.len() as u32).to_le_bytes());
+        data.extend(block);
+
+        // The fragment, uncompressed, containing the tail of hello and
+        // README.
+        let fragment_start = SUPERBLOCK_LEN + data.len();
+        data.extend(&hello[BLOCK_SIZE as usize..]);
+        data.extend(b"readme");
+        let readme_inode = inodes.len() as u64;
+        inodes.extend(inode_header(BASIC_FILE));
+        inodes.extend(0u32.to_le_bytes());
+        inodes.extend(0u32.to_le_bytes());
+        inodes.extend(6u32.to_le_bytes());
+        inodes.extend(6u32.to_le_bytes());
+
+        let symlink_inode = inodes.len() as u64;
+        inodes.extend(inode_header(3));
+        inodes.extend(1u32.to_le_bytes());
+        inodes.extend(13u32.to_le_bytes());
+        inodes.extend(b"usr/bin/hello");
+
+        let mut dir = |inodes: &mut Vec<u8>, entries: &[(&str, u64)]| {
+            let listing_offset = dirs.len() as u16;
+            dirs.extend((entries.len() as u32 - 1).to_le_bytes());
+            dirs.extend(0u32.to_le_bytes());
+            dirs.extend(1u32.to_le_bytes());
+            for (name, inode) in entries {
+                dirs.extend((*inode as u16).to_le_bytes());
+                dirs.extend(0u16.to_le_bytes());
+                dirs.extend(1u16.to_le_bytes());
+                dirs.extend((name.len() as u16 - 1).to_le_bytes());
+                dirs.extend(name.as_bytes());
+            }
+            let listing_len = dirs.len() as u16 - listing_offset + 3;
+
+            let inode = inodes.len() as u64;
+            inodes.extend(inode_header(BASIC_DIR));
+            inodes.extend(0u32.to_le_bytes());
+            inodes.extend(2u32.to_le_bytes());
+            inodes.extend(listing_len.to_le_bytes());
+            inodes.extend(listing_offset.to_le_bytes());
+            inodes.extend(0u32.to_le_bytes());
+            inode
+        };
+        let bin_inode = dir(&mut inodes, &[("hello", hello_inode)]);
+        let share_inode = dir(&mut inodes, &[("README", readme_inode)]);
+        let usr_inode = dir(&mut inodes, &[("bin", bin_inode), ("share", share_inode)]);
+        let root_inode = dir(
+            &mut inodes,
+            &[("AppRun", symlink_inode), ("usr", usr_inode)],
+        );
+
+        let mut fragments = (fragment_start as u64).to_le_bytes().to_vec();
+        fragments.extend((12 | DATA_UNCOMPRESSED).to_le_bytes());
+        fragments.extend(0u32.to_le_bytes());
+
+        let mut tables = Vec::new();
+        let metadata_block = |tables: &mut Vec<u8>, block: &[u8]| {
+            let start = (SUPERBLOCK_LEN + data.len() + tables.len()) as u64;
+            tables.extend((block.len() as u16 | METADATA_UNCOMPRESSED).to_le_bytes());
+            tables.extend(block);
+            start
+        };
+        let inode_table = metadata_block(&mut tables, &inodes);
+        let dir_table = metadata_block(&mut tables, &dirs);
+        let fragment_block = metadata_block(&mut tables, &fragments);
+        let fragment_table = (SUPERBLOCK_LEN + data.len() + tables.len()) as u64;
+        tables.extend(fragment_block.to_le_bytes());
+        let id_table = (SUPERBLOCK_LEN + data.len() + tables.len()) as u64;
+
+        let mut squashfs = SQUASHFS_MAGIC.to_vec();
+        for field in [5, 0, BLOCK_SIZE, 1] {
+            squashfs.extend(field.to_le_bytes());
+        }
+        for field in [1u16, 12, 0, 1, 4, 0] {
+            squashfs.extend(field.to_le_bytes());
+        }
+        for field in [
+            root_inode,
+            id_table,
+            id_table,
+            u64::MAX,
+            inode_table,
+            dir_table,
+            fragment_table,
+            u64::MAX,
+        ] {
+            squashfs.extend(field.to_le_bytes());
+        }
+        squashfs.extend(data);
+        squashfs.extend(tables);
+
+        // ELF header with a single section header at offset 64.
+        let mut appimage = ELF_MAGIC.to_vec();
+        appimage.extend([2, 1, 1]);
+        appimage.resize(ELF_HEADER_LEN, 0);
+        appimage[0x28..0x30].copy_from_slice(&64u64.to_le_bytes());
+        appimage[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        appimage[0x3c..0x3e].copy_from_slice(&1u16.to_le_bytes());
+        appimage.resize(128, 0);
+        appimage.extend(squashfs);
+        appimage
+    }
+
+    fn tmpfile(content: &[u8]) -> File {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(content).unwrap();
+        f
+    }
+
+    #[test]
+    fn test_extract_appimage() {
+        let hello: Vec<u8> = (0..4096 + 6).map(|i| i as u8).collect();
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted_files = do_extract_appimage(
+            tmpfile(&appimage()),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("usr/bin/hello")));
+        assert!(extracted_files.has_file(Path::new("usr/share/README")));
+        assert!(extracted_files.get_dir(Path::new("usr/bin")).is_some());
+        assert!(!extracted_files.has_file(Path::new("AppRun")));
+        assert_eq!(
+            std::fs::read(dir.path().join("usr/bin/hello")).unwrap(),
+            hello
+        );
+
+        let mut vfs = InMemoryVfs::new();
+        do_extract_appimage(
+            tmpfile(&appimage()),
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vfs.get_file(Path::new("usr/share/README"))
+                .unwrap()
+                .as_ref(),
+            b"readme"
+        );
+        assert!(vfs.has_dir(Path::new("usr/share")));
+    }
+
+    #[test]
+    fn test_invalid_appimage() {
+        let mut appimage = appimage();
+        appimage[128..132].copy_from_slice(b"sqsh");
+
+        assert!(matches!(
+            SquashFs::new(tmpfile(&appimage)),
+            Err(DownloadError::InvalidAppImage(_))
+        ));
+    }
+
+    #[test]
+    fn test_runtime_len() {
+        let mut header = [0; ELF_HEADER_LEN];
+        header[..4].copy_from_slice(ELF_MAGIC);
+
+        // 32-bit big-endian, 3 section headers of 40 bytes at offset 1000.
+        header[4..6].copy_from_slice(&[1, 2]);
+        header[0x20..0x24].copy_from_slice(&1000u32.to_be_bytes());
+        header[0x2e..0x30].copy_from_slice(&40u16.to_be_bytes());
+        header[0x30..0x32].copy_from_slice(&3u16.to_be_bytes());
+        assert_eq!(runtime_len(&header).unwrap(), 1120);
+
+        header[5] = 3;
+        assert!(runtime_len(&header).is_err());
+
+        header[4..6].copy_from_slice(&[3, 1]);
+        assert!(runtime_len(&header).is_err());
+
+        header[..4].copy_from_slice(b"MZ\0\0");
+        assert!(runtime_len(&header).is_err());
+    }
+
+    /// Check that the AppImage is rejected once `patch` is applied to the
+    /// squashfs, which starts at offset 128.
+    fn assert_rejected(patch: impl FnOnce(&mut [u8])) {
+        let mut appimage = appimage();
+        patch(&mut appimage[128..]);
+
+        assert!(matches!(
+            SquashFs::new(tmpfile(&appimage)).and_then(|mut fs| fs.for_each_entry(|_, _| Ok(()))),
+            Err(DownloadError::InvalidAppImage(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_superblock() {
+        // Unsupported compression
+        assert_rejected(|squashfs| squashfs[20..22].copy_from_slice(&3u16.to_le_bytes()));
+        // Unsupported version
+        assert_rejected(|squashfs| squashfs[28..30].copy_from_slice(&3u16.to_le_bytes()));
+        // Block size too small and too large
+        assert_rejected(|squashfs| squashfs[12..16].copy_from_slice(&512u32.to_le_bytes()));
+        assert_rejected(|squashfs| {
+            squashfs[12..16].copy_from_slice(&(MAX_BLOCK_SIZE * 2).to_le_bytes())
+        });
+        // Root inode pointing past the inode table
+        assert_rejected(|squashfs| squashfs[32..40].copy_from_slice(&0xffffu64.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_invalid_file_name() {
+        assert_rejected(|squashfs| {
+            let name = squashfs
+                .windows(5)
+                .rposition(|window| window == b"hello")
+                .unwrap();
+            squashfs[name..name + 5].copy_from_slice(b"../hi");
+        });
+    }
+
+    #[test]
+    fn test_truncated_appimage() {
+        let appimage = appimage();
+
+        for len in [32, 128 + SUPERBLOCK_LEN / 2, appimage.len() - 8] {
+            assert!(SquashFs::new(tmpfile(&appimage[..len])).is_err());
+        }
+    }
+
+    #[test]
+    fn test_extraction_limits() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for limits in [
+            ExtractionLimits {
+                max_size: 4096,
+                ..Default::default()
+            },
+            ExtractionLimits {
+                max_entries: 3,
+                ..Default::default()
+            },
+        ] {
+            assert!(do_extract_appimage(
+                tmpfile(&appimage()),
+                &mut FsSink::new(dir.path()),
+                &mut LimitTracker::new(limits),
+            )
+            .is_err());
+        }
+    }
+}
@@ -2,155 +2,808 @@ use std::{
     borrow::Cow,
     fs,
     future::Future,
-    io::{self, Write},
+    io::{self, Read, Seek, Write},
     path::{Component, Path, PathBuf},
+    pin::Pin,
 };
 
 use bytes::Bytes;
-use futures_util::Stream;
-use tempfile::tempfile as create_tmpfile;
+use flate2::bufread::MultiGzDecoder;
+use futures_util::{stream::Peekable, Stream};
+use tempfile::{tempfile as create_tmpfile, NamedTempFile};
 use tokio::sync::mpsc;
-use tracing::debug;
-
-use super::{extracter::*, DownloadError, ExtractedFiles, TarBasedFmt};
+use tracing::{debug, warn};
+
+use super::{
+    extracter::*,
+    extraction_limits::LimitTracker,
+    extraction_sink::create_parent_dir,
+    parallel_writer::{ParallelWriter, PendingFile, MAX_PARALLEL_FILE_SIZE},
+    DownloadError, ExtractedFiles, ExtractionLimits, ExtractionSink, MetadataPolicy, ModePolicy,
+    PkgFmt, TarBasedFmt,
+};
+#[cfg(feature = "dmg")]
+use crate::download::dmg_extraction::do_extract_dmg;
 use crate::{
-    download::zip_extraction::do_extract_zip,
+    download::{
+        appimage_extraction::do_extract_appimage, cpio_extraction::do_extract_cpio,
+        deb_extraction::do_extract_deb, installer_extraction::do_extract_installer,
+        msi_extraction::do_extract_msi, rpm_extraction::do_extract_rpm,
+        sevenz_extraction::do_extract_7z, zip_extraction::do_extract_zip_from_stream,
+    },
     utils::{extract_with_blocking_task, StreamReadable},
 };
 
-pub async fn extract_bin<S>(stream: S, path: &Path) -> Result<ExtractedFiles, DownloadError>
+/// Validate `fmt` guessed from the url against the magic bytes of the first
+/// chunk of `stream`, and return the format sniffed by [`sniff_pkg_fmt`] if
+/// they differ.
+///
+/// [`PkgFmt::Bin`] is never overridden since the binary may be of any format,
+/// nor is any format by the compressed tarball or binary of the same
+/// compression, e.g. [`PkgFmt::Gz`] by [`PkgFmt::Tgz`].
+pub async fn sniff_fmt<S>(stream: &mut Peekable<S>, fmt: PkgFmt) -> PkgFmt
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin,
+{
+    /// Return the compressed tarball format of the same compression.
+    fn as_tarball(fmt: PkgFmt) -> PkgFmt {
+        match fmt {
+            PkgFmt::Gz => PkgFmt::Tgz,
+            PkgFmt::Xz => PkgFmt::Txz,
+            PkgFmt::Zst => PkgFmt::Tzstd,
+            fmt => fmt,
+        }
+    }
+
+    if fmt == PkgFmt::Bin {
+        return fmt;
+    }
+
+    match Pin::new(stream).peek().await {
+        Some(Ok(bytes)) => match sniff_pkg_fmt(bytes) {
+            Some(sniffed) if sniffed != PkgFmt::Bin && as_tarball(sniffed) != as_tarball(fmt) => {
+                warn!("Expected {fmt} archive but the download is {sniffed}, extracting it as {sniffed}");
+                sniffed
+            }
+            _ => fmt,
+        },
+        _ => fmt,
+    }
+}
+
+/// Detect the format of `stream`, whose url has no recognisable extension,
+/// from the magic bytes of its first chunk with [`sniff_pkg_fmt`].
+pub async fn sniff_unknown_fmt<S>(stream: &mut Peekable<S>) -> Option<PkgFmt>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin,
+{
+    match Pin::new(stream).peek().await {
+        Some(Ok(bytes)) => sniff_pkg_fmt(bytes),
+        _ => None,
+    }
+}
+
+pub async fn extract_bin<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
 {
-    debug!("Writing to `{}`", path.display());
+    debug!("Writing to `{}`", file_name.display());
+
+    let file_name = file_name.to_owned();
 
-    extract_with_blocking_decoder(stream, path, |rx, path| {
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
         let mut extracted_files = ExtractedFiles::new();
 
-        extracted_files.add_file(Path::new(path.file_name().unwrap()));
+        extracted_files.add_file(&file_name);
 
-        write_stream_to_file(rx, fs::File::create(path)?)?;
+        sink.write_file(&file_name, &mut limits.reader(StreamReadable::new(rx)))?;
 
         Ok(extracted_files)
     })
     .await
 }
 
-pub async fn extract_zip<S>(stream: S, path: &Path) -> Result<ExtractedFiles, DownloadError>
+/// Extract the executable installed by the NSIS or Inno Setup installer
+/// to `file_name`.
+pub async fn extract_installer<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    debug!("Downloading from installer to tempfile");
+
+    let file_name = file_name.to_owned();
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Extracting `{}` from installer", file_name.display());
+
+        // The installer is unpacked from a path.
+        let installer = NamedTempFile::new()?;
+        let f = write_stream_to_file(rx, installer.as_file().try_clone()?)?;
+
+        do_extract_installer(installer.path(), f, &file_name, sink, limits)
+    })
+    .await
+}
+
+/// Decompress the gzip-compressed binary to `file_name`.
+pub async fn extract_gz<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    debug!("Decompressing from gzip to `{}`", file_name.display());
+
+    extract_compressed_bin(stream, sink, file_name, limits, gz_decoder).await
+}
+
+/// Decompress the xz-compressed binary to `file_name`.
+pub async fn extract_xz<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    debug!("Decompressing from xz to `{}`", file_name.display());
+
+    extract_compressed_bin(stream, sink, file_name, limits, xz_decoder).await
+}
+
+/// Decompress the zstd-compressed binary to `file_name`.
+pub async fn extract_zst<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    debug!("Decompressing from zstd to `{}`", file_name.display());
+
+    extract_compressed_bin(stream, sink, file_name, limits, zst_decoder).await
+}
+
+pub async fn extract_zip<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
 {
-    debug!("Downloading from zip archive to tempfile");
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Decompressing from zip archive");
 
-    extract_with_blocking_decoder(stream, path, |rx, path| {
-        debug!("Decompressing from zip archive to `{}`", path.display());
+        do_extract_zip_from_stream(StreamReadable::new(rx), create_tmpfile()?, sink, limits)
+    })
+    .await
+}
 
-        do_extract_zip(write_stream_to_file(rx, create_tmpfile()?)?, path).map_err(io::Error::from)
+pub async fn extract_7z<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Downloading from 7z archive to tempfile");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Decompressing from 7z archive");
+
+        let f = write_stream_to_file(rx, create_tmpfile()?)?;
+        do_extract_7z(f, sink, limits)
     })
     .await
 }
 
-pub async fn extract_tar_based_stream<S>(
+/// Extract the binaries of the deb package.
+pub async fn extract_deb<S, Sink>(
     stream: S,
-    dst: &Path,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Extracting binaries from deb package");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        do_extract_deb(StreamReadable::new(rx), sink, limits)
+    })
+    .await
+}
+
+/// Extract the binaries of the rpm package.
+pub async fn extract_rpm<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Extracting binaries from rpm package");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        do_extract_rpm(StreamReadable::new(rx), sink, limits)
+    })
+    .await
+}
+
+pub async fn extract_cpio<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Extracting from cpio archive");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        do_extract_cpio(StreamReadable::new(rx), sink, limits)
+    })
+    .await
+}
+
+/// Strip the runtime of the AppImage and extract the application.
+pub async fn extract_appimage<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Downloading from AppImage to tempfile");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Extracting from AppImage");
+
+        let f = write_stream_to_file(rx, create_tmpfile()?)?;
+        do_extract_appimage(f, sink, limits)
+    })
+    .await
+}
+
+/// Attach the macOS disk image with `hdiutil` and copy its content.
+#[cfg(feature = "dmg")]
+pub async fn extract_dmg<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Downloading from dmg image to tempfile");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Extracting from dmg image");
+
+        // hdiutil needs a path to the image.
+        let image = NamedTempFile::new()?;
+        write_stream_to_file(rx, image.as_file().try_clone()?)?;
+
+        do_extract_dmg(image.path(), sink, limits)
+    })
+    .await
+}
+
+/// Extract the executables of the Windows installer package.
+pub async fn extract_msi<S, Sink>(
+    stream: S,
+    sink: Sink,
+    limits: ExtractionLimits,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
+    Sink: ExtractionSink,
+{
+    debug!("Downloading from msi package to tempfile");
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        debug!("Extracting executables from msi package");
+
+        let mut msi = write_stream_to_file(rx, create_tmpfile()?)?;
+        msi.rewind()?;
+
+        do_extract_msi(msi, sink, limits)
+    })
+    .await
+}
+
+/// Return true if `target` of symlink `link`, relative to `dst`, stays
+/// inside of `dst`.
+///
+/// The directory of the link must exist, and is resolved in case it goes
+/// through other symlinks.
+pub(super) fn is_symlink_inside(dst: &Path, link: &Path, target: &Path) -> io::Result<bool> {
+    let dst = dst.canonicalize()?;
+    let parent = dst.join(link);
+    let parent = parent
+        .parent()
+        .expect("all full entry paths should have parent paths");
+
+    Ok(parent
+        .canonicalize()?
+        .strip_prefix(&dst)
+        .is_ok_and(|parent| is_symlink_target_inside(parent.components().count(), target)))
+}
+
+/// Return true if `target` of a symlink in a directory `depth` levels below
+/// the directory extracted to stays inside of it.
+///
+/// `..` is only allowed at the start of `target`, since it could go up from
+/// the target of another symlink otherwise.
+fn is_symlink_target_inside(mut depth: usize, target: &Path) -> bool {
+    let mut is_leading = true;
+
+    for part in target.components() {
+        match part {
+            Component::ParentDir if is_leading => match depth.checked_sub(1) {
+                Some(parent_depth) => depth = parent_depth,
+                None => return false,
+            },
+            Component::CurDir => (),
+            Component::Normal(..) => is_leading = false,
+            Component::Prefix(..) | Component::RootDir | Component::ParentDir => return false,
+        }
+    }
+
+    true
+}
+
+pub async fn extract_tar_based_stream<S, Sink>(
+    stream: S,
+    mut sink: Sink,
     fmt: TarBasedFmt,
-) -> Result<ExtractedFiles, DownloadError>
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
+) -> Result<(ExtractedFiles, Sink), DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
 {
-    debug!("Extracting from {fmt} archive to {}", dst.display());
+    debug!("Extracting from {fmt} archive");
 
-    extract_with_blocking_decoder(stream, dst, move |rx, dst| {
-        // Adapted from https://docs.rs/tar/latest/src/tar/archive.rs.html#189-219
+    extract_with_blocking_task(stream, limits.low_memory, move |rx| {
+        let tar = create_tar_decoder(StreamReadable::new(rx), fmt, limits.max_decoder_memory)?;
 
-        if dst.symlink_metadata().is_err() {
-            fs::create_dir_all(dst)?;
-        }
+        let extracted_files = match sink.dir() {
+            Some(dst) => unpack_tar(tar, dst, preserve_symlinks, limits, metadata_policy)?,
+            None => {
+                extract_tar_to_sink(tar, &mut sink, preserve_symlinks, limits, metadata_policy)?
+            }
+        };
 
-        // Canonicalizing the dst directory will prepend the path with '\\?\'
-        // on windows which will allow windows APIs to treat the path as an
-        // extended-length path with a 32,767 character limit. Otherwise all
-        // unpacked paths over 260 characters will fail on creation with a
-        // NotFound exception.
-        let dst = &dst
-            .canonicalize()
-            .map(Cow::Owned)
-            .unwrap_or(Cow::Borrowed(dst));
+        Ok((extracted_files, sink))
+    })
+    .await
+}
 
-        let mut tar = create_tar_decoder(StreamReadable::new(rx), fmt)?;
-        let mut entries = tar.entries()?;
+/// Unpack `tar` to `dst` on the filesystem with [`tar`] itself, writing
+/// its files in parallel and restoring their metadata and hardlinks.
+fn unpack_tar(
+    mut tar: tar::Archive<Box<dyn Read>>,
+    dst: &Path,
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
+) -> io::Result<ExtractedFiles> {
+    // Adapted from https://docs.rs/tar/latest/src/tar/archive.rs.html#189-219
+
+    if dst.symlink_metadata().is_err() {
+        fs::create_dir_all(dst)?;
+    }
 
-        let mut extracted_files = ExtractedFiles::new();
+    // Canonicalizing the dst directory will prepend the path with '\\?\'
+    // on windows which will allow windows APIs to treat the path as an
+    // extended-length path with a 32,767 character limit. Otherwise all
+    // unpacked paths over 260 characters will fail on creation with a
+    // NotFound exception.
+    let dst = &dst
+        .canonicalize()
+        .map(Cow::Owned)
+        .unwrap_or(Cow::Borrowed(dst));
+
+    let mut entries = tar.entries()?;
+
+    let mut extracted_files = ExtractedFiles::new();
+    // Reading files into memory to write them concurrently is only
+    // worth it if memory is not scarce, and the extended attributes are
+    // only restored by tar.
+    let write_in_parallel = !limits.low_memory && !metadata_policy.preserve_xattrs;
+    let mut limits = LimitTracker::new(limits);
+    let mut writer = ParallelWriter::new();
+
+    // Delay any directory entries until the end (they will be created if needed by
+    // descendants), to ensure that directory permissions do not interfer with descendant
+    // extraction.
+    let mut directories = Vec::new();
+
+    while let Some(mut entry) = entries.next().transpose()? {
+        limits.add_entry()?;
+
+        match entry.header().entry_type() {
+            tar::EntryType::Regular
+                if write_in_parallel && entry.size() <= MAX_PARALLEL_FILE_SIZE =>
+            {
+                limits.add_size(entry.size())?;
+
+                // Skipped like unpack_in does.
+                let Some(path) = normalize_entry_path(&entry.path()?) else {
+                    continue;
+                };
+
+                let file_path = dst.join(&path);
+                let parent = file_path
+                    .parent()
+                    .expect("all full entry paths should have parent paths");
+                fs::create_dir_all(parent)?;
+                // Same check as unpack_in, so that files are never
+                // written through a symlink pointing outside of dst.
+                if !parent.canonicalize()?.starts_with(dst) {
+                    return Err(io::Error::other(format!(
+                        "trying to unpack `{}` outside of destination path `{}`",
+                        path.display(),
+                        dst.display()
+                    )));
+                }
 
-        // Delay any directory entries until the end (they will be created if needed by
-        // descendants), to ensure that directory permissions do not interfer with descendant
-        // extraction.
-        let mut directories = Vec::new();
-
-        while let Some(mut entry) = entries.next().transpose()? {
-            match entry.header().entry_type() {
-                tar::EntryType::Regular => {
-                    // unpack_in returns false if the path contains ".."
-                    // and is skipped.
-                    if entry.unpack_in(dst)? {
-                        let path = entry.path()?;
-
-                        // create normalized_path in the same way
-                        // tar::Entry::unpack_in would normalize the path.
-                        let mut normalized_path = PathBuf::new();
-
-                        for part in path.components() {
-                            match part {
-                                Component::Prefix(..) | Component::RootDir | Component::CurDir => {
-                                    continue
-                                }
-
-                                // unpack_in would return false if this happens.
-                                Component::ParentDir => unreachable!(),
-
-                                Component::Normal(part) => normalized_path.push(part),
-                            }
-                        }
-
-                        extracted_files.add_file(&normalized_path);
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut data)?;
+                writer.write(PendingFile {
+                    path: file_path,
+                    data,
+                    mode: entry
+                        .header()
+                        .mode()
+                        .ok()
+                        .map(|mode| metadata_policy.mode.apply(mode, false)),
+                    mtime: entry
+                        .header()
+                        .mtime()
+                        .ok()
+                        .filter(|_| metadata_policy.preserve_mtime),
+                })?;
+                extracted_files.add_file(&path);
+            }
+            tar::EntryType::Regular => {
+                // This is the size once written, including the holes
+                // of sparse files.
+                limits.add_size(entry.size())?;
+
+                if normalize_entry_path(&entry.path()?)
+                    .is_some_and(|path| writer.is_pending(&dst.join(path)))
+                {
+                    writer.wait()?;
+                }
+
+                // unpack_in returns false if the path contains ".."
+                // and is skipped.
+                if unpack_in_with_policy(&mut entry, dst, metadata_policy)? {
+                    if let Some(path) = normalize_entry_path(&entry.path()?) {
+                        extracted_files.add_file(&path);
+                    }
+                }
+            }
+            tar::EntryType::Symlink if preserve_symlinks => {
+                // Links are only created once the files are written.
+                writer.wait()?;
+
+                let Some(link) = normalize_entry_path(&entry.path()?) else {
+                    continue;
+                };
+
+                let Some(target) = entry.link_name()? else {
+                    continue;
+                };
+
+                let parent = dst.join(&link);
+                let parent = parent
+                    .parent()
+                    .expect("all full entry paths should have parent paths");
+                fs::create_dir_all(parent)?;
+
+                if !is_symlink_inside(dst, &link, &target)? {
+                    warn!(
+                        "Skipping symlink `{}` pointing outside of the package to `{}`",
+                        link.display(),
+                        target.display()
+                    );
+                    continue;
+                }
+
+                match entry.unpack_in(dst) {
+                    Ok(true) => extracted_files.add_file(&link),
+                    Ok(false) => (),
+                    // Creating symlinks requires privileges on windows.
+                    Err(err) if cfg!(windows) => {
+                        warn!("Failed to create symlink `{}`: {err}", link.display())
                     }
+                    Err(err) => return Err(err),
+                }
+            }
+            tar::EntryType::Link => {
+                writer.wait()?;
+
+                let Some(link) = normalize_entry_path(&entry.path()?) else {
+                    continue;
+                };
+
+                // Only link to files extracted before, which are known
+                // to be inside of the package.
+                let Some(target) = entry
+                    .link_name()?
+                    .as_deref()
+                    .and_then(normalize_entry_path)
+                    .filter(|target| *target != link && extracted_files.has_file(target))
+                else {
+                    warn!("Skipping hardlink `{}` to a missing file", link.display());
+                    continue;
+                };
+
+                let link_path = dst.join(&link);
+                let target_path = dst.join(&target);
+
+                // A hardlink to a symlink is a symlink resolved from
+                // the directory of the hardlink, which may be outside
+                // of the package.
+                if !fs::symlink_metadata(&target_path)?.is_file() {
+                    warn!(
+                        "Skipping hardlink `{}` to `{}`, which is not a regular file",
+                        link.display(),
+                        target.display()
+                    );
+                    continue;
+                }
+
+                if let Some(parent) = link_path.parent() {
+                    fs::create_dir_all(parent)?;
                 }
-                tar::EntryType::Directory => {
-                    directories.push(entry);
+                match fs::remove_file(&link_path) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => (),
                 }
-                _ => (),
+
+                match fs::hard_link(&target_path, &link_path) {
+                    Ok(()) => (),
+                    // The filesystem may not support hardlinks.
+                    Err(err) if cfg!(windows) => {
+                        warn!(
+                            "Failed to create hardlink `{}`, copying it instead: {err}",
+                            link.display()
+                        );
+                        fs::copy(&target_path, &link_path)?;
+                    }
+                    Err(err) => return Err(err),
+                }
+                extracted_files.add_hardlink(&link, &target);
+            }
+            tar::EntryType::Directory => {
+                directories.push(entry);
             }
+            _ => (),
         }
+    }
+
+    writer.wait()?;
 
-        for mut dir in directories {
-            if dir.unpack_in(dst)? {
-                extracted_files.add_dir(&dir.path()?);
+    for mut dir in directories {
+        if unpack_in_with_policy(&mut dir, dst, metadata_policy)? {
+            extracted_files.add_dir(&dir.path()?);
+        }
+    }
+
+    Ok(extracted_files)
+}
+
+/// Same as [`tar::Entry::unpack_in`], restoring the metadata of `entry`
+/// according to `policy`.
+fn unpack_in_with_policy<R: io::Read>(
+    entry: &mut tar::Entry<'_, R>,
+    dst: &Path,
+    policy: MetadataPolicy,
+) -> io::Result<bool> {
+    entry.set_preserve_permissions(policy.mode == ModePolicy::PreserveAll);
+    entry.set_preserve_mtime(policy.preserve_mtime);
+    entry.set_unpack_xattrs(policy.preserve_xattrs);
+
+    if !entry.unpack_in(dst)? {
+        return Ok(false);
+    }
+
+    #[cfg(unix)]
+    if policy.mode == ModePolicy::Normalize {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let (Some(path), Ok(mode)) =
+            (normalize_entry_path(&entry.path()?), entry.header().mode())
+        {
+            let is_dir = entry.header().entry_type().is_dir();
+            fs::set_permissions(
+                dst.join(path),
+                fs::Permissions::from_mode(policy.mode.apply(mode, is_dir)),
+            )?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Extract the regular files, directories and symlinks of `tar` to `sink`.
+fn extract_tar_to_sink(
+    mut tar: tar::Archive<Box<dyn Read>>,
+    sink: &mut dyn ExtractionSink,
+    preserve_symlinks: bool,
+    limits: ExtractionLimits,
+    metadata_policy: MetadataPolicy,
+) -> io::Result<ExtractedFiles> {
+    let mut entries = tar.entries()?;
+
+    let mut extracted_files = ExtractedFiles::new();
+    let mut limits = LimitTracker::new(limits);
+
+    while let Some(mut entry) = entries.next().transpose()? {
+        limits.add_entry()?;
+
+        // Skip entries that would escape from the destination, the same
+        // as tar::Entry::unpack_in.
+        let Some(path) = normalize_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        match entry.header().entry_type() {
+            tar::EntryType::Regular => {
+                create_parent_dir(sink, &path)?;
+                sink.write_file(&path, &mut limits.reader(&mut entry))?;
+                if let Ok(mode) = entry.header().mode() {
+                    sink.set_mode(&path, metadata_policy.mode.apply(mode, false))?;
+                }
+                extracted_files.add_file(&path);
+            }
+            tar::EntryType::Symlink if preserve_symlinks => {
+                let Some(target) = entry.link_name()? else {
+                    continue;
+                };
+
+                create_parent_dir(sink, &path)?;
+                if sink.create_symlink(&path, &target)? {
+                    extracted_files.add_file(&path);
+                }
             }
+            tar::EntryType::Directory => {
+                sink.create_dir_all(&path)?;
+                extracted_files.add_dir(&path);
+            }
+            _ => (),
+        }
+    }
+
+    Ok(extracted_files)
+}
+
+/// Normalize `path` in the same way `tar::Entry::unpack_in` does.
+///
+/// Return `None` if `path` contains `..` or is empty.
+fn normalize_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized_path = PathBuf::new();
+
+    for part in path.components() {
+        match part {
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => continue,
+            Component::ParentDir => return None,
+            Component::Normal(part) => normalized_path.push(part),
         }
+    }
+
+    (!normalized_path.as_os_str().is_empty()).then_some(normalized_path)
+}
+
+/// Create the decoder of a compressed binary, which may use `max_memory`
+/// bytes of memory.
+type BinDecoder = fn(StreamReadable, u64) -> io::Result<Box<dyn Read>>;
+
+fn gz_decoder(reader: StreamReadable, _max_memory: u64) -> io::Result<Box<dyn Read>> {
+    Ok(Box::new(MultiGzDecoder::new(reader)))
+}
+
+fn xz_decoder(reader: StreamReadable, max_memory: u64) -> io::Result<Box<dyn Read>> {
+    Ok(Box::new(LimitedXzDecoder::new_multi_decoder(
+        reader, max_memory,
+    )?))
+}
+
+fn zst_decoder(reader: StreamReadable, max_memory: u64) -> io::Result<Box<dyn Read>> {
+    // Concatenated frames are decoded as well.
+    decompress(reader, TarBasedFmt::Tzstd, max_memory)
+}
+
+/// Decompress the compressed binary in `stream` to `file_name` with
+/// `decoder`.
+fn extract_compressed_bin<S, Sink>(
+    stream: S,
+    sink: Sink,
+    file_name: &Path,
+    limits: ExtractionLimits,
+    decoder: BinDecoder,
+) -> impl Future<Output = Result<(ExtractedFiles, Sink), DownloadError>>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+    Sink: ExtractionSink,
+{
+    let file_name = file_name.to_owned();
+
+    extract_with_sink(stream, sink, limits, move |rx, sink, limits| {
+        let mut extracted_files = ExtractedFiles::new();
+
+        extracted_files.add_file(&file_name);
+
+        let decoder = decoder(StreamReadable::new(rx), limits.max_decoder_memory())?;
+        sink.write_file(&file_name, &mut limits.reader(decoder))?;
 
         Ok(extracted_files)
     })
-    .await
 }
 
-fn extract_with_blocking_decoder<S, F, T>(
+/// Run `f` in a blocking task with the chunks of `stream`, extracting to
+/// `sink` within `limits`.
+fn extract_with_sink<S, Sink, F>(
     stream: S,
-    path: &Path,
+    mut sink: Sink,
+    limits: ExtractionLimits,
     f: F,
-) -> impl Future<Output = Result<T, DownloadError>>
+) -> impl Future<Output = Result<(ExtractedFiles, Sink), DownloadError>>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
-    F: FnOnce(mpsc::Receiver<Bytes>, &Path) -> io::Result<T> + Send + Sync + 'static,
-    T: Send + 'static,
+    Sink: ExtractionSink,
+    F: FnOnce(
+            mpsc::Receiver<Bytes>,
+            &mut dyn ExtractionSink,
+            &mut LimitTracker,
+        ) -> Result<ExtractedFiles, DownloadError>
+        + Send
+        + Sync
+        + 'static,
 {
-    let path = path.to_owned();
-
-    extract_with_blocking_task(stream, move |rx| {
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+    extract_with_blocking_task(stream, limits.low_memory, move |rx| {
+        let extracted_files =
+            f(rx, &mut sink, &mut LimitTracker::new(limits)).map_err(io::Error::from)?;
 
-        f(rx, &path)
+        Ok((extracted_files, sink))
     })
 }
 
@@ -1,17 +1,28 @@
-use std::{borrow::Cow, fmt::Debug, io, path::Path, pin::Pin};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    io::{self, Read},
+    path::Path,
+    pin::Pin,
+};
 
 use async_compression::tokio::bufread;
-use bytes::Bytes;
-use futures_util::{Stream, StreamExt};
-use tokio::io::{copy, sink, AsyncRead};
+use bytes::{Bytes, BytesMut};
+use futures_util::{future::try_join, stream, Stream, StreamExt};
+use tokio::{
+    io::{copy, sink, AsyncRead},
+    sync::mpsc,
+};
 use tokio_tar::{Archive, Entry, EntryType};
 use tokio_util::io::StreamReader;
 use tracing::debug;
 
 use super::{
+    extracter::decompress,
     DownloadError,
     TarBasedFmt::{self, *},
 };
+use crate::utils::{extract_with_blocking_task, StreamReadable};
 
 pub trait TarEntry: AsyncRead + Send + Sync + Unpin + Debug {
     /// Returns the path name for this entry.
@@ -91,10 +102,11 @@ pub trait TarEntriesVisitor: Send + Sync {
 pub(crate) async fn extract_tar_based_stream_and_visit<S>(
     stream: S,
     fmt: TarBasedFmt,
+    low_memory: bool,
     visitor: &mut dyn TarEntriesVisitor,
 ) -> Result<(), DownloadError>
 where
-    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync,
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
 {
     debug!("Extracting from {fmt} archive to process it in memory");
 
@@ -105,8 +117,50 @@ where
         Tgz => Box::pin(bufread::GzipDecoder::new(reader)),
         Txz => Box::pin(bufread::XzDecoder::new(reader)),
         Tzstd => Box::pin(bufread::ZstdDecoder::new(reader)),
+        Tbr => Box::pin(bufread::BrotliDecoder::new(reader)),
+        Tlz4 | Tlz => {
+            // There are no async lz4 and lzip decoders, so decompress it in
+            // a blocking task, which sends the decompressed data back.
+            let (tx, mut rx) = mpsc::channel(1);
+
+            let decompress =
+                extract_with_blocking_task(reader.into_inner(), low_memory, move |rx| {
+                    let mut decoder = decompress(StreamReadable::new(rx), fmt, u64::MAX)?;
+
+                    loop {
+                        let mut buf = BytesMut::zeroed(DECOMPRESSED_CHUNK_LEN);
+                        let n = decoder.read(&mut buf)?;
+                        if n == 0 {
+                            break Ok(());
+                        }
+                        buf.truncate(n);
+
+                        if tx.blocking_send(buf.freeze()).is_err() {
+                            // The rest of the archive is not needed.
+                            break Ok(());
+                        }
+                    }
+                });
+            let decompressed = StreamReader::new(stream::poll_fn(move |cx| {
+                rx.poll_recv(cx).map(|bytes| bytes.map(io::Result::Ok))
+            }));
+
+            return try_join(decompress, visit_entries(decompressed, visitor))
+                .await
+                .map(|_| ());
+        }
     };
 
+    visit_entries(decoder, visitor).await
+}
+
+/// Size of the chunks decompressed by the blocking decoders.
+const DECOMPRESSED_CHUNK_LEN: usize = 64 * 1024;
+
+async fn visit_entries(
+    decoder: impl AsyncRead + Send + Sync + Unpin,
+    visitor: &mut dyn TarEntriesVisitor,
+) -> Result<(), DownloadError> {
     let mut tar = Archive::new(decoder);
     let mut entries = tar.entries()?;
 
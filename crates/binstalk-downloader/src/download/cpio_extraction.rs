@@ -0,0 +1,319 @@
+//! Extraction of cpio archives in the "new ascii", "new crc" and portable
+//! ascii formats, which some vendors ship as is and which are also the
+//! payload of rpm packages.
+
+use std::{
+    io::{self, Read},
+    path::Path,
+    str,
+};
+
+use tracing::debug;
+
+use super::{
+    extraction_limits::LimitTracker, extraction_sink::create_parent_dir,
+    zip_extraction::sanitized_name, DownloadError, ExtractedFiles, ExtractionSink,
+};
+
+/// Magic of the "new ascii" and "new crc" formats, whose fields are hex.
+const NEWC_MAGICS: &[&[u8]] = &[b"070701", b"070702"];
+const NEWC_HEADER_LEN: usize = 110;
+
+/// Magic of the portable ascii format, whose fields are octal.
+const ODC_MAGIC: &[u8] = b"070707";
+const ODC_HEADER_LEN: usize = 76;
+
+const MAGIC_LEN: usize = 6;
+
+pub(super) const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+fn invalid(msg: &'static str) -> DownloadError {
+    DownloadError::InvalidCpio(msg)
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(len), &mut io::sink())?;
+    Ok(())
+}
+
+fn parse_field(field: &[u8], radix: u32) -> Result<u64, DownloadError> {
+    str::from_utf8(field)
+        .ok()
+        .and_then(|field| u64::from_str_radix(field, radix).ok())
+        .ok_or_else(|| invalid("invalid header"))
+}
+
+pub(super) struct CpioEntry {
+    pub(super) name: String,
+    pub(super) mode: u32,
+}
+
+impl CpioEntry {
+    pub(super) fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    pub(super) fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub(super) fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+}
+
+/// Call `each` with every entry of the cpio archive read from `reader`
+/// along with its data, which is the target of symbolic links.
+pub(super) fn for_each_entry(
+    mut reader: impl Read,
+    mut each: impl FnMut(&CpioEntry, &mut dyn Read) -> Result<(), DownloadError>,
+) -> Result<(), DownloadError> {
+    loop {
+        let mut magic = [0; MAGIC_LEN];
+        reader.read_exact(&mut magic)?;
+
+        // Both the header along with the name and the data are padded to a
+        // multiple of 4 bytes in the "new" formats, but not in the portable
+        // one.
+        let (mode, data_len, name_len, header_len, alignment) = if NEWC_MAGICS.contains(&&magic[..])
+        {
+            let mut header = [0; NEWC_HEADER_LEN - MAGIC_LEN];
+            reader.read_exact(&mut header)?;
+            (
+                parse_field(&header[8..16], 16)?,
+                parse_field(&header[48..56], 16)?,
+                parse_field(&header[88..96], 16)?,
+                NEWC_HEADER_LEN as u64,
+                4,
+            )
+        } else if magic == ODC_MAGIC {
+            let mut header = [0; ODC_HEADER_LEN - MAGIC_LEN];
+            reader.read_exact(&mut header)?;
+            (
+                parse_field(&header[12..18], 8)?,
+                parse_field(&header[59..70], 8)?,
+                parse_field(&header[53..59], 8)?,
+                ODC_HEADER_LEN as u64,
+                1,
+            )
+        } else {
+            return Err(invalid("unsupported format"));
+        };
+        let mut name = Vec::new();
+        (&mut reader).take(name_len).read_to_end(&mut name)?;
+        // The name is nul-terminated.
+        if name.len() as u64 != name_len || name.pop() != Some(0) {
+            return Err(invalid("invalid header"));
+        }
+        skip(
+            &mut reader,
+            (alignment - (header_len + name_len) % alignment) % alignment,
+        )?;
+        let name = String::from_utf8(name).map_err(|_| invalid("invalid file name"))?;
+
+        if name == TRAILER {
+            return Ok(());
+        }
+
+        let entry = CpioEntry {
+            name,
+            mode: u32::try_from(mode).map_err(|_| invalid("invalid header"))?,
+        };
+        let mut data = (&mut reader).take(data_len);
+        each(&entry, &mut data)?;
+        skip(&mut data, u64::MAX)?;
+        skip(&mut reader, (alignment - data_len % alignment) % alignment)?;
+    }
+}
+
+pub(super) fn do_extract_cpio(
+    reader: impl Read,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_entry(reader, |entry, data| {
+        limits.add_entry()?;
+        let Some(name) = sanitized_name(entry.name.as_bytes()) else {
+            return Ok(());
+        };
+
+        if entry.is_dir() {
+            sink.create_dir_all(&name)?;
+            extracted_files.add_dir(&name);
+        } else if entry.is_file() || entry.is_symlink() {
+            create_parent_dir(sink, &name)?;
+
+            debug!("Extracting `{}`", name.display());
+
+            if entry.is_symlink() {
+                let mut target = String::new();
+                data.read_to_string(&mut target)?;
+
+                if !sink.create_symlink(&name, Path::new(&target))? {
+                    return Ok(());
+                }
+            } else {
+                sink.write_file(&name, &mut limits.reader(data))?;
+                sink.set_mode(&name, entry.mode & 0o777)?;
+            }
+            extracted_files.add_file(&name);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+/// Append an entry in the "new ascii" format to `cpio`.
+#[cfg(test)]
+pub(super) fn newc_entry(cpio: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+    cpio.extend(
+        format!(
+            "070701{:08x}{mode:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+            1,
+            0,
+            0,
+            1,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+            0
+        )
+        .into_bytes(),
+    );
+    cpio.extend(name.as_bytes());
+    cpio.push(0);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+    cpio.extend(data);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use super::super::{ExtractionLimits, FsSink, InMemoryVfs};
+
+    fn odc_entry(cpio: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        cpio.extend(
+            format!(
+                "070707{:06o}{:06o}{mode:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
+                0,
+                1,
+                0,
+                0,
+                1,
+                0,
+                0,
+                name.len() + 1,
+                data.len()
+            )
+            .into_bytes(),
+        );
+        cpio.extend(name.as_bytes());
+        cpio.push(0);
+        cpio.extend(data);
+    }
+
+    fn cpio(entry: fn(&mut Vec<u8>, &str, u32, &[u8])) -> Vec<u8> {
+        let mut cpio = Vec::new();
+        entry(&mut cpio, ".", 0o40755, b"");
+        entry(&mut cpio, "./bin", 0o40755, b"");
+        entry(&mut cpio, "./bin/hello", 0o100755, b"hello");
+        entry(&mut cpio, "share/README", 0o100644, b"readme");
+        entry(&mut cpio, "bin/link", 0o120777, b"hello");
+        entry(&mut cpio, "bin/readme", 0o120777, b"../share/README");
+        entry(&mut cpio, "bin/absolute", 0o120777, b"/etc/passwd");
+        entry(&mut cpio, "bin/outside", 0o120777, b"../../escape");
+        entry(&mut cpio, "../escape", 0o100644, b"escape");
+        entry(&mut cpio, TRAILER, 0, b"");
+        cpio
+    }
+
+    #[test]
+    fn test_extract_cpio() {
+        for cpio in [cpio(newc_entry), cpio(odc_entry)] {
+            let dir = tempfile::tempdir().unwrap();
+            let dir = dir.path();
+
+            let extracted_files = do_extract_cpio(
+                &cpio[..],
+                &mut FsSink::new(dir),
+                &mut LimitTracker::new(ExtractionLimits::default()),
+            )
+            .unwrap();
+
+            assert!(extracted_files.has_file(Path::new("bin/hello")));
+            assert!(extracted_files.has_file(Path::new("share/README")));
+            assert!(extracted_files.has_file(Path::new("bin/link")));
+            assert!(!extracted_files.has_file(Path::new("escape")));
+            assert_eq!(fs::read(dir.join("bin/hello")).unwrap(), b"hello");
+            assert_eq!(fs::read(dir.join("share/README")).unwrap(), b"readme");
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                let mode = fs::metadata(dir.join("bin/hello"))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o777, 0o755);
+                assert_eq!(
+                    fs::read_link(dir.join("bin/link")).unwrap(),
+                    Path::new("hello")
+                );
+                assert_eq!(fs::read(dir.join("bin/readme")).unwrap(), b"readme");
+                assert!(!extracted_files.has_file(Path::new("bin/absolute")));
+                assert!(!extracted_files.has_file(Path::new("bin/outside")));
+                assert!(fs::symlink_metadata(dir.join("bin/absolute")).is_err());
+            }
+
+            let mut vfs = InMemoryVfs::new();
+            let extracted_files = do_extract_cpio(
+                &cpio[..],
+                &mut vfs,
+                &mut LimitTracker::new(ExtractionLimits::default()),
+            )
+            .unwrap();
+
+            assert!(extracted_files.has_file(Path::new("bin/hello")));
+            assert!(!extracted_files.has_file(Path::new("bin/link")));
+            assert_eq!(
+                vfs.get_file(Path::new("share/README")).unwrap().as_ref(),
+                b"readme"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_cpio() {
+        let extract = |cpio: &[u8]| {
+            do_extract_cpio(
+                cpio,
+                &mut InMemoryVfs::new(),
+                &mut LimitTracker::new(ExtractionLimits::default()),
+            )
+        };
+
+        assert!(matches!(extract(b"0707010000"), Err(DownloadError::Io(_))));
+        assert!(matches!(
+            extract(b"!<arch>\n"),
+            Err(DownloadError::InvalidCpio(_))
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+//! Extraction of the binaries of Debian packages, which are `ar` archives
+//! with the files to install in a `data.tar.*` member.
+
+use std::{
+    ffi::OsStr,
+    io::{self, BufRead, Read},
+    path::Path,
+    str,
+};
+
+use tar::EntryType;
+
+use super::{
+    extracter::create_tar_decoder, extraction_limits::LimitTracker, DownloadError, ExtractedFiles,
+    ExtractionSink, PkgFmt, PkgFmtDecomposed, TarBasedFmt,
+};
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+
+/// Size of the header of a member of an `ar` archive.
+const AR_HEADER_LEN: usize = 60;
+
+/// Directories of the package the binaries are extracted from, which are
+/// also used for rpm packages.
+const BIN_DIRS: &[&str] = &["usr/bin", "usr/local/bin"];
+
+/// Return the `data.tar.*` member of the `ar` archive read from `reader`,
+/// along with its format.
+fn data_tar<R: BufRead>(mut reader: R) -> Result<(io::Take<R>, TarBasedFmt), DownloadError> {
+    let mut magic = [0; AR_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != AR_MAGIC {
+        return Err(DownloadError::InvalidDeb("not an ar archive"));
+    }
+
+    loop {
+        let mut header = [0; AR_HEADER_LEN];
+        match reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(DownloadError::InvalidDeb("data.tar member not found"))
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // GNU ar terminates names with `/`.
+        let name = str::from_utf8(&header[..16])
+            .map_err(|_| DownloadError::InvalidDeb("invalid member name"))?
+            .trim_end()
+            .trim_end_matches('/');
+        let size: u64 = str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|size| size.trim_end().parse().ok())
+            .ok_or(DownloadError::InvalidDeb("invalid member size"))?;
+
+        if name.starts_with("data.tar") {
+            return match PkgFmt::guess_pkg_format(name).map(PkgFmt::decompose) {
+                Some(PkgFmtDecomposed::Tar(fmt)) => Ok((reader.take(size), fmt)),
+                _ => Err(DownloadError::InvalidDeb(
+                    "unsupported data.tar compression",
+                )),
+            };
+        }
+
+        // Members are aligned to even offsets.
+        io::copy(&mut (&mut reader).take(size + size % 2), &mut io::sink())?;
+    }
+}
+
+/// Return the name of the binary at `path` in the package, or `None` if it
+/// is not in one of [`BIN_DIRS`].
+pub(super) fn bin_name(path: &Path) -> Option<&OsStr> {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let parent = path.parent()?;
+
+    BIN_DIRS
+        .iter()
+        .any(|dir| parent == Path::new(dir))
+        .then(|| path.file_name())
+        .flatten()
+}
+
+fn for_each_bin(
+    reader: impl BufRead + 'static,
+    max_decoder_memory: u64,
+    mut each: impl FnMut(&Path, &mut dyn Read) -> io::Result<()>,
+) -> Result<(), DownloadError> {
+    let (data, fmt) = data_tar(reader)?;
+    let mut tar = create_tar_decoder(data, fmt, max_decoder_memory)?;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+
+        let path = entry.path()?;
+        if let Some(name) = bin_name(&path) {
+            let name = Path::new(name).to_owned();
+            each(&name, &mut entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_extract_deb(
+    reader: impl BufRead + 'static,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_bin(reader, limits.max_decoder_memory(), |name, reader| {
+        limits.add_entry()?;
+        sink.write_file(name, &mut limits.reader(reader))?;
+        extracted_files.add_file(name);
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::{ExtractionLimits, FsSink, InMemoryVfs};
+
+    fn ar_member(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut member = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            format!("{name}/"),
+            0,
+            0,
+            0,
+            100644,
+            data.len()
+        )
+        .into_bytes();
+        member.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    fn deb() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in [
+            ("./usr/bin/hello", &b"hello"[..]),
+            ("./usr/local/bin/world", b"world"),
+            ("./usr/share/doc/hello/README", b"readme"),
+            ("./usr/bin/nested/evil", b"evil"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content).unwrap();
+        }
+
+        let mut deb = AR_MAGIC.to_vec();
+        deb.extend(ar_member("debian-binary", b"2.0\n"));
+        deb.extend(ar_member("control.tar", b"odd"));
+        deb.extend(ar_member("data.tar", &builder.into_inner().unwrap()));
+        deb
+    }
+
+    #[test]
+    fn test_extract_deb() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted_files = do_extract_deb(
+            io::Cursor::new(deb()),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert!(extracted_files.has_file(Path::new("world")));
+        assert!(!extracted_files.has_file(Path::new("README")));
+        assert!(!extracted_files.has_file(Path::new("evil")));
+        assert_eq!(std::fs::read(dir.path().join("hello")).unwrap(), b"hello");
+
+        let mut vfs = InMemoryVfs::new();
+        let extracted_files = do_extract_deb(
+            io::Cursor::new(deb()),
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("world")));
+        assert_eq!(vfs.get_file(Path::new("world")).unwrap().as_ref(), b"world");
+    }
+
+    #[test]
+    fn test_invalid_deb() {
+        assert!(matches!(
+            data_tar(&b"PK\x03\x04"[..]),
+            Err(DownloadError::Io(_))
+        ));
+        assert!(matches!(
+            data_tar(&b"!<arch>\nnot a header"[..]),
+            Err(DownloadError::InvalidDeb(_))
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! Extraction of macOS disk images, which are attached read-only with
+//! `hdiutil` so that their content can be copied.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+use super::{extraction_limits::LimitTracker, DownloadError, ExtractedFiles, ExtractionSink};
+
+/// A disk image attached at a temporary mount point, detached on drop.
+struct MountedDmg {
+    mount_point: TempDir,
+}
+
+impl MountedDmg {
+    fn attach(image: &Path) -> Result<Self, DownloadError> {
+        if !cfg!(target_os = "macos") {
+            return Err(DownloadError::InvalidDmg(
+                "dmg images can only be extracted on macOS",
+            ));
+        }
+
+        // The license agreement some images are shipped with is not accepted
+        // on behalf of the user.
+        let imageinfo = Command::new("hdiutil")
+            .arg("imageinfo")
+            .arg(image)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if has_license_agreement(&String::from_utf8_lossy(&imageinfo.stdout)) {
+            return Err(DownloadError::InvalidDmg(
+                "the image requires accepting a license agreement",
+            ));
+        }
+
+        let mount_point = tempfile::tempdir()?;
+        debug!("Attaching dmg image at `{}`", mount_point.path().display());
+
+        // hdiutil fails instead of waiting for the license agreement to be
+        // accepted on stdin if the check above misses it.
+        let output = Command::new("hdiutil")
+            .args([
+                "attach",
+                "-nobrowse",
+                "-readonly",
+                "-noautoopen",
+                "-mountpoint",
+            ])
+            .arg(mount_point.path())
+            .arg(image)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            warn!(
+                "hdiutil failed to attach the dmg image: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(DownloadError::InvalidDmg(
+                "hdiutil failed to attach the image",
+            ));
+        }
+
+        Ok(Self { mount_point })
+    }
+
+    fn path(&self) -> &Path {
+        self.mount_point.path()
+    }
+}
+
+impl Drop for MountedDmg {
+    fn drop(&mut self) {
+        let detach = |force: bool| {
+            let mut command = Command::new("hdiutil");
+            command.args(["detach", "-quiet"]);
+            if force {
+                command.arg("-force");
+            }
+            command
+                .arg(self.path())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+
+        // The volume could still be busy right after copying from it.
+        if !detach(false) && !detach(true) {
+            warn!(
+                "Failed to detach the dmg image mounted at `{}`",
+                self.path().display()
+            );
+        }
+    }
+}
+
+/// Return true if `imageinfo`, the output of `hdiutil imageinfo`, shows
+/// that the image has a license agreement to accept before attaching it.
+fn has_license_agreement(imageinfo: &str) -> bool {
+    imageinfo
+        .lines()
+        .any(|line| line.trim() == "Software License Agreement: true")
+}
+
+/// Call `f` with each directory and regular file under `root`, relative to
+/// it and along with the path of the file, parents before their children.
+///
+/// Symlinks, e.g. the `Applications` link of drag-and-drop installers, and
+/// hidden entries at the root of the volume, e.g. `.Trashes`, are skipped.
+fn for_each_entry(
+    root: &Path,
+    mut f: impl FnMut(&Path, Option<&Path>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut dirs = vec![PathBuf::new()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(root.join(&dir))?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_unstable_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry.file_name();
+            if dir.as_os_str().is_empty() && name.as_encoded_bytes().starts_with(b".") {
+                continue;
+            }
+
+            let path = dir.join(&name);
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                f(&path, None)?;
+                dirs.push(path);
+            } else if file_type.is_file() {
+                f(&path, Some(&entry.path()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_extract_dmg(
+    image: &Path,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mounted = MountedDmg::attach(image)?;
+
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_entry(mounted.path(), |path, src| {
+        limits.add_entry()?;
+        match src {
+            None => {
+                sink.create_dir_all(path)?;
+                extracted_files.add_dir(path);
+            }
+            Some(src) => {
+                sink.write_file(path, &mut limits.reader(File::open(src)?))?;
+
+                // Keep the binaries executable.
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    sink.set_mode(path, fs::metadata(src)?.permissions().mode() & 0o777)?;
+                }
+
+                extracted_files.add_file(path);
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        super::{ExtractionLimits, FsSink},
+        *,
+    };
+
+    #[test]
+    fn test_for_each_entry() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        fs::create_dir_all(root.join("Tool.app/Contents/MacOS")).unwrap();
+        fs::write(root.join("Tool.app/Contents/MacOS/tool"), "tool").unwrap();
+        fs::write(root.join("README"), "readme").unwrap();
+        fs::create_dir(root.join(".Trashes")).unwrap();
+        fs::write(root.join(".Trashes/file"), "").unwrap();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/.hidden"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/Applications", root.join("Applications")).unwrap();
+
+        let mut entries = Vec::new();
+        for_each_entry(root, |path, src| {
+            if let Some(src) = src {
+                assert_eq!(src, root.join(path));
+            }
+            entries.push((path.to_owned(), src.is_some()));
+            Ok(())
+        })
+        .unwrap();
+        entries.sort_unstable();
+
+        assert_eq!(
+            entries,
+            [
+                (PathBuf::from("README"), true),
+                (PathBuf::from("Tool.app"), false),
+                (PathBuf::from("Tool.app/Contents"), false),
+                (PathBuf::from("Tool.app/Contents/MacOS"), false),
+                (PathBuf::from("Tool.app/Contents/MacOS/tool"), true),
+                (PathBuf::from("docs"), false),
+                (PathBuf::from("docs/.hidden"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_has_license_agreement() {
+        let imageinfo = |license| {
+            format!(
+                "Format Description: UDIF read-only compressed (zlib)\n\
+                 Properties:\n\
+                 \tEncrypted: false\n\
+                 \tSoftware License Agreement: {license}\n\
+                 \tPartitioned: false\n"
+            )
+        };
+
+        assert!(has_license_agreement(&imageinfo(true)));
+        assert!(!has_license_agreement(&imageinfo(false)));
+        assert!(!has_license_agreement(""));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(matches!(
+            do_extract_dmg(
+                Path::new("tool.dmg"),
+                &mut FsSink::new(dir.path()),
+                &mut LimitTracker::new(ExtractionLimits::default())
+            ),
+            Err(DownloadError::InvalidDmg(_))
+        ));
+    }
+}
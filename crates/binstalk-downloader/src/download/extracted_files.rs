@@ -1,7 +1,9 @@
 use std::{
     collections::{hash_map::Entry as HashMapEntry, HashMap, HashSet},
     ffi::OsStr,
-    path::Path,
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
 };
 
 #[derive(Debug)]
@@ -22,11 +24,15 @@ impl ExtractedFilesEntry {
 }
 
 #[derive(Debug)]
-pub struct ExtractedFiles(pub(super) HashMap<Box<Path>, ExtractedFilesEntry>);
+pub struct ExtractedFiles(
+    pub(super) HashMap<Box<Path>, ExtractedFilesEntry>,
+    /// Hardlinks, mapped to the file they are linked to.
+    HashMap<Box<Path>, Box<Path>>,
+);
 
 impl ExtractedFiles {
     pub(super) fn new() -> Self {
-        Self(Default::default())
+        Self(Default::default(), Default::default())
     }
 
     /// * `path` - must be canonical and must not be empty
@@ -38,6 +44,13 @@ impl ExtractedFiles {
         self.add_dir_if_has_parent(path);
     }
 
+    /// * `path` - must be canonical and must not be empty
+    /// * `target` - must be canonical and a file already added
+    pub(super) fn add_hardlink(&mut self, path: &Path, target: &Path) {
+        self.add_file(path);
+        self.1.insert(path.into(), target.into());
+    }
+
     fn add_dir_if_has_parent(&mut self, path: &Path) {
         if let Some(parent) = path.parent() {
             if !parent.as_os_str().is_empty() {
@@ -105,4 +118,345 @@ impl ExtractedFiles {
     pub fn has_file(&self, path: &Path) -> bool {
         matches!(self.get_entry(path), Some(ExtractedFilesEntry::File))
     }
+
+    /// Look up the file at `path` with each component compared
+    /// case-insensitively (ASCII only), and return the actual path.
+    ///
+    /// An exact match is preferred if there are multiple candidates.
+    ///
+    /// * `path` - must be a relative path without `.`, `..`, `/`, `prefix:/`
+    ///   and must not be empty, for these values it is guaranteed to
+    ///   return `None`.
+    pub fn find_file_case_insensitive(&self, path: &Path) -> Option<PathBuf> {
+        if self.has_file(path) {
+            return Some(path.to_path_buf());
+        }
+
+        let mut actual_path = PathBuf::new();
+
+        for component in path.components() {
+            let Component::Normal(component) = component else {
+                return None;
+            };
+
+            let dir = if actual_path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                &actual_path
+            };
+
+            let file_name = self
+                .get_dir(dir)?
+                .iter()
+                .find(|file_name| file_name.eq_ignore_ascii_case(component))?;
+
+            actual_path.push(&**file_name);
+        }
+
+        self.has_file(&actual_path).then_some(actual_path)
+    }
+
+    /// Iterate over all files extracted, in arbitrary order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.0.iter().filter_map(|(path, entry)| match entry {
+            ExtractedFilesEntry::File => Some(&**path),
+            ExtractedFilesEntry::Dir(_) => None,
+        })
+    }
+
+    /// Return the file which the file at `path` is hardlinked to, if it is
+    /// extracted from a hardlink entry of the archive.
+    ///
+    /// Both are regular files, sharing the same content on disk.
+    pub fn hardlink_target(&self, path: &Path) -> Option<&Path> {
+        self.1.get(path).map(|target| &**target)
+    }
+
+    /// Iterate over all hardlinks extracted with the files they are linked
+    /// to, in arbitrary order.
+    pub fn hardlinks(&self) -> impl Iterator<Item = (&Path, &Path)> + '_ {
+        self.1.iter().map(|(path, target)| (&**path, &**target))
+    }
+
+    /// Return all files matching glob `pattern`, sorted by depth and then
+    /// by path, so that the shallowest match comes first.
+    ///
+    /// Supported syntax:
+    ///  - `?` matches any single character except `/`
+    ///  - `*` matches zero or more characters except `/`
+    ///  - `**` as a whole path component matches zero or more directories
+    pub fn glob(&self, pattern: &str) -> Vec<&Path> {
+        let pattern: Vec<&str> = pattern
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect();
+
+        sort_by_depth(
+            self.files()
+                .filter(|path| {
+                    let components: Vec<&OsStr> = path
+                        .components()
+                        .filter_map(|component| match component {
+                            Component::Normal(component) => Some(component),
+                            _ => None,
+                        })
+                        .collect();
+
+                    glob_match_components(&pattern, &components)
+                })
+                .collect(),
+        )
+    }
+
+    /// Return all files with `file_name` at any depth, sorted by depth and
+    /// then by path, so that the shallowest match comes first.
+    pub fn find_by_file_name(&self, file_name: &OsStr) -> Vec<&Path> {
+        sort_by_depth(
+            self.files()
+                .filter(|path| path.file_name() == Some(file_name))
+                .collect(),
+        )
+    }
+
+    /// Flatten nested single directories at the top-level of the archive.
+    ///
+    /// Starting from the top-level, descend into the only entry as long as
+    /// it is a directory, and return the innermost directory reached.
+    ///
+    /// Return `None` if the top-level does not consist of exactly one
+    /// directory, e.g. the archive has no wrapping directory.
+    pub fn top_level_dir(&self) -> Option<PathBuf> {
+        let mut dir: Option<PathBuf> = None;
+
+        loop {
+            let entries = self.get_dir(dir.as_deref().unwrap_or(Path::new(".")))?;
+
+            if entries.len() != 1 {
+                break;
+            }
+
+            let file_name: &OsStr = entries.iter().next().unwrap();
+            let path = match &dir {
+                Some(dir) => dir.join(file_name),
+                None => PathBuf::from(file_name),
+            };
+
+            if self.get_dir(&path).is_none() {
+                break;
+            }
+
+            dir = Some(path);
+        }
+
+        dir
+    }
+
+    /// Return true if the file at `path` looks like an executable, based on
+    /// its magic bytes (ELF, Mach-O, PE or shebang) or, on unix, its
+    /// permission bits.
+    ///
+    /// * `root` - the directory the files are extracted to.
+    ///
+    /// This function uses blocking I/O.
+    pub fn is_executable(&self, root: &Path, path: &Path) -> io::Result<bool> {
+        if !self.has_file(path) {
+            return Ok(false);
+        }
+
+        let path = root.join(path);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if fs::metadata(&path)?.permissions().mode() & 0o111 != 0 {
+                return Ok(true);
+            }
+        }
+
+        let mut magic = [0_u8; 4];
+        let mut file = fs::File::open(path)?;
+        let mut n = 0;
+        while n < magic.len() {
+            match file.read(&mut magic[n..])? {
+                0 => break,
+                cnt => n += cnt,
+            }
+        }
+
+        Ok(is_executable_magic(&magic[..n]))
+    }
+
+    /// Return the largest file extracted, useful as a last resort for
+    /// guessing the binary in an archive that contains only one executable
+    /// alongside small docs.
+    ///
+    /// * `root` - the directory the files are extracted to.
+    ///
+    /// This function uses blocking I/O.
+    pub fn largest_file(&self, root: &Path) -> io::Result<Option<&Path>> {
+        let mut largest = None;
+
+        for path in self.files() {
+            let len = fs::metadata(root.join(path))?.len();
+
+            match largest {
+                Some((_, largest_len)) if largest_len >= len => (),
+                _ => largest = Some((path, len)),
+            }
+        }
+
+        Ok(largest.map(|(path, _)| path))
+    }
+}
+
+fn sort_by_depth(mut paths: Vec<&Path>) -> Vec<&Path> {
+    paths.sort_unstable_by_key(|path| (path.components().count(), *path));
+    paths
+}
+
+fn is_executable_magic(magic: &[u8]) -> bool {
+    matches!(
+        magic,
+        // ELF
+        [0x7f, b'E', b'L', b'F', ..]
+        // Mach-O 32/64-bit, both endianness, and universal binary
+        | [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+        | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+        | [0xca, 0xfe, 0xba, 0xbe, ..]
+        // PE
+        | [b'M', b'Z', ..]
+        // Script with shebang
+        | [b'#', b'!', ..]
+    )
+}
+
+fn glob_match_components(pattern: &[&str], components: &[&OsStr]) -> bool {
+    match pattern.split_first() {
+        None => components.is_empty(),
+        Some((&"**", pattern)) => {
+            (0..=components.len()).any(|i| glob_match_components(pattern, &components[i..]))
+        }
+        Some((first, pattern)) => match components.split_first() {
+            Some((component, components)) => {
+                component
+                    .to_str()
+                    .map(|component| wildcard_match(first, component))
+                    .unwrap_or(false)
+                    && glob_match_components(pattern, components)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match `s` against `pattern` which can contain `?` and `*`.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    let (mut p, mut i) = (0, 0);
+    // Position of the last `*` in pattern and the position in `s` it matched up to.
+    let mut backtrack = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_i)) => {
+                    p = star_p + 1;
+                    i = star_i + 1;
+                    backtrack = Some((star_p, star_i + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_extracted_files(files: &[&str]) -> ExtractedFiles {
+        let mut extracted_files = ExtractedFiles::new();
+        for file in files {
+            extracted_files.add_file(Path::new(file));
+        }
+        extracted_files
+    }
+
+    #[test]
+    fn test_glob() {
+        let extracted_files = new_extracted_files(&[
+            "cargo-watch-v8.4.0/cargo-watch",
+            "cargo-watch-v8.4.0/cargo-watch.1",
+            "cargo-watch-v8.4.0/completions/zsh",
+            "README.md",
+        ]);
+
+        assert_eq!(
+            extracted_files.glob("*/cargo-watch"),
+            [Path::new("cargo-watch-v8.4.0/cargo-watch")]
+        );
+        assert_eq!(
+            extracted_files.glob("**/cargo-watch*"),
+            [
+                Path::new("cargo-watch-v8.4.0/cargo-watch"),
+                Path::new("cargo-watch-v8.4.0/cargo-watch.1")
+            ]
+        );
+        assert_eq!(
+            extracted_files.glob("**/?sh"),
+            [Path::new("cargo-watch-v8.4.0/completions/zsh")]
+        );
+        assert_eq!(extracted_files.glob("**"), extracted_files.glob("**/*"));
+        assert!(extracted_files.glob("cargo-watch").is_empty());
+    }
+
+    #[test]
+    fn test_top_level_dir() {
+        let extracted_files = new_extracted_files(&["a/b/c/bin", "a/b/c/README.md"]);
+        assert_eq!(
+            extracted_files.top_level_dir().as_deref(),
+            Some(Path::new("a/b/c"))
+        );
+
+        let extracted_files = new_extracted_files(&["a/bin", "README.md"]);
+        assert_eq!(extracted_files.top_level_dir(), None);
+
+        let extracted_files = new_extracted_files(&["bin"]);
+        assert_eq!(extracted_files.top_level_dir(), None);
+    }
+
+    #[test]
+    fn test_find_file_case_insensitive() {
+        let extracted_files = new_extracted_files(&["Dir/Tool.EXE", "README.md"]);
+        assert_eq!(
+            extracted_files.find_file_case_insensitive(Path::new("dir/tool.exe")),
+            Some(PathBuf::from("Dir/Tool.EXE"))
+        );
+        assert_eq!(
+            extracted_files.find_file_case_insensitive(Path::new("dir")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_by_file_name() {
+        let extracted_files = new_extracted_files(&["a/b/bin", "a/bin", "c/d"]);
+        assert_eq!(
+            extracted_files.find_by_file_name(OsStr::new("bin")),
+            [Path::new("a/bin"), Path::new("a/b/bin")]
+        );
+    }
 }
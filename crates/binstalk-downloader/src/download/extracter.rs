@@ -1,31 +1,237 @@
-use std::io::{self, BufRead, Read};
+use std::io::{self, BufRead, BufReader, Read};
 
+use brotli_decompressor::Decompressor as BrotliDecoder;
+use bytes::{Buf, Bytes};
 use bzip2::bufread::BzDecoder;
 use flate2::bufread::GzDecoder;
+use lz4_flex::frame::FrameDecoder as Lz4Decoder;
 use tar::Archive;
-use xz2::bufread::XzDecoder;
+use tracing::warn;
+use xz2::{
+    bufread::XzDecoder,
+    stream::{self as xz_stream, Stream as XzStream},
+};
 use zstd::stream::Decoder as ZstdDecoder;
 
-use super::TarBasedFmt;
+use super::{
+    extraction_limits::decoder_memory_exceeded, lzip::LzipDecoder, PkgFmt, PkgFmtDecomposed,
+    TarBasedFmt,
+};
 
+/// `max_memory` is the number of bytes of memory the decoders may use.
 pub fn create_tar_decoder(
     dat: impl BufRead + 'static,
     fmt: TarBasedFmt,
+    max_memory: u64,
 ) -> io::Result<Archive<Box<dyn Read>>> {
+    let mut r = BufReader::new(decompress(dat, fmt, max_memory)?);
+
+    if fmt != TarBasedFmt::Tar {
+        // Some servers compress already compressed archives again.
+        match sniff_pkg_fmt(&Bytes::copy_from_slice(r.fill_buf()?)).map(PkgFmt::decompose) {
+            Some(PkgFmtDecomposed::Tar(inner_fmt)) if inner_fmt != TarBasedFmt::Tar => {
+                warn!(
+                    "The {fmt} archive is compressed again as {inner_fmt}, decompressing it twice"
+                );
+                return Ok(Archive::new(decompress(r, inner_fmt, max_memory)?));
+            }
+            _ => (),
+        }
+    }
+
+    Ok(Archive::new(Box::new(r)))
+}
+
+/// Length of the start of a tar archive up to the end of its magic bytes.
+const TAR_MAGIC_END: u64 = 262;
+
+/// Return true if `data`, the start of a `fmt` compressed stream,
+/// decompresses to the start of a tar archive, or is too short to tell.
+fn is_tarball(data: Bytes, fmt: TarBasedFmt) -> bool {
+    let mut header = Vec::new();
+    let res = decompress(data.reader(), fmt, u64::MAX)
+        .and_then(|reader| reader.take(TAR_MAGIC_END).read_to_end(&mut header));
+
+    match res {
+        // The stream is truncated, so only what is decompressed before the
+        // end matters, but other errors mean that it is not `fmt` at all.
+        Err(err) if err.kind() != io::ErrorKind::UnexpectedEof => false,
+        _ => header.len() < TAR_MAGIC_END as usize || PkgFmt::sniff(&header) == Some(PkgFmt::Tar),
+    }
+}
+
+/// Magic bytes of executables: ELF, PE and Mach-O, both thin and universal.
+const EXECUTABLE_MAGIC_BYTES: &[&[u8]] = &[
+    b"\x7fELF",
+    b"MZ",
+    b"\xcf\xfa\xed\xfe",
+    b"\xce\xfa\xed\xfe",
+    b"\xca\xfe\xba\xbe",
+];
+
+/// Detect the format of `data`, the start of a package, from its magic
+/// bytes.
+///
+/// Compressed data is only detected as a compressed tarball if it
+/// decompresses to a tarball, or is too short to tell, otherwise it is a
+/// compressed binary, or not detected at all for the formats only used for
+/// tarballs, e.g. text starting with the `BZh` of bzip2.
+/// Executables are detected as [`PkgFmt::Bin`].
+pub(super) fn sniff_pkg_fmt(data: &Bytes) -> Option<PkgFmt> {
+    let Some(fmt) = PkgFmt::sniff(data) else {
+        return EXECUTABLE_MAGIC_BYTES
+            .iter()
+            .any(|magic| data.starts_with(magic))
+            .then_some(PkgFmt::Bin);
+    };
+
+    match fmt.decompose() {
+        PkgFmtDecomposed::Tar(tar_fmt)
+            if tar_fmt != TarBasedFmt::Tar && !is_tarball(data.clone(), tar_fmt) =>
+        {
+            match fmt {
+                PkgFmt::Tgz => Some(PkgFmt::Gz),
+                PkgFmt::Txz => Some(PkgFmt::Xz),
+                PkgFmt::Tzstd => Some(PkgFmt::Zst),
+                _ => None,
+            }
+        }
+        _ => Some(fmt),
+    }
+}
+
+/// Size of the internal buffer of the brotli decoder.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Decompress `dat`, failing with [`super::DownloadError::LimitExceeded`]
+/// if the decoder needs more than `max_memory` bytes of memory.
+pub(super) fn decompress(
+    mut dat: impl BufRead + 'static,
+    fmt: TarBasedFmt,
+    max_memory: u64,
+) -> io::Result<Box<dyn Read>> {
     use TarBasedFmt::*;
 
-    let r: Box<dyn Read> = match fmt {
+    Ok(match fmt {
         Tar => Box::new(dat),
         Tbz2 => Box::new(BzDecoder::new(dat)),
         Tgz => Box::new(GzDecoder::new(dat)),
-        Txz => Box::new(XzDecoder::new(dat)),
+        Txz => Box::new(LimitedXzDecoder::new(dat, max_memory)?),
         Tzstd => {
+            check_zstd_window(dat.fill_buf()?, max_memory)?;
             // The error can only come from raw::Decoder::with_dictionary as of zstd 0.10.2 and
             // 0.11.2, which is specified as `&[]` by `ZstdDecoder::new`, thus `ZstdDecoder::new`
             // should not return any error.
-            Box::new(ZstdDecoder::with_buffer(dat)?)
+            let mut decoder = ZstdDecoder::with_buffer(dat)?;
+            if let Some(window_log_max) = zstd_window_log_max(max_memory) {
+                decoder.window_log_max(window_log_max)?;
+            }
+            Box::new(decoder)
         }
-    };
+        Tlz4 => Box::new(Lz4Decoder::new(dat)),
+        Tbr => Box::new(BrotliDecoder::new(dat, BROTLI_BUFFER_SIZE)),
+        Tlz => Box::new(LzipDecoder::new(dat, max_memory)),
+    })
+}
+
+/// [`XzDecoder`] failing with [`super::DownloadError::LimitExceeded`]
+/// instead of [`xz_stream::Error::MemLimit`].
+pub(super) struct LimitedXzDecoder<R> {
+    decoder: XzDecoder<R>,
+    max_memory: u64,
+}
+
+impl<R: BufRead> LimitedXzDecoder<R> {
+    /// Same as [`XzDecoder::new`].
+    pub(super) fn new(reader: R, max_memory: u64) -> io::Result<Self> {
+        let stream = XzStream::new_stream_decoder(max_memory, 0)?;
+        Ok(Self::with_stream(reader, stream, max_memory))
+    }
+
+    /// Same as [`XzDecoder::new_multi_decoder`].
+    pub(super) fn new_multi_decoder(reader: R, max_memory: u64) -> io::Result<Self> {
+        let stream = XzStream::new_auto_decoder(max_memory, xz_stream::CONCATENATED)?;
+        Ok(Self::with_stream(reader, stream, max_memory))
+    }
+
+    fn with_stream(reader: R, stream: XzStream, max_memory: u64) -> Self {
+        Self {
+            decoder: XzDecoder::new_stream(reader, stream),
+            max_memory,
+        }
+    }
+}
+
+impl<R: BufRead> Read for LimitedXzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf).map_err(|err| {
+            let is_mem_limit = err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<xz_stream::Error>())
+                .is_some_and(|err| matches!(err, xz_stream::Error::MemLimit));
+
+            if is_mem_limit {
+                decoder_memory_exceeded(self.max_memory)
+            } else {
+                err
+            }
+        })
+    }
+}
+
+const ZSTD_MAGIC: u32 = 0xFD2FB528;
+
+/// Return the window size of the zstd frame starting with `header`, which
+/// is the memory needed to decode it, or `None` if it cannot tell.
+///
+/// See <https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#frame_header>.
+fn zstd_window_size(header: &[u8]) -> Option<u64> {
+    if u32::from_le_bytes(header.get(..4)?.try_into().unwrap()) != ZSTD_MAGIC {
+        return None;
+    }
+
+    let descriptor = *header.get(4)?;
+    let single_segment = descriptor & 0x20 != 0;
+
+    if !single_segment {
+        let window_descriptor = *header.get(5)?;
+        let base = 1u64 << (10 + (window_descriptor >> 3));
+        return Some(base + base / 8 * u64::from(window_descriptor & 7));
+    }
+
+    // The window is the whole content, whose size follows the dictionary id.
+    let dict_id_len = [0, 1, 2, 4][usize::from(descriptor & 3)];
+    let start = 5 + dict_id_len;
+    match descriptor >> 6 {
+        0 => header.get(start).map(|size| u64::from(*size)),
+        1 => header
+            .get(start..start + 2)
+            .map(|size| u64::from(u16::from_le_bytes(size.try_into().unwrap())) + 256),
+        2 => header
+            .get(start..start + 4)
+            .map(|size| u64::from(u32::from_le_bytes(size.try_into().unwrap()))),
+        _ => header
+            .get(start..start + 8)
+            .map(|size| u64::from_le_bytes(size.try_into().unwrap())),
+    }
+}
+
+/// Fail if the zstd frame starting with `header` needs more than
+/// `max_memory` bytes of memory to be decoded, to fail with a clear error
+/// instead of the one of the decoder.
+pub(super) fn check_zstd_window(header: &[u8], max_memory: u64) -> io::Result<()> {
+    match zstd_window_size(header) {
+        Some(window_size) if window_size > max_memory => Err(decoder_memory_exceeded(max_memory)),
+        _ => Ok(()),
+    }
+}
 
-    Ok(Archive::new(r))
+/// Return the log2 of the largest window the zstd decoder may use within
+/// `max_memory`, or `None` to keep its default.
+///
+/// It also limits the frames following the first one, which are not checked
+/// by [`check_zstd_window`].
+pub(super) fn zstd_window_log_max(max_memory: u64) -> Option<u32> {
+    // 10 and 31 are the minimum and maximum supported by zstd.
+    (max_memory != u64::MAX).then(|| max_memory.max(1).ilog2().clamp(10, 31))
 }
@@ -0,0 +1,145 @@
+use std::io::{self, Read};
+
+use super::DownloadError;
+
+/// Limits of what is extracted from a package by [`super::Download::and_extract`],
+/// to fail on decompression bombs or corrupted archives instead of filling
+/// the disk or running out of memory.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ExtractionLimits {
+    /// Maximum number of bytes written once decompressed.
+    pub max_size: u64,
+    /// Maximum number of files, directories and links extracted.
+    pub max_entries: u64,
+    /// Maximum number of bytes of memory the xz, zstd and lzip decoders may
+    /// use, which is set by the compressor, e.g. `xz -9` needs 65 MiB and
+    /// `zstd --long=31` 2 GiB.
+    pub max_decoder_memory: u64,
+    /// Reduce the memory used, at the expense of speed, e.g. on devices
+    /// with little RAM: the files of tarballs are written one at a time,
+    /// and at most one chunk of the download is buffered for the decoder.
+    pub low_memory: bool,
+}
+
+impl ExtractionLimits {
+    /// Do not limit the extraction at all.
+    pub const UNLIMITED: Self = Self {
+        max_size: u64::MAX,
+        max_entries: u64::MAX,
+        max_decoder_memory: u64::MAX,
+        low_memory: false,
+    };
+}
+
+impl Default for ExtractionLimits {
+    /// 16 GiB and a million entries, which is far more than any package
+    /// of binaries needs, leaving the memory of the decoders to their own
+    /// defaults.
+    fn default() -> Self {
+        Self {
+            max_size: 16 * 1024 * 1024 * 1024,
+            max_entries: 1_000_000,
+            max_decoder_memory: u64::MAX,
+            low_memory: false,
+        }
+    }
+}
+
+/// Track what an extraction writes against its [`ExtractionLimits`].
+///
+/// Errors are [`DownloadError::LimitExceeded`] wrapped in an [`io::Error`],
+/// for use inside of the blocking extractors.
+#[derive(Debug)]
+pub(super) struct LimitTracker {
+    limits: ExtractionLimits,
+    size: u64,
+    entries: u64,
+}
+
+impl LimitTracker {
+    pub(super) fn new(limits: ExtractionLimits) -> Self {
+        Self {
+            limits,
+            size: 0,
+            entries: 0,
+        }
+    }
+
+    pub(super) fn max_decoder_memory(&self) -> u64 {
+        self.limits.max_decoder_memory
+    }
+
+    /// Forget what was extracted so far, to extract again from the start.
+    pub(super) fn reset(&mut self) {
+        *self = Self::new(self.limits);
+    }
+
+    /// Count an entry about to be extracted.
+    pub(super) fn add_entry(&mut self) -> io::Result<()> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            Err(io::Error::other(DownloadError::LimitExceeded {
+                what: "entries",
+                limit: self.limits.max_entries,
+            }))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Count `size` bytes about to be written, e.g. by an external tool.
+    pub(super) fn add_size(&mut self, size: u64) -> io::Result<()> {
+        self.size = self.size.saturating_add(size);
+        if self.size > self.limits.max_size {
+            Err(io::Error::other(DownloadError::LimitExceeded {
+                what: "bytes",
+                limit: self.limits.max_size,
+            }))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of bytes which can be read from the decoder: one more than
+    /// allowed, to detect that the limit is exceeded.
+    fn remaining(&self) -> u64 {
+        self.limits
+            .max_size
+            .saturating_sub(self.size)
+            .saturating_add(1)
+    }
+
+    /// Wrap `reader` to count the bytes read from it, failing right after
+    /// exceeding the limit, e.g. for [`super::ExtractionSink::write_file`].
+    pub(super) fn reader<R: Read>(&mut self, reader: R) -> LimitedReader<'_, R> {
+        LimitedReader {
+            tracker: self,
+            reader,
+        }
+    }
+}
+
+/// Reader returned by [`LimitTracker::reader`].
+pub(super) struct LimitedReader<'a, R> {
+    tracker: &'a mut LimitTracker,
+    reader: R,
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.tracker.remaining().try_into().unwrap_or(usize::MAX));
+        let read = self.reader.read(&mut buf[..len])?;
+        self.tracker.add_size(read as u64)?;
+        Ok(read)
+    }
+}
+
+/// Error of a decoder needing more than `limit` bytes of memory.
+pub(super) fn decoder_memory_exceeded(limit: u64) -> io::Error {
+    io::Error::other(DownloadError::LimitExceeded {
+        what: "bytes of decoder memory",
+        limit,
+    })
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use cfg_if::cfg_if;
+
+/// Destination of an extraction, e.g. [`FsSink`] for the real filesystem
+/// or [`InMemoryVfs`].
+///
+/// All paths passed to the sink are relative, normalized and never
+/// contain `..`, so the sink does not need to do any sanitization itself.
+/// The targets of symlinks are not sanitized though.
+///
+/// Methods of this trait are always called in a blocking context.
+pub trait ExtractionSink: Send + Sync + 'static {
+    /// Create directory `path` along with all of its parents.
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()>;
+
+    /// Create a regular file at `path` with data read from `content` until
+    /// its end.
+    ///
+    /// The parent of `path`, if it is not empty, is guaranteed to be created
+    /// via [`ExtractionSink::create_dir_all`] before this is called.
+    ///
+    /// If a file already exists at `path`, it should be overwritten.
+    fn write_file(&mut self, path: &Path, content: &mut dyn Read) -> io::Result<()>;
+
+    /// Set the permissions of the file at `path` to the unix `mode`.
+    ///
+    /// Ignored by default.
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        let _ = (path, mode);
+        Ok(())
+    }
+
+    /// Create a symlink at `link` pointing to `target`, replacing the file
+    /// already at `link` if any.
+    ///
+    /// The parent of `link` is created like for [`ExtractionSink::write_file`].
+    ///
+    /// Return false if the symlink is skipped, which it always is by default.
+    fn create_symlink(&mut self, link: &Path, target: &Path) -> io::Result<bool> {
+        let _ = (link, target);
+        Ok(false)
+    }
+
+    /// Return the directory on the filesystem the sink writes to, if any.
+    ///
+    /// Tarballs are then unpacked there by `tar` itself, restoring the
+    /// metadata and hardlinks of their entries.
+    fn dir(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// An [`ExtractionSink`] writing to a directory on the filesystem, which is
+/// what [`super::Download::and_extract`] uses.
+#[derive(Debug)]
+pub struct FsSink {
+    dir: PathBuf,
+}
+
+impl FsSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl ExtractionSink for FsSink {
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(self.dir.join(path))
+    }
+
+    fn write_file(&mut self, path: &Path, content: &mut dyn Read) -> io::Result<()> {
+        let path = self.dir.join(path);
+
+        // Never write through a symlink, which may point outside of dir.
+        if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_symlink()) {
+            fs::remove_file(&path)?;
+        }
+
+        io::copy(content, &mut fs::File::create(path)?)?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(self.dir.join(path), fs::Permissions::from_mode(mode))
+    }
+
+    fn create_symlink(&mut self, link: &Path, target: &Path) -> io::Result<bool> {
+        let path = self.dir.join(link);
+
+        cfg_if! {
+            if #[cfg(windows)] {
+                // Symlinks are extracted as files holding their targets on windows.
+                fs::write(path, target.to_string_lossy().as_bytes())?;
+            } else {
+                use tracing::warn;
+
+                use super::async_extracter::is_symlink_inside;
+
+                match fs::symlink_metadata(&path) {
+                    Ok(metadata) if !metadata.is_dir() => fs::remove_file(&path)?,
+                    _ => (),
+                }
+
+                if !is_symlink_inside(&self.dir, link, target)? {
+                    warn!(
+                        "Skipping symlink `{}` pointing outside of the package to `{}`",
+                        link.display(),
+                        target.display()
+                    );
+                    return Ok(false);
+                }
+                std::os::unix::fs::symlink(target, path)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        Some(&self.dir)
+    }
+}
+
+/// An in-memory virtual filesystem, useful for inspecting the content
+/// of an archive without touching the disk, e.g. for fuzzing or
+/// figuring out what files would be installed.
+#[derive(Debug, Default)]
+pub struct InMemoryVfs {
+    files: BTreeMap<Box<Path>, Bytes>,
+    dirs: BTreeSet<Box<Path>>,
+}
+
+impl InMemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return content of the file at `path`.
+    pub fn get_file(&self, path: &Path) -> Option<&Bytes> {
+        self.files.get(path)
+    }
+
+    /// Return true if `path` is a directory.
+    pub fn has_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    /// Iterate over all files, sorted by their path.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &Bytes)> + '_ {
+        self.files.iter().map(|(path, content)| (&**path, content))
+    }
+
+    /// Total size of all files stored.
+    pub fn total_size(&self) -> u64 {
+        self.files
+            .values()
+            .map(|content| content.len() as u64)
+            .sum()
+    }
+}
+
+impl ExtractionSink for InMemoryVfs {
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        for dir in path.ancestors() {
+            if dir.as_os_str().is_empty() || !self.dirs.insert(dir.into()) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, content: &mut dyn Read) -> io::Result<()> {
+        if self.dirs.contains(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is a directory", path.display()),
+            ));
+        }
+
+        let mut buffer = Vec::new();
+        content.read_to_end(&mut buffer)?;
+
+        self.files.insert(path.into(), buffer.into());
+
+        Ok(())
+    }
+}
+
+/// An [`ExtractionSink`] only recording the files and their sizes, with
+/// their content discarded, e.g. to check that a package contains the
+/// expected binaries without extracting it.
+#[derive(Debug, Default)]
+pub struct ArchiveListing {
+    files: BTreeMap<Box<Path>, u64>,
+    dirs: BTreeSet<Box<Path>>,
+}
+
+impl ArchiveListing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return size of the file at `path`.
+    pub fn get_size(&self, path: &Path) -> Option<u64> {
+        self.files.get(path).copied()
+    }
+
+    /// Return true if `path` is a file.
+    pub fn has_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Return true if `path` is a directory.
+    pub fn has_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    /// Iterate over all files with their sizes, sorted by their path.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, u64)> + '_ {
+        self.files.iter().map(|(path, size)| (&**path, *size))
+    }
+
+    /// Total size of all files listed.
+    pub fn total_size(&self) -> u64 {
+        self.files.values().sum()
+    }
+}
+
+impl ExtractionSink for ArchiveListing {
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        for dir in path.ancestors() {
+            if dir.as_os_str().is_empty() || !self.dirs.insert(dir.into()) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, content: &mut dyn Read) -> io::Result<()> {
+        if self.dirs.contains(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is a directory", path.display()),
+            ));
+        }
+
+        let size = io::copy(content, &mut io::sink())?;
+        self.files.insert(path.into(), size);
+
+        Ok(())
+    }
+}
+
+/// Create the parent of `path` in `sink`, if it is not empty.
+pub(super) fn create_parent_dir(sink: &mut dyn ExtractionSink, path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => sink.create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,400 @@
+//! Extraction of self-extracting Windows installers made with NSIS or
+//! Inno Setup, which are often shipped as the only Windows asset of a tool.
+//!
+//! Their files are unpacked with `7z` and `innoextract` respectively, and
+//! the executable of the tool is extracted in place of the installer.
+//!
+//! Since these tools are run on the download, this is only done for the
+//! `installer` pkg-fmt, which has to be set explicitly.
+
+use std::{
+    collections::BTreeSet,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use tracing::{debug, warn};
+
+use super::{extraction_limits::LimitTracker, DownloadError, ExtractedFiles, ExtractionSink};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum InstallerKind {
+    Nsis,
+    InnoSetup,
+}
+
+/// Signature of the first header of NSIS installers, which starts 4 bytes
+/// after a 512 bytes aligned offset.
+const NSIS_SIGNATURE: &[u8] = b"\xEF\xBE\xAD\xDENullsoftInst";
+const NSIS_ALIGNMENT: usize = 512;
+
+/// Start of the setup data of Inno Setup installers, followed by the version.
+const INNO_SETUP_SIGNATURE: &[u8] = b"Inno Setup Setup Data (";
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Return the kind of installer `f` is, or `None` if it is not one.
+pub(super) fn installer_kind(f: &mut File) -> io::Result<Option<InstallerKind>> {
+    f.seek(SeekFrom::Start(0))?;
+
+    let mut buf = vec![0; CHUNK_SIZE + INNO_SETUP_SIGNATURE.len()];
+    // Bytes of the previous chunk kept at the start of `buf`, so that
+    // signatures spanning two chunks are found.
+    let mut kept = 0;
+    // Offset in the file of the start of `buf`.
+    let mut offset = 0;
+    let mut is_exe = None;
+
+    loop {
+        let n = f.read(&mut buf[kept..])?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let data = &buf[..kept + n];
+
+        if *is_exe.get_or_insert_with(|| data.starts_with(b"MZ")) {
+            if let Some(kind) = find_signature(data, offset) {
+                return Ok(Some(kind));
+            }
+        } else {
+            return Ok(None);
+        }
+
+        kept = data.len().min(INNO_SETUP_SIGNATURE.len());
+        let start = data.len() - kept;
+        offset += start;
+        buf.copy_within(start..start + kept, 0);
+    }
+}
+
+/// Find the signature of an installer in `data`, which starts `offset`
+/// bytes into the file.
+fn find_signature(data: &[u8], offset: usize) -> Option<InstallerKind> {
+    let first_aligned = offset.next_multiple_of(NSIS_ALIGNMENT) - offset;
+    let is_nsis = (first_aligned..data.len())
+        .step_by(NSIS_ALIGNMENT)
+        .any(|start| data.get(start + 4..start + 4 + NSIS_SIGNATURE.len()) == Some(NSIS_SIGNATURE));
+
+    if is_nsis {
+        Some(InstallerKind::Nsis)
+    } else if data
+        .windows(INNO_SETUP_SIGNATURE.len())
+        .any(|window| window == INNO_SETUP_SIGNATURE)
+    {
+        Some(InstallerKind::InnoSetup)
+    } else {
+        None
+    }
+}
+
+/// Unpack the files of `installer` into `target_dir`.
+fn unpack(installer: &Path, kind: InstallerKind, target_dir: &Path) -> Result<(), DownloadError> {
+    let (program, missing) = match kind {
+        InstallerKind::Nsis => ("7z", "7z is required to extract NSIS installers"),
+        InstallerKind::InnoSetup => (
+            "innoextract",
+            "innoextract is required to extract Inno Setup installers",
+        ),
+    };
+
+    let mut command = Command::new(program);
+    match kind {
+        InstallerKind::Nsis => command
+            .args(["x", "-y"])
+            .arg(format!("-o{}", target_dir.display()))
+            .arg(installer),
+        InstallerKind::InnoSetup => command
+            .args(["--extract", "--silent", "--output-dir"])
+            .arg(target_dir)
+            .arg(installer),
+    };
+
+    debug!(
+        "Unpacking {kind:?} installer with {program} to `{}`",
+        target_dir.display()
+    );
+
+    let output = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(DownloadError::InvalidInstaller(missing))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        warn!(
+            "{program} failed to unpack the installer: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Err(DownloadError::InvalidInstaller(
+            "failed to unpack the installer",
+        ))
+    }
+}
+
+fn is_exe(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+/// Call `f` with the file name and path of each executable under `root`.
+///
+/// If executables in different directories share the same name, only the
+/// first one found is kept.
+fn for_each_exe(root: &Path, mut f: impl FnMut(&OsStr, &Path) -> io::Result<()>) -> io::Result<()> {
+    let mut dirs = vec![root.to_owned()];
+    let mut seen = BTreeSet::<OsString>::new();
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_unstable_by_key(|entry| entry.file_name());
+
+        let mut subdirs = Vec::new();
+
+        for entry in entries {
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                subdirs.push(path);
+            } else if file_type.is_file() && is_exe(&path) {
+                let name = entry.file_name();
+                if seen.insert(name.to_ascii_lowercase()) {
+                    f(&name, &path)?;
+                } else {
+                    warn!(
+                        "Ignoring `{}` since an executable of the same name is extracted",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        // Files are visited before the subdirectories, in alphabetical order,
+        // so push the subdirectories in reverse to pop the first one next.
+        dirs.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(())
+}
+
+fn is_uninstaller(name: &OsStr) -> bool {
+    // NSIS names it `uninstall.exe` or `uninst.exe`, Inno Setup `unins000.exe`.
+    name.to_string_lossy()
+        .to_ascii_lowercase()
+        .starts_with("unins")
+}
+
+/// Count the files unpacked to `root` against `limits`, since they are
+/// written by an external tool.
+fn check_limits(root: &Path, limits: &mut LimitTracker) -> io::Result<()> {
+    let mut dirs = vec![root.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+
+            limits.add_entry()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                limits.add_size(metadata.len())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Return the executable of the tool among the ones unpacked to `root`.
+///
+/// It is an error if there are several, since there is no telling which
+/// one is the tool.
+fn find_payload(root: &Path) -> Result<PathBuf, DownloadError> {
+    let mut exes = Vec::new();
+    for_each_exe(root, |name, path| {
+        if !is_uninstaller(name) {
+            exes.push(path.to_owned());
+        }
+        Ok(())
+    })?;
+
+    match <[_; 1]>::try_from(exes) {
+        Ok([payload]) => Ok(payload),
+        Err(exes) if exes.is_empty() => Err(DownloadError::InvalidInstaller(
+            "no executable is found in the installer",
+        )),
+        Err(exes) => {
+            warn!(
+                "The installer contains {} executables: {}",
+                exes.len(),
+                exes.iter()
+                    .map(|exe| exe.strip_prefix(root).unwrap_or(exe).display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Err(DownloadError::InvalidInstaller(
+                "several executables are found in the installer",
+            ))
+        }
+    }
+}
+
+/// Write the executable installed by the NSIS or Inno Setup installer at
+/// `installer`, opened as `f`, to `file_name` in `sink`.
+pub(super) fn do_extract_installer(
+    installer: &Path,
+    mut f: File,
+    file_name: &Path,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let kind = installer_kind(&mut f)?.ok_or(DownloadError::InvalidInstaller(
+        "not an NSIS or Inno Setup installer",
+    ))?;
+    drop(f);
+
+    debug!("`{}` is a {kind:?} installer", installer.display());
+
+    let target_dir = tempfile::tempdir()?;
+    unpack(installer, kind, target_dir.path())?;
+    check_limits(target_dir.path(), limits)?;
+
+    let payload = find_payload(target_dir.path())?;
+    debug!(
+        "Extracting `{}` from the installer",
+        payload
+            .strip_prefix(target_dir.path())
+            .unwrap_or(&payload)
+            .display()
+    );
+
+    // Already counted against the limits by check_limits.
+    sink.write_file(file_name, &mut File::open(&payload)?)?;
+
+    let mut extracted_files = ExtractedFiles::new();
+    extracted_files.add_file(file_name);
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::ExtractionLimits, *};
+
+    use std::io::Write;
+
+    fn installer_kind_of(data: &[u8]) -> Option<InstallerKind> {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(data).unwrap();
+        installer_kind(&mut f).unwrap()
+    }
+
+    #[test]
+    fn test_installer_kind() {
+        let mut nsis = b"MZ".to_vec();
+        nsis.resize(3 * CHUNK_SIZE + 4, 0);
+        nsis.extend_from_slice(NSIS_SIGNATURE);
+        assert_eq!(installer_kind_of(&nsis), Some(InstallerKind::Nsis));
+
+        // Not aligned
+        nsis.insert(0, 0);
+        nsis[..3].copy_from_slice(b"MZ\0");
+        assert_eq!(installer_kind_of(&nsis), None);
+
+        // Spanning two chunks
+        let mut inno = b"MZ".to_vec();
+        inno.resize(CHUNK_SIZE - 5, 0);
+        inno.extend_from_slice(b"Inno Setup Setup Data (6.2.0)");
+        assert_eq!(installer_kind_of(&inno), Some(InstallerKind::InnoSetup));
+
+        inno[0] = b'\x7f';
+        assert_eq!(installer_kind_of(&inno), None);
+        assert_eq!(installer_kind_of(b"MZ"), None);
+    }
+
+    #[test]
+    fn test_for_each_exe() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        fs::create_dir_all(root.join("PFiles/Tool/bin")).unwrap();
+        fs::write(root.join("PFiles/Tool/bin/tool.exe"), "tool").unwrap();
+        fs::write(root.join("PFiles/Tool/bin/helper.EXE"), "helper").unwrap();
+        fs::write(root.join("PFiles/Tool/README.txt"), "readme").unwrap();
+        fs::create_dir_all(root.join("PFiles/Tool/old")).unwrap();
+        fs::write(root.join("PFiles/Tool/old/tool.exe"), "old").unwrap();
+        fs::write(root.join("tool.msi"), "msi").unwrap();
+
+        let mut exes = Vec::new();
+        for_each_exe(root, |name, src| {
+            exes.push((name.to_owned(), fs::read_to_string(src)?));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            exes,
+            [
+                ("helper.EXE".into(), "helper".to_owned()),
+                ("tool.exe".into(), "tool".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_payload() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("app/tool.exe"), "tool").unwrap();
+        fs::write(root.join("app/unins000.exe"), "uninstaller").unwrap();
+        assert_eq!(find_payload(root).unwrap(), root.join("app/tool.exe"));
+
+        fs::write(root.join("app/helper.exe"), "h").unwrap();
+        assert!(matches!(
+            find_payload(root),
+            Err(DownloadError::InvalidInstaller(_))
+        ));
+
+        fs::remove_file(root.join("app/tool.exe")).unwrap();
+        fs::remove_file(root.join("app/helper.exe")).unwrap();
+        assert!(matches!(
+            find_payload(root),
+            Err(DownloadError::InvalidInstaller(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("app/tool.exe"), "tool").unwrap();
+
+        let limits = |max_size, max_entries| {
+            LimitTracker::new(ExtractionLimits {
+                max_size,
+                max_entries,
+                ..Default::default()
+            })
+        };
+        check_limits(root, &mut limits(4, 2)).unwrap();
+        check_limits(root, &mut limits(3, 2)).unwrap_err();
+        check_limits(root, &mut limits(4, 1)).unwrap_err();
+    }
+}
@@ -0,0 +1,238 @@
+//! Decoder of lzip-compressed streams, made of one or more members wrapping
+//! raw LZMA data between a header and a trailer.
+//!
+//! See <https://www.nongnu.org/lzip/manual/lzip_manual.html#File-format>.
+
+use std::{
+    cell::RefCell,
+    io::{self, Read},
+    rc::Rc,
+};
+
+use flate2::CrcReader;
+use lzma_rust::LZMAReader;
+
+use super::extraction_limits::decoder_memory_exceeded;
+
+const MAGIC: &[u8; 4] = b"LZIP";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 6;
+const TRAILER_LEN: usize = 20;
+
+/// Literal context bits, literal position bits and position bits of the
+/// LZMA data, which are fixed by the format.
+const LC: u32 = 3;
+const LP: u32 = 0;
+const PB: u32 = 2;
+
+/// Shares the compressed stream between the decoder of the current member
+/// and [`LzipDecoder`], which reads the headers and trailers.
+struct SharedReader<R>(Rc<RefCell<R>>);
+
+impl<R: Read> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+pub(super) struct LzipDecoder<R> {
+    reader: Rc<RefCell<R>>,
+    member: Option<CrcReader<LZMAReader<SharedReader<R>>>>,
+    /// Number of members decoded so far.
+    members: u64,
+    /// Maximum dictionary size of the members.
+    max_memory: u64,
+}
+
+impl<R: Read> LzipDecoder<R> {
+    /// Fails with [`super::DownloadError::LimitExceeded`] on members whose
+    /// dictionary is larger than `max_memory` bytes.
+    pub(super) fn new(reader: R, max_memory: u64) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(reader)),
+            member: None,
+            members: 0,
+            max_memory,
+        }
+    }
+
+    /// Read the header of the next member, return false if there is none.
+    fn start_member(&mut self) -> io::Result<bool> {
+        let mut header = [0; HEADER_LEN];
+        let len = read_up_to(&mut *self.reader.borrow_mut(), &mut header)?;
+
+        if !header.starts_with(MAGIC) || len < HEADER_LEN {
+            // Like lzip, ignore the data trailing the last member.
+            return if self.members > 0 {
+                Ok(false)
+            } else {
+                Err(invalid_data("missing lzip header"))
+            };
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("unsupported lzip version"));
+        }
+
+        let dict_size = dict_size(header[5])?;
+        if u64::from(dict_size) > self.max_memory {
+            return Err(decoder_memory_exceeded(self.max_memory));
+        }
+
+        let reader = LZMAReader::new(
+            SharedReader(self.reader.clone()),
+            u64::MAX,
+            LC,
+            LP,
+            PB,
+            dict_size,
+            None,
+        )?;
+        self.member = Some(CrcReader::new(reader));
+
+        Ok(true)
+    }
+
+    /// Read the trailer of the member decoded by `member` and check it.
+    fn finish_member(&mut self, member: CrcReader<LZMAReader<SharedReader<R>>>) -> io::Result<()> {
+        let mut trailer = [0; TRAILER_LEN];
+        self.reader.borrow_mut().read_exact(&mut trailer)?;
+
+        let crc32 = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let data_size = u64::from_le_bytes(trailer[4..12].try_into().unwrap());
+
+        let crc = member.crc();
+        // Crc only counts the lower 32 bits of the size.
+        if crc.sum() != crc32 || u64::from(crc.amount()) != data_size & u64::from(u32::MAX) {
+            return Err(invalid_data("lzip member is corrupted"));
+        }
+
+        self.members += 1;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for LzipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if let Some(member) = &mut self.member {
+                let n = member.read(buf)?;
+                if n > 0 {
+                    return Ok(n);
+                }
+
+                let member = self.member.take().unwrap();
+                self.finish_member(member)?;
+            }
+
+            if !self.start_member()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Decode the dictionary size from the last byte of the header.
+fn dict_size(coded: u8) -> io::Result<u32> {
+    let exp = u32::from(coded & 0x1f);
+    if !(12..=29).contains(&exp) {
+        return Err(invalid_data("invalid lzip dictionary size"));
+    }
+
+    let base = 1u32 << exp;
+    Ok(base - (base / 16) * u32::from(coded >> 5))
+}
+
+/// Read into `buf` until it is full or the end of `reader`.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::*;
+
+    use crate::download::DownloadError;
+    use lzma_rust::{CountingWriter, LZMA2Options, LZMAWriter};
+
+    /// Compress `data` into a single lzip member.
+    pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+        // 1 MiB
+        let coded_dict_size = 20;
+        let mut options = LZMA2Options::with_preset(6);
+        options.dict_size = 1 << 20;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&[VERSION, coded_dict_size]);
+
+        let mut writer =
+            LZMAWriter::new_no_header(CountingWriter::new(&mut out), &options, true).unwrap();
+        io::Write::write_all(&mut writer, data).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let member_size = out.len() + TRAILER_LEN;
+        out.extend_from_slice(&crc.sum().to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(member_size as u64).to_le_bytes());
+
+        out
+    }
+
+    #[test]
+    fn test_lzip_decoder() {
+        let data = b"hello world, hello lzip\n".repeat(100);
+
+        // Two members followed by trailing data
+        let mut compressed = compress(&data);
+        compressed.extend(compress(b"!"));
+        compressed.extend_from_slice(b"\0\0");
+
+        let mut decompressed = Vec::new();
+        LzipDecoder::new(compressed.as_slice(), u64::MAX)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed[..data.len()], data);
+        assert_eq!(&decompressed[data.len()..], b"!");
+
+        let mut corrupted = compress(&data);
+        let len = corrupted.len();
+        corrupted[len - TRAILER_LEN] ^= 1;
+        assert!(LzipDecoder::new(corrupted.as_slice(), u64::MAX)
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        assert!(LzipDecoder::new(&b"not lzip"[..], u64::MAX)
+            .read_to_end(&mut Vec::new())
+            .is_err());
+        assert_eq!(dict_size(0xd3).unwrap(), 320 * 1024);
+
+        // The dictionary of 1 MiB exceeds the limit.
+        let err = LzipDecoder::new(compress(&data).as_slice(), 1024 * 1024 - 1)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(
+            DownloadError::from(err),
+            DownloadError::LimitExceeded { .. }
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+/// How the mode of the files and directories extracted from tar-based
+/// packages is set on unix.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ModePolicy {
+    /// Keep the permission bits of the archive, dropping the setuid, setgid
+    /// and sticky bits like tar does by default.
+    #[default]
+    Preserve,
+    /// Keep all of the mode bits of the archive, including the setuid,
+    /// setgid and sticky bits.
+    PreserveAll,
+    /// Ignore the mode of the archive, setting `0o755` on directories and
+    /// files executable by anyone, and `0o644` on the other files.
+    Normalize,
+}
+
+impl ModePolicy {
+    /// Return the mode to set on an entry whose mode is `mode` in the archive.
+    pub(super) fn apply(self, mode: u32, is_dir: bool) -> u32 {
+        match self {
+            ModePolicy::Preserve => mode & 0o777,
+            ModePolicy::PreserveAll => mode & 0o7777,
+            ModePolicy::Normalize if is_dir || mode & 0o111 != 0 => 0o755,
+            ModePolicy::Normalize => 0o644,
+        }
+    }
+}
+
+/// Metadata of the entries of tar-based packages restored by
+/// [`super::Download::and_extract`], along with their content.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MetadataPolicy {
+    pub mode: ModePolicy,
+    /// Set the modification time of the files to the one in the archive,
+    /// instead of the time they are extracted at.
+    pub preserve_mtime: bool,
+    /// Set the extended attributes of the files stored in the archive on
+    /// unix, e.g. `security.capability` or `com.apple.quarantine`.
+    pub preserve_xattrs: bool,
+}
+
+impl Default for MetadataPolicy {
+    /// Same as tar, preserving the permission bits and the modification time
+    /// but not the extended attributes.
+    fn default() -> Self {
+        Self {
+            mode: ModePolicy::Preserve,
+            preserve_mtime: true,
+            preserve_xattrs: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mode_policy() {
+        assert_eq!(ModePolicy::Preserve.apply(0o104755, false), 0o755);
+        assert_eq!(ModePolicy::PreserveAll.apply(0o104755, false), 0o4755);
+        assert_eq!(ModePolicy::Normalize.apply(0o104700, false), 0o755);
+        assert_eq!(ModePolicy::Normalize.apply(0o100600, false), 0o644);
+        assert_eq!(ModePolicy::Normalize.apply(0o40700, true), 0o755);
+    }
+}
@@ -0,0 +1,291 @@
+//! Extraction of Windows installer packages, the files of which are read from
+//! the cabinets embedded in the package, without running the installer.
+//!
+//! Only executables are extracted, to the root of the destination, since the
+//! layout of the installed files varies between packages.
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, Read, Seek},
+    iter,
+    path::Path,
+};
+
+use cab::Cabinet;
+use msi::{Package, Select};
+use tracing::{debug, warn};
+
+use super::{extraction_limits::LimitTracker, DownloadError, ExtractedFiles, ExtractionSink};
+
+fn invalid(msg: &'static str) -> DownloadError {
+    DownloadError::InvalidMsi(msg)
+}
+
+/// An executable of the package, stored in a cabinet under its `key`.
+#[derive(Debug)]
+struct Exe {
+    key: String,
+    name: String,
+    sequence: i32,
+}
+
+/// Return the long name out of `file_name`, which is either `short|long` or
+/// only one name.
+fn long_name(file_name: &str) -> &str {
+    file_name.rsplit('|').next().unwrap_or(file_name)
+}
+
+/// Return true if `name` is the name of an executable which can be
+/// extracted as is to the root of the destination.
+fn is_exe(name: &str) -> bool {
+    !name.contains(['/', '\\', ':'])
+        && Path::new(name)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+/// Return the executables of the package, ordered by their sequence in
+/// the cabinets.
+///
+/// If executables in different directories share the same name, only the
+/// first one is kept.
+fn exes<F: Read + Seek>(package: &mut Package<F>) -> io::Result<Vec<Exe>> {
+    let mut exes: Vec<_> = package
+        .select_rows(Select::table("File").columns(&["File", "FileName", "Sequence"]))?
+        .filter_map(|row| {
+            Some(Exe {
+                key: row[0].as_str()?.to_owned(),
+                name: long_name(row[1].as_str()?).to_owned(),
+                sequence: row[2].as_int()?,
+            })
+        })
+        .filter(|exe| is_exe(&exe.name))
+        .collect();
+    exes.sort_by_key(|exe| exe.sequence);
+
+    let mut seen = BTreeSet::new();
+    exes.retain(|exe| {
+        let is_new = seen.insert(exe.name.to_ascii_lowercase());
+        if !is_new {
+            warn!(
+                "Ignoring `{}` since an executable of the same name is extracted",
+                exe.key
+            );
+        }
+        is_new
+    });
+
+    Ok(exes)
+}
+
+/// Return the cabinets of the package along with the last sequence of the
+/// files they hold, ordered by it.
+///
+/// The cabinets embedded in the package are named after their stream,
+/// prefixed with `#`.
+fn cabinets<F: Read + Seek>(package: &mut Package<F>) -> io::Result<Vec<(i32, Option<String>)>> {
+    let mut cabinets: Vec<_> = package
+        .select_rows(Select::table("Media").columns(&["LastSequence", "Cabinet"]))?
+        .filter_map(|row| Some((row[0].as_int()?, row[1].as_str().map(str::to_owned))))
+        .collect();
+    cabinets.sort_by_key(|(last_sequence, _)| *last_sequence);
+
+    Ok(cabinets)
+}
+
+/// Call `f` with the name and content of each executable of the package.
+fn for_each_exe<F: Read + Seek>(
+    package: &mut Package<F>,
+    mut f: impl FnMut(&str, &mut dyn Read) -> io::Result<()>,
+) -> Result<(), DownloadError> {
+    let mut exes = exes(package)?.into_iter().peekable();
+
+    for (last_sequence, cabinet) in cabinets(package)? {
+        let exes: Vec<_> =
+            iter::from_fn(|| exes.next_if(|exe| exe.sequence <= last_sequence)).collect();
+        if exes.is_empty() {
+            continue;
+        }
+
+        let Some(stream) = cabinet.as_deref().and_then(|name| name.strip_prefix('#')) else {
+            return Err(invalid("files outside of the package are not supported"));
+        };
+        debug!("Reading cabinet `{stream}` of the msi package");
+        let mut cabinet = Cabinet::new(package.read_stream(stream)?)?;
+
+        for exe in exes {
+            f(&exe.name, &mut cabinet.read_file(&exe.key)?)?;
+        }
+    }
+
+    if exes.peek().is_some() {
+        Err(invalid("files outside of the cabinets are not supported"))
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) fn do_extract_msi(
+    msi: File,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut package = Package::open(msi)?;
+
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_exe(&mut package, |name, content| {
+        limits.add_entry()?;
+        sink.write_file(Path::new(name), &mut limits.reader(content))?;
+        extracted_files.add_file(Path::new(name));
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        super::{ExtractionLimits, FsSink, InMemoryVfs},
+        *,
+    };
+
+    use std::{
+        fs,
+        io::{Cursor, Write},
+    };
+
+    use cab::{CabinetBuilder, CompressionType};
+    use msi::{Column, Insert, PackageType, Value};
+
+    /// Build a package of `files`, which are `(key, file name, content)`,
+    /// stored in one embedded cabinet.
+    fn msi(files: &[(&str, &str, &[u8])]) -> File {
+        let mut builder = CabinetBuilder::new();
+        let folder = builder.add_folder(CompressionType::MsZip);
+        for (key, _, _) in files {
+            folder.add_file(*key);
+        }
+        let mut writer = builder.build(Cursor::new(Vec::new())).unwrap();
+        let mut contents = files.iter().map(|(_, _, content)| content);
+        while let Some(mut file) = writer.next_file().unwrap() {
+            file.write_all(contents.next().unwrap()).unwrap();
+        }
+        let cabinet = writer.finish().unwrap().into_inner();
+
+        let mut package = Package::create(PackageType::Installer, Cursor::new(Vec::new())).unwrap();
+        package
+            .create_table(
+                "File",
+                vec![
+                    Column::build("File").primary_key().id_string(72),
+                    Column::build("FileName").string(255),
+                    Column::build("Sequence").int16(),
+                ],
+            )
+            .unwrap();
+        package
+            .insert_rows(files.iter().zip(1..).fold(
+                Insert::into("File"),
+                |insert, ((key, file_name, _), sequence)| {
+                    insert.row(vec![
+                        Value::from(*key),
+                        Value::from(*file_name),
+                        Value::Int(sequence),
+                    ])
+                },
+            ))
+            .unwrap();
+        package
+            .create_table(
+                "Media",
+                vec![
+                    Column::build("DiskId").primary_key().int16(),
+                    Column::build("LastSequence").int16(),
+                    Column::build("Cabinet").nullable().string(255),
+                ],
+            )
+            .unwrap();
+        package
+            .insert_rows(Insert::into("Media").row(vec![
+                Value::Int(1),
+                Value::Int(files.len() as i32),
+                Value::from("#cab1.cab"),
+            ]))
+            .unwrap();
+        package
+            .write_stream("cab1.cab")
+            .unwrap()
+            .write_all(&cabinet)
+            .unwrap();
+
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(&package.into_inner().unwrap().into_inner())
+            .unwrap();
+        f.rewind().unwrap();
+        f
+    }
+
+    #[test]
+    fn test_long_name() {
+        assert_eq!(long_name("TOOL~1.EXE|tool-cli.exe"), "tool-cli.exe");
+        assert_eq!(long_name("tool.exe"), "tool.exe");
+        assert!(is_exe("tool.EXE"));
+        assert!(!is_exe("README.txt"));
+        assert!(!is_exe("..\\tool.exe"));
+    }
+
+    #[test]
+    fn test_extract_msi() {
+        let files: &[(&str, &str, &[u8])] = &[
+            ("tool_exe", "TOOL~1.EXE|tool.exe", b"tool"),
+            ("readme", "README.txt", b"readme"),
+            ("helper_exe", "helper.EXE", b"helper"),
+            ("old_tool_exe", "tool.exe", b"old"),
+            ("escape_exe", "..\\escape.exe", b"escape"),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let extracted_files = do_extract_msi(
+            msi(files),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("tool.exe")));
+        assert!(extracted_files.has_file(Path::new("helper.EXE")));
+        assert!(!extracted_files.has_file(Path::new("README.txt")));
+        assert_eq!(fs::read(dir.path().join("tool.exe")).unwrap(), b"tool");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let mut vfs = InMemoryVfs::new();
+        do_extract_msi(
+            msi(files),
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+        assert_eq!(
+            vfs.get_file(Path::new("helper.EXE")).unwrap().as_ref(),
+            b"helper"
+        );
+    }
+
+    #[test]
+    fn test_extraction_limits() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(do_extract_msi(
+            msi(&[("tool_exe", "tool.exe", b"tool")]),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits {
+                max_size: 3,
+                ..Default::default()
+            }),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// Files up to this size are read into memory and written concurrently,
+/// larger ones are written by the decoder itself.
+pub(super) const MAX_PARALLEL_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Maximum number of bytes read into memory and waiting to be written.
+const MAX_PENDING_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Regular file to write, with the mode and mtime to set if any.
+pub(super) struct PendingFile {
+    pub(super) path: PathBuf,
+    pub(super) data: Vec<u8>,
+    pub(super) mode: Option<u32>,
+    pub(super) mtime: Option<u64>,
+}
+
+/// Write the regular files of an archive concurrently on the blocking pool,
+/// while the decoder keeps reading the archive.
+///
+/// Files are written in any order, so the caller must [`wait`] before
+/// creating anything depending on them, e.g. links or directories.
+///
+/// [`wait`]: ParallelWriter::wait
+pub(super) struct ParallelWriter {
+    handle: Handle,
+    tasks: Vec<JoinHandle<io::Result<()>>>,
+    paths: HashSet<PathBuf>,
+    pending_bytes: u64,
+}
+
+impl ParallelWriter {
+    /// Must be called inside of the tokio runtime, e.g. on its blocking pool.
+    pub(super) fn new() -> Self {
+        Self {
+            handle: Handle::current(),
+            tasks: Vec::new(),
+            paths: HashSet::new(),
+            pending_bytes: 0,
+        }
+    }
+
+    /// Return true if a write to `path` is pending.
+    pub(super) fn is_pending(&self, path: &Path) -> bool {
+        self.paths.contains(path)
+    }
+
+    /// Write `file` on the blocking pool, once its parent directory exists.
+    pub(super) fn write(&mut self, file: PendingFile) -> io::Result<()> {
+        let len = file.data.len() as u64;
+        // The same path may appear more than once in an archive, with the
+        // last one winning.
+        if self.is_pending(&file.path) || self.pending_bytes + len > MAX_PENDING_BYTES {
+            self.wait()?;
+        }
+
+        self.pending_bytes += len;
+        self.paths.insert(file.path.clone());
+        self.tasks
+            .push(self.handle.spawn_blocking(move || write_file(file)));
+
+        Ok(())
+    }
+
+    /// Wait for all of the pending writes.
+    pub(super) fn wait(&mut self) -> io::Result<()> {
+        self.paths.clear();
+        self.pending_bytes = 0;
+
+        let mut res = Ok(());
+        // Wait for every task even if one fails, so that no file is
+        // written once the extraction is over.
+        for task in self.tasks.drain(..) {
+            let task_res = self
+                .handle
+                .block_on(task)
+                .map_err(io::Error::other)
+                .and_then(|res| res);
+            res = res.and(task_res);
+        }
+        res
+    }
+}
+
+impl Drop for ParallelWriter {
+    fn drop(&mut self) {
+        // Do not leave files being written if the extraction fails.
+        let _ = self.wait();
+    }
+}
+
+fn write_file(file: PendingFile) -> io::Result<()> {
+    // Replace the existing file instead of writing through it, in case it
+    // is a hardlink or symlink.
+    match fs::remove_file(&file.path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => (),
+    }
+
+    let mut f = fs::File::create(&file.path)?;
+    f.write_all(&file.data)?;
+
+    // Same as tar, which does not preserve the mtime 0 since some tools
+    // treat it specially.
+    if let Some(mtime) = file.mtime {
+        f.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime.max(1)))?;
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = file.mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        f.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = file.mode;
+
+    Ok(())
+}
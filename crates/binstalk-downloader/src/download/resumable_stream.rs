@@ -0,0 +1,292 @@
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_util::{stream::FusedStream, FutureExt, Stream, StreamExt};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::DownloadError;
+use crate::remote::{
+    header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_RANGE, RANGE},
+    Client, Error as RemoteError, Method, Response, StatusCode, Url,
+};
+
+/// Number of times a truncated download is resumed before giving up.
+const MAX_RESUME_COUNT: u8 = 3;
+
+type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, RemoteError>> + Send + Sync>>;
+
+enum State {
+    Streaming(BodyStream),
+    /// The request is spawned since the future of it is not `Sync`.
+    Resuming(JoinHandle<Result<Response, RemoteError>>),
+    Done,
+}
+
+/// Body of a response with `Content-Length`, which resumes the download
+/// using a range request if the connection is closed before all the data
+/// is received, so that truncated data is never passed to the extracters.
+///
+/// The range request is the original request, with the same headers and
+/// sent to the url before any redirect, so that it is authenticated and
+/// redirected the same way.
+pub(super) struct ResumableStream {
+    client: Client,
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    expected: Option<u64>,
+    received: u64,
+    resume_count: u8,
+    state: State,
+}
+
+impl ResumableStream {
+    pub(super) fn new(response: Response) -> Self {
+        Self {
+            client: response.client().clone(),
+            method: response.method().clone(),
+            url: response.request_url().clone(),
+            headers: response.request_headers().clone(),
+            expected: response.content_length(),
+            received: 0,
+            resume_count: 0,
+            state: State::Streaming(Box::pin(response.bytes_stream())),
+        }
+    }
+
+    fn is_truncated(&self) -> bool {
+        self.expected
+            .is_some_and(|expected| self.received < expected)
+    }
+
+    fn truncated_error(&self) -> DownloadError {
+        DownloadError::Truncated {
+            url: Box::new(self.url.clone()),
+            expected: self.expected.unwrap_or_default(),
+            received: self.received,
+        }
+    }
+
+    /// Return false if the download cannot be resumed any more.
+    fn resume(&mut self) -> bool {
+        if self.resume_count >= MAX_RESUME_COUNT {
+            return false;
+        }
+        self.resume_count += 1;
+
+        warn!(
+            "Download of {} is truncated after {} bytes, resuming",
+            self.url, self.received
+        );
+
+        let mut headers = self.headers.clone();
+        headers.insert(
+            RANGE,
+            HeaderValue::try_from(format!("bytes={}-", self.received)).unwrap(),
+        );
+        // Content-Range is the range of the encoded body.
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+
+        let request = self
+            .client
+            .request(self.method.clone(), self.url.clone())
+            .headers(headers);
+        self.state = State::Resuming(tokio::spawn(request.send(false)));
+
+        true
+    }
+
+    /// Return true if `response` is the rest of the body.
+    fn is_resumed_response(&self, response: &Response) -> bool {
+        response.status() == StatusCode::PARTIAL_CONTENT
+            && response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("bytes "))
+                .and_then(|value| value.split_once('-'))
+                .and_then(|(start, _)| start.parse::<u64>().ok())
+                == Some(self.received)
+    }
+}
+
+impl Stream for ResumableStream {
+    type Item = Result<Bytes, DownloadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            match &mut this.state {
+                State::Streaming(stream) => match ready!(stream.poll_next_unpin(cx)) {
+                    Some(Ok(bytes)) => {
+                        this.received += bytes.len() as u64;
+                        break Poll::Ready(Some(Ok(bytes)));
+                    }
+                    Some(Err(err)) => {
+                        if !(this.is_truncated() && this.resume()) {
+                            this.state = State::Done;
+                            break Poll::Ready(Some(Err(err.into())));
+                        }
+                        debug!(?err, "Failed to read body");
+                    }
+                    None => {
+                        if this.is_truncated() {
+                            if !this.resume() {
+                                this.state = State::Done;
+                                break Poll::Ready(Some(Err(this.truncated_error())));
+                            }
+                        } else {
+                            this.state = State::Done;
+                            break Poll::Ready(None);
+                        }
+                    }
+                },
+                State::Resuming(handle) => match ready!(handle.poll_unpin(cx)) {
+                    Ok(Ok(response)) if this.is_resumed_response(&response) => {
+                        this.state = State::Streaming(Box::pin(response.bytes_stream()));
+                    }
+                    res => {
+                        match res {
+                            Ok(Ok(response)) => debug!(
+                                status = %response.status(),
+                                "Server does not support resuming download"
+                            ),
+                            Ok(Err(err)) => debug!(?err, "Failed to resume download"),
+                            Err(err) => debug!(?err, "Failed to resume download"),
+                        }
+                        this.state = State::Done;
+                        break Poll::Ready(Some(Err(this.truncated_error())));
+                    }
+                },
+                State::Done => break Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl FusedStream for ResumableStream {
+    fn is_terminated(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+}
+
+impl Drop for ResumableStream {
+    fn drop(&mut self) {
+        if let State::Resuming(handle) = &self.state {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_util::TryStreamExt;
+
+    use crate::remote::{
+        test_server::{client, response, serve, Request},
+        AuthScheme,
+    };
+
+    /// Serve `/a`, redirecting to `/b`, which is truncated after 5 bytes
+    /// unless the rest is requested.
+    fn handle(request: &Request, _: usize) -> Vec<u8> {
+        match (request.path.as_str(), request.header("range")) {
+            ("/a", _) => response("302 Found", &[("Location", "/b")], 0, b""),
+            ("/b", None) => response("200 OK", &[], 10, b"01234"),
+            ("/b", Some("bytes=5-")) => response(
+                "206 Partial Content",
+                &[("Content-Range", "bytes 5-9/10")],
+                5,
+                b"56789",
+            ),
+            _ => response("404 Not Found", &[], 0, b""),
+        }
+    }
+
+    async fn download(client: &Client, url: &Url) -> Result<Vec<u8>, DownloadError> {
+        let response = client
+            .get(url.join("a").unwrap())
+            .header("x-test", "1")
+            .send(true)
+            .await
+            .unwrap();
+
+        ResumableStream::new(response)
+            .try_fold(Vec::new(), |mut data, bytes| async move {
+                data.extend_from_slice(&bytes);
+                Ok(data)
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let (url, requests) = serve(handle).await;
+        let client = client();
+        client.add_auth_for_domain("127.0.0.1", AuthScheme::Bearer, "secret");
+
+        assert_eq!(download(&client, &url).await.unwrap(), b"0123456789");
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<_> = requests.iter().map(|req| req.path.as_str()).collect();
+        assert_eq!(paths, ["/a", "/b", "/a", "/b"]);
+
+        // The download is resumed with the same request, following the same
+        // redirect.
+        for request in &requests[2..] {
+            assert_eq!(request.header("range"), Some("bytes=5-"));
+            assert_eq!(request.header("accept-encoding"), Some("identity"));
+            assert_eq!(request.header("x-test"), Some("1"));
+            assert_eq!(request.header("authorization"), Some("Bearer secret"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_unsupported() {
+        let (url, requests) = serve(|request, i| match i {
+            // The range is ignored.
+            2.. => response("200 OK", &[], 10, b"0123456789"),
+            _ => handle(request, i),
+        })
+        .await;
+
+        assert!(matches!(
+            download(&client(), &url).await.unwrap_err(),
+            DownloadError::Truncated {
+                expected: 10,
+                received: 5,
+                ..
+            }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_resume_count() {
+        let (url, requests) = serve(|request, _| match request.header("range") {
+            // Every resumed download, which is not redirected, is truncated
+            // again.
+            Some(_) => response(
+                "206 Partial Content",
+                &[("Content-Range", "bytes 5-9/10")],
+                5,
+                b"",
+            ),
+            None => handle(request, 0),
+        })
+        .await;
+
+        assert!(download(&client(), &url).await.is_err());
+        assert_eq!(
+            requests.lock().unwrap().len(),
+            2 + MAX_RESUME_COUNT as usize
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Extraction of the binaries of rpm packages, which consist of a lead, a
+//! signature header and a header followed by a compressed cpio archive.
+
+use std::{
+    io::{self, BufRead, Read},
+    path::Path,
+};
+
+use super::{
+    cpio_extraction::for_each_entry, deb_extraction::bin_name, extracter::decompress,
+    extraction_limits::LimitTracker, DownloadError, ExtractedFiles, ExtractionSink, PkgFmt,
+    PkgFmtDecomposed, TarBasedFmt,
+};
+
+const LEAD_MAGIC: &[u8] = b"\xed\xab\xee\xdb";
+const LEAD_LEN: usize = 96;
+
+const HEADER_MAGIC: &[u8] = b"\x8e\xad\xe8\x01";
+/// Size of the header intro, i.e. magic, reserved bytes, number of index
+/// entries and size of the data.
+const HEADER_INTRO_LEN: usize = 16;
+/// Size of an index entry of the header.
+const INDEX_ENTRY_LEN: u64 = 16;
+/// Size of the longest magic bytes of the compressions of the payload, i.e.
+/// the one of xz.
+const PAYLOAD_MAGIC_LEN: usize = 6;
+
+fn invalid(msg: &'static str) -> DownloadError {
+    DownloadError::InvalidRpm(msg)
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(len), &mut io::sink())?;
+    Ok(())
+}
+
+/// Skip a header, return its size.
+fn skip_header(reader: &mut impl Read) -> Result<u64, DownloadError> {
+    let mut intro = [0; HEADER_INTRO_LEN];
+    reader.read_exact(&mut intro)?;
+    if &intro[..4] != HEADER_MAGIC {
+        return Err(invalid("invalid header magic"));
+    }
+
+    let index_len = u32::from_be_bytes(intro[8..12].try_into().unwrap());
+    let data_len = u32::from_be_bytes(intro[12..16].try_into().unwrap());
+    let len = u64::from(index_len) * INDEX_ENTRY_LEN + u64::from(data_len);
+    skip(reader, len)?;
+
+    Ok(HEADER_INTRO_LEN as u64 + len)
+}
+
+/// Return the decompressed cpio payload of the rpm package read from
+/// `reader`.
+fn payload(
+    mut reader: impl BufRead + 'static,
+    max_decoder_memory: u64,
+) -> Result<Box<dyn Read>, DownloadError> {
+    let mut lead = [0; LEAD_LEN];
+    reader.read_exact(&mut lead)?;
+    if !lead.starts_with(LEAD_MAGIC) {
+        return Err(invalid("not an rpm package"));
+    }
+
+    // The signature header is padded to a multiple of 8 bytes.
+    let signature_len = skip_header(&mut reader)?;
+    skip(&mut reader, (8 - signature_len % 8) % 8)?;
+    skip_header(&mut reader)?;
+
+    // Read the magic bytes of the compression, which may be split across
+    // the chunks of the download, then put them back.
+    let mut magic = Vec::with_capacity(PAYLOAD_MAGIC_LEN);
+    (&mut reader)
+        .take(PAYLOAD_MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+
+    let fmt = match PkgFmt::sniff(&magic).map(PkgFmt::decompose) {
+        Some(PkgFmtDecomposed::Tar(fmt)) => fmt,
+        // The payload is not compressed.
+        _ => TarBasedFmt::Tar,
+    };
+
+    Ok(decompress(
+        io::Cursor::new(magic).chain(reader),
+        fmt,
+        max_decoder_memory,
+    )?)
+}
+
+fn for_each_bin(
+    reader: impl BufRead + 'static,
+    max_decoder_memory: u64,
+    mut each: impl FnMut(&Path, &mut dyn Read) -> io::Result<()>,
+) -> Result<(), DownloadError> {
+    for_each_entry(payload(reader, max_decoder_memory)?, |entry, data| {
+        if entry.is_file() {
+            if let Some(name) = bin_name(Path::new(&entry.name)) {
+                each(Path::new(name), data)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+pub(super) fn do_extract_rpm(
+    reader: impl BufRead + 'static,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_bin(reader, limits.max_decoder_memory(), |name, reader| {
+        limits.add_entry()?;
+        sink.write_file(name, &mut limits.reader(reader))?;
+        extracted_files.add_file(name);
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::{
+        cpio_extraction::{newc_entry, TRAILER},
+        ExtractionLimits, FsSink, InMemoryVfs,
+    };
+
+    fn header(index_len: u32, data_len: u32) -> Vec<u8> {
+        let mut header = HEADER_MAGIC.to_vec();
+        header.extend([0; 4]);
+        header.extend(index_len.to_be_bytes());
+        header.extend(data_len.to_be_bytes());
+        header.resize(header.len() + (index_len * 16 + data_len) as usize, 0xff);
+        header
+    }
+
+    fn rpm() -> Vec<u8> {
+        let mut cpio = Vec::new();
+        newc_entry(&mut cpio, "./usr/bin", 0o40755, b"");
+        newc_entry(&mut cpio, "./usr/bin/hello", 0o100755, b"hello");
+        newc_entry(&mut cpio, "./usr/local/bin/world", 0o100755, b"world!");
+        newc_entry(&mut cpio, "./usr/share/doc/README", 0o100644, b"readme");
+        newc_entry(&mut cpio, "./usr/bin/link", 0o120777, b"hello");
+        newc_entry(&mut cpio, TRAILER, 0, b"");
+
+        let mut rpm = LEAD_MAGIC.to_vec();
+        rpm.resize(LEAD_LEN, 0);
+        // 16 + 16 + 3 bytes, padded to 40 bytes.
+        rpm.extend(header(1, 3));
+        rpm.extend([0; 5]);
+        rpm.extend(header(2, 7));
+        rpm.extend(zstd::encode_all(&cpio[..], 0).unwrap());
+        rpm
+    }
+
+    #[test]
+    fn test_extract_rpm() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted_files = do_extract_rpm(
+            io::Cursor::new(rpm()),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert!(extracted_files.has_file(Path::new("world")));
+        assert!(!extracted_files.has_file(Path::new("README")));
+        assert!(!extracted_files.has_file(Path::new("link")));
+        assert_eq!(std::fs::read(dir.path().join("world")).unwrap(), b"world!");
+
+        let mut vfs = InMemoryVfs::new();
+        let extracted_files = do_extract_rpm(
+            io::Cursor::new(rpm()),
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(vfs.get_file(Path::new("hello")).unwrap().as_ref(), b"hello");
+
+        // The download is read one byte at a time.
+        let mut vfs = InMemoryVfs::new();
+        let extracted_files = do_extract_rpm(
+            io::BufReader::with_capacity(1, io::Cursor::new(rpm())),
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("world")));
+        assert_eq!(
+            vfs.get_file(Path::new("world")).unwrap().as_ref(),
+            b"world!"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rpm() {
+        assert!(matches!(
+            payload(&[0; LEAD_LEN][..], u64::MAX),
+            Err(DownloadError::InvalidRpm(_))
+        ));
+    }
+}
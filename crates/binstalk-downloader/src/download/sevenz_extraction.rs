@@ -0,0 +1,143 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader};
+
+use super::{
+    extraction_limits::LimitTracker, extraction_sink::create_parent_dir, DownloadError,
+    ExtractedFiles, ExtractionSink,
+};
+
+/// Return the relative path of the entry, or `None` if it escapes the
+/// destination.
+fn sanitized_name(name: &str) -> Option<PathBuf> {
+    // 7z stores paths with `/` on every platform, but some archivers
+    // write `\` instead.
+    let name = name.replace('\\', "/");
+    let mut path = PathBuf::new();
+
+    for component in Path::new(&name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => (),
+            Component::Prefix(..) | Component::RootDir | Component::ParentDir => return None,
+        }
+    }
+
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+fn for_each_entry(
+    mut f: File,
+    mut each: impl FnMut(&Path, &SevenZArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> Result<(), DownloadError> {
+    let len = f.seek(SeekFrom::End(0))?;
+    f.rewind()?;
+
+    SevenZReader::new(f, len, Password::empty())?.for_each_entries(|entry, reader| {
+        match sanitized_name(entry.name()) {
+            Some(name) => each(&name, entry, reader)?,
+            // Entries of a solid archive share one stream, so it has to be
+            // consumed even if the entry is skipped.
+            None => {
+                io::copy(reader, &mut io::sink())?;
+            }
+        }
+        Ok(true)
+    })?;
+
+    Ok(())
+}
+
+pub(super) fn do_extract_7z(
+    f: File,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    for_each_entry(f, |name, entry, reader| {
+        limits.add_entry()?;
+
+        if entry.is_directory() {
+            sink.create_dir_all(name)?;
+            extracted_files.add_dir(name);
+        } else {
+            create_parent_dir(sink, name)?;
+            sink.write_file(name, &mut limits.reader(reader))?;
+            extracted_files.add_file(name);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(extracted_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        super::{ExtractionLimits, FsSink},
+        *,
+    };
+
+    use std::{fs, io::Write};
+
+    /// Solid archive of `dir/bin`, `../evil` and `README`.
+    const ARCHIVE: &[u8] = &[
+        0x37, 0x7a, 0xbc, 0xaf, 0x27, 0x1c, 0x00, 0x02, 0x3c, 0x60, 0xad, 0xeb, 0x90, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x2e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x8e,
+        0xbc, 0xf7, 0x01, 0x00, 0x07, 0x62, 0x69, 0x6e, 0x73, 0x74, 0x61, 0x6c, 0x6c, 0x00, 0x01,
+        0x00, 0x03, 0x65, 0x76, 0x69, 0x6c, 0x00, 0x01, 0x00, 0x05, 0x72, 0x65, 0x61, 0x64, 0x6d,
+        0x65, 0x00, 0x00, 0x00, 0x81, 0x33, 0x07, 0xae, 0x4f, 0x9f, 0x2e, 0x1e, 0x83, 0x32, 0x8d,
+        0x55, 0x83, 0x8f, 0x42, 0xf9, 0xe9, 0x82, 0xe3, 0x0b, 0x50, 0xda, 0x27, 0x90, 0x27, 0x0d,
+        0x94, 0x23, 0xbb, 0x85, 0x02, 0xb7, 0xd3, 0xf0, 0x67, 0x07, 0x6b, 0xad, 0x6d, 0x66, 0x6f,
+        0xa5, 0x9e, 0xe0, 0xe9, 0xe8, 0xdb, 0xbe, 0x91, 0x23, 0x29, 0x97, 0x7d, 0x91, 0x2f, 0x04,
+        0x30, 0x52, 0x7d, 0xc8, 0xf0, 0x59, 0x6e, 0x15, 0x46, 0xab, 0x46, 0xfc, 0xc9, 0x60, 0x50,
+        0xad, 0x16, 0x33, 0xf6, 0xa0, 0xe8, 0xfe, 0xe9, 0xcd, 0xfa, 0x89, 0xa2, 0x12, 0xea, 0xa8,
+        0xfa, 0xe6, 0xb3, 0xca, 0x68, 0x58, 0x75, 0x52, 0x39, 0xe7, 0x33, 0x7b, 0x49, 0x55, 0x41,
+        0xae, 0x6e, 0xac, 0x51, 0x85, 0xa3, 0x5e, 0xc7, 0xe0, 0x27, 0x00, 0x17, 0x06, 0x1e, 0x01,
+        0x09, 0x72, 0x0a, 0x01, 0xc2, 0x55, 0x7b, 0x01, 0x00, 0x07, 0x0b, 0x01, 0x00, 0x01, 0x23,
+        0x03, 0x01, 0x01, 0x05, 0x5d, 0x00, 0x00, 0x80, 0x00, 0x0c, 0x80, 0x8b, 0x0a, 0x01, 0x96,
+        0x29, 0x57, 0x93, 0x00, 0x08, 0x0d, 0x01, 0x09, 0x0a, 0x01, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_sanitized_name() {
+        assert_eq!(
+            sanitized_name("dir/./bin"),
+            Some(PathBuf::from("dir").join("bin"))
+        );
+        assert_eq!(
+            sanitized_name("dir\\bin.exe"),
+            Some(PathBuf::from("dir").join("bin.exe"))
+        );
+        assert_eq!(sanitized_name("../bin"), None);
+        assert_eq!(sanitized_name("/bin"), None);
+        assert_eq!(sanitized_name("."), None);
+    }
+
+    #[test]
+    fn test_extract_7z() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(ARCHIVE).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("dst");
+
+        let extracted_files = do_extract_7z(
+            f,
+            &mut FsSink::new(&dst),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(&Path::new("dir").join("bin")));
+        assert!(extracted_files.has_file(Path::new("README")));
+        assert_eq!(fs::read(dst.join("dir").join("bin")).unwrap(), b"binstall");
+        assert_eq!(fs::read(dst.join("README")).unwrap(), b"readme");
+        assert!(!dir.path().join("evil").exists());
+    }
+}
@@ -1,68 +1,715 @@
 use std::{
-    fs::{create_dir_all, File},
-    io,
-    path::Path,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
 };
 
-use cfg_if::cfg_if;
+use flate2::{bufread::DeflateDecoder, CrcReader};
 use rc_zip_sync::{rc_zip::parse::EntryKind, ReadZip};
+use tracing::{debug, info};
 
-use super::{DownloadError, ExtractedFiles};
+use super::{
+    extraction_limits::{LimitTracker, LimitedReader},
+    extraction_sink::create_parent_dir,
+    DownloadError, ExtractedFiles, ExtractionSink,
+};
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+
+const FLAG_ENCRYPTED: u16 = 1;
+/// The crc and sizes of the entry follow its data instead of being in its
+/// local file header.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const ZIP64_SIZE_MARKER: u32 = u32::MAX;
+
+const MIB: u64 = 1024 * 1024;
+/// Entries at least this large are reported as they are extracted, so that
+/// extracting multi-gigabyte archives does not appear hung.
+const LARGE_ENTRY_SIZE: u64 = 64 * MIB;
+/// Interval of the reports of the progress of large entries.
+const PROGRESS_INTERVAL: u64 = 256 * MIB;
+
+/// Entries up to this size, the `PATH_MAX` of linux, are kept in memory
+/// while streaming since they may be symlinks holding their targets.
+const MAX_SYMLINK_TARGET_LEN: u64 = 4096;
+
+/// Hosts whose external attributes of the entries hold unix modes.
+const HOST_UNIX: u8 = 3;
+const HOST_OSX: u8 = 19;
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+enum StreamedZip {
+    Extracted(ExtractedFiles),
+    /// An entry needs the central directory of the archive to be extracted.
+    NotStreamable,
+}
+
+/// Reader writing every byte read to `spool`, so that the archive can still
+/// be extracted from its central directory once an entry which cannot be
+/// streamed is found.
+struct SpoolingReader<R> {
+    inner: R,
+    spool: BufWriter<File>,
+}
+
+impl<R: Read> SpoolingReader<R> {
+    /// Spool the rest of the archive and return the spool.
+    fn finish(mut self) -> io::Result<File> {
+        io::copy(&mut self.inner, &mut self.spool)?;
+        self.spool
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+}
+
+impl<R: Read> Read for SpoolingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.spool.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Extract the zip archive read from `reader`, while it is read if possible.
+///
+/// The archive is spooled to `spool` as it is read, and extracted from it
+/// once fully read if one of its entries cannot be extracted without the
+/// central directory.
+pub(super) fn do_extract_zip_from_stream(
+    reader: impl Read,
+    spool: File,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut reader = BufReader::new(SpoolingReader {
+        inner: reader,
+        spool: BufWriter::new(spool),
+    });
+
+    match do_extract_zip_streaming(&mut reader, sink, limits)? {
+        StreamedZip::Extracted(extracted_files) => Ok(extracted_files),
+        StreamedZip::NotStreamable => {
+            debug!("Zip archive needs its central directory, downloading it to tempfile");
+
+            // The bytes buffered but not consumed are already spooled.
+            let spool = reader.into_inner().finish()?;
+            limits.reset();
+            do_extract_zip(spool, sink, limits)
+        }
+    }
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(reader: &mut impl Read, len: u16) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len.into()];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(len), &mut io::sink())?;
+    Ok(())
+}
+
+/// Path of the entry relative to the extraction directory, `None` if it
+/// points outside of it.
+pub(super) fn sanitized_name(name: &[u8]) -> Option<PathBuf> {
+    let name = String::from_utf8_lossy(name);
+    let path = name
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .map(|component| (component != ".." && !component.contains(':')).then_some(component))
+        .collect::<Option<PathBuf>>()?;
+
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+struct LocalFileHeader {
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    name: Vec<u8>,
+}
+
+impl LocalFileHeader {
+    /// Parse the header following its signature.
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let _version_needed = read_u16(reader)?;
+        let flags = read_u16(reader)?;
+        let method = read_u16(reader)?;
+        let _modified = read_u32(reader)?;
+        let crc32 = read_u32(reader)?;
+        let compressed_size = read_u32(reader)?;
+        let uncompressed_size = read_u32(reader)?;
+        let name_len = read_u16(reader)?;
+        let extra_len = read_u16(reader)?;
+        let name = read_bytes(reader, name_len)?;
+        let mut extra = &read_bytes(reader, extra_len)?[..];
+
+        let mut header = Self {
+            flags,
+            method,
+            crc32,
+            compressed_size: compressed_size.into(),
+            uncompressed_size: uncompressed_size.into(),
+            name,
+        };
+
+        // Sizes which do not fit in 32 bits are in the zip64 extra field,
+        // leaving a marker in the header. Unlike in the central directory,
+        // the field of the local header holds both sizes if either of them
+        // is too large.
+        let is_zip64 =
+            uncompressed_size == ZIP64_SIZE_MARKER || compressed_size == ZIP64_SIZE_MARKER;
+
+        while is_zip64 && extra.len() >= 4 {
+            let id = read_u16(&mut extra)?;
+            let len = read_u16(&mut extra)?;
+            if extra.len() < len.into() {
+                break;
+            }
+            let (mut field, rest) = extra.split_at(len.into());
+            extra = rest;
+
+            if id == ZIP64_EXTRA_FIELD_ID {
+                header.uncompressed_size = read_u64(&mut field)?;
+                header.compressed_size = read_u64(&mut field)?;
+            }
+        }
+
+        Ok(header)
+    }
+
+    fn is_streamable(&self) -> bool {
+        self.flags & (FLAG_ENCRYPTED | FLAG_DATA_DESCRIPTOR) == 0
+            && matches!(self.method, METHOD_STORED | METHOD_DEFLATED)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/") || self.name.ends_with(b"\\")
+    }
+}
+
+/// Return the name of the entry if it is a symlink.
+fn read_central_directory_header(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let version_made_by = read_u16(reader)?;
+    skip(reader, 22)?;
+    let name_len = read_u16(reader)?;
+    let extra_len = read_u16(reader)?;
+    let comment_len = read_u16(reader)?;
+    skip(reader, 4)?;
+    let external_attrs = read_u32(reader)?;
+    skip(reader, 4)?;
+    let name = read_bytes(reader, name_len)?;
+    skip(reader, u64::from(extra_len) + u64::from(comment_len))?;
+
+    let is_unix = matches!((version_made_by >> 8) as u8, HOST_UNIX | HOST_OSX);
+    let is_symlink = is_unix && (external_attrs >> 16) & S_IFMT == S_IFLNK;
+
+    Ok(is_symlink.then_some(name))
+}
+
+/// Extract the zip archive from its local file headers as it is read.
+///
+/// Each entry must be neither encrypted nor followed by a data descriptor,
+/// and stored or deflated, which can differ between the entries of an
+/// archive, e.g. Go's `archive/zip` only writes data descriptors after
+/// files. [`StreamedZip::NotStreamable`] is returned on the first entry
+/// which is not, the entries before it being already extracted.
+///
+/// Symlinks are only known once the central directory is read at the end of
+/// the stream, so they are extracted as files holding their targets and then
+/// replaced.
+fn do_extract_zip_streaming(
+    reader: &mut impl BufRead,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<StreamedZip, DownloadError> {
+    if read_u32(reader).ok() != Some(LOCAL_FILE_HEADER_SIGNATURE) {
+        return Ok(StreamedZip::NotStreamable);
+    }
 
-pub(super) fn do_extract_zip(f: File, dir: &Path) -> Result<ExtractedFiles, DownloadError> {
     let mut extracted_files = ExtractedFiles::new();
+    let mut symlink_targets = HashMap::new();
 
-    for entry in f.read_zip()?.entries() {
-        let Some(name) = entry.sanitized_name().map(Path::new) else {
-            continue;
+    let mut signature = loop {
+        let Ok(entry) = LocalFileHeader::read(reader) else {
+            return Ok(StreamedZip::NotStreamable);
         };
-        let path = dir.join(name);
+        if !entry.is_streamable() {
+            return Ok(StreamedZip::NotStreamable);
+        }
+        limits.add_entry()?;
+
+        let mut data = (&mut *reader).take(entry.compressed_size);
 
-        let do_extract_file = || {
-            let mut entry_writer = File::create(&path)?;
-            let mut entry_reader = entry.reader();
-            io::copy(&mut entry_reader, &mut entry_writer)?;
+        match sanitized_name(&entry.name) {
+            Some(name) if !entry.is_dir() => {
+                create_parent_dir(sink, &name)?;
 
-            Ok::<_, io::Error>(())
+                debug!("Extracting `{}`", name.display());
+
+                let expected_size = entry.uncompressed_size;
+                let (crc32, size, content) = if entry.method == METHOD_DEFLATED {
+                    let reader = DeflateDecoder::new(&mut data);
+                    write_entry(reader, sink, &name, expected_size, limits)?
+                } else {
+                    write_entry(&mut data, sink, &name, expected_size, limits)?
+                };
+                if crc32 != entry.crc32 || size != expected_size {
+                    return Err(DownloadError::InvalidZip("entry is corrupted"));
+                }
+
+                extracted_files.add_file(&name);
+                if let Some(content) = content {
+                    symlink_targets.insert(name, content);
+                }
+            }
+            _ => (),
+        }
+        skip(&mut data, u64::MAX)?;
+
+        let signature = read_u32(reader)?;
+        if signature != LOCAL_FILE_HEADER_SIGNATURE {
+            break signature;
+        }
+    };
+
+    while signature == CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+        if let Some(name) = read_central_directory_header(reader)? {
+            if let Some((name, target)) = sanitized_name(&name)
+                .and_then(|name| symlink_targets.remove(&name).map(|target| (name, target)))
+            {
+                let target = String::from_utf8(target)
+                    .map_err(|_| DownloadError::InvalidZip("invalid symlink target"))?;
+                sink.create_symlink(&name, Path::new(&target))?;
+            }
+        }
+        signature = read_u32(reader)?;
+    }
+    skip(reader, u64::MAX)?;
+
+    Ok(StreamedZip::Extracted(extracted_files))
+}
+
+/// Write entry `name` read from `reader` to `sink`, and return its crc32
+/// and size once decompressed, along with its content if it may be the
+/// target of a symlink.
+fn write_entry(
+    reader: impl Read,
+    sink: &mut dyn ExtractionSink,
+    name: &Path,
+    size: u64,
+    limits: &mut LimitTracker,
+) -> io::Result<(u32, u64, Option<Vec<u8>>)> {
+    let mut reader = CrcReader::new(entry_reader(reader, name, size, limits));
+
+    let content = if size <= MAX_SYMLINK_TARGET_LEN {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        sink.write_file(name, &mut &content[..])?;
+        Some(content)
+    } else {
+        sink.write_file(name, &mut reader)?;
+        None
+    };
+
+    Ok((reader.crc().sum(), reader.get_ref().read, content))
+}
+
+/// Return the reader of the content of entry `name`, which is `size` bytes
+/// long once decompressed, reporting the progress of large entries.
+///
+/// `size` is only used for reporting, the bytes actually decompressed are
+/// counted against `limits`.
+fn entry_reader<'a, R: Read>(
+    reader: R,
+    name: &'a Path,
+    size: u64,
+    limits: &'a mut LimitTracker,
+) -> ProgressReader<'a, LimitedReader<'a, R>> {
+    if size >= LARGE_ENTRY_SIZE {
+        info!("Extracting `{}` ({} MiB)", name.display(), size / MIB);
+    }
+
+    ProgressReader {
+        inner: limits.reader(reader),
+        name,
+        size,
+        read: 0,
+        reported: 0,
+    }
+}
+
+struct ProgressReader<'a, R> {
+    inner: R,
+    name: &'a Path,
+    size: u64,
+    read: u64,
+    reported: u64,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+
+        if self.size >= LARGE_ENTRY_SIZE && self.read - self.reported >= PROGRESS_INTERVAL {
+            self.reported = self.read;
+            info!(
+                "Extracted {} of {} MiB of `{}`",
+                self.read / MIB,
+                self.size / MIB,
+                self.name.display()
+            );
+        }
+
+        Ok(n)
+    }
+}
+
+/// Extract the zip archive `f`, including zip64 archives larger than 4 GiB
+/// or with more than 65535 entries, whose sizes and counts are read from
+/// the zip64 records by [`ReadZip`].
+fn do_extract_zip(
+    f: File,
+    sink: &mut dyn ExtractionSink,
+    limits: &mut LimitTracker,
+) -> Result<ExtractedFiles, DownloadError> {
+    let mut extracted_files = ExtractedFiles::new();
+
+    let archive = f.read_zip()?;
+    let total = archive.entries().count();
+
+    for (index, entry) in archive.entries().enumerate() {
+        limits.add_entry()?;
+        let Some(name) = entry.sanitized_name().map(Path::new) else {
+            continue;
         };
 
-        let parent = path
-            .parent()
-            .expect("all full entry paths should have parent paths");
-        create_dir_all(parent)?;
+        debug!(
+            "Extracting entry {}/{total} `{}`",
+            index + 1,
+            name.display()
+        );
 
         match entry.kind() {
             EntryKind::Symlink => {
-                extracted_files.add_file(name);
-                cfg_if! {
-                    if #[cfg(windows)] {
-                        do_extract_file()?;
-                    } else {
-                        use std::{fs, io::Read};
-
-                        match fs::symlink_metadata(&path) {
-                            Ok(metadata) if metadata.is_file() => fs::remove_file(&path)?,
-                            _ => (),
-                        }
-
-                        let mut src = String::new();
-                        entry.reader().read_to_string(&mut src)?;
-
-                        // validate pointing path before creating a symbolic link
-                        if src.contains("..") {
-                            continue;
-                        }
-                        std::os::unix::fs::symlink(src, &path)?;
-                    }
+                create_parent_dir(sink, name)?;
+
+                let mut target = String::new();
+                entry.reader().read_to_string(&mut target)?;
+
+                if sink.create_symlink(name, Path::new(&target))? {
+                    extracted_files.add_file(name);
                 }
             }
-            EntryKind::Directory => (),
+            EntryKind::Directory => {
+                sink.create_dir_all(name)?;
+                extracted_files.add_dir(name);
+            }
             EntryKind::File => {
+                create_parent_dir(sink, name)?;
+
+                let mut reader =
+                    entry_reader(entry.reader(), name, entry.uncompressed_size, limits);
+                sink.write_file(name, &mut reader)?;
                 extracted_files.add_file(name);
-                do_extract_file()?;
             }
         }
     }
 
     Ok(extracted_files)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Write};
+
+    use flate2::{write::DeflateEncoder, Compression, Crc};
+    use tempfile::tempdir;
+
+    use super::{
+        super::{ExtractionLimits, FsSink, InMemoryVfs},
+        *,
+    };
+
+    #[derive(Default)]
+    struct ZipBuilder {
+        data: Vec<u8>,
+        central_directory: Vec<u8>,
+        entries: u16,
+        /// Store the sizes of the entries in zip64 extra fields.
+        zip64: bool,
+    }
+
+    impl ZipBuilder {
+        fn add(&mut self, name: &str, flags: u16, method: u16, mode: u32, content: &[u8]) {
+            let mut crc = Crc::new();
+            crc.update(content);
+
+            let compressed = if method == METHOD_DEFLATED {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                content.to_vec()
+            };
+
+            let mut header = Vec::new();
+            header.extend(20_u16.to_le_bytes());
+            header.extend(flags.to_le_bytes());
+            header.extend(method.to_le_bytes());
+            header.extend(0_u32.to_le_bytes());
+            header.extend(crc.sum().to_le_bytes());
+
+            let mut extra = Vec::new();
+            if self.zip64 {
+                header.extend(ZIP64_SIZE_MARKER.to_le_bytes());
+                header.extend(ZIP64_SIZE_MARKER.to_le_bytes());
+                extra.extend(ZIP64_EXTRA_FIELD_ID.to_le_bytes());
+                extra.extend(16_u16.to_le_bytes());
+                extra.extend((content.len() as u64).to_le_bytes());
+                extra.extend((compressed.len() as u64).to_le_bytes());
+            } else {
+                header.extend((compressed.len() as u32).to_le_bytes());
+                header.extend((content.len() as u32).to_le_bytes());
+            }
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend((extra.len() as u16).to_le_bytes());
+
+            let offset = self.data.len() as u32;
+            self.data.extend(LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+            self.data.extend(&header);
+            self.data.extend(name.as_bytes());
+            self.data.extend(&extra);
+            self.data.extend(&compressed);
+
+            let cd = &mut self.central_directory;
+            cd.extend(CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes());
+            cd.extend((u16::from(HOST_UNIX) << 8 | 20).to_le_bytes());
+            cd.extend(&header);
+            cd.extend([0; 6]);
+            cd.extend((mode << 16).to_le_bytes());
+            cd.extend(offset.to_le_bytes());
+            cd.extend(name.as_bytes());
+            cd.extend(&extra);
+
+            self.entries += 1;
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            let offset = self.data.len() as u32;
+            self.data.extend(&self.central_directory);
+            self.data.extend(0x06054b50_u32.to_le_bytes());
+            self.data.extend([0; 4]);
+            self.data.extend(self.entries.to_le_bytes());
+            self.data.extend(self.entries.to_le_bytes());
+            self.data
+                .extend((self.central_directory.len() as u32).to_le_bytes());
+            self.data.extend(offset.to_le_bytes());
+            self.data.extend(0_u16.to_le_bytes());
+            self.data
+        }
+    }
+
+    #[test]
+    fn test_extract_zip_streaming() {
+        let mut zip = ZipBuilder::default();
+        zip.add("tool/", 0, METHOD_STORED, 0o40755, b"");
+        zip.add("tool/hello", 0, METHOD_STORED, 0o100755, b"hello");
+        zip.add(
+            "tool/world",
+            0,
+            METHOD_DEFLATED,
+            0o100644,
+            &b"world".repeat(100),
+        );
+        zip.add("tool/link", 0, METHOD_STORED, 0o120777, b"hello");
+        zip.add("../escape", 0, METHOD_STORED, 0o100644, b"escape");
+        let zip = zip.finish();
+
+        let dir = tempdir().unwrap();
+        let StreamedZip::Extracted(extracted_files) = do_extract_zip_streaming(
+            &mut &zip[..],
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap() else {
+            panic!("zip archive should be streamable");
+        };
+
+        assert_eq!(fs::read(dir.path().join("tool/hello")).unwrap(), b"hello");
+        assert_eq!(
+            fs::read(dir.path().join("tool/world")).unwrap(),
+            b"world".repeat(100)
+        );
+        assert!(extracted_files.has_file(Path::new("tool/link")));
+        assert!(!extracted_files.has_file(Path::new("escape")));
+        assert!(!dir.path().parent().unwrap().join("escape").exists());
+
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dir.path().join("tool/link")).unwrap(),
+            Path::new("hello")
+        );
+
+        // Symlinks are kept as files holding their targets in sinks
+        // without symlinks.
+        let mut vfs = InMemoryVfs::new();
+        do_extract_zip_streaming(
+            &mut &zip[..],
+            &mut vfs,
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+        assert_eq!(
+            vfs.get_file(Path::new("tool/link")).unwrap().as_ref(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_extract_zip64_streaming() {
+        let mut zip = ZipBuilder {
+            zip64: true,
+            ..Default::default()
+        };
+        zip.add("hello", 0, METHOD_STORED, 0o100755, b"hello");
+        zip.add("world", 0, METHOD_DEFLATED, 0o100644, &b"world".repeat(100));
+        let zip = zip.finish();
+
+        let dir = tempdir().unwrap();
+        let StreamedZip::Extracted(extracted_files) = do_extract_zip_streaming(
+            &mut &zip[..],
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap() else {
+            panic!("zip64 archive should be streamable");
+        };
+
+        assert!(extracted_files.has_file(Path::new("hello")));
+        assert_eq!(fs::read(dir.path().join("hello")).unwrap(), b"hello");
+        assert_eq!(
+            fs::read(dir.path().join("world")).unwrap(),
+            b"world".repeat(100)
+        );
+    }
+
+    #[test]
+    fn test_extract_zip_not_streamable() {
+        // Layout of Go's `archive/zip`, whose directories are followed by
+        // files with data descriptors.
+        let mut zip = ZipBuilder::default();
+        zip.add("tool/", 0, METHOD_STORED, 0o40755, b"");
+        zip.add(
+            "tool/hello",
+            FLAG_DATA_DESCRIPTOR,
+            METHOD_DEFLATED,
+            0o100755,
+            b"hello",
+        );
+        zip.add("tool/link", 0, METHOD_STORED, 0o120777, b"hello");
+        let zip = zip.finish();
+
+        let dir = tempdir().unwrap();
+        assert!(matches!(
+            do_extract_zip_streaming(
+                &mut &zip[..],
+                &mut FsSink::new(dir.path()),
+                &mut LimitTracker::new(ExtractionLimits::default()),
+            )
+            .unwrap(),
+            StreamedZip::NotStreamable
+        ));
+
+        let dir = tempdir().unwrap();
+        let extracted_files = do_extract_zip_from_stream(
+            &zip[..],
+            tempfile::tempfile().unwrap(),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("tool/hello")));
+        assert_eq!(fs::read(dir.path().join("tool/hello")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_extract_zip_symlink_outside() {
+        let mut zip = ZipBuilder::default();
+        zip.add("tool/hello", 0, METHOD_STORED, 0o100755, b"hello");
+        zip.add("tool/up", 0, METHOD_STORED, 0o120777, b"../hello");
+        zip.add("tool/absolute", 0, METHOD_STORED, 0o120777, b"/etc/passwd");
+        zip.add("tool/escape", 0, METHOD_STORED, 0o120777, b"../../hello");
+        let zip = zip.finish();
+
+        let dir = tempdir().unwrap();
+        do_extract_zip_from_stream(
+            &zip[..],
+            tempfile::tempfile().unwrap(),
+            &mut FsSink::new(dir.path()),
+            &mut LimitTracker::new(ExtractionLimits::default()),
+        )
+        .unwrap();
+
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                fs::read_link(dir.path().join("tool/up")).unwrap(),
+                Path::new("../hello")
+            );
+            assert!(fs::symlink_metadata(dir.path().join("tool/absolute")).is_err());
+            assert!(fs::symlink_metadata(dir.path().join("tool/escape")).is_err());
+        }
+    }
+
+    #[test]
+    fn test_extract_zip_streaming_corrupted() {
+        let mut zip = ZipBuilder::default();
+        zip.add("hello", 0, METHOD_STORED, 0o100755, b"hello");
+        let mut zip = zip.finish();
+        // Corrupt the content following the 30 bytes long local file header
+        // and the name of the entry.
+        zip[30 + "hello".len()] = b'j';
+
+        let dir = tempdir().unwrap();
+        assert!(matches!(
+            do_extract_zip_streaming(
+                &mut &zip[..],
+                &mut FsSink::new(dir.path()),
+                &mut LimitTracker::new(ExtractionLimits::default())
+            ),
+            Err(DownloadError::InvalidZip(_))
+        ));
+    }
+}
@@ -0,0 +1,58 @@
+//! Restriction of the cryptography to FIPS 140-3, enabled by the `fips`
+//! feature:
+//!
+//! - TLS connections use rustls with the FIPS validated module of aws-lc-rs,
+//!   which only negotiates the approved algorithms.
+//! - Packages are only verified with SHA-2 checksums and signatures, e.g.
+//!   BLAKE3 checksums and minisign signatures are refused.
+//!
+//! Note that the checksums and signatures of packages are still verified by
+//! RustCrypto, which is not a FIPS validated module.
+
+#[cfg(feature = "fips")]
+use rustls_crate::crypto::{default_fips_provider, CryptoProvider};
+#[cfg(feature = "fips")]
+use thiserror::Error as ThisError;
+
+/// True if the `fips` feature is enabled.
+pub const ENABLED: bool = cfg!(feature = "fips");
+
+/// The TLS crypto provider of the process is not operating in FIPS mode.
+#[cfg(feature = "fips")]
+#[derive(Debug, ThisError)]
+#[error("TLS crypto provider is not operating in FIPS mode, which is required by this build")]
+pub struct TlsProviderError(());
+
+/// Install the TLS crypto provider of aws-lc-rs operating in FIPS mode as
+/// the default one of the process, since reqwest does not support setting
+/// it on the builder.
+///
+/// This is meant to be called at startup, and fails if the provider is not
+/// operating in FIPS mode, or if another provider is installed already.
+#[cfg(feature = "fips")]
+pub fn install_tls_provider() -> Result<(), TlsProviderError> {
+    let provider = default_fips_provider();
+    if !provider.fips() {
+        return Err(TlsProviderError(()));
+    }
+
+    match provider.install_default() {
+        Ok(()) => Ok(()),
+        Err(installed) if installed.fips() => Ok(()),
+        Err(_) => Err(TlsProviderError(())),
+    }
+}
+
+/// Make `builder` use rustls with the TLS crypto provider operating in
+/// FIPS mode.
+#[cfg(feature = "fips")]
+pub(crate) fn use_fips_tls(
+    builder: reqwest::ClientBuilder,
+) -> Result<reqwest::ClientBuilder, TlsProviderError> {
+    install_tls_provider()?;
+
+    match CryptoProvider::get_default() {
+        Some(provider) if provider.fips() => Ok(builder.use_rustls_tls()),
+        _ => Err(TlsProviderError(())),
+    }
+}
@@ -2,5 +2,6 @@
 
 pub use bytes;
 pub mod download;
+pub mod fips;
 pub mod remote;
 mod utils;
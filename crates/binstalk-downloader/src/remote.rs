@@ -1,7 +1,9 @@
 use std::{
+    collections::HashSet,
+    future::Future,
     num::{NonZeroU16, NonZeroU64, NonZeroU8},
     ops::ControlFlow,
-    sync::Arc,
+    sync::{Arc, Mutex, RwLock},
     time::{Duration, SystemTime},
 };
 
@@ -9,12 +11,14 @@ use bytes::Bytes;
 use futures_util::Stream;
 use httpdate::parse_http_date;
 use reqwest::{
-    header::{HeaderMap, HeaderValue, RETRY_AFTER},
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER},
     Request,
 };
 use thiserror::Error as ThisError;
 use tracing::{debug, info, instrument};
+use zeroize::Zeroizing;
 
+pub use binstalk_types::cargo_toml_binstall::AuthScheme;
 pub use reqwest::{header, Error as ReqwestError, Method, StatusCode};
 pub use url::Url;
 
@@ -24,9 +28,29 @@ use delay_request::DelayRequest;
 mod certificate;
 pub use certificate::Certificate;
 
+#[cfg(test)]
+pub(crate) mod test_server;
+
+#[cfg(feature = "cookies")]
+mod cookie_jar;
+#[cfg(feature = "cookies")]
+use cookie_jar::DomainCookieJar;
+
+mod domain_auth;
+use domain_auth::{Auth, DomainCredentials, LazyAuth};
+
+mod fixtures;
+pub use fixtures::{FixtureError, Fixtures};
+
+mod redirect;
+pub use redirect::{CrossHostAuth, RedirectPolicy};
+
 mod request_builder;
 pub use request_builder::{Body, RequestBuilder, Response};
 
+mod tls_roots;
+pub use tls_roots::TLSRoots;
+
 mod tls_version;
 pub use tls_version::TLSVersion;
 
@@ -54,9 +78,22 @@ pub enum Error {
     #[error(transparent)]
     Http(Box<HttpError>),
 
+    #[error(transparent)]
+    Fixture(Box<FixtureError>),
+
+    #[error("too many redirects, last redirected to {0}")]
+    TooManyRedirects(Box<Url>),
+
+    #[error("refused to redirect authenticated request for {from} to another host: {to}")]
+    CrossHostRedirect { from: Box<Url>, to: Box<Url> },
+
     #[cfg(feature = "json")]
     #[error("Failed to parse http response body as Json: {0}")]
     Json(#[from] JsonError),
+
+    #[cfg(feature = "fips")]
+    #[error(transparent)]
+    Fips(#[from] crate::fips::TlsProviderError),
 }
 
 #[derive(Debug, ThisError)]
@@ -79,6 +116,12 @@ impl HttpError {
 struct Inner {
     client: reqwest::Client,
     service: DelayRequest,
+    warmed_up_origins: Mutex<HashSet<url::Origin>>,
+    domain_credentials: RwLock<Vec<DomainCredentials>>,
+    redirect_policy: RwLock<RedirectPolicy>,
+    fixtures: RwLock<Option<Fixtures>>,
+    #[cfg(feature = "cookies")]
+    cookie_jar: Arc<DomainCookieJar>,
 }
 
 #[derive(Clone, Debug)]
@@ -105,7 +148,12 @@ impl Client {
         certificates: impl IntoIterator<Item = Certificate>,
     ) -> Result<Self, Error> {
         Self::from_builder(
-            Self::default_builder(user_agent.as_ref(), min_tls, &mut certificates.into_iter()),
+            Self::default_builder(
+                user_agent.as_ref(),
+                min_tls,
+                None,
+                &mut certificates.into_iter(),
+            ),
             per_millis,
             num_request,
         )
@@ -116,9 +164,12 @@ impl Client {
     /// This may be used alongside [`Client::from_builder`] to start from reasonable
     /// defaults, but still be able to customise the reqwest instance. Arguments are
     /// as [`Client::new`], but without generic parameters.
+    ///
+    /// * `tls_roots` - The root certificates to trust, see [`TLSRoots`].
     pub fn default_builder(
         user_agent: &str,
         min_tls: Option<TLSVersion>,
+        tls_roots: Option<TLSRoots>,
         certificates: &mut dyn Iterator<Item = Certificate>,
     ) -> reqwest::ClientBuilder {
         let mut builder = reqwest::ClientBuilder::new()
@@ -139,6 +190,10 @@ impl Client {
 
             builder = builder.min_tls_version(tls_ver.into());
 
+            if let Some(tls_roots) = tls_roots {
+                builder = tls_roots.apply(builder);
+            }
+
             for certificate in certificates {
                 builder = builder.add_root_certificate(certificate.0);
             }
@@ -155,11 +210,27 @@ impl Client {
     /// Construct a custom client from a [`reqwest::ClientBuilder`].
     ///
     /// You may want to also use [`Client::default_builder`].
+    ///
+    /// Redirects are followed by the client according to its
+    /// [`RedirectPolicy`] instead of the redirect policy of `builder`.
+    ///
+    /// With the `fips` feature, TLS uses the crypto provider operating in
+    /// FIPS mode, see [`crate::fips`], and this fails if it cannot be used.
     pub fn from_builder(
         builder: reqwest::ClientBuilder,
         per_millis: NonZeroU16,
         num_request: NonZeroU64,
     ) -> Result<Self, Error> {
+        let builder = builder.redirect(reqwest::redirect::Policy::none());
+
+        #[cfg(feature = "fips")]
+        let builder = crate::fips::use_fips_tls(builder)?;
+
+        #[cfg(feature = "cookies")]
+        let cookie_jar = Arc::new(DomainCookieJar::default());
+        #[cfg(feature = "cookies")]
+        let builder = builder.cookie_provider(cookie_jar.clone());
+
         let client = builder.build()?;
 
         Ok(Client(Arc::new(Inner {
@@ -169,6 +240,12 @@ impl Client {
                 Duration::from_millis(per_millis.get() as u64),
                 client,
             ),
+            warmed_up_origins: Default::default(),
+            domain_credentials: Default::default(),
+            redirect_policy: Default::default(),
+            fixtures: Default::default(),
+            #[cfg(feature = "cookies")]
+            cookie_jar,
         })))
     }
 
@@ -177,6 +254,174 @@ impl Client {
         &self.0.client
     }
 
+    /// Authenticate every request to `domain` and its subdomains with HTTP
+    /// basic auth, e.g. for services storing artifacts of private projects.
+    ///
+    /// The credentials are not sent to other domains, even when redirected.
+    pub fn add_basic_auth_for_domain(&self, domain: &str, username: &str, password: &str) {
+        self.add_domain_credentials(DomainCredentials::new(
+            domain,
+            Auth::Basic {
+                username: username.into(),
+                password: password.into(),
+            },
+        ));
+    }
+
+    /// Authenticate every request to `domain` and its subdomains with the
+    /// bearer token returned by `get_token`, along with `headers`, e.g. the
+    /// api version required by the server for bearer auth.
+    ///
+    /// `get_token` is only called on the first request to the domain, so
+    /// that credentials, e.g. of a cloud provider, are only discovered when
+    /// needed. Requests are sent without auth if it returns `None`.
+    pub fn add_lazy_bearer_auth_for_domain<F, Fut>(
+        &self,
+        domain: &str,
+        headers: &'static [(&'static str, &'static str)],
+        get_token: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<Zeroizing<Box<str>>>> + Send + 'static,
+    {
+        self.add_domain_credentials(DomainCredentials::new(
+            domain,
+            Auth::Lazy(Arc::new(LazyAuth::new(
+                AuthScheme::Bearer,
+                headers,
+                get_token,
+            ))),
+        ));
+    }
+
+    /// Authenticate every request to `domain` and its subdomains with
+    /// `credential` using `scheme`.
+    ///
+    /// For [`AuthScheme::Basic`], `credential` is `username:password`, or
+    /// just the password if it contains no `:`.
+    pub fn add_auth_for_domain(&self, domain: &str, scheme: AuthScheme, credential: &str) {
+        self.add_domain_credentials(DomainCredentials::new(
+            domain,
+            Auth::new(scheme, credential),
+        ));
+    }
+
+    /// Same as [`Client::add_auth_for_domain`], but the credential is
+    /// returned by `get_credential` on the first request to the domain, e.g.
+    /// from a secrets manager. Requests are sent without auth if it returns
+    /// `None`.
+    pub fn add_lazy_auth_for_domain<F, Fut>(
+        &self,
+        domain: &str,
+        scheme: AuthScheme,
+        get_credential: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<Zeroizing<Box<str>>>> + Send + 'static,
+    {
+        self.add_domain_credentials(DomainCredentials::new(
+            domain,
+            Auth::Lazy(Arc::new(LazyAuth::new(scheme, &[], get_credential))),
+        ));
+    }
+
+    /// The credentials added first take precedence if several of them match
+    /// the host of a request.
+    fn add_domain_credentials(&self, credentials: DomainCredentials) {
+        self.0.domain_credentials.write().unwrap().push(credentials);
+    }
+
+    /// Authenticate `request` with the credentials added for its host,
+    /// unless it is already authenticated.
+    async fn authenticate(&self, request: Request) -> Result<Request, ReqwestError> {
+        if request.headers().contains_key(AUTHORIZATION) {
+            return Ok(request);
+        }
+
+        let auth = request.url().host_str().and_then(|host| {
+            self.0
+                .domain_credentials
+                .read()
+                .unwrap()
+                .iter()
+                .find(|credentials| credentials.matches(host))
+                .map(|credentials| credentials.auth.clone())
+        });
+
+        match auth {
+            Some(auth) => auth
+                .authenticate(reqwest::RequestBuilder::from_parts(
+                    self.0.client.clone(),
+                    request,
+                ))
+                .await
+                .build(),
+            None => Ok(request),
+        }
+    }
+
+    /// Set how redirects are followed, defaults to following at most 10
+    /// redirects and stripping credentials on redirects to other hosts.
+    pub fn set_redirect_policy(&self, policy: RedirectPolicy) {
+        *self.0.redirect_policy.write().unwrap() = policy;
+    }
+
+    /// Record the responses to, or replay them from, `fixtures`.
+    pub fn set_fixtures(&self, fixtures: Fixtures) {
+        *self.0.fixtures.write().unwrap() = Some(fixtures);
+    }
+
+    fn fixtures(&self) -> Option<Fixtures> {
+        self.0.fixtures.read().unwrap().clone()
+    }
+
+    /// Store the cookies set by `domain` and its subdomains and send them
+    /// back in later requests, e.g. for download portals requiring a session
+    /// cookie.
+    ///
+    /// Cookies of other domains are ignored.
+    #[cfg(feature = "cookies")]
+    pub fn enable_cookies_for_domain(&self, domain: &str) {
+        self.0.cookie_jar.add_domain(domain);
+    }
+
+    /// Pre-resolve DNS and pre-open (TLS) connections to the origins of
+    /// `urls` in the background, so that later requests to them, e.g. the
+    /// downloads planned by resolution, can start instantly.
+    ///
+    /// Each origin is only warmed up once per client, and errors are ignored
+    /// since the actual request would report them anyway.
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn warm_up(&self, urls: impl IntoIterator<Item = Url>) {
+        if matches!(self.fixtures(), Some(Fixtures::Replay(_))) {
+            return;
+        }
+
+        let mut warmed_up_origins = self.0.warmed_up_origins.lock().unwrap();
+
+        for url in urls {
+            let origin = url.origin();
+            if !origin.is_tuple() || !warmed_up_origins.insert(origin.clone()) {
+                continue;
+            }
+
+            let Ok(origin_url) = Url::parse(&origin.ascii_serialization()) else {
+                continue;
+            };
+
+            // Bypass the rate limiter so that warming up never delays
+            // the actual requests.
+            let client = self.0.client.clone();
+            tokio::spawn(async move {
+                match client.head(origin_url.clone()).send().await {
+                    Ok(_) => debug!("Warmed up connection to {origin_url}"),
+                    Err(err) => debug!("Failed to warm up connection to {origin_url}: {err}"),
+                }
+            });
+        }
+    }
+
     /// Return `Err(_)` for fatal error tht cannot be retried.
     ///
     /// Return `Ok(ControlFlow::Continue(res))` for retryable error, `res`
@@ -288,27 +533,76 @@ impl Client {
     /// * `request` - `Request::try_clone` must always return `Some`.
     async fn send_request(
         &self,
-        request: Request,
+        mut request: Request,
         error_for_status: bool,
     ) -> Result<reqwest::Response, Error> {
-        debug!("Downloading from: '{}'", request.url());
+        let policy = *self.0.redirect_policy.read().unwrap();
+        let mut redirects = 0;
+
+        let http_error = |request: &Request, err| {
+            Error::Http(Box::new(HttpError {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                err,
+            }))
+        };
 
-        self.send_request_inner(&request)
-            .await
-            .and_then(|response| {
-                if error_for_status {
-                    response.error_for_status()
+        loop {
+            let authenticated_request = self
+                .authenticate(request.try_clone().unwrap())
+                .await
+                .map_err(|err| http_error(&request, err))?;
+
+            debug!("Downloading from: '{}'", request.url());
+
+            let response = match self.fixtures() {
+                Some(Fixtures::Replay(dir)) => fixtures::replay(&dir, &request).await?,
+                fixtures => {
+                    let response = self
+                        .send_request_inner(&authenticated_request)
+                        .await
+                        .map_err(|err| http_error(&request, err))?;
+
+                    match fixtures {
+                        Some(Fixtures::Record(dir)) => {
+                            fixtures::record(&dir, &request, response).await?
+                        }
+                        _ => response,
+                    }
+                }
+            };
+
+            let Some(next) =
+                redirect::next_request(&request, &response).filter(|_| policy.max_redirects > 0)
+            else {
+                break if error_for_status {
+                    response
+                        .error_for_status()
+                        .map_err(|err| http_error(&request, err))
                 } else {
                     Ok(response)
-                }
-            })
-            .map_err(|err| {
-                Error::Http(Box::new(HttpError {
-                    method: request.method().clone(),
-                    url: request.url().clone(),
-                    err,
-                }))
-            })
+                };
+            };
+
+            if redirects >= policy.max_redirects {
+                break Err(Error::TooManyRedirects(Box::new(next.url().clone())));
+            }
+            redirects += 1;
+
+            if policy.cross_host_auth == CrossHostAuth::Deny
+                && redirect::is_cross_host(request.url(), next.url())
+                && authenticated_request.headers().contains_key(AUTHORIZATION)
+            {
+                break Err(Error::CrossHostRedirect {
+                    from: Box::new(request.url().clone()),
+                    to: Box::new(next.url().clone()),
+                });
+            }
+
+            debug!("Redirected to: '{}'", next.url());
+            request_builder::discard_body(response).await;
+            request = next;
+        }
     }
 
     async fn head_or_fallback_to_get(
@@ -351,8 +645,18 @@ impl Client {
     }
 
     /// Check if remote exists using `Method::GET`.
+    ///
+    /// Only the first byte is requested, and the response is read so that
+    /// the connection can be reused to download `url` later.
     pub async fn remote_gettable(&self, url: Url) -> Result<bool, Error> {
-        Ok(self.get(url).send(false).await?.status().is_success())
+        let response = self
+            .get(url)
+            .header("range", "bytes=0-0")
+            .send(false)
+            .await?;
+        let is_success = response.status().is_success();
+        response.discard_body().await;
+        Ok(is_success)
     }
 
     /// Attempt to get final redirected url using `Method::HEAD` or fallback
@@ -417,3 +721,143 @@ fn parse_header_retry_after(headers: &HeaderMap) -> Option<Duration> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        test_server::{client, response, serve},
+        *,
+    };
+
+    fn redirect(location: &str) -> Vec<u8> {
+        response("302 Found", &[("Location", location)], 0, b"")
+    }
+
+    #[tokio::test]
+    async fn test_redirect_loop() {
+        let (url, requests) = serve(|_, _| redirect("/loop")).await;
+        let client = client();
+
+        let err = client.get(url.clone()).send(false).await.unwrap_err();
+        assert!(matches!(err, Error::TooManyRedirects(_)), "{err}");
+        assert_eq!(
+            requests.lock().unwrap().len(),
+            1 + RedirectPolicy::default().max_redirects as usize
+        );
+
+        requests.lock().unwrap().clear();
+        client.set_redirect_policy(RedirectPolicy {
+            max_redirects: 2,
+            ..Default::default()
+        });
+        let err = client.get(url).send(false).await.unwrap_err();
+        assert!(matches!(err, Error::TooManyRedirects(_)), "{err}");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_not_followed() {
+        let (url, _) = serve(|_, _| redirect("/b")).await;
+        let client = client();
+        client.set_redirect_policy(RedirectPolicy {
+            max_redirects: 0,
+            ..Default::default()
+        });
+
+        let response = client.get(url).send(true).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_post() {
+        let (url, requests) = serve(|request, _| match request.path.as_str() {
+            "/a" => response("303 See Other", &[("Location", "/b")], 0, b""),
+            _ => response("200 OK", &[], 0, b""),
+        })
+        .await;
+
+        client()
+            .post(url.join("a").unwrap(), "body")
+            .send(true)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].path, "/b");
+        assert_eq!(requests[1].header("content-length"), None);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_cross_host() {
+        let (url, requests) = serve(|request, _| match request.path.as_str() {
+            "/a" => redirect(&format!(
+                "http://localhost:{}/b",
+                request.header("host").unwrap().rsplit(':').next().unwrap()
+            )),
+            _ => response("200 OK", &[], 0, b""),
+        })
+        .await;
+        let url = url.join("a").unwrap();
+        let client = client();
+        client.add_auth_for_domain("127.0.0.1", AuthScheme::Bearer, "secret");
+
+        // The credentials are stripped by default.
+        client.get(url.clone()).send(true).await.unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+            assert_eq!(requests[1].path, "/b");
+            assert_eq!(requests[1].header("authorization"), None);
+        }
+
+        requests.lock().unwrap().clear();
+        client.set_redirect_policy(RedirectPolicy {
+            cross_host_auth: CrossHostAuth::Deny,
+            ..Default::default()
+        });
+        let err = client.get(url).send(true).await.unwrap_err();
+        assert!(matches!(err, Error::CrossHostRedirect { .. }), "{err}");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let (url, requests) = serve(|request, _| match request.path.as_str() {
+            "/a" => response("303 See Other", &[("Location", "/b")], 0, b""),
+            _ => response("200 OK", &[("X-Fixture", "b")], 4, b"body"),
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let recording = client();
+        recording.set_fixtures(Fixtures::Record(dir.path().into()));
+        let body = recording
+            .get(url.join("a").unwrap())
+            .send(true)
+            .await
+            .unwrap();
+        assert_eq!(body.bytes().await.unwrap(), "body");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let replaying = client();
+        replaying.set_fixtures(Fixtures::Replay(dir.path().into()));
+        let response = replaying
+            .get(url.join("a").unwrap())
+            .send(true)
+            .await
+            .unwrap();
+        assert_eq!(response.url().as_str(), url.join("b").unwrap().as_str());
+        assert_eq!(response.headers()["x-fixture"], "b");
+        assert_eq!(response.bytes().await.unwrap(), "body");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let err = replaying
+            .get(url.join("c").unwrap())
+            .send(true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Fixture(_)), "{err}");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+}
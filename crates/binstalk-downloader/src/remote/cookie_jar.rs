@@ -0,0 +1,49 @@
+use std::sync::RwLock;
+
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    header::HeaderValue,
+};
+use url::Url;
+
+use super::domain_auth::domain_matches;
+
+/// Cookie jar which only stores and sends cookies of the domains it is
+/// enabled for, so that other hosts cannot track the requests.
+#[derive(Debug, Default)]
+pub(super) struct DomainCookieJar {
+    jar: Jar,
+    domains: RwLock<Vec<Box<str>>>,
+}
+
+impl DomainCookieJar {
+    pub(super) fn add_domain(&self, domain: &str) {
+        self.domains.write().unwrap().push(domain.into());
+    }
+
+    fn is_enabled_for(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|host| {
+            self.domains
+                .read()
+                .unwrap()
+                .iter()
+                .any(|domain| domain_matches(domain, host))
+        })
+    }
+}
+
+impl CookieStore for DomainCookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        if self.is_enabled_for(url) {
+            self.jar.set_cookies(cookie_headers, url)
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        if self.is_enabled_for(url) {
+            self.jar.cookies(url)
+        } else {
+            None
+        }
+    }
+}
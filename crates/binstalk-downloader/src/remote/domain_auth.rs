@@ -0,0 +1,167 @@
+use std::{fmt, future::Future, sync::Arc};
+
+use binstalk_types::cargo_toml_binstall::AuthScheme;
+use futures_util::future::BoxFuture;
+use tokio::sync::OnceCell;
+use zeroize::Zeroizing;
+
+type GetCredential = Box<dyn Fn() -> BoxFuture<'static, Option<Zeroizing<Box<str>>>> + Send + Sync>;
+
+/// Credentials sent to a domain and its subdomains.
+pub(super) struct DomainCredentials {
+    domain: Box<str>,
+    pub(super) auth: Auth,
+}
+
+#[derive(Clone)]
+pub(super) enum Auth {
+    Basic {
+        username: Box<str>,
+        password: Box<str>,
+    },
+    Bearer(Box<str>),
+    Lazy(Arc<LazyAuth>),
+}
+
+impl DomainCredentials {
+    pub(super) fn new(domain: &str, auth: Auth) -> Self {
+        Self {
+            domain: domain.into(),
+            auth,
+        }
+    }
+
+    pub(super) fn matches(&self, host: &str) -> bool {
+        domain_matches(&self.domain, host)
+    }
+}
+
+/// Return true if `host` is `domain` or one of its subdomains.
+pub(super) fn domain_matches(domain: &str, host: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+}
+
+impl Auth {
+    /// For [`AuthScheme::Basic`], `credential` is `username:password`, or
+    /// just the password if it contains no `:`.
+    pub(super) fn new(scheme: AuthScheme, credential: &str) -> Self {
+        match scheme {
+            AuthScheme::Basic => {
+                let (username, password) = credential.split_once(':').unwrap_or(("", credential));
+                Auth::Basic {
+                    username: username.into(),
+                    password: password.into(),
+                }
+            }
+            AuthScheme::Bearer => Auth::Bearer(credential.into()),
+        }
+    }
+
+    pub(super) async fn authenticate(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        match self {
+            Auth::Basic { username, password } => builder.basic_auth(username, Some(password)),
+            Auth::Bearer(token) => builder.bearer_auth(token),
+            Auth::Lazy(auth) => auth.authenticate(builder).await,
+        }
+    }
+}
+
+impl fmt::Debug for DomainCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("DomainCredentials");
+        s.field("domain", &self.domain);
+        match &self.auth {
+            Auth::Basic { username, .. } => s.field("username", username),
+            Auth::Bearer(_) => s.field("bearer", &".."),
+            Auth::Lazy(auth) => s.field("lazy_auth", auth),
+        };
+        s.finish_non_exhaustive()
+    }
+}
+
+/// Credential retrieved on the first request that needs it, sent using
+/// `scheme` along with `headers`.
+pub(super) struct LazyAuth {
+    scheme: AuthScheme,
+    headers: &'static [(&'static str, &'static str)],
+    auth: OnceCell<Option<Auth>>,
+    get_credential: GetCredential,
+}
+
+impl fmt::Debug for LazyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyAuth")
+            .field("scheme", &self.scheme)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LazyAuth {
+    pub(super) fn new<F, Fut>(
+        scheme: AuthScheme,
+        headers: &'static [(&'static str, &'static str)],
+        get_credential: F,
+    ) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<Zeroizing<Box<str>>>> + Send + 'static,
+    {
+        Self {
+            scheme,
+            headers,
+            auth: OnceCell::new(),
+            get_credential: Box::new(move || Box::pin(get_credential())),
+        }
+    }
+
+    pub(super) async fn authenticate(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        let auth = self
+            .auth
+            .get_or_init(|| async {
+                let credential = (self.get_credential)().await?;
+                Some(Auth::new(self.scheme, &credential))
+            })
+            .await;
+
+        let builder = match auth {
+            Some(Auth::Basic { username, password }) => {
+                builder.basic_auth(username, Some(password))
+            }
+            Some(Auth::Bearer(token)) => builder.bearer_auth(token),
+            _ => return builder,
+        };
+
+        self.headers.iter().fold(builder, |builder, (key, value)| {
+            builder.header(*key, *value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let credentials = DomainCredentials::new(
+            "example.com",
+            Auth::Basic {
+                username: "user".into(),
+                password: "password".into(),
+            },
+        );
+
+        assert!(credentials.matches("example.com"));
+        assert!(credentials.matches("a.example.com"));
+        assert!(!credentials.matches("badexample.com"));
+        assert!(!credentials.matches("example.com.org"));
+    }
+}
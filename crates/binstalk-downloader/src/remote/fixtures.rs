@@ -0,0 +1,136 @@
+//! Responses recorded to a directory, one file per request, which can be
+//! replayed instead of sending the requests.
+
+use std::{io, path::Path, sync::Arc};
+
+use reqwest::{header::RANGE, Request, ResponseBuilderExt, StatusCode};
+use thiserror::Error as ThisError;
+use tokio::fs;
+
+use super::{Error, Method, Url};
+
+/// Directory of recorded responses, e.g. to benchmark the resolver without
+/// depending on the network.
+#[derive(Clone, Debug)]
+pub enum Fixtures {
+    /// Send the requests and record their responses to the directory.
+    Record(Arc<Path>),
+    /// Replay the responses recorded to the directory instead of sending
+    /// the requests, failing on requests which are not recorded.
+    Replay(Arc<Path>),
+}
+
+#[derive(Debug, ThisError)]
+#[error("could not {action} the response of {method} {url}: {err}")]
+pub struct FixtureError {
+    action: &'static str,
+    method: Method,
+    url: Url,
+    #[source]
+    err: io::Error,
+}
+
+fn fixture_error(action: &'static str, request: &Request, err: io::Error) -> Error {
+    Error::Fixture(Box::new(FixtureError {
+        action,
+        method: request.method().clone(),
+        url: request.url().clone(),
+        err,
+    }))
+}
+
+/// The first line of a fixture, identifying the request it is recorded for.
+fn request_line(request: &Request) -> String {
+    format!("{} {}\n", request.method(), request.url())
+}
+
+/// Name of the fixture of `request`, a FNV-1a hash of what identifies it
+/// which is stable across builds, unlike the hashers of std.
+fn file_name(request: &Request) -> String {
+    let range = request.headers().get(RANGE).map(|range| range.as_bytes());
+    let body = request.body().and_then(|body| body.as_bytes());
+
+    let hash = [
+        request_line(request).as_bytes(),
+        range.unwrap_or_default(),
+        b"\n",
+        body.unwrap_or_default(),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{hash:016x}")
+}
+
+/// Record `response` to `dir` and return an equivalent response.
+///
+/// The fixture consists of the request line, the status, the headers and
+/// the body of the response.
+pub(super) async fn record(
+    dir: &Path,
+    request: &Request,
+    response: reqwest::Response,
+) -> Result<reqwest::Response, Error> {
+    let status = response.status();
+    let url = response.url().clone();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+
+    let mut fixture = format!("{}{}\n", request_line(request), status.as_u16()).into_bytes();
+    for (name, value) in &headers {
+        fixture.extend_from_slice(name.as_str().as_bytes());
+        fixture.extend_from_slice(b": ");
+        fixture.extend_from_slice(value.as_bytes());
+        fixture.push(b'\n');
+    }
+    fixture.push(b'\n');
+    fixture.extend_from_slice(&body);
+
+    fs::create_dir_all(dir)
+        .await
+        .map_err(|err| fixture_error("record", request, err))?;
+    fs::write(dir.join(file_name(request)), fixture)
+        .await
+        .map_err(|err| fixture_error("record", request, err))?;
+
+    let mut builder = http::Response::builder().status(status).url(url);
+    if let Some(builder_headers) = builder.headers_mut() {
+        *builder_headers = headers;
+    }
+    Ok(builder.body(body).unwrap().into())
+}
+
+/// Return the response recorded to `dir` for `request`.
+pub(super) async fn replay(dir: &Path, request: &Request) -> Result<reqwest::Response, Error> {
+    let fixture = fs::read(dir.join(file_name(request)))
+        .await
+        .map_err(|err| fixture_error("replay", request, err))?;
+
+    parse(request, &fixture)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed fixture"))
+        .map_err(|err| fixture_error("replay", request, err))
+}
+
+fn parse(request: &Request, fixture: &[u8]) -> Option<reqwest::Response> {
+    let fixture = fixture.strip_prefix(request_line(request).as_bytes())?;
+
+    let end_of_headers = fixture.windows(2).position(|window| window == b"\n\n")?;
+    let (head, body) = fixture.split_at(end_of_headers + 1);
+    let body = &body[1..];
+
+    let mut lines = head.split(|byte| *byte == b'\n');
+    let status = StatusCode::from_bytes(lines.next()?).ok()?;
+
+    let mut builder = http::Response::builder()
+        .status(status)
+        .url(request.url().clone());
+    for line in lines.filter(|line| !line.is_empty()) {
+        let separator = line.windows(2).position(|window| window == b": ")?;
+        builder = builder.header(&line[..separator], &line[separator + 2..]);
+    }
+
+    builder.body(body.to_vec()).ok().map(Into::into)
+}
@@ -0,0 +1,105 @@
+use reqwest::{
+    header::{
+        AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+        PROXY_AUTHORIZATION, TRANSFER_ENCODING, WWW_AUTHENTICATE,
+    },
+    Method, Request, StatusCode,
+};
+use url::Url;
+
+/// How [`Client`](super::Client) follows redirects.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RedirectPolicy {
+    /// Maximum number of redirects followed for a request, `0` to return
+    /// the redirect response instead of following it.
+    pub max_redirects: u8,
+    /// What to do with the credentials of a request redirected to another
+    /// host.
+    pub cross_host_auth: CrossHostAuth,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_redirects: 10,
+            cross_host_auth: CrossHostAuth::default(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum CrossHostAuth {
+    /// Drop the credentials of the request, so that the redirected request
+    /// is only authenticated with the credentials added for its host.
+    #[default]
+    Strip,
+    /// Return an error instead of following the redirect of an
+    /// authenticated request.
+    Deny,
+}
+
+pub(super) fn is_cross_host(previous: &Url, next: &Url) -> bool {
+    previous.host_str() != next.host_str()
+        || previous.port_or_known_default() != next.port_or_known_default()
+}
+
+/// Return the request to send for the redirect `response` to `request`,
+/// or `None` if it is not a redirect that can be followed.
+pub(super) fn next_request(request: &Request, response: &reqwest::Response) -> Option<Request> {
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let url = request.url().join(location).ok()?;
+
+    let mut next = request.try_clone()?;
+    match response.status() {
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+            if !matches!(*next.method(), Method::GET | Method::HEAD) {
+                *next.method_mut() = Method::GET;
+                *next.body_mut() = None;
+                let headers = next.headers_mut();
+                for header in [
+                    CONTENT_ENCODING,
+                    CONTENT_LENGTH,
+                    CONTENT_TYPE,
+                    TRANSFER_ENCODING,
+                ] {
+                    headers.remove(header);
+                }
+            }
+        }
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => (),
+        _ => return None,
+    }
+
+    if is_cross_host(request.url(), &url) {
+        let headers = next.headers_mut();
+        for header in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+            headers.remove(header);
+        }
+    }
+    *next.url_mut() = url;
+
+    Some(next)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_cross_host() {
+        let url = |url| Url::parse(url).unwrap();
+
+        assert!(!is_cross_host(
+            &url("https://example.com/a"),
+            &url("https://example.com:443/b")
+        ));
+        assert!(is_cross_host(
+            &url("https://example.com/a"),
+            &url("https://cdn.example.com/a")
+        ));
+        assert!(is_cross_host(
+            &url("https://example.com/a"),
+            &url("https://example.com:8443/a")
+        ));
+    }
+}
@@ -8,6 +8,9 @@ use super::{header, Client, Error, HttpError, StatusCode, Url};
 
 pub use reqwest::Body;
 
+/// Bodies of at most this size are read before the response is dropped.
+const MAX_DISCARDED_BODY_SIZE: u64 = 64 * 1024;
+
 #[cfg(feature = "json")]
 pub use serde_json::Error as JsonError;
 
@@ -32,6 +35,14 @@ impl RequestBuilder {
         }
     }
 
+    /// Add `headers`, replacing the ones of the same names.
+    pub fn headers(self, headers: header::HeaderMap) -> Self {
+        Self {
+            client: self.client,
+            inner: self.inner.headers(headers),
+        }
+    }
+
     pub fn body(self, body: impl Into<Body>) -> Self {
         Self {
             client: self.client,
@@ -42,9 +53,14 @@ impl RequestBuilder {
     pub async fn send(self, error_for_status: bool) -> Result<Response, Error> {
         let request = self.inner.build()?;
         let method = request.method().clone();
+        let request_url = request.url().clone();
+        let request_headers = request.headers().clone();
         Ok(Response {
             inner: self.client.send_request(request, error_for_status).await?,
             method,
+            request_url,
+            request_headers,
+            client: self.client,
         })
     }
 }
@@ -53,6 +69,11 @@ impl RequestBuilder {
 pub struct Response {
     inner: reqwest::Response,
     method: Method,
+    /// Url of the request, before any redirect.
+    request_url: Url,
+    /// Headers of the request, without the credentials added for its host.
+    request_headers: header::HeaderMap,
+    client: Client,
 }
 
 impl Response {
@@ -79,6 +100,26 @@ impl Response {
         self.inner.status()
     }
 
+    /// Read the rest of the body if it is small and drop the response.
+    ///
+    /// HTTP/1.1 connections are closed if the body of the response is not
+    /// read to the end, so this lets later requests, e.g. the download of
+    /// the url probed, reuse the pooled connection instead of doing another
+    /// TCP and TLS handshake.
+    pub async fn discard_body(self) {
+        discard_body(self.inner).await
+    }
+
+    /// Length of the body, if known in advance.
+    pub fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+
+    /// The client which sent the request.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
     pub fn url(&self) -> &Url {
         self.inner.url()
     }
@@ -87,6 +128,16 @@ impl Response {
         &self.method
     }
 
+    /// Url of the request sent, before any redirect.
+    pub fn request_url(&self) -> &Url {
+        &self.request_url
+    }
+
+    /// Headers of the request sent, e.g. to send the same request again.
+    pub fn request_headers(&self) -> &header::HeaderMap {
+        &self.request_headers
+    }
+
     pub fn error_for_status_ref(&self) -> Result<&Self, Error> {
         match self.inner.error_for_status_ref() {
             Ok(_) => Ok(self),
@@ -118,3 +169,21 @@ impl Response {
         Ok(serde_json::from_slice(&bytes)?)
     }
 }
+
+pub(super) async fn discard_body(mut response: reqwest::Response) {
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_DISCARDED_BODY_SIZE)
+    {
+        return;
+    }
+
+    // Errors only mean that the connection cannot be reused.
+    let mut remaining = MAX_DISCARDED_BODY_SIZE;
+    while let Ok(Some(chunk)) = response.chunk().await {
+        match remaining.checked_sub(chunk.len() as u64) {
+            Some(rest) => remaining = rest,
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! A minimal HTTP/1.1 server for tests, closing the connection after each
+//! response so that truncated bodies can be served.
+
+use std::{
+    num::{NonZeroU16, NonZeroU64},
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use super::{Client, Url};
+
+/// A request received by the server.
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Requests received by the server, in order.
+pub(crate) type Requests = Arc<Mutex<Vec<Request>>>;
+
+/// Serve each request with the raw response returned by `handle`, given the
+/// request and the number of requests received before it.
+///
+/// Return the url of the server and the requests received.
+pub(crate) async fn serve(
+    handle: impl Fn(&Request, usize) -> Vec<u8> + Send + 'static,
+) -> (Url, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let requests = Requests::default();
+
+    let received = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut buffer = Vec::new();
+            while !buffer.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                if stream.read(&mut byte).await.unwrap() == 0 {
+                    break;
+                }
+                buffer.push(byte[0]);
+            }
+
+            let head = String::from_utf8(buffer).unwrap();
+            let mut lines = head.lines();
+            let mut request_line = lines.next().unwrap_or_default().split(' ');
+            let method = request_line.next().unwrap_or_default().to_owned();
+            let path = request_line.next().unwrap_or_default().to_owned();
+            let headers = lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.to_owned(), value.trim().to_owned()))
+                .collect();
+            let request = Request {
+                method,
+                path,
+                headers,
+            };
+
+            let response = {
+                let mut received = received.lock().unwrap();
+                let response = handle(&request, received.len());
+                received.push(request);
+                response
+            };
+
+            stream.write_all(&response).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+/// Return a raw response with `status`, `headers` and `body`, the length of
+/// which is `content_length`.
+pub(crate) fn response(
+    status: &str,
+    headers: &[(&str, &str)],
+    content_length: usize,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    for (key, value) in headers {
+        response += &format!("{key}: {value}\r\n");
+    }
+    response += &format!("Content-Length: {content_length}\r\n\r\n");
+
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+/// Return a client which can send requests to the server.
+pub(crate) fn client() -> Client {
+    Client::from_builder(
+        reqwest::ClientBuilder::new(),
+        NonZeroU16::new(1).unwrap(),
+        NonZeroU64::new(100).unwrap(),
+    )
+    .unwrap()
+}
@@ -0,0 +1,39 @@
+/// Root certificates trusted by [`crate::remote::Client`], in addition to
+/// the ones added explicitly.
+///
+/// By default, both the bundled webpki roots and the roots of the system
+/// are trusted if the `rustls` feature is enabled, and only the roots of the
+/// system otherwise.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TLSRoots {
+    /// Only trust the Mozilla root certificates bundled in the binary,
+    /// which do not depend on the system, e.g. for static binaries.
+    ///
+    /// Requires the `rustls` feature, the roots of the system are used
+    /// otherwise.
+    Webpki,
+    /// Only trust the root certificates of the system, e.g. corporate root
+    /// certificates installed in it.
+    Native,
+}
+
+impl TLSRoots {
+    #[cfg_attr(not(feature = "__tls"), allow(dead_code))]
+    pub(super) fn apply(self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        #[cfg(feature = "rustls")]
+        {
+            let webpki = self == TLSRoots::Webpki;
+            builder
+                .tls_built_in_webpki_certs(webpki)
+                .tls_built_in_native_certs(!webpki)
+        }
+
+        #[cfg(not(feature = "rustls"))]
+        {
+            if self == TLSRoots::Webpki {
+                tracing::warn!("Webpki roots require the rustls feature, using the system roots");
+            }
+            builder
+        }
+    }
+}
@@ -7,8 +7,11 @@ use bytes::{Buf, Bytes};
 use futures_util::{FutureExt, Stream, StreamExt};
 use tokio::{sync::mpsc, task};
 
+/// Run `f` in a blocking task, receiving the chunks of `stream`, of which at
+/// most one is buffered if `low_memory` is true.
 pub(super) fn extract_with_blocking_task<E, StreamError, S, F, T>(
     stream: S,
+    low_memory: bool,
     f: F,
 ) -> impl Future<Output = Result<T, E>>
 where
@@ -83,8 +86,9 @@ where
         }
     }
 
-    // Use channel size = 5 to minimize the waiting time in the extraction task
-    let (tx, rx) = mpsc::channel(5);
+    // Use channel size = 5 to minimize the waiting time in the extraction task,
+    // unless memory is scarce.
+    let (tx, rx) = mpsc::channel(if low_memory { 1 } else { 5 });
 
     let task = asyncify(move || f(rx));
 
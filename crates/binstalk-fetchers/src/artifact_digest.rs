@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use binstalk_downloader::download::DataVerifier;
+use binstalk_types::crate_info::{ArtifactInfo, ChecksumAlgorithm};
+use bytes::Bytes;
+use compact_str::CompactString;
+use sha2::{Digest, Sha256, Sha512};
+use url::Url;
+
+/// Incremental computation of a digest, for any [`ChecksumAlgorithm`].
+pub(crate) trait Hasher: Send + Sync {
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+
+    /// Return the lowercase hex-encoded digest.
+    fn finalize_hex(self: Box<Self>) -> CompactString {
+        self.finalize()
+            .iter()
+            .fold(CompactString::default(), |mut hex, byte| {
+                write!(hex, "{byte:02x}").unwrap();
+                hex
+            })
+    }
+}
+
+impl Hasher for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data)
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for Sha512 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data)
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        blake3::Hasher::finalize(&self).as_bytes().to_vec()
+    }
+}
+
+pub(crate) fn new_hasher(algorithm: ChecksumAlgorithm) -> Box<dyn Hasher> {
+    match algorithm {
+        ChecksumAlgorithm::Sha256 => Box::new(Sha256::new()),
+        ChecksumAlgorithm::Sha512 => Box::new(Sha512::new()),
+        ChecksumAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+    }
+}
+
+/// [`DataVerifier`] that records the SHA256 digest of the data, along with
+/// the ones of other algorithms requested, before forwarding it to the
+/// inner verifier.
+pub(crate) struct DigestRecorder<'a> {
+    inner: &'a mut dyn DataVerifier,
+    sha256: Box<dyn Hasher>,
+    others: Vec<(ChecksumAlgorithm, Box<dyn Hasher>)>,
+}
+
+impl<'a> DigestRecorder<'a> {
+    pub(crate) fn new(inner: &'a mut dyn DataVerifier, algorithms: &[ChecksumAlgorithm]) -> Self {
+        Self {
+            inner,
+            sha256: new_hasher(ChecksumAlgorithm::Sha256),
+            others: algorithms
+                .iter()
+                .filter(|algorithm| **algorithm != ChecksumAlgorithm::Sha256)
+                .map(|algorithm| (*algorithm, new_hasher(*algorithm)))
+                .collect(),
+        }
+    }
+
+    /// * `url` - the url the data is downloaded from.
+    pub(crate) fn into_artifact_info(self, url: Url) -> ArtifactInfo {
+        ArtifactInfo {
+            url,
+            sha256: self.sha256.finalize_hex(),
+            digests: self
+                .others
+                .into_iter()
+                .map(|(algorithm, hasher)| (algorithm, hasher.finalize_hex()))
+                .collect(),
+            rekor_log_index: None,
+            release_digest: None,
+        }
+    }
+}
+
+impl DataVerifier for DigestRecorder<'_> {
+    fn update(&mut self, data: &Bytes) {
+        self.sha256.update(data);
+        for (_, hasher) in &mut self.others {
+            hasher.update(data);
+        }
+        self.inner.update(data);
+    }
+
+    fn validate(&mut self) -> bool {
+        self.inner.validate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest_recorder() {
+        let mut inner = ();
+        let mut recorder = DigestRecorder::new(
+            &mut inner,
+            &[ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Blake3],
+        );
+        recorder.update(&Bytes::from_static(b"te"));
+        recorder.update(&Bytes::from_static(b"st"));
+
+        let artifact = recorder.into_artifact_info(Url::parse("https://example.com").unwrap());
+        assert_eq!(
+            artifact.sha256,
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+        assert_eq!(
+            artifact.digest(ChecksumAlgorithm::Blake3).unwrap(),
+            blake3::hash(b"test").to_hex().as_str()
+        );
+        assert_eq!(artifact.digest(ChecksumAlgorithm::Sha512), None);
+    }
+}
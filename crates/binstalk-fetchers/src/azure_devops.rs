@@ -0,0 +1,366 @@
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use binstalk_downloader::remote::StatusCode;
+use binstalk_types::cargo_toml_binstall::{BinDir, Strategy};
+use compact_str::ToCompactString;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    common::*, ArtifactInfo, Data, DigestRecorder, FetchError, FindDiagnostic, SignaturePolicy,
+    SignatureVerifier, TargetDataErased,
+};
+
+/// Domains serving Azure DevOps and its artifacts, which need the personal
+/// access token of the user for private projects.
+pub const AZURE_DEVOPS_DOMAINS: &[&str] = &["dev.azure.com", "visualstudio.com"];
+
+const API_VERSION: &str = "7.1";
+
+/// Number of builds of a tag to look through for one of the repository,
+/// since builds can only be filtered by project.
+const MAX_BUILDS_PER_TAG: u32 = 10;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct AzureRepo {
+    organization: CompactString,
+    project: CompactString,
+    repo: CompactString,
+}
+
+impl AzureRepo {
+    /// Extract the repository from `https://dev.azure.com/{org}/{project}/_git/{repo}`
+    /// or the legacy `https://{org}.visualstudio.com/{project}/_git/{repo}`.
+    fn try_extract_from_url(url: &Url) -> Option<Self> {
+        let mut segments = url.path_segments()?;
+
+        let organization = match url.domain()? {
+            "dev.azure.com" => segments.next()?,
+            domain => domain.strip_suffix(".visualstudio.com")?,
+        };
+        let project = segments.next()?;
+        if segments.next()? != "_git" {
+            return None;
+        }
+        let repo = segments.next()?;
+        let repo = repo.strip_suffix(".git").unwrap_or(repo);
+
+        (!organization.is_empty() && !project.is_empty() && !repo.is_empty()).then(|| Self {
+            organization: organization.into(),
+            project: project.into(),
+            repo: repo.into(),
+        })
+    }
+
+    fn api_url(&self, path: &str) -> Result<Url, FetchError> {
+        let mut url = Url::parse(&format!(
+            "https://dev.azure.com/{}/{}/_apis/{path}",
+            self.organization, self.project
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("api-version", API_VERSION);
+        Ok(url)
+    }
+}
+
+#[derive(Deserialize)]
+struct List<T> {
+    value: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Build {
+    id: u64,
+    repository: Repository,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct BuildArtifact {
+    name: String,
+    resource: ArtifactResource,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactResource {
+    download_url: Option<String>,
+}
+
+#[derive(Debug)]
+struct FoundArtifact {
+    name: String,
+    url: Url,
+}
+
+pub struct AzureDevOps {
+    client: Client,
+    data: Arc<Data>,
+    target_data: Arc<TargetDataErased>,
+    signature_policy: SignaturePolicy,
+
+    diagnostic: OnceLock<FindDiagnostic>,
+    resolved: OnceLock<FoundArtifact>,
+    artifact: OnceLock<ArtifactInfo>,
+}
+
+impl AzureDevOps {
+    /// Return `Ok(None)` if the resource does not exist or is not
+    /// accessible with the credentials provided.
+    async fn get_json<T: DeserializeOwned>(&self, url: Url) -> Result<Option<T>, FetchError> {
+        debug!(%url, "Querying Azure DevOps API");
+
+        let response = self.client.get(url).send(false).await?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+                debug!(status=%response.status(), "Azure DevOps resource is not accessible");
+                Ok(None)
+            }
+            _ => Ok(Some(response.error_for_status()?.json().await?)),
+        }
+    }
+
+    /// Return the id of the latest succeeded build of `tag` in `repo`.
+    async fn find_build(&self, repo: &AzureRepo, tag: &str) -> Result<Option<u64>, FetchError> {
+        let mut url = repo.api_url("build/builds")?;
+        url.query_pairs_mut()
+            .append_pair("branchName", &format!("refs/tags/{tag}"))
+            .append_pair("resultFilter", "succeeded")
+            .append_pair("queryOrder", "finishTimeDescending")
+            .append_pair("$top", &MAX_BUILDS_PER_TAG.to_string());
+
+        Ok(self
+            .get_json::<List<Build>>(url)
+            .await?
+            .and_then(|builds| {
+                builds
+                    .value
+                    .into_iter()
+                    .find(|build| build.repository.name.eq_ignore_ascii_case(&repo.repo))
+            })
+            .map(|build| build.id))
+    }
+
+    async fn find_artifact(
+        &self,
+        repo: &AzureRepo,
+        build_id: u64,
+    ) -> Result<Option<FoundArtifact>, FetchError> {
+        let url = repo.api_url(&format!("build/builds/{build_id}/artifacts"))?;
+        let Some(artifacts) = self.get_json::<List<BuildArtifact>>(url).await? else {
+            return Ok(None);
+        };
+
+        let name = &self.data.name;
+        let target = &self.target_data.target;
+        let candidates = [format!("{name}-{target}"), target.to_string()];
+
+        for candidate in &candidates {
+            let found = artifacts
+                .value
+                .iter()
+                .find(|artifact| &artifact.name == candidate)
+                .and_then(|artifact| artifact.resource.download_url.as_deref());
+
+            if let Some(url) = found {
+                return Ok(Some(FoundArtifact {
+                    name: candidate.clone(),
+                    url: Url::parse(url)?,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Fetcher for AzureDevOps {
+    fn new(
+        client: Client,
+        _gh_api_client: GhApiClient,
+        data: Arc<Data>,
+        target_data: Arc<TargetDataErased>,
+        signature_policy: SignaturePolicy,
+    ) -> Arc<dyn super::Fetcher> {
+        Arc::new(Self {
+            client,
+            data,
+            target_data,
+            signature_policy,
+
+            diagnostic: OnceLock::new(),
+            resolved: OnceLock::new(),
+            artifact: OnceLock::new(),
+        })
+    }
+
+    fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
+        tokio::spawn(async move {
+            let Some(repo) = self.data.repo.as_deref() else {
+                self.diagnostic.set(FindDiagnostic::MissingRepository).ok();
+                return Ok(false);
+            };
+            let Some(repo) = Url::parse(repo)
+                .ok()
+                .as_ref()
+                .and_then(AzureRepo::try_extract_from_url)
+            else {
+                debug!("Repository is not hosted on Azure DevOps");
+                return Ok(false);
+            };
+
+            // Azure Pipelines does not sign its artifacts.
+            if self.signature_policy == SignaturePolicy::Require {
+                return Err(FetchError::MissingSignature);
+            }
+
+            let version = &self.data.version;
+            for tag in [format!("v{version}"), version.to_string()] {
+                let Some(build_id) = self.find_build(&repo, &tag).await? else {
+                    continue;
+                };
+
+                if let Some(found) = self.find_artifact(&repo, build_id).await? {
+                    debug!(?found, "Found artifact of build {build_id} of tag {tag}");
+                    if self.resolved.set(found).is_err() {
+                        panic!("<AzureDevOps as Fetcher>::find is run twice");
+                    }
+                    return Ok(true);
+                }
+            }
+
+            self.diagnostic.set(FindDiagnostic::NoArtifact).ok();
+            Ok(false)
+        })
+    }
+
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        self.diagnostic.get().copied().into_iter().collect()
+    }
+
+    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
+        let Some(found) = self.resolved.get() else {
+            panic!("<AzureDevOps as Fetcher>::find has not been called yet!")
+        };
+
+        debug!(url=%found.url, "Downloading package");
+        let mut data_verifier = SignatureVerifier::Noop.data_verifier()?;
+        let mut data_verifier =
+            DigestRecorder::new(data_verifier.as_mut(), &self.data.digest_algorithms);
+        let files = Download::new_with_data_verifier(
+            self.client.clone(),
+            found.url.clone(),
+            &mut data_verifier,
+        )
+        .extract_nested_archive()
+        .with_low_memory(self.data.low_memory)
+        .and_extract(self.pkg_fmt(), dst)
+        .await?;
+        self.artifact
+            .set(data_verifier.into_artifact_info(found.url.clone()))
+            .ok();
+        Ok(files)
+    }
+
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        self.artifact.get().cloned()
+    }
+
+    /// Artifacts are always downloaded as zip.
+    fn pkg_fmt(&self) -> PkgFmt {
+        PkgFmt::Zip
+    }
+
+    fn target_meta(&self) -> PkgMeta {
+        let mut meta = self.target_data.meta.clone();
+        meta.pkg_fmt = Some(self.pkg_fmt());
+        // The zip contains a directory named after the artifact, which the
+        // `bin-dir` configured, if any, is relative to.
+        if let Some(found) = self.resolved.get() {
+            let prefix = |bin_dir: &str| format!("{}/{bin_dir}", found.name);
+            meta.bin_dir = Some(match &meta.bin_dir {
+                Some(bin_dir) => BinDir::Multiple(bin_dir.iter().map(prefix).collect()),
+                None => prefix("{ bin }{ binary-ext }").into(),
+            });
+        }
+        meta
+    }
+
+    fn source_name(&self) -> CompactString {
+        self.resolved
+            .get()
+            .map(|found| {
+                if let Some(domain) = found.url.domain() {
+                    domain.to_compact_string()
+                } else if let Some(host) = found.url.host_str() {
+                    host.to_compact_string()
+                } else {
+                    found.url.to_compact_string()
+                }
+            })
+            .unwrap_or_else(|| "invalid url".into())
+    }
+
+    fn fetcher_name(&self) -> &'static str {
+        "AzureDevOps"
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::AzureDevOps
+    }
+
+    fn is_third_party(&self) -> bool {
+        false
+    }
+
+    fn target(&self) -> &str {
+        &self.target_data.target
+    }
+
+    fn target_data(&self) -> &Arc<TargetDataErased> {
+        &self.target_data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extract_azure_repo() {
+        let expected = AzureRepo {
+            organization: "org".into(),
+            project: "project".into(),
+            repo: "repo".into(),
+        };
+
+        for url in [
+            "https://dev.azure.com/org/project/_git/repo",
+            "https://dev.azure.com/org/project/_git/repo.git",
+            "https://org.visualstudio.com/project/_git/repo",
+        ] {
+            assert_eq!(
+                AzureRepo::try_extract_from_url(&Url::parse(url).unwrap()),
+                Some(expected.clone())
+            );
+        }
+
+        for url in [
+            "https://dev.azure.com/org/project",
+            "https://dev.azure.com/org/project/_build",
+            "https://github.com/org/project/_git/repo",
+        ] {
+            assert_eq!(
+                AzureRepo::try_extract_from_url(&Url::parse(url).unwrap()),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use binstalk_downloader::download::DataVerifier;
+use binstalk_types::crate_info::ChecksumAlgorithm;
+use bytes::Bytes;
+use compact_str::CompactString;
+
+use crate::{
+    artifact_digest::{new_hasher, Hasher},
+    FetchError,
+};
+
+/// Algorithm of a sums file named e.g. `SHA512SUMS`, `pkg.tgz.sha256` or
+/// `pkg.tgz.b3`.
+fn algorithm_from_file_name(file_name: &str) -> Option<ChecksumAlgorithm> {
+    let file_name = file_name.to_ascii_lowercase();
+
+    if file_name.contains("sha512") {
+        Some(ChecksumAlgorithm::Sha512)
+    } else if file_name.contains("sha256") {
+        Some(ChecksumAlgorithm::Sha256)
+    } else if file_name.contains("blake3")
+        || file_name.contains("b3sum")
+        || file_name.ends_with(".b3")
+    {
+        Some(ChecksumAlgorithm::Blake3)
+    } else {
+        None
+    }
+}
+
+/// Algorithm of the tag of a BSD-style line, e.g. `SHA256 (pkg.tgz) = ...`.
+fn algorithm_from_tag(tag: &str) -> Option<ChecksumAlgorithm> {
+    match tag.to_ascii_uppercase().as_str() {
+        "SHA256" | "SHA2-256" => Some(ChecksumAlgorithm::Sha256),
+        "SHA512" | "SHA2-512" => Some(ChecksumAlgorithm::Sha512),
+        "BLAKE3" => Some(ChecksumAlgorithm::Blake3),
+        _ => None,
+    }
+}
+
+/// Algorithm guessed from the length of the digest, for sums files whose
+/// name does not tell it.
+fn algorithm_from_hex_len(len: usize) -> Option<ChecksumAlgorithm> {
+    match len {
+        64 => Some(ChecksumAlgorithm::Sha256),
+        128 => Some(ChecksumAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+/// Checksum of the package listed in a sums file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ExpectedChecksum {
+    pub(crate) algorithm: ChecksumAlgorithm,
+    pub(crate) digest: CompactString,
+}
+
+impl ExpectedChecksum {
+    pub(crate) fn sha256(digest: CompactString) -> Self {
+        Self {
+            algorithm: ChecksumAlgorithm::Sha256,
+            digest,
+        }
+    }
+
+    /// Find the checksum of `artifact_name` in the sums file `file_name`.
+    ///
+    /// Supports the formats of GNU `sha256sum`/`sha512sum` and `b3sum`
+    /// (`<digest>  <file>`, or `<digest> *<file>` in binary mode), the BSD
+    /// format (`SHA256 (<file>) = <digest>`) and files containing only the
+    /// digest of the artifact.
+    pub(crate) fn from_sums_file(
+        file_name: &str,
+        contents: &str,
+        artifact_name: &str,
+    ) -> Option<Self> {
+        let algorithm_of_file = algorithm_from_file_name(file_name);
+        let is_artifact = |name: &str| {
+            let name = name.strip_prefix("./").unwrap_or(name);
+            name == artifact_name || name.rsplit('/').next() == Some(artifact_name)
+        };
+
+        let mut lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let (algorithm, digest) = lines
+            .clone()
+            .find_map(|line| {
+                if let Some((tag, rest)) = line.split_once(" (") {
+                    // BSD format
+                    let (name, digest) = rest.rsplit_once(") = ")?;
+                    is_artifact(name).then_some((algorithm_from_tag(tag)?, digest))
+                } else {
+                    // GNU format, the line starts with `\` if the file name is escaped
+                    let line = line.strip_prefix('\\').unwrap_or(line);
+                    let (digest, name) = line.split_once(char::is_whitespace)?;
+                    let name = name.trim_start();
+                    let name = name.strip_prefix('*').unwrap_or(name);
+                    is_artifact(name).then_some((
+                        algorithm_of_file.or_else(|| algorithm_from_hex_len(digest.len()))?,
+                        digest,
+                    ))
+                }
+            })
+            // A sidecar file containing only the digest of the artifact
+            .or_else(|| match (lines.next(), lines.next()) {
+                (Some(digest), None) if !digest.contains(char::is_whitespace) => Some((
+                    algorithm_of_file.or_else(|| algorithm_from_hex_len(digest.len()))?,
+                    digest,
+                )),
+                _ => None,
+            })?;
+
+        (digest.len() == algorithm.hex_len() && digest.bytes().all(|b| b.is_ascii_hexdigit())).then(
+            || Self {
+                algorithm,
+                digest: digest.to_ascii_lowercase().into(),
+            },
+        )
+    }
+}
+
+/// [`DataVerifier`] that computes the checksum of the data before
+/// forwarding it to the inner verifier, so that it can be compared with the
+/// [`ExpectedChecksum`] once the download is done.
+pub(crate) struct ChecksumVerifier<'a> {
+    inner: &'a mut dyn DataVerifier,
+    hasher: Option<Box<dyn Hasher>>,
+    expected: Option<ExpectedChecksum>,
+}
+
+impl<'a> ChecksumVerifier<'a> {
+    pub(crate) fn new(inner: &'a mut dyn DataVerifier, expected: Option<ExpectedChecksum>) -> Self {
+        let hasher = expected
+            .as_ref()
+            .map(|expected| new_hasher(expected.algorithm));
+
+        Self {
+            inner,
+            hasher,
+            expected,
+        }
+    }
+
+    /// Return the checksum if it matches the data.
+    pub(crate) fn check(self) -> Result<Option<ExpectedChecksum>, FetchError> {
+        let (Some(hasher), Some(expected)) = (self.hasher, self.expected) else {
+            return Ok(None);
+        };
+
+        let actual = hasher.finalize_hex();
+
+        if actual == expected.digest {
+            Ok(Some(expected))
+        } else {
+            Err(FetchError::ChecksumMismatch {
+                algorithm: expected.algorithm.name(),
+                expected: expected.digest,
+                actual,
+            })
+        }
+    }
+}
+
+impl DataVerifier for ChecksumVerifier<'_> {
+    fn update(&mut self, data: &Bytes) {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(data);
+        }
+        self.inner.update(data);
+    }
+
+    fn validate(&mut self) -> bool {
+        self.inner.validate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_sums_file_formats() {
+        let sha512 = "ee26b0dd4af7e749aa1a8ee3c10ae9923f618980772e473f8819a5d4940e0db27ac185f8a0e1d5f84f88bc887fd67b143732c304cc5fa9ad8e6f57f50028a8ff";
+        let expected = |algorithm, digest: &str| {
+            Some(ExpectedChecksum {
+                algorithm,
+                digest: digest.into(),
+            })
+        };
+
+        // GNU
+        assert_eq!(
+            ExpectedChecksum::from_sums_file(
+                "SHA256SUMS",
+                &format!("{}  other.tgz\n{SHA256} *./pkg.tgz\n", "0".repeat(64)),
+                "pkg.tgz",
+            ),
+            expected(ChecksumAlgorithm::Sha256, SHA256)
+        );
+        // sha512sum
+        assert_eq!(
+            ExpectedChecksum::from_sums_file(
+                "checksums.txt",
+                &format!("{sha512}  pkg.tgz"),
+                "pkg.tgz"
+            ),
+            expected(ChecksumAlgorithm::Sha512, sha512)
+        );
+        // b3sum
+        assert_eq!(
+            ExpectedChecksum::from_sums_file("B3SUMS", &format!("{SHA256}  pkg.tgz"), "pkg.tgz"),
+            expected(ChecksumAlgorithm::Blake3, SHA256)
+        );
+        // BSD
+        assert_eq!(
+            ExpectedChecksum::from_sums_file(
+                "CHECKSUMS",
+                &format!("SHA512 (pkg.tgz) = {}", sha512.to_ascii_uppercase()),
+                "pkg.tgz",
+            ),
+            expected(ChecksumAlgorithm::Sha512, sha512)
+        );
+        // Sidecar
+        assert_eq!(
+            ExpectedChecksum::from_sums_file("pkg.tgz.sha256", SHA256, "pkg.tgz"),
+            expected(ChecksumAlgorithm::Sha256, SHA256)
+        );
+
+        assert_eq!(
+            ExpectedChecksum::from_sums_file(
+                "SHA256SUMS",
+                &format!("{SHA256}  other.tgz"),
+                "pkg.tgz"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_checksum_verifier() {
+        let mut inner = ();
+        let mut verifier =
+            ChecksumVerifier::new(&mut inner, Some(ExpectedChecksum::sha256(SHA256.into())));
+        verifier.update(&Bytes::from_static(b"test"));
+        assert_eq!(
+            verifier.check().unwrap(),
+            Some(ExpectedChecksum::sha256(SHA256.into()))
+        );
+
+        let mut verifier =
+            ChecksumVerifier::new(&mut inner, Some(ExpectedChecksum::sha256(SHA256.into())));
+        verifier.update(&Bytes::from_static(b"tset"));
+        assert!(verifier.check().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! `gs://` and `az://` urls of objects in Google Cloud Storage and
+//! Azure Blob Storage, which are downloaded through their https endpoints.
+
+use url::Url;
+
+use crate::FetchError;
+
+pub const GCS_DOMAIN: &str = "storage.googleapis.com";
+pub const AZURE_BLOB_DOMAIN: &str = "blob.core.windows.net";
+
+/// Azure Blob Storage only accepts bearer tokens with a recent api version.
+pub const AZURE_BLOB_AUTH_HEADERS: &[(&str, &str)] = &[("x-ms-version", "2020-04-08")];
+
+/// Translate `gs://{bucket}/{object}` to
+/// `https://storage.googleapis.com/{bucket}/{object}` and
+/// `az://{account}/{container}/{blob}` to
+/// `https://{account}.blob.core.windows.net/{container}/{blob}`.
+///
+/// Urls of other schemes are returned as-is.
+pub(crate) fn to_https_url(url: Url) -> Result<Url, FetchError> {
+    let (Some(host), path) = (url.host_str(), url.path()) else {
+        return Ok(url);
+    };
+
+    Ok(match url.scheme() {
+        "gs" => Url::parse(&format!("https://{GCS_DOMAIN}/{host}{path}"))?,
+        "az" => Url::parse(&format!("https://{host}.{AZURE_BLOB_DOMAIN}{path}"))?,
+        _ => url,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn translate(url: &str) -> String {
+        to_https_url(Url::parse(url).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_to_https_url() {
+        assert_eq!(
+            translate("gs://bucket/a/b-x86_64-unknown-linux-gnu.tgz"),
+            "https://storage.googleapis.com/bucket/a/b-x86_64-unknown-linux-gnu.tgz"
+        );
+        assert_eq!(
+            translate("az://account/container/a/b.zip"),
+            "https://account.blob.core.windows.net/container/a/b.zip"
+        );
+        assert_eq!(
+            translate("https://example.com/a.tgz"),
+            "https://example.com/a.tgz"
+        );
+    }
+}
@@ -9,7 +9,8 @@ use std::{
 };
 
 pub(super) use binstalk_downloader::{
-    download::{Download, ExtractedFiles},
+    download::{DataVerifier, Download, ExtractedFiles},
+    fips,
     remote::{Client, Url},
 };
 pub(super) use binstalk_git_repo_api::gh_api_client::GhApiClient;
@@ -23,6 +24,7 @@ use crate::FetchError;
 
 static WARN_RATE_LIMIT_ONCE: Once = Once::new();
 static WARN_UNAUTHORIZED_ONCE: Once = Once::new();
+static WARN_RELEASE_ASSETS_HIDDEN_ONCE: Once = Once::new();
 
 /// Return Ok(Some(api_artifact_url)) if exists, or Ok(None) if it doesn't.
 ///
@@ -37,6 +39,12 @@ pub(super) async fn get_gh_release_artifact_url(
     match gh_api_client.has_release_artifact(artifact).await {
         Ok(ret) => Ok(ret),
         Err(GhApiError::NotFound) => Ok(None),
+        Err(GhApiError::ReleaseAssetsHidden) => {
+            WARN_RELEASE_ASSETS_HIDDEN_ONCE.call_once(|| {
+                warn!("GitHub release exists but none of its assets are visible: they may still be uploading, or the release is a draft which requires a token with push access to the repository.");
+            });
+            Ok(None)
+        }
 
         Err(GhApiError::RateLimit { retry_after }) => {
             WARN_RATE_LIMIT_ONCE.call_once(|| {
@@ -73,7 +81,7 @@ pub(super) async fn does_url_exist(
     debug!("Checking for package at: '{url}'");
 
     if !GH_API_CLIENT_FAILED.load(Relaxed) {
-        if let Some(artifact) = GhReleaseArtifact::try_extract_from_url(url) {
+        if let Some(artifact) = gh_api_client.extract_release_artifact(url) {
             match get_gh_release_artifact_url(gh_api_client, artifact).await {
                 Ok(ret) => return Ok(ret.is_some()),
 
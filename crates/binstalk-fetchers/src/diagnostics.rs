@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Typed reason for [`crate::Fetcher::find`] not finding any package.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FindDiagnostic {
+    /// The crate specifies neither `pkg-url` nor `repository`.
+    MissingRepository,
+
+    /// The crate does not specify `pkg-url` and its repository is hosted
+    /// somewhere without a default `pkg-url`.
+    UnknownRepositoryHost,
+
+    /// The source does not provide pre-built binaries for the target.
+    UnsupportedTarget,
+
+    /// None of the candidate urls exists.
+    NoArtifact,
+
+    /// GitHub API is rate limited, so the search falls back to slower
+    /// and less reliable HEAD/GET requests.
+    RateLimited,
+
+    /// No GitHub token is available, so GitHub API is not used for
+    /// the search.
+    TokenAbsent,
+}
+
+impl fmt::Display for FindDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingRepository => {
+                "the crate specifies neither pkg-url nor the repository field"
+            }
+            Self::UnknownRepositoryHost => {
+                "the crate does not specify pkg-url and there is no default for its repository host"
+            }
+            Self::UnsupportedTarget => "the source does not provide pre-built for the target",
+            Self::NoArtifact => "none of the candidate urls exists",
+            Self::RateLimited => "GitHub API is rate limited",
+            Self::TokenAbsent => "no GitHub token is available",
+        })
+    }
+}
@@ -1,8 +1,8 @@
-use std::{borrow::Cow, fmt, iter, path::Path, sync::Arc};
+use std::{borrow::Cow, cmp::Reverse, fmt, iter, path::Path, sync::Arc};
 
-use binstalk_git_repo_api::gh_api_client::{GhApiError, GhReleaseArtifact, GhReleaseArtifactUrl};
-use binstalk_types::cargo_toml_binstall::Strategy;
-use compact_str::{CompactString, ToCompactString};
+use binstalk_git_repo_api::gh_api_client::{GhApiError, GhRelease, GhReleaseArtifactUrl};
+use binstalk_types::cargo_toml_binstall::{PkgSigning, SigningAlgorithm, Strategy};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use either::Either;
 use leon::Template;
 use once_cell::sync::OnceCell;
@@ -12,13 +12,23 @@ use tracing::{debug, info, trace, warn};
 use url::Url;
 
 use crate::{
-    common::*, futures_resolver::FuturesResolver, Data, FetchError, InvalidPkgFmtError, RepoInfo,
-    SignaturePolicy, SignatureVerifier, TargetDataErased, DEFAULT_GH_API_RETRY_DURATION,
+    cloud_storage, common::*, futures_resolver::FuturesResolver, key_discovery::discover_pubkey,
+    AllowedSigners, ArtifactInfo, ChecksumAlgorithm, ChecksumVerifier, Data, DigestRecorder,
+    ExpectedChecksum, FetchError, FindDiagnostic, RepoInfo, SignaturePolicy, SignatureVerifier,
+    TargetDataErased, UrlParseError, DEFAULT_GH_API_RETRY_DURATION,
 };
 
 pub const FETCHER_GH_CRATE_META: &str = "GhCrateMeta";
 
+/// Header of the SHA256 digest of the artifact, sent by JFrog Artifactory
+/// for artifacts in generic repositories.
+const CHECKSUM_SHA256_HEADER: &str = "x-checksum-sha256";
+
+/// Parts are numbered with three digits, starting from `.001`.
+const MAX_PKG_PARTS: u16 = 999;
+
 pub(crate) mod hosting;
+use hosting::RepositoryHost;
 
 pub struct GhCrateMeta {
     client: Client,
@@ -27,6 +37,10 @@ pub struct GhCrateMeta {
     target_data: Arc<TargetDataErased>,
     signature_policy: SignaturePolicy,
     resolution: OnceCell<Resolved>,
+    not_found_reason: OnceCell<FindDiagnostic>,
+    artifact: OnceCell<ArtifactInfo>,
+    /// pkg-fmt detected from the content of the package.
+    sniffed_pkg_fmt: OnceCell<PkgFmt>,
 }
 
 #[derive(Debug)]
@@ -38,9 +52,141 @@ struct Resolved {
     subcrate: Option<String>,
     gh_release_artifact_url: Option<GhReleaseArtifactUrl>,
     is_repo_private: bool,
+    /// SHA256 digest advertised by the server, checked after download.
+    sha256: Option<CompactString>,
+    /// Urls of the parts of a split package, empty if it is not split.
+    parts: Vec<Url>,
+    /// Whether pkg-fmt is unknown and has to be detected from the content
+    /// of the package, in which case `pkg_fmt` is only a placeholder.
+    sniff_pkg_fmt: bool,
 }
 
 impl GhCrateMeta {
+    /// Look for the package in the listing of the assets of the GitHub
+    /// release of the crate, for releases whose assets are not named after
+    /// the default pkg-url templates.
+    async fn find_in_release_assets(
+        &self,
+        pkg_fmts: impl Iterator<Item = PkgFmt> + Clone,
+        repo: Option<&str>,
+        subcrate: Option<&str>,
+        is_repo_private: bool,
+    ) -> Result<Option<Resolved>, FetchError> {
+        let Some(gh_repo) = repo
+            .and_then(|repo| Url::parse(repo).ok())
+            .and_then(|repo| self.gh_api_client.extract_repo(&repo))
+        else {
+            return Ok(None);
+        };
+
+        let version = &self.data.version;
+        let is_windows = self.target_data.target.contains("windows");
+
+        let tags = [format_compact!("v{version}"), version.clone()];
+        for tag in tags {
+            let release = GhRelease {
+                repo: gh_repo.clone(),
+                tag,
+            };
+            let names = match self
+                .gh_api_client
+                .list_release_artifacts(release.clone())
+                .await
+            {
+                Ok(Some(names)) => names,
+                Ok(None) | Err(GhApiError::NotFound) => continue,
+                Err(err) => {
+                    debug!("Failed to list the assets of {release:?}: {err}");
+                    return Ok(None);
+                }
+            };
+
+            let Some((name, pkg_fmt)) = best_release_asset(
+                &names,
+                &self.data.name,
+                &self.target_data.target,
+                pkg_fmts.clone(),
+                is_windows,
+            ) else {
+                continue;
+            };
+
+            let url = format!(
+                "{}/releases/download/{}/{name}",
+                gh_repo.repo_url()?,
+                release.tag
+            );
+            debug!("Found package in the assets of the release: '{url}'");
+
+            let resolver = FuturesResolver::default();
+            self.launch_baseline_find_tasks(
+                &resolver,
+                pkg_fmt,
+                &Template::parse(&url)?,
+                repo,
+                subcrate,
+                is_repo_private,
+            );
+            return Ok(resolver.resolve().await);
+        }
+
+        Ok(None)
+    }
+
+    /// Render `template` with the url and context the package is found with.
+    fn render_url_of_resolved(
+        &self,
+        resolved: &Resolved,
+        template: &Template<'_>,
+    ) -> Result<Url, FetchError> {
+        Context::from_data_with_repo(
+            &self.data,
+            &self.target_data.target,
+            &self.target_data.target_related_info,
+            resolved.archive_suffix.as_deref(),
+            resolved.repo.as_deref(),
+            resolved.subcrate.as_deref(),
+        )
+        .with_url(&resolved.url)
+        .render_url_with(template)
+    }
+
+    /// Download the sums file rendered from `checksum_file` and find the
+    /// checksum of the package in it.
+    async fn fetch_checksum(
+        &self,
+        resolved: &Resolved,
+        checksum_file: &str,
+    ) -> Result<ExpectedChecksum, FetchError> {
+        let checksum_url =
+            self.render_url_of_resolved(resolved, &Template::parse(checksum_file)?)?;
+
+        debug!(?checksum_url, "Downloading checksum file");
+        let contents = Download::new(self.client.clone(), checksum_url.clone())
+            .into_bytes()
+            .await?;
+
+        let file_name = |url: &Url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let expected = ExpectedChecksum::from_sums_file(
+            &file_name(&checksum_url),
+            &String::from_utf8_lossy(&contents),
+            &file_name(&resolved.url),
+        )
+        .ok_or_else(|| FetchError::MissingChecksum(Box::new(checksum_url)))?;
+
+        if fips::ENABLED && !expected.algorithm.is_fips_approved() {
+            return Err(FetchError::NotFipsApproved(expected.algorithm.name()));
+        }
+
+        Ok(expected)
+    }
+
     fn launch_baseline_find_tasks(
         &self,
         futures_resolver: &FuturesResolver<Resolved, FetchError>,
@@ -50,6 +196,7 @@ impl GhCrateMeta {
         subcrate: Option<&str>,
         is_repo_private: bool,
     ) {
+        let pkg_parts = self.target_data.meta.pkg_parts.filter(|parts| *parts > 1);
         let render_url = |ext| {
             let ctx = Context::from_data_with_repo(
                 &self.data,
@@ -90,7 +237,12 @@ impl GhCrateMeta {
             let repo = repo.map(ToString::to_string);
             let subcrate = subcrate.map(ToString::to_string);
             let archive_suffix = ext.map(ToString::to_string);
-            let gh_release_artifact = GhReleaseArtifact::try_extract_from_url(&url);
+            let parts: Vec<_> = pkg_parts
+                .map(|parts| (1..=parts).map(|part| part_url(&url, part)).collect())
+                .unwrap_or_default();
+            // The existence of a split package is checked using its first part
+            let check_url = parts.first().unwrap_or(&url).clone();
+            let gh_release_artifact = gh_api_client.extract_release_artifact(&check_url);
 
             async move {
                 debug!("Checking for package at: '{url}'");
@@ -103,6 +255,9 @@ impl GhCrateMeta {
                     archive_suffix,
                     is_repo_private,
                     gh_release_artifact_url: None,
+                    sha256: None,
+                    parts,
+                    sniff_pkg_fmt: false,
                 };
 
                 if let Some(artifact) = gh_release_artifact {
@@ -114,7 +269,11 @@ impl GhCrateMeta {
                                 resolved.gh_release_artifact_url = Some(artifact_url);
                                 return Ok(Some(resolved));
                             }
-                            Ok(None) => return Ok(None),
+                            Ok(None) if pkg_parts.is_some() || is_repo_private => return Ok(None),
+                            Ok(None) => {
+                                resolved.parts = discover_parts(&gh_api_client, &url).await?;
+                                return Ok((!resolved.parts.is_empty()).then_some(resolved));
+                            }
 
                             Err(GhApiError::RateLimit { retry_after }) => {
                                 sleep(retry_after.unwrap_or(DEFAULT_GH_API_RETRY_DURATION)).await;
@@ -126,14 +285,100 @@ impl GhCrateMeta {
                     }
                 }
 
-                Ok(Box::pin(client.remote_gettable(url))
-                    .await?
-                    .then_some(resolved))
+                // Only the first byte is requested, and the response is read
+                // so that the download can reuse the connection.
+                let response = Box::pin(
+                    client
+                        .get(check_url)
+                        .header("range", "bytes=0-0")
+                        .send(false),
+                )
+                .await?;
+                if !response.status().is_success() {
+                    response.discard_body().await;
+                    return Ok(None);
+                }
+                // The digest is of the first part only
+                if resolved.parts.is_empty() {
+                    resolved.sha256 = response
+                        .headers()
+                        .get(CHECKSUM_SHA256_HEADER)
+                        .and_then(|sha256| sha256.to_str().ok())
+                        .map(|sha256| sha256.trim().to_ascii_lowercase().into());
+                }
+                response.discard_body().await;
+
+                Ok(Some(resolved))
             }
         }));
     }
 }
 
+/// Pick the asset of `names` most likely to be the package of `crate_name`
+/// for `target`, among those named after the target with an extension of one
+/// of `pkg_fmts`.
+///
+/// Assets named after the crate are preferred, then those of the formats
+/// coming first.
+fn best_release_asset<'a>(
+    names: &'a [CompactString],
+    crate_name: &str,
+    target: &str,
+    pkg_fmts: impl Iterator<Item = PkgFmt> + Clone,
+    is_windows: bool,
+) -> Option<(&'a str, PkgFmt)> {
+    names
+        .iter()
+        .filter(|name| name.contains(target))
+        .filter_map(|name| {
+            let (index, pkg_fmt) = pkg_fmts.clone().enumerate().find(|(_, pkg_fmt)| {
+                pkg_fmt
+                    .extensions(is_windows)
+                    .iter()
+                    .any(|ext| !ext.is_empty() && name.ends_with(ext))
+            })?;
+            Some((name, pkg_fmt, index))
+        })
+        .max_by_key(|(name, _, index)| (name.contains(crate_name), Reverse(*index)))
+        .map(|(name, pkg_fmt, _)| (name.as_str(), pkg_fmt))
+}
+
+/// Return the url of `part` of the package at `url`.
+fn part_url(url: &Url, part: u16) -> Url {
+    let mut part_url = url.clone();
+    part_url.set_path(&format!("{}.{part:03}", url.path()));
+    part_url
+}
+
+/// Find the parts of the GitHub release artifact at `url` split for hosts
+/// limiting the size of files, by looking up `{url}.001`, `{url}.002`, ...
+/// in the cached listing of the release until one is missing, so that no
+/// request is sent for each of them.
+///
+/// Return an empty `Vec` if the package is not split.
+async fn discover_parts(gh_api_client: &GhApiClient, url: &Url) -> Result<Vec<Url>, FetchError> {
+    let mut parts = Vec::new();
+
+    for part in 1..=MAX_PKG_PARTS {
+        let part_url = part_url(url, part);
+
+        let Some(artifact) = gh_api_client.extract_release_artifact(&part_url) else {
+            break;
+        };
+        if get_gh_release_artifact_url(gh_api_client.clone(), artifact)
+            .await?
+            .is_none()
+        {
+            break;
+        }
+
+        debug!("Found package part at: '{part_url}'");
+        parts.push(part_url);
+    }
+
+    Ok(parts)
+}
+
 #[async_trait::async_trait]
 impl super::Fetcher for GhCrateMeta {
     fn new(
@@ -150,6 +395,9 @@ impl super::Fetcher for GhCrateMeta {
             target_data,
             signature_policy,
             resolution: OnceCell::new(),
+            not_found_reason: OnceCell::new(),
+            artifact: OnceCell::new(),
+            sniffed_pkg_fmt: OnceCell::new(),
         })
     }
 
@@ -162,6 +410,7 @@ impl super::Fetcher for GhCrateMeta {
             let is_repo_private = info.map(|info| info.is_private).unwrap_or_default();
 
             let mut pkg_fmt = self.target_data.meta.pkg_fmt;
+            let mut sniff_pkg_fmt = false;
 
             let pkg_urls = if let Some(pkg_url) = self.target_data.meta.pkg_url.as_deref() {
                 let template = Template::parse(pkg_url)?;
@@ -182,27 +431,25 @@ impl super::Fetcher for GhCrateMeta {
                     let version = &self.data.version;
                     let target = &self.target_data.target;
 
-                    if pkg_fmt.is_none() {
-                        return Err(InvalidPkgFmtError {
-                            crate_name: crate_name.clone(),
-                            version: version.clone(),
-                            target: target.into(),
-                            pkg_url: pkg_url.into(),
-                            reason:
-                                &"pkg-fmt is not specified, yet pkg-url does not contain format, \
-                                archive-format or archive-suffix which is required for automatically \
-                                deducing pkg-fmt",
-                        }
-                        .into());
+                    if let Some(pkg_fmt) = pkg_fmt {
+                        warn!(
+                            "Crate {crate_name}@{version} on target {target} does not specify pkg-fmt \
+                            but its pkg-url also does not contain key format, archive-format or \
+                            archive-suffix.\nbinstall was able to guess that from pkg-url, but \
+                            just note that it could be wrong:\npkg-fmt=\"{pkg_fmt}\", pkg-url=\"{pkg_url}\"",
+                        );
+                    } else {
+                        // e.g. GitHub API asset urls or `?raw=true` urls,
+                        // the pkg-fmt is detected from the magic bytes of
+                        // the package once downloaded instead.
+                        warn!(
+                            "Crate {crate_name}@{version} on target {target} does not specify pkg-fmt \
+                            and binstall cannot guess it from its pkg-url.\nIt will be detected from \
+                            the content of the package: pkg-url=\"{pkg_url}\""
+                        );
+                        sniff_pkg_fmt = true;
+                        pkg_fmt = Some(PkgFmt::Bin);
                     }
-
-                    warn!(
-                        "Crate {crate_name}@{version} on target {target} does not specify pkg-fmt \
-                        but its pkg-url also does not contain key format, archive-format or \
-                        archive-suffix.\nbinstall was able to guess that from pkg-url, but \
-                        just note that it could be wrong:\npkg-fmt=\"{pkg_fmt}\", pkg-url=\"{pkg_url}\"",
-                        pkg_fmt = pkg_fmt.unwrap(),
-                    );
                 }
 
                 Either::Left(iter::once(template))
@@ -232,6 +479,9 @@ impl super::Fetcher for GhCrateMeta {
                         repo, self.target_data.target
                     );
 
+                    self.not_found_reason
+                        .set(FindDiagnostic::UnknownRepositoryHost)
+                        .ok();
                     return Ok(false);
                 }
             } else {
@@ -243,6 +493,9 @@ impl super::Fetcher for GhCrateMeta {
                     self.target_data.target
                 );
 
+                self.not_found_reason
+                    .set(FindDiagnostic::MissingRepository)
+                    .ok();
                 return Ok(false);
             };
 
@@ -256,7 +509,10 @@ impl super::Fetcher for GhCrateMeta {
             let pkg_fmts = if let Some(pkg_fmt) = pkg_fmt {
                 Either::Left(iter::once(pkg_fmt))
             } else {
-                Either::Right(PkgFmt::iter())
+                let target = &*self.target_data.target;
+                Either::Right(
+                    PkgFmt::iter().filter(move |pkg_fmt| pkg_fmt.is_probed_by_default(target)),
+                )
             };
 
             let resolver = FuturesResolver::default();
@@ -279,18 +535,57 @@ impl super::Fetcher for GhCrateMeta {
                 }
             }
 
-            if let Some(resolved) = resolver.resolve().await {
+            let resolved = match resolver.resolve().await {
+                // Releases not named after the default templates may still
+                // have an asset for the target.
+                None if self.target_data.meta.pkg_url.is_none() => {
+                    this.find_in_release_assets(pkg_fmts, repo, subcrate, is_repo_private)
+                        .await?
+                }
+                resolved => resolved,
+            };
+
+            if let Some(mut resolved) = resolved {
+                resolved.sniff_pkg_fmt = sniff_pkg_fmt;
                 debug!(?resolved, "Winning URL found!");
                 self.resolution
                     .set(resolved)
                     .expect("find() should be only called once");
                 Ok(true)
             } else {
+                self.not_found_reason.set(FindDiagnostic::NoArtifact).ok();
                 Ok(false)
             }
         })
     }
 
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        let Some(&reason) = self.not_found_reason.get() else {
+            return Vec::new();
+        };
+
+        let mut diagnostics = vec![reason];
+
+        let github_host = match self.data.repo_info.get() {
+            Some(Some(RepoInfo {
+                repository_host: RepositoryHost::GitHub,
+                repo,
+                ..
+            })) => repo.host_str(),
+            _ => None,
+        };
+        if let (FindDiagnostic::NoArtifact, Some(host)) = (reason, github_host) {
+            if self.gh_api_client.is_rate_limited_for(host) {
+                diagnostics.push(FindDiagnostic::RateLimited);
+            }
+            if !self.gh_api_client.has_gh_token_for(host) {
+                diagnostics.push(FindDiagnostic::TokenAbsent);
+            }
+        }
+
+        diagnostics
+    }
+
     async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
         let resolved = self
             .resolution
@@ -312,16 +607,7 @@ impl super::Fetcher for GhCrateMeta {
                 };
                 trace!(?template, "parsed signature file template");
 
-                let sign_url = Context::from_data_with_repo(
-                    &self.data,
-                    &self.target_data.target,
-                    &self.target_data.target_related_info,
-                    resolved.archive_suffix.as_deref(),
-                    resolved.repo.as_deref(),
-                    resolved.subcrate.as_deref(),
-                )
-                .with_url(&resolved.url)
-                .render_url_with(&template)?;
+                let sign_url = self.render_url_of_resolved(resolved, &template)?;
 
                 debug!(?sign_url, "Downloading signature");
                 let signature = Download::new(self.client.clone(), sign_url)
@@ -329,7 +615,33 @@ impl super::Fetcher for GhCrateMeta {
                     .await?;
                 trace!(?signature, "got signature contents");
 
-                SignatureVerifier::new(config, &signature)?
+                if config.pubkey.is_some() {
+                    SignatureVerifier::new(config, &signature)?
+                } else if config.algorithm == SigningAlgorithm::Ssh {
+                    let allowed_signers = self.data.allowed_signers.as_deref();
+                    SignatureVerifier::new_ssh(
+                        allowed_signers.unwrap_or(&AllowedSigners::default()),
+                        &signature,
+                    )?
+                } else {
+                    let repo = resolved
+                        .repo
+                        .as_deref()
+                        .or(self.data.repo.as_deref())
+                        .ok_or_else(|| FetchError::PubkeyNotFound(self.data.name.clone()))?;
+                    let pubkey = discover_pubkey(
+                        &self.client,
+                        repo,
+                        self.data.pinned_keys.as_deref(),
+                        self.data.doh_resolver.as_ref(),
+                    )
+                    .await?;
+                    let config = PkgSigning {
+                        pubkey: Some(pubkey.to_string().into()),
+                        ..config.clone()
+                    };
+                    SignatureVerifier::new(&config, &signature)?
+                }
             }
         };
 
@@ -339,21 +651,74 @@ impl super::Fetcher for GhCrateMeta {
             fmt=?resolved.pkg_fmt,
             "Downloading package",
         );
+        let expected_checksum = match self.target_data.meta.checksum_file.as_deref() {
+            Some(checksum_file) => Some(self.fetch_checksum(resolved, checksum_file).await?),
+            None => resolved.sha256.clone().map(ExpectedChecksum::sha256),
+        };
+
         let mut data_verifier = verifier.data_verifier()?;
-        let files = match resolved.gh_release_artifact_url.as_ref() {
-            Some(artifact_url) if resolved.is_repo_private => self
-                .gh_api_client
-                .download_artifact(artifact_url.clone())
-                .await?
-                .with_data_verifier(data_verifier.as_mut()),
-            _ => Download::new_with_data_verifier(
+        let mut checksum_verifier =
+            ChecksumVerifier::new(data_verifier.as_mut(), expected_checksum);
+        let mut data_verifier =
+            DigestRecorder::new(&mut checksum_verifier, &self.data.digest_algorithms);
+        // Assets of drafts are only available via the API, and those of
+        // immutable releases are preferably downloaded from the pinned asset,
+        // which requires a token.
+        let api_download = match resolved.gh_release_artifact_url.as_ref() {
+            _ if !resolved.parts.is_empty() => None,
+            Some(artifact_url) if resolved.is_repo_private || artifact_url.is_draft() => Some(
+                self.gh_api_client
+                    .download_artifact(artifact_url.clone())
+                    .await?,
+            ),
+            Some(artifact_url) if artifact_url.is_immutable() => {
+                match self
+                    .gh_api_client
+                    .download_artifact(artifact_url.clone())
+                    .await
+                {
+                    Ok(download) => Some(download),
+                    Err(GhApiError::Unauthorized) => None,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            _ => None,
+        };
+        let download = match api_download {
+            _ if !resolved.parts.is_empty() => Download::new_parts_with_data_verifier(
+                self.client.clone(),
+                resolved.parts.clone(),
+                &mut data_verifier,
+            ),
+            Some(download) => download.with_data_verifier(&mut data_verifier),
+            None => Download::new_with_data_verifier(
                 self.client.clone(),
                 resolved.url.clone(),
-                data_verifier.as_mut(),
+                &mut data_verifier,
             ),
+        };
+        let download = if self.target_data.meta.extract_nested == Some(false) {
+            download
+        } else {
+            download.extract_nested_archive()
+        };
+        let download = if self.target_data.meta.preserve_symlinks == Some(true) {
+            download.preserve_symlinks()
+        } else {
+            download
         }
-        .and_extract(resolved.pkg_fmt, dst)
-        .await?;
+        .with_low_memory(self.data.low_memory);
+        let files = if resolved.sniff_pkg_fmt {
+            let (pkg_fmt, files) = download.and_extract_sniffed(dst).await?;
+            info!(
+                "Detected pkg-fmt=\"{pkg_fmt}\" from the content of package '{}'",
+                self.data.name
+            );
+            self.sniffed_pkg_fmt.set(pkg_fmt).ok();
+            files
+        } else {
+            download.and_extract(resolved.pkg_fmt, dst).await?
+        };
         trace!("validating signature (if any)");
         if data_verifier.validate() {
             if let Some(info) = verifier.info() {
@@ -362,14 +727,58 @@ impl super::Fetcher for GhCrateMeta {
                     self.data.name
                 );
             }
+            let mut artifact = data_verifier.into_artifact_info(resolved.url.clone());
+            if let Some(checksum) = checksum_verifier.check()? {
+                debug!(
+                    "Verified {} of package '{}'",
+                    checksum.algorithm.name(),
+                    self.data.name
+                );
+                if checksum.algorithm != ChecksumAlgorithm::Sha256 {
+                    artifact.digests.insert(checksum.algorithm, checksum.digest);
+                }
+            }
+            // The digest is of the first part only if the package is split,
+            // and verified even if a sums file is published as well.
+            if let Some(artifact_url) = resolved
+                .gh_release_artifact_url
+                .as_ref()
+                .filter(|_| resolved.parts.is_empty())
+            {
+                if let Some(sha256) = artifact_url.sha256() {
+                    let expected = sha256.to_ascii_lowercase();
+                    if artifact.sha256 != expected {
+                        return Err(FetchError::ChecksumMismatch {
+                            algorithm: ChecksumAlgorithm::Sha256.name(),
+                            expected: expected.into(),
+                            actual: artifact.sha256,
+                        });
+                    }
+                    info!(
+                        digest = artifact_url.digest(),
+                        immutable = artifact_url.is_immutable(),
+                        "Verified GitHub release digest of package '{}'",
+                        self.data.name
+                    );
+                    artifact.release_digest = artifact_url.digest().map(CompactString::from);
+                }
+            }
+            self.artifact.set(artifact).ok();
             Ok(files)
         } else {
             Err(FetchError::InvalidSignature)
         }
     }
 
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        self.artifact.get().cloned()
+    }
+
     fn pkg_fmt(&self) -> PkgFmt {
-        self.resolution.get().unwrap().pkg_fmt
+        self.sniffed_pkg_fmt
+            .get()
+            .copied()
+            .unwrap_or_else(|| self.resolution.get().unwrap().pkg_fmt)
     }
 
     fn target_meta(&self) -> PkgMeta {
@@ -433,7 +842,8 @@ struct Context<'c> {
     /// Workspace of the crate inside the repository.
     subcrate: Option<&'c str>,
 
-    /// Url of the file being downloaded (only for signing.file)
+    /// Url of the file being downloaded (only for signing.file and
+    /// checksum-file), which relative urls are resolved against
     url: Option<&'c Url>,
 
     target_related_info: &'c dyn leon::Values,
@@ -527,7 +937,13 @@ impl<'c> Context<'c> {
 
     fn render_url_with(&self, template: &Template<'_>) -> Result<Url, FetchError> {
         debug!(?template, context=?self, "render url template");
-        Ok(Url::parse(&template.render(self)?)?)
+        let rendered = template.render(self)?;
+        let url = match (Url::parse(&rendered), self.url) {
+            // e.g. a signature file next to the package but named differently
+            (Err(UrlParseError::RelativeUrlWithoutBase), Some(url)) => url.join(&rendered)?,
+            (url, _) => url?,
+        };
+        cloud_storage::to_https_url(url)
     }
 
     #[cfg(test)]
@@ -538,8 +954,8 @@ impl<'c> Context<'c> {
 
 #[cfg(test)]
 mod test {
-    use super::{super::Data, Context};
-    use compact_str::ToCompactString;
+    use super::{super::Data, best_release_asset, part_url, Context, PkgFmt};
+    use compact_str::{CompactString, ToCompactString};
     use url::Url;
 
     const DEFAULT_PKG_URL: &str = "{ repo }/releases/download/v{ version }/{ name }-{ target }-v{ version }.{ archive-format }";
@@ -568,6 +984,53 @@ mod test {
         assert_eq!(ctx.render_url(template).unwrap(), expected_url);
     }
 
+    #[test]
+    fn part_urls() {
+        let url = Url::parse("https://example.com/pkg.zip?token=1").unwrap();
+        assert_eq!(
+            part_url(&url, 2).as_str(),
+            "https://example.com/pkg.zip.002?token=1"
+        );
+    }
+
+    #[test]
+    fn best_release_assets() {
+        let names = [
+            "tool-x86_64-unknown-linux-gnu.tar.gz.sha256",
+            "other-x86_64-unknown-linux-gnu.tar.gz",
+            "tool-x86_64-unknown-linux-gnu.zip",
+            "tool-x86_64-unknown-linux-gnu.tar.gz",
+            "tool-aarch64-apple-darwin.tar.gz",
+        ]
+        .map(CompactString::from);
+        let pkg_fmts = [PkgFmt::Tgz, PkgFmt::Zip].into_iter();
+
+        assert_eq!(
+            best_release_asset(
+                &names,
+                "tool",
+                "x86_64-unknown-linux-gnu",
+                pkg_fmts.clone(),
+                false
+            ),
+            Some(("tool-x86_64-unknown-linux-gnu.tar.gz", PkgFmt::Tgz))
+        );
+        assert_eq!(
+            best_release_asset(
+                &names,
+                "other",
+                "aarch64-apple-darwin",
+                pkg_fmts.clone(),
+                false
+            ),
+            Some(("tool-aarch64-apple-darwin.tar.gz", PkgFmt::Tgz))
+        );
+        assert_eq!(
+            best_release_asset(&names, "tool", "x86_64-pc-windows-msvc", pkg_fmts, true),
+            None
+        );
+    }
+
     #[test]
     fn defaults() {
         assert_context_rendering(
@@ -613,6 +1076,38 @@ mod test {
         );
     }
 
+    #[test]
+    fn relative_to_url() {
+        let data = Data::new(
+            "cargo-watch".to_compact_string(),
+            "9.0.0".to_compact_string(),
+            Some("https://github.com/watchexec/cargo-watch".to_string()),
+        );
+        let target_info = leon::vals(|_| None);
+        let url = Url::parse("https://github.com/watchexec/cargo-watch/releases/download/v9.0.0/cargo-watch-v9.0.0-x86_64-unknown-linux-gnu.tgz").unwrap();
+
+        let mut ctx = Context::from_data_with_repo(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            &target_info,
+            Some(".tgz"),
+            data.repo.as_deref(),
+            None,
+        );
+        ctx.with_url(&url);
+
+        assert_eq!(
+            ctx.render_url("{ name }-{ target }.minisig").unwrap().as_str(),
+            "https://github.com/watchexec/cargo-watch/releases/download/v9.0.0/cargo-watch-x86_64-unknown-linux-gnu.minisig"
+        );
+        assert_eq!(
+            ctx.render_url("https://sigs.example.com/{ name }/{ version }.sig")
+                .unwrap()
+                .as_str(),
+            "https://sigs.example.com/cargo-watch/9.0.0.sig"
+        );
+    }
+
     #[test]
     fn deprecated_format() {
         assert_context_rendering(
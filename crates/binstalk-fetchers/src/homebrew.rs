@@ -0,0 +1,419 @@
+//! Bottles of Homebrew formulae, for crates packaged by Homebrew without
+//! pre-built binaries of their own.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use binstalk_downloader::remote::StatusCode;
+use binstalk_types::cargo_toml_binstall::Strategy;
+use compact_str::format_compact;
+use serde::Deserialize;
+use tracing::trace;
+
+use crate::{
+    common::*, ArtifactInfo, ChecksumVerifier, Data, DigestRecorder, ExpectedChecksum, FetchError,
+    FindDiagnostic, SignaturePolicy, SignatureVerifier, TargetDataErased,
+};
+
+const FORMULA_API_URL: &str = "https://formulae.brew.sh/api/formula";
+
+/// Bottles hosted on ghcr.io are public, but it still requires a bearer
+/// token, which can be anything for anonymous access.
+const GHCR_ANONYMOUS_TOKEN: &str = "QQ==";
+
+/// Bottles which do not need their paths rewritten to the Homebrew prefix,
+/// so that they work once copied anywhere.
+const RELOCATABLE_CELLAR: &str = ":any_skip_relocation";
+
+/// Tags of the bottles usable on `target`, the most compatible first.
+fn bottle_tags(target: &str) -> &'static [&'static str] {
+    match target {
+        // Bottles built for older macOS run on newer ones.
+        "aarch64-apple-darwin" => &[
+            "arm64_big_sur",
+            "arm64_monterey",
+            "arm64_ventura",
+            "arm64_sonoma",
+            "arm64_sequoia",
+            "all",
+        ],
+        "x86_64-apple-darwin" => &["big_sur", "monterey", "ventura", "sonoma", "sequoia", "all"],
+        "x86_64-unknown-linux-gnu" => &["x86_64_linux", "all"],
+        "aarch64-unknown-linux-gnu" => &["arm64_linux", "all"],
+        _ => &[],
+    }
+}
+
+/// Strip the scheme, `www.`, `.git` suffix and trailing slashes of `url`,
+/// which is lowercased so that it can be compared to another one.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url).to_owned()
+}
+
+/// Return true if `url` is `repo` or a page or file within it.
+fn is_within_repo(url: &str, repo: &str) -> bool {
+    let (url, repo) = (normalize_url(url), normalize_url(repo));
+    !repo.is_empty()
+        && url
+            .strip_prefix(&repo)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[derive(Deserialize)]
+struct Formula {
+    homepage: Option<String>,
+    #[serde(default)]
+    urls: BTreeMap<CompactString, SourceUrl>,
+    versions: Versions,
+    #[serde(default)]
+    revision: u32,
+    bottle: Bottles,
+}
+
+#[derive(Deserialize)]
+struct SourceUrl {
+    url: String,
+}
+
+impl Formula {
+    /// Return true if the homepage or a source url of the formula is within
+    /// `repo`, so that a formula merely named like the crate is not used.
+    fn is_of_repo(&self, repo: &str) -> bool {
+        self.homepage
+            .iter()
+            .chain(self.urls.values().map(|source| &source.url))
+            .any(|url| is_within_repo(url, repo))
+    }
+}
+
+#[derive(Deserialize)]
+struct Versions {
+    stable: CompactString,
+}
+
+#[derive(Deserialize)]
+struct Bottles {
+    stable: Option<Bottle>,
+}
+
+#[derive(Deserialize)]
+struct Bottle {
+    files: BTreeMap<CompactString, BottleFile>,
+}
+
+#[derive(Deserialize)]
+struct BottleFile {
+    cellar: CompactString,
+    url: Url,
+    sha256: CompactString,
+}
+
+#[derive(Debug)]
+struct FoundBottle {
+    url: Url,
+    sha256: CompactString,
+    /// Directory of the formula in the bottle, e.g. `ripgrep/14.1.1`.
+    prefix: CompactString,
+}
+
+pub struct Homebrew {
+    client: Client,
+    data: Arc<Data>,
+    target_data: Arc<TargetDataErased>,
+    signature_policy: SignaturePolicy,
+
+    diagnostic: OnceLock<FindDiagnostic>,
+    resolved: OnceLock<FoundBottle>,
+    artifact: OnceLock<ArtifactInfo>,
+}
+
+impl Homebrew {
+    /// Return `Ok(None)` if there is no formula named after the crate, or if
+    /// it is not built from the repository of the crate.
+    async fn get_formula(&self) -> Result<Option<Formula>, FetchError> {
+        let Some(repo) = self.data.repo.as_deref() else {
+            debug!("Crate has no repository to match a Homebrew formula against");
+            return Ok(None);
+        };
+
+        let url = Url::parse(&format!("{FORMULA_API_URL}/{}.json", self.data.name))?;
+        debug!(%url, "Querying Homebrew formula");
+
+        let response = self.client.get(url).send(false).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let formula: Formula = response.error_for_status()?.json().await?;
+
+        if !formula.is_of_repo(repo) {
+            debug!(
+                "Homebrew formula {} is not built from {repo}",
+                self.data.name
+            );
+            return Ok(None);
+        }
+        Ok(Some(formula))
+    }
+
+    /// Find the bottle of `formula` usable on the target, if it packages the
+    /// version of the crate.
+    fn find_bottle(&self, formula: Formula) -> Option<FoundBottle> {
+        if formula.versions.stable != self.data.version {
+            debug!(
+                "Homebrew only provides the latest version {}",
+                formula.versions.stable
+            );
+            return None;
+        }
+
+        let mut files = formula.bottle.stable?.files;
+        let (tag, file) = bottle_tags(&self.target_data.target)
+            .iter()
+            .find_map(|tag| Some((tag, files.remove(*tag)?)))?;
+
+        if file.cellar != RELOCATABLE_CELLAR {
+            debug!("Bottle {tag} is not relocatable");
+            return None;
+        }
+
+        let version = &formula.versions.stable;
+        let prefix = match formula.revision {
+            0 => format_compact!("{}/{version}", self.data.name),
+            revision => format_compact!("{}/{version}_{revision}", self.data.name),
+        };
+
+        Some(FoundBottle {
+            url: file.url,
+            sha256: file.sha256,
+            prefix,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Fetcher for Homebrew {
+    fn new(
+        client: Client,
+        _gh_api_client: GhApiClient,
+        data: Arc<Data>,
+        target_data: Arc<TargetDataErased>,
+        signature_policy: SignaturePolicy,
+    ) -> Arc<dyn super::Fetcher> {
+        Arc::new(Self {
+            client,
+            data,
+            target_data,
+            signature_policy,
+
+            diagnostic: OnceLock::new(),
+            resolved: OnceLock::new(),
+            artifact: OnceLock::new(),
+        })
+    }
+
+    fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
+        tokio::spawn(async move {
+            if bottle_tags(&self.target_data.target).is_empty() {
+                self.diagnostic.set(FindDiagnostic::UnsupportedTarget).ok();
+                return Ok(false);
+            }
+
+            // Bottles are not signed.
+            if self.signature_policy == SignaturePolicy::Require {
+                return Err(FetchError::MissingSignature);
+            }
+
+            let Some(found) = self
+                .get_formula()
+                .await?
+                .and_then(|formula| self.find_bottle(formula))
+            else {
+                self.diagnostic.set(FindDiagnostic::NoArtifact).ok();
+                return Ok(false);
+            };
+
+            debug!(?found, "Found Homebrew bottle");
+            if self.resolved.set(found).is_err() {
+                panic!("<Homebrew as Fetcher>::find is run twice");
+            }
+            Ok(true)
+        })
+    }
+
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        self.diagnostic.get().copied().into_iter().collect()
+    }
+
+    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
+        let Some(found) = self.resolved.get() else {
+            panic!("<Homebrew as Fetcher>::find has not been called yet!")
+        };
+
+        debug!(url=%found.url, "Downloading bottle");
+        let mut data_verifier = SignatureVerifier::Noop.data_verifier()?;
+        let mut checksum_verifier = ChecksumVerifier::new(
+            data_verifier.as_mut(),
+            Some(ExpectedChecksum::sha256(found.sha256.clone())),
+        );
+        let mut data_verifier =
+            DigestRecorder::new(&mut checksum_verifier, &self.data.digest_algorithms);
+
+        let response = self
+            .client
+            .get(found.url.clone())
+            .bearer_auth(&GHCR_ANONYMOUS_TOKEN)
+            .send(true)
+            .await?;
+        let files = Download::from_response_with_data_verifier(response, &mut data_verifier)
+            .with_low_memory(self.data.low_memory)
+            .and_extract(self.pkg_fmt(), dst)
+            .await?;
+
+        trace!("validating checksum of the bottle");
+        let artifact = data_verifier.into_artifact_info(found.url.clone());
+        checksum_verifier.check()?;
+        self.artifact.set(artifact).ok();
+
+        Ok(files)
+    }
+
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        self.artifact.get().cloned()
+    }
+
+    fn pkg_fmt(&self) -> PkgFmt {
+        PkgFmt::Tgz
+    }
+
+    fn target_meta(&self) -> PkgMeta {
+        let mut meta = self.target_data.meta.clone();
+        meta.pkg_fmt = Some(self.pkg_fmt());
+        if let Some(found) = self.resolved.get() {
+            meta.bin_dir = Some(format!("{}/bin/{{ bin }}{{ binary-ext }}", found.prefix).into());
+        }
+        meta
+    }
+
+    fn source_name(&self) -> CompactString {
+        self.resolved
+            .get()
+            .and_then(|found| found.url.host_str())
+            .map(CompactString::from)
+            .unwrap_or_else(|| "Homebrew".into())
+    }
+
+    fn fetcher_name(&self) -> &'static str {
+        "Homebrew"
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::Homebrew
+    }
+
+    fn is_third_party(&self) -> bool {
+        true
+    }
+
+    fn target(&self) -> &str {
+        &self.target_data.target
+    }
+
+    fn target_data(&self) -> &Arc<TargetDataErased> {
+        &self.target_data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_formula_de() {
+        let formula: Formula = serde_json::from_str(
+            r#"{
+                "name": "ripgrep",
+                "homepage": "https://github.com/BurntSushi/ripgrep",
+                "urls": {
+                    "stable": {
+                        "url": "https://github.com/BurntSushi/ripgrep/archive/refs/tags/14.1.1.tar.gz",
+                        "tag": null
+                    },
+                    "head": {
+                        "url": "https://github.com/BurntSushi/ripgrep.git",
+                        "branch": "master"
+                    }
+                },
+                "versions": { "stable": "14.1.1", "head": "HEAD", "bottle": true },
+                "revision": 1,
+                "bottle": {
+                    "stable": {
+                        "rebuild": 0,
+                        "root_url": "https://ghcr.io/v2/homebrew/core",
+                        "files": {
+                            "arm64_sonoma": {
+                                "cellar": ":any",
+                                "url": "https://ghcr.io/v2/homebrew/core/ripgrep/blobs/sha256:aa",
+                                "sha256": "aa"
+                            },
+                            "x86_64_linux": {
+                                "cellar": ":any_skip_relocation",
+                                "url": "https://ghcr.io/v2/homebrew/core/ripgrep/blobs/sha256:bb",
+                                "sha256": "bb"
+                            }
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(formula.versions.stable, "14.1.1");
+        assert_eq!(formula.revision, 1);
+
+        let files = formula.bottle.stable.unwrap().files;
+        assert_eq!(files["x86_64_linux"].cellar, RELOCATABLE_CELLAR);
+        assert_eq!(files["arm64_sonoma"].sha256, "aa");
+    }
+
+    #[test]
+    fn test_is_of_repo() {
+        let formula = |homepage: &str, source: &str| Formula {
+            homepage: Some(homepage.into()),
+            urls: [("stable".into(), SourceUrl { url: source.into() })].into(),
+            versions: Versions {
+                stable: "1.0.0".into(),
+            },
+            revision: 0,
+            bottle: Bottles { stable: None },
+        };
+
+        let repo = "https://github.com/BurntSushi/ripgrep";
+        assert!(formula(
+            "https://example.com",
+            "https://github.com/burntsushi/ripgrep/archive/1.0.0.tar.gz"
+        )
+        .is_of_repo(repo));
+        assert!(formula("http://www.github.com/BurntSushi/ripgrep/", "").is_of_repo(repo));
+        assert!(formula(repo, "").is_of_repo(&format!("{repo}.git")));
+
+        assert!(
+            !formula("https://example.com", "https://example.com/ripgrep.tar.gz").is_of_repo(repo)
+        );
+        assert!(!formula("https://github.com/BurntSushi/ripgrep-all", "").is_of_repo(repo));
+        assert!(!formula("https://github.com/BurntSushi/ripgrep", "").is_of_repo(""));
+    }
+
+    #[test]
+    fn test_bottle_tags() {
+        assert_eq!(bottle_tags("x86_64-unknown-linux-gnu")[0], "x86_64_linux");
+        assert!(bottle_tags("aarch64-apple-darwin").contains(&"arm64_sonoma"));
+        assert!(bottle_tags("x86_64-unknown-linux-musl").is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use binstalk_downloader::remote::{header::ACCEPT, Client, StatusCode};
+use compact_str::{format_compact, CompactString};
+use minisign_verify::PublicKey;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::FetchError;
+
+/// DNS-over-HTTPS resolver used to look up the TXT record of the key, unless
+/// another one is configured.
+const DOH_RESOLVER: &str = "https://cloudflare-dns.com/dns-query";
+
+/// Type of TXT records in DNS.
+const DNS_TYPE_TXT: u16 = 16;
+
+/// Signing keys discovered from the hosts of repositories, pinned after the
+/// first use so that a compromised host cannot swap the key later.
+///
+/// The keys are stored as `<host> <pubkey>` lines.
+#[derive(Debug, Default)]
+pub struct PinnedKeys {
+    path: Option<PathBuf>,
+    keys: Mutex<BTreeMap<CompactString, CompactString>>,
+}
+
+impl PinnedKeys {
+    /// Load the keys pinned in `path`, which discovered keys are added to.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let keys = match fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| line.trim().split_once(' '))
+                .map(|(host, key)| (host.into(), key.trim().into()))
+                .collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            keys: Mutex::new(keys),
+        })
+    }
+
+    fn get(&self, host: &str) -> Option<CompactString> {
+        self.keys.lock().unwrap().get(host).cloned()
+    }
+
+    /// Hosts and the keys pinned for them, sorted by host.
+    pub fn keys(&self) -> Vec<(CompactString, CompactString)> {
+        self.keys
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host, key)| (host.clone(), key.clone()))
+            .collect()
+    }
+
+    /// Pin `key` for `host`, replacing the key pinned before if any.
+    pub fn insert(&self, host: &str, key: &str) -> io::Result<()> {
+        let mut keys = self.keys.lock().unwrap();
+        keys.insert(host.into(), key.into());
+        self.save(&keys)
+    }
+
+    /// Unpin the keys of host `host_or_key`, or equal to it, returning
+    /// the number of keys unpinned.
+    pub fn remove(&self, host_or_key: &str) -> io::Result<usize> {
+        let mut keys = self.keys.lock().unwrap();
+        let len = keys.len();
+        keys.retain(|host, key| host != host_or_key && key != host_or_key);
+
+        let removed = len - keys.len();
+        if removed > 0 {
+            self.save(&keys)?;
+        }
+        Ok(removed)
+    }
+
+    fn save(&self, keys: &BTreeMap<CompactString, CompactString>) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let contents: String = keys
+            .iter()
+            .map(|(host, key)| format!("{host} {key}\n"))
+            .collect();
+        fs::write(path, contents)
+    }
+
+    fn pin(&self, host: &str, key: &str) {
+        self.keys.lock().unwrap().insert(host.into(), key.into());
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let res = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{host} {key}"));
+        if let Err(err) = res {
+            warn!(
+                "Failed to pin signing key of {host} in {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DnsResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsAnswer {
+    #[serde(rename = "type")]
+    ty: u16,
+    data: String,
+}
+
+/// Return the public key in `contents`, ignoring the untrusted comment
+/// minisign puts before the key.
+fn parse_pubkey(contents: &str) -> Option<&str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+}
+
+/// Return the minisign public key in `contents`, the `.pub` file minisign
+/// generates or the key alone, if it is valid.
+pub fn parse_minisign_pubkey(contents: &str) -> Option<&str> {
+    parse_pubkey(contents).filter(|key| PublicKey::from_base64(key).is_ok())
+}
+
+/// Return the contents of TXT record `data`, which is split into quoted
+/// strings of at most 255 bytes.
+fn parse_txt_record(data: &str) -> String {
+    data.split('"').skip(1).step_by(2).collect::<String>()
+}
+
+async fn fetch_well_known_key(client: &Client, host: &str) -> Result<Option<String>, FetchError> {
+    let url = Url::parse(&format!("https://{host}/.well-known/binstall-key"))?;
+    debug!(%url, "Looking up signing key");
+
+    let response = client.get(url).send(false).await?;
+    if response.status() != StatusCode::OK {
+        return Ok(None);
+    }
+    let contents = response.bytes().await?;
+
+    Ok(parse_pubkey(&String::from_utf8_lossy(&contents)).map(ToString::to_string))
+}
+
+async fn fetch_dns_key(
+    client: &Client,
+    host: &str,
+    doh_resolver: Option<&Url>,
+) -> Result<Option<String>, FetchError> {
+    let mut url = match doh_resolver {
+        Some(doh_resolver) => doh_resolver.clone(),
+        None => Url::parse(DOH_RESOLVER)?,
+    };
+    url.query_pairs_mut()
+        .append_pair("name", &format!("_binstall-key.{host}"))
+        .append_pair("type", "TXT");
+    debug!(%url, "Looking up signing key");
+
+    let response = client
+        .get(url)
+        .header(ACCEPT.as_str(), "application/dns-json")
+        .send(false)
+        .await?;
+    if response.status() != StatusCode::OK {
+        return Ok(None);
+    }
+    let response: DnsResponse = response.json().await?;
+
+    Ok(response
+        .answer
+        .into_iter()
+        .find(|answer| answer.ty == DNS_TYPE_TXT)
+        .map(|answer| parse_txt_record(&answer.data)))
+}
+
+/// Discover the signing key of the host of `repo` from
+/// `https://<host>/.well-known/binstall-key`, or else the TXT record of
+/// `_binstall-key.<host>` looked up with `doh_resolver`.
+///
+/// The key is pinned on first use and the pinned key is used afterwards.
+pub(crate) async fn discover_pubkey(
+    client: &Client,
+    repo: &str,
+    pinned_keys: Option<&PinnedKeys>,
+    doh_resolver: Option<&Url>,
+) -> Result<CompactString, FetchError> {
+    let repo = Url::parse(repo)?;
+    let host = repo
+        .host_str()
+        .ok_or_else(|| FetchError::PubkeyNotFound(repo.as_str().into()))?;
+
+    if let Some(key) = pinned_keys.and_then(|pinned_keys| pinned_keys.get(host)) {
+        debug!("Using signing key pinned for {host}");
+        return Ok(key);
+    }
+
+    let key = match fetch_well_known_key(client, host).await? {
+        Some(key) => key,
+        None => fetch_dns_key(client, host, doh_resolver)
+            .await?
+            .ok_or_else(|| FetchError::PubkeyNotFound(host.into()))?,
+    };
+    let key = format_compact!("{}", key.trim());
+
+    info!("Discovered signing key of {host}: {key}");
+    if let Some(pinned_keys) = pinned_keys {
+        pinned_keys.pin(host, &key);
+    }
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pinned_keys() {
+        let pinned_keys = PinnedKeys::default();
+        pinned_keys.insert("a.example", "key-a").unwrap();
+        pinned_keys.insert("b.example", "key-b").unwrap();
+        pinned_keys.insert("a.example", "key-c").unwrap();
+
+        assert_eq!(pinned_keys.remove("key-b").unwrap(), 1);
+        assert_eq!(pinned_keys.remove("c.example").unwrap(), 0);
+        assert_eq!(
+            pinned_keys.keys(),
+            [(
+                CompactString::from("a.example"),
+                CompactString::from("key-c")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_key() {
+        const KEY: &str = "RWSGOq2NVecA2UPNdBUZykf1CCb147pkmdtYxgb3Ti+JO/wCYvhbAb/U";
+
+        assert_eq!(
+            parse_pubkey(&format!(
+                "untrusted comment: minisign public key 43E9A92B3FA88D4\n{KEY}\n"
+            )),
+            Some(KEY)
+        );
+        assert_eq!(parse_minisign_pubkey(KEY), Some(KEY));
+        assert_eq!(parse_minisign_pubkey("ssh-ed25519 AAAA"), None);
+        assert_eq!(
+            parse_txt_record(&format!("\"{}\" \"{}\"", &KEY[..20], &KEY[20..])),
+            KEY
+        );
+    }
+}
@@ -4,7 +4,10 @@ use std::{path::Path, sync::Arc, time::Duration};
 
 use binstalk_downloader::{download::DownloadError, remote::Error as RemoteError};
 use binstalk_git_repo_api::gh_api_client::{GhApiError, GhRepo, RepoInfo as GhRepoInfo};
-use binstalk_types::cargo_toml_binstall::{SigningAlgorithm, Strategy};
+use binstalk_types::{
+    cargo_toml_binstall::{SigningAlgorithm, Strategy},
+    crate_info::{ArtifactInfo, ChecksumAlgorithm},
+};
 use thiserror::Error as ThisError;
 use tokio::{sync::OnceCell, task::JoinError, time::sleep};
 pub use url::ParseError as UrlParseError;
@@ -17,14 +20,40 @@ mod quickinstall;
 #[cfg(feature = "quickinstall")]
 pub use quickinstall::*;
 
+mod azure_devops;
+pub use azure_devops::*;
+
+mod homebrew;
+pub use homebrew::*;
+
+mod wapm;
+pub use wapm::*;
+
+mod cloud_storage;
+pub use cloud_storage::{AZURE_BLOB_AUTH_HEADERS, AZURE_BLOB_DOMAIN, GCS_DOMAIN};
+
 mod common;
 use common::*;
 
+mod diagnostics;
+pub use diagnostics::FindDiagnostic;
+
+mod artifact_digest;
+use artifact_digest::DigestRecorder;
+
+mod checksum;
+use checksum::{ChecksumVerifier, ExpectedChecksum};
+
 mod signing;
-use signing::*;
+pub use signing::{AllowedSigners, SignatureVerifier};
+
+mod key_discovery;
+pub use key_discovery::{parse_minisign_pubkey, PinnedKeys};
 
 mod futures_resolver;
 
+pub mod rekor;
+
 use gh_crate_meta::hosting::RepositoryHost;
 
 static DEFAULT_GH_API_RETRY_DURATION: Duration = Duration::from_secs(1);
@@ -71,6 +100,48 @@ pub enum FetchError {
     #[error("Failed to verify signature")]
     InvalidSignature,
 
+    #[error("Signing key of {0} is not found in its .well-known/binstall-key nor DNS")]
+    PubkeyNotFound(CompactString),
+
+    /// The artifact does not match the checksum listed in the sums file of
+    /// the crate or advertised by the server, e.g. `X-Checksum-Sha256` of
+    /// JFrog Artifactory or the digest of GitHub release assets.
+    #[error("Checksum mismatch: expected {algorithm} {expected}, found {actual}")]
+    ChecksumMismatch {
+        algorithm: &'static str,
+        expected: CompactString,
+        actual: CompactString,
+    },
+
+    #[error("Checksum of the package is not listed in {0}")]
+    MissingChecksum(Box<Url>),
+
+    /// The package is verified with an algorithm not approved by FIPS 140-3,
+    /// which builds with the `fips` feature refuse.
+    #[error("{0} is not approved by FIPS 140-3, which is required by this build")]
+    NotFipsApproved(&'static str),
+
+    #[error("Artifact with sha256 {0} is not found in the Rekor transparency log")]
+    NotInTransparencyLog(CompactString),
+
+    #[error(
+        "Digest of the artifact is unknown, so it cannot be found in the Rekor transparency log"
+    )]
+    UnknownArtifactDigest,
+
+    /// The artifact was found but failed signature or extraction checks,
+    /// with the build info of the artifact attached for triage.
+    #[error(
+        "{err}\n\
+        Build info of {package}: {build_info}\n\
+        Please report this to https://github.com/cargo-bins/cargo-quickinstall/issues"
+    )]
+    QuickInstallArtifact {
+        package: Box<str>,
+        build_info: Box<str>,
+        err: Box<FetchError>,
+    },
+
     #[error("Failed to wait for task: {0}")]
     TaskJoinError(#[from] JoinError),
 }
@@ -114,6 +185,21 @@ pub trait Fetcher: Send + Sync {
     /// fatal conditions only.
     fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>>;
 
+    /// Explain why [`Fetcher::find`] returned `false`.
+    ///
+    /// Must only be called after [`Fetcher::find`] is done, returns an
+    /// empty `Vec` if the reason is unknown.
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        Vec::new()
+    }
+
+    /// Return the url and digest of the artifact downloaded.
+    ///
+    /// Must only be called after [`Fetcher::fetch_and_extract`] succeeds.
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        None
+    }
+
     /// Report to upstream that cargo-binstall tries to use this fetcher.
     /// Currently it is only overriden by [`quickinstall::QuickInstall`].
     fn report_to_upstream(self: Arc<Self>) {}
@@ -174,6 +260,11 @@ pub struct Data {
     version: CompactString,
     repo: Option<String>,
     repo_info: OnceCell<Option<RepoInfo>>,
+    pinned_keys: Option<Arc<PinnedKeys>>,
+    doh_resolver: Option<Url>,
+    allowed_signers: Option<Arc<AllowedSigners>>,
+    digest_algorithms: Vec<ChecksumAlgorithm>,
+    low_memory: bool,
 }
 
 impl Data {
@@ -183,9 +274,53 @@ impl Data {
             version,
             repo,
             repo_info: OnceCell::new(),
+            pinned_keys: None,
+            doh_resolver: None,
+            allowed_signers: None,
+            digest_algorithms: Vec::new(),
+            low_memory: false,
         }
     }
 
+    /// Pin the signing keys discovered for the crate in `pinned_keys`.
+    pub fn with_pinned_keys(mut self, pinned_keys: Arc<PinnedKeys>) -> Self {
+        self.pinned_keys = Some(pinned_keys);
+        self
+    }
+
+    /// Look up the signing keys published in DNS with `doh_resolver`, or
+    /// Cloudflare if it is `None`.
+    pub fn with_doh_resolver(mut self, doh_resolver: Option<Url>) -> Self {
+        self.doh_resolver = doh_resolver;
+        self
+    }
+
+    /// Verify ssh signatures of packages without `pubkey` in their metadata
+    /// against the local `allowed_signers`.
+    pub fn with_allowed_signers(mut self, allowed_signers: Arc<AllowedSigners>) -> Self {
+        self.allowed_signers = Some(allowed_signers);
+        self
+    }
+
+    /// Record the digests of the artifacts downloaded with `algorithms` in
+    /// [`Fetcher::artifact`], in addition to their SHA256 digest.
+    pub fn with_digest_algorithms(mut self, algorithms: Vec<ChecksumAlgorithm>) -> Self {
+        self.digest_algorithms = algorithms;
+        self
+    }
+
+    /// Reduce the memory used to extract packages if `low_memory` is true,
+    /// see [`binstalk_downloader::download::Download::with_low_memory`].
+    pub fn with_low_memory(mut self, low_memory: bool) -> Self {
+        self.low_memory = low_memory;
+        self
+    }
+
+    /// Return whether the memory used to extract packages is reduced.
+    pub fn low_memory(&self) -> bool {
+        self.low_memory
+    }
+
     #[instrument(skip(client))]
     async fn get_repo_info(&self, client: &GhApiClient) -> Result<Option<&RepoInfo>, FetchError> {
         async fn gh_get_repo_info(
@@ -208,13 +343,20 @@ impl Data {
             repo: &str,
             client: &GhApiClient,
         ) -> Result<RepoInfo, FetchError> {
+            // GitHub Enterprise Servers are usually not hosted on a domain
+            // starting with `github`, so check the hosts known to the client.
+            let guess_git_hosting_services = |repo: &Url| match repo.host_str() {
+                Some(host) if client.is_gh_host(host) => RepositoryHost::GitHub,
+                _ => RepositoryHost::guess_git_hosting_services(repo),
+            };
+
             let repo = Url::parse(repo)?;
             let mut repo = client
                 .remote_client()
                 .get_redirected_final_url(repo.clone())
                 .await
                 .unwrap_or(repo);
-            let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
+            let repository_host = guess_git_hosting_services(&repo);
 
             let subcrate = RepoInfo::detect_subcrate(&mut repo, repository_host);
 
@@ -223,9 +365,9 @@ impl Data {
                 .strip_suffix(".git")
                 .and_then(|s| Url::parse(s).ok())
             {
-                let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
-                match GhRepo::try_extract_from_url(&repo) {
-                    Some(gh_repo) if client.has_gh_token() => {
+                let repository_host = guess_git_hosting_services(&repo);
+                match client.extract_repo(&repo) {
+                    Some(gh_repo) if client.has_gh_token_for(&gh_repo.host) => {
                         if let Ok(gh_repo_info) = gh_get_repo_info(client, &gh_repo).await {
                             return Ok(RepoInfo {
                                 subcrate,
@@ -241,7 +383,7 @@ impl Data {
                         {
                             return Ok(RepoInfo {
                                 subcrate,
-                                repository_host: RepositoryHost::guess_git_hosting_services(&repo),
+                                repository_host: guess_git_hosting_services(&repo),
                                 repo,
                                 is_private: false,
                             });
@@ -251,8 +393,8 @@ impl Data {
             }
 
             Ok(RepoInfo {
-                is_private: match GhRepo::try_extract_from_url(&repo) {
-                    Some(gh_repo) if client.has_gh_token() => {
+                is_private: match client.extract_repo(&repo) {
+                    Some(gh_repo) if client.has_gh_token_for(&gh_repo.host) => {
                         gh_get_repo_info(client, &gh_repo).await?.is_private()
                     }
                     _ => false,
@@ -4,15 +4,15 @@ use std::{
     sync::{Arc, Mutex, OnceLock},
 };
 
-use binstalk_downloader::remote::Method;
+use binstalk_downloader::{download::DownloadError, remote::Method};
 use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgMeta, PkgSigning, Strategy};
 use tokio::sync::OnceCell;
 use tracing::{error, info, trace};
 use url::Url;
 
 use crate::{
-    common::*, Data, FetchError, SignaturePolicy, SignatureVerifier, SigningAlgorithm,
-    TargetDataErased,
+    common::*, ArtifactInfo, Data, DigestRecorder, FetchError, Fetcher, FindDiagnostic,
+    SignaturePolicy, SignatureVerifier, SigningAlgorithm, TargetDataErased,
 };
 
 const BASE_URL: &str = "https://github.com/cargo-bins/cargo-quickinstall/releases/download";
@@ -23,6 +23,7 @@ const QUICKINSTALL_SIGN_KEY: Cow<'static, str> =
     Cow::Borrowed("RWTdnnab2pAka9OdwgCMYyOE66M/BlQoFWaJ/JjwcPV+f3n24IRTj97t");
 const QUICKINSTALL_SUPPORTED_TARGETS_URL: &str =
     "https://raw.githubusercontent.com/cargo-bins/cargo-quickinstall/main/supported-targets";
+const RELEASES_URL: &str = "https://github.com/cargo-bins/cargo-quickinstall/releases/tag";
 
 fn is_universal_macos(target: &str) -> bool {
     ["universal-apple-darwin", "universal2-apple-darwin"].contains(&target)
@@ -69,6 +70,7 @@ pub struct QuickInstall {
 
     signature_verifier: OnceLock<SignatureVerifier>,
     status: Mutex<Status>,
+    artifact: OnceLock<ArtifactInfo>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -123,7 +125,7 @@ impl QuickInstall {
                         trace!(?signature, "got signature contents");
                         let config = PkgSigning {
                             algorithm: SigningAlgorithm::Minisign,
-                            pubkey: QUICKINSTALL_SIGN_KEY,
+                            pubkey: Some(QUICKINSTALL_SIGN_KEY),
                             file: None,
                         };
                         SignatureVerifier::new(&config, &signature)
@@ -142,6 +144,61 @@ impl QuickInstall {
         })
     }
 
+    /// Return the link to the release of the artifact, which has its build
+    /// logs.
+    fn build_info(&self) -> Box<str> {
+        format!("{RELEASES_URL}/{}-{}", self.data.name, self.data.version).into()
+    }
+
+    /// Attach the build info to errors caused by the artifact itself,
+    /// rather than by the network.
+    fn attach_build_info(&self, err: FetchError) -> FetchError {
+        match err {
+            FetchError::InvalidSignature
+            | FetchError::Download(DownloadError::Unzip(_) | DownloadError::Io(_)) => {
+                FetchError::QuickInstallArtifact {
+                    package: self.package.as_str().into(),
+                    build_info: self.build_info(),
+                    err: Box::new(err),
+                }
+            }
+            err => err,
+        }
+    }
+
+    async fn download_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
+        let Some(verifier) = self.signature_verifier.get() else {
+            panic!("<QuickInstall as Fetcher>::find has not been called yet!")
+        };
+
+        debug!(url=%self.package_url, "Downloading package");
+        let mut data_verifier = verifier.data_verifier()?;
+        let mut data_verifier =
+            DigestRecorder::new(data_verifier.as_mut(), &self.data.digest_algorithms);
+        let files = Download::new_with_data_verifier(
+            self.client.clone(),
+            self.package_url.clone(),
+            &mut data_verifier,
+        )
+        .with_low_memory(self.data.low_memory)
+        .and_extract(self.pkg_fmt(), dst)
+        .await?;
+        trace!("validating signature (if any)");
+        if data_verifier.validate() {
+            if let Some(info) = verifier.info() {
+                info!("Verified signature for package '{}': {info}", self.package);
+            }
+            self.set_status(Status::InstalledFromTarball);
+            self.artifact
+                .set(data_verifier.into_artifact_info(self.package_url.clone()))
+                .ok();
+            Ok(files)
+        } else {
+            self.set_status(Status::InvalidSignature);
+            Err(FetchError::InvalidSignature)
+        }
+    }
+
     fn get_status(&self) -> Status {
         *self.status.lock().unwrap()
     }
@@ -185,6 +242,7 @@ impl super::Fetcher for QuickInstall {
 
             signature_verifier: OnceLock::new(),
             status: Mutex::new(Status::Start),
+            artifact: OnceLock::new(),
         })
     }
 
@@ -221,6 +279,16 @@ impl super::Fetcher for QuickInstall {
         })
     }
 
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        if self.is_supported_v.get().copied() == Some(false) {
+            vec![FindDiagnostic::UnsupportedTarget]
+        } else if let Status::NotFound = self.get_status() {
+            vec![FindDiagnostic::NoArtifact]
+        } else {
+            Vec::new()
+        }
+    }
+
     fn report_to_upstream(self: Arc<Self>) {
         if cfg!(debug_assertions) {
             debug!("Not sending quickinstall report in debug mode");
@@ -245,30 +313,13 @@ by rust officially."#,
 
     async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
         self.set_status(Status::AttemptingInstall);
-        let Some(verifier) = self.signature_verifier.get() else {
-            panic!("<QuickInstall as Fetcher>::find has not been called yet!")
-        };
+        self.download_and_extract(dst)
+            .await
+            .map_err(|err| self.attach_build_info(err))
+    }
 
-        debug!(url=%self.package_url, "Downloading package");
-        let mut data_verifier = verifier.data_verifier()?;
-        let files = Download::new_with_data_verifier(
-            self.client.clone(),
-            self.package_url.clone(),
-            data_verifier.as_mut(),
-        )
-        .and_extract(self.pkg_fmt(), dst)
-        .await?;
-        trace!("validating signature (if any)");
-        if data_verifier.validate() {
-            if let Some(info) = verifier.info() {
-                info!("Verified signature for package '{}': {info}", self.package);
-            }
-            self.set_status(Status::InstalledFromTarball);
-            Ok(files)
-        } else {
-            self.set_status(Status::InvalidSignature);
-            Err(FetchError::InvalidSignature)
-        }
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        self.artifact.get().cloned()
     }
 
     fn pkg_fmt(&self) -> PkgFmt {
@@ -278,7 +329,7 @@ by rust officially."#,
     fn target_meta(&self) -> PkgMeta {
         let mut meta = self.target_data.meta.clone();
         meta.pkg_fmt = Some(self.pkg_fmt());
-        meta.bin_dir = Some("{ bin }{ binary-ext }".to_string());
+        meta.bin_dir = Some("{ bin }{ binary-ext }".to_string().into());
         meta
     }
 
@@ -0,0 +1,575 @@
+//! Check the inclusion of artifacts in the [Rekor] transparency log of
+//! Sigstore.
+//!
+//! An entry is only accepted if:
+//!  - its signed entry timestamp, and the checkpoint its inclusion proof is
+//!    computed against if any, are signed by the pinned key of Rekor,
+//!  - it is a `hashedrekord` of the artifact, signed by one of the keys
+//!    trusted by the user.
+//!
+//! [Rekor]: https://docs.sigstore.dev/logging/overview/
+
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use binstalk_downloader::remote::{Client, Url};
+use compact_str::CompactString;
+use p256::{
+    ecdsa::{
+        signature::{hazmat::PrehashVerifier, Verifier},
+        DerSignature, VerifyingKey,
+    },
+    pkcs8::{DecodePublicKey, EncodePublicKey},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error as ThisError;
+use tracing::{debug, info};
+
+use crate::FetchError;
+
+const REKOR_URL: &str = "https://rekor.sigstore.dev";
+
+/// Public key of rekor.sigstore.dev, base64-encoded in DER.
+const REKOR_PUBLIC_KEY: &str = "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE2G2Y+2tabdTV5BcGiBIx0a9fAFwrkBbmLSGtks4L3qX6yYY0zufBnhC8Ur/iy55GhWP/9A/bY2LhC30M9+RYtw==";
+
+/// Maximum number of entries of the artifact checked.
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, ThisError)]
+#[error("Invalid ECDSA P-256 public key in PEM")]
+pub struct InvalidSignerKey;
+
+/// Public key of a signer of entries, e.g. the `cosign.pub` of
+/// `cosign sign-blob --key`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignerKey(VerifyingKey);
+
+impl SignerKey {
+    pub fn from_pem(pem: &str) -> Result<Self, InvalidSignerKey> {
+        VerifyingKey::from_public_key_pem(pem.trim())
+            .map(Self)
+            .map_err(|_| InvalidSignerKey)
+    }
+}
+
+/// Key of the log, along with its id, which is the hex-encoded sha256 of
+/// the key in DER.
+struct LogKey {
+    key: VerifyingKey,
+    id: String,
+}
+
+impl LogKey {
+    fn new(key: VerifyingKey) -> Self {
+        let der = key
+            .to_public_key_der()
+            .expect("P-256 public keys can always be encoded in DER");
+        let id = Sha256::digest(der.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        Self { key, id }
+    }
+
+    fn rekor() -> Self {
+        let der = STANDARD
+            .decode(REKOR_PUBLIC_KEY)
+            .expect("REKOR_PUBLIC_KEY is valid base64");
+        Self::new(VerifyingKey::from_public_key_der(&der).expect("REKOR_PUBLIC_KEY is a P-256 key"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEntry {
+    body: String,
+    integrated_time: u64,
+    #[serde(rename = "logID")]
+    log_id: String,
+    log_index: u64,
+    verification: Option<Verification>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Verification {
+    inclusion_proof: Option<InclusionProof>,
+    signed_entry_timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InclusionProof {
+    hashes: Vec<String>,
+    log_index: u64,
+    root_hash: String,
+    tree_size: u64,
+    checkpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EntryBody {
+    kind: CompactString,
+    spec: EntrySpec,
+}
+
+#[derive(Deserialize)]
+struct EntrySpec {
+    data: EntryData,
+    signature: EntrySignature,
+}
+
+#[derive(Deserialize)]
+struct EntryData {
+    hash: EntryHash,
+}
+
+#[derive(Deserialize)]
+struct EntryHash {
+    algorithm: CompactString,
+    value: CompactString,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntrySignature {
+    /// Base64-encoded signature of the artifact, in DER.
+    content: String,
+    public_key: EntryPublicKey,
+}
+
+#[derive(Deserialize)]
+struct EntryPublicKey {
+    /// Base64-encoded public key in PEM, or certificate for keyless
+    /// signing, which is not supported.
+    content: String,
+}
+
+fn hash_leaf(leaf: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0])
+        .chain_update(leaf)
+        .finalize()
+        .into()
+}
+
+fn hash_children(left: &[u8], right: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([1])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// Verify the inclusion proof of the leaf at `index` of a Merkle tree of
+/// `tree_size` leaves, as specified in RFC 9162 section 2.1.3.2.
+fn verify_inclusion_proof(
+    index: u64,
+    tree_size: u64,
+    leaf_hash: [u8; 32],
+    proof: &[[u8; 32]],
+    root_hash: &[u8],
+) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+
+    let (mut f_n, mut s_n) = (index, tree_size - 1);
+    let mut hash = leaf_hash;
+
+    for p in proof {
+        if s_n == 0 {
+            return false;
+        }
+
+        if f_n & 1 == 1 || f_n == s_n {
+            hash = hash_children(p, &hash);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            hash = hash_children(&hash, p);
+        }
+
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+
+    s_n == 0 && hash == root_hash
+}
+
+fn decode_hash(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut hash = [0; 32];
+    for (byte, hex) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+fn verify_signature(key: &VerifyingKey, msg: &[u8], signature: &[u8]) -> bool {
+    DerSignature::from_bytes(signature).is_ok_and(|signature| key.verify(msg, &signature).is_ok())
+}
+
+/// Verify that `checkpoint`, a signed note of the tree of the log, is
+/// signed by `log_key` and commits to `tree_size` and `root_hash`.
+fn verify_checkpoint(
+    checkpoint: &str,
+    log_key: &VerifyingKey,
+    tree_size: u64,
+    root_hash: &[u8; 32],
+) -> bool {
+    let Some((note, signatures)) = checkpoint.split_once("\n\n") else {
+        return false;
+    };
+    // The signed text ends with the newline preceding the empty line.
+    let text = &checkpoint[..note.len() + 1];
+
+    // The first line is the origin of the log.
+    let mut lines = note.lines().skip(1);
+    let commits_to_tree = lines.next().and_then(|size| size.parse().ok()) == Some(tree_size)
+        && lines
+            .next()
+            .and_then(|hash| STANDARD.decode(hash).ok())
+            .is_some_and(|hash| hash == root_hash);
+
+    // Each signature line is `— <name> <base64 of key hint and signature>`,
+    // the key hint being the first 4 bytes of the hash of the key.
+    commits_to_tree
+        && signatures.lines().any(|line| {
+            line.strip_prefix("\u{2014} ")
+                .and_then(|line| line.rsplit_once(' '))
+                .and_then(|(_name, signature)| STANDARD.decode(signature).ok())
+                .is_some_and(|signature| {
+                    signature.len() > 4
+                        && verify_signature(log_key, text.as_bytes(), &signature[4..])
+                })
+        })
+}
+
+impl LogEntry {
+    /// Verify the signed entry timestamp, which is the signature of the log
+    /// over the canonical JSON of the entry.
+    fn verify_signed_entry_timestamp(&self, log_key: &LogKey, set: &str) -> bool {
+        let canonical = format!(
+            r#"{{"body":"{}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+            self.body, self.integrated_time, self.log_id, self.log_index
+        );
+
+        self.log_id == log_key.id
+            && STANDARD
+                .decode(set)
+                .is_ok_and(|set| verify_signature(&log_key.key, canonical.as_bytes(), &set))
+    }
+
+    /// Return true if the entry is of the artifact, signed by one of
+    /// `signers`, and is proven to be in the log of `log_key`.
+    fn verify(&self, uuid: &str, sha256: &str, log_key: &LogKey, signers: &[SignerKey]) -> bool {
+        let Some(verification) = &self.verification else {
+            return false;
+        };
+        let (Some(proof), Some(set)) = (
+            &verification.inclusion_proof,
+            &verification.signed_entry_timestamp,
+        ) else {
+            return false;
+        };
+
+        // Everything below is only trusted if the log signed the entry.
+        if !self.verify_signed_entry_timestamp(log_key, set) {
+            debug!(uuid, "Signed entry timestamp cannot be verified");
+            return false;
+        }
+
+        let Ok(body) = STANDARD.decode(&self.body) else {
+            return false;
+        };
+        let Ok(EntryBody { kind, spec }) = serde_json::from_slice::<EntryBody>(&body) else {
+            return false;
+        };
+        let Some(digest) = decode_hash(sha256) else {
+            return false;
+        };
+        if kind != "hashedrekord"
+            || spec.data.hash.algorithm != "sha256"
+            || spec.data.hash.value != sha256
+        {
+            return false;
+        }
+
+        let signer = STANDARD
+            .decode(&spec.signature.public_key.content)
+            .ok()
+            .and_then(|pem| String::from_utf8(pem).ok())
+            .and_then(|pem| SignerKey::from_pem(&pem).ok());
+        let Some(signer) = signer.filter(|signer| signers.contains(signer)) else {
+            debug!(uuid, "Entry is not signed by a trusted key");
+            return false;
+        };
+        let is_signed = STANDARD
+            .decode(&spec.signature.content)
+            .ok()
+            .and_then(|signature| DerSignature::from_bytes(&signature).ok())
+            .is_some_and(|signature| signer.0.verify_prehash(&digest, &signature).is_ok());
+        if !is_signed {
+            return false;
+        }
+
+        let leaf_hash = hash_leaf(&body);
+        // The uuid of an entry is the leaf hash, prefixed by the id of the
+        // tree of the log shard.
+        let Some(uuid_hash) = uuid
+            .get(uuid.len().saturating_sub(64)..)
+            .and_then(decode_hash)
+        else {
+            return false;
+        };
+        let (Some(proof_hashes), Some(root_hash)) = (
+            proof
+                .hashes
+                .iter()
+                .map(|hash| decode_hash(hash))
+                .collect::<Option<Vec<_>>>(),
+            decode_hash(&proof.root_hash),
+        ) else {
+            return false;
+        };
+
+        let is_checkpoint_valid = proof.checkpoint.as_deref().map_or(true, |checkpoint| {
+            verify_checkpoint(checkpoint, &log_key.key, proof.tree_size, &root_hash)
+        });
+
+        uuid_hash == leaf_hash
+            && is_checkpoint_valid
+            && verify_inclusion_proof(
+                proof.log_index,
+                proof.tree_size,
+                leaf_hash,
+                &proof_hashes,
+                &root_hash,
+            )
+    }
+}
+
+/// Find the entry of the artifact with the lowercase hex-encoded `sha256`
+/// in Rekor, signed by one of `signers`, and verify it.
+///
+/// Return the index of the entry in the log.
+pub async fn verify_inclusion(
+    client: &Client,
+    sha256: &str,
+    signers: &[SignerKey],
+) -> Result<u64, FetchError> {
+    let not_found = || FetchError::NotInTransparencyLog(sha256.into());
+    let log_key = LogKey::rekor();
+
+    let url = Url::parse(&format!("{REKOR_URL}/api/v1/index/retrieve"))?;
+    let uuids: Vec<String> = client
+        .post(url, format!(r#"{{"hash":"sha256:{sha256}"}}"#))
+        .header("content-type", "application/json")
+        .send(true)
+        .await?
+        .json()
+        .await?;
+    debug!(?uuids, "Found entries of the artifact in Rekor");
+
+    for uuid in uuids.iter().take(MAX_ENTRIES) {
+        let url = Url::parse(&format!("{REKOR_URL}/api/v1/log/entries/{uuid}"))?;
+        let entries: BTreeMap<String, LogEntry> = client.get(url).send(true).await?.json().await?;
+
+        for (uuid, entry) in &entries {
+            if entry.verify(uuid, sha256, &log_key, signers) {
+                info!(
+                    "Verified inclusion of the artifact in Rekor at log index {}",
+                    entry.log_index
+                );
+                return Ok(entry.log_index);
+            }
+            debug!(uuid, "Entry in Rekor cannot be verified");
+        }
+    }
+
+    Err(not_found())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use p256::{
+        ecdsa::{
+            signature::{hazmat::PrehashSigner, Signer},
+            Signature, SigningKey,
+        },
+        pkcs8::LineEnding,
+    };
+
+    #[test]
+    fn test_rekor_log_id() {
+        assert_eq!(
+            LogKey::rekor().id,
+            "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d"
+        );
+    }
+
+    #[test]
+    fn test_verify_inclusion_proof() {
+        let leaves: Vec<_> = (0..5u8).map(|i| hash_leaf(&[i])).collect();
+        let h01 = hash_children(&leaves[0], &leaves[1]);
+        let h23 = hash_children(&leaves[2], &leaves[3]);
+        let h0123 = hash_children(&h01, &h23);
+        let root = hash_children(&h0123, &leaves[4]);
+
+        assert!(verify_inclusion_proof(
+            2,
+            5,
+            leaves[2],
+            &[leaves[3], h01, leaves[4]],
+            &root
+        ));
+        assert!(verify_inclusion_proof(4, 5, leaves[4], &[h0123], &root));
+
+        assert!(!verify_inclusion_proof(
+            3,
+            5,
+            leaves[2],
+            &[leaves[3], h01, leaves[4]],
+            &root
+        ));
+        assert!(!verify_inclusion_proof(4, 5, leaves[4], &[h01], &root));
+        assert!(!verify_inclusion_proof(5, 5, leaves[4], &[h0123], &root));
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn sign(key: &SigningKey, msg: &[u8]) -> Vec<u8> {
+        let signature: Signature = key.sign(msg);
+        signature.to_der().as_bytes().to_vec()
+    }
+
+    /// Return a log entry of the artifact `sha256` signed by `signer`, the
+    /// only leaf of the tree of the log of `log_key`, along with its uuid.
+    fn entry(log_key: &SigningKey, signer: &SigningKey, sha256: &[u8; 32]) -> (String, LogEntry) {
+        let signature: Signature = signer.sign_prehash(sha256).unwrap();
+        let pem = signer
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        let body = format!(
+            r#"{{"apiVersion":"0.0.1","kind":"hashedrekord","spec":{{"data":{{"hash":{{"algorithm":"sha256","value":"{}"}}}},"signature":{{"content":"{}","publicKey":{{"content":"{}"}}}}}}}}"#,
+            hex(sha256),
+            STANDARD.encode(signature.to_der()),
+            STANDARD.encode(pem),
+        );
+        let leaf_hash = hash_leaf(body.as_bytes());
+
+        let checkpoint = format!(
+            "rekor.sigstore.dev - 1\n1\n{}\n",
+            STANDARD.encode(leaf_hash)
+        );
+        let checkpoint_signature = [&[0; 4][..], &sign(log_key, checkpoint.as_bytes())].concat();
+        let checkpoint = format!(
+            "{checkpoint}\n\u{2014} rekor.sigstore.dev {}\n",
+            STANDARD.encode(checkpoint_signature)
+        );
+
+        let mut entry = LogEntry {
+            body: STANDARD.encode(body),
+            integrated_time: 1_700_000_000,
+            log_id: LogKey::new(*log_key.verifying_key()).id,
+            log_index: 42,
+            verification: None,
+        };
+        let canonical = format!(
+            r#"{{"body":"{}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+            entry.body, entry.integrated_time, entry.log_id, entry.log_index
+        );
+        entry.verification = Some(Verification {
+            inclusion_proof: Some(InclusionProof {
+                hashes: Vec::new(),
+                log_index: 0,
+                root_hash: hex(&leaf_hash),
+                tree_size: 1,
+                checkpoint: Some(checkpoint),
+            }),
+            signed_entry_timestamp: Some(STANDARD.encode(sign(log_key, canonical.as_bytes()))),
+        });
+
+        (hex(&leaf_hash), entry)
+    }
+
+    #[test]
+    fn test_verify_entry() {
+        let log_key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let signer = SigningKey::from_slice(&[2; 32]).unwrap();
+        let other = SigningKey::from_slice(&[3; 32]).unwrap();
+        let sha256 = [7; 32];
+
+        let trusted = LogKey::new(*log_key.verifying_key());
+        let signers = [SignerKey(*signer.verifying_key())];
+
+        let (uuid, valid) = entry(&log_key, &signer, &sha256);
+        assert!(valid.verify(&uuid, &hex(&sha256), &trusted, &signers));
+
+        // Entry of another artifact
+        assert!(!valid.verify(&uuid, &hex(&[8; 32]), &trusted, &signers));
+
+        // Signed by a key not trusted by the user
+        let (uuid, entry_of_other) = entry(&log_key, &other, &sha256);
+        assert!(!entry_of_other.verify(&uuid, &hex(&sha256), &trusted, &signers));
+
+        // Served by another log, e.g. a MITM
+        let (uuid, forged) = entry(&other, &signer, &sha256);
+        assert!(!forged.verify(&uuid, &hex(&sha256), &trusted, &signers));
+
+        // Forged checkpoint
+        let (uuid, mut forged) = entry(&log_key, &signer, &sha256);
+        let proof = forged
+            .verification
+            .as_mut()
+            .unwrap()
+            .inclusion_proof
+            .as_mut()
+            .unwrap();
+        proof.checkpoint = entry(&other, &signer, &sha256)
+            .1
+            .verification
+            .unwrap()
+            .inclusion_proof
+            .unwrap()
+            .checkpoint;
+        assert!(!forged.verify(&uuid, &hex(&sha256), &trusted, &signers));
+
+        // Without signed entry timestamp
+        let (uuid, mut unsigned) = entry(&log_key, &signer, &sha256);
+        unsigned
+            .verification
+            .as_mut()
+            .unwrap()
+            .signed_entry_timestamp = None;
+        assert!(!unsigned.verify(&uuid, &hex(&sha256), &trusted, &signers));
+    }
+
+    #[test]
+    fn test_signer_key_from_pem() {
+        let key = SigningKey::from_slice(&[2; 32]).unwrap();
+        let pem = key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+
+        assert_eq!(
+            SignerKey::from_pem(&pem).unwrap(),
+            SignerKey(*key.verifying_key())
+        );
+        assert!(SignerKey::from_pem("not a key").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use binstalk_downloader::download::DataVerifier;
+use binstalk_downloader::{download::DataVerifier, fips};
 use binstalk_types::cargo_toml_binstall::{PkgSigning, SigningAlgorithm};
 use bytes::Bytes;
 use minisign_verify::{PublicKey, Signature, StreamVerifier};
@@ -6,25 +6,46 @@ use tracing::{error, trace};
 
 use crate::FetchError;
 
+mod ssh;
+pub use ssh::{AllowedSigners, SshVerifier};
+
 pub enum SignatureVerifier {
     Noop,
     Minisign(Box<MinisignVerifier>),
+    Ssh(Box<SshVerifier>),
 }
 
 impl SignatureVerifier {
     pub fn new(config: &PkgSigning, signature: &[u8]) -> Result<Self, FetchError> {
         match config.algorithm {
+            // Minisign signs the BLAKE2b digest of the data.
+            SigningAlgorithm::Minisign if fips::ENABLED => {
+                Err(FetchError::NotFipsApproved("minisign"))
+            }
             SigningAlgorithm::Minisign => MinisignVerifier::new(config, signature)
                 .map(Box::new)
                 .map(Self::Minisign),
+            SigningAlgorithm::Ssh => {
+                let allowed_signers =
+                    AllowedSigners::parse(config.pubkey.as_deref().unwrap_or_default());
+                Self::new_ssh(&allowed_signers, signature)
+            }
             algorithm => Err(FetchError::UnsupportedSigningAlgorithm(algorithm)),
         }
     }
 
+    /// Verify `ssh-keygen -Y sign` signatures against `allowed_signers`.
+    pub fn new_ssh(allowed_signers: &AllowedSigners, signature: &[u8]) -> Result<Self, FetchError> {
+        SshVerifier::new(allowed_signers, signature)
+            .map(Box::new)
+            .map(Self::Ssh)
+    }
+
     pub fn data_verifier(&self) -> Result<Box<dyn DataVerifier + '_>, FetchError> {
         match self {
             Self::Noop => Ok(Box::new(())),
             Self::Minisign(v) => v.data_verifier(),
+            Self::Ssh(v) => v.data_verifier(),
         }
     }
 
@@ -32,6 +53,7 @@ impl SignatureVerifier {
         match self {
             Self::Noop => None,
             Self::Minisign(v) => Some(v.signature.trusted_comment().into()),
+            Self::Ssh(v) => Some(v.info()),
         }
     }
 }
@@ -44,7 +66,8 @@ pub struct MinisignVerifier {
 impl MinisignVerifier {
     pub fn new(config: &PkgSigning, signature: &[u8]) -> Result<Self, FetchError> {
         trace!(key=?config.pubkey, "parsing public key");
-        let pubkey = PublicKey::from_base64(&config.pubkey).map_err(|err| {
+        let pubkey = config.pubkey.as_deref().unwrap_or_default();
+        let pubkey = PublicKey::from_base64(pubkey).map_err(|err| {
             error!("Package public key is invalid: {err}");
             FetchError::InvalidSignature
         })?;
@@ -0,0 +1,248 @@
+use std::{fs, io, mem, path::PathBuf};
+
+use binstalk_downloader::download::DataVerifier;
+use binstalk_types::crate_info::ChecksumAlgorithm;
+use bytes::Bytes;
+use compact_str::CompactString;
+use signature::Verifier;
+use ssh_key::{public::KeyData, HashAlg, PublicKey, SshSig};
+use tracing::{error, trace, warn};
+
+use crate::{
+    artifact_digest::{new_hasher, Hasher},
+    FetchError,
+};
+
+/// Namespace of signatures of files, i.e. `ssh-keygen -Y sign -n file`.
+const NAMESPACE: &str = "file";
+
+/// Magic preamble of the data signed for a SSHSIG signature.
+const MAGIC_PREAMBLE: &[u8] = b"SSHSIG";
+
+#[derive(Debug)]
+struct AllowedSigner {
+    principals: CompactString,
+    /// Namespaces restricted by the `namespaces="..."` option.
+    namespaces: Option<Vec<CompactString>>,
+    key: KeyData,
+}
+
+/// Keys allowed to sign packages, in the format of the allowed signers file
+/// of `ssh-keygen`, i.e. `<principals> [options] <key type> <key>` lines.
+///
+/// A line with only the public key is also accepted.
+#[derive(Debug, Default)]
+pub struct AllowedSigners(Vec<AllowedSigner>);
+
+impl AllowedSigners {
+    pub fn parse(contents: &str) -> Self {
+        Self(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| {
+                    let signer = AllowedSigner::parse(line);
+                    if signer.is_none() {
+                        warn!("Ignoring invalid allowed signer: {line}");
+                    }
+                    signer
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Load the allowed signers file at `path`, which does not need to exist.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn find(&self, key: &KeyData) -> Option<&AllowedSigner> {
+        self.0.iter().find(|signer| {
+            signer.key == *key
+                && signer.namespaces.as_ref().map_or(true, |namespaces| {
+                    namespaces.iter().any(|namespace| namespace == NAMESPACE)
+                })
+        })
+    }
+}
+
+impl AllowedSigner {
+    fn parse(line: &str) -> Option<Self> {
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        // The key type is the first token which can be parsed as a key
+        // along with the token following it.
+        let (i, key) = tokens.windows(2).enumerate().find_map(|(i, window)| {
+            let key = PublicKey::from_openssh(&window.join(" ")).ok()?;
+            Some((i, key))
+        })?;
+
+        let (principals, options) = match tokens[..i] {
+            [] => ("*", None),
+            [principals] => (principals, None),
+            [principals, options] => (principals, Some(options)),
+            _ => return None,
+        };
+        let namespaces = options.and_then(|options| {
+            const OPTION: &str = "namespaces=\"";
+            let namespaces = &options[options.find(OPTION)? + OPTION.len()..];
+            let namespaces = &namespaces[..namespaces.find('"')?];
+            Some(namespaces.split(',').map(CompactString::from).collect())
+        });
+
+        Some(Self {
+            principals: principals.into(),
+            namespaces,
+            key: key.key_data().clone(),
+        })
+    }
+}
+
+pub struct SshVerifier {
+    signature: SshSig,
+    principals: CompactString,
+}
+
+impl SshVerifier {
+    pub fn new(allowed_signers: &AllowedSigners, signature: &[u8]) -> Result<Self, FetchError> {
+        trace!(?signature, "parsing signature");
+        let signature = SshSig::from_pem(signature).map_err(|err| {
+            error!("Signature file is invalid: {err}");
+            FetchError::InvalidSignature
+        })?;
+
+        if signature.namespace() != NAMESPACE {
+            error!(
+                "Signature is made for namespace {}, instead of {NAMESPACE}",
+                signature.namespace()
+            );
+            return Err(FetchError::InvalidSignature);
+        }
+
+        let signer = allowed_signers
+            .find(signature.public_key())
+            .ok_or_else(|| {
+                error!("Signature is not made by an allowed signer");
+                FetchError::InvalidSignature
+            })?;
+
+        Ok(Self {
+            principals: signer.principals.clone(),
+            signature,
+        })
+    }
+
+    pub fn data_verifier(&self) -> Result<Box<dyn DataVerifier + '_>, FetchError> {
+        let algorithm = match self.signature.hash_alg() {
+            HashAlg::Sha256 => ChecksumAlgorithm::Sha256,
+            HashAlg::Sha512 => ChecksumAlgorithm::Sha512,
+            hash_alg => {
+                error!("Signature hash algorithm {hash_alg} is not supported");
+                return Err(FetchError::InvalidSignature);
+            }
+        };
+
+        Ok(Box::new(SshDataVerifier {
+            signature: &self.signature,
+            algorithm,
+            hasher: new_hasher(algorithm),
+        }))
+    }
+
+    pub fn info(&self) -> String {
+        format!("signed by {}", self.principals)
+    }
+}
+
+pub struct SshDataVerifier<'a> {
+    signature: &'a SshSig,
+    algorithm: ChecksumAlgorithm,
+    hasher: Box<dyn Hasher>,
+}
+
+impl DataVerifier for SshDataVerifier<'_> {
+    fn update(&mut self, data: &Bytes) {
+        self.hasher.update(data);
+    }
+
+    fn validate(&mut self) -> bool {
+        let hash = mem::replace(&mut self.hasher, new_hasher(self.algorithm)).finalize();
+
+        let mut signed_data = MAGIC_PREAMBLE.to_vec();
+        for field in [
+            NAMESPACE.as_bytes(),
+            self.signature.reserved(),
+            self.signature.hash_alg().as_str().as_bytes(),
+            &hash,
+        ] {
+            signed_data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            signed_data.extend_from_slice(field);
+        }
+
+        if let Err(err) = self
+            .signature
+            .public_key()
+            .verify(&signed_data, self.signature.signature())
+        {
+            error!("Failed to verify signature: {err}");
+            false
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PUBKEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMG8SaLqE22xO0YkqR5Pi5JJCXWXjV6ZithYrXaC+5Cl";
+    const SIGNATURE: &str = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgwbxJouoTbbE7RiSpHk+LkkkJdZ
+eNXpmK2FitdoL7kKUAAAAEZmlsZQAAAAAAAAAGc2hhNTEyAAAAUwAAAAtzc2gtZWQyNTUx
+OQAAAED+1vXcDRhWbJw0xcVW3AxK9iKWWZOWqzVOJua57riFijxAEsvCLAPHU0ZoDGeU19
+ETz5IcDKyloLmtAtAC6HgA
+-----END SSH SIGNATURE-----
+";
+
+    fn verify(allowed_signers: &str, data: &'static [u8]) -> bool {
+        let Ok(verifier) = SshVerifier::new(
+            &AllowedSigners::parse(allowed_signers),
+            SIGNATURE.as_bytes(),
+        ) else {
+            return false;
+        };
+        let mut data_verifier = verifier.data_verifier().unwrap();
+        data_verifier.update(&Bytes::from_static(data));
+        data_verifier.validate()
+    }
+
+    #[test]
+    fn test_ssh_signature() {
+        assert!(verify(PUBKEY, b"binstall"));
+        assert!(verify(&format!("alice@example.com {PUBKEY}"), b"binstall"));
+        assert!(verify(
+            &format!("alice@example.com namespaces=\"git,file\" {PUBKEY} comment"),
+            b"binstall"
+        ));
+
+        assert!(!verify(PUBKEY, b"binstal1"));
+        assert!(!verify(
+            &format!("alice@example.com namespaces=\"git\" {PUBKEY}"),
+            b"binstall"
+        ));
+        assert!(!verify(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBCK4Lha1xNLjEkaLTtTm6i6AZQZ5cpYxk9Fi6F5Cc6R",
+            b"binstall"
+        ));
+    }
+}
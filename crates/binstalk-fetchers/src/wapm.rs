@@ -0,0 +1,265 @@
+//! Packages of the Wasmer registry (formerly WAPM), for wasi builds of crates
+//! published there instead of in release artifacts.
+
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use binstalk_types::cargo_toml_binstall::{BinDir, Strategy};
+use compact_str::format_compact;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    common::*, ArtifactInfo, Data, DigestRecorder, FetchError, FindDiagnostic, SignaturePolicy,
+    SignatureVerifier, TargetDataErased,
+};
+
+const GRAPHQL_URL: &str = "https://registry.wasmer.io/graphql";
+
+const PACKAGE_VERSION_QUERY: &str = "query ($name: String!, $version: String!) {
+    getPackageVersion(name: $name, version: $version) {
+        distribution { downloadUrl }
+    }
+}";
+
+/// Paths of the modules in the package, which are laid out as in the crate
+/// unless they are at the root.
+const BIN_DIRS: &[&str] = &[
+    "{ bin }.wasm",
+    "target/wasm32-wasip1/release/{ bin }.wasm",
+    "target/wasm32-wasi/release/{ bin }.wasm",
+];
+
+#[derive(Deserialize)]
+struct Response {
+    data: ResponseData,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponseData {
+    get_package_version: Option<PackageVersion>,
+}
+
+#[derive(Deserialize)]
+struct PackageVersion {
+    distribution: Distribution,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Distribution {
+    download_url: Option<Url>,
+}
+
+/// Name of the package of the crate in the registry, namespaced by the owner
+/// of its repository, e.g. `owner/name` for `https://github.com/owner/repo`.
+fn package_name(repo: &str, name: &str) -> Option<CompactString> {
+    let repo = Url::parse(repo).ok()?;
+    let owner = repo
+        .path_segments()?
+        .next()
+        .filter(|owner| !owner.is_empty())?;
+    Some(format_compact!("{}/{name}", owner.to_ascii_lowercase()))
+}
+
+pub struct Wapm {
+    client: Client,
+    data: Arc<Data>,
+    target_data: Arc<TargetDataErased>,
+    signature_policy: SignaturePolicy,
+
+    diagnostic: OnceLock<FindDiagnostic>,
+    resolved: OnceLock<Url>,
+    artifact: OnceLock<ArtifactInfo>,
+}
+
+impl Wapm {
+    async fn find_package(&self, package: &str) -> Result<Option<Url>, FetchError> {
+        let query = json!({
+            "query": PACKAGE_VERSION_QUERY,
+            "variables": { "name": package, "version": self.data.version },
+        });
+        debug!(package, "Querying Wasmer registry");
+
+        let response: Response = self
+            .client
+            .post(Url::parse(GRAPHQL_URL)?, query.to_string())
+            .header("content-type", "application/json")
+            .send(true)
+            .await?
+            .json()
+            .await?;
+
+        Ok(response
+            .data
+            .get_package_version
+            .and_then(|version| version.distribution.download_url))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Fetcher for Wapm {
+    fn new(
+        client: Client,
+        _gh_api_client: GhApiClient,
+        data: Arc<Data>,
+        target_data: Arc<TargetDataErased>,
+        signature_policy: SignaturePolicy,
+    ) -> Arc<dyn super::Fetcher> {
+        Arc::new(Self {
+            client,
+            data,
+            target_data,
+            signature_policy,
+
+            diagnostic: OnceLock::new(),
+            resolved: OnceLock::new(),
+            artifact: OnceLock::new(),
+        })
+    }
+
+    fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
+        tokio::spawn(async move {
+            if !self.target_data.target.starts_with("wasm32-wasi") {
+                self.diagnostic.set(FindDiagnostic::UnsupportedTarget).ok();
+                return Ok(false);
+            }
+
+            let Some(package) = self
+                .data
+                .repo
+                .as_deref()
+                .and_then(|repo| package_name(repo, &self.data.name))
+            else {
+                self.diagnostic.set(FindDiagnostic::MissingRepository).ok();
+                return Ok(false);
+            };
+
+            // Packages of the registry are not signed.
+            if self.signature_policy == SignaturePolicy::Require {
+                return Err(FetchError::MissingSignature);
+            }
+
+            let Some(url) = self.find_package(&package).await? else {
+                self.diagnostic.set(FindDiagnostic::NoArtifact).ok();
+                return Ok(false);
+            };
+
+            debug!(%url, "Found package {package} in Wasmer registry");
+            if self.resolved.set(url).is_err() {
+                panic!("<Wapm as Fetcher>::find is run twice");
+            }
+            Ok(true)
+        })
+    }
+
+    fn find_diagnostics(&self) -> Vec<FindDiagnostic> {
+        self.diagnostic.get().copied().into_iter().collect()
+    }
+
+    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
+        let Some(url) = self.resolved.get() else {
+            panic!("<Wapm as Fetcher>::find has not been called yet!")
+        };
+
+        debug!(%url, "Downloading package");
+        let mut data_verifier = SignatureVerifier::Noop.data_verifier()?;
+        let mut data_verifier =
+            DigestRecorder::new(data_verifier.as_mut(), &self.data.digest_algorithms);
+        let files =
+            Download::new_with_data_verifier(self.client.clone(), url.clone(), &mut data_verifier)
+                .with_low_memory(self.data.low_memory)
+                .and_extract(self.pkg_fmt(), dst)
+                .await?;
+        self.artifact
+            .set(data_verifier.into_artifact_info(url.clone()))
+            .ok();
+        Ok(files)
+    }
+
+    fn artifact(&self) -> Option<ArtifactInfo> {
+        self.artifact.get().cloned()
+    }
+
+    fn pkg_fmt(&self) -> PkgFmt {
+        PkgFmt::Tgz
+    }
+
+    fn target_meta(&self) -> PkgMeta {
+        let mut meta = self.target_data.meta.clone();
+        meta.pkg_fmt = Some(self.pkg_fmt());
+        meta.bin_dir = Some(BinDir::Multiple(
+            BIN_DIRS.iter().map(ToString::to_string).collect(),
+        ));
+        meta
+    }
+
+    fn source_name(&self) -> CompactString {
+        self.resolved
+            .get()
+            .and_then(|url| url.host_str())
+            .map(CompactString::from)
+            .unwrap_or_else(|| "Wasmer registry".into())
+    }
+
+    fn fetcher_name(&self) -> &'static str {
+        "Wapm"
+    }
+
+    fn strategy(&self) -> Strategy {
+        Strategy::Wapm
+    }
+
+    fn is_third_party(&self) -> bool {
+        true
+    }
+
+    fn target(&self) -> &str {
+        &self.target_data.target
+    }
+
+    fn target_data(&self) -> &Arc<TargetDataErased> {
+        &self.target_data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(
+            package_name("https://github.com/Wasmer/tool", "tool").as_deref(),
+            Some("wasmer/tool")
+        );
+        assert_eq!(package_name("https://github.com/", "tool"), None);
+        assert_eq!(package_name("not a url", "tool"), None);
+    }
+
+    #[test]
+    fn test_response_de() {
+        let response: Response = serde_json::from_str(
+            r#"{"data":{"getPackageVersion":{"distribution":{"downloadUrl":"https://cdn.wasmer.io/tool-1.0.0.tar.gz"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response
+                .data
+                .get_package_version
+                .unwrap()
+                .distribution
+                .download_url
+                .unwrap()
+                .as_str(),
+            "https://cdn.wasmer.io/tool-1.0.0.tar.gz"
+        );
+
+        let response: Response =
+            serde_json::from_str(r#"{"data":{"getPackageVersion":null}}"#).unwrap();
+        assert!(response.data.get_package_version.is_none());
+    }
+}
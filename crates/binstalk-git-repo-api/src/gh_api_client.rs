@@ -21,35 +21,46 @@ mod error;
 mod release_artifacts;
 mod repo_info;
 
-use common::{check_http_status_and_header, percent_decode_http_url_path};
+use common::{check_http_status_and_header, percent_decode_http_url_path, ApiEndpoints};
 pub use error::{GhApiContextError, GhApiError, GhGraphQLErrors};
 pub use repo_info::RepoInfo;
 
 /// default retry duration if x-ratelimit-reset is not found in response header
 const DEFAULT_RETRY_DURATION: Duration = Duration::from_secs(10 * 60);
 
+pub const GITHUB_HOST: &str = "github.com";
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct GhRepo {
+    /// [`GITHUB_HOST`] or the host of a GitHub Enterprise Server.
+    pub host: CompactString,
     pub owner: CompactString,
     pub repo: CompactString,
 }
 impl GhRepo {
     pub fn repo_url(&self) -> Result<Url, url::ParseError> {
         Url::parse(&format_compact!(
-            "https://github.com/{}/{}",
+            "https://{}/{}/{}",
+            self.host,
             self.owner,
             self.repo
         ))
     }
 
+    /// Extract a repository on github.com from `url`, use
+    /// [`GhApiClient::extract_repo`] to also extract repositories on
+    /// GitHub Enterprise Servers.
     pub fn try_extract_from_url(url: &Url) -> Option<Self> {
-        if url.domain() != Some("github.com") {
-            return None;
-        }
+        Self::try_extract_from_url_with(url, |host| host == GITHUB_HOST)
+    }
+
+    fn try_extract_from_url_with(url: &Url, is_gh_host: impl FnOnce(&str) -> bool) -> Option<Self> {
+        let host = url.host_str().filter(|host| is_gh_host(host))?;
 
         let mut path_segments = url.path_segments()?;
 
         Some(Self {
+            host: host.to_compact_string(),
             owner: path_segments.next()?.to_compact_string(),
             repo: path_segments.next()?.to_compact_string(),
         })
@@ -71,11 +82,18 @@ pub struct GhReleaseArtifact {
 }
 
 impl GhReleaseArtifact {
-    /// Create [`GhReleaseArtifact`] from url.
+    /// Create [`GhReleaseArtifact`] from url on github.com, use
+    /// [`GhApiClient::extract_release_artifact`] to also extract artifacts on
+    /// GitHub Enterprise Servers.
     pub fn try_extract_from_url(url: &remote::Url) -> Option<Self> {
-        if url.domain() != Some("github.com") {
-            return None;
-        }
+        Self::try_extract_from_url_with(url, |host| host == GITHUB_HOST)
+    }
+
+    fn try_extract_from_url_with(
+        url: &remote::Url,
+        is_gh_host: impl FnOnce(&str) -> bool,
+    ) -> Option<Self> {
+        let host = url.host_str().filter(|host| is_gh_host(host))?;
 
         let mut path_segments = url.path_segments()?;
 
@@ -93,6 +111,7 @@ impl GhReleaseArtifact {
             || Self {
                 release: GhRelease {
                     repo: GhRepo {
+                        host: host.to_compact_string(),
                         owner: percent_decode_http_url_path(owner),
                         repo: percent_decode_http_url_path(repo),
                     },
@@ -124,37 +143,111 @@ where
     }
 }
 
+/// API endpoints and credentials of github.com or a GitHub Enterprise Server.
 #[derive(Debug)]
-struct Inner {
-    client: remote::Client,
-    release_artifacts: Map<GhRelease, OnceCell<Option<release_artifacts::Artifacts>>>,
+struct Host {
+    endpoints: ApiEndpoints,
     retry_after: Mutex<Option<Instant>>,
 
     auth_token: Option<Zeroizing<Box<str>>>,
     is_auth_token_valid: AtomicBool,
+}
+
+impl Host {
+    fn new(host: &str, auth_token: Option<Zeroizing<Box<str>>>) -> Result<Self, url::ParseError> {
+        Ok(Self {
+            endpoints: ApiEndpoints::new(host)?,
+            retry_after: Default::default(),
+
+            auth_token,
+            is_auth_token_valid: AtomicBool::new(true),
+        })
+    }
+
+    fn check_retry_after(&self) -> Result<(), GhApiError> {
+        let mut guard = self.retry_after.lock().unwrap();
+
+        if let Some(retry_after) = *guard {
+            if retry_after.elapsed().is_zero() {
+                return Err(GhApiError::RateLimit {
+                    retry_after: Some(retry_after - Instant::now()),
+                });
+            } else {
+                // Instant retry_after is already reached.
+                *guard = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_auth_token(&self) -> Option<&str> {
+        if self.is_auth_token_valid.load(Relaxed) {
+            self.auth_token.as_deref().map(|s| &**s)
+        } else {
+            None
+        }
+    }
+
+    fn is_rate_limited(&self) -> bool {
+        self.retry_after
+            .lock()
+            .unwrap()
+            .is_some_and(|retry_after| retry_after.elapsed().is_zero())
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    client: remote::Client,
+    release_artifacts: Map<GhRelease, OnceCell<Option<release_artifacts::Artifacts>>>,
+    /// Shared by crates from the same repository, e.g. a workspace.
+    repo_infos: Map<GhRepo, OnceCell<Option<RepoInfo>>>,
+
+    /// github.com and GitHub Enterprise Servers, each with its own token.
+    hosts: RwLock<HashMap<CompactString, Arc<Host>>>,
 
     only_use_restful_api: AtomicBool,
 }
 
 /// Github API client for querying whether a release artifact exitsts.
-/// Can only handle github.com for now.
+///
+/// Handles github.com, and GitHub Enterprise Servers added with
+/// [`GhApiClient::add_enterprise_host`].
 #[derive(Clone, Debug)]
 pub struct GhApiClient(Arc<Inner>);
 
 impl GhApiClient {
     pub fn new(client: remote::Client, auth_token: Option<Zeroizing<Box<str>>>) -> Self {
+        let github = Host::new(GITHUB_HOST, auth_token)
+            .expect("github.com api endpoints should always be valid urls");
+
         Self(Arc::new(Inner {
             client,
             release_artifacts: Default::default(),
-            retry_after: Default::default(),
+            repo_infos: Default::default(),
 
-            auth_token,
-            is_auth_token_valid: AtomicBool::new(true),
+            hosts: RwLock::new(HashMap::from([(
+                CompactString::const_new(GITHUB_HOST),
+                Arc::new(github),
+            )])),
 
             only_use_restful_api: AtomicBool::new(false),
         }))
     }
 
+    /// Add a GitHub Enterprise Server at `host`, whose API is authenticated
+    /// with `auth_token` instead of the github.com one.
+    pub fn add_enterprise_host(
+        &self,
+        host: &str,
+        auth_token: Option<Zeroizing<Box<str>>>,
+    ) -> Result<(), GhApiError> {
+        let state = Arc::new(Host::new(host, auth_token)?);
+        self.0.hosts.write().unwrap().insert(host.into(), state);
+        Ok(())
+    }
+
     /// If you don't want to use GitHub GraphQL API for whatever reason, call this.
     pub fn set_only_use_restful_api(&self) {
         self.0.only_use_restful_api.store(true, Relaxed);
@@ -163,90 +256,165 @@ impl GhApiClient {
     pub fn remote_client(&self) -> &remote::Client {
         &self.0.client
     }
+
+    /// Return true if `host` is github.com or a GitHub Enterprise Server
+    /// added to this client.
+    pub fn is_gh_host(&self, host: &str) -> bool {
+        self.0.hosts.read().unwrap().contains_key(host)
+    }
+
+    /// Like [`GhRepo::try_extract_from_url`], but also extracts repositories
+    /// on GitHub Enterprise Servers.
+    pub fn extract_repo(&self, url: &Url) -> Option<GhRepo> {
+        GhRepo::try_extract_from_url_with(url, |host| self.is_gh_host(host))
+    }
+
+    /// Like [`GhReleaseArtifact::try_extract_from_url`], but also extracts
+    /// artifacts on GitHub Enterprise Servers.
+    pub fn extract_release_artifact(&self, url: &remote::Url) -> Option<GhReleaseArtifact> {
+        GhReleaseArtifact::try_extract_from_url_with(url, |host| self.is_gh_host(host))
+    }
 }
 
 impl GhApiClient {
-    fn check_retry_after(&self) -> Result<(), GhApiError> {
-        let mut guard = self.0.retry_after.lock().unwrap();
+    fn host(&self, host: &str) -> Result<Arc<Host>, GhApiError> {
+        self.0
+            .hosts
+            .read()
+            .unwrap()
+            .get(host)
+            .cloned()
+            .ok_or_else(|| GhApiError::UnknownHost(host.into()))
+    }
 
-        if let Some(retry_after) = *guard {
-            if retry_after.elapsed().is_zero() {
-                return Err(GhApiError::RateLimit {
-                    retry_after: Some(retry_after - Instant::now()),
-                });
-            } else {
-                // Instant retry_after is already reached.
-                *guard = None;
-            }
-        }
+    /// Return true if there is a valid token for github.com.
+    pub fn has_gh_token(&self) -> bool {
+        self.has_gh_token_for(GITHUB_HOST)
+    }
 
-        Ok(())
+    /// Return true if there is a valid token for `host`.
+    pub fn has_gh_token_for(&self, host: &str) -> bool {
+        self.host(host)
+            .is_ok_and(|host| host.get_auth_token().is_some())
     }
 
-    fn get_auth_token(&self) -> Option<&str> {
-        if self.0.is_auth_token_valid.load(Relaxed) {
-            self.0.auth_token.as_deref().map(|s| &**s)
-        } else {
-            None
-        }
+    /// Return true if GitHub API is currently rate limited, in which case
+    /// requests to it fail until the rate limit is lifted.
+    pub fn is_rate_limited(&self) -> bool {
+        self.is_rate_limited_for(GITHUB_HOST)
     }
 
-    pub fn has_gh_token(&self) -> bool {
-        self.get_auth_token().is_some()
+    /// Return true if the GitHub API of `host` is currently rate limited.
+    pub fn is_rate_limited_for(&self, host: &str) -> bool {
+        self.host(host).is_ok_and(|host| host.is_rate_limited())
     }
 
     async fn do_fetch<T, U, GraphQLFn, RestfulFn, GraphQLFut, RestfulFut>(
         &self,
+        host: &Host,
         graphql_func: GraphQLFn,
         restful_func: RestfulFn,
         data: &T,
     ) -> Result<U, GhApiError>
     where
-        GraphQLFn: Fn(&remote::Client, &T, &str) -> GraphQLFut,
-        RestfulFn: Fn(&remote::Client, &T, Option<&str>) -> RestfulFut,
+        GraphQLFn: Fn(&remote::Client, &ApiEndpoints, &T, &str) -> GraphQLFut,
+        RestfulFn: Fn(&remote::Client, &ApiEndpoints, &T, Option<&str>) -> RestfulFut,
         GraphQLFut: Future<Output = Result<U, GhApiError>> + Send + 'static,
         RestfulFut: Future<Output = Result<U, GhApiError>> + Send + 'static,
     {
-        self.check_retry_after()?;
+        host.check_retry_after()?;
 
         if !self.0.only_use_restful_api.load(Relaxed) {
-            if let Some(auth_token) = self.get_auth_token() {
-                match graphql_func(&self.0.client, data, auth_token).await {
+            if let Some(auth_token) = host.get_auth_token() {
+                match graphql_func(&self.0.client, &host.endpoints, data, auth_token).await {
                     Err(GhApiError::Unauthorized) => {
-                        self.0.is_auth_token_valid.store(false, Relaxed);
+                        host.is_auth_token_valid.store(false, Relaxed);
                     }
                     res => return res.map_err(|err| err.context("GraphQL API")),
                 }
             }
         }
 
-        restful_func(&self.0.client, data, self.get_auth_token())
+        restful_func(&self.0.client, &host.endpoints, data, host.get_auth_token())
             .await
             .map_err(|err| err.context("Restful API"))
     }
 
+    /// Caches the info of `repo`.
     #[instrument(skip(self), ret(level = Level::DEBUG))]
     pub async fn get_repo_info(&self, repo: &GhRepo) -> Result<Option<RepoInfo>, GhApiError> {
-        match self
-            .do_fetch(
-                repo_info::fetch_repo_info_graphql_api,
-                repo_info::fetch_repo_info_restful_api,
-                repo,
-            )
+        let host = self.host(&repo.host)?;
+        let once_cell = self.0.repo_infos.get(repo.clone());
+
+        once_cell
+            .get_or_try_init(|| {
+                Box::pin(async {
+                    match self
+                        .do_fetch(
+                            &host,
+                            repo_info::fetch_repo_info_graphql_api,
+                            repo_info::fetch_repo_info_restful_api,
+                            repo,
+                        )
+                        .await
+                    {
+                        Ok(repo_info) => Ok(repo_info.map(|mut repo_info| {
+                            repo_info.host = repo.host.clone();
+                            repo_info
+                        })),
+                        Err(GhApiError::NotFound) => Ok(None),
+                        Err(err) => Err(err),
+                    }
+                })
+            })
             .await
-        {
-            Ok(repo_info) => Ok(repo_info),
-            Err(GhApiError::NotFound) => Ok(None),
-            Err(err) => Err(err),
-        }
+            .cloned()
     }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub struct GhReleaseArtifactUrl(Url);
+pub struct GhReleaseArtifactUrl {
+    /// Host of the repository, used to pick the token to download with.
+    host: CompactString,
+    url: Url,
+    digest: Option<CompactString>,
+    is_draft: bool,
+    is_immutable: bool,
+}
+
+impl GhReleaseArtifactUrl {
+    /// Digest of the artifact computed by GitHub, e.g. `sha256:<hex>`,
+    /// if the release asset is recent enough to have one.
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+
+    /// Hex-encoded SHA256 [digest](Self::digest) of the artifact.
+    pub fn sha256(&self) -> Option<&str> {
+        self.digest()?.strip_prefix("sha256:")
+    }
+
+    /// Return true if the artifact is of a draft release, which can only be
+    /// downloaded with [`GhApiClient::download_artifact`].
+    pub fn is_draft(&self) -> bool {
+        self.is_draft
+    }
+
+    /// Return true if the artifact is of an immutable release, which cannot
+    /// be replaced once published.
+    pub fn is_immutable(&self) -> bool {
+        self.is_immutable
+    }
+}
 
 impl GhApiClient {
-    /// Return `Ok(Some(api_artifact_url))` if exists.
+    /// Return `Ok(Some(api_artifact_url))` if exists, `Ok(None)` if the
+    /// release or the artifact does not exist, and
+    /// [`GhApiError::ReleaseAssetsHidden`] if the release exists without any
+    /// asset visible.
+    ///
+    /// Draft releases are found as well if the token has push access to
+    /// the repository.
     ///
     /// Caches info on all artifacts matching (repo, tag).
     ///
@@ -259,20 +427,59 @@ impl GhApiClient {
             artifact_name,
         }: GhReleaseArtifact,
     ) -> Result<Option<GhReleaseArtifactUrl>, GhApiError> {
+        let host = release.repo.host.clone();
+        let res = self
+            .with_release_artifacts(&release, |artifacts| {
+                artifacts
+                    .get_artifact(&artifact_name)
+                    .map(|artifact| GhReleaseArtifactUrl {
+                        host,
+                        url: artifact.url.clone(),
+                        digest: artifact.digest.clone(),
+                        is_draft: artifacts.draft,
+                        is_immutable: artifacts.immutable,
+                    })
+            })
+            .await?;
+
+        Ok(res.flatten())
+    }
+
+    /// Return the names of all artifacts of the release, however many pages
+    /// listing them takes, or `Ok(None)` if the release does not exist.
+    ///
+    /// Shares the cache of [`GhApiClient::has_release_artifact`].
+    pub async fn list_release_artifacts(
+        &self,
+        release: GhRelease,
+    ) -> Result<Option<Vec<CompactString>>, GhApiError> {
+        self.with_release_artifacts(&release, |artifacts| {
+            artifacts.names().map(CompactString::from).collect()
+        })
+        .await
+    }
+
+    async fn with_release_artifacts<T>(
+        &self,
+        release: &GhRelease,
+        f: impl FnOnce(&release_artifacts::Artifacts) -> T,
+    ) -> Result<Option<T>, GhApiError> {
+        let host = self.host(&release.repo.host)?;
         let once_cell = self.0.release_artifacts.get(release.clone());
         let res = once_cell
             .get_or_try_init(|| {
                 Box::pin(async {
                     match self
                         .do_fetch(
+                            &host,
                             release_artifacts::fetch_release_artifacts_graphql_api,
                             release_artifacts::fetch_release_artifacts_restful_api,
-                            &release,
+                            release,
                         )
                         .await
                     {
                         Ok(artifacts) => Ok(Some(artifacts)),
-                        Err(GhApiError::NotFound) => Ok(None),
+                        Err(GhApiError::NotFound) => self.find_draft_release(&host, release).await,
                         Err(err) => Err(err),
                     }
                 })
@@ -280,12 +487,11 @@ impl GhApiClient {
             .await;
 
         match res {
-            Ok(Some(artifacts)) => Ok(artifacts
-                .get_artifact_url(&artifact_name)
-                .map(GhReleaseArtifactUrl)),
+            Ok(Some(artifacts)) if artifacts.is_empty() => Err(GhApiError::ReleaseAssetsHidden),
+            Ok(Some(artifacts)) => Ok(Some(f(artifacts))),
             Ok(None) => Ok(None),
             Err(GhApiError::RateLimit { retry_after }) => {
-                *self.0.retry_after.lock().unwrap() =
+                *host.retry_after.lock().unwrap() =
                     Some(Instant::now() + retry_after.unwrap_or(DEFAULT_RETRY_DURATION));
 
                 Err(GhApiError::RateLimit { retry_after })
@@ -294,20 +500,44 @@ impl GhApiClient {
         }
     }
 
+    async fn find_draft_release(
+        &self,
+        host: &Host,
+        release: &GhRelease,
+    ) -> Result<Option<release_artifacts::Artifacts>, GhApiError> {
+        let Some(auth_token) = host.get_auth_token() else {
+            return Ok(None);
+        };
+
+        match release_artifacts::fetch_draft_release_artifacts_restful_api(
+            &self.0.client,
+            &host.endpoints,
+            release,
+            auth_token,
+        )
+        .await
+        {
+            Ok(artifacts) => Ok(Some(artifacts)),
+            Err(GhApiError::NotFound | GhApiError::Unauthorized) => Ok(None),
+            Err(err) => Err(err.context("Restful API")),
+        }
+    }
+
     pub async fn download_artifact(
         &self,
         artifact_url: GhReleaseArtifactUrl,
     ) -> Result<Download<'static>, GhApiError> {
-        self.check_retry_after()?;
+        let host = self.host(&artifact_url.host)?;
+        host.check_retry_after()?;
 
-        let Some(auth_token) = self.get_auth_token() else {
+        let Some(auth_token) = host.get_auth_token() else {
             return Err(GhApiError::Unauthorized);
         };
 
         let response = self
             .0
             .client
-            .get(artifact_url.0)
+            .get(artifact_url.url)
             .header("Accept", "application/octet-stream")
             .bearer_auth(&auth_token)
             .send(false)
@@ -315,7 +545,7 @@ impl GhApiClient {
 
         match check_http_status_and_header(response) {
             Err(GhApiError::Unauthorized) => {
-                self.0.is_auth_token_valid.store(false, Relaxed);
+                host.is_auth_token_valid.store(false, Relaxed);
                 Err(GhApiError::Unauthorized)
             }
             res => res.map(Download::from_response),
@@ -335,10 +565,11 @@ mod test {
     static DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
 
     mod cargo_binstall_v0_20_1 {
-        use super::{CompactString, GhRelease, GhRepo};
+        use super::{CompactString, GhRelease, GhRepo, GITHUB_HOST};
 
         pub(super) const RELEASE: GhRelease = GhRelease {
             repo: GhRepo {
+                host: CompactString::const_new(GITHUB_HOST),
                 owner: CompactString::const_new("cargo-bins"),
                 repo: CompactString::const_new("cargo-binstall"),
             },
@@ -376,6 +607,7 @@ mod test {
 
         pub(super) const RELEASE: GhRelease = GhRelease {
             repo: GhRepo {
+                host: CompactString::const_new(GITHUB_HOST),
                 owner: CompactString::const_new("rustsec"),
                 repo: CompactString::const_new("rustsec"),
             },
@@ -429,6 +661,31 @@ mod test {
         })
     }
 
+    #[test]
+    fn extract_from_enterprise_host() {
+        let client = GhApiClient::new(create_remote_client(), None);
+        client.add_enterprise_host("git.example.com", None).unwrap();
+
+        let url = Url::parse("https://git.example.com/owner/repo").unwrap();
+        let repo = client.extract_repo(&url).unwrap();
+        assert_eq!(repo.host, "git.example.com");
+        assert_eq!(repo.repo_url().unwrap(), url);
+
+        let artifact = client
+            .extract_release_artifact(
+                &Url::parse("https://git.example.com/owner/repo/releases/download/v1/a.tgz")
+                    .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(artifact.release.repo, repo);
+        assert_eq!(artifact.artifact_name, "a.tgz");
+
+        assert!(client
+            .extract_repo(&Url::parse("https://example.com/owner/repo").unwrap())
+            .is_none());
+        assert!(GhRepo::try_extract_from_url(&url).is_none());
+    }
+
     fn try_extract_artifact_from_str(s: &str) -> Option<GhReleaseArtifact> {
         GhReleaseArtifact::try_extract_from_url(&url::Url::parse(s).unwrap())
     }
@@ -444,7 +701,7 @@ mod test {
         use cargo_binstall_v0_20_1::*;
 
         let GhRelease {
-            repo: GhRepo { owner, repo },
+            repo: GhRepo { owner, repo, .. },
             tag,
         } = RELEASE;
 
@@ -468,7 +725,7 @@ mod test {
         use cargo_binstall_v0_20_1::*;
 
         let GhRelease {
-            repo: GhRepo { owner, repo },
+            repo: GhRepo { owner, repo, .. },
             tag,
         } = RELEASE;
 
@@ -486,6 +743,21 @@ mod test {
         }
     }
 
+    #[test]
+    fn gh_release_artifact_url_sha256() {
+        let artifact_url = |digest: Option<&str>| GhReleaseArtifactUrl {
+            host: "github.com".into(),
+            url: Url::parse("https://api.github.com/repos/o/r/releases/assets/1").unwrap(),
+            digest: digest.map(CompactString::from),
+            is_draft: false,
+            is_immutable: false,
+        };
+
+        assert_eq!(artifact_url(Some("sha256:abc")).sha256(), Some("abc"));
+        assert_eq!(artifact_url(Some("sha512:abc")).sha256(), None);
+        assert_eq!(artifact_url(None).sha256(), None);
+    }
+
     fn init_logger() {
         // Disable time, target, file, line_num, thread name/ids to make the
         // output more readable
@@ -542,14 +814,17 @@ mod test {
     #[tokio::test]
     async fn rate_limited_test_get_repo_info() {
         const PUBLIC_REPOS: [GhRepo; 1] = [GhRepo {
+            host: CompactString::const_new(GITHUB_HOST),
             owner: CompactString::const_new("cargo-bins"),
             repo: CompactString::const_new("cargo-binstall"),
         }];
         const PRIVATE_REPOS: [GhRepo; 1] = [GhRepo {
+            host: CompactString::const_new(GITHUB_HOST),
             owner: CompactString::const_new("cargo-bins"),
             repo: CompactString::const_new("private-repo-for-testing"),
         }];
         const NON_EXISTENT_REPOS: [GhRepo; 1] = [GhRepo {
+            host: CompactString::const_new(GITHUB_HOST),
             owner: CompactString::const_new("cargo-bins"),
             repo: CompactString::const_new("ttt"),
         }];
@@ -607,6 +882,7 @@ mod test {
         )];
         const NON_EXISTENT_RELEASES: [GhRelease; 1] = [GhRelease {
             repo: GhRepo {
+                host: CompactString::const_new(GITHUB_HOST),
                 owner: CompactString::const_new("cargo-bins"),
                 repo: CompactString::const_new("cargo-binstall"),
             },
@@ -644,7 +920,7 @@ mod test {
                             artifact_name: artifact_name.to_compact_string(),
                         };
 
-                        let browser_download_task = client.get_auth_token().map(|_| {
+                        let browser_download_task = client.has_gh_token().then(|| {
                             tokio::spawn(
                                 Download::new(
                                     client.remote_client().clone(),
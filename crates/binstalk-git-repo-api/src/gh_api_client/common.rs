@@ -1,4 +1,4 @@
-use std::{fmt::Debug, future::Future, sync::OnceLock};
+use std::{fmt::Debug, future::Future};
 
 use binstalk_downloader::remote::{self, Response, Url};
 use compact_str::CompactString;
@@ -7,7 +7,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::to_string as to_json_string;
 use tracing::debug;
 
-use super::{GhApiError, GhGraphQLErrors};
+use super::{GhApiError, GhGraphQLErrors, GITHUB_HOST};
 
 pub(super) fn percent_decode_http_url_path(input: &str) -> CompactString {
     if input.contains('%') {
@@ -27,28 +27,64 @@ pub(super) fn check_http_status_and_header(response: Response) -> Result<Respons
     }
 }
 
-fn get_api_endpoint() -> &'static Url {
-    static API_ENDPOINT: OnceLock<Url> = OnceLock::new();
+/// Endpoints of the GitHub API of a host.
+#[derive(Clone, Debug)]
+pub(super) struct ApiEndpoints {
+    restful: Url,
+    graphql: Url,
+}
 
-    API_ENDPOINT.get_or_init(|| {
-        Url::parse("https://api.github.com/").expect("Literal provided must be a valid url")
-    })
+impl ApiEndpoints {
+    /// github.com serves its API from `api.github.com`, while GitHub
+    /// Enterprise Server serves it under `/api` of the host itself.
+    pub(super) fn new(host: &str) -> Result<Self, url::ParseError> {
+        if host == GITHUB_HOST {
+            Ok(Self {
+                restful: Url::parse("https://api.github.com/")?,
+                graphql: Url::parse("https://api.github.com/graphql")?,
+            })
+        } else {
+            Ok(Self {
+                restful: Url::parse(&format!("https://{host}/api/v3"))?,
+                graphql: Url::parse(&format!("https://{host}/api/graphql"))?,
+            })
+        }
+    }
 }
 
 pub(super) fn issue_restful_api<T>(
     client: &remote::Client,
+    endpoints: &ApiEndpoints,
     path: &[&str],
     auth_token: Option<&str>,
 ) -> impl Future<Output = Result<T, GhApiError>> + Send + 'static
 where
     T: DeserializeOwned,
 {
-    let mut url = get_api_endpoint().clone();
+    issue_restful_api_with_query(client, endpoints, path, &[], auth_token)
+}
+
+pub(super) fn issue_restful_api_with_query<T>(
+    client: &remote::Client,
+    endpoints: &ApiEndpoints,
+    path: &[&str],
+    query: &[(&str, &str)],
+    auth_token: Option<&str>,
+) -> impl Future<Output = Result<T, GhApiError>> + Send + 'static
+where
+    T: DeserializeOwned,
+{
+    let mut url = endpoints.restful.clone();
 
     url.path_segments_mut()
-        .expect("get_api_endpoint() should return a https url")
+        .expect("ApiEndpoints should only contain https urls")
+        .pop_if_empty()
         .extend(path);
 
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
     debug!("Getting restful API: {url}");
 
     let mut request_builder = client
@@ -80,19 +116,9 @@ struct GraphQLQuery {
     query: String,
 }
 
-fn get_graphql_endpoint() -> Url {
-    let mut graphql_endpoint = get_api_endpoint().clone();
-
-    graphql_endpoint
-        .path_segments_mut()
-        .expect("get_api_endpoint() should return a https url")
-        .push("graphql");
-
-    graphql_endpoint
-}
-
 pub(super) fn issue_graphql_query<T>(
     client: &remote::Client,
+    endpoints: &ApiEndpoints,
     query: String,
     auth_token: &str,
 ) -> impl Future<Output = Result<T, GhApiError>> + Send + 'static
@@ -102,7 +128,7 @@ where
     let res = to_json_string(&GraphQLQuery { query })
         .map_err(remote::Error::from)
         .map(|graphql_query| {
-            let graphql_endpoint = get_graphql_endpoint();
+            let graphql_endpoint = endpoints.graphql.clone();
 
             debug!("Sending graphql query to {graphql_endpoint}: '{graphql_query}'");
 
@@ -38,8 +38,16 @@ pub enum GhApiError {
     #[error("Corresponding resource is not found")]
     NotFound,
 
+    /// The release exists but none of its assets are visible, e.g. they are
+    /// not uploaded yet, as opposed to [`GhApiError::NotFound`].
+    #[error("Release exists but its assets are hidden or not uploaded yet")]
+    ReleaseAssetsHidden,
+
     #[error("Does not have permission to access the API")]
     Unauthorized,
+
+    #[error("{0} is neither github.com nor a known GitHub Enterprise Server")]
+    UnknownHost(CompactString),
 }
 
 impl GhApiError {
@@ -47,7 +55,10 @@ impl GhApiError {
     pub fn context(self, context: impl fmt::Display) -> Self {
         use GhApiError::*;
 
-        if matches!(self, RateLimit { .. } | NotFound | Unauthorized) {
+        if matches!(
+            self,
+            RateLimit { .. } | NotFound | ReleaseAssetsHidden | Unauthorized
+        ) {
             self
         } else {
             Self::Context(Box::new(GhApiContextError {
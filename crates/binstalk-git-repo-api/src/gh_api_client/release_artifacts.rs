@@ -12,16 +12,24 @@ use serde::Deserialize;
 use url::Url;
 
 use super::{
-    common::{issue_graphql_query, issue_restful_api},
+    common::{issue_graphql_query, issue_restful_api, issue_restful_api_with_query, ApiEndpoints},
     GhApiError, GhRelease, GhRepo,
 };
 
+/// Maximum number of assets per page of the API, beyond which releases,
+/// which may have hundreds of per-target assets, need to be paginated.
+const ASSETS_PER_PAGE: usize = 100;
+
 // Only include fields we do care about
 
 #[derive(Eq, Deserialize, Debug)]
-struct Artifact {
+pub(super) struct Artifact {
     name: CompactString,
-    url: Url,
+    pub(super) url: Url,
+    /// Digest of the asset computed by GitHub, e.g. `sha256:<hex>`, which is
+    /// absent for assets uploaded before June 2025.
+    #[serde(default)]
+    pub(super) digest: Option<CompactString>,
 }
 
 // Manually implement PartialEq and Hash to ensure it will always produce the
@@ -56,30 +64,140 @@ impl Borrow<str> for Artifact {
 #[derive(Debug, Default, Deserialize)]
 pub(super) struct Artifacts {
     assets: HashSet<Artifact>,
+    /// Drafts are only visible with a token having push access to the
+    /// repository, and their assets can only be downloaded via the API.
+    #[serde(default)]
+    pub(super) draft: bool,
+    /// Assets of immutable releases cannot be modified once published.
+    #[serde(default)]
+    pub(super) immutable: bool,
 }
 
 impl Artifacts {
-    /// get url for downloading the artifact using GitHub API (for private repository).
-    pub(super) fn get_artifact_url(&self, artifact_name: &str) -> Option<Url> {
-        self.assets
-            .get(artifact_name)
-            .map(|artifact| artifact.url.clone())
+    pub(super) fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    pub(super) fn names(&self) -> impl Iterator<Item = &str> {
+        self.assets.iter().map(|artifact| artifact.name.as_str())
+    }
+
+    /// get the artifact, with its url for downloading it using GitHub API
+    /// (for private repository).
+    pub(super) fn get_artifact(&self, artifact_name: &str) -> Option<&Artifact> {
+        self.assets.get(artifact_name)
     }
 }
 
 pub(super) fn fetch_release_artifacts_restful_api(
     client: &remote::Client,
+    endpoints: &ApiEndpoints,
     GhRelease {
-        repo: GhRepo { owner, repo },
+        repo: GhRepo { owner, repo, .. },
         tag,
     }: &GhRelease,
     auth_token: Option<&str>,
 ) -> impl Future<Output = Result<Artifacts, GhApiError>> + Send + 'static {
-    issue_restful_api(
-        client,
-        &["repos", owner, repo, "releases", "tags", tag],
-        auth_token,
-    )
+    let client = client.clone();
+    let endpoints = endpoints.clone();
+    let (owner, repo) = (owner.clone(), repo.clone());
+    let auth_token = auth_token.map(CompactString::from);
+
+    let future = issue_restful_api::<RestfulRelease>(
+        &client,
+        &endpoints,
+        &["repos", &owner, &repo, "releases", "tags", tag],
+        auth_token.as_deref(),
+    );
+
+    async move {
+        future
+            .await?
+            .fetch_remaining_assets(&client, &endpoints, &owner, &repo, auth_token.as_deref())
+            .await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RestfulRelease {
+    id: u64,
+    tag_name: CompactString,
+    #[serde(flatten)]
+    artifacts: Artifacts,
+}
+
+impl RestfulRelease {
+    /// Assets embedded in the release are truncated, so list all of them
+    /// page by page if there may be more, replacing the embedded ones.
+    async fn fetch_remaining_assets(
+        self,
+        client: &remote::Client,
+        endpoints: &ApiEndpoints,
+        owner: &str,
+        repo: &str,
+        auth_token: Option<&str>,
+    ) -> Result<Artifacts, GhApiError> {
+        let mut artifacts = self.artifacts;
+        if artifacts.assets.len() < ASSETS_PER_PAGE {
+            return Ok(artifacts);
+        }
+        artifacts.assets.clear();
+
+        let id = self.id.to_compact_string();
+        let per_page = ASSETS_PER_PAGE.to_compact_string();
+        let mut page = 1_u32;
+        loop {
+            let assets: Vec<Artifact> = issue_restful_api_with_query(
+                client,
+                endpoints,
+                &["repos", owner, repo, "releases", &id, "assets"],
+                &[("per_page", &per_page), ("page", &page.to_compact_string())],
+                auth_token,
+            )
+            .await?;
+
+            let len = assets.len();
+            artifacts.assets.extend(assets);
+            if len < ASSETS_PER_PAGE {
+                break Ok(artifacts);
+            }
+            page += 1;
+        }
+    }
+}
+
+/// Draft releases are not found by their tag, only in the list of releases
+/// of users with push access to the repository, where they come first.
+pub(super) fn fetch_draft_release_artifacts_restful_api(
+    client: &remote::Client,
+    endpoints: &ApiEndpoints,
+    GhRelease {
+        repo: GhRepo { owner, repo, .. },
+        tag,
+    }: &GhRelease,
+    auth_token: &str,
+) -> impl Future<Output = Result<Artifacts, GhApiError>> + Send + 'static {
+    let client = client.clone();
+    let endpoints = endpoints.clone();
+    let (owner, repo, tag) = (owner.clone(), repo.clone(), tag.clone());
+    let auth_token = auth_token.to_compact_string();
+
+    let future = issue_restful_api::<Vec<RestfulRelease>>(
+        &client,
+        &endpoints,
+        &["repos", &owner, &repo, "releases"],
+        Some(&auth_token),
+    );
+
+    async move {
+        future
+            .await?
+            .into_iter()
+            .find(|release| release.artifacts.draft && release.tag_name == tag)
+            .ok_or(GhApiError::NotFound)?
+            .fetch_remaining_assets(&client, &endpoints, &owner, &repo, Some(&auth_token))
+            .await
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -96,6 +214,10 @@ struct GraphQLRepo {
 struct GraphQLRelease {
     #[serde(rename = "releaseAssets")]
     assets: GraphQLReleaseAssets,
+    #[serde(rename = "isDraft", default)]
+    is_draft: bool,
+    #[serde(default)]
+    immutable: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -130,24 +252,27 @@ impl fmt::Display for FilterCondition {
 
 pub(super) fn fetch_release_artifacts_graphql_api(
     client: &remote::Client,
+    endpoints: &ApiEndpoints,
     GhRelease {
-        repo: GhRepo { owner, repo },
+        repo: GhRepo { owner, repo, .. },
         tag,
     }: &GhRelease,
     auth_token: &str,
 ) -> impl Future<Output = Result<Artifacts, GhApiError>> + Send + 'static {
     let client = client.clone();
+    let endpoints = endpoints.clone();
     let auth_token = auth_token.to_compact_string();
 
     let base_query_prefix = format!(
         r#"
 query {{
   repository(owner:"{owner}",name:"{repo}") {{
-    release(tagName:"{tag}") {{"#
+    release(tagName:"{tag}") {{
+      isDraft immutable"#
     );
 
     let base_query_suffix = r#"
-  nodes { name url }
+  nodes { name url digest }
   pageInfo { endCursor hasNextPage }
 }}}}"#
         .trim();
@@ -165,14 +290,15 @@ releaseAssets({cond}) {{
 {base_query_suffix}"#
             );
 
-            let data: GraphQLData = issue_graphql_query(&client, query, &auth_token).await?;
+            let data: GraphQLData =
+                issue_graphql_query(&client, &endpoints, query, &auth_token).await?;
 
-            let assets = data
-                .repository
-                .and_then(|repository| repository.release)
-                .map(|release| release.assets);
+            let release = data.repository.and_then(|repository| repository.release);
 
-            if let Some(assets) = assets {
+            if let Some(release) = release {
+                let assets = release.assets;
+                artifacts.draft = release.is_draft;
+                artifacts.immutable = release.immutable;
                 artifacts.assets.extend(assets.nodes);
 
                 match assets.page_info {
@@ -190,3 +316,56 @@ releaseAssets({cond}) {{
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draft_release_de() {
+        let releases: Vec<RestfulRelease> = serde_json::from_str(
+            r#"[
+                {
+                    "id": 2,
+                    "tag_name": "v1.0.0",
+                    "draft": true,
+                    "immutable": false,
+                    "assets": [
+                        {
+                            "name": "tool-x86_64-unknown-linux-gnu.tgz",
+                            "url": "https://api.github.com/repos/o/r/releases/assets/1",
+                            "digest": "sha256:aa"
+                        }
+                    ]
+                },
+                {
+                    "id": 1,
+                    "tag_name": "v0.9.0",
+                    "draft": false,
+                    "immutable": true,
+                    "assets": []
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        let draft = &releases[0];
+        assert_eq!(draft.tag_name, "v1.0.0");
+        assert!(draft.artifacts.draft);
+        assert!(!draft.artifacts.immutable);
+        let artifact = draft
+            .artifacts
+            .get_artifact("tool-x86_64-unknown-linux-gnu.tgz")
+            .unwrap();
+        assert_eq!(artifact.digest.as_deref(), Some("sha256:aa"));
+
+        let published = &releases[1];
+        assert!(!published.artifacts.draft);
+        assert!(published.artifacts.immutable);
+        assert!(published.artifacts.is_empty());
+        assert_eq!(
+            draft.artifacts.names().collect::<Vec<_>>(),
+            ["tool-x86_64-unknown-linux-gnu.tgz"]
+        );
+    }
+}
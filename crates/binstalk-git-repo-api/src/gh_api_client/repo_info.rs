@@ -4,7 +4,7 @@ use compact_str::CompactString;
 use serde::Deserialize;
 
 use super::{
-    common::{issue_graphql_query, issue_restful_api},
+    common::{issue_graphql_query, issue_restful_api, ApiEndpoints},
     remote, GhApiError, GhRepo,
 };
 
@@ -15,6 +15,10 @@ struct Owner {
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize)]
 pub struct RepoInfo {
+    /// Not part of the API response, set to the host the repository is
+    /// fetched from.
+    #[serde(skip)]
+    pub(super) host: CompactString,
     owner: Owner,
     name: CompactString,
     private: bool,
@@ -32,8 +36,9 @@ impl fmt::Display for RepoInfo {
 
 impl RepoInfo {
     #[cfg(test)]
-    pub(crate) fn new(GhRepo { owner, repo }: GhRepo, private: bool) -> Self {
+    pub(crate) fn new(GhRepo { host, owner, repo }: GhRepo, private: bool) -> Self {
         Self {
+            host,
             owner: Owner { login: owner },
             name: repo,
             private,
@@ -41,6 +46,7 @@ impl RepoInfo {
     }
     pub fn repo(&self) -> GhRepo {
         GhRepo {
+            host: self.host.clone(),
             owner: self.owner.login.clone(),
             repo: self.name.clone(),
         }
@@ -53,10 +59,11 @@ impl RepoInfo {
 
 pub(super) fn fetch_repo_info_restful_api(
     client: &remote::Client,
-    GhRepo { owner, repo }: &GhRepo,
+    endpoints: &ApiEndpoints,
+    GhRepo { owner, repo, .. }: &GhRepo,
     auth_token: Option<&str>,
 ) -> impl Future<Output = Result<Option<RepoInfo>, GhApiError>> + Send + 'static {
-    issue_restful_api(client, &["repos", owner, repo], auth_token)
+    issue_restful_api(client, endpoints, &["repos", owner, repo], auth_token)
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,7 +73,8 @@ struct GraphQLData {
 
 pub(super) fn fetch_repo_info_graphql_api(
     client: &remote::Client,
-    GhRepo { owner, repo }: &GhRepo,
+    endpoints: &ApiEndpoints,
+    GhRepo { owner, repo, .. }: &GhRepo,
     auth_token: &str,
 ) -> impl Future<Output = Result<Option<RepoInfo>, GhApiError>> + Send + 'static {
     let query = format!(
@@ -82,7 +90,7 @@ query {{
 }}"#
     );
 
-    let future = issue_graphql_query(client, query, auth_token);
+    let future = issue_graphql_query(client, endpoints, query, auth_token);
 
     async move {
         let data: GraphQLData = future.await?;
@@ -0,0 +1,271 @@
+//! Binstall's `binstall/config.toml`, located in the cargo home.
+//!
+//! This manifest is used by users to configure Binstall itself, unlike
+//! [`cargo_config`](crate::cargo_config) which is shared with Cargo.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use binstalk_types::cargo_toml_binstall::AuthScheme;
+use compact_str::CompactString;
+use fs_lock::FileLock;
+use home::cargo_home;
+use serde::Deserialize;
+
+pub use crate::cargo_config::ConfigLoadError;
+
+/// Configuration of the `cargo-install` fallback.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Compile {
+    /// Wrapper to run rustc with, e.g. `sccache`, passed as `RUSTC_WRAPPER`.
+    pub rustc_wrapper: Option<PathBuf>,
+    /// Target directory shared by all builds, so that dependencies are only
+    /// compiled once.
+    pub target_dir: Option<PathBuf>,
+}
+
+/// Configuration of the requests sent to all hosts, including the registry.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Http {
+    /// User-Agent to send instead of `cargo-binstall/{version}`, e.g. for
+    /// internal mirrors routing requests on it.
+    pub user_agent: Option<CompactString>,
+    /// Extra headers to send with every request.
+    #[serde(default)]
+    pub headers: BTreeMap<CompactString, CompactString>,
+    /// Maximum number of redirects followed for a request, `0` to not
+    /// follow redirects.
+    pub max_redirects: Option<u8>,
+    /// What to do with the credentials of a request redirected to another
+    /// host.
+    pub cross_host_redirect_auth: Option<CrossHostRedirectAuth>,
+}
+
+/// What to do with the credentials of a request redirected to another host.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrossHostRedirectAuth {
+    /// Follow the redirect without the credentials.
+    Strip,
+    /// Fail instead of following the redirect.
+    Deny,
+}
+
+/// Authentication to a host, e.g. a self-hosted artifact server.
+///
+/// One of `credential-env` and `credential-helper` is required.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HostAuth {
+    pub scheme: AuthScheme,
+    /// Environment variable containing the credential, which is
+    /// `username:password` for basic auth.
+    pub credential_env: Option<CompactString>,
+    /// Program along with its arguments, which is run with an extra `get`
+    /// argument to supply the credential on demand, following the
+    /// credential helper protocol of binstall.
+    pub credential_helper: Option<Vec<CompactString>>,
+}
+
+/// Programs along with their arguments run on install events, which are
+/// given a JSON description of the operation on stdin, e.g. to record the
+/// installed crates in an inventory.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hooks {
+    /// Run before resolving the crates requested, aborting if it fails.
+    pub pre_resolve: Option<Vec<CompactString>>,
+    /// Run before installing the crates resolved, aborting if it fails.
+    pub pre_install: Option<Vec<CompactString>>,
+    /// Run after installing the crates, which is only warned about if it
+    /// fails.
+    pub post_install: Option<Vec<CompactString>>,
+}
+
+/// Configuration of the output.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Ui {
+    /// Set of styles applied to the output, overridden by `--theme`.
+    pub theme: Option<Theme>,
+}
+
+/// Set of styles applied to the output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    Default,
+    HighContrast,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BinstallConfig {
+    pub compile: Option<Compile>,
+    pub http: Option<Http>,
+    pub hooks: Option<Hooks>,
+    pub ui: Option<Ui>,
+    /// Authentication to hosts, keyed by the domain of the host.
+    #[serde(default)]
+    pub auth: BTreeMap<CompactString, HostAuth>,
+}
+
+impl BinstallConfig {
+    pub fn default_path() -> Result<PathBuf, ConfigLoadError> {
+        Ok(cargo_home()?.join("binstall").join("config.toml"))
+    }
+
+    pub fn load() -> Result<Self, ConfigLoadError> {
+        Self::load_from_path(Self::default_path()?)
+    }
+
+    /// * `dir` - path to the dir where the config.toml is located,
+    ///   relative `target-dir` is joined with it.
+    pub fn load_from_reader<R: io::Read>(
+        mut reader: R,
+        dir: &Path,
+    ) -> Result<Self, ConfigLoadError> {
+        let mut vec = Vec::new();
+        reader.read_to_end(&mut vec)?;
+
+        if vec.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let mut config: Self = toml_edit::de::from_slice(&vec)?;
+        if let Some(target_dir) = config
+            .compile
+            .as_mut()
+            .and_then(|compile| compile.target_dir.as_mut())
+        {
+            if target_dir.is_relative() {
+                *target_dir = dir.join(&*target_dir);
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, ConfigLoadError> {
+        let path = path.as_ref();
+        match File::open(path) {
+            Ok(file) => {
+                let file = FileLock::new_shared(file)?.set_file_path(path);
+                // Any regular file must have a parent dir
+                Self::load_from_reader(file, path.parent().unwrap())
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_loading() {
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new("[compile]\nrustc-wrapper = \"sccache\"\ntarget-dir = \"target\"\n"),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        let compile = config.compile.unwrap();
+        assert_eq!(compile.rustc_wrapper.unwrap(), Path::new("sccache"));
+        assert_eq!(
+            compile.target_dir.unwrap(),
+            Path::new("root").join("target")
+        );
+        assert!(config.http.is_none());
+        assert!(config.hooks.is_none());
+        assert!(config.ui.is_none());
+        assert!(config.auth.is_empty());
+    }
+
+    #[test]
+    fn test_loading_ui() {
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new("[ui]\ntheme = \"high-contrast\"\n"),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        assert_eq!(config.ui.unwrap().theme, Some(Theme::HighContrast));
+    }
+
+    #[test]
+    fn test_loading_hooks() {
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new(
+                "[hooks]\npre-install = [\"inventory\", \"check\"]\npost-install = [\"inventory\", \"record\"]\n",
+            ),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        let hooks = config.hooks.unwrap();
+        assert!(hooks.pre_resolve.is_none());
+        assert_eq!(hooks.pre_install.unwrap(), ["inventory", "check"]);
+        assert_eq!(hooks.post_install.unwrap(), ["inventory", "record"]);
+    }
+
+    #[test]
+    fn test_loading_http() {
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new(
+                "[http]\nuser-agent = \"mirror-client\"\nheaders = { X-Mirror-Group = \"build\" }\nmax-redirects = 3\ncross-host-redirect-auth = \"deny\"\n",
+            ),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        let http = config.http.unwrap();
+        assert_eq!(http.user_agent.unwrap(), "mirror-client");
+        assert_eq!(http.headers["X-Mirror-Group"], "build");
+        assert_eq!(http.max_redirects, Some(3));
+        assert_eq!(
+            http.cross_host_redirect_auth,
+            Some(CrossHostRedirectAuth::Deny)
+        );
+    }
+
+    #[test]
+    fn test_loading_auth() {
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new(
+                "[auth.\"artifacts.example.com\"]\nscheme = \"bearer\"\ncredential-env = \"ARTIFACTS_TOKEN\"\n",
+            ),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        let auth = &config.auth["artifacts.example.com"];
+        assert_eq!(auth.scheme, AuthScheme::Bearer);
+        assert_eq!(auth.credential_env.as_deref(), Some("ARTIFACTS_TOKEN"));
+        assert!(auth.credential_helper.is_none());
+
+        let config = BinstallConfig::load_from_reader(
+            Cursor::new(
+                "[auth.\"artifacts.example.com\"]\nscheme = \"basic\"\ncredential-helper = [\"vault-helper\", \"--profile\", \"ci\"]\n",
+            ),
+            Path::new("root"),
+        )
+        .unwrap();
+
+        let auth = &config.auth["artifacts.example.com"];
+        assert_eq!(auth.scheme, AuthScheme::Basic);
+        assert_eq!(
+            auth.credential_helper.as_deref().unwrap(),
+            ["vault-helper", "--profile", "ci"]
+        );
+    }
+}
@@ -151,18 +151,39 @@ pub struct Records {
     data: BTreeSet<Data>,
 }
 
-impl Records {
-    fn load_impl(&mut self) -> Result<(), Error> {
-        let reader = io::BufReader::with_capacity(BUFFER_SIZE, &mut self.file);
-        let stream_deser = serde_json::Deserializer::from_reader(reader).into_iter();
+/// Read the records concatenated in `reader` into `data`.
+fn read_records(reader: impl io::Read, data: &mut BTreeSet<Data>) -> Result<(), Error> {
+    let reader = io::BufReader::with_capacity(BUFFER_SIZE, reader);
+    let stream_deser = serde_json::Deserializer::from_reader(reader).into_iter();
 
-        for res in stream_deser {
-            let item = res?;
+    for res in stream_deser {
+        let item = res?;
 
-            self.data.replace(item);
-        }
+        data.replace(item);
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Load the records from `path` under a shared lock, for read-only queries.
+///
+/// The file is not created if it does not exist.
+pub fn load_shared(path: impl AsRef<Path>) -> Result<BTreeSet<Data>, Error> {
+    let path = path.as_ref();
+    let mut data = BTreeSet::new();
+
+    match fs::File::open(path) {
+        Ok(file) => read_records(FileLock::new_shared(file)?.set_file_path(path), &mut data)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(data)
+}
+
+impl Records {
+    fn load_impl(&mut self) -> Result<(), Error> {
+        read_records(&mut self.file, &mut self.data)
     }
 
     pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
@@ -189,6 +210,19 @@ impl Records {
         Ok(())
     }
 
+    /// Serialize the records in the same format as [`Records::overwrite`].
+    pub(crate) fn to_json_string(&self) -> Result<String, Error> {
+        let mut json = String::new();
+        for data in &self.data {
+            json += &serde_json::to_string(data)?;
+        }
+        Ok(json)
+    }
+
+    pub(crate) fn file_mut(&mut self) -> &mut FileLock {
+        &mut self.file
+    }
+
     pub fn get(&self, value: impl AsRef<str>) -> Option<&CrateInfo> {
         self.data.get(value.as_ref()).map(|data| &data.crate_info)
     }
@@ -271,6 +305,9 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into(), "2".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
             },
             CrateInfo {
                 name: "b".into(),
@@ -279,6 +316,9 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into(), "2".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
             },
             CrateInfo {
                 name: "a".into(),
@@ -287,6 +327,9 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
             },
         ];
 
@@ -317,6 +360,9 @@ mod test {
             source: CrateSource::cratesio_registry(),
             target,
             bins: vec!["1".into(), "2".into()],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
         };
         append_to_path(path, [new_metadata.clone()]).unwrap();
         metadata_set.insert(new_metadata);
@@ -0,0 +1,705 @@
+//! Binstall's `crates-v2.json` manifest.
+//!
+//! This is the successor of [`binstall_crates_v1`](crate::binstall_crates_v1).
+//! Unlike v1, the file is a single JSON document with an explicit schema version:
+//!
+//! ```json
+//! { "schema": 2, "crates": [ { "name": "...", ... } ] }
+//! ```
+//!
+//! On top of everything recorded by v1, each crate records the files it
+//! installed, when it was installed and an optional version pin.
+//!
+//! [`Records::load_or_migrate`] transparently migrates the records from
+//! `crates-v1.json` which are missing or outdated, e.g. when `crates-v2.json`
+//! does not exist yet or an older version of cargo-binstall, which only
+//! writes `crates-v1.json`, installed a crate since.
+
+use std::{
+    borrow::Borrow,
+    cmp,
+    collections::{btree_set, BTreeSet},
+    fmt::Write as _,
+    fs,
+    io::{self, Read, Seek, Write},
+    iter::{IntoIterator, Iterator},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use compact_str::CompactString;
+use fs_lock::FileLock;
+use home::cargo_home;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    binstall_crates_v1::{Data as V1Data, Records as V1Records},
+    crate_info::CrateInfo,
+    helpers::create_if_not_exist,
+};
+
+/// Buffer size for loading and writing binstall_crates_v2 manifest.
+const BUFFER_SIZE: usize = 4096 * 5;
+
+/// Schema version written by this implementation.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to parse json: {0}")]
+    SerdeJsonParse(#[from] serde_json::Error),
+
+    #[error("Unsupported schema version {0}, expected at most {SCHEMA_VERSION}")]
+    #[diagnostic(help("The manifest is written by a newer version of cargo-binstall"))]
+    UnsupportedSchema(u32),
+}
+
+pub fn default_path() -> Result<PathBuf, Error> {
+    let dir = cargo_home()?.join("binstall");
+
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir.join("crates-v2.json"))
+}
+
+/// A file installed by a crate.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct FileRecord {
+    /// Name of the binary, as listed in `bins`.
+    pub name: CompactString,
+
+    /// Where the file was installed to, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// SHA256 of the installed file in lower case hex, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<CompactString>,
+}
+
+impl FileRecord {
+    fn from_bin_name(name: CompactString) -> Self {
+        Self {
+            name,
+            path: None,
+            sha256: None,
+        }
+    }
+}
+
+/// Return the SHA256 of the file at `path` in lower case hex, as recorded in
+/// [`FileRecord::sha256`].
+pub fn file_sha256(path: &Path) -> io::Result<CompactString> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .fold(CompactString::default(), |mut hex, byte| {
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        }))
+}
+
+/// Keeps a crate from being upgraded out of `version_req`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Pin {
+    pub version_req: CompactString,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<CompactString>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Data {
+    /// Provenance (registry/git source, the downloaded artifact and the
+    /// publisher of the version) is recorded in `crate_info`, same as v1.
+    #[serde(flatten)]
+    pub crate_info: CrateInfo,
+
+    #[serde(default)]
+    pub files: Vec<FileRecord>,
+
+    /// Time of installation, in seconds since the unix epoch.
+    ///
+    /// `None` for records migrated from v1, which does not record it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<Pin>,
+
+    /// Forwards compatibility. Unknown keys from future versions
+    /// will be stored here and retained when the file is saved.
+    ///
+    /// We use an `Vec` here since it is never accessed in Rust.
+    #[serde(flatten, with = "tuple_vec_map")]
+    pub other: Vec<(CompactString, serde_json::Value)>,
+}
+
+impl From<CrateInfo> for Data {
+    fn from(crate_info: CrateInfo) -> Self {
+        let files = crate_info
+            .bins
+            .iter()
+            .cloned()
+            .map(FileRecord::from_bin_name)
+            .collect();
+
+        Self {
+            crate_info,
+            files,
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_secs()),
+            pin: None,
+            other: Vec::new(),
+        }
+    }
+}
+
+impl From<V1Data> for Data {
+    fn from(data: V1Data) -> Self {
+        let mut this = Self::from(data.crate_info);
+        this.installed_at = None;
+        this.other = data.other;
+        this
+    }
+}
+
+impl From<Data> for CrateInfo {
+    fn from(data: Data) -> Self {
+        data.crate_info
+    }
+}
+
+impl Borrow<str> for Data {
+    fn borrow(&self) -> &str {
+        &self.crate_info.name
+    }
+}
+
+impl PartialEq for Data {
+    fn eq(&self, other: &Self) -> bool {
+        self.crate_info.name == other.crate_info.name
+    }
+}
+impl PartialEq<CrateInfo> for Data {
+    fn eq(&self, other: &CrateInfo) -> bool {
+        self.crate_info.name == other.name
+    }
+}
+impl PartialEq<Data> for CrateInfo {
+    fn eq(&self, other: &Data) -> bool {
+        self.name == other.crate_info.name
+    }
+}
+impl Eq for Data {}
+
+impl PartialOrd for Data {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Data {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.crate_info.name.cmp(&other.crate_info.name)
+    }
+}
+
+#[derive(Deserialize)]
+struct SchemaVersion {
+    schema: u32,
+}
+
+#[derive(Deserialize)]
+struct DocumentDe {
+    crates: BTreeSet<Data>,
+}
+
+#[derive(Serialize)]
+struct DocumentSer<'a> {
+    schema: u32,
+    crates: &'a BTreeSet<Data>,
+}
+
+#[derive(Debug)]
+pub struct Records {
+    file: FileLock,
+    /// Use BTreeSet to dedup the metadata
+    data: BTreeSet<Data>,
+}
+
+/// Read the records in `reader`, which has none if it is empty.
+fn read_records(mut reader: impl Read) -> Result<BTreeSet<Data>, Error> {
+    let mut buffer = Vec::with_capacity(BUFFER_SIZE);
+    reader.read_to_end(&mut buffer)?;
+
+    if buffer.iter().all(u8::is_ascii_whitespace) {
+        return Ok(BTreeSet::new());
+    }
+
+    let SchemaVersion { schema } = serde_json::from_slice(&buffer)?;
+    if schema > SCHEMA_VERSION {
+        return Err(Error::UnsupportedSchema(schema));
+    }
+
+    let DocumentDe { crates } = serde_json::from_slice(&buffer)?;
+
+    Ok(crates)
+}
+
+/// Migrate the v1 records missing from `data`, or of another version or
+/// target than the ones in `data`, since they are installed by an older
+/// version of cargo-binstall.
+///
+/// The pins of the records replaced are retained.
+fn migrate<'a>(data: &mut BTreeSet<Data>, v1: impl IntoIterator<Item = &'a V1Data>) {
+    for v1_data in v1 {
+        let v1_info = &v1_data.crate_info;
+        let prev = data.get(v1_info.name.as_str());
+        if let Some(prev) = prev {
+            if prev.crate_info.current_version == v1_info.current_version
+                && prev.crate_info.target == v1_info.target
+            {
+                continue;
+            }
+        }
+
+        let pin = prev.and_then(|prev| prev.pin.clone());
+        let mut migrated = Data::from(V1Data {
+            crate_info: v1_info.clone(),
+            other: v1_data.other.clone(),
+        });
+        migrated.pin = pin;
+        data.replace(migrated);
+    }
+}
+
+/// Load the records from `path` under a shared lock and migrate the missing
+/// or outdated ones from the v1 records, for read-only queries.
+///
+/// The file is not created if it does not exist.
+pub fn load_shared(path: impl AsRef<Path>, v1: &BTreeSet<V1Data>) -> Result<BTreeSet<Data>, Error> {
+    let path = path.as_ref();
+
+    let mut data = match fs::File::open(path) {
+        Ok(file) => read_records(FileLock::new_shared(file)?.set_file_path(path))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+        Err(err) => return Err(err.into()),
+    };
+    migrate(&mut data, v1);
+
+    Ok(data)
+}
+
+impl Records {
+    fn load_impl(&mut self) -> Result<(), Error> {
+        self.data = read_records(&mut self.file)?;
+        Ok(())
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut this = Self {
+            file: create_if_not_exist(path.as_ref())?,
+            data: BTreeSet::default(),
+        };
+        this.load_impl()?;
+        Ok(this)
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from_path(default_path()?)
+    }
+
+    /// Load records from `path` and migrate the missing or outdated ones
+    /// from the v1 records.
+    ///
+    /// The v1 records are left untouched.
+    pub fn load_or_migrate(path: impl AsRef<Path>, v1: &V1Records) -> Result<Self, Error> {
+        let mut this = Self {
+            file: create_if_not_exist(path.as_ref())?,
+            data: BTreeSet::default(),
+        };
+        this.load_impl()?;
+        migrate(&mut this.data, v1);
+
+        Ok(this)
+    }
+
+    /// Serialize the records in the same format as [`Records::overwrite`].
+    pub(crate) fn to_json_string(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(&DocumentSer {
+            schema: SCHEMA_VERSION,
+            crates: &self.data,
+        })?)
+    }
+
+    pub(crate) fn file_mut(&mut self) -> &mut FileLock {
+        &mut self.file
+    }
+
+    /// **Warning: This will overwrite all existing records!**
+    pub fn overwrite(mut self) -> Result<(), Error> {
+        self.file.rewind()?;
+
+        let mut writer = io::BufWriter::with_capacity(BUFFER_SIZE, &mut self.file);
+        serde_json::to_writer_pretty(
+            &mut writer,
+            &DocumentSer {
+                schema: SCHEMA_VERSION,
+                crates: &self.data,
+            },
+        )?;
+        writer.flush()?;
+        drop(writer);
+
+        let len = self.file.stream_position()?;
+        self.file.set_len(len)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, value: impl AsRef<str>) -> Option<&Data> {
+        self.data.get(value.as_ref())
+    }
+
+    pub fn contains(&self, value: impl AsRef<str>) -> bool {
+        self.data.contains(value.as_ref())
+    }
+
+    /// Adds a value to the set.
+    /// If the set did not have an equal element present, true is returned.
+    /// If the set did have an equal element present, false is returned,
+    /// and the entry is not updated.
+    pub fn insert(&mut self, value: CrateInfo) -> bool {
+        self.data.insert(Data::from(value))
+    }
+
+    /// Return the previous `CrateInfo` for the package if there is any.
+    ///
+    /// The pin of the previous record is retained.
+    pub fn replace(&mut self, value: CrateInfo) -> Option<CrateInfo> {
+        let mut data = Data::from(value);
+        let prev = self.data.take(data.crate_info.name.as_str());
+        if let Some(prev) = &prev {
+            data.pin.clone_from(&prev.pin);
+        }
+        self.data.insert(data);
+        prev.map(CrateInfo::from)
+    }
+
+    /// Set or clear the pin of crate `name`, return `false` if it is not
+    /// installed.
+    pub fn set_pin(&mut self, name: impl AsRef<str>, pin: Option<Pin>) -> bool {
+        match self.data.take(name.as_ref()) {
+            Some(mut data) => {
+                data.pin = pin;
+                self.data.insert(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the time of installation of crate `name`, return `false` if it
+    /// is not installed.
+    pub fn set_installed_at(&mut self, name: impl AsRef<str>, installed_at: u64) -> bool {
+        match self.data.take(name.as_ref()) {
+            Some(mut data) => {
+                data.installed_at = Some(installed_at);
+                self.data.insert(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record the path and SHA256 of the files of crate `name` installed in
+    /// `dir`, return `false` if it is not installed.
+    ///
+    /// The path of the files missing from `dir` is not recorded.
+    pub fn record_files(&mut self, name: impl AsRef<str>, dir: &Path) -> bool {
+        match self.data.take(name.as_ref()) {
+            Some(mut data) => {
+                for file in &mut data.files {
+                    let path = dir.join(file.name.as_str());
+                    file.sha256 = file_sha256(&path).ok();
+                    file.path = file.sha256.as_ref().map(|_| path);
+                }
+                self.data.insert(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, value: impl AsRef<str>) -> bool {
+        self.data.remove(value.as_ref())
+    }
+
+    pub fn take(&mut self, value: impl AsRef<str>) -> Option<CrateInfo> {
+        self.data.take(value.as_ref()).map(CrateInfo::from)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Records {
+    type Item = &'a Data;
+
+    type IntoIter = btree_set::Iter<'a, Data>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binstall_crates_v1, crate_info::CrateSource};
+
+    use detect_targets::TARGET;
+    use semver::Version;
+    use tempfile::TempDir;
+
+    fn crate_info(name: &str, version: Version) -> CrateInfo {
+        CrateInfo {
+            name: name.into(),
+            version_req: "*".into(),
+            current_version: version,
+            source: CrateSource::cratesio_registry(),
+            target: TARGET.into(),
+            bins: vec!["1".into(), "2".into()],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
+        }
+    }
+
+    #[test]
+    fn rw_test() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("crates-v2.json");
+
+        let mut records = Records::load_from_path(&path).unwrap();
+        assert!(records.is_empty());
+
+        assert!(records.insert(crate_info("a", Version::new(0, 1, 0))));
+        assert!(records.insert(crate_info("b", Version::new(0, 1, 0))));
+        assert!(records.set_pin(
+            "a",
+            Some(Pin {
+                version_req: "0.1".into(),
+                reason: None,
+            })
+        ));
+        records.overwrite().unwrap();
+
+        let mut records = Records::load_from_path(&path).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let a = records.get("a").unwrap();
+        assert_eq!(a.crate_info.current_version, Version::new(0, 1, 0));
+        assert_eq!(a.files.len(), 2);
+        assert_eq!(a.files[0].name, "1");
+        assert!(a.installed_at.is_some());
+
+        // Upgrading retains the pin
+        records.replace(crate_info("a", Version::new(0, 1, 1)));
+        assert!(records.set_installed_at("a", 42));
+        assert!(records.remove("b"));
+        records.overwrite().unwrap();
+
+        let records = Records::load_from_path(&path).unwrap();
+        assert_eq!(records.len(), 1);
+
+        let a = records.get("a").unwrap();
+        assert_eq!(a.crate_info.current_version, Version::new(0, 1, 1));
+        assert_eq!(a.pin.as_ref().unwrap().version_req, "0.1");
+        assert_eq!(a.installed_at, Some(42));
+    }
+
+    #[test]
+    fn unknown_keys_are_retained() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("crates-v2.json");
+
+        fs::write(
+            &path,
+            r#"{
+                "schema": 2,
+                "crates": [{
+                    "name": "a",
+                    "version_req": "*",
+                    "current_version": "0.1.0",
+                    "source": { "source_type": "Registry", "url": "https://github.com/rust-lang/crates.io-index" },
+                    "target": "x86_64-unknown-linux-gnu",
+                    "bins": ["a"],
+                    "files": [{ "name": "a", "path": "/bin/a" }],
+                    "from_the_future": 1
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let records = Records::load_from_path(&path).unwrap();
+        let a = records.get("a").unwrap();
+        assert_eq!(a.files[0].path.as_deref(), Some(Path::new("/bin/a")));
+        assert_eq!(a.other.len(), 1);
+        records.overwrite().unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("from_the_future"));
+    }
+
+    #[test]
+    fn unsupported_schema() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("crates-v2.json");
+
+        fs::write(&path, r#"{ "schema": 3, "crates": [] }"#).unwrap();
+
+        assert!(matches!(
+            Records::load_from_path(&path).unwrap_err(),
+            Error::UnsupportedSchema(3)
+        ));
+    }
+
+    #[test]
+    fn load_shared_test() {
+        let dir = TempDir::new().unwrap();
+        let v1_path = dir.path().join("crates-v1.json");
+        let v2_path = dir.path().join("crates-v2.json");
+
+        binstall_crates_v1::append_to_path(&v1_path, [crate_info("a", Version::new(0, 1, 0))])
+            .unwrap();
+        let v1 = binstall_crates_v1::load_shared(&v1_path).unwrap();
+
+        // Nothing is created, the records are migrated from v1.
+        let data = load_shared(&v2_path, &v1).unwrap();
+        assert!(data.contains("a"));
+        assert!(!v2_path.exists());
+
+        let mut records = Records::load_from_path(&v2_path).unwrap();
+        assert!(records.insert(crate_info("b", Version::new(0, 2, 0))));
+        records.overwrite().unwrap();
+
+        let data = load_shared(&v2_path, &v1).unwrap();
+        assert_eq!(data.len(), 2);
+        assert!(data.contains("a"));
+        assert!(data.contains("b"));
+    }
+
+    #[test]
+    fn test_file_sha256() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a");
+        fs::write(&path, "abc").unwrap();
+
+        assert_eq!(
+            file_sha256(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn migrate_from_v1() {
+        let dir = TempDir::new().unwrap();
+        let v1_path = dir.path().join("crates-v1.json");
+        let v2_path = dir.path().join("crates-v2.json");
+
+        binstall_crates_v1::append_to_path(
+            &v1_path,
+            [
+                crate_info("a", Version::new(0, 1, 0)),
+                crate_info("b", Version::new(0, 2, 0)),
+            ],
+        )
+        .unwrap();
+
+        let v1 = V1Records::load_from_path(&v1_path).unwrap();
+        let mut records = Records::load_or_migrate(&v2_path, &v1).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let b = records.get("b").unwrap();
+        assert_eq!(b.crate_info.current_version, Version::new(0, 2, 0));
+        assert_eq!(b.files.len(), 2);
+        assert_eq!(b.installed_at, None);
+
+        assert!(records.set_installed_at("a", 42));
+        assert!(records.set_pin(
+            "b",
+            Some(Pin {
+                version_req: "0".into(),
+                reason: None,
+            })
+        ));
+        records.overwrite().unwrap();
+
+        // Crates installed by older versions of cargo-binstall after v2 is
+        // written are only recorded in v1.
+        drop(v1);
+        binstall_crates_v1::append_to_path(
+            &v1_path,
+            [
+                crate_info("b", Version::new(0, 2, 1)),
+                crate_info("c", Version::new(0, 3, 0)),
+            ],
+        )
+        .unwrap();
+
+        let v1 = V1Records::load_from_path(&v1_path).unwrap();
+        let records = Records::load_or_migrate(&v2_path, &v1).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records.contains("c"));
+
+        // Up-to-date records are kept as is.
+        assert_eq!(records.get("a").unwrap().installed_at, Some(42));
+
+        // Outdated records are replaced, retaining their pin.
+        let b = records.get("b").unwrap();
+        assert_eq!(b.crate_info.current_version, Version::new(0, 2, 1));
+        assert_eq!(b.pin.as_ref().unwrap().version_req, "0");
+    }
+
+    #[test]
+    fn record_files_test() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("crates-v2.json");
+
+        let mut records = Records::load_from_path(&path).unwrap();
+        assert!(records.insert(crate_info("a", Version::new(0, 1, 0))));
+        fs::write(dir.path().join("1"), "abc").unwrap();
+
+        assert!(records.record_files("a", dir.path()));
+        assert!(!records.record_files("b", dir.path()));
+
+        let files = &records.get("a").unwrap().files;
+        assert_eq!(files[0].path.as_deref(), Some(&*dir.path().join("1")));
+        assert_eq!(
+            files[0].sha256.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        // `2` is not installed
+        assert_eq!(files[1].path, None);
+        assert_eq!(files[1].sha256, None);
+    }
+}
@@ -31,6 +31,16 @@ use super::crate_info::CrateInfo;
 mod crate_version_source;
 use crate_version_source::*;
 
+/// Return the key used by cargo to identify an installed crate,
+/// e.g. `cargo-binstall 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)`.
+pub(crate) fn package_id(metadata: &CrateInfo) -> String {
+    let name = &metadata.name;
+    let version = &metadata.current_version;
+    let source = Source::from(&metadata.source);
+
+    format!("{name} {version} ({source})")
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct CratesToml<'a> {
     #[serde(with = "tuple_vec_map")]
@@ -89,14 +99,17 @@ impl<'v1> CratesToml<'v1> {
             this: &CratesToml<'_>,
             writer: &mut dyn io::Write,
         ) -> Result<(), CratesTomlParseError> {
-            let data = toml_edit::ser::to_string_pretty(&this)?;
-            writer.write_all(data.as_bytes())?;
+            writer.write_all(this.to_string_pretty()?.as_bytes())?;
             Ok(())
         }
 
         inner(self, &mut writer)
     }
 
+    pub fn to_string_pretty(&self) -> Result<String, CratesTomlParseError> {
+        Ok(toml_edit::ser::to_string_pretty(self)?)
+    }
+
     pub fn write_to_file(&self, file: &mut File) -> Result<(), CratesTomlParseError> {
         self.write_to_writer(&mut *file)?;
         let pos = file.stream_position()?;
@@ -112,23 +125,13 @@ impl<'v1> CratesToml<'v1> {
     }
 
     pub fn add_crate(&mut self, metadata: &'v1 CrateInfo) {
-        let name = &metadata.name;
-        let version = &metadata.current_version;
-        let source = Source::from(&metadata.source);
-
-        self.v1.push((
-            format!("{name} {version} ({source})").into(),
-            Cow::borrowed(&metadata.bins),
-        ));
+        self.v1
+            .push((package_id(metadata).into(), Cow::borrowed(&metadata.bins)));
     }
 
-    pub fn append_to_file(
-        file: &mut File,
-        crates: &[CrateInfo],
-    ) -> Result<(), CratesTomlParseError> {
-        let mut c1 = CratesToml::load_from_reader(&mut *file)?;
-
-        c1.remove_all(&{
+    /// Add `crates`, replacing existing entries of the same crates.
+    pub fn add_crates(&mut self, crates: &'v1 [CrateInfo]) {
+        self.remove_all(&{
             let mut crate_names: Vec<_> = crates
                 .iter()
                 .map(|metadata| metadata.name.as_str())
@@ -137,11 +140,19 @@ impl<'v1> CratesToml<'v1> {
             crate_names
         });
 
-        c1.v1.reserve_exact(crates.len());
+        self.v1.reserve_exact(crates.len());
 
         for metadata in crates {
-            c1.add_crate(metadata);
+            self.add_crate(metadata);
         }
+    }
+
+    pub fn append_to_file(
+        file: &mut File,
+        crates: &[CrateInfo],
+    ) -> Result<(), CratesTomlParseError> {
+        let mut c1 = CratesToml::load_from_reader(&mut *file)?;
+        c1.add_crates(crates);
 
         file.rewind()?;
         c1.write_to_file(file)?;
@@ -237,6 +248,9 @@ mod tests {
                 source: CrateSource::cratesio_registry(),
                 target: TARGET.into(),
                 bins: vec!["cargo-binstall".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
             }],
         )
         .unwrap();
@@ -263,6 +277,9 @@ mod tests {
                 source: CrateSource::cratesio_registry(),
                 target: TARGET.into(),
                 bins: vec!["cargo-binstall".into()],
+                artifact: None,
+                provenance: None,
+                preferred_target: None,
             }],
         )
         .unwrap();
@@ -0,0 +1,195 @@
+//! Cargo's `.crates2.json` manifest.
+//!
+//! On top of what is recorded in [`.crates.toml`](crate::cargo_crates_v1), Cargo records
+//! how each crate was built (features, profile, target and rustc version) here, and uses it to
+//! decide whether `cargo install` needs to reinstall an already installed crate.
+//!
+//! Binstall records its installs the way `cargo install --target <target>` of the crate with
+//! default features would, but leaves `rustc` unset since the binaries were not built locally,
+//! which Cargo treats as matching any toolchain.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::PathBuf,
+};
+
+use compact_str::CompactString;
+use home::cargo_home;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{cargo_crates_v1::package_id, crate_info::CrateInfo};
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum CratesJsonParseError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to parse json: {0}")]
+    SerdeJsonParse(#[from] serde_json::Error),
+}
+
+/// Mirrors `InstallInfo` of `cargo`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InstallInfo {
+    /// Version requirement passed to `--version`.
+    pub version_req: Option<CompactString>,
+    pub bins: BTreeSet<CompactString>,
+    pub features: BTreeSet<CompactString>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub profile: CompactString,
+    pub target: Option<CompactString>,
+    /// Output of `rustc -V`, `None` if the binaries were not built locally.
+    pub rustc: Option<CompactString>,
+
+    /// Forwards compatibility, retained when the file is saved.
+    #[serde(flatten, with = "tuple_vec_map")]
+    pub other: Vec<(CompactString, serde_json::Value)>,
+}
+
+impl From<&CrateInfo> for InstallInfo {
+    fn from(metadata: &CrateInfo) -> Self {
+        Self {
+            version_req: (metadata.version_req != "*").then(|| metadata.version_req.clone()),
+            bins: metadata.bins.iter().cloned().collect(),
+            features: BTreeSet::new(),
+            all_features: false,
+            no_default_features: false,
+            profile: CompactString::const_new("release"),
+            target: Some(metadata.target.clone()),
+            rustc: None,
+            other: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CratesJson {
+    /// Key is the package id, same as the key in `.crates.toml`.
+    installs: BTreeMap<CompactString, InstallInfo>,
+
+    /// Forwards compatibility, retained when the file is saved.
+    #[serde(flatten, with = "tuple_vec_map")]
+    other: Vec<(CompactString, serde_json::Value)>,
+}
+
+impl CratesJson {
+    pub fn default_path() -> Result<PathBuf, CratesJsonParseError> {
+        Ok(cargo_home()?.join(".crates2.json"))
+    }
+
+    pub fn load_from_reader<R: io::Read>(mut reader: R) -> Result<Self, CratesJsonParseError> {
+        let mut vec = Vec::new();
+        reader.read_to_end(&mut vec)?;
+
+        if vec.iter().all(u8::is_ascii_whitespace) {
+            Ok(Self::default())
+        } else {
+            serde_json::from_slice(&vec).map_err(CratesJsonParseError::from)
+        }
+    }
+
+    pub fn get(&self, package_id: &str) -> Option<&InstallInfo> {
+        self.installs.get(package_id)
+    }
+
+    /// Add `crates`, replacing existing entries of the same crates.
+    pub fn add_crates(&mut self, crates: &[CrateInfo]) {
+        self.installs.retain(|package_id, _| {
+            let crate_name = package_id.split(' ').next().unwrap_or_default();
+            !crates.iter().any(|metadata| metadata.name == crate_name)
+        });
+
+        for metadata in crates {
+            self.installs
+                .insert(package_id(metadata).into(), InstallInfo::from(metadata));
+        }
+    }
+
+    pub fn to_json_string(&self) -> Result<String, CratesJsonParseError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_info::CrateSource;
+
+    use detect_targets::TARGET;
+    use semver::Version;
+
+    #[test]
+    fn test_add_crates() {
+        let raw_data = br#"{
+            "installs": {
+                "cargo-binstall 0.10.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "version_req": null,
+                    "bins": ["cargo-binstall"],
+                    "features": ["static"],
+                    "all_features": false,
+                    "no_default_features": true,
+                    "profile": "release",
+                    "target": "x86_64-unknown-linux-gnu",
+                    "rustc": "rustc 1.79.0 (129f3b996 2024-06-10)"
+                },
+                "tokei 12.1.2 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "version_req": null,
+                    "bins": ["tokei"],
+                    "features": [],
+                    "all_features": false,
+                    "no_default_features": false,
+                    "profile": "release",
+                    "target": "x86_64-unknown-linux-gnu",
+                    "rustc": "rustc 1.79.0 (129f3b996 2024-06-10)",
+                    "from_the_future": 1
+                }
+            }
+        }"#;
+
+        let mut crates_json = CratesJson::load_from_reader(raw_data.as_slice()).unwrap();
+
+        crates_json.add_crates(&[CrateInfo {
+            name: "cargo-binstall".into(),
+            version_req: "*".into(),
+            current_version: Version::new(0, 11, 1),
+            source: CrateSource::cratesio_registry(),
+            target: TARGET.into(),
+            bins: vec!["cargo-binstall".into()],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
+        }]);
+
+        let crates_json =
+            CratesJson::load_from_reader(crates_json.to_json_string().unwrap().as_bytes()).unwrap();
+        assert_eq!(crates_json.installs.len(), 2);
+
+        let info = crates_json
+            .get("cargo-binstall 0.11.1 (registry+https://github.com/rust-lang/crates.io-index)")
+            .unwrap();
+        assert_eq!(info.version_req, None);
+        assert!(info.features.is_empty());
+        assert!(!info.no_default_features);
+        assert_eq!(info.profile, "release");
+        assert_eq!(info.target.as_deref(), Some(TARGET));
+        assert_eq!(info.rustc, None);
+
+        let info = crates_json
+            .get("tokei 12.1.2 (registry+https://github.com/rust-lang/crates.io-index)")
+            .unwrap();
+        assert_eq!(info.other.len(), 1);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(CratesJson::load_from_reader(&b""[..])
+            .unwrap()
+            .installs
+            .is_empty());
+    }
+}
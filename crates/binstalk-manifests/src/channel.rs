@@ -0,0 +1,80 @@
+//! Channel manifest, a toml file listing the versions of crates approved for
+//! installation and the digests of their artifacts.
+//!
+//! It is usually hosted by an organisation and signed with minisign, so
+//! that its members only install vetted versions.
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+pub use binstalk_types::channel::{Channel, ChannelCrate};
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum ChannelLoadError {
+    #[error("Failed to deserialize toml: {0}")]
+    TomlParse(Box<toml_edit::de::Error>),
+}
+
+impl From<toml_edit::de::Error> for ChannelLoadError {
+    fn from(e: toml_edit::de::Error) -> Self {
+        ChannelLoadError::TomlParse(Box::new(e))
+    }
+}
+
+pub fn parse(content: &[u8]) -> Result<Channel, ChannelLoadError> {
+    Ok(toml_edit::de::from_slice(content)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use binstalk_types::crate_info::ChecksumAlgorithm;
+    use semver::Version;
+
+    #[test]
+    fn test_parse() {
+        let channel = parse(
+            br#"
+[crates.ripgrep]
+version = "14.1.0"
+
+[crates.ripgrep.sha256]
+x86_64-unknown-linux-musl = "4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e"
+
+[crates.ripgrep.blake3]
+x86_64-unknown-linux-musl = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+aarch64-apple-darwin = "2bdfd4c2f8ac2a5ba1bd5ce7a6e8bc2a1f43d6ed4d3ad6f5e3d8ee1b1cf6ea77"
+
+[crates.cargo-watch]
+version = "8.5.2"
+"#,
+        )
+        .unwrap();
+
+        let ripgrep = &channel.crates["ripgrep"];
+        assert_eq!(ripgrep.version, Version::new(14, 1, 0));
+        assert_eq!(
+            ripgrep.sha256["x86_64-unknown-linux-musl"],
+            "4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e"
+        );
+        assert_eq!(
+            ripgrep.digest_algorithms(),
+            [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Blake3]
+        );
+        // sha256 is preferred
+        assert_eq!(
+            ripgrep.digest("x86_64-unknown-linux-musl").unwrap().0,
+            ChecksumAlgorithm::Sha256
+        );
+        assert_eq!(
+            ripgrep.digest("aarch64-apple-darwin").unwrap().0,
+            ChecksumAlgorithm::Blake3
+        );
+        assert_eq!(ripgrep.digest("x86_64-pc-windows-msvc"), None);
+        assert!(!channel.crates["cargo-watch"].lists_digests());
+
+        assert!(parse(b"[crates.ripgrep]\nversion = \"latest\"").is_err());
+    }
+}
@@ -1,8 +1,8 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs,
     io::{self, Seek},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use fs_lock::FileLock;
@@ -10,11 +10,19 @@ use miette::Diagnostic;
 use thiserror::Error as ThisError;
 
 use crate::{
-    binstall_crates_v1::{Error as BinstallCratesV1Error, Records as BinstallCratesV1Records},
+    binstall_crates_v1::{
+        self, Error as BinstallCratesV1Error, Records as BinstallCratesV1Records,
+    },
+    binstall_crates_v2::{
+        self, Data as BinstallCratesV2Data, Error as BinstallCratesV2Error,
+        Records as BinstallCratesV2Records,
+    },
     cargo_crates_v1::{CratesToml, CratesTomlParseError},
-    crate_info::CrateInfo,
+    cargo_crates_v2::{CratesJson, CratesJsonParseError},
+    crate_info::{CrateInfo, InstalledBy, InstalledCrate},
     helpers::create_if_not_exist,
-    CompactString, Version,
+    journal::{self, Update},
+    CompactString,
 };
 
 #[derive(Debug, Diagnostic, ThisError)]
@@ -24,38 +32,131 @@ pub enum ManifestsError {
     #[diagnostic(transparent)]
     BinstallCratesV1(#[from] BinstallCratesV1Error),
 
+    #[error("failed to parse binstall crates-v2 manifest: {0}")]
+    #[diagnostic(transparent)]
+    BinstallCratesV2(#[from] BinstallCratesV2Error),
+
     #[error("failed to parse cargo v1 manifest: {0}")]
     #[diagnostic(transparent)]
     CargoManifestV1(#[from] CratesTomlParseError),
 
+    #[error("failed to parse cargo v2 manifest: {0}")]
+    #[diagnostic(transparent)]
+    CargoManifestV2(#[from] CratesJsonParseError),
+
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
 }
 
 pub struct Manifests {
     binstall: BinstallCratesV1Records,
+    binstall_v2: BinstallCratesV2Records,
     cargo_crates_v1: FileLock,
+    cargo_crates_v2: FileLock,
+    paths: Paths,
+    /// Time of installation recorded instead of the current time.
+    frozen_time: Option<u64>,
+}
+
+struct Paths {
+    cargo_roots: PathBuf,
+    /// Directory the binaries of the crates tracked are installed to.
+    bin: PathBuf,
+    binstall: PathBuf,
+    binstall_v2: PathBuf,
+    cargo_crates_v1: PathBuf,
+    cargo_crates_v2: PathBuf,
+    journal: PathBuf,
+}
+
+impl Paths {
+    fn new(cargo_roots: &Path) -> Self {
+        let binstall_dir = cargo_roots.join("binstall");
+
+        Self {
+            cargo_roots: cargo_roots.to_owned(),
+            bin: cargo_roots.join("bin"),
+            binstall: binstall_dir.join("crates-v1.json"),
+            binstall_v2: binstall_dir.join("crates-v2.json"),
+            cargo_crates_v1: cargo_roots.join(".crates.toml"),
+            cargo_crates_v2: cargo_roots.join(".crates2.json"),
+            journal: binstall_dir.join("manifests.journal"),
+        }
+    }
 }
 
 impl Manifests {
     pub fn open_exclusive(cargo_roots: &Path) -> Result<Self, ManifestsError> {
-        // Read cargo_binstall_metadata
-        let metadata_path = cargo_roots.join("binstall/crates-v1.json");
-        fs::create_dir_all(metadata_path.parent().unwrap())?;
+        fs::create_dir_all(cargo_roots.join("binstall"))?;
 
-        let binstall = BinstallCratesV1Records::load_from_path(&metadata_path)?;
+        let paths = Paths::new(cargo_roots);
 
         // Read cargo_install_v1_metadata
-        let manifest_path = cargo_roots.join(".crates.toml");
+        //
+        // Its lock is taken first and held until the end of `update`,
+        // which serializes replaying of the journal.
+        let mut cargo_crates_v1 = create_if_not_exist(&paths.cargo_crates_v1)?;
+
+        journal::replay(
+            &paths.journal,
+            (&paths.cargo_crates_v1, &mut cargo_crates_v1),
+        )?;
+
+        let cargo_crates_v2 = create_if_not_exist(&paths.cargo_crates_v2)?;
 
-        let cargo_crates_v1 = create_if_not_exist(&manifest_path)?;
+        // Read cargo_binstall_metadata
+        let binstall = BinstallCratesV1Records::load_from_path(&paths.binstall)?;
+
+        // v1 is still kept up-to-date for older versions of cargo-binstall
+        // and third party tooling.
+        let binstall_v2 = BinstallCratesV2Records::load_or_migrate(&paths.binstall_v2, &binstall)?;
 
         Ok(Self {
             binstall,
+            binstall_v2,
             cargo_crates_v1,
+            cargo_crates_v2,
+            paths,
+            frozen_time: None,
         })
     }
 
+    /// Load the records of the crates installed by cargo-binstall in
+    /// `cargo_roots`, for read-only queries.
+    ///
+    /// Unlike [`Manifests::open_exclusive`], the manifests are only locked
+    /// shared and never created, so this does not wait for a running
+    /// installation and works without write access to `cargo_roots`.
+    pub fn load_binstall_crates(
+        cargo_roots: &Path,
+    ) -> Result<BTreeSet<BinstallCratesV2Data>, ManifestsError> {
+        let paths = Paths::new(cargo_roots);
+
+        let binstall = binstall_crates_v1::load_shared(&paths.binstall)?;
+
+        Ok(binstall_crates_v2::load_shared(
+            &paths.binstall_v2,
+            &binstall,
+        )?)
+    }
+
+    /// Record `timestamp`, in seconds since the unix epoch, as the time of
+    /// installation of updated crates so that the manifests are reproducible.
+    pub fn freeze_time(&mut self, timestamp: u64) {
+        self.frozen_time = Some(timestamp);
+    }
+
+    /// Return the path the manifests were opened at, to open them again
+    /// once they are updated.
+    pub fn cargo_roots(&self) -> &Path {
+        &self.paths.cargo_roots
+    }
+
+    /// Return the records of the crates installed by cargo-binstall.
+    pub fn binstall_crates(&self) -> &BinstallCratesV2Records {
+        &self.binstall_v2
+    }
+
     fn rewind_cargo_crates_v1(&mut self) -> Result<(), ManifestsError> {
         self.cargo_crates_v1.rewind().map_err(ManifestsError::from)
     }
@@ -63,27 +164,159 @@ impl Manifests {
     /// `cargo-uninstall` can be called to uninstall crates,
     /// but it only updates .crates.toml.
     ///
-    /// So here we will honour .crates.toml only.
+    /// So here we will honour .crates.toml only, binstall's own manifest
+    /// is only used to tell whether the installed version comes from
+    /// cargo-binstall or not.
     pub fn load_installed_crates(
         &mut self,
-    ) -> Result<BTreeMap<CompactString, Version>, ManifestsError> {
+    ) -> Result<BTreeMap<CompactString, InstalledCrate>, ManifestsError> {
         self.rewind_cargo_crates_v1()?;
 
-        CratesToml::load_from_reader(&mut self.cargo_crates_v1)
-            .and_then(CratesToml::collect_into_crates_versions)
-            .map_err(ManifestsError::from)
+        let crates = CratesToml::load_from_reader(&mut self.cargo_crates_v1)
+            .and_then(CratesToml::collect_into_crates_versions)?;
+
+        Ok(crates
+            .into_iter()
+            .map(|(name, version)| {
+                let installed_by = match self.binstall_v2.get(&name) {
+                    Some(data) if data.crate_info.current_version == version => {
+                        InstalledBy::CargoBinstall
+                    }
+                    _ => InstalledBy::CargoInstall,
+                };
+
+                (
+                    name,
+                    InstalledCrate {
+                        version,
+                        installed_by,
+                    },
+                )
+            })
+            .collect())
     }
 
     pub fn update(mut self, metadata_vec: Vec<CrateInfo>) -> Result<(), ManifestsError> {
         self.rewind_cargo_crates_v1()?;
 
-        CratesToml::append_to_file(&mut self.cargo_crates_v1, &metadata_vec)?;
+        let mut crates_toml = CratesToml::load_from_reader(&mut self.cargo_crates_v1)?;
+        crates_toml.add_crates(&metadata_vec);
+        let crates_toml = crates_toml.to_string_pretty()?;
+
+        self.cargo_crates_v2.rewind()?;
+        let mut crates_json = CratesJson::load_from_reader(&mut self.cargo_crates_v2)?;
+        crates_json.add_crates(&metadata_vec);
+        let crates_json = crates_json.to_json_string()?;
 
-        for metadata in metadata_vec {
-            self.binstall.replace(metadata);
+        for metadata in &metadata_vec {
+            self.binstall_v2.replace(metadata.clone());
+            if let Some(timestamp) = self.frozen_time {
+                self.binstall_v2.set_installed_at(&metadata.name, timestamp);
+            }
+            self.binstall_v2
+                .record_files(&metadata.name, &self.paths.bin);
+            self.binstall.replace(metadata.clone());
         }
-        self.binstall.overwrite()?;
+        let binstall = self.binstall.to_json_string()?;
+        let binstall_v2 = self.binstall_v2.to_json_string()?;
+
+        journal::commit(
+            &self.paths.journal,
+            &mut [
+                Update {
+                    path: &self.paths.cargo_crates_v1,
+                    file: &mut self.cargo_crates_v1,
+                    content: crates_toml,
+                },
+                Update {
+                    path: &self.paths.cargo_crates_v2,
+                    file: &mut self.cargo_crates_v2,
+                    content: crates_json,
+                },
+                Update {
+                    path: &self.paths.binstall,
+                    file: self.binstall.file_mut(),
+                    content: binstall,
+                },
+                Update {
+                    path: &self.paths.binstall_v2,
+                    file: self.binstall_v2.file_mut(),
+                    content: binstall_v2,
+                },
+            ],
+        )?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crate_info::CrateSource;
+
+    use detect_targets::TARGET;
+    use semver::Version;
+    use tempfile::TempDir;
+
+    fn crate_info(name: &str, version: Version) -> CrateInfo {
+        CrateInfo {
+            name: name.into(),
+            version_req: "*".into(),
+            current_version: version,
+            source: CrateSource::cratesio_registry(),
+            target: TARGET.into(),
+            bins: vec![name.into()],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
+        }
+    }
+
+    #[test]
+    fn test_load_installed_crates() {
+        let dir = TempDir::new().unwrap();
+
+        Manifests::open_exclusive(dir.path())
+            .unwrap()
+            .update(vec![
+                crate_info("a", Version::new(1, 0, 0)),
+                crate_info("b", Version::new(1, 0, 0)),
+            ])
+            .unwrap();
+
+        // `cargo install` only updates .crates.toml
+        CratesToml::append_to_path(
+            dir.path().join(".crates.toml"),
+            &[
+                crate_info("b", Version::new(2, 0, 0)),
+                crate_info("c", Version::new(1, 0, 0)),
+            ],
+        )
+        .unwrap();
+
+        let installed_crates = Manifests::open_exclusive(dir.path())
+            .unwrap()
+            .load_installed_crates()
+            .unwrap();
+        let installed_crates: Vec<_> = installed_crates
+            .iter()
+            .map(|(name, installed)| {
+                (
+                    name.as_str(),
+                    installed.version.to_string(),
+                    installed.installed_by,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            installed_crates,
+            [
+                ("a", "1.0.0".to_owned(), InstalledBy::CargoBinstall),
+                ("b", "2.0.0".to_owned(), InstalledBy::CargoInstall),
+                ("c", "1.0.0".to_owned(), InstalledBy::CargoInstall),
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Crash-safe update of several manifests at once.
+//!
+//! The manifests are locked through the manifest files themselves, so they
+//! cannot be replaced by renaming a new file over them: processes waiting
+//! on the lock would end up holding the lock of the old, unlinked file.
+//!
+//! Instead, the new content of every manifest is first written to a
+//! journal, which is created with write-temp + fsync + rename so that it is
+//! either complete or absent. The manifests are then rewritten in place and
+//! synced, and the journal is removed.
+//!
+//! A crash before the journal is renamed into place leaves every manifest
+//! untouched, while a crash after that is recovered by [`replay`] the next
+//! time the manifests are opened.
+
+use std::{
+    fs,
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::create_if_not_exist;
+
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    path: &'a Path,
+    content: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnedEntry {
+    path: PathBuf,
+    content: String,
+}
+
+/// A manifest to be updated by [`commit`].
+pub(crate) struct Update<'a> {
+    pub(crate) path: &'a Path,
+    /// An opened and exclusively locked handle to `path`.
+    pub(crate) file: &'a mut fs::File,
+    pub(crate) content: String,
+}
+
+fn write_in_place(file: &mut fs::File, content: &str) -> io::Result<()> {
+    file.rewind()?;
+    file.write_all(content.as_bytes())?;
+    file.set_len(content.len() as u64)?;
+    file.sync_all()
+}
+
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    // Directories cannot be opened on Windows, where rename is durable
+    // once it returns anyway.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Atomically apply all `updates`, using `journal_path` as the journal.
+pub(crate) fn commit(journal_path: &Path, updates: &mut [Update<'_>]) -> io::Result<()> {
+    let entries: Vec<_> = updates
+        .iter()
+        .map(|update| Entry {
+            path: update.path,
+            content: &update.content,
+        })
+        .collect();
+
+    let tmp_path = journal_path.with_extension("tmp");
+    {
+        let mut tmp = fs::File::create(&tmp_path)?;
+        serde_json::to_writer(&mut tmp, &entries)?;
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, journal_path)?;
+    sync_parent_dir(journal_path)?;
+
+    for update in updates {
+        write_in_place(update.file, &update.content)?;
+    }
+
+    fs::remove_file(journal_path)?;
+    sync_parent_dir(journal_path)
+}
+
+/// Complete the update recorded in `journal_path` if the previous process
+/// crashed in the middle of [`commit`].
+///
+/// Must be called with the lock of at least one of the manifests held,
+/// which is passed in as `held` since it cannot be locked twice.
+pub(crate) fn replay(journal_path: &Path, held: (&Path, &mut fs::File)) -> io::Result<()> {
+    let json = match fs::read(journal_path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let entries: Vec<OwnedEntry> = serde_json::from_slice(&json)?;
+
+    let (held_path, held_file) = held;
+    for OwnedEntry { path, content } in entries {
+        if path == held_path {
+            write_in_place(held_file, &content)?;
+        } else {
+            let mut file = create_if_not_exist(&path)?;
+            write_in_place(&mut file, &content)?;
+        }
+    }
+
+    fs::remove_file(journal_path)?;
+    sync_parent_dir(journal_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn commit_and_replay() {
+        let dir = TempDir::new().unwrap();
+        let journal_path = dir.path().join("manifests.journal");
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+
+        let mut file_a = create_if_not_exist(&a).unwrap();
+        let mut file_b = create_if_not_exist(&b).unwrap();
+        write_in_place(&mut file_a, "a very long old content").unwrap();
+
+        commit(
+            &journal_path,
+            &mut [
+                Update {
+                    path: &a,
+                    file: &mut file_a,
+                    content: "new a".into(),
+                },
+                Update {
+                    path: &b,
+                    file: &mut file_b,
+                    content: "new b".into(),
+                },
+            ],
+        )
+        .unwrap();
+        drop(file_a);
+        drop(file_b);
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        assert!(!journal_path.exists());
+
+        let mut file_a = create_if_not_exist(&a).unwrap();
+        replay(&journal_path, (&a, &mut file_a)).unwrap();
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+
+        // Simulate a crash after the journal is written
+        let entries = [
+            Entry {
+                path: &a,
+                content: "replayed a",
+            },
+            Entry {
+                path: &b,
+                content: "replayed b",
+            },
+        ];
+        fs::write(&journal_path, serde_json::to_vec(&entries).unwrap()).unwrap();
+
+        replay(&journal_path, (&a, &mut file_a)).unwrap();
+        drop(file_a);
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), "replayed a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "replayed b");
+        assert!(!journal_path.exists());
+    }
+}
@@ -1,19 +1,35 @@
 //! Manifest formats and utilities.
 //!
-//! There are three types of manifests Binstall may deal with:
+//! There are four types of manifests Binstall may deal with:
 //! - manifests that define how to fetch and install a package
 //!   ([Cargo.toml's `[metadata.binstall]`][cargo_toml_binstall]);
 //! - manifests that record which packages _are_ installed
-//!   ([Cargo's `.crates.toml`][cargo_crates_v1] and
-//!   [Binstall's `.crates-v1.json`][binstall_crates_v1]);
-//! - manifests that specify which packages _to_ install (currently none).
+//!   ([Cargo's `.crates.toml`][cargo_crates_v1], [`.crates2.json`][cargo_crates_v2] and
+//!   [Binstall's `.crates-v1.json`][binstall_crates_v1] and its successor
+//!   [`.crates-v2.json`][binstall_crates_v2]);
+//! - manifests that specify which packages _to_ install ([tools_manifest]);
+//! - manifests that specify which packages _may_ be installed
+//!   ([channel]).
+//!
+//! Binstall also keeps [strategy_stats] locally, to fetch crates with the
+//! strategy which fetched them before first.
 
 mod helpers;
+mod journal;
 
+pub mod binstall_config;
 pub mod binstall_crates_v1;
+pub mod binstall_crates_v2;
 pub mod cargo_config;
 pub mod cargo_crates_v1;
-/// Contains both [`binstall_crates_v1`] and [`cargo_crates_v1`].
+pub mod cargo_crates_v2;
+pub mod channel;
+pub mod rust_toolchain;
+pub mod strategy_stats;
+pub mod tools_manifest;
+
+/// Contains [`binstall_crates_v1`], [`binstall_crates_v2`], [`cargo_crates_v1`]
+/// and [`cargo_crates_v2`].
 pub mod crates_manifests;
 
 pub use binstalk_types::{cargo_toml_binstall, crate_info};
@@ -0,0 +1,114 @@
+//! Rustup's `rust-toolchain.toml` and the legacy `rust-toolchain`.
+//!
+//! These are used by projects to pin the toolchain they are built with.
+//!
+//! Binstall reads them to pick the toolchain used when falling back to
+//! `cargo-install`, the same way rustup would pick it for `cargo`.
+
+use std::{fs, io, path::Path};
+
+use compact_str::CompactString;
+use miette::Diagnostic;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// File names in the order rustup looks them up in each directory.
+const FILE_NAMES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+#[derive(Debug, Deserialize)]
+struct ToolchainSection {
+    channel: Option<CompactString>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustToolchainToml {
+    toolchain: ToolchainSection,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum RustToolchainLoadError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to deserialize toml: {0}")]
+    TomlParse(Box<toml_edit::de::Error>),
+}
+
+impl From<toml_edit::de::Error> for RustToolchainLoadError {
+    fn from(e: toml_edit::de::Error) -> Self {
+        RustToolchainLoadError::TomlParse(Box::new(e))
+    }
+}
+
+/// Return the toolchain pinned by `content`, a toolchain file in either
+/// the legacy one-line format or the toml format.
+pub fn parse(content: &str) -> Result<Option<CompactString>, RustToolchainLoadError> {
+    let trimmed = content.trim();
+
+    if !trimmed.is_empty() && !trimmed.contains(['\n', '=', '[']) {
+        return Ok(Some(trimmed.into()));
+    }
+
+    // A toolchain installed at a custom `path` cannot be selected with `+`,
+    // so only the `channel` is used.
+    Ok(toml_edit::de::from_str::<RustToolchainToml>(content)?
+        .toolchain
+        .channel)
+}
+
+/// Search `dir` and its ancestors for a toolchain file and return the
+/// toolchain pinned by the first one found.
+pub fn find_toolchain(dir: &Path) -> Result<Option<CompactString>, RustToolchainLoadError> {
+    for dir in dir.ancestors() {
+        for file_name in FILE_NAMES {
+            match fs::read_to_string(dir.join(file_name)) {
+                Ok(content) => return parse(&content),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("nightly-2024-06-01\n").unwrap().unwrap(),
+            "nightly-2024-06-01"
+        );
+        assert_eq!(
+            parse("[toolchain]\nchannel = \"1.79.0\"\ncomponents = [\"clippy\"]\n")
+                .unwrap()
+                .unwrap(),
+            "1.79.0"
+        );
+        assert_eq!(parse("[toolchain]\nprofile = \"minimal\"\n").unwrap(), None);
+        assert_eq!(parse("[toolchain]\npath = \"/opt/rust\"\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_toolchain() {
+        let tempdir = TempDir::new().unwrap();
+        let nested = tempdir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+
+        fs::write(
+            tempdir.path().join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"stable\"\n",
+        )
+        .unwrap();
+        assert_eq!(find_toolchain(&nested).unwrap().unwrap(), "stable");
+
+        fs::write(tempdir.path().join("a/rust-toolchain"), "beta").unwrap();
+        assert_eq!(find_toolchain(&nested).unwrap().unwrap(), "beta");
+    }
+}
@@ -0,0 +1,175 @@
+//! Binstall's `strategy-stats.json`, recording which strategy fetched each
+//! crate over time.
+//!
+//! It is only kept locally, unlike the statistics reported to
+//! quickinstall, and is used to try the strategy which last fetched a
+//! crate first, instead of probing the other ones again:
+//!
+//! ```json
+//! { "ripgrep": { "quick-install": { "successes": 3, "last-success": 1760000000 } } }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use compact_str::CompactString;
+use fs_lock::FileLock;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{cargo_toml_binstall::Strategy, helpers::create_if_not_exist};
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to parse json: {0}")]
+    SerdeJsonParse(#[from] serde_json::Error),
+}
+
+/// Return the path of `strategy-stats.json` in `cargo_home`, creating the
+/// directory containing it.
+pub fn default_path(cargo_home: &Path) -> Result<PathBuf, Error> {
+    let dir = cargo_home.join("binstall");
+
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir.join("strategy-stats.json"))
+}
+
+/// How often a strategy fetched a crate.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StrategyRecord {
+    pub successes: u64,
+    /// Time of the last success, in seconds since the unix epoch.
+    pub last_success: u64,
+}
+
+#[derive(Debug)]
+pub struct StrategyStats {
+    file: FileLock,
+    data: BTreeMap<CompactString, BTreeMap<Strategy, StrategyRecord>>,
+}
+
+impl StrategyStats {
+    /// Load the stats from `path`, which is locked until they are dropped.
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut file = create_if_not_exist(path.as_ref())?;
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        let data = if buffer.iter().all(u8::is_ascii_whitespace) {
+            BTreeMap::new()
+        } else {
+            serde_json::from_slice(&buffer)?
+        };
+
+        Ok(Self { file, data })
+    }
+
+    pub fn get(&self, crate_name: &str) -> Option<&BTreeMap<Strategy, StrategyRecord>> {
+        self.data.get(crate_name)
+    }
+
+    /// Return the strategies which fetched `crate_name`, the one which did
+    /// most recently first.
+    pub fn strategies_by_recency(&self, crate_name: &str) -> Vec<Strategy> {
+        let mut records: Vec<_> = self
+            .get(crate_name)
+            .into_iter()
+            .flatten()
+            .map(|(strategy, record)| (*strategy, record.last_success))
+            .collect();
+        records.sort_by(|(_, x), (_, y)| y.cmp(x));
+
+        records.into_iter().map(|(strategy, _)| strategy).collect()
+    }
+
+    /// [`StrategyStats::strategies_by_recency`] of all the crates recorded.
+    pub fn strategy_order(&self) -> BTreeMap<CompactString, Vec<Strategy>> {
+        self.data
+            .keys()
+            .map(|crate_name| (crate_name.clone(), self.strategies_by_recency(crate_name)))
+            .collect()
+    }
+
+    /// Record that `strategy` fetched `crate_name` at `time`.
+    pub fn record_success(&mut self, crate_name: &str, strategy: Strategy, time: SystemTime) {
+        let record = self
+            .data
+            .entry(crate_name.into())
+            .or_default()
+            .entry(strategy)
+            .or_default();
+
+        record.successes += 1;
+        record.last_success = time
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+    }
+
+    /// **Warning: This will overwrite all existing stats!**
+    pub fn overwrite(mut self) -> Result<(), Error> {
+        self.file.rewind()?;
+
+        let mut writer = io::BufWriter::new(&mut self.file);
+        serde_json::to_writer_pretty(&mut writer, &self.data)?;
+        writer.flush()?;
+        drop(writer);
+
+        let len = self.file.stream_position()?;
+        self.file.set_len(len)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn rw_test() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("strategy-stats.json");
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let mut stats = StrategyStats::load_from_path(&path).unwrap();
+        assert!(stats.strategies_by_recency("a").is_empty());
+
+        stats.record_success("a", Strategy::QuickInstall, at(10));
+        stats.record_success("a", Strategy::CrateMetaData, at(20));
+        stats.record_success("a", Strategy::QuickInstall, at(30));
+        stats.record_success("b", Strategy::CrateMetaData, at(10));
+        stats.overwrite().unwrap();
+
+        let stats = StrategyStats::load_from_path(&path).unwrap();
+        assert_eq!(
+            stats.get("a").unwrap()[&Strategy::QuickInstall],
+            StrategyRecord {
+                successes: 2,
+                last_success: 30
+            }
+        );
+        assert_eq!(
+            stats.strategies_by_recency("a"),
+            [Strategy::QuickInstall, Strategy::CrateMetaData]
+        );
+        assert_eq!(stats.strategy_order()["b"], [Strategy::CrateMetaData]);
+    }
+}
@@ -0,0 +1,186 @@
+//! `tools.toml`, listing the tools to install, e.g. the toolchain of a
+//! project.
+//!
+//! ```toml
+//! [tools.wasm-tools]
+//! version = "1.200"
+//!
+//! [tools.cargo-component]
+//! version = "0.13"
+//! requires = ["wasm-tools"]
+//! ```
+//!
+//! A tool is installed after the tools it `requires`, and is not installed
+//! if any of them fails.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+};
+
+use compact_str::CompactString;
+use miette::Diagnostic;
+use semver::VersionReq;
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ToolsManifest {
+    #[serde(default)]
+    pub tools: BTreeMap<CompactString, Tool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Tool {
+    /// Version requirement of the tool, the latest version if not specified.
+    pub version: Option<VersionReq>,
+    /// Tools to install before this one.
+    #[serde(default)]
+    pub requires: Vec<CompactString>,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum ToolsManifestError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to deserialize toml: {0}")]
+    TomlParse(Box<toml_edit::de::Error>),
+
+    #[error("tool {tool} requires {requirement}, which is not in the tools manifest")]
+    UnknownRequirement {
+        tool: CompactString,
+        requirement: CompactString,
+    },
+
+    #[error("tool {0} requires itself through its requirements")]
+    Cycle(CompactString),
+}
+
+impl From<toml_edit::de::Error> for ToolsManifestError {
+    fn from(e: toml_edit::de::Error) -> Self {
+        ToolsManifestError::TomlParse(Box::new(e))
+    }
+}
+
+impl ToolsManifest {
+    pub fn parse(content: &str) -> Result<Self, ToolsManifestError> {
+        Ok(toml_edit::de::from_str(content)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ToolsManifestError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Return the names of the tools, each one after the tools it requires.
+    pub fn install_order(&self) -> Result<Vec<&CompactString>, ToolsManifestError> {
+        fn visit<'a>(
+            tools: &'a BTreeMap<CompactString, Tool>,
+            name: &'a CompactString,
+            visiting: &mut BTreeSet<&'a str>,
+            order: &mut Vec<&'a CompactString>,
+        ) -> Result<(), ToolsManifestError> {
+            if order.contains(&name) {
+                return Ok(());
+            }
+            if !visiting.insert(name) {
+                return Err(ToolsManifestError::Cycle(name.clone()));
+            }
+
+            for requirement in &tools[name].requires {
+                let (requirement, _) = tools.get_key_value(requirement).ok_or_else(|| {
+                    ToolsManifestError::UnknownRequirement {
+                        tool: name.clone(),
+                        requirement: requirement.clone(),
+                    }
+                })?;
+                visit(tools, requirement, visiting, order)?;
+            }
+
+            visiting.remove(name.as_str());
+            order.push(name);
+
+            Ok(())
+        }
+
+        let mut order = Vec::with_capacity(self.tools.len());
+        for name in self.tools.keys() {
+            visit(&self.tools, name, &mut BTreeSet::new(), &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    /// Return the tools requiring `tool`, directly or through other tools.
+    pub fn dependents(&self, tool: &str) -> BTreeSet<&str> {
+        let mut dependents = BTreeSet::new();
+        let mut queue = vec![tool];
+
+        while let Some(tool) = queue.pop() {
+            for (name, dependent) in &self.tools {
+                if dependent.requires.iter().any(|r| r == tool) && dependents.insert(name.as_str())
+                {
+                    queue.push(name);
+                }
+            }
+        }
+
+        dependents
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_install_order() {
+        let tools = ToolsManifest::parse(
+            r#"
+[tools.cargo-component]
+version = "0.13"
+requires = ["wasm-tools", "wit-bindgen-cli"]
+
+[tools.wit-bindgen-cli]
+requires = ["wasm-tools"]
+
+[tools.wasm-tools]
+version = "1.200"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            tools.install_order().unwrap(),
+            ["wasm-tools", "wit-bindgen-cli", "cargo-component"]
+        );
+        assert_eq!(
+            tools.dependents("wasm-tools"),
+            BTreeSet::from(["cargo-component", "wit-bindgen-cli"])
+        );
+        assert!(tools.dependents("cargo-component").is_empty());
+
+        let tools = ToolsManifest::parse(
+            r#"
+[tools.a]
+requires = ["b"]
+
+[tools.b]
+requires = ["a"]
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            tools.install_order(),
+            Err(ToolsManifestError::Cycle(_))
+        ));
+
+        let tools = ToolsManifest::parse("[tools.a]\nrequires = [\"b\"]").unwrap();
+        assert!(matches!(
+            tools.install_order(),
+            Err(ToolsManifestError::UnknownRequirement { .. })
+        ));
+    }
+}
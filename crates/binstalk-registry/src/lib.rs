@@ -45,6 +45,9 @@ pub use crates_io_registry::fetch_crate_cratesio_api;
 mod sparse_registry;
 pub use sparse_registry::SparseRegistry;
 
+mod provenance;
+pub use provenance::fetch_crate_provenance;
+
 #[derive(Debug, ThisError, Diagnostic)]
 #[diagnostic(severity(error), code(binstall::cargo_registry))]
 #[non_exhaustive]
@@ -0,0 +1,55 @@
+//! Publisher of versions on crates.io, exposed by its API but not by the
+//! index.
+
+use binstalk_downloader::remote::{Client, Url};
+use binstalk_types::crate_info::{PublishProvenance, TrustedPublisher};
+use compact_str::CompactString;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::RegistryError;
+
+const CRATES_IO_API: &str = "https://crates.io/api/v1/crates";
+
+/// Fetch who published version `version` of crate `name` on crates.io.
+///
+/// Return `None` if crates.io does not record the publisher, e.g. for old
+/// versions.
+pub async fn fetch_crate_provenance(
+    client: &Client,
+    name: &str,
+    version: &str,
+) -> Result<Option<PublishProvenance>, RegistryError> {
+    #[derive(Deserialize)]
+    struct CrateVersion {
+        version: Inner,
+    }
+
+    #[derive(Deserialize)]
+    struct Inner {
+        published_by: Option<User>,
+        trustpub_data: Option<TrustedPublisher>,
+    }
+
+    #[derive(Deserialize)]
+    struct User {
+        login: CompactString,
+    }
+
+    let mut url = Url::parse(CRATES_IO_API)?;
+    url.path_segments_mut().unwrap().push(name).push(version);
+
+    debug!("Looking up publisher of {name} v{version}");
+
+    let CrateVersion { version } = client.get(url).send(true).await?.json().await?;
+
+    let provenance = PublishProvenance {
+        published_by: version.published_by.map(|user| user.login),
+        trusted_publisher: version.trustpub_data,
+    };
+
+    Ok(
+        (provenance.published_by.is_some() || provenance.trusted_publisher.is_some())
+            .then_some(provenance),
+    )
+}
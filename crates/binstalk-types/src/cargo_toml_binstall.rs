@@ -41,6 +41,19 @@ pub enum Strategy {
     CrateMetaData,
     /// Query third-party QuickInstall for the crates.
     QuickInstall,
+    /// Download artifacts of the Azure Pipelines build of the release tag,
+    /// for crates hosted on Azure DevOps.
+    #[serde(rename = "azure-devops")]
+    AzureDevOps,
+    /// Download bottles of the Homebrew formula named after the crate.
+    ///
+    /// Only used if enabled explicitly.
+    Homebrew,
+    /// Download packages of the Wasmer registry (formerly WAPM) for wasi
+    /// targets.
+    ///
+    /// Only used if enabled explicitly.
+    Wapm,
     /// Build the crates from source using `cargo-build`.
     Compile,
 }
@@ -50,6 +63,9 @@ impl Strategy {
         match self {
             Strategy::CrateMetaData => "crate-meta-data",
             Strategy::QuickInstall => "quick-install",
+            Strategy::AzureDevOps => "azure-devops",
+            Strategy::Homebrew => "homebrew",
+            Strategy::Wapm => "wapm",
             Strategy::Compile => "compile",
         }
     }
@@ -67,12 +83,29 @@ pub struct PkgMeta {
     /// Format for package downloads
     pub pkg_fmt: Option<PkgFmt>,
 
-    /// Path template for binary files in packages
-    pub bin_dir: Option<String>,
+    /// Number of parts the package is split into, downloaded from
+    /// `{pkg-url}.001`, `{pkg-url}.002`, ... and concatenated before
+    /// extraction
+    pub pkg_parts: Option<u16>,
+
+    /// Path template(s) for binary files in packages
+    pub bin_dir: Option<BinDir>,
 
     /// Package signing configuration
     pub signing: Option<PkgSigning>,
 
+    /// URL template of the sums file listing the checksum of the package,
+    /// e.g. `{ url }.sha256` or `{ repo }/releases/download/v{ version }/SHA512SUMS`
+    pub checksum_file: Option<String>,
+
+    /// Whether to also extract the archive or compressed file the package
+    /// only contains, e.g. a tgz inside a zip, recursively; defaults to true
+    pub extract_nested: Option<bool>,
+
+    /// Whether to recreate the symlinks of tar-based packages, e.g.
+    /// `tool -> tool-1.2.3`, instead of skipping them; defaults to false
+    pub preserve_symlinks: Option<bool>,
+
     /// Stratgies to disable
     pub disabled_strategies: Option<Box<[Strategy]>>,
 
@@ -89,9 +122,18 @@ impl PkgMeta {
         if let Some(o) = &pkg_override.pkg_fmt {
             self.pkg_fmt = Some(*o);
         }
+        if let Some(o) = &pkg_override.pkg_parts {
+            self.pkg_parts = Some(*o);
+        }
         if let Some(o) = &pkg_override.bin_dir {
             self.bin_dir = Some(o.clone());
         }
+        if let Some(o) = &pkg_override.extract_nested {
+            self.extract_nested = Some(*o);
+        }
+        if let Some(o) = &pkg_override.preserve_symlinks {
+            self.preserve_symlinks = Some(*o);
+        }
     }
 
     /// Merge configuration overrides into object
@@ -119,6 +161,12 @@ impl PkgMeta {
                 .find_map(|pkg_override| pkg_override.pkg_fmt)
                 .or(self.pkg_fmt),
 
+            pkg_parts: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.pkg_parts)
+                .or(self.pkg_parts),
+
             bin_dir: pkg_overrides
                 .clone()
                 .into_iter()
@@ -131,6 +179,24 @@ impl PkgMeta {
                 .find_map(|pkg_override| pkg_override.signing.clone())
                 .or_else(|| self.signing.clone()),
 
+            checksum_file: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.checksum_file.clone())
+                .or_else(|| self.checksum_file.clone()),
+
+            extract_nested: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.extract_nested)
+                .or(self.extract_nested),
+
+            preserve_symlinks: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.preserve_symlinks)
+                .or(self.preserve_symlinks),
+
             disabled_strategies: if ignore_disabled_strategies {
                 None
             } else {
@@ -165,8 +231,11 @@ pub struct PkgOverride {
     /// Format override for package downloads
     pub pkg_fmt: Option<PkgFmt>,
 
-    /// Path template override for binary files in packages
-    pub bin_dir: Option<String>,
+    /// Number of parts override for package downloads
+    pub pkg_parts: Option<u16>,
+
+    /// Path template(s) override for binary files in packages
+    pub bin_dir: Option<BinDir>,
 
     /// Stratgies to disable
     pub disabled_strategies: Option<Box<[Strategy]>>,
@@ -174,10 +243,56 @@ pub struct PkgOverride {
     /// Package signing configuration
     pub signing: Option<PkgSigning>,
 
+    /// URL template override of the sums file listing the checksum of the package
+    pub checksum_file: Option<String>,
+
+    /// Whether to also extract the archive or compressed file the package
+    /// only contains
+    pub extract_nested: Option<bool>,
+
+    /// Whether to recreate the symlinks of tar-based packages
+    pub preserve_symlinks: Option<bool>,
+
     #[serde(skip)]
     pub ignore_disabled_strategies: bool,
 }
 
+/// Path template(s) for binary files in packages.
+///
+/// Either a single template or a list of templates tried in order,
+/// which is useful for crates whose archive layout changed between versions.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BinDir {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl BinDir {
+    /// Iterate over the templates in order of preference.
+    pub fn iter(&self) -> impl Iterator<Item = &str> + Clone + '_ {
+        let templates = match self {
+            BinDir::Single(template) => std::slice::from_ref(template),
+            BinDir::Multiple(templates) => templates.as_slice(),
+        };
+
+        templates.iter().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            BinDir::Single(_) => false,
+            BinDir::Multiple(templates) => templates.is_empty(),
+        }
+    }
+}
+
+impl From<String> for BinDir {
+    fn from(template: String) -> Self {
+        BinDir::Single(template)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct BinMeta {
@@ -194,28 +309,63 @@ pub struct PkgSigning {
     /// Signing algorithm supported by Binstall.
     pub algorithm: SigningAlgorithm,
 
-    /// Signing public key
-    pub pubkey: Cow<'static, str>,
+    /// Signing public key, discovered from the host of the repository if
+    /// not specified
+    #[serde(default)]
+    pub pubkey: Option<Cow<'static, str>>,
 
     /// Signature file override template (url to download)
     #[serde(default)]
     pub file: Option<String>,
 }
 
+/// Scheme of the credential of a host, configured by the user.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthScheme {
+    Basic,
+    Bearer,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 #[non_exhaustive]
 pub enum SigningAlgorithm {
     /// [minisign](https://jedisct1.github.io/minisign/)
     Minisign,
+    /// Signatures of `ssh-keygen -Y sign -n file`, with `pubkey` in the
+    /// format of the allowed signers file of `ssh-keygen`
+    Ssh,
 }
 
 #[cfg(test)]
 mod tests {
-    use strum::VariantArray;
+    use strum::{IntoEnumIterator, VariantArray};
 
     use super::*;
 
+    #[test]
+    fn test_bin_dir_de() {
+        let meta: PkgMeta =
+            serde_json::from_str(r#"{"bin-dir": "{ bin }{ binary-ext }"}"#).unwrap();
+        assert_eq!(
+            meta.bin_dir.unwrap().iter().collect::<Vec<_>>(),
+            ["{ bin }{ binary-ext }"]
+        );
+
+        let meta: PkgMeta = serde_json::from_str(
+            r#"{"bin-dir": ["{ name }-{ target }/{ bin }{ binary-ext }", "{ bin }{ binary-ext }"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            meta.bin_dir.unwrap().iter().collect::<Vec<_>>(),
+            [
+                "{ name }-{ target }/{ bin }{ binary-ext }",
+                "{ bin }{ binary-ext }"
+            ]
+        );
+    }
+
     #[test]
     fn test_strategy_ser() {
         Strategy::VARIANTS.iter().for_each(|strategy| {
@@ -225,4 +375,96 @@ mod tests {
             )
         });
     }
+
+    #[test]
+    fn test_guess_pkg_format() {
+        let guess = |url| PkgFmt::guess_pkg_format(url);
+
+        assert_eq!(guess("https://a.com/tool-x86_64.tar.gz"), Some(PkgFmt::Tgz));
+        assert_eq!(guess("https://a.com/tool-x86_64.gz"), Some(PkgFmt::Gz));
+        assert_eq!(guess("https://a.com/tool-x86_64.tar.xz"), Some(PkgFmt::Txz));
+        assert_eq!(guess("https://a.com/foo-linux.xz"), Some(PkgFmt::Xz));
+        assert_eq!(guess("https://a.com/tool.tar.zst"), Some(PkgFmt::Tzstd));
+        assert_eq!(guess("https://a.com/tool-x86_64.zst"), Some(PkgFmt::Zst));
+        assert_eq!(guess("https://a.com/tool.tar.lz4"), Some(PkgFmt::Tlz4));
+        assert_eq!(guess("https://a.com/tool.tlz4"), Some(PkgFmt::Tlz4));
+        assert_eq!(guess("https://a.com/tool.lz4"), None);
+        assert_eq!(guess("https://a.com/tool.tar.br"), Some(PkgFmt::Tbr));
+        assert_eq!(guess("https://a.com/tool.tbr"), Some(PkgFmt::Tbr));
+        assert_eq!(guess("https://a.com/tool.br"), None);
+        assert_eq!(guess("https://a.com/tool.tar.lz"), Some(PkgFmt::Tlz));
+        assert_eq!(guess("https://a.com/tool.tlz"), Some(PkgFmt::Tlz));
+        assert_eq!(guess("https://a.com/tool.lz"), None);
+        assert_eq!(guess("hello.gz"), Some(PkgFmt::Gz));
+        assert_eq!(guess("hello.tar.gz"), Some(PkgFmt::Tgz));
+        assert_eq!(guess("tar.gz"), None);
+        assert_eq!(guess("https://a.com/tool_amd64.deb"), Some(PkgFmt::Deb));
+        assert_eq!(
+            guess("https://a.com/tool-1.0-1.x86_64.rpm"),
+            Some(PkgFmt::Rpm)
+        );
+        assert_eq!(guess("https://a.com/tool-x86_64.cpio"), Some(PkgFmt::Cpio));
+        assert_eq!(
+            guess("https://a.com/tool-x86_64.AppImage"),
+            Some(PkgFmt::AppImage)
+        );
+        assert_eq!(guess("https://a.com/tool-macos.dmg"), Some(PkgFmt::Dmg));
+        assert_eq!(guess("https://a.com/tool-x64.msi"), Some(PkgFmt::Msi));
+        assert_eq!(
+            guess("https://a.com/tool-x86_64.7z"),
+            Some(PkgFmt::SevenZip)
+        );
+    }
+
+    #[test]
+    fn test_pkg_fmt_probed_by_default() {
+        let probed = |target| {
+            PkgFmt::iter()
+                .filter(|pkg_fmt| pkg_fmt.is_probed_by_default(target))
+                .collect::<Vec<_>>()
+        };
+
+        let linux = probed("x86_64-unknown-linux-gnu");
+        assert!(linux.contains(&PkgFmt::AppImage));
+        assert!(linux.contains(&PkgFmt::Deb));
+        assert!(!linux.contains(&PkgFmt::AppImageExtract));
+        assert!(!linux.contains(&PkgFmt::Msi));
+
+        let windows = probed("x86_64-pc-windows-msvc");
+        assert!(windows.contains(&PkgFmt::Zip));
+        assert!(windows.contains(&PkgFmt::Msi));
+        assert!(!windows.contains(&PkgFmt::Installer));
+        assert!(!windows.contains(&PkgFmt::AppImage));
+        assert!(!windows.contains(&PkgFmt::Dmg));
+
+        let macos = probed("aarch64-apple-darwin");
+        assert!(macos.contains(&PkgFmt::Dmg));
+        assert!(!macos.contains(&PkgFmt::Rpm));
+    }
+
+    #[test]
+    fn test_pkg_fmt_sniff() {
+        assert_eq!(PkgFmt::sniff(b"\x1f\x8b\x08\x00"), Some(PkgFmt::Tgz));
+        assert_eq!(PkgFmt::sniff(b"PK\x03\x04\x14\x00"), Some(PkgFmt::Zip));
+        assert_eq!(
+            PkgFmt::sniff(b"7z\xbc\xaf\x27\x1c\x00\x04"),
+            Some(PkgFmt::SevenZip)
+        );
+        assert_eq!(PkgFmt::sniff(b"!<arch>\ndebian-binary"), Some(PkgFmt::Deb));
+        assert_eq!(
+            PkgFmt::sniff(b"\xed\xab\xee\xdb\x03\x00"),
+            Some(PkgFmt::Rpm)
+        );
+        assert_eq!(PkgFmt::sniff(b"07070100000001"), Some(PkgFmt::Cpio));
+        assert_eq!(
+            PkgFmt::sniff(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1\x00"),
+            Some(PkgFmt::Msi)
+        );
+        assert_eq!(PkgFmt::sniff(b"\x7fELF\x02\x01"), None);
+
+        let mut tar = vec![0; 512];
+        tar[257..263].copy_from_slice(b"ustar\x00");
+        assert_eq!(PkgFmt::sniff(&tar), Some(PkgFmt::Tar));
+        assert_eq!(PkgFmt::sniff(&tar[..100]), None);
+    }
 }
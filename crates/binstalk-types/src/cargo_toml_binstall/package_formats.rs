@@ -18,10 +18,55 @@ pub enum PkgFmt {
     Txz,
     /// Download format is TAR + Zstd
     Tzstd,
+    /// Download format is TAR + LZ4
+    Tlz4,
+    /// Download format is TAR + Brotli
+    Tbr,
+    /// Download format is TAR + Lzip
+    Tlz,
     /// Download format is Zip
     Zip,
+    /// Download format is 7z
+    #[serde(rename = "7z")]
+    #[strum(serialize = "7z")]
+    SevenZip,
     /// Download format is raw / binary
     Bin,
+    /// Download format is an NSIS or Inno Setup installer, unpacked with `7z`
+    /// and `innoextract` respectively to extract the executable inside
+    Installer,
+    /// Download format is a single gzip-compressed binary, without tar
+    Gz,
+    /// Download format is a single xz-compressed binary, without tar
+    Xz,
+    /// Download format is a single zstd-compressed binary, without tar
+    Zst,
+    /// Download format is a Debian package, only binaries installed to
+    /// `usr/bin` and `usr/local/bin` are extracted
+    Deb,
+    /// Download format is a rpm package, only binaries installed to
+    /// `usr/bin` and `usr/local/bin` are extracted
+    Rpm,
+    /// Download format is an uncompressed cpio archive, in the "new ascii",
+    /// "new crc" or portable ascii format
+    Cpio,
+    /// Download format is an AppImage, installed as-is
+    #[serde(rename = "appimage")]
+    #[strum(serialize = "appimage")]
+    AppImage,
+    /// Download format is an AppImage, the runtime of which is stripped
+    /// and the application inside is extracted
+    #[serde(rename = "appimage-extract")]
+    #[strum(serialize = "appimage-extract")]
+    AppImageExtract,
+    /// Download format is a macOS disk image, which is mounted with
+    /// `hdiutil` to copy its content, thus only supported on macOS with the
+    /// `dmg` feature enabled. Images requiring to accept a license agreement
+    /// are rejected.
+    Dmg,
+    /// Download format is a Windows installer package, only executables are
+    /// extracted from the cabinets embedded in it
+    Msi,
 }
 
 impl Default for PkgFmt {
@@ -39,8 +84,23 @@ impl PkgFmt {
             PkgFmt::Tgz => PkgFmtDecomposed::Tar(TarBasedFmt::Tgz),
             PkgFmt::Txz => PkgFmtDecomposed::Tar(TarBasedFmt::Txz),
             PkgFmt::Tzstd => PkgFmtDecomposed::Tar(TarBasedFmt::Tzstd),
+            PkgFmt::Tlz4 => PkgFmtDecomposed::Tar(TarBasedFmt::Tlz4),
+            PkgFmt::Tbr => PkgFmtDecomposed::Tar(TarBasedFmt::Tbr),
+            PkgFmt::Tlz => PkgFmtDecomposed::Tar(TarBasedFmt::Tlz),
             PkgFmt::Bin => PkgFmtDecomposed::Bin,
+            PkgFmt::Installer => PkgFmtDecomposed::Installer,
+            PkgFmt::Gz => PkgFmtDecomposed::Gz,
+            PkgFmt::Xz => PkgFmtDecomposed::Xz,
+            PkgFmt::Zst => PkgFmtDecomposed::Zst,
             PkgFmt::Zip => PkgFmtDecomposed::Zip,
+            PkgFmt::SevenZip => PkgFmtDecomposed::SevenZip,
+            PkgFmt::Deb => PkgFmtDecomposed::Deb,
+            PkgFmt::Rpm => PkgFmtDecomposed::Rpm,
+            PkgFmt::Cpio => PkgFmtDecomposed::Cpio,
+            PkgFmt::AppImage => PkgFmtDecomposed::Bin,
+            PkgFmt::AppImageExtract => PkgFmtDecomposed::AppImage,
+            PkgFmt::Dmg => PkgFmtDecomposed::Dmg,
+            PkgFmt::Msi => PkgFmtDecomposed::Msi,
         }
     }
 
@@ -56,6 +116,9 @@ impl PkgFmt {
             PkgFmt::Tgz => &[".tgz", ".tar.gz"],
             PkgFmt::Txz => &[".txz", ".tar.xz"],
             PkgFmt::Tzstd => &[".tzstd", ".tzst", ".tar.zst"],
+            PkgFmt::Tlz4 => &[".tlz4", ".tar.lz4"],
+            PkgFmt::Tbr => &[".tbr", ".tar.br"],
+            PkgFmt::Tlz => &[".tlz", ".tar.lz"],
             PkgFmt::Bin => {
                 if is_windows {
                     &[".bin", "", ".exe"]
@@ -63,31 +126,82 @@ impl PkgFmt {
                     &[".bin", ""]
                 }
             }
+            PkgFmt::Installer => &[".exe"],
             PkgFmt::Zip => &[".zip"],
+            PkgFmt::SevenZip => &[".7z"],
+            PkgFmt::Gz => &[".gz"],
+            PkgFmt::Xz => &[".xz"],
+            PkgFmt::Zst => &[".zst"],
+            PkgFmt::Deb => &[".deb"],
+            PkgFmt::Rpm => &[".rpm"],
+            PkgFmt::Cpio => &[".cpio"],
+            PkgFmt::AppImage | PkgFmt::AppImageExtract => &[".AppImage", ".appimage"],
+            PkgFmt::Dmg => &[".dmg"],
+            PkgFmt::Msi => &[".msi"],
+        }
+    }
+
+    /// Return true if the format is probed for `target` when the package
+    /// does not specify one.
+    ///
+    /// Installers and extracted AppImages are only used if asked to, and
+    /// packages of an OS are only probed for targets of that OS.
+    pub fn is_probed_by_default(self, target: &str) -> bool {
+        match self {
+            PkgFmt::Installer | PkgFmt::AppImageExtract => false,
+            PkgFmt::Deb | PkgFmt::Rpm | PkgFmt::Cpio | PkgFmt::AppImage => target.contains("linux"),
+            PkgFmt::Dmg => target.contains("apple-darwin"),
+            PkgFmt::Msi => target.contains("windows"),
+            _ => true,
         }
     }
 
     /// Given the pkg-url template, guess the possible pkg-fmt.
     pub fn guess_pkg_format(pkg_url: &str) -> Option<Self> {
-        let mut it = pkg_url.rsplitn(3, '.');
+        let mut it = pkg_url.rsplitn(3, '.').peekable();
+
+        let ext = it.next()?;
+        // Only consume the `tar` of compressed tarballs, so that the rest of
+        // e.g. `hello.gz` is still checked below.
+        let is_tarball = matches!(ext, "bz2" | "gz" | "xz" | "zst" | "lz4" | "br" | "lz")
+            && it.next_if_eq(&"tar").is_some();
 
-        let guess = match it.next()? {
+        let guess = match ext {
             "tar" => Some(PkgFmt::Tar),
 
             "tbz2" => Some(PkgFmt::Tbz2),
-            "bz2" if it.next() == Some("tar") => Some(PkgFmt::Tbz2),
+            "bz2" if is_tarball => Some(PkgFmt::Tbz2),
 
             "tgz" => Some(PkgFmt::Tgz),
-            "gz" if it.next() == Some("tar") => Some(PkgFmt::Tgz),
+            "gz" if is_tarball => Some(PkgFmt::Tgz),
+            "gz" => Some(PkgFmt::Gz),
 
             "txz" => Some(PkgFmt::Txz),
-            "xz" if it.next() == Some("tar") => Some(PkgFmt::Txz),
+            "xz" if is_tarball => Some(PkgFmt::Txz),
+            "xz" => Some(PkgFmt::Xz),
 
             "tzstd" | "tzst" => Some(PkgFmt::Tzstd),
-            "zst" if it.next() == Some("tar") => Some(PkgFmt::Tzstd),
+            "zst" if is_tarball => Some(PkgFmt::Tzstd),
+            "zst" => Some(PkgFmt::Zst),
+
+            "tlz4" => Some(PkgFmt::Tlz4),
+            "lz4" if is_tarball => Some(PkgFmt::Tlz4),
+
+            "tbr" => Some(PkgFmt::Tbr),
+            "br" if is_tarball => Some(PkgFmt::Tbr),
+
+            "tlz" => Some(PkgFmt::Tlz),
+            "lz" if is_tarball => Some(PkgFmt::Tlz),
 
             "exe" | "bin" => Some(PkgFmt::Bin),
             "zip" => Some(PkgFmt::Zip),
+            "7z" => Some(PkgFmt::SevenZip),
+            "deb" => Some(PkgFmt::Deb),
+            "rpm" => Some(PkgFmt::Rpm),
+            "cpio" => Some(PkgFmt::Cpio),
+            "AppImage" | "appimage" => Some(PkgFmt::AppImage),
+            "dmg" => Some(PkgFmt::Dmg),
+            "msi" => Some(PkgFmt::Msi),
 
             _ => None,
         };
@@ -98,13 +212,62 @@ impl PkgFmt {
             None
         }
     }
+
+    /// Detect the archive format of `data`, the start of a package, from its
+    /// magic bytes.
+    ///
+    /// Return `None` if it is not an archive or `data` is too short to tell.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        const MAGIC_BYTES: &[(&[u8], PkgFmt)] = &[
+            (b"\x1f\x8b", PkgFmt::Tgz),
+            (b"BZh", PkgFmt::Tbz2),
+            (b"\xfd7zXZ\x00", PkgFmt::Txz),
+            (b"\x28\xb5\x2f\xfd", PkgFmt::Tzstd),
+            (b"\x04\x22\x4d\x18", PkgFmt::Tlz4),
+            (b"LZIP", PkgFmt::Tlz),
+            (b"PK\x03\x04", PkgFmt::Zip),
+            (b"7z\xbc\xaf\x27\x1c", PkgFmt::SevenZip),
+            (b"!<arch>\n", PkgFmt::Deb),
+            (b"\xed\xab\xee\xdb", PkgFmt::Rpm),
+            (b"070701", PkgFmt::Cpio),
+            (b"070702", PkgFmt::Cpio),
+            (b"070707", PkgFmt::Cpio),
+            // Compound file, the container of Windows installer packages.
+            (b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", PkgFmt::Msi),
+        ];
+        // Offset of the magic `ustar` in the header of a tar archive.
+        const TAR_MAGIC_OFFSET: usize = 257;
+
+        // Checked first since a tar archive starts with the name of its first
+        // entry, which may start with any magic bytes.
+        if data.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5) == Some(b"ustar") {
+            return Some(PkgFmt::Tar);
+        }
+
+        MAGIC_BYTES
+            .iter()
+            .find(|(magic, _)| data.starts_with(magic))
+            .map(|(_, fmt)| *fmt)
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum PkgFmtDecomposed {
     Tar(TarBasedFmt),
     Bin,
+    Installer,
+    Gz,
+    Xz,
+    Zst,
     Zip,
+    SevenZip,
+    Deb,
+    Rpm,
+    Cpio,
+    /// The squashfs filesystem of an AppImage
+    AppImage,
+    Dmg,
+    Msi,
 }
 
 #[derive(Debug, Display, Copy, Clone, Eq, PartialEq)]
@@ -119,6 +282,12 @@ pub enum TarBasedFmt {
     Txz,
     /// Download format is TAR + Zstd
     Tzstd,
+    /// Download format is TAR + LZ4
+    Tlz4,
+    /// Download format is TAR + Brotli
+    Tbr,
+    /// Download format is TAR + Lzip
+    Tlz,
 }
 
 impl From<TarBasedFmt> for PkgFmt {
@@ -129,6 +298,9 @@ impl From<TarBasedFmt> for PkgFmt {
             TarBasedFmt::Tgz => PkgFmt::Tgz,
             TarBasedFmt::Txz => PkgFmt::Txz,
             TarBasedFmt::Tzstd => PkgFmt::Tzstd,
+            TarBasedFmt::Tlz4 => PkgFmt::Tlz4,
+            TarBasedFmt::Tbr => PkgFmt::Tbr,
+            TarBasedFmt::Tlz => PkgFmt::Tlz,
         }
     }
 }
@@ -0,0 +1,83 @@
+//! Channel manifest, listing the versions of crates approved for
+//! installation, e.g. by the platform team of an organisation.
+//!
+//! ```toml
+//! [crates.ripgrep]
+//! version = "14.1.0"
+//!
+//! [crates.ripgrep.sha256]
+//! x86_64-unknown-linux-musl = "4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e"
+//!
+//! [crates.ripgrep.blake3]
+//! aarch64-apple-darwin = "..."
+//! ```
+
+use std::collections::BTreeMap;
+
+use compact_str::CompactString;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::crate_info::ChecksumAlgorithm;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Channel {
+    /// Crates approved for installation, by name.
+    #[serde(default)]
+    pub crates: BTreeMap<CompactString, ChannelCrate>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ChannelCrate {
+    /// The only version of the crate allowed to be installed.
+    pub version: Version,
+    /// Lowercase hex-encoded sha256 digests of the approved artifacts, by
+    /// target.
+    ///
+    /// If any digest is listed, only the pre-built artifacts listed here,
+    /// in [`ChannelCrate::sha512`] or in [`ChannelCrate::blake3`] can be
+    /// installed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sha256: BTreeMap<CompactString, CompactString>,
+    /// Same as [`ChannelCrate::sha256`], with sha512 digests.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sha512: BTreeMap<CompactString, CompactString>,
+    /// Same as [`ChannelCrate::sha256`], with blake3 digests.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blake3: BTreeMap<CompactString, CompactString>,
+}
+
+impl ChannelCrate {
+    fn digests(&self) -> [(ChecksumAlgorithm, &BTreeMap<CompactString, CompactString>); 3] {
+        [
+            (ChecksumAlgorithm::Sha256, &self.sha256),
+            (ChecksumAlgorithm::Sha512, &self.sha512),
+            (ChecksumAlgorithm::Blake3, &self.blake3),
+        ]
+    }
+
+    /// Return true if only the artifacts whose digest is listed can be
+    /// installed.
+    pub fn lists_digests(&self) -> bool {
+        self.digests()
+            .iter()
+            .any(|(_, digests)| !digests.is_empty())
+    }
+
+    /// Algorithms of the digests listed, for any target.
+    pub fn digest_algorithms(&self) -> Vec<ChecksumAlgorithm> {
+        self.digests()
+            .into_iter()
+            .filter(|(_, digests)| !digests.is_empty())
+            .map(|(algorithm, _)| algorithm)
+            .collect()
+    }
+
+    /// Return the digest of the artifact approved for `target`, preferring
+    /// sha256 if it is listed with several algorithms.
+    pub fn digest(&self, target: &str) -> Option<(ChecksumAlgorithm, &CompactString)> {
+        self.digests()
+            .into_iter()
+            .find_map(|(algorithm, digests)| Some((algorithm, digests.get(target)?)))
+    }
+}
@@ -1,6 +1,6 @@
 //! Common structure for crate information for post-install manifests.
 
-use std::{borrow, cmp, hash};
+use std::{borrow, cmp, collections::BTreeMap, fmt, hash};
 
 use compact_str::CompactString;
 use maybe_owned::MaybeOwned;
@@ -24,6 +24,160 @@ pub struct CrateInfo {
     pub source: CrateSource,
     pub target: CompactString,
     pub bins: Vec<CompactString>,
+    /// The exact pre-built artifact installed, `None` if installed from
+    /// source or by an older version of binstall.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ArtifactInfo>,
+    /// Who published the version to crates.io, `None` if installed from
+    /// another source or the registry does not say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<PublishProvenance>,
+    /// Target preferred on the host, if no artifact was found for it and
+    /// the one installed is for the fallback `target` instead, e.g. musl on
+    /// a glibc host, so that a native build can be preferred once available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_target: Option<CompactString>,
+}
+
+/// Record of an artifact downloaded, used as a trustworthy baseline for
+/// auditing and verifying the installation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactInfo {
+    /// Url the artifact is downloaded from.
+    pub url: Url,
+    /// Lowercase hex-encoded SHA256 digest of the artifact.
+    pub sha256: CompactString,
+    /// Lowercase hex-encoded digests of the artifact with other algorithms,
+    /// e.g. the one of the checksum published along with it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digests: BTreeMap<ChecksumAlgorithm, CompactString>,
+    /// Index of the entry of the artifact in the Rekor transparency log,
+    /// if its inclusion is verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rekor_log_index: Option<u64>,
+    /// Digest of the release asset published by GitHub, e.g.
+    /// `sha256:<hex>`, if the artifact is verified against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_digest: Option<CompactString>,
+}
+
+impl ArtifactInfo {
+    /// Return the lowercase hex-encoded `algorithm` digest of the artifact,
+    /// if it is known.
+    pub fn digest(&self, algorithm: ChecksumAlgorithm) -> Option<&CompactString> {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Some(&self.sha256),
+            _ => self.digests.get(&algorithm),
+        }
+    }
+}
+
+/// Algorithm of a digest of an artifact.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    pub const fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// BLAKE3 is not approved by FIPS 140-3.
+    pub const fn is_fips_approved(self) -> bool {
+        !matches!(self, ChecksumAlgorithm::Blake3)
+    }
+
+    /// Length of the hex-encoded digest.
+    pub const fn hex_len(self) -> usize {
+        match self {
+            ChecksumAlgorithm::Sha256 | ChecksumAlgorithm::Blake3 => 64,
+            ChecksumAlgorithm::Sha512 => 128,
+        }
+    }
+}
+
+/// Publisher of a version on crates.io, another trust signal on top of the
+/// artifact itself.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PublishProvenance {
+    /// Login of the user who published the version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_by: Option<CompactString>,
+    /// The CI workflow run which published the version via trusted
+    /// publishing, instead of an API token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trusted_publisher: Option<TrustedPublisher>,
+}
+
+/// Workflow run publishing a version via trusted publishing.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TrustedPublisher {
+    /// CI provider, e.g. `github`.
+    pub provider: CompactString,
+    /// Repository the workflow runs in, e.g. `owner/repo`.
+    pub repository: CompactString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<CompactString>,
+    /// Commit the workflow runs on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha: Option<CompactString>,
+}
+
+/// Formats as e.g. `trusted publishing from github repository owner/repo
+/// at commit abc123 (run 42)` or `user alice`.
+impl fmt::Display for PublishProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.trusted_publisher, &self.published_by) {
+            (Some(publisher), _) => {
+                write!(
+                    f,
+                    "trusted publishing from {} repository {}",
+                    publisher.provider, publisher.repository
+                )?;
+                if let Some(sha) = &publisher.sha {
+                    write!(f, " at commit {sha}")?;
+                }
+                if let Some(run_id) = &publisher.run_id {
+                    write!(f, " (run {run_id})")?;
+                }
+                Ok(())
+            }
+            (None, Some(login)) => write!(f, "user {login}"),
+            (None, None) => f.write_str("unknown publisher"),
+        }
+    }
+}
+
+/// Tool that installed a crate.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InstalledBy {
+    CargoBinstall,
+    /// `cargo-install`, or any other tool that only updates cargo's manifests.
+    CargoInstall,
+}
+
+impl fmt::Display for InstalledBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CargoBinstall => "cargo-binstall",
+            Self::CargoInstall => "cargo-install",
+        })
+    }
+}
+
+/// Version of a crate that is already installed.
+#[derive(Clone, Debug)]
+pub struct InstalledCrate {
+    pub version: Version,
+    pub installed_by: InstalledBy,
 }
 
 impl borrow::Borrow<str> for CrateInfo {
@@ -81,4 +235,81 @@ impl CrateSource {
             url: MaybeOwned::Borrowed(cratesio_url()),
         }
     }
+
+    pub fn is_cratesio_registry(&self) -> bool {
+        matches!(self.source_type, SourceType::Registry) && *self.url == *cratesio_url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_info_de() {
+        let json = r#"{
+            "name": "cargo-binstall",
+            "version_req": "*",
+            "current_version": "1.0.0",
+            "source": {
+                "source_type": "Registry",
+                "url": "https://github.com/rust-lang/crates.io-index"
+            },
+            "target": "x86_64-unknown-linux-gnu",
+            "bins": ["cargo-binstall"]
+        }"#;
+
+        // Manifests written by older versions do not have artifact
+        let mut crate_info: CrateInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(crate_info.artifact, None);
+
+        crate_info.artifact = Some(ArtifactInfo {
+            url: Url::parse("https://example.com/cargo-binstall.tgz").unwrap(),
+            sha256: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".into(),
+            digests: [(
+                ChecksumAlgorithm::Blake3,
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262".into(),
+            )]
+            .into(),
+            rekor_log_index: Some(42),
+            release_digest: Some(
+                "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".into(),
+            ),
+        });
+
+        let serialized = serde_json::to_string(&crate_info).unwrap();
+        assert!(serialized.contains(r#""digests":{"blake3":"af13"#));
+        let deserialized: CrateInfo = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.artifact, crate_info.artifact);
+    }
+
+    #[test]
+    fn test_publish_provenance() {
+        let provenance: PublishProvenance = serde_json::from_str(
+            r#"{
+                "trusted_publisher": {
+                    "provider": "github",
+                    "repository": "cargo-bins/cargo-binstall",
+                    "run_id": "42",
+                    "sha": "0123abc"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(provenance.published_by, None);
+        assert_eq!(
+            provenance.to_string(),
+            "trusted publishing from github repository cargo-bins/cargo-binstall at commit 0123abc (run 42)"
+        );
+
+        let provenance = PublishProvenance {
+            published_by: Some("alice".into()),
+            trusted_publisher: None,
+        };
+        assert_eq!(provenance.to_string(), "user alice");
+        assert_eq!(
+            serde_json::to_string(&provenance).unwrap(),
+            r#"{"published_by":"alice"}"#
+        );
+    }
 }
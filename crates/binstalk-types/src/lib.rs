@@ -1,4 +1,5 @@
 pub mod cargo_toml_binstall;
+pub mod channel;
 pub mod crate_info;
 
 pub use maybe_owned;
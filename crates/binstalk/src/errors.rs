@@ -2,6 +2,7 @@ use std::{
     fmt, io, ops,
     path::PathBuf,
     process::{ExitCode, ExitStatus, Termination},
+    time::Duration,
 };
 
 use binstalk_downloader::{download::DownloadError, remote::Error as RemoteError};
@@ -9,6 +10,7 @@ use binstalk_fetchers::FetchError;
 use compact_str::CompactString;
 use itertools::Itertools;
 use miette::{Diagnostic, Report};
+use semver::Version;
 use target_lexicon::ParseError as TargetTripleParseError;
 use thiserror::Error;
 use tokio::task;
@@ -20,6 +22,7 @@ use crate::{
         cargo_toml::Error as CargoTomlError,
         cargo_toml_workspace::Error as LoadManifestFromWSError, gh_api_client::GhApiError,
     },
+    manifests::crate_info::InstalledBy,
     registry::{InvalidRegistryError, RegistryError},
 };
 
@@ -31,6 +34,14 @@ pub struct VersionParseError {
     pub err: semver::Error,
 }
 
+#[derive(Debug, Error)]
+#[error("refusing to downgrade from v{installed} (installed by {installed_by}) to v{new}")]
+pub struct DowngradeError {
+    pub installed: semver::Version,
+    pub installed_by: InstalledBy,
+    pub new: semver::Version,
+}
+
 #[derive(Debug, Diagnostic, Error)]
 #[error("For crate {crate_name}: {err}")]
 pub struct CrateContextError {
@@ -40,6 +51,12 @@ pub struct CrateContextError {
     err: BinstallError,
 }
 
+impl CrateContextError {
+    pub fn crate_name(&self) -> &str {
+        &self.crate_name
+    }
+}
+
 #[derive(Debug)]
 pub struct CrateErrors(Box<[Box<CrateContextError>]>);
 
@@ -299,6 +316,14 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::version::parse))]
     VersionParse(#[from] Box<VersionParseError>),
 
+    /// A tool required by the crate in the tools manifest failed to install.
+    ///
+    /// - Code: `binstall::required_tool_failed`
+    /// - Exit: 81
+    #[error("required tool {0} failed to install")]
+    #[diagnostic(severity(error), code(binstall::required_tool_failed))]
+    RequiredToolFailed(CompactString),
+
     /// The crate@version syntax was used at the same time as the --version option.
     ///
     /// You can't do that as it's ambiguous which should apply.
@@ -313,6 +338,18 @@ pub enum BinstallError {
     )]
     SuperfluousVersionOption,
 
+    /// The version resolved is older than the version already installed.
+    ///
+    /// - Code: `binstall::version::downgrade`
+    /// - Exit: 85
+    #[error(transparent)]
+    #[diagnostic(
+        severity(error),
+        code(binstall::version::downgrade),
+        help("Use --allow-downgrade or --force to install it anyway.")
+    )]
+    Downgrade(#[from] Box<DowngradeError>),
+
     /// No binaries were found for the crate.
     ///
     /// When installing, either the binaries are specified in the crate's Cargo.toml, or they're
@@ -375,6 +412,44 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::SourceFilePath))]
     DuplicateSourceFilePath { path: PathBuf },
 
+    /// Fallback to `cargo-install` is refused since building the crate is
+    /// estimated to be too expensive, see `--max-compile-deps`.
+    ///
+    /// - Code: `binstall::compile_too_expensive`
+    /// - Exit: 91
+    #[error("building from source with {dependencies} direct dependencies exceeds the limit of {max_compile_deps}")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::compile_too_expensive),
+        help("Raise --max-compile-deps or remove --no-heavy-compile to build it anyway.")
+    )]
+    CompileTooExpensive {
+        dependencies: usize,
+        max_compile_deps: usize,
+    },
+
+    /// The crate or its artifact is not approved by the channel specified
+    /// with `--channel`.
+    ///
+    /// - Code: `binstall::not_approved_by_channel`
+    /// - Exit: 92
+    #[error("{0} is not approved by the channel")]
+    #[diagnostic(severity(error), code(binstall::not_approved_by_channel))]
+    NotApprovedByChannel(CompactString),
+
+    /// The version requested does not match the one approved by the channel
+    /// specified with `--channel`.
+    ///
+    /// - Code: `binstall::channel_version_conflict`
+    /// - Exit: 93
+    #[error("version {req} of {crate_name} is requested, but only {approved} is approved by the channel")]
+    #[diagnostic(severity(error), code(binstall::channel_version_conflict))]
+    ChannelVersionConflict {
+        crate_name: CompactString,
+        req: CompactString,
+        approved: Version,
+    },
+
     /// Fallback to `cargo-install` is disabled.
     ///
     /// - Code: `binstall::no_fallback_to_cargo_install`
@@ -424,6 +499,18 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::load_manifest_from_workspace))]
     LoadManifestFromWSError(#[from] Box<LoadManifestFromWSError>),
 
+    /// The resolution of a crate took longer than `--timeout`.
+    ///
+    /// - Code: `binstall::timeout`
+    /// - Exit: 100
+    #[error("timed out after {}s", .0.as_secs())]
+    #[diagnostic(
+        severity(error),
+        code(binstall::timeout),
+        help("Use a larger --timeout if the host is only slow.")
+    )]
+    Timeout(Duration),
+
     /// A wrapped error providing the context of which crate the error is about.
     #[error(transparent)]
     #[diagnostic(transparent)]
@@ -455,12 +542,17 @@ impl BinstallError {
             CargoManifest { .. } => 78,
             RegistryParseError(..) => 79,
             VersionParse { .. } => 80,
+            RequiredToolFailed(_) => 81,
             SuperfluousVersionOption => 84,
+            Downgrade(_) => 85,
             UnspecifiedBinaries => 86,
             NoViableTargets => 87,
             BinFile(_) => 88,
             CargoTomlMissingPackage(_) => 89,
             DuplicateSourceFilePath { .. } => 90,
+            CompileTooExpensive { .. } => 91,
+            NotApprovedByChannel(_) => 92,
+            ChannelVersionConflict { .. } => 93,
             NoFallbackToCargoInstall => 94,
             InvalidPkgFmt(..) => 95,
             GhApiErr(..) => 96,
@@ -468,6 +560,7 @@ impl BinstallError {
             #[cfg(feature = "git")]
             GitError(_) => 98,
             LoadManifestFromWSError(_) => 99,
+            Timeout(_) => 100,
             CrateContext(context) => context.err.exit_number(),
             Errors(errors) => (errors.0)[0].err.exit_number(),
         };
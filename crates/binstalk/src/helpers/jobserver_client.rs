@@ -5,7 +5,7 @@ use tokio::sync::OnceCell;
 
 use crate::errors::BinstallError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LazyJobserverClient(OnceCell<Client>);
 
 impl LazyJobserverClient {
@@ -1,6 +1,7 @@
 use std::{future::Future, sync::Mutex};
 
 use binstalk_git_repo_api::gh_api_client::GhApiClient;
+use compact_str::CompactString;
 use tokio::sync::OnceCell;
 use zeroize::Zeroizing;
 
@@ -11,11 +12,16 @@ use crate::{
 
 pub type GitHubToken = Option<Zeroizing<Box<str>>>;
 
+/// Hosts of GitHub Enterprise Servers, each with its own token.
+pub type GitHubEnterpriseHosts = Vec<(CompactString, GitHubToken)>;
+
 #[derive(Debug)]
 pub struct LazyGhApiClient {
     client: remote::Client,
     inner: OnceCell<GhApiClient>,
     task: Mutex<Option<AutoAbortJoinHandle<GitHubToken>>>,
+    enterprise_hosts_task: Mutex<Option<AutoAbortJoinHandle<GitHubEnterpriseHosts>>>,
+    enterprise_hosts_added: OnceCell<()>,
 }
 
 impl LazyGhApiClient {
@@ -24,6 +30,8 @@ impl LazyGhApiClient {
             inner: OnceCell::new_with(Some(GhApiClient::new(client.clone(), auth_token))),
             client,
             task: Mutex::new(None),
+            enterprise_hosts_task: Mutex::new(None),
+            enterprise_hosts_added: OnceCell::new(),
         }
     }
 
@@ -35,19 +43,47 @@ impl LazyGhApiClient {
             inner: OnceCell::new(),
             task: Mutex::new(Some(AutoAbortJoinHandle::spawn(get_auth_token_future))),
             client,
+            enterprise_hosts_task: Mutex::new(None),
+            enterprise_hosts_added: OnceCell::new(),
         }
     }
 
+    /// Also use the API of the GitHub Enterprise Servers returned by
+    /// `get_enterprise_hosts_future`.
+    pub fn with_enterprise_hosts_future<Fut>(self, get_enterprise_hosts_future: Fut) -> Self
+    where
+        Fut: Future<Output = GitHubEnterpriseHosts> + Send + Sync + 'static,
+    {
+        *self.enterprise_hosts_task.lock().unwrap() =
+            Some(AutoAbortJoinHandle::spawn(get_enterprise_hosts_future));
+        self
+    }
+
     pub async fn get(&self) -> Result<&GhApiClient, BinstallError> {
-        self.inner
+        let gh_api_client = self
+            .inner
             .get_or_try_init(|| async {
                 let task = self.task.lock().unwrap().take();
-                Ok(if let Some(task) = task {
+                Ok::<_, BinstallError>(if let Some(task) = task {
                     GhApiClient::new(self.client.clone(), task.await?)
                 } else {
                     GhApiClient::new(self.client.clone(), None)
                 })
             })
-            .await
+            .await?;
+
+        self.enterprise_hosts_added
+            .get_or_try_init(|| async {
+                let task = self.enterprise_hosts_task.lock().unwrap().take();
+                if let Some(task) = task {
+                    for (host, auth_token) in task.await? {
+                        gh_api_client.add_enterprise_host(&host, auth_token)?;
+                    }
+                }
+                Ok::<_, BinstallError>(())
+            })
+            .await?;
+
+        Ok(gh_api_client)
     }
 }
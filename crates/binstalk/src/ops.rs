@@ -1,21 +1,38 @@
 //! Concrete Binstall operations.
 
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use compact_str::CompactString;
 use semver::VersionReq;
+use tokio::sync::Semaphore;
 
 use crate::{
-    fetchers::{Data, Fetcher, SignaturePolicy, TargetDataErased},
+    bins,
+    fetchers::{
+        rekor, AllowedSigners, Data, Fetcher, PinnedKeys, SignaturePolicy, TargetDataErased,
+    },
     helpers::{
-        gh_api_client::GhApiClient, jobserver_client::LazyJobserverClient,
-        lazy_gh_api_client::LazyGhApiClient, remote::Client,
+        gh_api_client::GhApiClient,
+        jobserver_client::LazyJobserverClient,
+        lazy_gh_api_client::LazyGhApiClient,
+        remote::{Client, Url},
+    },
+    manifests::{
+        cargo_toml_binstall::{PkgOverride, Strategy},
+        channel::Channel,
     },
-    manifests::cargo_toml_binstall::PkgOverride,
     registry::Registry,
     DesiredTargets,
 };
 
+pub mod check_update;
 pub mod resolve;
+pub mod staging;
 
 pub type Resolver =
     fn(Client, GhApiClient, Arc<Data>, Arc<TargetDataErased>, SignaturePolicy) -> Arc<dyn Fetcher>;
@@ -28,12 +45,34 @@ pub enum CargoTomlFetchOverride {
     Path(PathBuf),
 }
 
+/// Tool used instead of `cargo install` to cross compile in the
+/// `cargo install` fallback.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CrossCompiler {
+    /// `cargo zigbuild install`, using zig as the linker.
+    Zigbuild,
+    /// `cross install`, building in a container.
+    Cross,
+}
+
 #[derive(Debug)]
 pub struct Options {
     pub no_symlinks: bool,
+    /// Replace the target of the destination of a binary if it is a
+    /// symlink, instead of the symlink itself.
+    pub install_through_symlinks: bool,
+    /// How binaries are installed to filesystems not supporting atomic
+    /// rename or permission bits.
+    pub fs_compat: bins::FsCompat,
     pub dry_run: bool,
+    /// Look up who published crates from crates.io, which costs a request to
+    /// its API per crate, to show it at confirmation.
+    pub show_provenance: bool,
     pub force: bool,
     pub quiet: bool,
+    /// Only log crates already up-to-date at the debug level.
+    pub quiet_unchanged: bool,
     pub locked: bool,
     pub no_track: bool,
 
@@ -42,11 +81,40 @@ pub struct Options {
     pub cli_overrides: PkgOverride,
 
     pub desired_targets: DesiredTargets,
+    /// Targets of specific crates, overriding `desired_targets`.
+    pub crate_desired_targets: BTreeMap<CompactString, DesiredTargets>,
     pub resolvers: Vec<Resolver>,
+    /// Strategies which fetched specific crates before, the most recent
+    /// first, tried before the other resolvers for these crates.
+    pub crate_strategy_order: BTreeMap<CompactString, Vec<Strategy>>,
     pub cargo_install_fallback: bool,
+    /// Toolchain to invoke `cargo +<toolchain> install` with.
+    pub compile_toolchain: Option<CompactString>,
+    /// Tool to compile with when the target is not the host.
+    pub cross_compiler: Option<CrossCompiler>,
+    /// `RUSTC_WRAPPER` for the `cargo install` fallback, e.g. `sccache`.
+    pub compile_rustc_wrapper: Option<PathBuf>,
+    /// `--target-dir` for the `cargo install` fallback.
+    pub compile_target_dir: Option<PathBuf>,
+    /// Refuse to fall back to `cargo install` for crates with more direct
+    /// dependencies than this.
+    pub max_compile_deps: Option<usize>,
+    pub allow_target_mismatch: bool,
+    /// Install versions older than the one already installed.
+    pub allow_downgrade: bool,
+    /// Explain why each fetcher was or wasn't attempted.
+    pub explain: bool,
+
+    /// Modification time set on the installed binaries instead of the time
+    /// of installation, for reproducible installs.
+    pub frozen_time: Option<SystemTime>,
 
     pub temp_dir: PathBuf,
     pub install_path: PathBuf,
+    /// Directory to stage the binaries in before installing them, so that
+    /// an interrupted installation can be resumed, if it is tracked in the
+    /// manifests.
+    pub staging_dir: Option<PathBuf>,
     pub cargo_root: Option<PathBuf>,
 
     pub client: Client,
@@ -55,7 +123,37 @@ pub struct Options {
     pub registry: Registry,
 
     pub signature_policy: SignaturePolicy,
+    /// Signing keys discovered for crates without `pubkey` in their metadata.
+    pub pinned_keys: Arc<PinnedKeys>,
+    /// DNS-over-HTTPS resolver to discover signing keys with, instead of
+    /// Cloudflare.
+    pub doh_resolver: Option<Url>,
+    /// Keys allowed to make ssh signatures for crates without `pubkey` in
+    /// their metadata.
+    pub allowed_signers: Arc<AllowedSigners>,
+    /// Require the artifacts to be in the Rekor transparency log, signed by
+    /// one of these keys.
+    pub transparency_log_signers: Option<Vec<rekor::SignerKey>>,
+    /// Only install the versions and artifacts approved by the channel.
+    pub channel: Option<Channel>,
     pub disable_telemetry: bool,
+    /// Download the packages of the crates one at a time, and extract them
+    /// buffering less, to reduce the memory used.
+    pub low_memory: bool,
+    /// Serializes the downloads of the packages with `low_memory`.
+    pub low_memory_download: Semaphore,
 
     pub maximum_resolution_timeout: Duration,
+    /// Maximum time to resolve each crate, including downloading and
+    /// extracting its package.
+    pub crate_timeout: Option<Duration>,
+}
+
+impl Options {
+    /// Return the targets to install `crate_name` for.
+    pub fn desired_targets_of(&self, crate_name: &str) -> &DesiredTargets {
+        self.crate_desired_targets
+            .get(crate_name)
+            .unwrap_or(&self.desired_targets)
+    }
 }
@@ -0,0 +1,105 @@
+//! Check whether a newer version of a crate is published, so that tools
+//! installed by cargo-binstall can show update hints.
+
+use compact_str::{CompactString, ToCompactString};
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use tracing::{debug, instrument};
+
+use crate::{
+    errors::{BinstallError, VersionParseError},
+    ops::{resolve::CrateName, Options},
+};
+
+/// Serializes to e.g.
+/// `{"name":"foo","current_version":"1.0.0","latest_version":"1.1.0","update_available":true}`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct UpdateCheck {
+    pub name: CompactString,
+    pub current_version: Version,
+    /// Latest version matching the version requirement of the crate.
+    pub latest_version: Version,
+    pub update_available: bool,
+}
+
+impl UpdateCheck {
+    pub fn new(name: CompactString, current_version: Version, latest_version: Version) -> Self {
+        Self {
+            update_available: latest_version > current_version,
+            name,
+            current_version,
+            latest_version,
+        }
+    }
+}
+
+/// Look up the latest version of `crate_name` in the registry, without
+/// resolving or downloading its binaries.
+#[instrument(skip(opts))]
+pub async fn check_update(
+    opts: &Options,
+    crate_name: CrateName,
+    current_version: Version,
+) -> Result<UpdateCheck, BinstallError> {
+    let latest_version = latest_version(opts, &crate_name).await?;
+
+    Ok(UpdateCheck::new(
+        crate_name.name,
+        current_version,
+        latest_version,
+    ))
+}
+
+/// Look up the latest version of `crate_name` matching its version
+/// requirement in the registry.
+pub async fn latest_version(
+    opts: &Options,
+    crate_name: &CrateName,
+) -> Result<Version, BinstallError> {
+    let version_req = crate_name.version_req.clone().unwrap_or(VersionReq::STAR);
+
+    let manifest = opts
+        .registry
+        .fetch_crate_matched(opts.client.clone(), &crate_name.name, &version_req)
+        .await?;
+    let package = manifest
+        .package
+        .ok_or_else(|| BinstallError::CargoTomlMissingPackage(crate_name.name.clone()))?;
+
+    let latest_version = package.version().to_compact_string();
+    let latest_version = Version::parse(&latest_version).map_err(|err| {
+        BinstallError::from(Box::new(VersionParseError {
+            v: latest_version,
+            err,
+        }))
+    })?;
+    debug!("Latest version of {} is {latest_version}", crate_name.name);
+
+    Ok(latest_version)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let check = UpdateCheck::new("foo".into(), Version::new(1, 0, 0), Version::new(1, 1, 0));
+
+        assert_eq!(
+            serde_json::to_string(&check).unwrap(),
+            r#"{"name":"foo","current_version":"1.0.0","latest_version":"1.1.0","update_available":true}"#
+        );
+
+        let check = UpdateCheck::new(
+            r#"foo"bar\"#.into(),
+            Version::new(1, 1, 0),
+            Version::new(1, 1, 0),
+        );
+
+        assert_eq!(
+            serde_json::to_string(&check).unwrap(),
+            r#"{"name":"foo\"bar\\","current_version":"1.1.0","latest_version":"1.1.0","update_available":false}"#
+        );
+    }
+}
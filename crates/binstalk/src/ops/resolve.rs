@@ -1,29 +1,37 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    future::Future,
     iter, mem,
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
 };
 
-use binstalk_fetchers::FETCHER_GH_CRATE_META;
+use binstalk_downloader::fips;
+use binstalk_fetchers::{rekor, FetchError, FETCHER_GH_CRATE_META};
 use binstalk_types::{
     cargo_toml_binstall::Strategy,
-    crate_info::{CrateSource, SourceType},
+    channel::ChannelCrate,
+    crate_info::{ChecksumAlgorithm, CrateSource, InstalledCrate, PublishProvenance, SourceType},
 };
-use compact_str::{CompactString, ToCompactString};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use itertools::Itertools;
 use leon::Template;
 use maybe_owned::MaybeOwned;
-use semver::{Version, VersionReq};
-use tokio::{task::spawn_blocking, time::timeout};
+use semver::{Comparator, Op, Version, VersionReq};
+use tokio::{
+    sync::Semaphore,
+    task::spawn_blocking,
+    time::{timeout, timeout_at, Instant},
+};
 use tracing::{debug, error, info, instrument, warn};
 use url::Url;
 
 use crate::{
     bins,
-    errors::{BinstallError, VersionParseError},
+    errors::{BinstallError, DowngradeError, VersionParseError},
     fetchers::{Data, Fetcher, TargetData},
     helpers::{
         cargo_toml::Manifest, cargo_toml_workspace::load_manifest_from_workspace,
@@ -32,39 +40,249 @@ use crate::{
     },
     manifests::cargo_toml_binstall::{Meta, PkgMeta, PkgOverride},
     ops::{CargoTomlFetchOverride, Options},
+    registry::{fetch_crate_provenance, RegistryError},
 };
 
 mod crate_name;
 #[doc(inline)]
 pub use crate_name::CrateName;
 
-mod version_ext;
+mod version;
 #[doc(inline)]
-pub use version_ext::VersionReqExt;
+pub use version::VersionReqExt;
 
 mod resolution;
 #[doc(inline)]
 pub use resolution::{Resolution, ResolutionFetch, ResolutionSource};
 
+mod explain;
+use explain::{Explanation, Outcome};
+
+mod compile_estimate;
+#[doc(inline)]
+pub use compile_estimate::{CompileEstimate, HEAVY_COMPILE_DEPS};
+
+mod pending_plans;
+use pending_plans::pending_plans;
+
 #[instrument(skip_all)]
 pub async fn resolve(
     opts: Arc<Options>,
     crate_name: CrateName,
-    curr_version: Option<Version>,
+    installed: Option<InstalledCrate>,
 ) -> Result<Resolution, BinstallError> {
     let crate_name_name = crate_name.name.clone();
-    let resolution = resolve_inner(opts, crate_name, curr_version)
-        .await
-        .map_err(|err| err.crate_context(crate_name_name))?;
+    let deadline = crate_deadline(&opts);
+    let resolution = with_crate_timeout(
+        opts.clone(),
+        &crate_name_name,
+        deadline,
+        resolve_inner(opts, crate_name, installed),
+    )
+    .await
+    .map_err(|err| err.crate_context(crate_name_name))?;
 
     Ok(resolution)
 }
 
+/// Number of crates from which [`resolve_batch`] should be used instead of
+/// [`resolve`].
+pub const LARGE_BATCH: usize = 100;
+
+/// Maximum number of manifests loaded concurrently by [`resolve_batch`].
+const MAX_CONCURRENT_PLANS: usize = 16;
+
+/// Resolve a large batch of crates in two phases: the manifests of all
+/// crates are loaded first, at most [`MAX_CONCURRENT_PLANS`] at a time, then
+/// the artifacts of each crate are resolved.
+///
+/// This avoids opening hundreds of connections to the registry at once, and
+/// lets the resolution of crates from the same repository share queries to
+/// the GitHub API.
+pub fn resolve_batch(
+    opts: Arc<Options>,
+    crate_names: Vec<(CrateName, Option<InstalledCrate>)>,
+) -> Vec<AutoAbortJoinHandle<Result<Resolution, BinstallError>>> {
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_PLANS));
+    let (pending_plans, all_plans) = pending_plans(crate_names.len());
+
+    crate_names
+        .into_iter()
+        .zip(pending_plans)
+        .map(|((crate_name, installed), pending_plan)| {
+            let opts = opts.clone();
+            let semaphore = semaphore.clone();
+            let all_plans = all_plans.clone();
+
+            AutoAbortJoinHandle::spawn(async move {
+                let crate_name_name = crate_name.name.clone();
+
+                let (plan, deadline) = {
+                    let _permit = semaphore.acquire().await;
+                    let deadline = crate_deadline(&opts);
+                    let plan = with_crate_timeout(
+                        opts.clone(),
+                        &crate_name_name,
+                        deadline,
+                        plan(&opts, crate_name, installed),
+                    )
+                    .await;
+                    (plan, deadline)
+                };
+                // Wait for all manifests to be loaded, even if this one failed.
+                drop(pending_plan);
+                let wait_start = Instant::now();
+                all_plans.wait().await;
+                // Waiting for the other crates does not count towards the
+                // timeout of this one.
+                let deadline = deadline.map(|deadline| deadline + wait_start.elapsed());
+
+                match plan {
+                    Ok(Some(plan)) => {
+                        with_crate_timeout(
+                            opts.clone(),
+                            &crate_name_name,
+                            deadline,
+                            execute(opts, plan),
+                        )
+                        .await
+                    }
+                    Ok(None) => Ok(Resolution::AlreadyUpToDate),
+                    Err(err) => Err(err),
+                }
+                .map_err(|err| err.crate_context(crate_name_name))
+            })
+        })
+        .collect()
+}
+
+/// Return the deadline of the resolution of a crate starting now.
+fn crate_deadline(opts: &Options) -> Option<Instant> {
+    opts.crate_timeout
+        .map(|crate_timeout| Instant::now() + crate_timeout)
+}
+
+/// Stably sort the `items` of each group by `key`, only swapping items of
+/// the same group so that each group keeps the positions it has.
+fn sort_within_groups<T: Clone, G: Ord, K: Ord>(
+    items: &mut [T],
+    group: impl Fn(&T) -> G,
+    key: impl Fn(&T) -> K,
+) {
+    let mut groups: BTreeMap<G, Vec<usize>> = BTreeMap::new();
+    for (i, item) in items.iter().enumerate() {
+        groups.entry(group(item)).or_default().push(i);
+    }
+
+    for indices in groups.into_values() {
+        let mut sorted: Vec<_> = indices.iter().map(|i| items[*i].clone()).collect();
+        sorted.sort_by_key(&key);
+        for (i, item) in indices.into_iter().zip(sorted) {
+            items[i] = item;
+        }
+    }
+}
+
+/// Return the directory the packages of crate `crate_name` are downloaded
+/// and extracted to.
+fn crate_temp_dir(opts: &Options, crate_name: &str) -> PathBuf {
+    opts.temp_dir.join(crate_name)
+}
+
+/// Return the data of the fetchers looking for the package of the crate
+/// `package_info` under `name`, the crate or its only binary.
+fn fetcher_data(
+    opts: &Options,
+    name: CompactString,
+    package_info: &PackageInfo,
+    digest_algorithms: Vec<ChecksumAlgorithm>,
+) -> Data {
+    Data::new(
+        name,
+        package_info.version_str.clone(),
+        package_info.repo.clone(),
+    )
+    .with_pinned_keys(opts.pinned_keys.clone())
+    .with_doh_resolver(opts.doh_resolver.clone())
+    .with_allowed_signers(opts.allowed_signers.clone())
+    .with_digest_algorithms(digest_algorithms)
+    .with_low_memory(opts.low_memory)
+}
+
+/// Run `fut`, which resolves crate `crate_name`, until `deadline`.
+///
+/// On timeout, `fut` is cancelled and the packages it has partially
+/// downloaded and extracted are removed.
+async fn with_crate_timeout<T>(
+    opts: Arc<Options>,
+    crate_name: &str,
+    deadline: Option<Instant>,
+    fut: impl Future<Output = Result<T, BinstallError>>,
+) -> Result<T, BinstallError> {
+    let (Some(deadline), Some(crate_timeout)) = (deadline, opts.crate_timeout) else {
+        return fut.await;
+    };
+
+    match timeout_at(deadline, fut).await {
+        Ok(res) => res,
+        Err(_elapsed) => {
+            warn!("Cancelling the resolution of {crate_name} after {crate_timeout:?}");
+
+            // Extraction runs on the blocking pool and cannot be cancelled,
+            // but it stops as soon as the download is dropped.
+            let crate_temp_dir = crate_temp_dir(&opts, crate_name);
+            let res = spawn_blocking(move || match std::fs::remove_dir_all(crate_temp_dir) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            })
+            .await;
+            if let Ok(Err(err)) = res {
+                warn!("Failed to remove the partial downloads of {crate_name}: {err}");
+            }
+
+            Err(BinstallError::Timeout(crate_timeout))
+        }
+    }
+}
+
 async fn resolve_inner(
     opts: Arc<Options>,
     crate_name: CrateName,
-    curr_version: Option<Version>,
+    installed: Option<InstalledCrate>,
 ) -> Result<Resolution, BinstallError> {
+    match plan(&opts, crate_name, installed).await? {
+        Some(plan) => execute(opts, plan).await,
+        None => Ok(Resolution::AlreadyUpToDate),
+    }
+}
+
+/// Wait for the lookup of the publisher of the crate, which is only a trust
+/// signal shown to the user, so failing to look it up is not an error.
+async fn join_provenance(
+    handle: Option<AutoAbortJoinHandle<Result<Option<PublishProvenance>, RegistryError>>>,
+) -> Option<PublishProvenance> {
+    match handle?.flattened_join().await {
+        Ok(provenance) => provenance,
+        Err(err) => {
+            warn!("Failed to look up the publisher of the crate: {err}");
+            None
+        }
+    }
+}
+
+/// A crate whose manifest is loaded, ready to be resolved by [`execute`].
+struct Plan {
+    package_info: PackageInfo,
+    version_req_str: CompactString,
+}
+
+/// Load the manifest of the crate, return `None` if it is already
+/// up-to-date.
+async fn plan(
+    opts: &Options,
+    crate_name: CrateName,
+    installed: Option<InstalledCrate>,
+) -> Result<Option<Plan>, BinstallError> {
     info!("Resolving package: '{}'", crate_name);
 
     let version_req = match (&crate_name.version_req, &opts.version_req) {
@@ -76,20 +294,73 @@ async fn resolve_inner(
 
     let version_req_str = version_req.to_compact_string();
 
-    let Some(package_info) = PackageInfo::resolve(
-        &opts,
+    // Only the version approved by the channel can be resolved to.
+    let channel_crate = match &opts.channel {
+        Some(channel) => Some(
+            channel
+                .crates
+                .get(&crate_name.name)
+                .ok_or_else(|| BinstallError::NotApprovedByChannel(crate_name.name.clone()))?,
+        ),
+        None => None,
+    };
+    let version_req = match channel_crate {
+        Some(channel_crate) => {
+            let approved = &channel_crate.version;
+            // `*` does not match pre-releases, which may still be approved.
+            if version_req_str != "*" && !version_req.matches(approved) {
+                return Err(BinstallError::ChannelVersionConflict {
+                    crate_name: crate_name.name,
+                    req: version_req_str,
+                    approved: approved.clone(),
+                });
+            }
+            debug!(
+                "Resolving {} to {approved} approved by the channel",
+                crate_name.name
+            );
+
+            MaybeOwned::Owned(VersionReq {
+                comparators: vec![Comparator {
+                    op: Op::Exact,
+                    major: approved.major,
+                    minor: Some(approved.minor),
+                    patch: Some(approved.patch),
+                    pre: approved.pre.clone(),
+                }],
+            })
+        }
+        None => version_req,
+    };
+
+    Ok(PackageInfo::resolve(
+        opts,
         crate_name.name,
-        curr_version,
+        installed,
         &version_req,
         opts.client.clone(),
     )
     .await?
-    else {
-        return Ok(Resolution::AlreadyUpToDate);
-    };
+    .map(|package_info| Plan {
+        package_info,
+        version_req_str,
+    }))
+}
+
+/// Resolve the artifacts of the crate planned, or fall back to building it
+/// from source.
+async fn execute(opts: Arc<Options>, plan: Plan) -> Result<Resolution, BinstallError> {
+    let Plan {
+        package_info,
+        version_req_str,
+    } = plan;
+    let channel_crate = opts
+        .channel
+        .as_ref()
+        .and_then(|channel| channel.crates.get(&package_info.name));
 
     let desired_targets = opts
-        .desired_targets
+        .desired_targets_of(&package_info.name)
         .get()
         .await
         .iter()
@@ -111,6 +382,33 @@ async fn resolve_inner(
         .collect::<Result<Vec<_>, BinstallError>>()?;
     let resolvers = &opts.resolvers;
 
+    // Looked up while the artifacts are resolved, to be shown at confirmation.
+    let provenance =
+        (opts.show_provenance && package_info.source.is_cratesio_registry()).then(|| {
+            let client = opts.client.clone();
+            let name = package_info.name.clone();
+            let version = package_info.version_str.clone();
+
+            AutoAbortJoinHandle::spawn(async move {
+                fetch_crate_provenance(&client, &name, &version).await
+            })
+        });
+
+    // Warm up connections to the hosts the fetchers are going to download
+    // from, pkg-url could be a template of a full url or start with { repo }.
+    opts.client.warm_up(
+        package_info
+            .repo
+            .as_deref()
+            .into_iter()
+            .chain(
+                desired_targets
+                    .iter()
+                    .filter_map(|target_data| target_data.meta.pkg_url.as_deref()),
+            )
+            .filter_map(|url| Url::parse(url).ok()),
+    );
+
     let binary_name = match package_info.binaries.as_slice() {
         [bin] if bin.name != package_info.name => Some(CompactString::from(bin.name.as_str())),
         _ => None,
@@ -127,6 +425,13 @@ async fn resolve_inner(
 
     let gh_api_client = opts.gh_api_client.get().await?;
 
+    let mut explanations = Vec::new();
+
+    // Recorded by the fetchers to check the artifact against the channel.
+    let digest_algorithms = channel_crate
+        .map(ChannelCrate::digest_algorithms)
+        .unwrap_or_default();
+
     let mut handles_fn =
         |data: Arc<Data>, filter_fetcher_by_name_predicate: fn(&'static str) -> bool| {
             handles.extend(
@@ -146,6 +451,12 @@ async fn resolve_inner(
                             target_data.meta.disabled_strategies.as_deref()
                         {
                             if disabled_strategies.contains(&fetcher.strategy()) {
+                                if opts.explain {
+                                    explanations.push(Explanation::new(
+                                        fetcher.as_ref(),
+                                        Outcome::DisabledByMetadata,
+                                    ));
+                                }
                                 return None;
                             }
                         }
@@ -156,26 +467,43 @@ async fn resolve_inner(
         };
 
     handles_fn(
-        Arc::new(Data::new(
+        Arc::new(fetcher_data(
+            &opts,
             package_info.name.clone(),
-            package_info.version_str.clone(),
-            package_info.repo.clone(),
+            &package_info,
+            digest_algorithms.clone(),
         )),
         |_| true,
     );
 
     if let Some(binary_name) = binary_name {
         handles_fn(
-            Arc::new(Data::new(
+            Arc::new(fetcher_data(
+                &opts,
                 binary_name,
-                package_info.version_str.clone(),
-                package_info.repo.clone(),
+                &package_info,
+                digest_algorithms,
             )),
             |name| name == FETCHER_GH_CRATE_META,
         );
     }
 
-    for fetcher in &handles {
+    // Try the strategies which fetched the crate before first among the
+    // fetchers of each target.
+    if let Some(order) = opts.crate_strategy_order.get(&package_info.name) {
+        sort_within_groups(
+            &mut handles,
+            |fetcher| fetcher.target().to_compact_string(),
+            |fetcher| {
+                order
+                    .iter()
+                    .position(|strategy| *strategy == fetcher.strategy())
+                    .unwrap_or(order.len())
+            },
+        );
+    }
+
+    for (i, fetcher) in handles.iter().enumerate() {
         match timeout(
             opts.maximum_resolution_timeout,
             AutoAbortJoinHandle::new(fetcher.clone().find()).flattened_join(),
@@ -185,25 +513,43 @@ async fn resolve_inner(
             Ok(ret) => match ret {
                 Ok(true) => {
                     // Generate temporary binary path
-                    let bin_path = opts.temp_dir.join(format!(
-                        "bin-{}-{}-{}",
-                        package_info.name,
+                    let bin_path = crate_temp_dir(&opts, &package_info.name).join(format!(
+                        "bin-{}-{}",
                         fetcher.target(),
                         fetcher.fetcher_name()
                     ));
 
-                    match download_extract_and_verify(
+                    let download_permit = if opts.low_memory {
+                        Some(opts.low_memory_download.acquire().await)
+                    } else {
+                        None
+                    };
+                    let res = download_extract_and_verify(
                         fetcher.as_ref(),
                         &bin_path,
                         &package_info,
-                        &opts.install_path,
-                        opts.no_symlinks,
+                        &opts,
+                        channel_crate,
                     )
-                    .await
-                    {
-                        Ok(bin_files) => {
+                    .await;
+                    drop(download_permit);
+
+                    match res {
+                        Ok((bin_files, rekor_log_index)) => {
                             if !bin_files.is_empty() {
                                 fetcher.clone().report_to_upstream();
+
+                                if opts.explain {
+                                    explanations.push(Explanation::new(
+                                        fetcher.as_ref(),
+                                        Outcome::Selected,
+                                    ));
+                                    explanations.extend(handles[i + 1..].iter().map(|fetcher| {
+                                        Explanation::new(fetcher.as_ref(), Outcome::NotAttempted)
+                                    }));
+                                    explain::report(&package_info.name, &explanations);
+                                }
+
                                 return Ok(Resolution::Fetch(Box::new(ResolutionFetch {
                                     fetcher: fetcher.clone(),
                                     new_version: package_info.version,
@@ -211,6 +557,13 @@ async fn resolve_inner(
                                     version_req: version_req_str,
                                     source: package_info.source,
                                     bin_files,
+                                    rekor_log_index,
+                                    provenance: join_provenance(provenance).await,
+                                    preferred_target: desired_targets
+                                        .first()
+                                        .map(|target_data| target_data.target.as_str())
+                                        .filter(|preferred| *preferred != fetcher.target())
+                                        .map(CompactString::from),
                                 })));
                             } else {
                                 warn!(
@@ -218,6 +571,15 @@ async fn resolve_inner(
                                 The fetcher does not provide any optional binary",
                                     fetcher.source_name(),
                                 );
+
+                                if opts.explain {
+                                    explanations.push(Explanation::new(
+                                        fetcher.as_ref(),
+                                        Outcome::Rejected(
+                                            "it does not provide any optional binary".into(),
+                                        ),
+                                    ));
+                                }
                             }
                         }
                         Err(err) => {
@@ -229,16 +591,37 @@ async fn resolve_inner(
                                 fetcher.source_name(),
                                 err
                             );
+
+                            if opts.explain {
+                                explanations.push(Explanation::new(
+                                    fetcher.as_ref(),
+                                    Outcome::Rejected(err.to_string()),
+                                ));
+                            }
                         }
                     }
                 }
-                Ok(false) => (),
+                Ok(false) => {
+                    if opts.explain {
+                        explanations.push(Explanation::new(
+                            fetcher.as_ref(),
+                            Outcome::NotFound(fetcher.find_diagnostics()),
+                        ));
+                    }
+                }
                 Err(err) => {
                     warn!(
                         "Error while checking fetcher {}: {}",
                         fetcher.source_name(),
                         err
                     );
+
+                    if opts.explain {
+                        explanations.push(Explanation::new(
+                            fetcher.as_ref(),
+                            Outcome::FindFailed(err.to_string()),
+                        ));
+                    }
                 }
             },
             Err(err) => {
@@ -247,10 +630,18 @@ async fn resolve_inner(
                     fetcher.source_name(),
                     err
                 );
+
+                if opts.explain {
+                    explanations.push(Explanation::new(fetcher.as_ref(), Outcome::Timeout));
+                }
             }
         }
     }
 
+    if opts.explain {
+        explain::report(&package_info.name, &explanations);
+    }
+
     // At this point, we don't know whether fallback to cargo install is allowed, or whether it will
     // succeed, but things start to get convoluted when try to include that data, so this will do.
     if !opts.disable_telemetry {
@@ -278,9 +669,20 @@ async fn resolve_inner(
         }
     }
 
+    let compile_estimate = package_info.compile_estimate;
+    if let Some(max_compile_deps) = opts.max_compile_deps {
+        if compile_estimate.dependencies > max_compile_deps {
+            return Err(BinstallError::CompileTooExpensive {
+                dependencies: compile_estimate.dependencies,
+                max_compile_deps,
+            });
+        }
+    }
+
     Ok(Resolution::InstallFromSource(ResolutionSource {
         name: package_info.name,
         version: package_info.version_str,
+        compile_estimate,
     }))
 }
 
@@ -288,18 +690,65 @@ async fn resolve_inner(
 ///
 /// Can return empty Vec if all `BinFile` is optional and does not exist
 /// in the archive downloaded.
+///
+/// Also return the index of the artifact in the Rekor transparency log if
+/// `opts.transparency_log_signers` is set.
+///
+/// If `channel_crate` lists digests of artifacts, the artifact must be one
+/// of them.
 async fn download_extract_and_verify(
     fetcher: &dyn Fetcher,
     bin_path: &Path,
     package_info: &PackageInfo,
-    install_path: &Path,
-    no_symlinks: bool,
-) -> Result<Vec<bins::BinFile>, BinstallError> {
+    opts: &Options,
+    channel_crate: Option<&ChannelCrate>,
+) -> Result<(Vec<bins::BinFile>, Option<u64>), BinstallError> {
     // Download and extract it.
     // If that fails, then ignore this fetcher.
     let extracted_files = fetcher.fetch_and_extract(bin_path).await?;
     debug!("extracted_files = {extracted_files:#?}");
 
+    let rekor_log_index = if let Some(signers) = &opts.transparency_log_signers {
+        let artifact = fetcher
+            .artifact()
+            .ok_or(FetchError::UnknownArtifactDigest)?;
+        Some(rekor::verify_inclusion(&opts.client, &artifact.sha256, signers).await?)
+    } else {
+        None
+    };
+
+    if let Some(channel_crate) = channel_crate.filter(|c| c.lists_digests()) {
+        let not_approved = || {
+            BinstallError::NotApprovedByChannel(format_compact!(
+                "artifact of {} for {}",
+                package_info.name,
+                fetcher.target()
+            ))
+        };
+        let (algorithm, expected) = channel_crate
+            .digest(fetcher.target())
+            .ok_or_else(not_approved)?;
+        if fips::ENABLED && !algorithm.is_fips_approved() {
+            return Err(FetchError::NotFipsApproved(algorithm.name()).into());
+        }
+        let actual = fetcher
+            .artifact()
+            .and_then(|artifact| artifact.digest(algorithm).cloned())
+            .ok_or_else(not_approved)?;
+
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(FetchError::ChecksumMismatch {
+                algorithm: algorithm.name(),
+                expected: expected.clone(),
+                actual,
+            }
+            .into());
+        }
+        debug!("Artifact digest {actual} is approved by the channel");
+    }
+
+    check_version_stamp(&extracted_files, bin_path, package_info);
+
     // Build final metadata
     let meta = fetcher.target_meta();
 
@@ -309,21 +758,42 @@ async fn download_extract_and_verify(
         package_info,
         meta,
         bin_path,
-        install_path,
-        no_symlinks,
+        &opts.install_path,
+        opts.no_symlinks,
         &extracted_files,
     )?;
 
     let name = &package_info.name;
+    let is_windows = fetcher.target().contains("windows");
 
-    package_info
+    let bin_files = package_info
         .binaries
         .iter()
         .zip(bin_files)
-        .filter_map(|(bin, bin_file)| {
+        .filter_map(|(bin, mut bin_file)| {
             match bin_file.check_source_exists(&mut |p| extracted_files.has_file(p)) {
                 Ok(()) => Some(Ok(bin_file)),
 
+                Err(_)
+                    if is_windows
+                        && bin_file.locate_source_windows(bin_path, &mut |p| {
+                            extracted_files.find_file_case_insensitive(p)
+                        }) =>
+                {
+                    Some(Ok(bin_file))
+                }
+
+                Err(_) if auto_detect_bin_source(&extracted_files, bin_path, &mut bin_file) => {
+                    warn!(
+                        "When resolving {name} bin {} is not found at the path specified \
+by bin-dir, but it is found at '{}' in the archive and will be installed from there. \
+Please contact with upstream to update bin-dir.",
+                        bin.name,
+                        bin_file.archive_source_path.display(),
+                    );
+                    Some(Ok(bin_file))
+                }
+
                 // This binary is optional
                 Err(err) => {
                     let required_features = &bin.required_features;
@@ -349,8 +819,146 @@ But since it requires features {features}, this bin is ignored."
                 }
             }
         })
-        .collect::<Result<Vec<bins::BinFile>, bins::Error>>()
-        .map_err(BinstallError::from)
+        .collect::<Result<Vec<bins::BinFile>, bins::Error>>()?;
+
+    // Reject bins built for another architecture, e.g. when upstream
+    // uploads a x86_64 build under the aarch64 name.
+    for bin_file in &bin_files {
+        match bin_file.check_target_arch(fetcher.target()) {
+            Ok(()) => (),
+            Err(err @ bins::Error::TargetMismatch { .. }) if opts.allow_target_mismatch => {
+                warn!("{err}, installing it anyway since --allow-target-mismatch is passed");
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok((bin_files, rekor_log_index))
+}
+
+/// Names of files that upstream commonly uses to record the version
+/// of the release in the archive.
+const VERSION_STAMP_FILES: [&str; 2] = ["VERSION", ".version"];
+
+/// Warn if the archive is stamped with a version other than the one being
+/// installed, which usually means the release asset is stale or misnamed.
+///
+/// The version is looked up in [`VERSION_STAMP_FILES`] at the root of the
+/// archive or inside its top-level dir, and in the name of the top-level dir.
+///
+/// This function uses blocking I/O.
+fn check_version_stamp(
+    extracted_files: &ExtractedFiles,
+    bin_path: &Path,
+    package_info: &PackageInfo,
+) {
+    let name = &package_info.name;
+    let version = &package_info.version;
+
+    let top_level_dir = extracted_files.top_level_dir();
+
+    let stamp_files = iter::once(Path::new(""))
+        .chain(top_level_dir.as_deref())
+        .flat_map(|dir| {
+            VERSION_STAMP_FILES
+                .iter()
+                .map(move |file_name| dir.join(file_name))
+        });
+
+    for path in stamp_files {
+        if !extracted_files.has_file(&path) {
+            continue;
+        }
+
+        let stamp = match std::fs::read_to_string(bin_path.join(&path)) {
+            Ok(stamp) => stamp,
+            Err(err) => {
+                debug!("Failed to read version stamp '{}': {err}", path.display());
+                continue;
+            }
+        };
+        let stamp = stamp.trim();
+
+        match Version::parse(stamp.strip_prefix('v').unwrap_or(stamp)) {
+            Ok(stamped_version) if stamped_version != *version => warn!(
+                "When resolving {name}, the archive contains '{}' stamped with version \
+{stamped_version}, which does not match the version {version} being installed. \
+The release asset might be stale or misnamed.",
+                path.display(),
+            ),
+            // Not a version, e.g. a git hash, ignore it.
+            _ => (),
+        }
+    }
+
+    let Some(top_level_dir) = top_level_dir else {
+        return;
+    };
+
+    for dir_name in top_level_dir.iter().filter_map(OsStr::to_str) {
+        if dir_name.contains(package_info.version_str.as_str()) {
+            continue;
+        }
+
+        let stamped_version = dir_name
+            .split(['-', '_'])
+            .filter_map(|token| Version::parse(token.strip_prefix('v').unwrap_or(token)).ok())
+            .find(|stamped_version| stamped_version != version);
+
+        if let Some(stamped_version) = stamped_version {
+            warn!(
+                "When resolving {name}, the top-level dir '{dir_name}' of the archive is stamped \
+with version {stamped_version}, which does not match the version {version} being installed. \
+The release asset might be stale or misnamed."
+            );
+        }
+    }
+}
+
+/// Maximum depth of the directory in the archive the bin is searched in
+/// by [`auto_detect_bin_source`].
+const MAX_AUTO_DETECT_BIN_DEPTH: usize = 4;
+
+/// Search `extracted_files` for `bin_file` at any depth when the path generated
+/// from bin-dir does not exist, and update `bin_file` on success.
+///
+/// To avoid picking up the wrong file, the search only succeeds if there is
+/// exactly one file with the expected name at the shallowest depth found,
+/// and it is not nested deeper than [`MAX_AUTO_DETECT_BIN_DEPTH`].
+fn auto_detect_bin_source(
+    extracted_files: &ExtractedFiles,
+    bin_path: &Path,
+    bin_file: &mut bins::BinFile,
+) -> bool {
+    let candidates = extracted_files.find_by_file_name(OsStr::new(bin_file.base_name.as_str()));
+
+    let depth = |path: &Path| path.components().count();
+
+    let path = match candidates.as_slice() {
+        [path] => *path,
+        [path, next, ..] if depth(path) < depth(next) => *path,
+        [] => return false,
+        _ => {
+            warn!(
+                "Found multiple candidates for bin {} in the archive, refusing to guess: {}",
+                bin_file.base_name,
+                candidates.iter().map(|path| path.display()).format(", "),
+            );
+            return false;
+        }
+    };
+
+    if depth(path) > MAX_AUTO_DETECT_BIN_DEPTH {
+        debug!(
+            "Found bin {} at '{}' in the archive, but it is nested too deep to be trusted",
+            bin_file.base_name,
+            path.display(),
+        );
+        return false;
+    }
+
+    bin_file.set_archive_source_path(bin_path, path.to_path_buf());
+    true
 }
 
 fn collect_bin_files(
@@ -375,22 +983,37 @@ fn collect_bin_files(
         target_related_info: &fetcher.target_data().target_related_info,
     };
 
-    let bin_dir = bin_data
-        .meta
-        .bin_dir
-        .as_deref()
-        .map(Cow::Borrowed)
-        .unwrap_or_else(|| {
-            bins::infer_bin_dir_template(&bin_data, &mut |p| extracted_files.get_dir(p).is_some())
-        });
+    let inferred_bin_dir: Cow<'static, str>;
+    let bin_dirs: Vec<&str> = match bin_data.meta.bin_dir.as_ref() {
+        Some(bin_dir) if !bin_dir.is_empty() => bin_dir.iter().collect(),
+        _ => {
+            inferred_bin_dir = bins::infer_bin_dir_template(&bin_data, &mut |p| {
+                extracted_files.get_dir(p).is_some()
+            });
+            vec![&*inferred_bin_dir]
+        }
+    };
+
+    let templates = bin_dirs
+        .into_iter()
+        .map(Template::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let bin_names: Vec<&str> = package_info
+        .binaries
+        .iter()
+        .map(|bin| bin.name.as_str())
+        .collect();
 
-    let template = Template::parse(&bin_dir)?;
+    let template = bins::select_bin_dir_template(&bin_data, &bin_names, &templates, &mut |p| {
+        extracted_files.has_file(p)
+    });
 
     // Create bin_files
     let bin_files = package_info
         .binaries
         .iter()
-        .map(|bin| bins::BinFile::new(&bin_data, bin.name.as_str(), &template, no_symlinks))
+        .map(|bin| bins::BinFile::new(&bin_data, bin.name.as_str(), template, no_symlinks))
         .collect::<Result<Vec<_>, bins::Error>>()?;
 
     let mut source_set = BTreeSet::new();
@@ -415,6 +1038,7 @@ struct PackageInfo {
     version: Version,
     repo: Option<String>,
     overrides: BTreeMap<String, PkgOverride>,
+    compile_estimate: CompileEstimate,
 }
 
 struct Bin {
@@ -427,7 +1051,7 @@ impl PackageInfo {
     async fn resolve(
         opts: &Options,
         name: CompactString,
-        curr_version: Option<Version>,
+        installed: Option<InstalledCrate>,
         version_req: &VersionReq,
         client: Client,
     ) -> Result<Option<Self>, BinstallError> {
@@ -500,6 +1124,8 @@ impl PackageInfo {
             ),
         };
 
+        let compile_estimate = CompileEstimate::new(&manifest);
+
         let Some(mut package) = manifest.package else {
             return Err(BinstallError::CargoTomlMissingPackage(name));
         };
@@ -516,14 +1142,8 @@ impl PackageInfo {
             }
         };
 
-        if let Some(curr_version) = curr_version {
-            if new_version == curr_version {
-                info!(
-                    "{} v{curr_version} is already installed, use --force to override",
-                    name
-                );
-                return Ok(None);
-            }
+        if !should_install(opts, &name, installed, &new_version)? {
+            return Ok(None);
         }
 
         let (mut meta, binaries): (_, Vec<Bin>) = (
@@ -557,11 +1177,52 @@ impl PackageInfo {
                 version_str: new_version_str,
                 version: new_version,
                 repo: package.repository().map(ToString::to_string),
+                compile_estimate,
             }))
         }
     }
 }
 
+/// Return whether `new_version` of crate `name` should be installed over the
+/// `installed` version, refusing to downgrade it unless allowed.
+fn should_install(
+    opts: &Options,
+    name: &str,
+    installed: Option<InstalledCrate>,
+    new_version: &Version,
+) -> Result<bool, BinstallError> {
+    let Some(InstalledCrate {
+        version: curr_version,
+        installed_by,
+    }) = installed
+    else {
+        return Ok(true);
+    };
+
+    if *new_version == curr_version {
+        if opts.quiet_unchanged {
+            debug!("{name} v{curr_version} is already installed");
+        } else {
+            info!(
+                "{} v{curr_version} is already installed, use --force to override",
+                name
+            );
+        }
+        return Ok(false);
+    }
+
+    if *new_version < curr_version && !opts.allow_downgrade {
+        return Err(Box::new(DowngradeError {
+            installed: curr_version,
+            installed_by,
+            new: new_version.clone(),
+        })
+        .into());
+    }
+
+    Ok(true)
+}
+
 /// Load binstall metadata from the crate `Cargo.toml` at the provided path
 ///
 /// This is a blocking function.
@@ -584,3 +1245,195 @@ pub fn load_manifest_path<P: AsRef<Path>, N: AsRef<str>>(
 
     inner(manifest_path.as_ref(), name.as_ref())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{num::NonZeroU16, time::Duration};
+
+    use binstalk_types::crate_info::InstalledBy;
+
+    use crate::{
+        fetchers::SignaturePolicy,
+        helpers::{jobserver_client::LazyJobserverClient, lazy_gh_api_client::LazyGhApiClient},
+    };
+
+    fn options(low_memory: bool) -> Options {
+        let client = Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+        )
+        .unwrap();
+
+        Options {
+            no_symlinks: false,
+            install_through_symlinks: false,
+            fs_compat: Default::default(),
+            dry_run: false,
+            show_provenance: false,
+            force: false,
+            quiet: false,
+            quiet_unchanged: false,
+            locked: false,
+            no_track: false,
+
+            version_req: None,
+            cargo_toml_fetch_override: None,
+            cli_overrides: PkgOverride::default(),
+
+            desired_targets: crate::get_desired_targets(Some(Vec::new())),
+            crate_desired_targets: BTreeMap::new(),
+            resolvers: Vec::new(),
+            crate_strategy_order: BTreeMap::new(),
+            cargo_install_fallback: false,
+            compile_toolchain: None,
+            cross_compiler: None,
+            compile_rustc_wrapper: None,
+            compile_target_dir: None,
+            max_compile_deps: None,
+            allow_target_mismatch: false,
+            allow_downgrade: false,
+            explain: false,
+
+            frozen_time: None,
+            temp_dir: PathBuf::new(),
+            install_path: PathBuf::new(),
+            staging_dir: None,
+            cargo_root: None,
+
+            gh_api_client: LazyGhApiClient::new(client.clone(), None),
+            client,
+            jobserver_client: LazyJobserverClient::new(),
+            registry: Default::default(),
+
+            signature_policy: SignaturePolicy::IfPresent,
+            pinned_keys: Default::default(),
+            doh_resolver: None,
+            allowed_signers: Default::default(),
+            transparency_log_signers: None,
+            channel: None,
+            disable_telemetry: true,
+            low_memory,
+            low_memory_download: Semaphore::new(1),
+
+            maximum_resolution_timeout: Duration::from_secs(15),
+            crate_timeout: None,
+        }
+    }
+
+    fn package_info() -> PackageInfo {
+        PackageInfo {
+            meta: PkgMeta::default(),
+            binaries: Vec::new(),
+            name: "cargo-binstall".into(),
+            version_str: "1.0.0".into(),
+            source: CrateSource::cratesio_registry(),
+            version: Version::new(1, 0, 0),
+            repo: Some("https://github.com/cargo-bins/cargo-binstall".to_owned()),
+            overrides: BTreeMap::new(),
+            compile_estimate: CompileEstimate { dependencies: 0 },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetcher_data_low_memory() {
+        for low_memory in [false, true] {
+            let opts = options(low_memory);
+            let package_info = package_info();
+
+            for name in [package_info.name.clone(), "binstall".into()] {
+                let data = fetcher_data(&opts, name, &package_info, Vec::new());
+                assert_eq!(data.low_memory(), low_memory);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_should_install() {
+        let installed = |version| {
+            Some(InstalledCrate {
+                version,
+                installed_by: InstalledBy::CargoBinstall,
+            })
+        };
+        let mut opts = options(false);
+
+        // Nothing is installed, e.g. with --force.
+        assert!(should_install(&opts, "foo", None, &Version::new(1, 0, 0)).unwrap());
+
+        assert!(should_install(
+            &opts,
+            "foo",
+            installed(Version::new(1, 0, 0)),
+            &Version::new(2, 0, 0)
+        )
+        .unwrap());
+        assert!(!should_install(
+            &opts,
+            "foo",
+            installed(Version::new(2, 0, 0)),
+            &Version::new(2, 0, 0)
+        )
+        .unwrap());
+
+        let err = should_install(
+            &opts,
+            "foo",
+            installed(Version::new(2, 0, 0)),
+            &Version::new(1, 0, 0),
+        )
+        .unwrap_err();
+        assert!(matches!(err, BinstallError::Downgrade(_)));
+        assert_eq!(
+            err.to_string(),
+            "refusing to downgrade from v2.0.0 (installed by cargo-binstall) to v1.0.0"
+        );
+
+        opts.allow_downgrade = true;
+        assert!(should_install(
+            &opts,
+            "foo",
+            installed(Version::new(2, 0, 0)),
+            &Version::new(1, 0, 0)
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_sort_within_groups() {
+        let mut items = [
+            ("crate-meta", "x86_64"),
+            ("crate-meta", "i686"),
+            ("quick-install", "x86_64"),
+            ("quick-install", "i686"),
+            ("homebrew", "x86_64"),
+        ];
+        let order = ["homebrew", "quick-install"];
+
+        sort_within_groups(
+            &mut items,
+            |(_, target)| *target,
+            |(strategy, _)| {
+                order
+                    .iter()
+                    .position(|s| s == strategy)
+                    .unwrap_or(order.len())
+            },
+        );
+
+        assert_eq!(
+            items,
+            [
+                ("homebrew", "x86_64"),
+                ("quick-install", "i686"),
+                ("quick-install", "x86_64"),
+                ("crate-meta", "i686"),
+                ("crate-meta", "x86_64"),
+            ]
+        );
+    }
+}
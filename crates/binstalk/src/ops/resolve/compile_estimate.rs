@@ -0,0 +1,105 @@
+//! Rough estimation of the cost of building a crate from source.
+
+use std::{fmt, time::Duration};
+
+use crate::{helpers::cargo_toml::Manifest, manifests::cargo_toml_binstall::Meta};
+
+/// Number of direct dependencies above which a build is considered heavy.
+pub const HEAVY_COMPILE_DEPS: usize = 30;
+
+/// Estimated cost of building a crate with `cargo install`.
+#[derive(Copy, Clone, Debug)]
+pub struct CompileEstimate {
+    /// Number of direct dependencies, including build dependencies and
+    /// optional dependencies enabled by the default features.
+    pub dependencies: usize,
+}
+
+impl CompileEstimate {
+    pub(super) fn new(manifest: &Manifest<Meta>) -> Self {
+        let default_features = manifest
+            .features
+            .get("default")
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let is_enabled_by_default = |name: &str| {
+            default_features.iter().any(|feature| {
+                let feature = feature.strip_prefix("dep:").unwrap_or(feature);
+                let dep = feature.split_once('/').map_or(feature, |(dep, _)| dep);
+                dep.trim_end_matches('?') == name
+            })
+        };
+
+        let dependencies = manifest
+            .dependencies
+            .iter()
+            .chain(&manifest.build_dependencies)
+            .filter(|(name, dep)| !dep.optional() || is_enabled_by_default(name))
+            .count();
+
+        Self { dependencies }
+    }
+
+    /// Typical time to build on a desktop, assuming every direct dependency
+    /// pulls in a few transitive ones.
+    pub fn build_time(&self) -> Duration {
+        Duration::from_secs(10 + 10 * self.dependencies as u64)
+    }
+
+    pub fn is_heavy(&self) -> bool {
+        self.dependencies > HEAVY_COMPILE_DEPS
+    }
+}
+
+impl fmt::Display for CompileEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.build_time().as_secs().div_ceil(60);
+        write!(
+            f,
+            "{} direct dependencies, about {minutes} minute{} to build",
+            self.dependencies,
+            if minutes == 1 { "" } else { "s" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compile_estimate() {
+        let manifest: Manifest<Meta> = Manifest::from_slice_with_metadata(
+            br#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[features]
+default = ["dep:b", "c/std"]
+
+[dependencies]
+b = { version = "1", optional = true }
+c = { version = "1", optional = true }
+d = { version = "1", optional = true }
+e = "1"
+
+[build-dependencies]
+f = "1"
+
+[dev-dependencies]
+g = "1"
+"#,
+        )
+        .unwrap();
+
+        let estimate = CompileEstimate::new(&manifest);
+        assert_eq!(estimate.dependencies, 4);
+        assert!(!estimate.is_heavy());
+        assert_eq!(
+            estimate.to_string(),
+            "4 direct dependencies, about 1 minute to build"
+        );
+    }
+}
@@ -4,7 +4,7 @@ use compact_str::CompactString;
 use itertools::Itertools;
 use semver::{Error, VersionReq};
 
-use super::version_ext::VersionReqExt;
+use super::version::VersionReqExt;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CrateName {
@@ -28,10 +28,25 @@ impl FromStr for CrateName {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, VersionReq::parse_from_cli)
+    }
+}
+
+impl CrateName {
+    /// Parse as [`FromStr`], but with the version parsed by
+    /// [`VersionReqExt::parse_exact_from_cli`].
+    pub fn parse_exact(s: &str) -> Result<Self, Error> {
+        Self::parse_with(s, VersionReq::parse_exact_from_cli)
+    }
+
+    fn parse_with(
+        s: &str,
+        parse_version: fn(&str) -> Result<VersionReq, Error>,
+    ) -> Result<Self, Error> {
         Ok(if let Some((name, version)) = s.split_once('@') {
             CrateName {
                 name: name.into(),
-                version_req: Some(VersionReq::parse_from_cli(version)?),
+                version_req: Some(parse_version(version)?),
             }
         } else {
             CrateName {
@@ -40,9 +55,7 @@ impl FromStr for CrateName {
             }
         })
     }
-}
 
-impl CrateName {
     pub fn dedup(mut crate_names: Vec<Self>) -> impl Iterator<Item = Self> {
         crate_names.sort_by(|x, y| x.name.cmp(&y.name));
         crate_names.into_iter().coalesce(|previous, current| {
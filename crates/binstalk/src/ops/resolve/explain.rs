@@ -0,0 +1,76 @@
+use std::fmt;
+
+use compact_str::CompactString;
+use itertools::Itertools;
+use tracing::info;
+
+use crate::fetchers::{Fetcher, FindDiagnostic};
+
+/// Why a fetcher was or wasn't attempted, and how the attempt ended.
+#[derive(Debug)]
+pub(super) enum Outcome {
+    /// The strategy is disabled via `disabled-strategies` in the package
+    /// metadata or `--disable-strategies`.
+    DisabledByMetadata,
+    /// A fetcher earlier in the list succeeded.
+    NotAttempted,
+    /// The fetcher found a package and it is used.
+    Selected,
+    NotFound(Vec<FindDiagnostic>),
+    Timeout,
+    FindFailed(String),
+    /// The package is found, but it is rejected after downloading.
+    Rejected(String),
+}
+
+#[derive(Debug)]
+pub(super) struct Explanation {
+    source_name: CompactString,
+    target: CompactString,
+    outcome: Outcome,
+}
+
+impl Explanation {
+    pub(super) fn new(fetcher: &dyn Fetcher, outcome: Outcome) -> Self {
+        Self {
+            source_name: fetcher.source_name(),
+            target: fetcher.target().into(),
+            outcome,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            source_name,
+            target,
+            outcome,
+        } = self;
+
+        write!(f, "{source_name} ({target}): ")?;
+
+        match outcome {
+            Outcome::DisabledByMetadata => f.write_str(
+                "not attempted, the strategy is disabled by package metadata or --disable-strategies",
+            ),
+            Outcome::NotAttempted => f.write_str("not attempted, an earlier fetcher succeeded"),
+            Outcome::Selected => f.write_str("found, selected for installation"),
+            Outcome::NotFound(diagnostics) if diagnostics.is_empty() => f.write_str("not found"),
+            Outcome::NotFound(diagnostics) => {
+                write!(f, "not found: {}", diagnostics.iter().format(", "))
+            }
+            Outcome::Timeout => f.write_str("timed out, see --maximum-resolution-timeout"),
+            Outcome::FindFailed(err) => write!(f, "failed: {err}"),
+            Outcome::Rejected(err) => write!(f, "found but rejected: {err}"),
+        }
+    }
+}
+
+/// Print why each fetcher was or wasn't attempted when resolving `name`.
+pub(super) fn report(name: &str, explanations: &[Explanation]) {
+    info!("Explanation of strategies attempted for {name}:");
+    for explanation in explanations {
+        info!("  {explanation}");
+    }
+}
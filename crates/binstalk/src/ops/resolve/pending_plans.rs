@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// A manifest being loaded by [`super::resolve_batch`], which is done once
+/// dropped, even if the task loading it panics or is aborted.
+pub(super) struct PendingPlan(Arc<watch::Sender<usize>>);
+
+impl Drop for PendingPlan {
+    fn drop(&mut self) {
+        self.0.send_modify(|left| *left -= 1);
+    }
+}
+
+/// Wait for all the manifests of a batch to be loaded.
+#[derive(Clone)]
+pub(super) struct AllPlans(watch::Receiver<usize>);
+
+impl AllPlans {
+    pub(super) async fn wait(mut self) {
+        // The sender is only dropped along with the last `PendingPlan`.
+        let _ = self.0.wait_for(|left| *left == 0).await;
+    }
+}
+
+/// Return one [`PendingPlan`] for each of the `n` manifests of a batch.
+pub(super) fn pending_plans(n: usize) -> (Vec<PendingPlan>, AllPlans) {
+    let (tx, rx) = watch::channel(n);
+    let tx = Arc::new(tx);
+
+    (
+        (0..n).map(|_| PendingPlan(tx.clone())).collect(),
+        AllPlans(rx),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{future::pending, time::Duration};
+
+    use tokio::{runtime, task, time::timeout};
+
+    #[test]
+    fn test_pending_plans() {
+        runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let (mut plans, all_plans) = pending_plans(3);
+
+                let plan = plans.pop().unwrap();
+                let panicked = task::spawn(async move {
+                    let _plan = plan;
+                    panic!("Failed to load the manifest");
+                });
+                assert!(panicked.await.is_err());
+
+                let plan = plans.pop().unwrap();
+                let aborted = task::spawn(async move {
+                    let _plan = plan;
+                    pending::<()>().await
+                });
+                aborted.abort();
+                assert!(aborted.await.unwrap_err().is_cancelled());
+
+                let wait = Duration::from_millis(10);
+                assert!(timeout(wait, all_plans.clone().wait()).await.is_err());
+
+                drop(plans);
+                timeout(wait, all_plans.wait()).await.unwrap();
+            });
+    }
+}
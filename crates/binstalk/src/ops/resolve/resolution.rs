@@ -1,4 +1,4 @@
-use std::{borrow::Cow, env, ffi::OsStr, fmt, iter, path::Path, sync::Arc};
+use std::{borrow::Cow, env, ffi::OsStr, fmt, fs, iter, path::Path, sync::Arc};
 
 use command_group::AsyncCommandGroup;
 use compact_str::{CompactString, ToCompactString};
@@ -12,8 +12,9 @@ use crate::{
     bins,
     errors::BinstallError,
     fetchers::Fetcher,
-    manifests::crate_info::{CrateInfo, CrateSource},
-    ops::Options,
+    manifests::crate_info::{ArtifactInfo, CrateInfo, CrateSource, PublishProvenance},
+    ops::{resolve::CompileEstimate, staging, CrossCompiler, Options},
+    TARGET,
 };
 
 pub struct ResolutionFetch {
@@ -23,11 +24,18 @@ pub struct ResolutionFetch {
     pub version_req: CompactString,
     pub bin_files: Vec<bins::BinFile>,
     pub source: CrateSource,
+    /// Index of the artifact in the Rekor transparency log, if verified.
+    pub rekor_log_index: Option<u64>,
+    /// Publisher of the version on crates.io, if known.
+    pub provenance: Option<PublishProvenance>,
+    /// The first desired target, if the artifact is for a fallback target.
+    pub preferred_target: Option<CompactString>,
 }
 
 pub struct ResolutionSource {
     pub name: CompactString,
     pub version: CompactString,
+    pub compile_estimate: CompileEstimate,
 }
 
 pub enum Resolution {
@@ -53,15 +61,26 @@ impl Resolution {
 impl ResolutionFetch {
     pub fn install(self, opts: &Options) -> Result<CrateInfo, BinstallError> {
         let crate_name = self.name.clone();
-        self.install_inner(opts)
-            .map_err(|err| err.crate_context(crate_name))
+        self.install_inner(opts).map_err(|err| {
+            // Report the failure instead of resuming the installation later.
+            if let Some(staging_dir) = &opts.staging_dir {
+                if let Err(err) = staging::unstage(staging_dir, &crate_name) {
+                    warn!("Failed to clean up the staged binaries of {crate_name}: {err}");
+                }
+            }
+            err.crate_context(crate_name)
+        })
     }
 
-    fn install_inner(self, opts: &Options) -> Result<CrateInfo, BinstallError> {
-        type InstallFp = fn(&bins::BinFile) -> Result<(), bins::Error>;
+    fn install_inner(mut self, opts: &Options) -> Result<CrateInfo, BinstallError> {
+        type InstallFp = fn(&bins::BinFile, bins::FsCompat) -> Result<(), bins::Error>;
 
         let (install_bin, install_link): (InstallFp, InstallFp) = match (opts.no_track, opts.force)
         {
+            (true, true) | (false, _) if opts.install_through_symlinks => (
+                bins::BinFile::install_bin_through_symlink,
+                bins::BinFile::install_link,
+            ),
             (true, true) | (false, _) => (bins::BinFile::install_bin, bins::BinFile::install_link),
             (true, false) => (
                 bins::BinFile::install_bin_noclobber,
@@ -69,30 +88,49 @@ impl ResolutionFetch {
             ),
         };
 
+        let crate_info = CrateInfo {
+            name: self.name,
+            version_req: self.version_req,
+            current_version: self.new_version,
+            source: self.source,
+            target: self.fetcher.target().to_compact_string(),
+            bins: self
+                .bin_files
+                .iter()
+                .map(|bin| bin.base_name.clone())
+                .collect(),
+            artifact: self.fetcher.artifact().map(|artifact| ArtifactInfo {
+                rekor_log_index: self.rekor_log_index,
+                ..artifact
+            }),
+            provenance: self.provenance,
+            preferred_target: self.preferred_target,
+        };
+
+        if let Some(staging_dir) = &opts.staging_dir {
+            staging::stage(staging_dir, &crate_info, &mut self.bin_files)?;
+        }
+
         info!("Installing binaries...");
         for file in &self.bin_files {
-            install_bin(file)?;
+            install_bin(file, opts.fs_compat)?;
+
+            if let Some(frozen_time) = opts.frozen_time {
+                fs::File::options()
+                    .write(true)
+                    .open(&file.dest)
+                    .and_then(|dest| dest.set_modified(frozen_time))?;
+            }
         }
 
         // Generate symlinks
         if !opts.no_symlinks {
             for file in &self.bin_files {
-                install_link(file)?;
+                install_link(file, opts.fs_compat)?;
             }
         }
 
-        Ok(CrateInfo {
-            name: self.name,
-            version_req: self.version_req,
-            current_version: self.new_version,
-            source: self.source,
-            target: self.fetcher.target().to_compact_string(),
-            bins: self
-                .bin_files
-                .into_iter()
-                .map(|bin| bin.base_name)
-                .collect(),
-        })
+        Ok(crate_info)
     }
 
     pub fn print(&self, opts: &Options) {
@@ -117,6 +155,20 @@ impl ResolutionFetch {
             fetcher.source_name()
         );
 
+        if let Some(preferred_target) = &self.preferred_target {
+            warn!(
+                fallback_target = target,
+                preferred_target = preferred_target.as_str(),
+                "No artifact of {name} v{new_version} is available for {preferred_target}, \
+                installing the one for fallback target {target} instead, which may be slower, \
+                e.g. if it runs under emulation"
+            );
+        }
+
+        if let Some(provenance) = &self.provenance {
+            info!("{name} v{new_version} was published to crates.io by {provenance}");
+        }
+
         info!("This will install the following binaries:");
         for file in bin_files {
             info!("  - {}", file.preview_bin());
@@ -140,7 +192,7 @@ impl ResolutionSource {
     }
 
     async fn install_inner(self, opts: Arc<Options>) -> Result<(), BinstallError> {
-        let target = if let Some(targets) = opts.desired_targets.get_initialized() {
+        let target = if let Some(targets) = opts.desired_targets_of(&self.name).get_initialized() {
             Some(targets.first().ok_or(BinstallError::NoViableTargets)?)
         } else {
             None
@@ -149,11 +201,31 @@ impl ResolutionSource {
         let name = &self.name;
         let version = &self.version;
 
-        let cargo = env::var_os("CARGO")
-            .map(Cow::Owned)
-            .unwrap_or_else(|| Cow::Borrowed(OsStr::new("cargo")));
+        let cross_compiler = opts
+            .cross_compiler
+            .filter(|_| target.is_some_and(|target| target.as_str() != TARGET));
 
-        let mut cmd = Command::new(cargo);
+        let mut cmd = if cross_compiler == Some(CrossCompiler::Cross) {
+            Command::new("cross")
+        } else if opts.compile_toolchain.is_some() {
+            // `CARGO` points to the cargo of a specific toolchain, only the
+            // rustup proxy understands `+toolchain`.
+            Command::new("cargo")
+        } else {
+            let cargo = env::var_os("CARGO")
+                .map(Cow::Owned)
+                .unwrap_or_else(|| Cow::Borrowed(OsStr::new("cargo")));
+
+            Command::new(cargo)
+        };
+
+        if let Some(toolchain) = &opts.compile_toolchain {
+            cmd.arg(format!("+{toolchain}"));
+        }
+
+        if cross_compiler == Some(CrossCompiler::Zigbuild) {
+            cmd.arg("zigbuild");
+        }
 
         cmd.arg("install")
             .arg(name)
@@ -185,6 +257,14 @@ impl ResolutionSource {
             cmd.arg("--no-track");
         }
 
+        if let Some(target_dir) = &opts.compile_target_dir {
+            cmd.arg("--target-dir").arg(target_dir);
+        }
+
+        if let Some(rustc_wrapper) = &opts.compile_rustc_wrapper {
+            cmd.env("RUSTC_WRAPPER", rustc_wrapper);
+        }
+
         debug!("Running `{}`", format_cmd(&cmd));
 
         if !opts.dry_run {
@@ -215,8 +295,8 @@ impl ResolutionSource {
 
     pub fn print(&self) {
         warn!(
-            "The package {} v{} will be installed from source (with cargo)",
-            self.name, self.version
+            "The package {} v{} will be installed from source (with cargo), it has {}",
+            self.name, self.version, self.compile_estimate
         )
     }
 }
@@ -0,0 +1,211 @@
+use compact_str::{format_compact, CompactString};
+use itertools::Itertools;
+use semver::{Comparator, Op, Prerelease, Version, VersionReq};
+
+/// Extension trait for [`VersionReq`].
+pub trait VersionReqExt {
+    /// Return `true` if `self.matches(version)` returns `true`
+    /// and the `version` is the latest one acceptable by `self`.
+    fn is_latest_compatible(&self, version: &Version) -> bool;
+
+    /// Parse from CLI option, with the grammar of version requirements of
+    /// Cargo.toml dependencies, e.g. `>=1.2, <1.5`, `1.*` or `1.2.3+build`.
+    ///
+    /// As in `cargo install`, a full version alone is treated as if preceded by `=`, while
+    /// other bare versions such as `1.2` are treated as if preceded by `^`.
+    fn parse_from_cli(str: &str) -> Result<Self, semver::Error>
+    where
+        Self: Sized;
+
+    /// Parse from CLI option as [`VersionReqExt::parse_from_cli`], but with every bare
+    /// version treated as if preceded by `=`, e.g. `1.2` matches `1.2.*` only.
+    fn parse_exact_from_cli(str: &str) -> Result<Self, semver::Error>
+    where
+        Self: Sized;
+}
+
+impl VersionReqExt for VersionReq {
+    fn is_latest_compatible(&self, version: &Version) -> bool {
+        if !self.matches(version) {
+            return false;
+        }
+
+        // Test if bumping patch will be accepted
+        let bumped_version = Version::new(version.major, version.minor, version.patch + 1);
+
+        if self.matches(&bumped_version) {
+            return false;
+        }
+
+        // Test if bumping prerelease will be accepted if version has one.
+        let pre = &version.pre;
+        if !pre.is_empty() {
+            // Bump pre by appending random number to the end.
+            let bumped_pre = format_compact!("{}.1", pre.as_str());
+
+            let bumped_version = Version {
+                major: version.major,
+                minor: version.minor,
+                patch: version.patch,
+                pre: Prerelease::new(&bumped_pre).unwrap(),
+                build: Default::default(),
+            };
+
+            if self.matches(&bumped_version) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn parse_from_cli(version: &str) -> Result<Self, semver::Error> {
+        parse_from_cli(version, false)
+    }
+
+    fn parse_exact_from_cli(version: &str) -> Result<Self, semver::Error> {
+        parse_from_cli(version, true)
+    }
+}
+
+fn parse_from_cli(version: &str, exact: bool) -> Result<VersionReq, semver::Error> {
+    let version = version.trim();
+
+    // Build metadata is ignored, as in cargo.
+    if let Ok(version) = Version::parse(version) {
+        return Ok(VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Exact,
+                major: version.major,
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+                pre: version.pre,
+            }],
+        });
+    }
+
+    if !exact {
+        return version.parse();
+    }
+
+    version
+        .split(',')
+        .map(str::trim)
+        .map(|comparator| {
+            if comparator.starts_with(|ch: char| ch.is_ascii_digit()) {
+                format_compact!("={comparator}")
+            } else {
+                CompactString::from(comparator)
+            }
+        })
+        .join(", ")
+        .parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_from_cli() {
+        for (version, req, exact_req) in [
+            // Full versions are exact
+            ("1.2.3", "=1.2.3", "=1.2.3"),
+            (" 1.2.3 ", "=1.2.3", "=1.2.3"),
+            ("0.14.1-alpha.5", "=0.14.1-alpha.5", "=0.14.1-alpha.5"),
+            ("1.2.3+build.5", "=1.2.3", "=1.2.3"),
+            ("1.2.3-rc.1+build", "=1.2.3-rc.1", "=1.2.3-rc.1"),
+            // Bare partial versions
+            ("1.2", "^1.2", "=1.2"),
+            ("1", "^1", "=1"),
+            // Operators
+            ("=1.2.3", "=1.2.3", "=1.2.3"),
+            ("^1.2", "^1.2", "^1.2"),
+            ("~1.2", "~1.2", "~1.2"),
+            (">= 1.2", ">=1.2", ">=1.2"),
+            ("<2.0.0-alpha", "<2.0.0-alpha", "<2.0.0-alpha"),
+            (">=1.2.3+build", ">=1.2.3", ">=1.2.3"),
+            // Wildcards
+            ("*", "*", "*"),
+            ("1.*", "1.*", "=1"),
+            ("1.2.x", "1.2.*", "=1.2"),
+            ("1.*.*", "1.*", "=1"),
+            // Multiple requirements
+            (">=1.2, <1.5", ">=1.2, <1.5", ">=1.2, <1.5"),
+            ("1.2,<1.5", "^1.2, <1.5", "=1.2, <1.5"),
+            ("~1.2.3, 1.2.5", "~1.2.3, ^1.2.5", "~1.2.3, =1.2.5"),
+        ] {
+            assert_eq!(
+                VersionReq::parse_from_cli(version).unwrap().to_string(),
+                req,
+                "{version}"
+            );
+            assert_eq!(
+                VersionReq::parse_exact_from_cli(version)
+                    .unwrap()
+                    .to_string(),
+                exact_req,
+                "{version}"
+            );
+        }
+
+        for version in [
+            "", "v1.2.3", "1.2.3.4", ">=1.2,", "1.2 <1.5", "^*", "latest",
+        ] {
+            assert!(VersionReq::parse_from_cli(version).is_err(), "{version}");
+            assert!(
+                VersionReq::parse_exact_from_cli(version).is_err(),
+                "{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_from_cli_matches() {
+        let matches = |req: &str, version: &str| {
+            VersionReq::parse_from_cli(req)
+                .unwrap()
+                .matches(&Version::parse(version).unwrap())
+        };
+
+        assert!(matches("1.2.3", "1.2.3"));
+        assert!(!matches("1.2.3", "1.2.4"));
+        assert!(matches("1.2", "1.9.0"));
+        assert!(!matches("1.2", "2.0.0"));
+        assert!(matches("0.2", "0.2.9"));
+        assert!(!matches("0.2", "0.3.0"));
+        assert!(matches("1.2.3+build", "1.2.3+other"));
+        assert!(matches(">=1.2, <1.5", "1.4.9"));
+        assert!(!matches(">=1.2, <1.5", "1.5.0"));
+        assert!(!matches("1.*", "1.0.0-alpha"));
+        assert!(matches("1.0.0-alpha", "1.0.0-alpha"));
+    }
+
+    #[test]
+    fn test() {
+        // Test star
+        assert!(!VersionReq::STAR.is_latest_compatible(&Version::parse("0.0.1").unwrap()));
+        assert!(!VersionReq::STAR.is_latest_compatible(&Version::parse("0.1.1").unwrap()));
+        assert!(!VersionReq::STAR.is_latest_compatible(&Version::parse("0.1.1-alpha").unwrap()));
+
+        // Test ^x.y.z
+        assert!(!VersionReq::parse("^0.1")
+            .unwrap()
+            .is_latest_compatible(&Version::parse("0.1.99").unwrap()));
+
+        // Test =x.y.z
+        assert!(VersionReq::parse("=0.1.0")
+            .unwrap()
+            .is_latest_compatible(&Version::parse("0.1.0").unwrap()));
+
+        // Test =x.y.z-alpha
+        assert!(VersionReq::parse("=0.1.0-alpha")
+            .unwrap()
+            .is_latest_compatible(&Version::parse("0.1.0-alpha").unwrap()));
+
+        // Test >=x.y.z-alpha
+        assert!(!VersionReq::parse(">=0.1.0-alpha")
+            .unwrap()
+            .is_latest_compatible(&Version::parse("0.1.0-alpha").unwrap()));
+    }
+}
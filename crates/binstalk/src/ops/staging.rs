@@ -0,0 +1,229 @@
+//! Staging of the binaries of a crate before they are installed, so that an
+//! interrupted installation is resumed by the next run of cargo-binstall
+//! instead of leaving only some of the binaries updated.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use compact_str::CompactString;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{bins, errors::BinstallError, manifests::crate_info::CrateInfo};
+
+/// Name of the directory under the install path the crates are staged in.
+pub const STAGING_DIR: &str = ".cargo-binstall-staging";
+
+/// Name of the record of a staged crate, which is only written once all of
+/// its binaries are staged.
+const STAGED_RECORD: &str = "staged.json";
+
+#[derive(Serialize, Deserialize)]
+struct StagedCrate {
+    crate_info: CrateInfo,
+    bins: Vec<StagedBin>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StagedBin {
+    base_name: CompactString,
+    dest: PathBuf,
+    link: Option<PathBuf>,
+}
+
+impl StagedBin {
+    /// The binary is staged under the file name it is installed as.
+    fn staged_path(&self, dir: &Path) -> PathBuf {
+        dir.join(self.dest.file_name().unwrap_or_default())
+    }
+
+    fn to_bin_file(&self, dir: &Path) -> bins::BinFile {
+        let source = self.staged_path(dir);
+
+        bins::BinFile {
+            base_name: self.base_name.clone(),
+            archive_source_path: source.clone(),
+            source,
+            dest: self.dest.clone(),
+            link: self.link.clone(),
+        }
+    }
+}
+
+fn remove_dir_if_exists(dir: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+/// Move `bin_files` into the staging directory of the crate of `crate_info`
+/// and record them, pointing their source to the staged binaries.
+pub(crate) fn stage(
+    staging_dir: &Path,
+    crate_info: &CrateInfo,
+    bin_files: &mut [bins::BinFile],
+) -> io::Result<()> {
+    let dir = staging_dir.join(&*crate_info.name);
+    // Supersede whatever is left by an earlier installation of the crate.
+    remove_dir_if_exists(&dir)?;
+    fs::create_dir_all(&dir)?;
+
+    let mut bins = Vec::with_capacity(bin_files.len());
+    for bin_file in bin_files {
+        let staged = StagedBin {
+            base_name: bin_file.base_name.clone(),
+            dest: bin_file.dest.clone(),
+            link: bin_file.link.clone(),
+        };
+        let path = staged.staged_path(&dir);
+
+        debug!(
+            "Staging '{}' at '{}'",
+            bin_file.source.display(),
+            path.display()
+        );
+        // Symlinks in the package are copied as the file they point to.
+        if bin_file.source.is_symlink() || fs::rename(&bin_file.source, &path).is_err() {
+            fs::copy(&bin_file.source, &path)?;
+        }
+        bin_file.source = path;
+        bins.push(staged);
+    }
+
+    let record = serde_json::to_vec(&StagedCrate {
+        crate_info: crate_info.clone(),
+        bins,
+    })
+    .map_err(io::Error::other)?;
+
+    let record_path = dir.join(STAGED_RECORD);
+    let tmp_path = record_path.with_extension("json.tmp");
+    fs::write(&tmp_path, record)?;
+    fs::rename(tmp_path, record_path)
+}
+
+/// Remove the staging directory of crate `name`, once it is installed and
+/// recorded in the manifests, or failed to install.
+pub fn unstage(staging_dir: &Path, name: &str) -> io::Result<()> {
+    remove_dir_if_exists(&staging_dir.join(name))
+}
+
+/// Install the crates staged by installations interrupted before they are
+/// recorded in the manifests, and return their info to record them.
+///
+/// Crates whose staging is incomplete are discarded, since none of their
+/// binaries has been installed yet.
+///
+/// The caller must hold the lock of the manifests, and unstage the crates
+/// once they are recorded.
+pub fn resume(
+    staging_dir: &Path,
+    fs_compat: bins::FsCompat,
+) -> Result<Vec<CrateInfo>, BinstallError> {
+    let entries = match fs::read_dir(staging_dir) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        res => res?,
+    };
+
+    let mut crate_infos = Vec::new();
+    for entry in entries {
+        let dir = entry?.path();
+
+        let staged = match fs::read(dir.join(STAGED_RECORD)) {
+            Ok(record) => serde_json::from_slice::<StagedCrate>(&record).ok(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let Some(staged) = staged else {
+            warn!(
+                "Discarding the incomplete staging of binaries at '{}'",
+                dir.display()
+            );
+            remove_dir_if_exists(&dir)?;
+            continue;
+        };
+
+        let crate_info = staged.crate_info;
+        info!(
+            "Resuming the interrupted installation of {} v{}",
+            crate_info.name, crate_info.current_version
+        );
+        for bin in &staged.bins {
+            let bin_file = bin.to_bin_file(&dir);
+            // Binaries installed before the interruption are moved out of
+            // the staging directory already.
+            if bin_file.source.try_exists()? {
+                bin_file.install_bin(fs_compat)?;
+            }
+            bin_file.install_link(fs_compat)?;
+        }
+
+        crate_infos.push(crate_info);
+    }
+
+    Ok(crate_infos)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use semver::Version;
+
+    use crate::manifests::crate_info::CrateSource;
+
+    #[test]
+    fn test_stage_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging_dir = dir.path().join(STAGING_DIR);
+        let install_path = dir.path().join("bin");
+        fs::create_dir_all(&install_path).unwrap();
+
+        let extracted = dir.path().join("hello-extracted");
+        fs::write(&extracted, b"hello").unwrap();
+
+        let crate_info = CrateInfo {
+            name: "hello".into(),
+            version_req: "*".into(),
+            current_version: Version::new(1, 0, 0),
+            source: CrateSource::cratesio_registry(),
+            target: "x86_64-unknown-linux-gnu".into(),
+            bins: vec!["hello".into()],
+            artifact: None,
+            provenance: None,
+            preferred_target: None,
+        };
+        let mut bin_files = [bins::BinFile {
+            base_name: "hello".into(),
+            source: extracted.clone(),
+            archive_source_path: extracted.clone(),
+            dest: install_path.join("hello"),
+            link: None,
+        }];
+
+        stage(&staging_dir, &crate_info, &mut bin_files).unwrap();
+        assert!(!extracted.exists());
+        assert_eq!(bin_files[0].source, staging_dir.join("hello").join("hello"));
+
+        // Interrupted before the binary is installed
+        let resumed = resume(&staging_dir, bins::FsCompat::Never).unwrap();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].name, "hello");
+        assert_eq!(fs::read(install_path.join("hello")).unwrap(), b"hello");
+
+        unstage(&staging_dir, "hello").unwrap();
+        assert!(resume(&staging_dir, bins::FsCompat::Never)
+            .unwrap()
+            .is_empty());
+
+        // Interrupted while staging
+        fs::create_dir_all(staging_dir.join("partial")).unwrap();
+        assert!(resume(&staging_dir, bins::FsCompat::Never)
+            .unwrap()
+            .is_empty());
+        assert!(!staging_dir.join("partial").exists());
+    }
+}
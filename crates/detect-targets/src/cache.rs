@@ -0,0 +1,183 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+use crate::detect_targets;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+
+/// Describe the env the targets are detected in, so that the cached targets
+/// are detected again once the OS, kernel or libc changes.
+fn fingerprint() -> String {
+    // Detected at runtime, unlike `crate::TARGET`, so that it changes once
+    // the binary is run on another machine, e.g. from a shared cargo home.
+    let mut fingerprint = guess_host_triple::guess_host_triple()
+        .unwrap_or("unknown")
+        .to_string();
+
+    // The host target of `$CARGO -vV` is preferred during detection.
+    if let Some(cargo) = env::var_os("CARGO") {
+        fingerprint.push(' ');
+        fingerprint.push_str(&cargo.to_string_lossy());
+    }
+
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        if let Ok(release) = fs::read_to_string("/proc/sys/kernel/osrelease") {
+            fingerprint.push(' ');
+            fingerprint.push_str(release.trim());
+        }
+
+        // Dynamic loaders of glibc and musl libc, e.g. `ld-linux-x86-64.so.2`
+        // and `ld-musl-x86_64.so.1`.
+        for dir in ["/lib", "/lib64"] {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut loaders: Vec<_> = entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| name.starts_with("ld-"))
+                .collect();
+            loaders.sort_unstable();
+
+            for loader in loaders {
+                fingerprint.push(' ');
+                fingerprint.push_str(&loader);
+            }
+        }
+    }
+
+    // The fingerprint is the first line of the cache.
+    fingerprint.replace('\n', " ")
+}
+
+fn read_cache(path: &Path, fingerprint: &str) -> Option<Vec<String>> {
+    let cache = fs::read_to_string(path).ok()?;
+    let mut lines = cache.lines();
+
+    if lines.next()? != fingerprint {
+        #[cfg(feature = "tracing")]
+        debug!("Cached targets are detected in a different env");
+        return None;
+    }
+
+    let targets: Vec<String> = lines.map(String::from).collect();
+    (!targets.is_empty()).then_some(targets)
+}
+
+fn write_cache(path: &Path, fingerprint: &str, targets: &[String]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut cache = fingerprint.to_string();
+    for target in targets {
+        cache.push('\n');
+        cache.push_str(target);
+    }
+
+    // Write to a temporary file and rename it, so that the cache is never
+    // read half-written by another process.
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let res = fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(cache.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp_path, path));
+    if res.is_err() {
+        fs::remove_file(&tmp_path).ok();
+    }
+    res
+}
+
+/// Same as [`detect_targets`], but the targets detected are cached in the
+/// file at `cache_path` and reused until the OS, kernel or libc changes.
+///
+/// If `redetect` is true, then the cache is ignored and overwritten.
+pub async fn detect_targets_cached(cache_path: &Path, redetect: bool) -> Vec<String> {
+    let fingerprint = fingerprint();
+
+    if !redetect {
+        if let Some(targets) = read_cache(cache_path, &fingerprint) {
+            #[cfg(feature = "tracing")]
+            debug!(
+                "Using targets cached in {}: {targets:?}",
+                cache_path.display()
+            );
+            return targets;
+        }
+    }
+
+    let targets = detect_targets().await;
+
+    if let Err(_err) = write_cache(cache_path, &fingerprint, &targets) {
+        #[cfg(feature = "tracing")]
+        debug!(
+            "Failed to cache targets in {}: {_err}",
+            cache_path.display()
+        );
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn targets() -> Vec<String> {
+        vec![
+            "x86_64-unknown-linux-gnu".into(),
+            "x86_64-unknown-linux-musl".into(),
+        ]
+    }
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("binstall/targets");
+
+        assert_eq!(read_cache(&path, "fingerprint"), None);
+
+        write_cache(&path, "fingerprint", &targets()).unwrap();
+        assert_eq!(read_cache(&path, "fingerprint"), Some(targets()));
+
+        // Only the cache is left in the dir.
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        // Overwritten
+        write_cache(&path, "fingerprint", &targets()[..1]).unwrap();
+        assert_eq!(
+            read_cache(&path, "fingerprint"),
+            Some(targets()[..1].to_vec())
+        );
+    }
+
+    #[test]
+    fn test_cache_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("targets");
+
+        write_cache(&path, "fingerprint", &targets()).unwrap();
+        assert_eq!(read_cache(&path, "another fingerprint"), None);
+
+        write_cache(&path, "fingerprint", &[]).unwrap();
+        assert_eq!(read_cache(&path, "fingerprint"), None);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = fingerprint();
+
+        assert!(!fingerprint.is_empty());
+        assert!(!fingerprint.contains('\n'));
+        assert_eq!(fingerprint, super::fingerprint());
+    }
+}
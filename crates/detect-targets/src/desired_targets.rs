@@ -1,15 +1,28 @@
-use crate::detect_targets;
+use crate::{detect_targets, detect_targets_cached};
 
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use tokio::sync::OnceCell;
 
+#[derive(Debug, Clone)]
+struct Cache {
+    path: PathBuf,
+    redetect: bool,
+}
+
 #[derive(Debug)]
 enum DesiredTargetsInner {
-    AutoDetect(Arc<OnceCell<Vec<String>>>),
+    AutoDetect(Arc<OnceCell<Vec<String>>>, Option<Cache>),
     Initialized(Vec<String>),
 }
 
+async fn detect(cache: Option<Cache>) -> Vec<String> {
+    match cache {
+        Some(cache) => detect_targets_cached(&cache.path, cache.redetect).await,
+        None => detect_targets().await,
+    }
+}
+
 #[derive(Debug)]
 pub struct DesiredTargets(DesiredTargetsInner);
 
@@ -18,15 +31,16 @@ impl DesiredTargets {
         Self(DesiredTargetsInner::Initialized(targets))
     }
 
-    fn auto_detect() -> Self {
+    fn auto_detect(cache: Option<Cache>) -> Self {
         let arc = Arc::new(OnceCell::new());
 
         let once_cell = arc.clone();
+        let spawned_cache = cache.clone();
         tokio::spawn(async move {
-            once_cell.get_or_init(detect_targets).await;
+            once_cell.get_or_init(|| detect(spawned_cache)).await;
         });
 
-        Self(DesiredTargetsInner::AutoDetect(arc))
+        Self(DesiredTargetsInner::AutoDetect(arc, cache))
     }
 
     pub async fn get(&self) -> &[String] {
@@ -38,7 +52,7 @@ impl DesiredTargets {
             // This will mostly just wait for the spawned task,
             // on rare occausion though, it will poll the future
             // returned by `detect_targets`.
-            AutoDetect(once_cell) => once_cell.get_or_init(detect_targets).await,
+            AutoDetect(once_cell, cache) => once_cell.get_or_init(|| detect(cache.clone())).await,
         }
     }
 
@@ -64,6 +78,23 @@ pub fn get_desired_targets(opts_targets: Option<Vec<String>>) -> DesiredTargets
     if let Some(targets) = opts_targets {
         DesiredTargets::initialized(targets)
     } else {
-        DesiredTargets::auto_detect()
+        DesiredTargets::auto_detect(None)
+    }
+}
+
+/// Same as [`get_desired_targets`], but the targets detected are cached in
+/// the file at `cache_path`, see [`detect_targets_cached`].
+pub fn get_desired_targets_cached(
+    opts_targets: Option<Vec<String>>,
+    cache_path: PathBuf,
+    redetect: bool,
+) -> DesiredTargets {
+    if let Some(targets) = opts_targets {
+        DesiredTargets::initialized(targets)
+    } else {
+        DesiredTargets::auto_detect(Some(Cache {
+            path: cache_path,
+            redetect,
+        }))
     }
 }
@@ -12,12 +12,15 @@
 //!  - The binary is built for x86_64-apple-darwin, but run on
 //!    aarch64-apple-darwin.
 //!
-//! This crate provides two API:
+//! This crate provides the following API:
 //!  - [`detect_targets`] provides the API to get the target
 //!    at runtime, but the code is run on the current thread.
 //!  - [`get_desired_targets`] provides the API to either
 //!    use override provided by the users, or run [`detect_targets`]
 //!    in the background using [`tokio::spawn`].
+//!  - [`detect_targets_cached`] and [`get_desired_targets_cached`] are the
+//!    same, but cache the targets detected in a file until the OS, kernel
+//!    or libc changes.
 //!
 //! # Example
 //!
@@ -67,8 +70,11 @@
 mod detect;
 pub use detect::detect_targets;
 
+mod cache;
+pub use cache::detect_targets_cached;
+
 mod desired_targets;
-pub use desired_targets::{get_desired_targets, DesiredTargets};
+pub use desired_targets::{get_desired_targets, get_desired_targets_cached, DesiredTargets};
 
 /// Compiled target triple, used as default for binary fetching
 pub const TARGET: &str = env!("TARGET");
@@ -15,3 +15,10 @@ export PATH="$CARGO_HOME/bin:$PATH"
     cargo-binstall@0.20.1
 # Test that the installed binaries can be run
 cargo binstall --help >/dev/null
+
+# Only trust the bundled root certificates
+"./$1" binstall \
+    --force \
+    --tls-roots webpki \
+    --no-confirm \
+    cargo-binstall@0.20.1